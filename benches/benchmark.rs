@@ -21,6 +21,7 @@ lazy_static! {
         R1CSFile::from_reader(r1cs_reader)
             .unwrap()
             .to_crate_format()
+            .unwrap()
     };
 }
 fn load_and_prove() {
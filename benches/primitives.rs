@@ -0,0 +1,133 @@
+//! Benchmarks of the individual subsystems a proof is made of, each at several sizes,
+//! so a regression in one shows up on its own rather than only in the end-to-end benchmark
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use rand::thread_rng;
+use std::collections::BTreeMap;
+use volonym::{
+    subspacevole::{calc_consistency_check, LinearCode, RAAACode},
+    vecccom::expand_seed_to_field_vec,
+    zkp::{quicksilver::Prover, FullR1CS, R1CSWithMetadata, R1CS},
+    FMatrix, FVec, Fr, LayoutMatrix,
+};
+
+fn random_matrix(rows: usize, cols: usize) -> FMatrix<Fr> {
+    FMatrix(
+        (0..rows)
+            .map(|_| FVec::random_with_rng(cols, &mut thread_rng()))
+            .collect(),
+    )
+}
+
+fn seed_expansion(c: &mut Criterion) {
+    let mut group = c.benchmark_group("expand_seed_to_field_vec");
+    for len in [1 << 10, 1 << 14, 1 << 18] {
+        group.bench_with_input(BenchmarkId::from_parameter(len), &len, |b, &len| {
+            b.iter(|| expand_seed_to_field_vec::<Fr>(black_box([7u8; 32]), len))
+        });
+    }
+    group.finish();
+}
+
+fn encoding(c: &mut Criterion) {
+    let code = RAAACode::rand_default();
+    let mut group = c.benchmark_group("RAAACode::encode");
+    for rows in [1, 16, 256] {
+        let inputs = (0..rows)
+            .map(|_| FVec::<Fr>::random_with_rng(code.k(), &mut thread_rng()))
+            .collect::<Vec<_>>();
+        group.bench_with_input(BenchmarkId::from_parameter(rows), &inputs, |b, inputs| {
+            b.iter(|| {
+                inputs
+                    .iter()
+                    .map(|row| code.encode(black_box(row)))
+                    .collect::<Vec<_>>()
+            })
+        });
+    }
+    group.finish();
+}
+
+fn transpose(c: &mut Criterion) {
+    let mut group = c.benchmark_group("FMatrix::transpose");
+    for (rows, cols) in [(64, 1024), (256, 1024), (1024, 1024)] {
+        let matrix = random_matrix(rows, cols);
+        group.bench_with_input(
+            BenchmarkId::from_parameter(format!("{rows}x{cols}")),
+            &matrix,
+            |b, matrix| b.iter(|| black_box(matrix).transpose()),
+        );
+    }
+    group.finish();
+}
+
+fn consistency_check(c: &mut Criterion) {
+    let mut group = c.benchmark_group("calc_consistency_check");
+    for rows in [64, 256, 1024] {
+        let u = LayoutMatrix::from_rows(random_matrix(rows, 1024));
+        let v = LayoutMatrix::from_rows(random_matrix(rows, 1024));
+        let challenge_hash = FVec::<Fr>::random_with_rng(rows, &mut thread_rng());
+        group.bench_function(BenchmarkId::from_parameter(rows), |b| {
+            b.iter(|| calc_consistency_check::<Fr>(black_box(&challenge_hash.0), &u, &v))
+        });
+    }
+    group.finish();
+}
+
+/// Two constraints, (w0 + w1)·2w1 = w2 and 2w0·w2 = w3, satisfied by [5, 2, 28, 280]
+fn small_circuit() -> (R1CSWithMetadata<Fr>, FVec<Fr>) {
+    let rows = |rows: [[u64; 4]; 2]| {
+        FMatrix(
+            rows.iter()
+                .map(|r| FVec(r.iter().map(|x| Fr::from(*x)).collect()))
+                .collect(),
+        )
+    };
+    let circuit = R1CSWithMetadata {
+        r1cs: R1CS::Full(FullR1CS {
+            a_rows: rows([[1, 1, 0, 0], [2, 0, 0, 0]]),
+            b_rows: rows([[0, 2, 0, 0], [0, 0, 1, 0]]),
+            c_rows: rows([[0, 0, 1, 0], [0, 0, 0, 1]]),
+        }),
+        public_inputs_indices: vec![0, 2],
+        public_outputs_indices: vec![3],
+        unpadded_wtns_len: 4,
+        custom_gates: vec![],
+        lookups: vec![],
+        memories: vec![],
+        branches: vec![],
+        ccs: vec![],
+        signal_names: BTreeMap::new(),
+    };
+    let witness = FVec([5u64, 2, 28, 280].iter().map(|x| Fr::from(*x)).collect());
+    (circuit, witness)
+}
+
+fn quicksilver(c: &mut Criterion) {
+    let (circuit, witness) = small_circuit();
+    let mut group = c.benchmark_group("quicksilver::Prover::prove");
+    group.sample_size(10);
+    for copies in [1 << 8, 1 << 12, 1 << 16] {
+        let repeated = circuit.repeat(copies);
+        let u = FVec(
+            std::iter::repeat_n(witness.0.iter().copied(), copies)
+                .flatten()
+                .collect(),
+        );
+        let v = FVec::<Fr>::random_with_rng(u.0.len(), &mut thread_rng());
+        let prover = Prover::from_vole(u, v.0, &repeated);
+        group.bench_function(BenchmarkId::from_parameter(copies), |b| {
+            b.iter(|| prover.prove(black_box(&Fr::from(7u64))))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    seed_expansion,
+    encoding,
+    transpose,
+    consistency_check,
+    quicksilver
+);
+criterion_main!(benches);
@@ -0,0 +1,29 @@
+// Generates the gRPC service's client and server from its definition here rather than from proto/volonym.proto,
+// since its messages are written by hand in src/proto.rs and building doesn't need protoc
+fn main() {
+    #[cfg(feature = "grpc")]
+    {
+        use tonic_build::manual::{Builder, Method, Service};
+
+        let method = |name: &str, route_name: &str, input: &str, output: &str| {
+            Method::builder()
+                .name(name)
+                .route_name(route_name)
+                .input_type(format!("crate::proto::{input}"))
+                .output_type(format!("crate::proto::{output}"))
+                .codec_path("tonic::codec::ProstCodec")
+        };
+        let service = Service::builder()
+            .name("ProvingService")
+            .package("volonym")
+            .method(
+                method("prove", "Prove", "ProveChunk", "ProveResponse")
+                    .client_streaming()
+                    .build(),
+            )
+            .method(method("verify", "Verify", "VerifyRequest", "VerifyResponse").build())
+            .method(method("status", "Status", "StatusRequest", "StatusResponse").build())
+            .build();
+        Builder::new().compile(&[service]);
+    }
+}
@@ -0,0 +1,26 @@
+//! Every decoder of `format` must reject malformed input with an error rather than panicking or allocating without bound,
+//! and an uncompressed proof that decodes must encode back to the same bytes. Run with `cargo fuzz run decode`
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use volonym::{
+    actors::actors::{CommitAndProof, Proof},
+    format::{ProofBundle, ProofEnvelope, ProofParams},
+    Fr,
+};
+
+fuzz_target!(|bytes: &[u8]| {
+    let _ = ProofParams::from_bytes(bytes);
+    if let Ok(cnp) = CommitAndProof::<Fr>::from_bytes(bytes) {
+        let compressed = ProofEnvelope::from_bytes(bytes).is_ok_and(|e| e.is_compressed());
+        if !compressed {
+            assert_eq!(cnp.to_bytes().unwrap(), bytes);
+        }
+    }
+    if ProofBundle::<Fr>::from_bytes(bytes).is_ok() {
+        assert!(ProofBundle::<Fr>::proof_at(bytes, 0).is_ok());
+    }
+    if let Ok(json) = std::str::from_utf8(bytes) {
+        let _ = Proof::<Fr>::from_json(json);
+    }
+});
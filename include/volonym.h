@@ -0,0 +1,47 @@
+/* C interface to volonym, built with `cargo build --release --features ffi`.
+ *
+ * Every function returns a VOLONYM_* status code. Circuits and witnesses are circom's
+ * .r1cs and .wtns files, and proofs are in volonym's compact binary encoding.
+ * Buffers returned by the library must be freed with volonym_free_buffer.
+ */
+#ifndef VOLONYM_H
+#define VOLONYM_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+#define VOLONYM_OK 0
+/* A required pointer was null */
+#define VOLONYM_NULL_POINTER 1
+#define VOLONYM_INVALID_R1CS 2
+#define VOLONYM_INVALID_WITNESS 3
+/* The proof bytes are not a valid encoded proof */
+#define VOLONYM_INVALID_PROOF_ENCODING 4
+#define VOLONYM_PROVING_FAILED 5
+/* The proof is well-formed but does not verify */
+#define VOLONYM_PROOF_REJECTED 6
+/* The library panicked, e.g. on a malformed input the parsers don't yet reject gracefully */
+#define VOLONYM_PANIC 7
+
+/* Proves the witness satisfies the circuit. On success, *proof_out and *proof_len_out
+ * are set to a buffer holding the encoded proof */
+int32_t volonym_prove(const uint8_t *r1cs, size_t r1cs_len,
+                      const uint8_t *wtns, size_t wtns_len,
+                      uint8_t **proof_out, size_t *proof_len_out);
+
+/* Returns VOLONYM_OK if the proof verifies for the circuit and VOLONYM_PROOF_REJECTED if it doesn't */
+int32_t volonym_verify(const uint8_t *proof, size_t proof_len,
+                       const uint8_t *r1cs, size_t r1cs_len);
+
+/* Frees a buffer returned by this library. Does nothing if buf is null */
+void volonym_free_buffer(uint8_t *buf, size_t len);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* VOLONYM_H */
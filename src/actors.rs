@@ -1,24 +1,44 @@
 ///! Provides the prover and verifier structs
 pub mod actors {
-    // use std::time::Instant;
-    use anyhow::{anyhow, Error, Ok};
-    use std::mem;
+    use std::{
+        borrow::Cow,
+        collections::{BTreeMap, VecDeque},
+        mem,
+        sync::mpsc,
+        time::{Duration, Instant},
+    };
 
-    use crate::{DataSize,
-        challenges::{calc_other_challenges, calc_quicksilver_challenge, challenge_from_seed},
+    pub use crate::zkp::quicksilver::PublicOpenings;
+    use crate::{
+        challenges::{
+            append_disclosure, append_grinding_nonce, append_quicksilver_proof,
+            calc_lookup_challenges, calc_other_challenges, calc_quicksilver_challenges,
+            check_grinding, grind, linked_transcript, proof_transcript, sample_challenges,
+            statement_digest, transcript::Transcript, vole_consistency_challenge, Challenger,
+            Challenges, FiatShamir, ProtocolParams, SecurityLevel, MAX_GRINDING_BITS,
+        },
+        error::{ProofError, VerifyError},
+        fields::ext::ExtensionField,
+        format::{ProofBundle, ProofEnvelope},
         smallvole::{self},
-        subspacevole::{calc_consistency_check, LinearCode, RAAACode},
+        subspacevole::{
+            calc_consistency_check, consistency_challenges, CodeParams, CodePreset, EncodeScratch,
+            LinearCode, RAAACode,
+        },
         vecccom::{
             commit_seed_commitments, commit_seeds, proof_for_revealed_seed, reconstruct_commitment,
         },
+        vith::{self, VithProver, VithVerifier},
         zkp::{
             quicksilver::{self, ZKP},
-            R1CSWithMetadata,
+            AuxLayout, CompositeCircuit, PadParams, R1CSWithMetadata, SparseR1CS, R1CS,
         },
-        FMatrix, FVec, PF,
+        DataSize, FMatrix, FVec, LayoutMatrix, SparseFMatrix, SparseVec, PF,
     };
-    use rand::{rngs::ThreadRng, RngCore};
+    use rand::{rngs::OsRng, CryptoRng, RngCore, SeedableRng};
+    use rand_chacha::ChaCha20Rng;
     use serde::{Deserialize, Serialize};
+    use subtle::{Choice, ConstantTimeEq};
 
     pub struct Prover<T: PF> {
         pub code: RAAACode,
@@ -32,69 +52,734 @@ pub mod actors {
         pub subspace_vole_secrets: Option<SubspaceVOLESecrets<T>>,
         /// Starts as None, added when the prover makes the subsapce VOLE
         pub seed_commitment: Option<[u8; 32]>,
+        /// Where the small VOLE seeds come from. `OsRng` if None
+        rng: Option<Box<dyn ProverRng>>,
+        /// Number of threads the small VOLEs are expanded on
+        parallelism: usize,
+        /// `statement_digest` of the circuit and parameters, bound into the Fiat-Shamir challenges
+        statement: [u8; 32],
+        /// Told about each phase of proving as it progresses
+        progress: Option<Box<dyn ProgressSink>>,
+        code_params: CodeParams,
+        security: SecurityLevel,
+        /// Least bits of grinding to do, even if `security` needs fewer
+        grinding: usize,
+        /// What the application binds the proof to, absorbed into the challenges after the statement
+        context: Vec<u8>,
+        /// A·w and B·w, kept up to date by an `IncrementalProver`
+        witness_products: Option<(FVec<T>, FVec<T>)>,
+        /// `circuit.aux_layout` for the code, from the `ProvingKey`
+        aux_layout: Option<AuxLayout>,
+    }
+
+    /// Phases of proving, in the order they happen
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    pub enum Phase {
+        /// Expanding the small VOLEs from their seeds
+        VoleGeneration,
+        /// Correcting U to lie in the code's subspace
+        Correction,
+        /// Proving the subspace VOLE is consistent
+        ConsistencyCheck,
+        Quicksilver,
+        /// Revealing the VitH S matrix and opening the seeds
+        SMatrix,
+    }
+
+    /// Receives progress updates from a `Prover`, e.g. to show progress in a UI while a large proof is made.
+    /// `fraction` is how much of `phase` is done, from 0.0 to 1.0, and only increases within a phase.
+    /// Every phase is reported at least at its start and end
+    pub trait ProgressSink: Send + Sync {
+        fn on_phase(&self, phase: Phase, fraction: f64);
+    }
+    impl<F: Fn(Phase, f64) + Send + Sync> ProgressSink for F {
+        fn on_phase(&self, phase: Phase, fraction: f64) {
+            self(phase, fraction)
+        }
+    }
+    /// Ignores progress
+    impl ProgressSink for () {
+        fn on_phase(&self, _phase: Phase, _fraction: f64) {}
+    }
+
+    /// Bytes of memory a `Prover` holds in its largest structures, and, with the `mem-stats` feature, the process's peak usage
+    #[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+    pub struct MemoryReport {
+        pub witness: usize,
+        pub witness_comm: usize,
+        pub circuit: usize,
+        /// U and V of the subspace VOLE, once it is made
+        pub subspace_vole: usize,
+        /// A·w and B·w, if an `IncrementalProver` keeps them
+        pub witness_products: usize,
+        /// Most bytes allocated on the heap at once, if `memstats::TrackingAllocator` is the global allocator
+        pub peak_heap: Option<usize>,
+        /// Peak resident set size in bytes, if the OS reports it
+        pub peak_rss: Option<usize>,
+    }
+    impl MemoryReport {
+        /// Sum of the prover's structures
+        pub fn footprint(&self) -> usize {
+            self.witness
+                + self.witness_comm
+                + self.circuit
+                + self.subspace_vole
+                + self.witness_products
+        }
+    }
+
+    /// How many progress updates VOLE generation makes
+    const VOLE_GENERATION_STEPS: usize = 16;
+    /// How many rows of the subspace VOLE `mkvole` expands at a time while correcting the previous ones
+    const PIPELINE_CHUNK_ROWS: usize = 64;
+
+    /// A cryptographically secure RNG a `Prover` can own
+    pub trait ProverRng: RngCore + CryptoRng + Send {}
+    impl<R: RngCore + CryptoRng + Send> ProverRng for R {}
+
+    /// A witness as given to a `Prover`
+    pub enum Witness<T: PF> {
+        Dense(FVec<T>),
+        /// Only the nonzero values, as (index, value) pairs. For witnesses that are mostly zeros, this saves materializing
+        /// the zeros more than once: they are written straight into the padded witness.
+        /// Every row is still committed to, since which values are zero is as secret as the rest of the witness
+        Sparse(SparseVec<T>),
+    }
+
+    impl<T: PF> From<FVec<T>> for Witness<T> {
+        fn from(witness: FVec<T>) -> Self {
+            Self::Dense(witness)
+        }
+    }
+
+    impl<T: PF> From<SparseVec<T>> for Witness<T> {
+        fn from(witness: SparseVec<T>) -> Self {
+            Self::Sparse(witness)
+        }
+    }
+
+    impl<T: PF> Witness<T> {
+        /// Checks the witness fits a circuit with witness length `len`
+        fn check_len(&self, len: usize) -> Result<(), ProofError> {
+            match self {
+                Self::Dense(w) if w.0.len() != len => Err(ProofError::WitnessLength {
+                    expected: len,
+                    found: w.0.len(),
+                }),
+                Self::Sparse(w) => match w.0.iter().find(|(i, _)| *i >= len) {
+                    Some((index, _)) => Err(ProofError::SparseWitnessIndex {
+                        index: *index,
+                        witness_len: len,
+                    }),
+                    None => Ok(()),
+                },
+                _ => Ok(()),
+            }
+        }
+
+        /// The witness zero padded to `padded_len`
+        fn into_padded(self, padded_len: usize) -> FVec<T> {
+            match self {
+                Self::Dense(mut w) => {
+                    w.zero_pad(padded_len - w.0.len());
+                    w
+                }
+                Self::Sparse(w) => w.to_fvec(padded_len),
+            }
+        }
+    }
+
+    /// Configures a `Prover`, checking the configuration is valid when it is built.
+    /// `Prover::from_witness_and_circuit_unpadded` is equivalent to building with the defaults
+    pub struct ProverBuilder<T: PF> {
+        witness: Witness<T>,
+        circuit: BuilderCircuit<T>,
+        code: CodeParams,
+        security_level: usize,
+        grinding_bits: usize,
+        context: Vec<u8>,
+        rng: Option<Box<dyn ProverRng>>,
+        parallelism: usize,
+        progress: Option<Box<dyn ProgressSink>>,
+        /// Witnesses concatenated in `witness`, each for a copy of the circuit. See `many`
+        instances: usize,
+    }
+
+    /// The circuit a `ProverBuilder` proves, as given or already prepared in a `ProvingKey`
+    enum BuilderCircuit<T: PF> {
+        Unprepared(Box<R1CSWithMetadata<T>>),
+        Key(Box<ProvingKey<T>>),
+    }
+
+    impl<T: PF> ProverBuilder<T> {
+        pub fn new(witness: impl Into<Witness<T>>, circuit: R1CSWithMetadata<T>) -> Self {
+            Self::with_circuit(
+                witness.into(),
+                BuilderCircuit::Unprepared(Box::new(circuit)),
+                CodeParams::default(),
+            )
+        }
+
+        /// Proves with the circuit and code prepared in `key`, which can be reused for any number of provers.
+        /// Setting a different code with `code` or `code_params` fails when the prover is built
+        pub fn from_proving_key(witness: impl Into<Witness<T>>, key: &ProvingKey<T>) -> Self {
+            Self::with_circuit(
+                witness.into(),
+                BuilderCircuit::Key(Box::new(key.clone())),
+                key.code_params,
+            )
+        }
+
+        fn with_circuit(witness: Witness<T>, circuit: BuilderCircuit<T>, code: CodeParams) -> Self {
+            Self {
+                witness,
+                circuit,
+                code,
+                security_level: 128,
+                grinding_bits: 0,
+                context: Vec::new(),
+                rng: None,
+                parallelism: 1,
+                progress: None,
+                instances: 1,
+            }
+        }
+
+        /// Proves each of `witnesses` satisfies `circuit` in one proof, see `Prover::prove_many`. Prove with `prove_many`
+        pub fn many(
+            witnesses: Vec<FVec<T>>,
+            circuit: R1CSWithMetadata<T>,
+        ) -> Result<Self, ProofError> {
+            let instances = witnesses.len();
+            if instances == 0 {
+                return Err(ProofError::NoWitnesses);
+            }
+            if !circuit.branches.is_empty() {
+                return Err(ProofError::RepeatedBranches);
+            }
+            if let Some(w) = witnesses
+                .iter()
+                .find(|w| w.0.len() != circuit.unpadded_wtns_len)
+            {
+                return Err(ProofError::WitnessLength {
+                    expected: circuit.unpadded_wtns_len,
+                    found: w.0.len(),
+                });
+            }
+            let witness = FVec(witnesses.into_iter().flat_map(|w| w.0).collect());
+            let mut builder = Self::new(witness, circuit.repeat(instances));
+            builder.instances = instances;
+            Ok(builder)
+        }
+
+        /// The linear code for the subspace VOLE. Defaults to `CodePreset::RAAADefault`
+        pub fn code(mut self, code: CodePreset) -> Self {
+            self.code.preset = code;
+            self
+        }
+
+        /// The code's interleaves, e.g. from a parameter file both parties have. The verifier must use the same ones,
+        /// see `VerifyingKey::with_code_params`. Defaults to `CodeParams::default()`
+        pub fn code_params(mut self, params: CodeParams) -> Self {
+            self.code = params;
+            self
+        }
+
+        /// Bits of soundness the proof must have, which is recorded in the proof. Defaults to 128, what the default code provides.
+        /// Higher levels, up to `SecurityLevel::max_for`, are reached by grinding, which makes proving slower, and `build` fails for levels above it
+        pub fn security_level(mut self, bits: usize) -> Self {
+            self.security_level = bits;
+            self
+        }
+
+        /// Grinds for at least `bits` bits, up to `MAX_GRINDING_BITS`, even if the security level needs fewer.
+        /// Each bit doubles the work of proving and of any attempt to forge the proof, and the bits ground for are recorded in the proof
+        pub fn grinding_bits(mut self, bits: usize) -> Self {
+            self.grinding_bits = bits;
+            self
+        }
+
+        /// Binds the proof to `context`, e.g. a session ID, a nonce the verifier chose, or the recipient, by absorbing it into every
+        /// challenge after the VOLE's consistency check. The proof then only verifies with `Verifier::verify_with_context` and the same context.
+        /// Empty by default, which is what `Verifier::verify` expects
+        pub fn context(mut self, context: &[u8]) -> Self {
+            self.context = context.to_vec();
+            self
+        }
+
+        /// RNG the small VOLE seeds are drawn from, e.g. a DRBG an environment requires. Defaults to `OsRng`, the operating system's RNG
+        pub fn rng(mut self, rng: impl ProverRng + 'static) -> Self {
+            self.rng = Some(Box::new(rng));
+            self
+        }
+
+        /// Derives every small VOLE seed from `seed`, so the same seed, witness, and circuit always give the same proof.
+        /// For test vectors and replaying audits: the seed must be kept as secret as the witness,
+        /// and reusing it for a different witness leaks the difference between the two witnesses
+        pub fn seed(self, seed: [u8; 32]) -> Self {
+            self.rng(ChaCha20Rng::from_seed(seed))
+        }
+
+        /// Number of threads to expand the small VOLEs on. Defaults to 1
+        pub fn parallelism(mut self, threads: usize) -> Self {
+            self.parallelism = threads;
+            self
+        }
+
+        /// Where to report the progress of proving. Not reported by default
+        pub fn progress(mut self, sink: impl ProgressSink + 'static) -> Self {
+            self.progress = Some(Box::new(sink));
+            self
+        }
+
+        pub fn build(self) -> Result<Prover<T>, ProofError> {
+            if self.parallelism == 0 {
+                return Err(ProofError::InvalidParallelism);
+            }
+            let available = SecurityLevel::max_for::<T>(self.code.preset);
+            if self.security_level > available.0 {
+                return Err(ProofError::InsufficientSecurity {
+                    requested: self.security_level,
+                    available: available.0,
+                });
+            }
+            if self.grinding_bits > MAX_GRINDING_BITS {
+                return Err(ProofError::TooMuchGrinding {
+                    requested: self.grinding_bits,
+                    max: MAX_GRINDING_BITS,
+                });
+            }
+            let key = match self.circuit {
+                BuilderCircuit::Unprepared(circuit) => {
+                    self.witness.check_len(circuit.unpadded_wtns_len)?;
+                    ProvingKey::with_code_params(*circuit, self.code)
+                }
+                BuilderCircuit::Key(key) if key.code_params == self.code => {
+                    self.witness.check_len(key.circuit.unpadded_wtns_len)?;
+                    *key
+                }
+                BuilderCircuit::Key(_) => return Err(ProofError::ProvingKeyCode),
+            };
+            let mut prover = Prover::from_key(self.witness, key);
+            prover.rng = self.rng;
+            prover.parallelism = self.parallelism;
+            prover.progress = self.progress;
+            prover.security = SecurityLevel(self.security_level);
+            prover.grinding = self.grinding_bits;
+            prover.context = self.context;
+            Ok(prover)
+        }
+
+        /// Builds the prover for the witnesses given to `many` and proves them all at once
+        pub fn prove_many(self) -> Result<MultiCommitAndProof<T>, ProofError> {
+            let instances = self.instances;
+            Ok(MultiCommitAndProof {
+                instances,
+                cnp: self.build()?.commit_and_prove()?,
+            })
+        }
     }
+
     pub struct Verifier<T: PF> {
+        pub key: VerifyingKey<T>,
+        /// Starts as None, set during Fiat Shamir
+        pub subspace_vole_deltas: Option<FVec<T>>,
+        /// Starts as None, set during Fiat Shamir
+        pub vith_delta: Option<T::Ext>,
+    }
+
+    /// Everything about a circuit the verifier needs, computed once so it can be reused to verify any number of proofs for that circuit
+    pub struct VerifyingKey<T: PF> {
+        /// The circuit with its constraint matrices in sparse form. Sparse matrices need no padding to the VOLE's dimensions.
+        /// The verifier only multiplies the matrices by vectors, Aq, Bq, and Cq, which sparse rows already do without touching a zero entry,
+        /// so there is nothing for a transposed copy of the matrices to speed up
         pub circuit: R1CSWithMetadata<T>,
+        /// `circuit_id` of the circuit the key was made from, i.e. before its conversion to sparse form
+        pub circuit_digest: [u8; 32],
+        /// `statement_digest` of the circuit and parameters, bound into the Fiat-Shamir challenges
+        pub statement_digest: [u8; 32],
+        pub code_params: CodeParams,
         pub code: RAAACode,
         pub num_voles: usize,
         pub vole_length: usize,
-        /// Starts as None, set during Fiat Shamir
-        pub subspace_vole_deltas: Option<FVec<T>>,
-        /// Starts as None, set during Fiat Shamir
-        pub vith_delta: Option<T>,
+        /// Proofs claiming a lower security level are rejected. Defaults to what the code provides without grinding
+        pub min_security: SecurityLevel,
+        /// `circuit.aux_layout` for the code
+        pub aux_layout: Option<AuxLayout>,
+    }
+
+    /// Everything about a circuit the prover needs besides the witness, computed once so it can be reused to prove any number of witnesses
+    /// for that circuit. See `Prover::from_proving_key`
+    #[derive(Clone)]
+    pub struct ProvingKey<T: PF> {
+        /// The circuit with its constraints zero padded to the padded witness's length
+        pub circuit: R1CSWithMetadata<T>,
+        /// `circuit_id` of the circuit the key was made from, i.e. before its padding
+        pub circuit_digest: [u8; 32],
+        /// `statement_digest` of the circuit and parameters, bound into the Fiat-Shamir challenges
+        pub statement_digest: [u8; 32],
+        pub code_params: CodeParams,
+        pub code: RAAACode,
+        pub padding: PadParams,
+        /// `circuit.aux_layout` for the code
+        pub aux_layout: Option<AuxLayout>,
+        pub num_voles: usize,
+        pub vole_length: usize,
     }
 
     /// Anything that the prover has learned by the time of the subspace VOLE's completion that it must keep hidden:
     pub struct SubspaceVOLESecrets<T: PF> {
         seeds: Vec<[[u8; 32]; 2]>,
-        // u: FMatrix,
-        // v: FMatrix,
-        /// First half of u_1s rows
-        u1: FMatrix<T>,
-        /// Second half of u_1s rows
-        u2: FMatrix<T>,
-        /// First half of v1_s rows
-        v1: FMatrix<T>,
-        /// Second half of v1_s rows
-        v2: FMatrix<T>,
+        /// The subspace VOLE's U and V, which VitH turns into the VOLE Quicksilver runs on
+        vith: VithProver<T>,
     }
 
-    #[derive(Clone, Debug, Serialize, Deserialize)]
+    #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
     pub struct ProverCommitment<T: PF> {
+        /// Parameters the subspace VOLE was made with, checked against the verifier's before anything else
+        pub params: ProtocolParams,
         /// Hash of every pair of seed's respective hashes for the seeds used to create the VOLEs. We are just using two seeds per VOLE!
         /// Can/should be used for Fiat-Shamir of subspace VOLE consistency check
         pub seed_comm: [u8; 32],
         /// l x k Witness split into vectors of the same length as the code's dimension k and committed by subtracting them from the first l rows of u1
         pub witness_comm: FMatrix<T>,
         pub subspace_vole_correction: FMatrix<T>,
-        /// subsapce VOLE consistency check of U and V's check values, respectively
+        /// subsapce VOLE consistency check of U and V's check values, respectively, as `calc_consistency_check` returns them
         pub consistency_check: (FVec<T>, FVec<T>),
     }
 
     #[derive(Clone, Debug, Serialize, Deserialize)]
+    #[serde(bound(
+        serialize = "T: Serialize, T::Ext: Serialize",
+        deserialize = "T: Deserialize<'de>, T::Ext: Deserialize<'de>"
+    ))]
     pub struct Proof<T: PF> {
-        pub zkp: ZKP<T>,
+        pub zkp: ZKP<T::Ext>,
         // pub prover_commitment: ProverCommitment,
         /// Opening of the seeds the verifier needs for subspace VOLE
         pub seed_openings: SubspaceVOLEOpening,
         /// Public input and output (u, v) tuples
         pub public_openings: PublicOpenings<T>,
-        /// The VitH S matrix
+        /// The VitH S matrix, a block per coefficient of ∆'
         pub s_matrix: FMatrix<T>,
-        /// Proof S was constructed correctly
+        /// Proof S was constructed correctly, as `VithProver::open` flattens it
         pub s_consistency_check: FVec<T>,
+        /// Security level the proof claims, which its grinding nonce must meet
+        pub security: SecurityLevel,
+        /// Leading zero bits the grinding nonce's hash has, at least what `security` needs
+        pub grinding_bits: usize,
+        pub grinding_nonce: u64,
     }
 
-    #[derive(Debug, Serialize, Deserialize)]
+    #[derive(Clone, Debug, Serialize, Deserialize)]
+    #[serde(bound(
+        serialize = "T: Serialize, T::Ext: Serialize",
+        deserialize = "T: Deserialize<'de>, T::Ext: Deserialize<'de>"
+    ))]
     pub struct CommitAndProof<T: PF> {
         pub commitment: ProverCommitment<T>,
         pub proof: Proof<T>,
     }
 
+    /// A check `Verifier::verify_report` runs
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    pub enum Check {
+        /// The proof's vectors and matrices have the sizes the verifier expects. If not, no other check is run
+        Dimensions,
+        /// The proof claims at least the verifier's minimum security level, and its grinding nonce meets the level it claims
+        /// and the bits of grinding it records
+        Security,
+        /// The opened seeds are the ones committed to. Includes reconstructing the subspace VOLE from them
+        SeedCommitment,
+        /// The subspace VOLE's outputs are in the subspace
+        ConsistencyCheck,
+        /// The S matrix is constructed properly
+        SMatrix,
+        Quicksilver,
+        PublicOpenings,
+    }
+
+    #[derive(Debug)]
+    pub struct CheckOutcome {
+        pub check: Check,
+        pub result: Result<(), VerifyError>,
+        pub elapsed: Duration,
+    }
+
+    /// Outcome and timing of each check of a proof, for debugging proofs that fail to verify.
+    /// Unlike `Verifier::verify`, a failed check doesn't stop the checks after it from running, unless they can't be run without it
+    #[derive(Debug)]
+    pub struct VerificationReport<T: PF> {
+        /// Checks in the order they were run
+        pub checks: Vec<CheckOutcome>,
+        /// The proof's public values, if it verified
+        pub public_values: Option<PublicUOpenings<T>>,
+    }
+
+    /// Witness values opened on top of the circuit's public inputs and outputs, e.g. extra fields of a credential an application asks for.
+    /// The indices must be chosen before the proof is made. A finished proof has revealed ∆', with which any opening can be forged,
+    /// so more indices can't be disclosed afterwards: revealing more fields takes a new proof with a new VOLE
+    #[derive(Clone, Debug, Serialize, Deserialize)]
+    #[serde(bound(
+        serialize = "T: Serialize, T::Ext: Serialize",
+        deserialize = "T: Deserialize<'de>, T::Ext: Deserialize<'de>"
+    ))]
+    pub struct Disclosure<T: PF> {
+        /// Indices into the unpadded witness
+        pub indices: Vec<usize>,
+        /// (u, v) tuple for each index
+        pub openings: Vec<(T, T::Ext)>,
+    }
+
+    #[derive(Clone, Debug, Serialize, Deserialize)]
+    #[serde(bound(
+        serialize = "T: Serialize, T::Ext: Serialize",
+        deserialize = "T: Deserialize<'de>, T::Ext: Deserialize<'de>"
+    ))]
+    pub struct DisclosingProof<T: PF> {
+        pub cnp: CommitAndProof<T>,
+        pub disclosure: Disclosure<T>,
+    }
+
+    /// A witness committed to before the circuits it will be proven against are chosen, e.g. a credential.
+    /// Its public half is the `ProverCommitment` returned by `commit`, which verifiers can hold on to.
+    ///
+    /// It can be proven to satisfy any number of circuits over the same witness, but only all at once:
+    /// the VOLE behind the commitment can be opened only once since opening it twice reveals the witness.
+    /// `prove` consumes it to enforce this
+    pub struct CommittedWitness<T: PF> {
+        /// Prover for a circuit with no constraints, which is only used to commit
+        prover: Prover<T>,
+    }
+
+    /// Proof that the witness behind a `ProverCommitment` satisfies several circuits
+    #[derive(Clone, Debug, Serialize, Deserialize)]
+    #[serde(bound(
+        serialize = "T: Serialize, T::Ext: Serialize",
+        deserialize = "T: Deserialize<'de>, T::Ext: Deserialize<'de>"
+    ))]
+    pub struct LinkedProof<T: PF> {
+        /// Quicksilver proof and public openings for each circuit, in order
+        pub statements: Vec<(ZKP<T::Ext>, PublicOpenings<T>)>,
+        pub seed_openings: SubspaceVOLEOpening,
+        pub s_matrix: FMatrix<T>,
+        pub s_consistency_check: FVec<T>,
+        /// Context the proof is bound to, see `CommittedWitness::context`
+        pub context: Vec<u8>,
+        /// As in `Proof`
+        pub security: SecurityLevel,
+        pub grinding_bits: usize,
+        pub grinding_nonce: u64,
+    }
+
+    /// A designated verifier's reply to the prover's commitment, sent before the prover learns any other challenge
+    #[derive(Clone, Debug, Serialize, Deserialize)]
+    #[serde(bound(
+        serialize = "T: Serialize, T::Ext: Serialize",
+        deserialize = "T: Deserialize<'de>, T::Ext: Deserialize<'de>"
+    ))]
+    pub struct DesignatedChallenge<T: PF> {
+        /// Challenge for the subspace VOLE consistency check, in place of the one derived from the seed commitment
+        pub vole_consistency: Vec<T::Ext>,
+        /// One challenge per Quicksilver repetition
+        pub quicksilver: Vec<T::Ext>,
+    }
+
+    /// The prover's answer to a `DesignatedChallenge`
+    #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+    #[serde(bound(
+        serialize = "T: Serialize, T::Ext: Serialize",
+        deserialize = "T: Deserialize<'de>, T::Ext: Deserialize<'de>"
+    ))]
+    pub struct DesignatedAnswer<T: PF> {
+        pub consistency_check: (FVec<T>, FVec<T>),
+        pub zkp: ZKP<T::Ext>,
+        pub public_openings: PublicOpenings<T>,
+    }
+
+    /// Transcript of a proof made interactively for a designated verifier, whose challenges came from that verifier instead of Fiat-Shamir.
+    /// It only convinces that verifier: anyone who knows the challenges in advance can make one without a witness.
+    /// It is a separate type from `CommitAndProof` so it can't be passed to `Verifier::verify` or shown to anyone else as a proof
+    #[derive(Clone, Debug, Serialize, Deserialize)]
+    #[serde(bound(
+        serialize = "T: Serialize, T::Ext: Serialize",
+        deserialize = "T: Deserialize<'de>, T::Ext: Deserialize<'de>"
+    ))]
+    pub struct DesignatedProof<T: PF> {
+        /// The commitment, with the consistency check answering the verifier's challenge
+        pub commitment: ProverCommitment<T>,
+        pub proof: Proof<T>,
+    }
+
+    /// Verifier for the designated verifier mode. It samples its challenges when it is made and hands them out one message at a time:
+    /// `receive_commitment` answers the prover's commitment, then `receive_answer` answers the prover's `DesignatedAnswer`.
+    /// It keeps both messages, so `verify` rejects a transcript that differs from what the prover sent before learning ∆'
+    pub struct DesignatedVerifier<T: PF> {
+        pub key: VerifyingKey<T>,
+        quicksilver_challenges: Vec<T::Ext>,
+        challenges: Challenges<T>,
+        commitment: Option<ProverCommitment<T>>,
+        answer: Option<DesignatedAnswer<T>>,
+    }
+
+    impl<T: PF> CommittedWitness<T> {
+        pub fn commit(witness: FVec<T>) -> Result<(Self, ProverCommitment<T>), ProofError> {
+            let empty = SparseR1CS {
+                a_rows: SparseFMatrix(vec![]),
+                b_rows: SparseFMatrix(vec![]),
+                c_rows: SparseFMatrix(vec![]),
+            };
+            let circuit = R1CSWithMetadata {
+                r1cs: R1CS::Sparse(empty),
+                public_inputs_indices: vec![],
+                public_outputs_indices: vec![],
+                unpadded_wtns_len: witness.0.len(),
+                custom_gates: vec![],
+                lookups: vec![],
+                memories: vec![],
+                branches: vec![],
+                ccs: vec![],
+                signal_names: BTreeMap::new(),
+            };
+            let mut prover = Prover::from_witness_and_circuit_unpadded(witness, circuit);
+            let commitment = prover.mkvole()?;
+            Ok((Self { prover }, commitment))
+        }
+
+        /// Bits of soundness the linked proof must have, as in `ProverBuilder::security_level`. Defaults to 128.
+        /// Fails for levels above `SecurityLevel::max_for`
+        pub fn security_level(mut self, bits: usize) -> Result<Self, ProofError> {
+            let available = SecurityLevel::max_for::<T>(self.prover.code_params.preset);
+            if bits > available.0 {
+                return Err(ProofError::InsufficientSecurity {
+                    requested: bits,
+                    available: available.0,
+                });
+            }
+            self.prover.security = SecurityLevel(bits);
+            Ok(self)
+        }
+
+        /// Binds the linked proof to `context`, as `ProverBuilder::context` does a proof.
+        /// It then only verifies with `Verifier::verify_linked_with_context` and the same context
+        pub fn context(mut self, context: &[u8]) -> Self {
+            self.prover.context = context.to_vec();
+            self
+        }
+
+        /// Proves the committed witness satisfies each of `circuits`, which must all have the witness's length.
+        /// Verify it with `Verifier::verify_linked`
+        pub fn prove(
+            self,
+            circuits: Vec<R1CSWithMetadata<T>>,
+        ) -> Result<LinkedProof<T>, ProofError> {
+            let p = &self.prover;
+            if circuits.is_empty() {
+                return Err(ProofError::NoCircuits);
+            }
+            let (svs, seed_comm, witness_comm) = match (
+                &p.subspace_vole_secrets,
+                &p.seed_commitment,
+                &p.witness_comm,
+            ) {
+                (Some(svs), Some(seed_comm), Some(witness_comm)) => (svs, seed_comm, witness_comm),
+                _ => return Err(ProofError::VoleNotCompleted),
+            };
+            let len = p.circuit.unpadded_wtns_len;
+            let pad_len = p.circuit.calc_padding_needed(p.code.k()).pad_len;
+
+            let mut statements = Vec::with_capacity(circuits.len());
+            let mut zkps = Vec::with_capacity(circuits.len());
+            for mut circuit in circuits {
+                if !circuit.lookups.is_empty() || !circuit.memories.is_empty() {
+                    return Err(ProofError::UnlinkableCircuit);
+                }
+                // The committed witness is fixed, so it's each circuit's length that is checked against it
+                if circuit.unpadded_wtns_len != len {
+                    return Err(ProofError::WitnessLength {
+                        expected: len,
+                        found: circuit.unpadded_wtns_len,
+                    });
+                }
+                let statement = statement_digest(
+                    &circuit.circuit_id(),
+                    &p.code_params,
+                    p.num_voles,
+                    p.vole_length,
+                );
+                circuit.zero_pad_constraints(pad_len);
+                // Linked circuits have no lookups or memories to lay out
+                let (transcript, _) =
+                    committed_transcript(None, &statement, &p.context, seed_comm, witness_comm);
+                let (u, v) = svs.vith.vole(&p.witness);
+                let prover = quicksilver::Prover::from_vole(u, v, &circuit);
+                let repetitions =
+                    p.security
+                        .quicksilver_repetitions::<T>(quicksilver::num_batched(
+                            prover.r1cs_with_metadata,
+                            prover.u.0.len(),
+                        ));
+                let challenges = calc_quicksilver_challenges::<T>(&transcript, repetitions);
+                let public_openings = PublicOpenings {
+                    public_inputs: prover
+                        .open_public(&prover.r1cs_with_metadata.public_inputs_indices),
+                    public_outputs: prover
+                        .open_public(&prover.r1cs_with_metadata.public_outputs_indices),
+                };
+                zkps.push((prover.prove_repeated(&challenges), public_openings));
+                statements.push(statement);
+            }
+
+            let mut transcript =
+                linked_transcript(&statements, &p.context, p.security, seed_comm, witness_comm);
+            for (zkp, public_openings) in &zkps {
+                append_quicksilver_proof(&mut transcript, zkp, public_openings);
+            }
+            let grinding_nonce = grind(&transcript, p.grinding_bits());
+            append_grinding_nonce(&mut transcript, grinding_nonce);
+            let challenges = calc_other_challenges::<T>(&transcript, p.vole_length, p.num_voles);
+            let (s_matrix, s_consistency_check) =
+                p.s_matrix_with_consistency_proof(&challenges.vith_delta, &challenges.s_challenge)?;
+            Ok(LinkedProof {
+                statements: zkps,
+                seed_openings: p.open_seeds(&challenges.delta_choices)?,
+                s_matrix,
+                s_consistency_check,
+                context: p.context.clone(),
+                security: p.security,
+                grinding_bits: p.grinding_bits(),
+                grinding_nonce,
+            })
+        }
+    }
+
+    /// Proofs for several witnesses of the same circuit, made as one proof for the circuit repeated once per witness
+    /// so the instances share one subspace VOLE and one set of seed openings
+    #[derive(Clone, Debug, Serialize, Deserialize)]
+    #[serde(bound(
+        serialize = "T: Serialize, T::Ext: Serialize",
+        deserialize = "T: Deserialize<'de>, T::Ext: Deserialize<'de>"
+    ))]
+    pub struct MultiCommitAndProof<T: PF> {
+        pub instances: usize,
+        pub cnp: CommitAndProof<T>,
+    }
+
+    impl<T: PF> DataSize for MultiCommitAndProof<T> {
+        fn size_in_bytes(&self) -> usize {
+            mem::size_of_val(&self.instances) + self.cnp.size_in_bytes()
+        }
+    }
+
     impl<T: PF> DataSize for CommitAndProof<T> {
         fn size_in_bytes(&self) -> usize {
             self.commitment.size_in_bytes() + self.proof.size_in_bytes()
         }
+        #[cfg(feature = "compression")]
+        fn compressed_size_in_bytes(&self) -> usize {
+            self.to_compressed_bytes()
+                .map_or_else(|_| self.size_in_bytes(), |bytes| bytes.len())
+        }
     }
 
     impl<T: PF> DataSize for ProverCommitment<T> {
@@ -114,6 +799,7 @@ pub mod actors {
                 + self.public_openings.size_in_bytes()
                 + self.s_matrix.size_in_bytes()
                 + self.s_consistency_check.size_in_bytes()
+                + mem::size_of_val(&self.grinding_nonce)
         }
     }
 
@@ -123,25 +809,38 @@ pub mod actors {
         }
     }
 
-    
-
-    impl<T: PF> DataSize for ZKP<T> {
+    impl<E> DataSize for ZKP<E> {
         fn size_in_bytes(&self) -> usize {
-            mem::size_of_val(&self.mul_proof.0) + mem::size_of_val(&self.mul_proof.1)
+            mem::size_of_val(&self.mul_proof.0)
+                + mem::size_of_val(&self.mul_proof.1)
+                + mem::size_of_val(self.gate_proof.as_slice())
+                + mem::size_of_val(self.branch_proof.as_slice())
+                + self
+                    .repetitions
+                    .iter()
+                    .map(|r| r.size_in_bytes())
+                    .sum::<usize>()
         }
     }
 
     impl DataSize for SubspaceVOLEOpening {
         fn size_in_bytes(&self) -> usize {
-            self.seed_opens.iter().map(|s| s.size_in_bytes()).sum::<usize>()
-                + self.seed_proofs.iter().map(|s| s.size_in_bytes()).sum::<usize>()
+            self.seed_opens
+                .iter()
+                .map(|s| s.size_in_bytes())
+                .sum::<usize>()
+                + self
+                    .seed_proofs
+                    .iter()
+                    .map(|s| s.size_in_bytes())
+                    .sum::<usize>()
         }
     }
 
     impl<T: PF> DataSize for PublicOpenings<T> {
         fn size_in_bytes(&self) -> usize {
-            self.public_inputs.len() * (mem::size_of::<T>() * 2)
-                + self.public_outputs.len() * (mem::size_of::<T>() * 2)
+            let pair = mem::size_of::<T>() + mem::size_of::<T::Ext>();
+            (self.public_inputs.len() + self.public_outputs.len()) * pair
         }
     }
 
@@ -157,65 +856,129 @@ pub mod actors {
         // pub final_gate: (Fr, Fr)
     }
 
-    impl<T: PF> Prover<T> {
-        /// Pads a witness and circuit to dimensions compatible with VitH and the linear code, then creates a prover
-        /// Witness of length w is padded to length l where l is a multiple of a linear code's input length. creates a VOLE of length 2l+2
-        /// Mutates and destroys its inputs by padding them and taking ownership of them
-        pub fn from_witness_and_circuit_unpadded(
-            mut witness: FVec<T>,
-            mut circuit: R1CSWithMetadata<T>,
-        ) -> Self {
-            let code = RAAACode::rand_default();
-            let k = code.k();
-            let pp = circuit.calc_padding_needed(k);
+    /// Everything the prover computes for the subspace VOLE before it sees the witness.
+    /// None of this depends on the witness, so it can be generated ahead of time (see `VolePool`)
+    pub struct PreparedVOLE<T: PF> {
+        pub(crate) num_voles: usize,
+        pub(crate) vole_length: usize,
+        pub(crate) seeds: Vec<[[u8; 32]; 2]>,
+        pub(crate) seed_comm: [u8; 32],
+        /// U after it has been corrected to lie in the code's subspace
+        pub(crate) u_rows: FMatrix<T>,
+        pub(crate) v_rows: FMatrix<T>,
+        pub(crate) correction: FMatrix<T>,
+        pub(crate) consistency_check: (FVec<T>, FVec<T>),
+    }
 
-            witness.zero_pad(pp.pad_len);
-            circuit.r1cs.zero_pad(pp.pad_len);
-            let mut witness_rows = Vec::with_capacity(pp.num_padded_wtns_rows);
+    impl<T: PF> PreparedVOLE<T> {
+        /// Creates `num_voles` small VOLEs of length `vole_length`, and converts them into a subspace VOLE for `code`
+        pub fn generate(
+            code: &RAAACode,
+            vole_length: usize,
+            num_voles: usize,
+        ) -> Result<Self, ProofError> {
+            Self::generate_with_rng(code, vole_length, num_voles, &mut OsRng)
+        }
 
-            let mut start_idx = 0;
-            for _i in 0..pp.num_padded_wtns_rows {
-                witness_rows.push(FVec::<T>(
-                    witness
-                        .0
-                        .get(start_idx..start_idx + k)
-                        .expect("This panic should not be reached")
-                        .to_vec(),
-                ));
-                start_idx += k;
-            }
+        /// Like `generate` with the seeds drawn from `rng` instead of `OsRng`
+        pub fn generate_with_rng<R: RngCore + CryptoRng + ?Sized>(
+            code: &RAAACode,
+            vole_length: usize,
+            num_voles: usize,
+            rng: &mut R,
+        ) -> Result<Self, ProofError> {
+            Self::from_seeds(code, vole_length, random_seeds(rng, num_voles))
+        }
 
-            Self {
-                num_voles: code.n(),
-                // One extra row for the hiding of the linear combination of the relevant values in the consistency check
-                // 2x extra rows to convert subsapce VOLE into VitH. Overall, we require 2 * `num_padded_witness_rows` + 2 rows
-                vole_length: 2 * (pp.num_padded_wtns_rows + 1),
-                code,
-                circuit,
-                witness: FMatrix(witness_rows),
-                seed_commitment: None,
-                subspace_vole_secrets: None,
-                witness_comm: None,
-            }
+        /// Like `generate` but expanding the small VOLEs a chunk at a time, as `from_seeds_streaming` does
+        pub fn generate_streaming(
+            code: &RAAACode,
+            vole_length: usize,
+            num_voles: usize,
+            chunk_rows: usize,
+        ) -> Result<Self, ProofError> {
+            Self::generate_streaming_with_rng(code, vole_length, num_voles, chunk_rows, &mut OsRng)
         }
 
-        /// Called first
-        /// Mutates self to contain secret artifacts, returning a commitment
-        // THOROUGHLY CHECK AND TEST IT GETS THE DIMENSIONS OF U, V, U1, U2, V1, V2, WITNESS, ETC. CORRECT
-        pub fn mkvole(&mut self) -> Result<ProverCommitment<T>, Error> {
-            if self.num_voles < 1024 {
-                eprintln!("Less than 1024 VOLEs could result in <128 bits of soundness with current parameters for linear codes");
+        /// Like `generate_streaming` with the seeds drawn from `rng` instead of `OsRng`
+        pub fn generate_streaming_with_rng<R: RngCore + CryptoRng + ?Sized>(
+            code: &RAAACode,
+            vole_length: usize,
+            num_voles: usize,
+            chunk_rows: usize,
+            rng: &mut R,
+        ) -> Result<Self, ProofError> {
+            Self::from_seeds_streaming(code, vole_length, random_seeds(rng, num_voles), chunk_rows)
+        }
+
+        /// Creates one small VOLE of length `vole_length` per pair of seeds, and converts them into a subspace VOLE for `code`
+        pub fn from_seeds(
+            code: &RAAACode,
+            vole_length: usize,
+            seeds: Vec<[[u8; 32]; 2]>,
+        ) -> Result<Self, ProofError> {
+            Self::from_seeds_parallel(code, vole_length, seeds, 1)
+        }
+
+        /// Like `from_seeds` but expands the small VOLEs on `threads` threads
+        pub fn from_seeds_parallel(
+            code: &RAAACode,
+            vole_length: usize,
+            seeds: Vec<[[u8; 32]; 2]>,
+            threads: usize,
+        ) -> Result<Self, ProofError> {
+            Self::from_seeds_with_progress(code, vole_length, seeds, threads, &())
+        }
+
+        fn from_seeds_with_progress(
+            code: &RAAACode,
+            vole_length: usize,
+            seeds: Vec<[[u8; 32]; 2]>,
+            threads: usize,
+            progress: &dyn ProgressSink,
+        ) -> Result<Self, ProofError> {
+            let num_voles = seeds.len();
+            if !num_voles.is_multiple_of(code.q) {
+                return Err(ProofError::InvalidNumVoles);
+            };
+            if threads == 0 {
+                return Err(ProofError::InvalidParallelism);
             }
-            let mut rng = ThreadRng::default();
-            let mut seeds: Vec<[[u8; 32]; 2]> = vec![[[0u8; 32]; 2]; self.num_voles];
-            let mut seed_commitments = Vec::with_capacity(self.num_voles);
-            let mut vole_outputs = Vec::with_capacity(self.num_voles);
+            let seed_commitments = seeds
+                .iter()
+                .map(|s| commit_seeds(&s[0], &s[1]))
+                .collect::<Vec<[u8; 32]>>();
             let sv = smallvole::VOLE::init();
-            for i in 0..self.num_voles {
-                rng.fill_bytes(&mut seeds[i][0]);
-                rng.fill_bytes(&mut seeds[i][1]);
-                seed_commitments.push(commit_seeds(&seeds[i][0], &seeds[i][1]));
-                vole_outputs.push(sv.prover_outputs(&seeds[i][0], &seeds[i][1], self.vole_length));
+            let expand = |seeds: &[[[u8; 32]; 2]]| {
+                seeds
+                    .iter()
+                    .map(|s| sv.prover_outputs(&s[0], &s[1], vole_length))
+                    .collect::<Vec<_>>()
+            };
+            let mut vole_outputs = Vec::with_capacity(num_voles);
+            {
+                let _span = span!("vole_generation");
+                progress.on_phase(Phase::VoleGeneration, 0.0);
+                for step in seeds.chunks(num_voles.div_ceil(VOLE_GENERATION_STEPS).max(1)) {
+                    if threads == 1 {
+                        vole_outputs.extend(expand(step));
+                    } else {
+                        std::thread::scope(|scope| {
+                            let handles = step
+                                .chunks(step.len().div_ceil(threads))
+                                .map(|chunk| scope.spawn(|| expand(chunk)))
+                                .collect::<Vec<_>>();
+                            for h in handles {
+                                vole_outputs
+                                    .extend(h.join().expect("VOLE expansion thread panicked"));
+                            }
+                        });
+                    }
+                    progress.on_phase(
+                        Phase::VoleGeneration,
+                        vole_outputs.len() as f64 / num_voles as f64,
+                    );
+                }
             }
 
             let seed_comm = commit_seed_commitments(&seed_commitments);
@@ -226,283 +989,1986 @@ pub mod actors {
                     .map(|o| o.u.clone())
                     .collect::<Vec<FVec<T>>>(),
             );
-            let v_cols = FMatrix(
+            let v = LayoutMatrix::from_cols(FMatrix(
                 vole_outputs
                     .iter()
                     .map(|o| o.v.clone())
                     .collect::<Vec<FVec<T>>>(),
-            );
+            ));
 
+            // The code corrects U row by row, but the consistency check can take U and V in whichever layout they are in
             let u_prime_rows = u_prime_cols.transpose();
-            let v_rows = v_cols.transpose();
 
-            let (new_u_rows, correction) = self.code.get_prover_correction(&u_prime_rows);
-
-            let witness_comm =
-                &self.witness - &FMatrix(new_u_rows.0[0..self.witness.0.len()].to_vec());
+            let (u_rows, correction) = {
+                let _span = span!("correction");
+                progress.on_phase(Phase::Correction, 0.0);
+                let corrected = code.get_prover_correction(&u_prime_rows);
+                progress.on_phase(Phase::Correction, 1.0);
+                corrected
+            };
 
-            self.witness_comm = Some(witness_comm.clone());
-            if self.num_voles % self.code.q != 0 {
-                return Err(anyhow!("invalid num_voles param"));
+            let consistency_check = {
+                let _span = span!("consistency_check");
+                progress.on_phase(Phase::ConsistencyCheck, 0.0);
+                let challenge_hash = vole_consistency_challenge::<T>(&seed_comm, vole_length);
+                let u = LayoutMatrix::from_rows(u_rows);
+                let check = calc_consistency_check::<T>(&challenge_hash, &u, &v);
+                progress.on_phase(Phase::ConsistencyCheck, 1.0);
+                (u.into_rows(), check)
             };
-            let challenge_hash = challenge_from_seed(
-                &seed_comm,
-                "vole_consistency_check".as_bytes(),
-                self.vole_length,
-            );
-            let consistency_check =
-                calc_consistency_check(&challenge_hash, &new_u_rows.transpose(), &v_cols);
+            let (u_rows, consistency_check) = consistency_check;
+            let v_rows = v.into_rows();
+
+            Ok(Self {
+                num_voles,
+                vole_length,
+                seeds,
+                seed_comm,
+                u_rows,
+                v_rows,
+                correction,
+                consistency_check,
+            })
+        }
 
-            // Before storing the secrets, split them in half, which will make reteiving the individual halves easier
+        /// Gives the same result as `from_seeds` but expands the small VOLEs `chunk_rows` rows at a time.
+        /// Each chunk is corrected, folded into the consistency check, and kept only as rows before the next chunk is expanded,
+        /// so the column-major copies of U and V and their transposes are never materialized.
+        ///
+        /// This does not bound memory by `chunk_rows`: the result holds all of U, V, and the correction, i.e.
+        /// `vole_length` * (2n + (n - k)) field elements (n = `num_voles`, k = the code's dimension), as the proof needs them.
+        /// Only the extra full-size copies `from_seeds` makes along the way are avoided.
+        pub fn from_seeds_streaming(
+            code: &RAAACode,
+            vole_length: usize,
+            seeds: Vec<[[u8; 32]; 2]>,
+            chunk_rows: usize,
+        ) -> Result<Self, ProofError> {
+            Self::from_seeds_pipelined_with_progress(code, vole_length, seeds, chunk_rows, 1, &())
+        }
 
-            let u_len = new_u_rows.0.len();
-            let v_len = v_rows.0.len();
+        /// Like `from_seeds_streaming` but expands each chunk on `threads` threads, overlapping the expansion of the next chunk
+        /// with the correction of the current one. This is how `mkvole` creates its subspace VOLE
+        pub fn from_seeds_pipelined(
+            code: &RAAACode,
+            vole_length: usize,
+            seeds: Vec<[[u8; 32]; 2]>,
+            chunk_rows: usize,
+            threads: usize,
+        ) -> Result<Self, ProofError> {
+            Self::from_seeds_pipelined_with_progress(
+                code,
+                vole_length,
+                seeds,
+                chunk_rows,
+                threads,
+                &(),
+            )
+        }
 
-            if !(u_len % 2 == 0) {
-                return Err(anyhow!("Number of u's rows must be even"));
+        /// Since generation, correction, and the consistency check are interleaved chunk by chunk, they progress together.
+        /// A producer thread expands the next chunk of the small VOLEs, on `threads` threads, and transposes it into rows
+        /// while the calling thread corrects the current chunk and folds it into the consistency check, so PRG work overlaps
+        /// with the field arithmetic. At most three chunks are alive at once: one being expanded, one queued, and one being corrected
+        fn from_seeds_pipelined_with_progress(
+            code: &RAAACode,
+            vole_length: usize,
+            seeds: Vec<[[u8; 32]; 2]>,
+            chunk_rows: usize,
+            threads: usize,
+            progress: &dyn ProgressSink,
+        ) -> Result<Self, ProofError> {
+            let num_voles = seeds.len();
+            if !num_voles.is_multiple_of(code.q) {
+                return Err(ProofError::InvalidNumVoles);
+            };
+            if chunk_rows == 0 {
+                return Err(ProofError::InvalidChunkRows);
             }
-            if !(v_len % 2 == 0) {
-                return Err(anyhow!("Number of v's rows must be even"));
+            if threads == 0 {
+                return Err(ProofError::InvalidParallelism);
             }
+            let seed_comm = commit_seed_commitments(
+                &seeds
+                    .iter()
+                    .map(|s| commit_seeds(&s[0], &s[1]))
+                    .collect::<Vec<[u8; 32]>>(),
+            );
+            // The check's challenge over `T` for each coordinate of each challenge row, each folded into a check of its own
+            let challenges = consistency_challenges::<T>(
+                &vole_consistency_challenge::<T>(&seed_comm, vole_length),
+                vole_length,
+            );
+
+            let sv = smallvole::VOLE::init();
+            let mut streams = seeds
+                .iter()
+                .map(|s| sv.prover_output_stream(&s[0], &s[1]))
+                .collect::<Vec<_>>();
 
-            let half_u_len = u_len / 2;
-            let half_v_len = v_len / 2;
+            let k = code.k();
+            let mut scratch = EncodeScratch::new();
+            let mut u_rows = Vec::with_capacity(vole_length);
+            let mut v_rows = Vec::with_capacity(vole_length);
+            let mut correction = Vec::with_capacity(vole_length);
+            let mut u_checks = vec![FVec::<T>(vec![T::ZERO; k]); challenges.len()];
+            let mut v_checks = vec![FVec::<T>(vec![T::ZERO; num_voles]); challenges.len()];
 
-            let u1 = FMatrix(new_u_rows.0[0..half_u_len].to_vec());
-            let u2 = FMatrix(new_u_rows.0[half_u_len..u_len].to_vec());
+            let phases = [
+                Phase::VoleGeneration,
+                Phase::Correction,
+                Phase::ConsistencyCheck,
+            ];
+            let _span = span!("vole_generation_streaming");
+            phases.iter().for_each(|p| progress.on_phase(*p, 0.0));
+            std::thread::scope(|scope| {
+                let (sender, receiver) = mpsc::sync_channel::<(Vec<FVec<T>>, Vec<FVec<T>>)>(1);
+                scope.spawn(move || {
+                    let mut start = 0;
+                    while start < vole_length {
+                        let len = chunk_rows.min(vole_length - start);
+                        let chunk_cols = expand_chunk(&mut streams, len, threads);
+                        let (u_prime_rows, v_rows) = (0..len)
+                            .map(|r| {
+                                (
+                                    FVec::<T>(chunk_cols.iter().map(|c| c.u.0[r]).collect()),
+                                    FVec::<T>(chunk_cols.iter().map(|c| c.v.0[r]).collect()),
+                                )
+                            })
+                            .unzip();
+                        if sender.send((u_prime_rows, v_rows)).is_err() {
+                            return;
+                        }
+                        start += len;
+                    }
+                });
 
-            let v1 = FMatrix(v_rows.0[0..half_v_len].to_vec());
-            let v2 = FMatrix(v_rows.0[half_v_len..v_len].to_vec());
+                let mut start = 0;
+                for (u_prime_chunk, v_chunk) in receiver {
+                    let len = u_prime_chunk.len();
+                    for (r, (u_prime_row, v_row)) in u_prime_chunk.iter().zip(v_chunk).enumerate() {
+                        let mut u_row = code
+                            .mul_vec_by_extended_inverse_with_scratch(u_prime_row, &mut scratch);
+                        let correction_row = FVec::<T>(u_row.0.split_off(k));
 
-            self.seed_commitment = Some(seed_comm.clone());
-            self.subspace_vole_secrets = Some(SubspaceVOLESecrets {
-                seeds,
-                u1,
-                u2,
-                v1,
-                v2,
+                        for ((challenge, u_check), v_check) in
+                            challenges.iter().zip(&mut u_checks).zip(&mut v_checks)
+                        {
+                            u_check.axpy_in_place(challenge.0[start + r], &u_row);
+                            v_check.axpy_in_place(challenge.0[start + r], &v_row);
+                        }
+
+                        u_rows.push(u_row);
+                        v_rows.push(v_row);
+                        correction.push(correction_row);
+                    }
+                    start += len;
+                    phases
+                        .iter()
+                        .for_each(|p| progress.on_phase(*p, start as f64 / vole_length as f64));
+                }
             });
-            Ok(ProverCommitment {
+
+            Ok(Self {
+                num_voles,
+                vole_length,
+                seeds,
                 seed_comm,
-                witness_comm,
-                consistency_check,
-                subspace_vole_correction: correction,
+                u_rows: FMatrix(u_rows),
+                v_rows: FMatrix(v_rows),
+                correction: FMatrix(correction),
+                consistency_check: (
+                    FVec(u_checks.into_iter().flat_map(|c| c.0).collect()),
+                    FVec(v_checks.into_iter().flat_map(|c| c.0).collect()),
+                ),
             })
         }
+    }
 
-        /// Called as part of proof()
-        /// Calculates the S matrix to reveal to the verifier once it learns ∆' and challenge
-        /// Returns (S, constency check value)
-        fn s_matrix_with_consistency_proof(
-            &self,
-            vith_delta: &T,
-            challenge: &FVec<T>,
-        ) -> Result<(FMatrix<T>, FVec<T>), Error> {
-            let svs = self
-                .subspace_vole_secrets
-                .as_ref()
-                .ok_or(anyhow!("VOLE must be completed before this step"))?;
-            let s = &svs.u1.scalar_mul(*vith_delta) + &svs.u2;
-            let proof = challenge * &(&svs.v1.scalar_mul(*vith_delta) + &svs.v2).transpose();
-            Ok((s, proof))
+    /// The next `len` outputs of each small VOLE stream, in the streams' order, split across up to `threads` threads
+    fn expand_chunk<T: PF>(
+        streams: &mut [smallvole::ProverSmallVOLEStream<T>],
+        len: usize,
+        threads: usize,
+    ) -> Vec<smallvole::ProverSmallVOLEOutputs<T>> {
+        if threads == 1 || streams.len() < 2 {
+            return streams.iter_mut().map(|s| s.next_chunk(len)).collect();
         }
+        let per_thread = streams.len().div_ceil(threads);
+        std::thread::scope(|scope| {
+            let handles = streams
+                .chunks_mut(per_thread)
+                .map(|part| {
+                    scope.spawn(move || {
+                        part.iter_mut()
+                            .map(|s| s.next_chunk(len))
+                            .collect::<Vec<_>>()
+                    })
+                })
+                .collect::<Vec<_>>();
+            handles
+                .into_iter()
+                .flat_map(|h| h.join().expect("VOLE expansion thread panicked"))
+                .collect()
+        })
+    }
 
-        /// Wrapper for all other prover functions
-        pub fn prove(&mut self) -> Result<Proof<T>, Error> {
-            // let mut start = Instant::now();
-            let err_uncompleted = || anyhow!("VOLE must be completed before this step");
-            let svs = self
-                .subspace_vole_secrets
-                .as_ref()
-                .ok_or(err_uncompleted())?;
-            let seed_comm = self.seed_commitment.as_ref().ok_or(err_uncompleted())?;
-            let witness_comm = self.witness_comm.as_ref().ok_or(err_uncompleted())?;
-
-            // println!("Committed {}", start.elapsed().as_micros()); start = Instant::now();
-            // TODO: without so much cloning
-            let prover = quicksilver::Prover::from_vith(
-                svs.u1.clone(),
-                svs.u2.clone(),
-                self.witness.clone(),
-                self.circuit.clone(),
-            );
+    /// The transcript of a proof up to and including its witness commitment. For circuits with lookups or memories, i.e. an `aux_layout`,
+    /// the lookup challenges are derived on the way, from the rows of `witness_comm` committed to before them, and returned too
+    fn committed_transcript<T: PF>(
+        aux_layout: Option<&AuxLayout>,
+        statement: &[u8; 32],
+        context: &[u8],
+        seed_comm: &[u8; 32],
+        witness_comm: &FMatrix<T>,
+    ) -> (Transcript, Option<(T, T)>) {
+        let mut transcript = proof_transcript(statement, context, seed_comm);
+        let lookup_challenges = aux_layout.map(|layout| {
+            let committed = &witness_comm.0[..layout.committed_rows.min(witness_comm.0.len())];
+            calc_lookup_challenges(&mut transcript, committed)
+        });
+        transcript.append_matrix(b"witness_comm", &witness_comm.0);
+        (transcript, lookup_challenges)
+    }
 
-            // println!("made prover from VitH {}", start.elapsed().as_micros()); start = Instant::now();
+    /// `circuit` with its lookups and memories reduced to custom gates by the challenges `committed_transcript` derived
+    fn resolve_aux_gates<'c, T: PF>(
+        circuit: &'c R1CSWithMetadata<T>,
+        lookup_challenges: Option<(T, T)>,
+        k: usize,
+    ) -> Cow<'c, R1CSWithMetadata<T>> {
+        match lookup_challenges {
+            None => Cow::Borrowed(circuit),
+            Some((alpha, gamma)) => circuit.with_aux_gates(&alpha, &gamma, k),
+        }
+    }
 
-            let challenge = calc_quicksilver_challenge(seed_comm, &witness_comm);
-            let zkp = prover.prove(&challenge);
+    /// Two fresh random seeds for each of `num_voles` small VOLEs
+    pub(crate) fn random_seeds<R: RngCore + ?Sized>(
+        rng: &mut R,
+        num_voles: usize,
+    ) -> Vec<[[u8; 32]; 2]> {
+        let mut seeds: Vec<[[u8; 32]; 2]> = vec![[[0u8; 32]; 2]; num_voles];
+        for seed_pair in seeds.iter_mut() {
+            rng.fill_bytes(&mut seed_pair[0]);
+            rng.fill_bytes(&mut seed_pair[1]);
+        }
+        seeds
+    }
 
-            // println!("made proof {}", start.elapsed().as_micros()); start = Instant::now();
+    /// A stock of subspace VOLEs generated ahead of time for circuits of one shape.
+    /// Generating the VOLE is the most expensive part of proving, so applications proving many statements
+    /// with the same circuit can fill the pool offline and have each `Prover` consume one VOLE per proof.
+    /// Each VOLE must only be used for a single proof, which `take` guarantees by removing it from the pool.
+    pub struct VolePool<T: PF> {
+        pub code: RAAACode,
+        pub vole_length: usize,
+        pub num_voles: usize,
+        voles: VecDeque<PreparedVOLE<T>>,
+        /// Where the small VOLE seeds come from. `OsRng` if None
+        rng: Option<Box<dyn ProverRng>>,
+    }
+
+    impl<T: PF> VolePool<T> {
+        /// Creates an empty pool whose VOLEs fit proofs for `circuit`
+        pub fn for_circuit(circuit: &R1CSWithMetadata<T>) -> Self {
+            let code = RAAACode::rand_default();
+            let pp = circuit.calc_padding_needed(code.k());
+            Self {
+                num_voles: code.n(),
+                vole_length: SecurityLevel::vole_length::<T>(pp.num_padded_wtns_rows),
+                code,
+                voles: VecDeque::new(),
+                rng: None,
+            }
+        }
+
+        /// Draws the seeds of the VOLEs `fill` generates from `rng` instead of `OsRng`
+        pub fn with_rng(mut self, rng: impl ProverRng + 'static) -> Self {
+            self.rng = Some(Box::new(rng));
+            self
+        }
+
+        /// Generates `count` more VOLEs and adds them to the pool
+        pub fn fill(&mut self, count: usize) -> Result<(), ProofError> {
+            for _ in 0..count {
+                let rng: &mut dyn ProverRng = match self.rng.as_mut() {
+                    Some(rng) => rng.as_mut(),
+                    None => &mut OsRng,
+                };
+                let prepared = PreparedVOLE::generate_with_rng(
+                    &self.code,
+                    self.vole_length,
+                    self.num_voles,
+                    rng,
+                )?;
+                self.voles.push_back(prepared);
+            }
+            Ok(())
+        }
+
+        /// Removes a VOLE from the pool, returning None if the pool is empty
+        pub fn take(&mut self) -> Option<PreparedVOLE<T>> {
+            self.voles.pop_front()
+        }
+
+        pub fn len(&self) -> usize {
+            self.voles.len()
+        }
+
+        pub fn is_empty(&self) -> bool {
+            self.voles.is_empty()
+        }
+    }
+
+    /// Proves the same circuit again and again as a few values of its witness change at a time.
+    /// The padded witness is kept between proofs along with its products with the R1CS's A and B matrices,
+    /// which an update changes only in the constraints that use the updated values. Re-proving therefore skips padding the witness,
+    /// hashing the circuit, and the witness's side of the Quicksilver multiplication check.
+    /// Each proof still commits to the whole witness with a fresh subspace VOLE, since committing twice with the same one would reveal how the witness changed.
+    /// The products aren't kept for circuits with branches, lookups, or memories, whose checks need more than them
+    pub struct IncrementalProver<T: PF> {
+        prover: Prover<T>,
+        /// The constraints each witness value is used in: whether it is in B rather than A, the constraint, and the coefficient
+        uses: Vec<Vec<(bool, usize, T)>>,
+    }
+
+    impl<T: PF> IncrementalProver<T> {
+        /// Keeps `prover`'s configuration, e.g. from `Prover::builder`, for every proof
+        pub fn new(mut prover: Prover<T>) -> Self {
+            let mut uses = vec![];
+            if prover.circuit.branches.is_empty() && prover.aux_layout.is_none() {
+                let witness = FVec(
+                    prover
+                        .witness
+                        .0
+                        .iter()
+                        .flat_map(|row| row.0.iter().copied())
+                        .collect(),
+                );
+                let (a, b, _) = prover.circuit.r1cs.vec_mul(&witness);
+                let SparseR1CS { a_rows, b_rows, .. } = prover.circuit.r1cs.clone().to_sparse();
+                uses = vec![vec![]; witness.0.len()];
+                for (in_b, rows) in [(false, a_rows), (true, b_rows)] {
+                    for (constraint, row) in rows.0.into_iter().enumerate() {
+                        for (i, coeff) in row.0 {
+                            uses[i].push((in_b, constraint, coeff));
+                        }
+                    }
+                }
+                prover.witness_products = Some((a, b));
+            }
+            Self { prover, uses }
+        }
+
+        /// Sets the witness values at the given indices. Fails without changing anything if an index is outside the circuit's witness
+        pub fn update(&mut self, changes: &SparseVec<T>) -> Result<(), ProofError> {
+            let witness_len = self.prover.circuit.unpadded_wtns_len;
+            if let Some((index, _)) = changes.0.iter().find(|(i, _)| *i >= witness_len) {
+                return Err(ProofError::SparseWitnessIndex {
+                    index: *index,
+                    witness_len,
+                });
+            }
+            let k = self.prover.code.k();
+            for (i, value) in &changes.0 {
+                let old = std::mem::replace(&mut self.prover.witness.0[i / k].0[i % k], *value);
+                if let Some((a, b)) = self.prover.witness_products.as_mut() {
+                    let delta = *value - old;
+                    for (in_b, constraint, coeff) in &self.uses[*i] {
+                        let products = if *in_b { &mut *b } else { &mut *a };
+                        products.0[*constraint] += *coeff * delta;
+                    }
+                }
+            }
+            Ok(())
+        }
+
+        /// Commits to the current witness with a fresh subspace VOLE and proves it
+        pub fn commit_and_prove(&mut self) -> Result<CommitAndProof<T>, ProofError> {
+            self.prover.commit_and_prove()
+        }
+    }
+
+    impl<T: PF> Prover<T> {
+        /// Pads a witness and circuit to dimensions compatible with VitH and the linear code, then creates a prover
+        /// Witness of length w is padded to length l where l is a multiple of a linear code's input length. creates a VOLE of length 2l+2
+        /// Mutates and destroys its inputs by padding them and taking ownership of them
+        pub fn from_witness_and_circuit_unpadded(
+            witness: FVec<T>,
+            circuit: R1CSWithMetadata<T>,
+        ) -> Self {
+            Self::from_key(
+                witness.into(),
+                ProvingKey::with_code_params(circuit, CodeParams::default()),
+            )
+        }
+
+        /// Creates a prover for the circuit and code prepared in `key`, so proving many witnesses for one circuit only prepares it once.
+        /// Fails if the witness doesn't fit the circuit. Use `ProverBuilder::from_proving_key` to configure the prover
+        pub fn from_proving_key(
+            witness: impl Into<Witness<T>>,
+            key: &ProvingKey<T>,
+        ) -> Result<Self, ProofError> {
+            ProverBuilder::from_proving_key(witness, key).build()
+        }
+
+        /// Like `from_witness_and_circuit_unpadded` for a witness given by its nonzero values.
+        /// Fails if any of their indices is outside the circuit's witness
+        pub fn from_sparse_witness_and_circuit(
+            witness: SparseVec<T>,
+            circuit: R1CSWithMetadata<T>,
+        ) -> Result<Self, ProofError> {
+            ProverBuilder::new(witness, circuit).build()
+        }
+
+        /// Configures the prover's code, security level, RNG, and parallelism instead of using the defaults
+        pub fn builder(
+            witness: impl Into<Witness<T>>,
+            circuit: R1CSWithMetadata<T>,
+        ) -> ProverBuilder<T> {
+            ProverBuilder::new(witness, circuit)
+        }
+
+        /// Pads the witness for `key`'s circuit and splits it into rows of the code's dimension
+        fn from_key(witness: Witness<T>, key: ProvingKey<T>) -> Self {
+            let ProvingKey {
+                circuit,
+                statement_digest,
+                code_params,
+                code,
+                padding,
+                aux_layout,
+                num_voles,
+                vole_length,
+                ..
+            } = key;
+            let k = code.k();
+
+            let mut witness = witness.into_padded(padding.padded_wtns_len);
+            if let Some(layout) = &aux_layout {
+                circuit.fill_aux_values(&mut witness, layout);
+            }
+            let witness_rows = witness
+                .0
+                .chunks(k)
+                .take(padding.num_padded_wtns_rows)
+                .map(|row| FVec::<T>(row.to_vec()))
+                .collect();
+
+            Self {
+                num_voles,
+                statement: statement_digest,
+                vole_length,
+                code,
+                circuit,
+                witness: FMatrix(witness_rows),
+                seed_commitment: None,
+                subspace_vole_secrets: None,
+                witness_comm: None,
+                rng: None,
+                parallelism: 1,
+                progress: None,
+                code_params,
+                security: SecurityLevel::default(),
+                grinding: 0,
+                context: Vec::new(),
+                witness_products: None,
+                aux_layout,
+            }
+        }
+
+        fn report(&self, phase: Phase, fraction: f64) {
+            if let Some(progress) = &self.progress {
+                progress.on_phase(phase, fraction);
+            }
+        }
+
+        /// Two seeds for each small VOLE, from the prover's RNG
+        fn random_seeds(&mut self) -> Vec<[[u8; 32]; 2]> {
+            match self.rng.as_mut() {
+                Some(rng) => random_seeds(rng.as_mut(), self.num_voles),
+                None => random_seeds(&mut OsRng, self.num_voles),
+            }
+        }
+
+        /// Called first
+        /// Mutates self to contain secret artifacts, returning a commitment
+        // THOROUGHLY CHECK AND TEST IT GETS THE DIMENSIONS OF U, V, U1, U2, V1, V2, WITNESS, ETC. CORRECT
+        pub fn mkvole(&mut self) -> Result<ProverCommitment<T>, ProofError> {
+            self.mkvole_streaming(PIPELINE_CHUNK_ROWS)
+        }
+
+        /// Like `mkvole` but processes the subspace VOLE `chunk_rows` rows at a time instead of `mkvole`'s default chunk size.
+        /// The whole subspace VOLE is still kept, see `PreparedVOLE::from_seeds_streaming`
+        pub fn mkvole_streaming(
+            &mut self,
+            chunk_rows: usize,
+        ) -> Result<ProverCommitment<T>, ProofError> {
+            let _span = span!("mkvole");
+            if self.num_voles < 1024 {
+                tracing::warn!(
+                    num_voles = self.num_voles,
+                    "Less than 1024 VOLEs could result in <128 bits of soundness with current parameters for linear codes"
+                );
+            }
+            let seeds = self.random_seeds();
+            let prepared = PreparedVOLE::from_seeds_pipelined_with_progress(
+                &self.code,
+                self.vole_length,
+                seeds,
+                chunk_rows,
+                self.parallelism,
+                self.progress.as_deref().unwrap_or(&()),
+            )?;
+            self.commit_with_prepared(prepared)
+        }
+
+        /// Like `mkvole` but consumes a subspace VOLE taken from `pool` rather than generating one on the spot
+        pub fn mkvole_from_pool(
+            &mut self,
+            pool: &mut VolePool<T>,
+        ) -> Result<ProverCommitment<T>, ProofError> {
+            let prepared = pool.take().ok_or(ProofError::EmptyVolePool)?;
+            self.commit_with_prepared(prepared)
+        }
+
+        /// Commits to the witness using an already generated subspace VOLE
+        /// Mutates self to contain secret artifacts, returning a commitment
+        pub fn commit_with_prepared(
+            &mut self,
+            prepared: PreparedVOLE<T>,
+        ) -> Result<ProverCommitment<T>, ProofError> {
+            if (prepared.num_voles != self.num_voles) || (prepared.vole_length != self.vole_length)
+            {
+                return Err(ProofError::VoleDimensionMismatch {
+                    found: (prepared.num_voles, prepared.vole_length),
+                    expected: (self.num_voles, self.vole_length),
+                });
+            }
+            let PreparedVOLE {
+                seeds,
+                seed_comm,
+                u_rows,
+                v_rows,
+                correction,
+                consistency_check,
+                ..
+            } = prepared;
+
+            if let Some(layout) = self.aux_layout.clone() {
+                // The inverses depend on the challenges, which depend on the commitment to the rest of the witness
+                let committed = &FMatrix(self.witness.0[..layout.committed_rows].to_vec())
+                    - &FMatrix(u_rows.0[..layout.committed_rows].to_vec());
+                let (alpha, gamma) = calc_lookup_challenges(
+                    &mut proof_transcript(&self.statement, &self.context, &seed_comm),
+                    &committed.0,
+                );
+                let k = self.code.k();
+                let inverses = self
+                    .circuit
+                    .aux_inverses(&alpha, &gamma, &layout, |i| self.witness.0[i / k].0[i % k]);
+                for (i, inverse) in inverses.into_iter().enumerate() {
+                    let idx = layout.inverses_start + i;
+                    self.witness.0[idx / k].0[idx % k] = inverse;
+                }
+            }
+            let vith = VithProver::from_subspace_vole(u_rows, v_rows)?;
+            let witness_comm = vith.commit_witness(&self.witness);
+            self.witness_comm = Some(witness_comm.clone());
+
+            self.seed_commitment = Some(seed_comm);
+            self.subspace_vole_secrets = Some(SubspaceVOLESecrets { seeds, vith });
+            Ok(ProverCommitment {
+                params: self.protocol_params(),
+                seed_comm,
+                witness_comm,
+                consistency_check,
+                subspace_vole_correction: correction,
+            })
+        }
+
+        /// Called as part of proof()
+        /// Calculates the S matrix to reveal to the verifier once it learns ∆' and challenge
+        /// Returns (S, constency check value)
+        fn s_matrix_with_consistency_proof(
+            &self,
+            vith_delta: &T::Ext,
+            challenge: &[T::Ext],
+        ) -> Result<(FMatrix<T>, FVec<T>), ProofError> {
+            let svs = self
+                .subspace_vole_secrets
+                .as_ref()
+                .ok_or(ProofError::VoleNotCompleted)?;
+            Ok(svs.vith.open(vith_delta, challenge))
+        }
+
+        /// Wrapper for all other prover functions
+        pub fn prove(&mut self) -> Result<Proof<T>, ProofError> {
+            self.prove_with_challenger(&mut FiatShamir)
+        }
+
+        /// Like `prove`, but with the challenges from `challenger` instead of Fiat-Shamir.
+        /// The proof only verifies with `Verifier::verify_with_challenger` given the same challenges
+        pub fn prove_with_challenger(
+            &mut self,
+            challenger: &mut impl Challenger<T>,
+        ) -> Result<Proof<T>, ProofError> {
+            let _span = span!("prove");
+            let (zkp, public_openings) = self.prove_quicksilver_challenged(challenger)?;
+            let (challenges, grinding_nonce) =
+                self.opening_challenges(challenger, &zkp, &public_openings, None)?;
+            self.open_vith_with(zkp, public_openings, &challenges, grinding_nonce)
+        }
+
+        /// First half of `prove`: the Quicksilver proof and the openings of the public values.
+        /// Everything it returns is revealed in the final proof
+        pub fn prove_quicksilver(&self) -> Result<(ZKP<T::Ext>, PublicOpenings<T>), ProofError> {
+            self.prove_quicksilver_challenged(&mut FiatShamir)
+        }
+
+        fn prove_quicksilver_challenged(
+            &self,
+            challenger: &mut impl Challenger<T>,
+        ) -> Result<(ZKP<T::Ext>, PublicOpenings<T>), ProofError> {
+            let repetitions = self
+                .security
+                .quicksilver_repetitions::<T>(quicksilver::num_batched(
+                    &self.circuit,
+                    vith::vole_rows::<T>(self.vole_length) * self.code.k(),
+                ));
+            let challenges =
+                challenger.quicksilver_challenges(&self.committed_transcript()?, repetitions);
+            if challenges.len() != repetitions {
+                return Err(ProofError::ChallengeLength);
+            }
+            self.prove_quicksilver_with(&challenges)
+        }
+
+        /// The proof's transcript up to and including the witness commitment
+        fn committed_transcript(&self) -> Result<Transcript, ProofError> {
+            match (&self.seed_commitment, &self.witness_comm) {
+                (Some(seed_comm), Some(witness_comm)) => Ok(committed_transcript(
+                    self.aux_layout.as_ref(),
+                    &self.statement,
+                    &self.context,
+                    seed_comm,
+                    witness_comm,
+                )
+                .0),
+                _ => Err(ProofError::VoleNotCompleted),
+            }
+        }
+
+        fn prove_quicksilver_with(
+            &self,
+            challenges: &[T::Ext],
+        ) -> Result<(ZKP<T::Ext>, PublicOpenings<T>), ProofError> {
+            let _span = span!("quicksilver");
+            let svs = self
+                .subspace_vole_secrets
+                .as_ref()
+                .ok_or(ProofError::VoleNotCompleted)?;
+
+            let (seed_comm, witness_comm) = match (&self.seed_commitment, &self.witness_comm) {
+                (Some(seed_comm), Some(witness_comm)) => (seed_comm, witness_comm),
+                _ => return Err(ProofError::VoleNotCompleted),
+            };
+
+            self.report(Phase::Quicksilver, 0.0);
+            let k = self.code.k();
+            let (_, lookup_challenges) = committed_transcript(
+                self.aux_layout.as_ref(),
+                &self.statement,
+                &self.context,
+                seed_comm,
+                witness_comm,
+            );
+            // The circuit is only copied if it has lookups or memories to resolve with the challenges
+            let circuit = resolve_aux_gates(&self.circuit, lookup_challenges, k);
+            let (u, v) = svs.vith.vole(&self.witness);
+            let mut prover = quicksilver::Prover::from_vole(u, v, &circuit);
+            prover.witness_products = self.witness_products.as_ref();
+
+            let zkp = prover.prove_repeated(challenges);
 
             let public_openings = PublicOpenings {
                 public_inputs: prover.open_public(&self.circuit.public_inputs_indices),
                 public_outputs: prover.open_public(&self.circuit.public_outputs_indices),
             };
 
-            // println!("made public openings {}", start.elapsed().as_micros()); start = Instant::now();
+            self.report(Phase::Quicksilver, 1.0);
+            Ok((zkp, public_openings))
+        }
 
-            let challenges = calc_other_challenges(
-                seed_comm,
-                witness_comm,
-                &zkp,
-                self.vole_length,
-                self.num_voles,
-                &public_openings,
-            );
-            let (s_matrix, s_consistency_check) = self
-                .s_matrix_with_consistency_proof(&challenges.vith_delta, &challenges.s_challenge)?;
+        /// Second half of `prove`: derives ∆' and the small VOLE ∆s from the Quicksilver proof and public openings,
+        /// then reveals the S matrix and the seeds the verifier is allowed to learn
+        pub fn open_vith(
+            &self,
+            zkp: ZKP<T::Ext>,
+            public_openings: PublicOpenings<T>,
+        ) -> Result<Proof<T>, ProofError> {
+            let (challenges, grinding_nonce) =
+                self.opening_challenges(&mut FiatShamir, &zkp, &public_openings, None)?;
+            self.open_vith_with(zkp, public_openings, &challenges, grinding_nonce)
+        }
 
-            let mut openings = Vec::with_capacity(self.num_voles);
-            let mut opening_proofs = Vec::with_capacity(self.num_voles);
-            for i in 0..svs.seeds.len() {
-                openings.push(svs.seeds[i][challenges.delta_choices[i]]);
-                opening_proofs.push(proof_for_revealed_seed(
-                    &svs.seeds[i][1 - challenges.delta_choices[i]],
-                ));
+        /// Grinds for the prover's security level, then asks `challenger` for the challenges for opening the VOLE given the transcript,
+        /// which includes the Quicksilver proof, public openings, and `disclosure` if there is one.
+        /// Returns them with the grinding nonce
+        fn opening_challenges(
+            &self,
+            challenger: &mut impl Challenger<T>,
+            zkp: &ZKP<T::Ext>,
+            public_openings: &PublicOpenings<T>,
+            disclosure: Option<&Disclosure<T>>,
+        ) -> Result<(Challenges<T>, u64), ProofError> {
+            let mut transcript = self.committed_transcript()?;
+            append_quicksilver_proof(&mut transcript, zkp, public_openings);
+            if let Some(d) = disclosure {
+                append_disclosure(&mut transcript, &d.indices, &d.openings);
             }
-            // println!("challenges, consistency check, opening proofs: {}", start.elapsed().as_micros()); start = Instant::now();
+            let grinding_nonce = {
+                let _span = span!("grinding");
+                grind(&transcript, self.grinding_bits())
+            };
+            append_grinding_nonce(&mut transcript, grinding_nonce);
+            let challenges =
+                challenger.opening_challenges(&transcript, self.vole_length, self.num_voles);
+            if !challenges.fit_vole(self.vole_length, self.num_voles) {
+                return Err(ProofError::ChallengeLength);
+            }
+            Ok((challenges, grinding_nonce))
+        }
+
+        /// Bits of grinding for the prover's security level, or more if more were asked for
+        fn grinding_bits(&self) -> usize {
+            self.security
+                .grinding_bits::<T>(self.code_params.preset)
+                .max(self.grinding)
+        }
+
+        fn open_vith_with(
+            &self,
+            zkp: ZKP<T::Ext>,
+            public_openings: PublicOpenings<T>,
+            challenges: &Challenges<T>,
+            grinding_nonce: u64,
+        ) -> Result<Proof<T>, ProofError> {
+            self.report(Phase::SMatrix, 0.0);
+            let (s_matrix, s_consistency_check) = {
+                let _span = span!("s_matrix");
+                self.s_matrix_with_consistency_proof(
+                    &challenges.vith_delta,
+                    &challenges.s_challenge,
+                )?
+            };
+
+            let seed_openings = self.open_seeds(&challenges.delta_choices)?;
+            self.report(Phase::SMatrix, 1.0);
 
             Ok(Proof {
                 zkp,
                 s_matrix,
                 s_consistency_check,
                 public_openings,
-                seed_openings: SubspaceVOLEOpening {
-                    seed_opens: openings,
-                    seed_proofs: opening_proofs,
-                },
+                seed_openings,
+                security: self.security,
+                grinding_bits: self.grinding_bits(),
+                grinding_nonce,
             })
         }
 
-        pub fn commit_and_prove(&mut self) -> Result<CommitAndProof<T>, Error> {
+        /// First step of the designated verifier mode. Like `mkvole` but the commitment has an empty consistency check,
+        /// which is instead made for the verifier's challenge by `answer_designated`.
+        /// Revealing two consistency checks would reveal a linear combination of the witness, so the one derived from the seed commitment is discarded
+        pub fn mkvole_designated(&mut self) -> Result<ProverCommitment<T>, ProofError> {
+            let mut commitment = self.mkvole()?;
+            commitment.consistency_check = (FVec(Vec::new()), FVec(Vec::new()));
+            Ok(commitment)
+        }
+
+        /// Second step of the designated verifier mode: the consistency check and Quicksilver proof for the verifier's challenge.
+        /// Like `open_vith`, this must only be called once per VOLE
+        pub fn answer_designated(
+            &self,
+            challenge: &DesignatedChallenge<T>,
+        ) -> Result<DesignatedAnswer<T>, ProofError> {
+            let svs = self
+                .subspace_vole_secrets
+                .as_ref()
+                .ok_or(ProofError::VoleNotCompleted)?;
+            if challenge.vole_consistency.len()
+                != SecurityLevel::vole_consistency_rows::<T>() * self.vole_length
+            {
+                return Err(ProofError::ChallengeLength);
+            }
+            let consistency_check = calc_consistency_check::<T>(
+                &challenge.vole_consistency,
+                &LayoutMatrix::from_rows(svs.vith.u_rows()),
+                &LayoutMatrix::from_rows(svs.vith.v_rows()),
+            );
+            let (zkp, public_openings) = self.prove_quicksilver_with(&challenge.quicksilver)?;
+            Ok(DesignatedAnswer {
+                consistency_check,
+                zkp,
+                public_openings,
+            })
+        }
+
+        /// Last step of the designated verifier mode: opens the VOLE for the verifier's ∆ choices and ∆', returning the whole transcript
+        pub fn open_designated(
+            &self,
+            mut commitment: ProverCommitment<T>,
+            answer: DesignatedAnswer<T>,
+            challenges: &Challenges<T>,
+        ) -> Result<DesignatedProof<T>, ProofError> {
+            if !challenges.fit_vole(self.vole_length, self.num_voles) {
+                return Err(ProofError::ChallengeLength);
+            }
+            commitment.consistency_check = answer.consistency_check;
+            // The verifier's challenges are not derived from the transcript, so there is nothing to grind for
+            let proof = self.open_vith_with(answer.zkp, answer.public_openings, challenges, 0)?;
+            Ok(DesignatedProof { commitment, proof })
+        }
+
+        /// Reveals the seed of each small VOLE the verifier chose, with a proof for the one it didn't
+        fn open_seeds(&self, delta_choices: &[usize]) -> Result<SubspaceVOLEOpening, ProofError> {
+            let _span = span!("seed_openings");
+            let svs = self
+                .subspace_vole_secrets
+                .as_ref()
+                .ok_or(ProofError::VoleNotCompleted)?;
+            let mut openings = Vec::with_capacity(self.num_voles);
+            let mut opening_proofs = Vec::with_capacity(self.num_voles);
+            for (seeds, &choice) in svs.seeds.iter().zip(delta_choices) {
+                openings.push(seeds[choice]);
+                opening_proofs.push(proof_for_revealed_seed(&seeds[1 - choice]));
+            }
+            Ok(SubspaceVOLEOpening {
+                seed_opens: openings,
+                seed_proofs: opening_proofs,
+            })
+        }
+
+        /// Parameters the prover makes its subspace VOLE with, sent in its commitment
+        pub fn protocol_params(&self) -> ProtocolParams {
+            ProtocolParams {
+                code: self.code_params.preset,
+                num_voles: self.num_voles,
+                vole_length: self.vole_length,
+            }
+        }
+
+        /// The seeds behind the subspace VOLE, if it has been made. These are as secret as the witness
+        pub(crate) fn vole_seeds(&self) -> Option<&Vec<[[u8; 32]; 2]>> {
+            self.subspace_vole_secrets.as_ref().map(|svs| &svs.seeds)
+        }
+
+        /// How much memory the prover's structures take now. The peaks are over the whole process, so cover proving
+        /// if this is called after it. `memstats::reset_peak_heap` starts the heap's peak over, e.g. between proofs
+        pub fn memory_report(&self) -> MemoryReport {
+            #[cfg(feature = "mem-stats")]
+            let (peak_heap, peak_rss) = (crate::memstats::peak_heap(), crate::memstats::peak_rss());
+            #[cfg(not(feature = "mem-stats"))]
+            let (peak_heap, peak_rss) = (None, None);
+            MemoryReport {
+                witness: self.witness.size_in_bytes(),
+                witness_comm: self.witness_comm.as_ref().map_or(0, |w| w.size_in_bytes()),
+                circuit: self.circuit.size_in_bytes(),
+                subspace_vole: self
+                    .subspace_vole_secrets
+                    .as_ref()
+                    .map_or(0, |svs| svs.vith.size_in_bytes()),
+                witness_products: self
+                    .witness_products
+                    .as_ref()
+                    .map_or(0, |(a, b)| a.size_in_bytes() + b.size_in_bytes()),
+                peak_heap,
+                peak_rss,
+            }
+        }
+
+        pub fn commit_and_prove(&mut self) -> Result<CommitAndProof<T>, ProofError> {
             let commitment = self.mkvole()?;
             let proof = self.prove()?;
             Ok(CommitAndProof { commitment, proof })
         }
+
+        /// Like `prove` but also opens the witness at `indices`, which needn't be public in the circuit.
+        /// The indices can be chosen any time before proving but not afterwards: once ∆' is revealed, an opening could be forged for any value.
+        /// The disclosure is therefore bound into the proof's challenges and verified along with it by `Verifier::verify_disclosing`
+        pub fn prove_disclosing(
+            &mut self,
+            indices: &[usize],
+        ) -> Result<(Proof<T>, Disclosure<T>), ProofError> {
+            let _span = span!("prove");
+            let disclosure = self.disclose(indices)?;
+            let (zkp, public_openings) = self.prove_quicksilver()?;
+            let (challenges, grinding_nonce) = self.opening_challenges(
+                &mut FiatShamir,
+                &zkp,
+                &public_openings,
+                Some(&disclosure),
+            )?;
+            let proof = self.open_vith_with(zkp, public_openings, &challenges, grinding_nonce)?;
+            Ok((proof, disclosure))
+        }
+
+        /// `commit_and_prove` with the witness opened at `indices`, see `prove_disclosing`
+        pub fn commit_and_prove_disclosing(
+            &mut self,
+            indices: &[usize],
+        ) -> Result<DisclosingProof<T>, ProofError> {
+            let commitment = self.mkvole()?;
+            let (proof, disclosure) = self.prove_disclosing(indices)?;
+            Ok(DisclosingProof {
+                cnp: CommitAndProof { commitment, proof },
+                disclosure,
+            })
+        }
+
+        /// Opens the VitH VOLE at each of `indices`. Its u values are the witness and its v values are from U2
+        fn disclose(&self, indices: &[usize]) -> Result<Disclosure<T>, ProofError> {
+            let svs = self
+                .subspace_vole_secrets
+                .as_ref()
+                .ok_or(ProofError::VoleNotCompleted)?;
+            let witness_len = self.circuit.unpadded_wtns_len;
+            let k = self.code.k();
+            let openings = indices
+                .iter()
+                .map(|&index| {
+                    if index >= witness_len {
+                        return Err(ProofError::DisclosureIndex { index, witness_len });
+                    }
+                    Ok((
+                        self.witness.0[index / k].0[index % k],
+                        svs.vith.v_at(index, k),
+                    ))
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            Ok(Disclosure {
+                indices: indices.to_vec(),
+                openings,
+            })
+        }
+
+        /// Proves each of `witnesses` satisfies `circuit` in one proof. The dominant costs, making the VOLE and opening its seeds,
+        /// are paid once rather than per witness. Verify it with `Verifier::verify_many`.
+        /// Proves with the default code and security level. Use `ProverBuilder::many` to configure the prover
+        pub fn prove_many(
+            witnesses: Vec<FVec<T>>,
+            circuit: R1CSWithMetadata<T>,
+        ) -> Result<MultiCommitAndProof<T>, ProofError> {
+            ProverBuilder::many(witnesses, circuit)?.prove_many()
+        }
+
+        /// Proves each of `witnesses` satisfies the corresponding circuit of `composite` in one proof with one commitment.
+        /// Verify it with a `Verifier` for `composite.to_circuit()`, then split its public values with `PublicUOpenings::split`
+        pub fn prove_composite(
+            witnesses: Vec<FVec<T>>,
+            composite: &CompositeCircuit<T>,
+        ) -> Result<CommitAndProof<T>, ProofError> {
+            let circuits = &composite.circuits;
+            if circuits.is_empty() {
+                return Err(ProofError::NoCircuits);
+            }
+            if witnesses.len() != circuits.len() {
+                return Err(ProofError::WitnessCount {
+                    expected: circuits.len(),
+                    found: witnesses.len(),
+                });
+            }
+            if circuits.iter().any(|c| !c.branches.is_empty()) {
+                return Err(ProofError::RepeatedBranches);
+            }
+            if let Some((w, c)) = witnesses
+                .iter()
+                .zip(circuits.iter())
+                .find(|(w, c)| w.0.len() != c.unpadded_wtns_len)
+            {
+                return Err(ProofError::WitnessLength {
+                    expected: c.unpadded_wtns_len,
+                    found: w.0.len(),
+                });
+            }
+            let witness = FVec(witnesses.into_iter().flat_map(|w| w.0).collect());
+            Prover::from_witness_and_circuit_unpadded(witness, composite.to_circuit())
+                .commit_and_prove()
+        }
     }
 
-    impl<T: PF> Verifier<T> {
-        /// Calculates the dimensions of the vole and pads the circuit.
-        pub fn from_circuit(mut circuit: R1CSWithMetadata<T>) -> Self {
-            let code = RAAACode::rand_default();
-            let pp = circuit.calc_padding_needed(code.k());
-            circuit.r1cs.zero_pad(pp.pad_len);
-            Verifier {
+    impl<T: PF> ProvingKey<T> {
+        /// Calculates the dimensions of the vole and pads the circuit's constraints for the default code
+        pub fn from_circuit(circuit: R1CSWithMetadata<T>) -> Self {
+            Self::with_code_params(circuit, CodeParams::default())
+        }
+
+        /// Like `from_circuit`, for the code of `params`, see `ProverBuilder::code_params`
+        pub fn with_code_params(mut circuit: R1CSWithMetadata<T>, params: CodeParams) -> Self {
+            let circuit_digest = circuit.circuit_id();
+            let code = params.code();
+            let padding = circuit.calc_padding_needed(code.k());
+            circuit.zero_pad_constraints(padding.pad_len);
+            let aux_layout = circuit.aux_layout(code.k());
+            let vole_length = SecurityLevel::vole_length::<T>(padding.num_padded_wtns_rows);
+            ProvingKey {
                 circuit,
+                statement_digest: statement_digest(&circuit_digest, &params, code.n(), vole_length),
+                circuit_digest,
+                code_params: params,
                 num_voles: code.n(),
-                // One extra row for the hiding of the linear combination of the relevant values in the consistency check
-                // 2x extra rows to convert subsapce VOLE into VitH. Overall, we require 2 * `num_padded_witness_rows` + 2 rows
-                vole_length: 2 * (pp.num_padded_wtns_rows + 1),
+                vole_length,
                 code,
-                subspace_vole_deltas: None,
-                vith_delta: None,
+                padding,
+                aux_layout,
             }
         }
+    }
+
+    impl<T: PF> VerifyingKey<T> {
+        /// Calculates the dimensions of the vole and converts the circuit to sparse form.
+        pub fn from_circuit(circuit: R1CSWithMetadata<T>) -> Self {
+            Self::with_code_params(circuit, CodeParams::default())
+        }
 
-        /// TODO: ensure every value in the ProverCommitment and Proof is checked in some way by this function:
-        pub fn verify(&self, cnp: &CommitAndProof<T>) -> Result<PublicUOpenings<T>, Error> {
-            let comm = &cnp.commitment;
-            let proof = &cnp.proof;
-            let challenges = calc_other_challenges(
+        /// Like `from_circuit`, for proofs made with the code of `params`, see `ProverBuilder::code_params`
+        pub fn with_code_params(circuit: R1CSWithMetadata<T>, params: CodeParams) -> Self {
+            let circuit_digest = circuit.circuit_id();
+            let code = params.code();
+            let pp = circuit.calc_padding_needed(code.k());
+            let vole_length = SecurityLevel::vole_length::<T>(pp.num_padded_wtns_rows);
+            let aux_layout = circuit.aux_layout(code.k());
+            VerifyingKey {
+                circuit: R1CSWithMetadata {
+                    r1cs: R1CS::Sparse(circuit.r1cs.to_sparse()),
+                    branches: circuit
+                        .branches
+                        .into_iter()
+                        .map(|b| R1CS::Sparse(b.to_sparse()))
+                        .collect(),
+                    ..circuit
+                },
+                statement_digest: statement_digest(&circuit_digest, &params, code.n(), vole_length),
+                circuit_digest,
+                code_params: params,
+                num_voles: code.n(),
+                vole_length,
+                code,
+                min_security: SecurityLevel::default(),
+                aux_layout,
+            }
+        }
+
+        /// Parameters the prover's commitment must have been made with
+        pub fn protocol_params(&self) -> ProtocolParams {
+            ProtocolParams {
+                code: self.code_params.preset,
+                num_voles: self.num_voles,
+                vole_length: self.vole_length,
+            }
+        }
+
+        /// Rejects proofs with a security level lower than `level`. Proofs over `T` claiming more than `SecurityLevel::max_for`
+        /// are rejected regardless
+        pub fn require_security(mut self, level: SecurityLevel) -> Self {
+            self.min_security = level;
+            self
+        }
+
+        /// Checks the proof claims at least the minimum security level, at most what the field and code allow,
+        /// and has ground enough for it, given the transcript up to the grinding nonce
+        fn check_security(
+            &self,
+            transcript: &Transcript,
+            proof: &Proof<T>,
+        ) -> Result<(), VerifyError> {
+            self.check_security_claim(
+                transcript,
+                proof.security,
+                proof.grinding_bits,
+                proof.grinding_nonce,
+            )
+        }
+
+        /// `check_security` for a proof claiming `security` with a grinding nonce of `grinding_bits`
+        fn check_security_claim(
+            &self,
+            transcript: &Transcript,
+            security: SecurityLevel,
+            grinding_bits: usize,
+            grinding_nonce: u64,
+        ) -> Result<(), VerifyError> {
+            let max = SecurityLevel::max_for::<T>(self.code_params.preset);
+            if security > max {
+                return Err(VerifyError::UnreachableSecurity {
+                    found: security.0,
+                    max: max.0,
+                });
+            }
+            if security < self.min_security {
+                return Err(VerifyError::InsufficientSecurity {
+                    required: self.min_security.0,
+                    found: security.0,
+                });
+            }
+            if grinding_bits < security.grinding_bits::<T>(self.code_params.preset)
+                || grinding_bits > MAX_GRINDING_BITS
+                || !check_grinding(transcript, grinding_nonce, grinding_bits)
+            {
+                return Err(VerifyError::Grinding);
+            }
+            Ok(())
+        }
+
+        /// Rejects proofs whose vectors and matrices aren't the sizes `verify` indexes them at
+        fn check_dimensions(
+            &self,
+            comm: &ProverCommitment<T>,
+            seed_openings: &SubspaceVOLEOpening,
+            s_matrix: &FMatrix<T>,
+            s_consistency_check: &FVec<T>,
+        ) -> Result<(), VerifyError> {
+            if comm.params != self.protocol_params() {
+                return Err(VerifyError::ParamsMismatch {
+                    expected: self.protocol_params(),
+                    found: comm.params,
+                });
+            }
+            let (n, k, degree) = (self.num_voles, self.code.k(), T::Ext::DEGREE);
+            let (h, checks) = (
+                vith::vole_rows::<T>(self.vole_length),
+                SecurityLevel::vole_consistency_rows::<T>() * degree,
+            );
+            let has_shape = |m: &FMatrix<T>, rows: usize, cols: usize| {
+                m.0.len() == rows && m.0.iter().all(|row| row.0.len() == cols)
+            };
+            if seed_openings.seed_opens.len() != n || seed_openings.seed_proofs.len() != n {
+                return Err(VerifyError::Malformed("wrong number of seed openings"));
+            }
+            if !has_shape(&comm.subspace_vole_correction, self.vole_length, n - k)
+                || comm.consistency_check.0 .0.len() != checks * k
+                || comm.consistency_check.1 .0.len() != checks * n
+            {
+                return Err(VerifyError::Malformed(
+                    "subspace VOLE correction or consistency check has the wrong dimensions",
+                ));
+            }
+            if !has_shape(&comm.witness_comm, h - checks, k) {
+                return Err(VerifyError::Malformed(
+                    "witness commitment has the wrong dimensions",
+                ));
+            }
+            if !has_shape(s_matrix, degree * h, k) || s_consistency_check.0.len() != degree * n {
+                return Err(VerifyError::Malformed(
+                    "S matrix or its consistency check has the wrong dimensions",
+                ));
+            }
+            Ok(())
+        }
+
+        /// The transcript of the proof committed to in `comm` up to and including the witness commitment,
+        /// and the circuit with its lookups and memories reduced to custom gates with the lookup challenges derived on the way
+        fn committed_transcript(
+            &self,
+            comm: &ProverCommitment<T>,
+            context: &[u8],
+        ) -> (Transcript, Cow<'_, R1CSWithMetadata<T>>) {
+            let k = self.code.k();
+            let (transcript, lookup_challenges) = committed_transcript(
+                self.aux_layout.as_ref(),
+                &self.statement_digest,
+                context,
                 &comm.seed_comm,
                 &comm.witness_comm,
-                &proof.zkp,
-                self.vole_length,
-                self.num_voles,
-                &proof.public_openings,
             );
+            (
+                transcript,
+                resolve_aux_gates(&self.circuit, lookup_challenges, k),
+            )
+        }
+
+        /// Number of Quicksilver challenges for a proof of `security`. Levels above `SecurityLevel::max_for` are rejected by `check_security`,
+        /// so they are capped here rather than deriving an unbounded number of challenges first
+        fn quicksilver_repetitions(&self, security: SecurityLevel) -> usize {
+            let security = security.min(SecurityLevel::max_for::<T>(self.code_params.preset));
+            security.quicksilver_repetitions::<T>(quicksilver::num_batched(
+                &self.circuit,
+                vith::vole_rows::<T>(self.vole_length) * self.code.k(),
+            ))
+        }
+
+        /// Challenge for the subspace VOLE consistency check of a non-interactive proof
+        fn consistency_challenge(&self, comm: &ProverCommitment<T>) -> Vec<T::Ext> {
+            vole_consistency_challenge::<T>(&comm.seed_comm, self.vole_length)
+        }
+
+        /// Reconstructs the subspace VOLE from the opened seeds and runs every check on it,
+        /// returning the residuals of the linear checks instead of checking them
+        fn check_vole(
+            &self,
+            comm: &ProverCommitment<T>,
+            seed_openings: &SubspaceVOLEOpening,
+            s_matrix: &FMatrix<T>,
+            s_consistency_check: &FVec<T>,
+            challenges: &Challenges<T>,
+            consistency_challenge: &[T::Ext],
+        ) -> Result<LinearCheckResiduals<T>, VerifyError> {
+            let (deltas, q_rows, seeds_match) =
+                self.reconstruct_vole(comm, seed_openings, &challenges.delta_choices);
+            if !seeds_match {
+                return Err(VerifyError::SeedCommitmentMismatch);
+            }
+            Ok(LinearCheckResiduals {
+                consistency: self.consistency_residual(
+                    comm,
+                    &deltas,
+                    &q_rows,
+                    consistency_challenge,
+                ),
+                s_matrix: self.s_matrix_residual(
+                    &deltas,
+                    &q_rows,
+                    s_matrix,
+                    s_consistency_check,
+                    challenges,
+                )?,
+            })
+        }
+
+        /// Calculates the small VOLE outputs from the opened seeds and corrects them into the subspace VOLE.
+        /// Returns its ∆s, its Q by rows, and whether the seeds were all committed to in the seed commitment
+        fn reconstruct_vole(
+            &self,
+            comm: &ProverCommitment<T>,
+            seed_openings: &SubspaceVOLEOpening,
+            delta_choices: &[usize],
+        ) -> (FVec<T>, LayoutMatrix<T>, bool) {
             let mut deltas = Vec::<T>::with_capacity(self.num_voles);
             let mut q_cols = Vec::<FVec<T>>::with_capacity(self.num_voles);
-            // Calculate small VOLE outputs then check they were all committed to in comm.seed_comm
-            let sv = smallvole::VOLE::<T>::init();
-            let mut hasher = blake3::Hasher::new();
-            for i in 0..self.num_voles {
-                let rec = reconstruct_commitment(
-                    &proof.seed_openings.seed_opens[i],
-                    challenges.delta_choices[i] != 0, // Convert usize that should be 0 or 1 to bool
-                    &proof.seed_openings.seed_proofs[i],
+            let seeds_match = {
+                let _span = span!("seed_openings");
+                let sv = smallvole::VOLE::<T>::init();
+                let mut hasher = blake3::Hasher::new();
+                for ((seed_open, seed_proof), &choice) in seed_openings
+                    .seed_opens
+                    .iter()
+                    .zip(&seed_openings.seed_proofs)
+                    .zip(delta_choices)
+                {
+                    let rec = reconstruct_commitment(
+                        seed_open,
+                        choice != 0, // Convert usize that should be 0 or 1 to bool
+                        seed_proof,
+                    );
+                    hasher.update(&rec);
+                    let vole_outs = sv.verifier_outputs(seed_open, choice == 0, self.vole_length);
+                    deltas.push(vole_outs.delta);
+                    q_cols.push(vole_outs.q);
+                }
+                *hasher.finalize().as_bytes() == comm.seed_comm
+            };
+
+            // Construct the subspace VOLE
+            let q_rows = LayoutMatrix::from_cols(FMatrix(q_cols)).into_rows();
+            let deltas = FVec::<T>(deltas);
+
+            let new_q_rows = {
+                let _span = span!("correction");
+                self.code
+                    .correct_verifier_qs(&q_rows, &deltas, &comm.subspace_vole_correction)
+            };
+            (deltas, LayoutMatrix::from_rows(new_q_rows), seeds_match)
+        }
+
+        /// Residual of the check that the subspace VOLE's outputs are in the subspace
+        fn consistency_residual(
+            &self,
+            comm: &ProverCommitment<T>,
+            deltas: &FVec<T>,
+            q: &LayoutMatrix<T>,
+            consistency_challenge: &[T::Ext],
+        ) -> FVec<T> {
+            let _span = span!("consistency_check");
+            self.code.consistency_check_residual(
+                consistency_challenge,
+                &comm.consistency_check,
+                deltas,
+                q,
+            )
+        }
+
+        /// Residual of the check that S is constructed properly
+        fn s_matrix_residual(
+            &self,
+            deltas: &FVec<T>,
+            q: &LayoutMatrix<T>,
+            s_matrix: &FMatrix<T>,
+            s_consistency_check: &FVec<T>,
+            challenges: &Challenges<T>,
+        ) -> Result<FVec<T>, VerifyError> {
+            let _span = span!("s_matrix");
+            let q_rows = q.rows();
+            if q_rows.0.len() != self.vole_length {
+                return Err(VerifyError::Malformed("Q must be vole_length"));
+            }
+            VithVerifier::<T>::new(challenges.vith_delta).s_matrix_residual(
+                &self.code,
+                deltas,
+                &q_rows,
+                s_matrix,
+                s_consistency_check,
+                &challenges.s_challenge,
+            )
+        }
+    }
+
+    impl<T: PF> Verifier<T> {
+        /// Makes the circuit's verifying key. To verify proofs for the same circuit with several verifiers, make the key once and use `from_verifying_key`
+        pub fn from_circuit(circuit: R1CSWithMetadata<T>) -> Self {
+            Self::from_verifying_key(VerifyingKey::from_circuit(circuit))
+        }
+
+        pub fn from_verifying_key(key: VerifyingKey<T>) -> Self {
+            Verifier {
+                key,
+                subspace_vole_deltas: None,
+                vith_delta: None,
+            }
+        }
+
+        /// Verifies the proof, returning its public values. Every vector and matrix in it is checked to have the dimensions
+        /// the verifying key's VOLE and circuit give it before any check indexes into it
+        pub fn verify(&self, cnp: &CommitAndProof<T>) -> Result<PublicUOpenings<T>, VerifyError> {
+            self.verify_with_context(cnp, &[])
+        }
+
+        /// Verifies a proof made by a prover with the context `context`, see `ProverBuilder::context`.
+        /// A proof made for any other context, e.g. another session, nonce, or recipient, fails as if it were forged
+        pub fn verify_with_context(
+            &self,
+            cnp: &CommitAndProof<T>,
+            context: &[u8],
+        ) -> Result<PublicUOpenings<T>, VerifyError> {
+            self.verify_with_challenger(cnp, context, &mut FiatShamir)
+        }
+
+        /// Verifies a proof made by `Prover::prove_with_challenger`. `challenger` must give the same challenges the prover's did
+        pub fn verify_with_challenger(
+            &self,
+            cnp: &CommitAndProof<T>,
+            context: &[u8],
+            challenger: &mut impl Challenger<T>,
+        ) -> Result<PublicUOpenings<T>, VerifyError> {
+            let _span = span!("verify");
+            let (public_openings, residuals) =
+                self.verify_deferring_linear_checks(cnp, None, context, challenger)?;
+            residuals.check()?;
+            Ok(public_openings)
+        }
+
+        /// Decodes and verifies a proof in the canonical encoding. Its declared parameters are checked against the verifying key's
+        /// before the payload is decoded, so a proof for another circuit's dimensions is rejected without decoding it
+        pub fn verify_envelope(
+            &self,
+            envelope: &ProofEnvelope,
+        ) -> Result<PublicUOpenings<T>, VerifyError> {
+            let params = &envelope.params;
+            if params.protocol() != self.key.protocol_params() {
+                return Err(VerifyError::ParamsMismatch {
+                    expected: self.key.protocol_params(),
+                    found: params.protocol(),
+                });
+            }
+            if params.security < self.key.min_security {
+                return Err(VerifyError::InsufficientSecurity {
+                    required: self.key.min_security.0,
+                    found: params.security.0,
+                });
+            }
+            self.verify(&CommitAndProof::from_envelope(envelope)?)
+        }
+
+        /// Verifies every proof in a bundle with `verify_batch`, once the bundle is checked to be for this verifier's circuit
+        pub fn verify_bundle(
+            &self,
+            bundle: &ProofBundle<T>,
+        ) -> Result<Vec<Result<PublicUOpenings<T>, VerifyError>>, VerifyError> {
+            if bundle.circuit_digest != self.key.circuit_digest {
+                return Err(VerifyError::Malformed("bundle is for another circuit"));
+            }
+            Ok(self.verify_batch(&bundle.proofs))
+        }
+
+        /// Verifies the proof and that its public values are `expected`. The values are compared in constant time
+        pub fn verify_with_public(
+            &self,
+            cnp: &CommitAndProof<T>,
+            expected: &PublicUOpenings<T>,
+        ) -> Result<(), VerifyError> {
+            let public_values = self.verify(cnp)?;
+            if bool::from(public_values.ct_eq(expected)) {
+                Ok(())
+            } else {
+                Err(VerifyError::PublicValuesMismatch)
+            }
+        }
+
+        /// Verifies a proof made by `Prover::prove_many`, returning each instance's public values in the order of the witnesses.
+        /// The proof must have been made with the key's code and at least its minimum security level
+        pub fn verify_many(
+            &self,
+            proof: &MultiCommitAndProof<T>,
+        ) -> Result<Vec<PublicUOpenings<T>>, VerifyError> {
+            let circuit = &self.key.circuit;
+            // Check the number of instances against the proof's size before building a circuit that large
+            let witness_rows = proof.cnp.commitment.witness_comm.0.len();
+            let fits = proof
+                .instances
+                .checked_mul(circuit.unpadded_wtns_len)
+                .is_some_and(|len| match self.key.aux_layout.is_none() {
+                    true => len / self.key.code.k() + 1 == witness_rows,
+                    // Values for lookups and memories take more rows, which `verify` checks once the circuit is repeated
+                    false => len < witness_rows * self.key.code.k(),
+                });
+            if !circuit.branches.is_empty() {
+                return Err(VerifyError::Malformed(
+                    "circuits with branches can't be proven for several witnesses at once",
+                ));
+            }
+            if proof.instances == 0 || !fits {
+                return Err(VerifyError::Malformed(
+                    "number of instances does not match the witness commitment",
+                ));
+            }
+            let key = VerifyingKey::with_code_params(
+                circuit.repeat(proof.instances),
+                self.key.code_params,
+            )
+            .require_security(self.key.min_security);
+            let public_values = Verifier::from_verifying_key(key).verify(&proof.cnp)?;
+
+            let lens = (
+                circuit.public_inputs_indices.len(),
+                circuit.public_outputs_indices.len(),
+            );
+            Ok(public_values.split_by(std::iter::repeat_n(lens, proof.instances)))
+        }
+
+        /// Verifies that the witness behind `commitment` satisfies the circuit of each of `keys`, in the order they were proven.
+        /// Returns the public values for each circuit. The proof must claim at least each key's minimum security level
+        pub fn verify_linked(
+            commitment: &ProverCommitment<T>,
+            keys: &[VerifyingKey<T>],
+            proof: &LinkedProof<T>,
+        ) -> Result<Vec<PublicUOpenings<T>>, VerifyError> {
+            Self::verify_linked_with_context(commitment, keys, proof, &[])
+        }
+
+        /// `verify_linked` for a proof bound to `context`, see `CommittedWitness::context`
+        pub fn verify_linked_with_context(
+            commitment: &ProverCommitment<T>,
+            keys: &[VerifyingKey<T>],
+            proof: &LinkedProof<T>,
+            context: &[u8],
+        ) -> Result<Vec<PublicUOpenings<T>>, VerifyError> {
+            if proof.context != context {
+                return Err(VerifyError::ContextMismatch);
+            }
+            let first = keys.first().ok_or(VerifyError::Malformed("no circuits"))?;
+            if keys.len() != proof.statements.len() {
+                return Err(VerifyError::Malformed(
+                    "number of statements does not match the number of circuits",
+                ));
+            }
+            if keys
+                .iter()
+                .any(|k| k.protocol_params() != first.protocol_params() || k.code != first.code)
+            {
+                return Err(VerifyError::Malformed(
+                    "circuits need VOLEs of different dimensions",
+                ));
+            }
+            if keys
+                .iter()
+                .any(|k| !k.circuit.lookups.is_empty() || !k.circuit.memories.is_empty())
+            {
+                return Err(VerifyError::Malformed(
+                    "linked proofs cannot have lookups or memories",
+                ));
+            }
+            first.check_dimensions(
+                commitment,
+                &proof.seed_openings,
+                &proof.s_matrix,
+                &proof.s_consistency_check,
+            )?;
+            let statements = keys.iter().map(|k| k.statement_digest).collect::<Vec<_>>();
+            let mut transcript = linked_transcript(
+                &statements,
+                context,
+                proof.security,
+                &commitment.seed_comm,
+                &commitment.witness_comm,
+            );
+            for (zkp, public_openings) in &proof.statements {
+                append_quicksilver_proof(&mut transcript, zkp, public_openings);
+            }
+            for key in keys {
+                key.check_security_claim(
+                    &transcript,
+                    proof.security,
+                    proof.grinding_bits,
+                    proof.grinding_nonce,
+                )?;
+            }
+            append_grinding_nonce(&mut transcript, proof.grinding_nonce);
+            let challenges = calc_other_challenges(&transcript, first.vole_length, first.num_voles);
+            let residuals = first.check_vole(
+                commitment,
+                &proof.seed_openings,
+                &proof.s_matrix,
+                &proof.s_consistency_check,
+                &challenges,
+                &first.consistency_challenge(commitment),
+            )?;
+
+            let mut public_values = Vec::with_capacity(keys.len());
+            for (key, (zkp, public_openings)) in keys.iter().zip(proof.statements.iter()) {
+                let vith = VithVerifier::<T>::new(challenges.vith_delta);
+                let zk_verifier = quicksilver::Verifier::from_vole(
+                    vith.vole(&proof.s_matrix, &commitment.witness_comm),
+                    vith.delta(),
+                    &key.circuit,
                 );
-                hasher.update(&rec);
-                let vole_outs = sv.verifier_outputs(
-                    &proof.seed_openings.seed_opens[i],
-                    challenges.delta_choices[i] == 0,
-                    self.vole_length,
+                let repetitions = key.quicksilver_repetitions(proof.security);
+                quicksilver::check_dimensions(&key.circuit, zkp, repetitions)?;
+                let quicksilver_challenges = calc_quicksilver_challenges::<T>(
+                    &key.committed_transcript(commitment, context).0,
+                    repetitions,
                 );
-                deltas.push(vole_outs.delta);
-                q_cols.push(vole_outs.q);
+                zk_verifier.verify_repeated(&quicksilver_challenges, zkp)?;
+                zk_verifier.verify_public(public_openings)?;
+                public_values.push(public_openings.u_values());
             }
+            residuals.check()?;
+            Ok(public_values)
+        }
 
-            if !(*hasher.finalize().as_bytes() == comm.seed_comm) {
-                return Err(anyhow!("Seed commitment is not a commitment to the seeds"));
+        /// Verifies many proofs for this verifier's circuit, returning each proof's result in the same order as `cnps`.
+        /// Proofs are checked in parallel on up to `std::thread::available_parallelism` threads.
+        /// Their subspace VOLE consistency and S matrix checks are combined at a random point into one check,
+        /// which is only broken down into the individual proofs' checks if it fails
+        pub fn verify_batch(
+            &self,
+            cnps: &[CommitAndProof<T>],
+        ) -> Vec<Result<PublicUOpenings<T>, VerifyError>> {
+            let _span = span!("verify_batch");
+            if cnps.is_empty() {
+                return Vec::new();
             }
+            let num_threads = std::thread::available_parallelism()
+                .map(|n| n.get())
+                .unwrap_or(1);
+            let chunk_size = cnps.len().div_ceil(num_threads);
+            let deferred = std::thread::scope(|scope| {
+                let handles = cnps
+                    .chunks(chunk_size)
+                    .map(|chunk| {
+                        scope.spawn(move || {
+                            chunk
+                                .iter()
+                                .map(|cnp| {
+                                    self.verify_deferring_linear_checks(
+                                        cnp,
+                                        None,
+                                        &[],
+                                        &mut FiatShamir,
+                                    )
+                                })
+                                .collect::<Vec<_>>()
+                        })
+                    })
+                    .collect::<Vec<_>>();
+                handles
+                    .into_iter()
+                    .flat_map(|h| h.join().expect("verification thread panicked"))
+                    .collect::<Vec<_>>()
+            });
 
-            // Construct the subspace VOLE
-            let q_rows = FMatrix(q_cols).transpose();
-            let deltas = FVec::<T>(deltas);
+            // The residuals' entries as the coefficients of one polynomial, evaluated at a random point of the challenge field.
+            // It is zero there only by chance unless every entry is
+            let point = T::Ext::sample(&mut OsRng);
+            let combined = deferred
+                .iter()
+                .filter_map(|d| d.as_ref().ok())
+                .flat_map(|(_, residuals)| {
+                    residuals.consistency.0.iter().chain(&residuals.s_matrix.0)
+                })
+                .fold(T::Ext::from(T::ZERO), |acc, x| {
+                    acc * point + T::Ext::from(*x)
+                });
+            let all_pass = combined == T::Ext::from(T::ZERO);
 
-            let new_q_rows =
-                self.code
-                    .correct_verifier_qs(&q_rows, &deltas, &comm.subspace_vole_correction);
-            // Check that its outputs are in the subspace
-            let challenge_hash = &challenge_from_seed(
-                &comm.seed_comm,
-                "vole_consistency_check".as_bytes(),
-                self.vole_length,
+            deferred
+                .into_iter()
+                .map(|d| {
+                    let (public_openings, residuals) = d?;
+                    if !all_pass {
+                        residuals.check()?;
+                    }
+                    Ok(public_openings)
+                })
+                .collect()
+        }
+
+        /// Runs the same checks as `verify` but reports the outcome and timing of each rather than stopping at the first failure.
+        /// Slower than `verify`, so meant for debugging rather than verifying in production.
+        /// Timings use `std::time::Instant`, which is unavailable on wasm32-unknown-unknown
+        pub fn verify_report(&self, cnp: &CommitAndProof<T>) -> VerificationReport<T> {
+            let _span = span!("verify");
+            let comm = &cnp.commitment;
+            let proof = &cnp.proof;
+            let mut report = VerificationReport {
+                checks: Vec::new(),
+                public_values: None,
+            };
+
+            let start = Instant::now();
+            let dimensions = self.check_dimensions(cnp);
+            if !report.record(Check::Dimensions, start, dimensions) {
+                return report;
+            }
+
+            let (mut transcript, circuit) = self.key.committed_transcript(comm, &[]);
+            let quicksilver_challenges = calc_quicksilver_challenges::<T>(
+                &transcript,
+                self.key.quicksilver_repetitions(proof.security),
             );
+            append_quicksilver_proof(&mut transcript, &proof.zkp, &proof.public_openings);
 
-            self.code.verify_consistency_check(
-                challenge_hash,
-                &comm.consistency_check,
+            let start = Instant::now();
+            let security = self.key.check_security(&transcript, proof);
+            report.record(Check::Security, start, security);
+
+            append_grinding_nonce(&mut transcript, proof.grinding_nonce);
+            let challenges =
+                calc_other_challenges(&transcript, self.key.vole_length, self.key.num_voles);
+
+            let start = Instant::now();
+            let (deltas, q_rows, seeds_match) =
+                self.key
+                    .reconstruct_vole(comm, &proof.seed_openings, &challenges.delta_choices);
+            let seed_commitment = if seeds_match {
+                Ok(())
+            } else {
+                Err(VerifyError::SeedCommitmentMismatch)
+            };
+            report.record(Check::SeedCommitment, start, seed_commitment);
+
+            let start = Instant::now();
+            let consistency = self.key.consistency_residual(
+                comm,
                 &deltas,
-                &new_q_rows.transpose(),
-            )?;
+                &q_rows,
+                &self.key.consistency_challenge(comm),
+            );
+            let consistency = if consistency.is_zero() {
+                Ok(())
+            } else {
+                Err(VerifyError::ConsistencyCheck)
+            };
+            report.record(Check::ConsistencyCheck, start, consistency);
 
-            // Perhaps this is better in a separate function since this is long but it is different to uncouple all the components of verification
-            // Doing the mutability like the prover may help split large functions:
-            // Check S matrix is constructed properly
-            debug_assert!(
-                (new_q_rows.0.len() == self.vole_length) && (self.vole_length % 2 == 0),
-                "Q must be vole_length and even"
+            let start = Instant::now();
+            let s_matrix = self
+                .key
+                .s_matrix_residual(
+                    &deltas,
+                    &q_rows,
+                    &proof.s_matrix,
+                    &proof.s_consistency_check,
+                    &challenges,
+                )
+                .and_then(|residual| match residual.is_zero() {
+                    true => Ok(()),
+                    false => Err(VerifyError::SMatrix),
+                });
+            report.record(Check::SMatrix, start, s_matrix);
+
+            let start = Instant::now();
+            let vith = VithVerifier::<T>::new(challenges.vith_delta);
+            let zk_verifier = quicksilver::Verifier::from_vole(
+                vith.vole(&proof.s_matrix, &comm.witness_comm),
+                vith.delta(),
+                &circuit,
             );
-            let half_len = self.vole_length / 2;
-            let q1 = FMatrix(new_q_rows.0[0..half_len].to_vec());
-            let q2 = FMatrix(new_q_rows.0[half_len..self.vole_length].to_vec());
-            let sgc_diag_delta = self
-                .code
-                .batch_encode(&proof.s_matrix.0)
+            let zkp = zk_verifier.verify_repeated(&quicksilver_challenges, &proof.zkp);
+            report.record(Check::Quicksilver, start, zkp);
+
+            let start = Instant::now();
+            let public = zk_verifier.verify_public(&proof.public_openings);
+            report.record(Check::PublicOpenings, start, public);
+
+            if report.passed() {
+                report.public_values = Some(proof.public_openings.u_values());
+            }
+            report
+        }
+
+        /// Verifies a proof made by `Prover::prove_disclosing`, returning its public values and the u values of the disclosed witness indices
+        pub fn verify_disclosing(
+            &self,
+            dp: &DisclosingProof<T>,
+        ) -> Result<(PublicUOpenings<T>, Vec<T>), VerifyError> {
+            let _span = span!("verify");
+            let disclosure = &dp.disclosure;
+            if disclosure.indices.len() != disclosure.openings.len() {
+                return Err(VerifyError::Malformed(
+                    "number of disclosed indices and openings differ",
+                ));
+            }
+            if disclosure
+                .indices
                 .iter()
-                .map(|row| row * &deltas)
-                .collect::<Vec<FVec<T>>>();
-            let lhs = &challenges.s_challenge
-                * &(&q1.scalar_mul(challenges.vith_delta) + &q2).transpose();
-            let rhs = &proof.s_consistency_check
-                + &(&challenges.s_challenge * &FMatrix(sgc_diag_delta).transpose());
-            if lhs != rhs {
-                return Err(anyhow!("failed to verify S matrix"));
+                .any(|i| *i >= self.key.circuit.unpadded_wtns_len)
+            {
+                return Err(VerifyError::Malformed(
+                    "disclosed index is outside the witness",
+                ));
+            }
+            let (public_openings, residuals) = self.verify_deferring_linear_checks(
+                &dp.cnp,
+                Some(disclosure),
+                &[],
+                &mut FiatShamir,
+            )?;
+            residuals.check()?;
+            Ok((
+                public_openings,
+                disclosure.openings.iter().map(|(u, _)| *u).collect(),
+            ))
+        }
+
+        /// Runs every check of `verify` except the linear consistency and S matrix checks, whose residuals it returns instead.
+        /// If there is a `disclosure`, it is bound into the challenges and its openings are checked too
+        fn verify_deferring_linear_checks(
+            &self,
+            cnp: &CommitAndProof<T>,
+            disclosure: Option<&Disclosure<T>>,
+            context: &[u8],
+            challenger: &mut impl Challenger<T>,
+        ) -> Result<(PublicUOpenings<T>, LinearCheckResiduals<T>), VerifyError> {
+            let comm = &cnp.commitment;
+            let proof = &cnp.proof;
+            self.check_dimensions(cnp)?;
+            let (mut transcript, circuit) = self.key.committed_transcript(comm, context);
+            let repetitions = self.key.quicksilver_repetitions(proof.security);
+            let quicksilver_challenges =
+                challenger.quicksilver_challenges(&transcript, repetitions);
+            if quicksilver_challenges.len() != repetitions {
+                return Err(VerifyError::ChallengeLength);
+            }
+            quicksilver::check_dimensions(&circuit, &proof.zkp, repetitions)?;
+            if proof.public_openings.public_inputs.len() != circuit.public_inputs_indices.len()
+                || proof.public_openings.public_outputs.len()
+                    != circuit.public_outputs_indices.len()
+            {
+                return Err(VerifyError::PublicValuesLength);
+            }
+            append_quicksilver_proof(&mut transcript, &proof.zkp, &proof.public_openings);
+            if let Some(d) = disclosure {
+                append_disclosure(&mut transcript, &d.indices, &d.openings);
             }
+            self.key.check_security(&transcript, proof)?;
+            append_grinding_nonce(&mut transcript, proof.grinding_nonce);
+            let challenges = challenger.opening_challenges(
+                &transcript,
+                self.key.vole_length,
+                self.key.num_voles,
+            );
+            if !challenges.fit_vole(self.key.vole_length, self.key.num_voles) {
+                return Err(VerifyError::ChallengeLength);
+            }
+            let residuals = self.key.check_vole(
+                comm,
+                &proof.seed_openings,
+                &proof.s_matrix,
+                &proof.s_consistency_check,
+                &challenges,
+                &self.key.consistency_challenge(comm),
+            )?;
 
             // Verify the ZKP
-            let zk_verifier = quicksilver::Verifier::from_vith(
-                &proof.s_matrix,
-                challenges.vith_delta.clone(),
-                &comm.witness_comm,
-                self.circuit.clone(),
+            let _span = span!("quicksilver");
+            let vith = VithVerifier::<T>::new(challenges.vith_delta);
+            let zk_verifier = quicksilver::Verifier::from_vole(
+                vith.vole(&proof.s_matrix, &comm.witness_comm),
+                vith.delta(),
+                &circuit,
             );
-            let quicksilver_challenge =
-                calc_quicksilver_challenge(&comm.seed_comm, &comm.witness_comm);
-            zk_verifier.verify(&quicksilver_challenge, &proof.zkp)?;
+            zk_verifier.verify_repeated(&quicksilver_challenges, &proof.zkp)?;
             zk_verifier.verify_public(&proof.public_openings)?;
+            if let Some(d) = disclosure {
+                zk_verifier.verify_openings(&d.indices, &d.openings)?;
+            }
 
             // Return the witness (u) values from the public openings (v isn't useful as a public value except for verifying the proof)
+            Ok((proof.public_openings.u_values(), residuals))
+        }
+
+        fn check_dimensions(&self, cnp: &CommitAndProof<T>) -> Result<(), VerifyError> {
+            self.key.check_dimensions(
+                &cnp.commitment,
+                &cnp.proof.seed_openings,
+                &cnp.proof.s_matrix,
+                &cnp.proof.s_consistency_check,
+            )
+        }
+    }
+
+    impl<T: PF> DesignatedVerifier<T> {
+        pub fn new<R: RngCore + CryptoRng>(key: VerifyingKey<T>, rng: &mut R) -> Self {
+            let challenges = sample_challenges(rng, key.vole_length, key.num_voles);
+            let repetitions = key.quicksilver_repetitions(key.min_security);
+            DesignatedVerifier {
+                quicksilver_challenges: (0..repetitions)
+                    .map(|_| T::Ext::sample(&mut *rng))
+                    .collect(),
+                challenges,
+                key,
+                commitment: None,
+                answer: None,
+            }
+        }
+
+        /// Keeps the prover's commitment and returns the challenge to send back. Fails if a commitment was already received
+        pub fn receive_commitment(
+            &mut self,
+            commitment: ProverCommitment<T>,
+        ) -> Result<DesignatedChallenge<T>, VerifyError> {
+            if self.commitment.is_some() {
+                return Err(VerifyError::UnexpectedMessage("a second commitment"));
+            }
+            self.commitment = Some(commitment);
+            Ok(DesignatedChallenge {
+                vole_consistency: self.challenges.subspace_challenge.clone(),
+                quicksilver: self.quicksilver_challenges.clone(),
+            })
+        }
+
+        /// Keeps the prover's answer and returns the challenges to send back. They reveal ∆', after which the prover can forge answers,
+        /// so this fails unless the commitment has been received and no answer has
+        pub fn receive_answer(
+            &mut self,
+            answer: DesignatedAnswer<T>,
+        ) -> Result<&Challenges<T>, VerifyError> {
+            if self.commitment.is_none() {
+                return Err(VerifyError::UnexpectedMessage(
+                    "an answer before the commitment",
+                ));
+            }
+            if self.answer.is_some() {
+                return Err(VerifyError::UnexpectedMessage("a second answer"));
+            }
+            self.answer = Some(answer);
+            Ok(&self.challenges)
+        }
+
+        /// Verifies the transcript against this verifier's challenges and the messages it received, returning its public values
+        pub fn verify(&self, dvp: &DesignatedProof<T>) -> Result<PublicUOpenings<T>, VerifyError> {
+            let _span = span!("verify");
+            let (Some(received), Some(answer)) = (&self.commitment, &self.answer) else {
+                return Err(VerifyError::UnexpectedMessage(
+                    "a transcript before the answer",
+                ));
+            };
+            let comm = &dvp.commitment;
+            let proof = &dvp.proof;
+            let answered = ProverCommitment {
+                consistency_check: answer.consistency_check.clone(),
+                ..received.clone()
+            };
+            if *comm != answered
+                || proof.zkp != answer.zkp
+                || proof.public_openings != answer.public_openings
+            {
+                return Err(VerifyError::DesignatedTranscript);
+            }
+            self.key.check_dimensions(
+                comm,
+                &proof.seed_openings,
+                &proof.s_matrix,
+                &proof.s_consistency_check,
+            )?;
+            let residuals = self.key.check_vole(
+                comm,
+                &proof.seed_openings,
+                &proof.s_matrix,
+                &proof.s_consistency_check,
+                &self.challenges,
+                &self.challenges.subspace_challenge,
+            )?;
+
+            let (_, circuit) = self.key.committed_transcript(comm, &[]);
+            let vith = VithVerifier::<T>::new(self.challenges.vith_delta);
+            let zk_verifier = quicksilver::Verifier::from_vole(
+                vith.vole(&proof.s_matrix, &comm.witness_comm),
+                vith.delta(),
+                &circuit,
+            );
+            zk_verifier.verify_repeated(&self.quicksilver_challenges, &proof.zkp)?;
+            zk_verifier.verify_public(&proof.public_openings)?;
+            residuals.check()?;
             Ok(proof.public_openings.u_values())
         }
     }
 
-    /// Values of the witness that the prover opens
-    #[derive(Clone, Debug, Serialize, Deserialize)]
-    pub struct PublicOpenings<T: PF> {
-        pub public_inputs: Vec<(T, T)>,
-        pub public_outputs: Vec<(T, T)>,
+    impl<T: PF> VerificationReport<T> {
+        /// Whether every check passed
+        pub fn passed(&self) -> bool {
+            self.checks.iter().all(|c| c.result.is_ok())
+        }
+
+        /// The first check that failed
+        pub fn first_failure(&self) -> Option<&CheckOutcome> {
+            self.checks.iter().find(|c| c.result.is_err())
+        }
+
+        /// Records a check that started at `start`, returning whether it passed
+        fn record(
+            &mut self,
+            check: Check,
+            start: Instant,
+            result: Result<(), VerifyError>,
+        ) -> bool {
+            let passed = result.is_ok();
+            self.checks.push(CheckOutcome {
+                check,
+                result,
+                elapsed: start.elapsed(),
+            });
+            passed
+        }
+    }
+
+    /// Differences between the two sides of the subspace VOLE consistency check and S matrix check. A proof passes them iff both are zero
+    struct LinearCheckResiduals<T: PF> {
+        consistency: FVec<T>,
+        s_matrix: FVec<T>,
+    }
+
+    impl<T: PF> LinearCheckResiduals<T> {
+        fn check(&self) -> Result<(), VerifyError> {
+            if !self.consistency.is_zero() {
+                return Err(VerifyError::ConsistencyCheck);
+            }
+            if !self.s_matrix.is_zero() {
+                return Err(VerifyError::SMatrix);
+            }
+            Ok(())
+        }
     }
 
     #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -510,6 +2976,21 @@ pub mod actors {
         pub public_inputs: Vec<T>,
         pub public_outputs: Vec<T>,
     }
+    /// Only the values are compared in constant time; the numbers of inputs and outputs are public anyway
+    impl<T: PF> ConstantTimeEq for PublicUOpenings<T> {
+        fn ct_eq(&self, other: &Self) -> Choice {
+            let ct_eq_vec = |a: &Vec<T>, b: &Vec<T>| {
+                if a.len() != b.len() {
+                    return Choice::from(0);
+                }
+                a.iter()
+                    .zip(b.iter())
+                    .fold(Choice::from(1), |acc, (x, y)| acc & x.ct_eq(y))
+            };
+            ct_eq_vec(&self.public_inputs, &other.public_inputs)
+                & ct_eq_vec(&self.public_outputs, &other.public_outputs)
+        }
+    }
     impl<T: PF> PublicOpenings<T> {
         pub fn u_values(&self) -> PublicUOpenings<T> {
             PublicUOpenings {
@@ -517,20 +2998,104 @@ pub mod actors {
                 public_outputs: self.public_outputs.iter().map(|(x, _)| x.clone()).collect(),
             }
         }
+
+        /// The u values of `circuit`'s named public signals, by name. See `PublicUOpenings::named`
+        pub fn named(&self, circuit: &R1CSWithMetadata<T>) -> BTreeMap<String, T> {
+            self.u_values().named(circuit)
+        }
+    }
+    impl<T: PF> PublicUOpenings<T> {
+        /// Splits the public values of a proof for `composite` into those of each of its circuits
+        pub fn split(&self, composite: &CompositeCircuit<T>) -> Vec<Self> {
+            self.split_by(composite.circuits.iter().map(|c| {
+                (
+                    c.public_inputs_indices.len(),
+                    c.public_outputs_indices.len(),
+                )
+            }))
+        }
+
+        /// Splits the values into consecutive groups of the given numbers of inputs and outputs
+        fn split_by(&self, lens: impl Iterator<Item = (usize, usize)>) -> Vec<Self> {
+            let mut inputs = self.public_inputs.iter().copied();
+            let mut outputs = self.public_outputs.iter().copied();
+            lens.map(|(num_inputs, num_outputs)| Self {
+                public_inputs: inputs.by_ref().take(num_inputs).collect(),
+                public_outputs: outputs.by_ref().take(num_outputs).collect(),
+            })
+            .collect()
+        }
+
+        /// The values of `circuit`'s named public inputs and outputs, by name. Unnamed ones are left out
+        pub fn named(&self, circuit: &R1CSWithMetadata<T>) -> BTreeMap<String, T> {
+            let public = circuit
+                .public_inputs_indices
+                .iter()
+                .zip(self.public_inputs.iter())
+                .chain(
+                    circuit
+                        .public_outputs_indices
+                        .iter()
+                        .zip(self.public_outputs.iter()),
+                )
+                .collect::<BTreeMap<_, _>>();
+            circuit
+                .signal_names
+                .iter()
+                .filter_map(|(name, index)| Some((name.clone(), **public.get(index)?)))
+                .collect()
+        }
+
+        /// The value of the public signal `name` of `circuit`, if there is one
+        pub fn get(&self, circuit: &R1CSWithMetadata<T>, name: &str) -> Option<T> {
+            let index = circuit.signal_names.get(name)?;
+            let position = |indices: &Vec<usize>| indices.iter().position(|i| i == index);
+            match position(&circuit.public_inputs_indices) {
+                Some(p) => self.public_inputs.get(p).copied(),
+                None => self
+                    .public_outputs
+                    .get(position(&circuit.public_outputs_indices)?)
+                    .copied(),
+            }
+        }
     }
 }
 
 pub mod test_helpers {
-    use anyhow::Error;
-
-    use crate::{zkp::R1CSWithMetadata, FVec, Fr};
+    use crate::{error::VerifyError, zkp::R1CSWithMetadata, FVec, Fr};
+    #[cfg(test)]
+    use ff::PrimeField;
 
+    #[cfg(test)]
+    use super::actors::CommitAndProof;
     use super::actors::{Prover, PublicUOpenings, Verifier};
 
+    /// A witness satisfying `zkp::test::TEST_CIRCUIT`
+    #[cfg(test)]
+    pub fn tiny_witness() -> FVec<Fr> {
+        FVec([5, 2, 28, 280].into_iter().map(Fr::from_u128).collect())
+    }
+
+    /// `zkp::test::TEST_CIRCUIT` and `tiny_witness`
+    #[cfg(test)]
+    pub fn tiny_circuit_and_witness() -> (R1CSWithMetadata<Fr>, FVec<Fr>) {
+        (crate::zkp::test::TEST_CIRCUIT.clone(), tiny_witness())
+    }
+
+    /// `tiny_circuit_and_witness`'s circuit and an honest proof for it with the default parameters
+    #[cfg(test)]
+    pub fn tiny_proof() -> (R1CSWithMetadata<Fr>, CommitAndProof<Fr>) {
+        let (circuit, witness) = tiny_circuit_and_witness();
+        let cnp = Prover::from_witness_and_circuit_unpadded(witness, circuit.clone())
+            .commit_and_prove()
+            .unwrap();
+        (circuit, cnp)
+    }
+
     pub fn e2e_test(
         witness: FVec<Fr>,
         circuit: R1CSWithMetadata<Fr>,
-    ) -> Result<PublicUOpenings<Fr>, Error> {
+    ) -> Result<PublicUOpenings<Fr>, VerifyError> {
         let mut prover =
             Prover::from_witness_and_circuit_unpadded(witness.clone(), circuit.clone());
         // let vole_comm = prover.mkvole().unwrap();
@@ -544,22 +3109,41 @@ pub mod test_helpers {
 mod test {
     use crate::{
         actors::{
-            actors::{CommitAndProof, Prover, Verifier},
-            test_helpers::e2e_test,
+            actors::{
+                Check, CommitAndProof, CommittedWitness, DesignatedVerifier, IncrementalProver,
+                Phase, PreparedVOLE, Prover, ProverBuilder, ProvingKey, PublicUOpenings, Verifier,
+                VerifyingKey, VolePool,
+            },
+            test_helpers::{e2e_test, tiny_circuit_and_witness, tiny_proof, tiny_witness},
+        },
+        challenges::{
+            sample_challenges, transcript::Transcript, Challenger, Challenges, SecurityLevel,
+            MAX_GRINDING_BITS,
         },
-        zkp, FVec, Fr,
+        error::{ProofError, VerifyError},
+        fields::{ext::QM31, m31::M31},
+        gadgets::CircuitBuilder,
+        subspacevole::{CodeParams, CodePreset, LinearCode, RAAACode},
+        zkp::{
+            self,
+            ccs::{ConstraintSystem, PlonkGate, CCS},
+            lookup::Lookup,
+            memory::Memory,
+            CompositeCircuit, FullR1CS, R1CSWithMetadata, SparseR1CS, R1CS,
+        },
+        DataSize, FMatrix, FVec, Fr, SparseFMatrix, SparseVec,
     };
     use ff::{Field, PrimeField};
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha12Rng;
+    use std::{
+        collections::BTreeMap,
+        sync::{Arc, Mutex},
+    };
 
     #[test]
     fn prover_verifier_full_integration_tiny_circuit() {
-        let circuit = zkp::test::TEST_R1CS_WITH_METADA.clone();
-        let correct_witness = FVec::<Fr>(
-            vec![5, 2, 28, 280]
-                .iter()
-                .map(|x| Fr::from_u128(*x))
-                .collect(),
-        );
+        let (circuit, correct_witness) = tiny_circuit_and_witness();
         let len = correct_witness.0.len();
 
         assert!(e2e_test(correct_witness.clone(), circuit.clone()).is_ok());
@@ -581,13 +3165,7 @@ mod test {
 
     #[test]
     fn public_values() {
-        let circuit = zkp::test::TEST_R1CS_WITH_METADA.clone();
-        let witness = FVec::<Fr>(
-            vec![5, 2, 28, 280]
-                .iter()
-                .map(|x| Fr::from_u128(*x))
-                .collect(),
-        );
+        let (circuit, witness) = tiny_circuit_and_witness();
 
         let mut prover =
             Prover::from_witness_and_circuit_unpadded(witness.clone(), circuit.clone());
@@ -647,4 +3225,1206 @@ mod test {
                 .is_err());
         }
     }
+
+    #[test]
+    fn errors_identify_the_failure() {
+        let (circuit, witness) = tiny_circuit_and_witness();
+        let mut prover = Prover::from_witness_and_circuit_unpadded(witness, circuit.clone());
+        assert!(matches!(prover.prove(), Err(ProofError::VoleNotCompleted)));
+
+        let cnp = prover.commit_and_prove().unwrap();
+        let verifier = Verifier::from_circuit(circuit);
+
+        let mut truncated = cnp.clone();
+        truncated.proof.seed_openings.seed_opens.pop();
+        assert!(matches!(
+            verifier.verify(&truncated),
+            Err(VerifyError::Malformed(_))
+        ));
+
+        let mut other_params = cnp.clone();
+        other_params.commitment.params.vole_length += 2;
+        assert!(matches!(
+            verifier.verify(&other_params),
+            Err(VerifyError::ParamsMismatch { found, .. }) if found == other_params.commitment.params
+        ));
+
+        let mut extra_coefficient = cnp.clone();
+        extra_coefficient.proof.zkp.branch_proof.push(Fr::ONE);
+        assert!(matches!(
+            verifier.verify(&extra_coefficient),
+            Err(VerifyError::Malformed(_))
+        ));
+
+        let mut extra_output = cnp.clone();
+        extra_output
+            .proof
+            .public_openings
+            .public_outputs
+            .push((Fr::ONE, Fr::ONE));
+        assert!(matches!(
+            verifier.verify(&extra_output),
+            Err(VerifyError::PublicValuesLength)
+        ));
+
+        let mut wrong_seed_comm = cnp.clone();
+        wrong_seed_comm.commitment.seed_comm[0] ^= 1;
+        assert!(matches!(
+            verifier.verify(&wrong_seed_comm),
+            Err(VerifyError::SeedCommitmentMismatch)
+        ));
+
+        let mut wrong_s = cnp.clone();
+        wrong_s.proof.s_consistency_check.0[0] += Fr::ONE;
+        assert!(matches!(
+            verifier.verify(&wrong_s),
+            Err(VerifyError::SMatrix)
+        ));
+    }
+
+    #[test]
+    fn verifying_key_verifies_many_proofs() {
+        let (circuit, witness) = tiny_circuit_and_witness();
+        let key = VerifyingKey::from_circuit(circuit.clone());
+        assert_eq!(key.circuit_digest, circuit.circuit_id());
+        assert!(matches!(key.circuit.r1cs, R1CS::Sparse(_)));
+
+        let verifier = Verifier::from_verifying_key(key);
+        for _ in 0..3 {
+            let mut prover =
+                Prover::from_witness_and_circuit_unpadded(witness.clone(), circuit.clone());
+            let cnp = prover.commit_and_prove().unwrap();
+            assert!(verifier.verify(&cnp).is_ok());
+        }
+    }
+
+    #[test]
+    fn batch_verification() {
+        let (circuit, witness) = tiny_circuit_and_witness();
+        let mut cnps = (0..4)
+            .map(|_| {
+                Prover::from_witness_and_circuit_unpadded(witness.clone(), circuit.clone())
+                    .commit_and_prove()
+                    .unwrap()
+            })
+            .collect::<Vec<_>>();
+        let verifier = Verifier::from_circuit(circuit);
+        assert!(verifier.verify_batch(&[]).is_empty());
+        assert!(verifier.verify_batch(&cnps).iter().all(|r| r.is_ok()));
+
+        cnps[1].proof.s_consistency_check.0[0] += Fr::ONE;
+        cnps[2].commitment.consistency_check.1 .0[0] += Fr::ONE;
+        cnps[3].proof.seed_openings.seed_proofs.pop();
+        let results = verifier.verify_batch(&cnps);
+        assert!(results[0].is_ok());
+        assert!(matches!(results[1], Err(VerifyError::SMatrix)));
+        assert!(matches!(results[2], Err(VerifyError::ConsistencyCheck)));
+        assert!(matches!(results[3], Err(VerifyError::Malformed(_))));
+    }
+
+    #[test]
+    fn prover_builder() {
+        let (circuit, witness) = tiny_circuit_and_witness();
+        let verifier = Verifier::from_circuit(circuit.clone());
+
+        let mut prover = Prover::builder(witness.clone(), circuit.clone())
+            .build()
+            .unwrap();
+        assert!(verifier.verify(&prover.commit_and_prove().unwrap()).is_ok());
+
+        let mut prover = Prover::builder(witness.clone(), circuit.clone())
+            .parallelism(4)
+            .build()
+            .unwrap();
+        assert!(verifier.verify(&prover.commit_and_prove().unwrap()).is_ok());
+
+        let seeded = || {
+            let mut prover = Prover::builder(witness.clone(), circuit.clone())
+                .rng(ChaCha12Rng::from_seed([7; 32]))
+                .parallelism(3)
+                .build()
+                .unwrap();
+            prover.commit_and_prove().unwrap()
+        };
+        let (a, b) = (seeded(), seeded());
+        assert_eq!(a.commitment.seed_comm, b.commitment.seed_comm);
+        assert!(verifier.verify(&a).is_ok());
+
+        assert!(matches!(
+            Prover::builder(witness.clone(), circuit.clone())
+                .security_level(256)
+                .build(),
+            Err(ProofError::InsufficientSecurity { .. })
+        ));
+        assert!(matches!(
+            Prover::builder(witness.clone(), circuit.clone())
+                .parallelism(0)
+                .build(),
+            Err(ProofError::InvalidParallelism)
+        ));
+        let mut short_witness = witness;
+        short_witness.0.pop();
+        assert!(matches!(
+            Prover::builder(short_witness, circuit).build(),
+            Err(ProofError::WitnessLength { .. })
+        ));
+    }
+
+    #[test]
+    fn seeded_proofs_are_reproducible() {
+        let (circuit, witness) = tiny_circuit_and_witness();
+        let prove = |seed: [u8; 32]| {
+            Prover::builder(witness.clone(), circuit.clone())
+                .seed(seed)
+                .build()
+                .unwrap()
+                .commit_and_prove()
+                .unwrap()
+        };
+        let a = prove([1; 32]);
+        assert_eq!(a.to_bytes().unwrap(), prove([1; 32]).to_bytes().unwrap());
+        assert_ne!(a.to_bytes().unwrap(), prove([2; 32]).to_bytes().unwrap());
+        assert!(Verifier::from_circuit(circuit).verify(&a).is_ok());
+    }
+
+    #[test]
+    fn proofs_are_bound_to_their_statement() {
+        let (circuit, cnp) = tiny_proof();
+        assert!(Verifier::from_circuit(circuit.clone()).verify(&cnp).is_ok());
+
+        // Same shape, different statement: the challenges differ, so the opened seeds are the wrong ones
+        let mut other = circuit;
+        other.public_inputs_indices.reverse();
+        assert!(matches!(
+            Verifier::from_circuit(other).verify(&cnp),
+            Err(VerifyError::SeedCommitmentMismatch)
+        ));
+    }
+
+    #[test]
+    fn verify_with_expected_public_values() {
+        let (circuit, cnp) = tiny_proof();
+        let verifier = Verifier::from_circuit(circuit);
+        let mut expected = PublicUOpenings {
+            public_inputs: vec![Fr::from_u128(5), Fr::from_u128(28)],
+            public_outputs: vec![Fr::from_u128(280)],
+        };
+        assert!(verifier.verify_with_public(&cnp, &expected).is_ok());
+
+        expected.public_outputs[0] += Fr::ONE;
+        assert!(matches!(
+            verifier.verify_with_public(&cnp, &expected),
+            Err(VerifyError::PublicValuesMismatch)
+        ));
+        expected.public_outputs.clear();
+        assert!(matches!(
+            verifier.verify_with_public(&cnp, &expected),
+            Err(VerifyError::PublicValuesMismatch)
+        ));
+    }
+
+    #[test]
+    fn progress_is_reported() {
+        let (circuit, witness) = tiny_circuit_and_witness();
+        let phases = [
+            Phase::VoleGeneration,
+            Phase::Correction,
+            Phase::ConsistencyCheck,
+            Phase::Quicksilver,
+            Phase::SMatrix,
+        ];
+        for streaming in [false, true] {
+            let reports = Arc::new(Mutex::new(Vec::<(Phase, f64)>::new()));
+            let sink = reports.clone();
+            let mut prover = Prover::builder(witness.clone(), circuit.clone())
+                .parallelism(2)
+                .progress(move |phase, fraction| sink.lock().unwrap().push((phase, fraction)))
+                .build()
+                .unwrap();
+            if streaming {
+                prover.mkvole_streaming(3).unwrap();
+            } else {
+                prover.mkvole().unwrap();
+            }
+            prover.prove().unwrap();
+
+            let reports = reports.lock().unwrap();
+            for phase in phases {
+                let fractions = reports
+                    .iter()
+                    .filter(|(p, _)| *p == phase)
+                    .map(|(_, f)| *f)
+                    .collect::<Vec<f64>>();
+                assert_eq!(fractions.first(), Some(&0.0));
+                assert_eq!(fractions.last(), Some(&1.0));
+                assert!(fractions.windows(2).all(|w| w[0] <= w[1]));
+            }
+        }
+    }
+
+    #[test]
+    fn prove_many_witnesses_at_once() {
+        let circuit = zkp::test::TEST_CIRCUIT.clone();
+        let witnesses = [[5, 2, 28, 280], [1, 1, 4, 8], [0, 3, 18, 0]]
+            .iter()
+            .map(|w| FVec::<Fr>(w.iter().map(|x| Fr::from_u128(*x)).collect()))
+            .collect::<Vec<_>>();
+        let verifier = Verifier::from_circuit(circuit.clone());
+
+        let multi = Prover::prove_many(witnesses.clone(), circuit.clone()).unwrap();
+        let public_values = verifier.verify_many(&multi).unwrap();
+        assert_eq!(public_values.len(), 3);
+        for (w, p) in witnesses.iter().zip(public_values.iter()) {
+            assert_eq!(p.public_inputs, vec![w.0[0], w.0[2]]);
+            assert_eq!(p.public_outputs, vec![w.0[3]]);
+        }
+
+        // Much smaller than one proof per witness
+        let single =
+            Prover::from_witness_and_circuit_unpadded(witnesses[0].clone(), circuit.clone())
+                .commit_and_prove()
+                .unwrap();
+        assert!(multi.size_in_bytes() < 2 * single.size_in_bytes());
+
+        let mut wrong_count = multi.clone();
+        wrong_count.instances = 1 << 40;
+        assert!(matches!(
+            verifier.verify_many(&wrong_count),
+            Err(VerifyError::Malformed(_))
+        ));
+
+        let mut wrong_witnesses = witnesses.clone();
+        wrong_witnesses[1].0[3] += Fr::ONE;
+        let wrong = Prover::prove_many(wrong_witnesses, circuit.clone()).unwrap();
+        assert!(verifier.verify_many(&wrong).is_err());
+
+        assert!(matches!(
+            Prover::prove_many(vec![], circuit.clone()),
+            Err(ProofError::NoWitnesses)
+        ));
+        assert!(matches!(
+            Prover::prove_many(vec![FVec(vec![Fr::ONE])], circuit.clone()),
+            Err(ProofError::WitnessLength { .. })
+        ));
+
+        // The repeated circuit is verified with the key's code and minimum security level
+        let params = CodeParams::from_seed(CodePreset::RAAADefault, b"shared code");
+        let configured = ProverBuilder::many(witnesses.clone(), circuit.clone())
+            .unwrap()
+            .code_params(params)
+            .prove_many()
+            .unwrap();
+        let key = VerifyingKey::with_code_params(circuit.clone(), params);
+        assert!(Verifier::from_verifying_key(key)
+            .verify_many(&configured)
+            .is_ok());
+        assert!(verifier.verify_many(&configured).is_err());
+        let strict = VerifyingKey::from_circuit(circuit).require_security(SecurityLevel(129));
+        assert!(matches!(
+            Verifier::from_verifying_key(strict).verify_many(&multi),
+            Err(VerifyError::InsufficientSecurity { .. })
+        ));
+    }
+
+    #[test]
+    fn security_level_is_enforced() {
+        let (circuit, witness) = tiny_circuit_and_witness();
+        let level = SecurityLevel(140);
+        let verifier = Verifier::from_verifying_key(
+            VerifyingKey::from_circuit(circuit.clone()).require_security(level),
+        );
+
+        let mut prover = Prover::builder(witness.clone(), circuit.clone())
+            .security_level(level.0)
+            .build()
+            .unwrap();
+        let cnp = prover.commit_and_prove().unwrap();
+        assert_eq!(cnp.proof.security, level);
+        assert!(verifier.verify(&cnp).is_ok());
+        assert!(Verifier::from_circuit(circuit.clone()).verify(&cnp).is_ok());
+
+        let mut prover = Prover::from_witness_and_circuit_unpadded(witness, circuit);
+        let default_level = prover.commit_and_prove().unwrap();
+        assert!(matches!(
+            verifier.verify(&default_level),
+            Err(VerifyError::InsufficientSecurity {
+                required: 140,
+                found: 128
+            })
+        ));
+        // Claiming a higher level than was ground for
+        let mut overclaimed = cnp.clone();
+        overclaimed.proof.security = SecurityLevel(160);
+        assert!(matches!(
+            verifier.verify(&overclaimed),
+            Err(VerifyError::Grinding)
+        ));
+        // Or a higher level than any proof over the field with the code can have
+        let mut overclaimed = cnp.clone();
+        overclaimed.proof.security = SecurityLevel(161);
+        assert!(matches!(
+            verifier.verify(&overclaimed),
+            Err(VerifyError::UnreachableSecurity {
+                found: 161,
+                max: 160
+            })
+        ));
+        // Or more grinding than was done
+        let mut overclaimed = cnp;
+        overclaimed.proof.grinding_bits += 20;
+        assert!(matches!(
+            verifier.verify(&overclaimed),
+            Err(VerifyError::Grinding)
+        ));
+    }
+
+    #[test]
+    fn small_fields_reach_default_security() {
+        let mut builder = CircuitBuilder::<M31>::new();
+        let x = builder.alloc(M31::from(7));
+        let y = builder.mul(x, x);
+        builder.public_output(y);
+        let (circuit, witness) = builder.build();
+        // ∆' and the challenges are from QM31, so M31 proofs reach the default level of 128 bits
+        let max = SecurityLevel::max_for::<M31>(CodePreset::RAAADefault);
+        assert!(max > SecurityLevel::default());
+
+        let cnp = Prover::builder(witness, circuit.clone())
+            .build()
+            .unwrap()
+            .commit_and_prove()
+            .unwrap();
+        assert_eq!(cnp.proof.security, SecurityLevel::default());
+        let verifier = Verifier::from_circuit(circuit);
+        assert_eq!(
+            verifier.verify(&cnp).unwrap().public_outputs,
+            vec![M31::from(49)]
+        );
+
+        let mut tampered = cnp.clone();
+        tampered.proof.zkp.mul_proof.0 += QM31::ONE;
+        assert!(verifier.verify(&tampered).is_err());
+        let mut overclaimed = cnp;
+        overclaimed.proof.security = SecurityLevel(max.0 + 1);
+        assert!(matches!(
+            verifier.verify(&overclaimed),
+            Err(VerifyError::UnreachableSecurity { max: m, .. }) if m == max.0
+        ));
+    }
+
+    #[test]
+    fn proof_bound_to_context() {
+        let (circuit, witness) = tiny_circuit_and_witness();
+        let cnp = Prover::builder(witness.clone(), circuit.clone())
+            .context(b"session 1")
+            .build()
+            .unwrap()
+            .commit_and_prove()
+            .unwrap();
+        let verifier = Verifier::from_circuit(circuit.clone());
+        assert!(verifier.verify_with_context(&cnp, b"session 1").is_ok());
+        assert!(verifier.verify_with_context(&cnp, b"session 2").is_err());
+        assert!(verifier.verify(&cnp).is_err());
+
+        // Without a context, proofs verify with the empty one
+        let mut prover = Prover::from_witness_and_circuit_unpadded(witness, circuit);
+        let cnp = prover.commit_and_prove().unwrap();
+        assert!(verifier.verify_with_context(&cnp, &[]).is_ok());
+        assert!(verifier.verify_with_context(&cnp, b"session 1").is_err());
+    }
+
+    #[test]
+    fn external_challenger() {
+        // Challenges from an outside source, e.g. a randomness beacon, which ignores the transcript
+        struct Beacon(ChaCha12Rng, usize);
+        impl Challenger<Fr> for Beacon {
+            fn quicksilver_challenges(&mut self, _: &Transcript, repetitions: usize) -> Vec<Fr> {
+                (0..repetitions).map(|_| Fr::random(&mut self.0)).collect()
+            }
+            fn opening_challenges(
+                &mut self,
+                _: &Transcript,
+                vole_length: usize,
+                num_voles: usize,
+            ) -> Challenges<Fr> {
+                sample_challenges(&mut self.0, vole_length, num_voles + self.1)
+            }
+        }
+        let beacon = |seed: u64| Beacon(ChaCha12Rng::seed_from_u64(seed), 0);
+
+        let (circuit, witness) = tiny_circuit_and_witness();
+        let mut prover = Prover::from_witness_and_circuit_unpadded(witness, circuit.clone());
+        let commitment = prover.mkvole().unwrap();
+        let proof = prover.prove_with_challenger(&mut beacon(1)).unwrap();
+        let cnp = CommitAndProof { commitment, proof };
+        let verifier = Verifier::from_circuit(circuit);
+        assert!(verifier
+            .verify_with_challenger(&cnp, &[], &mut beacon(1))
+            .is_ok());
+        assert!(verifier
+            .verify_with_challenger(&cnp, &[], &mut beacon(2))
+            .is_err());
+        assert!(verifier.verify(&cnp).is_err());
+
+        // Challenges that don't fit the VOLE are rejected rather than indexed out of bounds
+        assert!(matches!(
+            prover.prove_with_challenger(&mut Beacon(ChaCha12Rng::seed_from_u64(1), 1)),
+            Err(ProofError::ChallengeLength)
+        ));
+        assert!(matches!(
+            verifier.verify_with_challenger(
+                &cnp,
+                &[],
+                &mut Beacon(ChaCha12Rng::seed_from_u64(1), 1)
+            ),
+            Err(VerifyError::ChallengeLength)
+        ));
+    }
+
+    #[test]
+    fn custom_code_params() {
+        let (circuit, witness) = tiny_circuit_and_witness();
+        let params = CodeParams::from_seed(CodePreset::RAAADefault, b"shared code");
+        let cnp = Prover::builder(witness, circuit.clone())
+            .code_params(params)
+            .build()
+            .unwrap()
+            .commit_and_prove()
+            .unwrap();
+        let key = VerifyingKey::with_code_params(circuit.clone(), params);
+        assert_eq!(key.code, params.code());
+        assert!(Verifier::from_verifying_key(key).verify(&cnp).is_ok());
+        // Verifying with any other code fails
+        assert!(Verifier::from_circuit(circuit.clone())
+            .verify(&cnp)
+            .is_err());
+        let other = CodeParams::from_seed(CodePreset::RAAADefault, b"other code");
+        assert!(
+            Verifier::from_verifying_key(VerifyingKey::with_code_params(circuit, other))
+                .verify(&cnp)
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn proving_key_reuse() {
+        let circuit = zkp::test::TEST_CIRCUIT.clone();
+        let witness = FVec::<Fr>([5, 2, 28, 280].iter().map(|x| Fr::from_u128(*x)).collect());
+        let key = ProvingKey::from_circuit(circuit.clone());
+        assert_eq!(
+            key.statement_digest,
+            VerifyingKey::from_circuit(circuit.clone()).statement_digest
+        );
+        let verifier = Verifier::from_circuit(circuit.clone());
+        for _ in 0..2 {
+            let cnp = Prover::from_proving_key(witness.clone(), &key)
+                .unwrap()
+                .commit_and_prove()
+                .unwrap();
+            assert!(verifier.verify(&cnp).is_ok());
+        }
+
+        let other = CodeParams::from_seed(CodePreset::RAAADefault, b"other code");
+        assert!(matches!(
+            ProverBuilder::from_proving_key(witness.clone(), &key)
+                .code_params(other)
+                .build(),
+            Err(ProofError::ProvingKeyCode)
+        ));
+        assert!(matches!(
+            Prover::from_proving_key(FVec::<Fr>(witness.0[..3].to_vec()), &key),
+            Err(ProofError::WitnessLength { .. })
+        ));
+    }
+
+    #[test]
+    fn extra_grinding() {
+        let (circuit, witness) = tiny_circuit_and_witness();
+        let mut prover = Prover::builder(witness.clone(), circuit.clone())
+            .grinding_bits(6)
+            .build()
+            .unwrap();
+        let cnp = prover.commit_and_prove().unwrap();
+        assert_eq!(cnp.proof.security, SecurityLevel::default());
+        assert_eq!(cnp.proof.grinding_bits, 6);
+        assert!(Verifier::from_circuit(circuit.clone()).verify(&cnp).is_ok());
+        // The security level's grinding is still done when it is more
+        let cnp = Prover::builder(witness.clone(), circuit.clone())
+            .security_level(140)
+            .grinding_bits(6)
+            .build()
+            .unwrap()
+            .commit_and_prove()
+            .unwrap();
+        assert_eq!(cnp.proof.grinding_bits, 12);
+        assert!(matches!(
+            Prover::builder(witness, circuit)
+                .grinding_bits(MAX_GRINDING_BITS + 1)
+                .build(),
+            Err(ProofError::TooMuchGrinding { .. })
+        ));
+    }
+
+    #[test]
+    fn verification_report() {
+        let (circuit, cnp) = tiny_proof();
+        let verifier = Verifier::from_circuit(circuit);
+
+        let report = verifier.verify_report(&cnp);
+        assert!(report.passed());
+        assert_eq!(
+            report.checks.iter().map(|c| c.check).collect::<Vec<_>>(),
+            vec![
+                Check::Dimensions,
+                Check::Security,
+                Check::SeedCommitment,
+                Check::ConsistencyCheck,
+                Check::SMatrix,
+                Check::Quicksilver,
+                Check::PublicOpenings
+            ]
+        );
+        assert_eq!(
+            report.public_values.unwrap().public_outputs,
+            verifier.verify(&cnp).unwrap().public_outputs
+        );
+
+        // Every check still runs after one fails
+        let mut bad_s = cnp.clone();
+        bad_s.proof.s_consistency_check.0[0] += Fr::ONE;
+        let report = verifier.verify_report(&bad_s);
+        assert!(!report.passed() && report.public_values.is_none());
+        assert_eq!(report.first_failure().unwrap().check, Check::SMatrix);
+        assert_eq!(report.checks.len(), 7);
+
+        let mut malformed = cnp;
+        malformed.proof.s_matrix.0.pop();
+        let report = verifier.verify_report(&malformed);
+        assert_eq!(report.checks.len(), 1);
+        assert!(matches!(
+            report.first_failure().unwrap().result,
+            Err(VerifyError::Malformed(_))
+        ));
+    }
+
+    #[test]
+    fn named_signals() {
+        let mut circuit = zkp::test::TEST_CIRCUIT.clone();
+        circuit.signal_names = [("main.x", 0), ("main.y", 1), ("main.z", 2), ("main.out", 3)]
+            .iter()
+            .map(|(name, i)| (name.to_string(), *i))
+            .collect();
+        let witness = tiny_witness();
+        let public_values = e2e_test(witness.clone(), circuit.clone()).unwrap();
+        // main.y is private
+        let expected = [("main.x", 5), ("main.z", 28), ("main.out", 280)]
+            .iter()
+            .map(|(name, x)| (name.to_string(), Fr::from_u128(*x)))
+            .collect::<BTreeMap<_, _>>();
+        assert_eq!(public_values.named(&circuit), expected);
+        assert_eq!(
+            public_values.get(&circuit, "main.out"),
+            Some(Fr::from_u128(280))
+        );
+        assert_eq!(public_values.get(&circuit, "main.y"), None);
+
+        let indices = circuit.signal_indices(&["main.y"]).unwrap();
+        let verifier = Verifier::from_circuit(circuit.clone());
+        let dp = Prover::from_witness_and_circuit_unpadded(witness, circuit.clone())
+            .commit_and_prove_disclosing(&indices)
+            .unwrap();
+        assert_eq!(
+            verifier.verify_disclosing(&dp).unwrap().1,
+            vec![Fr::from_u128(2)]
+        );
+        assert!(matches!(
+            circuit.signal_indices(&["main.w"]),
+            Err(ProofError::UnknownSignal(_))
+        ));
+    }
+
+    #[test]
+    fn disclose_extra_witness_indices() {
+        let (circuit, witness) = tiny_circuit_and_witness();
+        let verifier = Verifier::from_circuit(circuit.clone());
+        let mut prover = Prover::from_witness_and_circuit_unpadded(witness, circuit);
+        let dp = prover.commit_and_prove_disclosing(&[0, 2]).unwrap();
+        let (public_values, disclosed) = verifier.verify_disclosing(&dp).unwrap();
+        assert_eq!(public_values.public_outputs, vec![Fr::from_u128(280)]);
+        assert_eq!(disclosed, vec![Fr::from_u128(5), Fr::from_u128(28)]);
+
+        // The disclosure is bound to the proof, so it can be neither stripped nor changed
+        assert!(verifier.verify(&dp.cnp).is_err());
+        let mut changed_index = dp.clone();
+        changed_index.disclosure.indices[1] = 1;
+        assert!(verifier.verify_disclosing(&changed_index).is_err());
+        let mut changed_value = dp.clone();
+        changed_value.disclosure.openings[0].0 += Fr::ONE;
+        assert!(verifier.verify_disclosing(&changed_value).is_err());
+        let mut out_of_range = dp;
+        out_of_range.disclosure.indices[1] = 4;
+        assert!(matches!(
+            verifier.verify_disclosing(&out_of_range),
+            Err(VerifyError::Malformed(_))
+        ));
+
+        assert!(matches!(
+            prover.prove_disclosing(&[4]),
+            Err(ProofError::DisclosureIndex {
+                index: 4,
+                witness_len: 4
+            })
+        ));
+    }
+
+    #[test]
+    fn designated_verifier_proof() {
+        let (circuit, witness) = tiny_circuit_and_witness();
+        let mut rng = ChaCha12Rng::from_seed([7u8; 32]);
+        let mut verifier =
+            DesignatedVerifier::new(VerifyingKey::from_circuit(circuit.clone()), &mut rng);
+        let mut prover = Prover::from_witness_and_circuit_unpadded(witness, circuit.clone());
+
+        let commitment = prover.mkvole_designated().unwrap();
+        assert!(commitment.consistency_check.0 .0.is_empty());
+        let answer = prover
+            .answer_designated(&verifier.receive_commitment(commitment.clone()).unwrap())
+            .unwrap();
+        assert!(matches!(
+            verifier.receive_commitment(commitment.clone()),
+            Err(VerifyError::UnexpectedMessage(_))
+        ));
+        let challenges = verifier.receive_answer(answer.clone()).unwrap().clone();
+        let dvp = prover
+            .open_designated(commitment, answer.clone(), &challenges)
+            .unwrap();
+        let public_values = verifier.verify(&dvp).unwrap();
+        assert_eq!(public_values.public_outputs, vec![Fr::from_u128(280)]);
+
+        // Once ∆' is revealed the prover could make a commitment and answer that pass, so they must be the ones received before
+        let mut forged = dvp.clone();
+        forged.commitment.witness_comm.0[0].0[0] += Fr::ONE;
+        assert!(matches!(
+            verifier.verify(&forged),
+            Err(VerifyError::DesignatedTranscript)
+        ));
+        let mut forged = dvp.clone();
+        forged.proof.zkp.mul_proof.0 += Fr::ONE;
+        assert!(matches!(
+            verifier.verify(&forged),
+            Err(VerifyError::DesignatedTranscript)
+        ));
+
+        // A verifier that hasn't received the commitment neither reveals ∆' for an answer nor accepts a transcript
+        let mut other = DesignatedVerifier::new(VerifyingKey::from_circuit(circuit), &mut rng);
+        assert!(matches!(
+            other.receive_answer(answer),
+            Err(VerifyError::UnexpectedMessage(_))
+        ));
+        assert!(other.verify(&dvp).is_err());
+    }
+
+    #[test]
+    fn lookups() {
+        let mut circuit = zkp::test::TEST_CIRCUIT.clone();
+        // The first three witness values are at most 5 bits
+        circuit.lookups = vec![Lookup {
+            table: (0..32).map(Fr::from_u128).collect(),
+            wires: vec![0, 1, 2],
+        }];
+        let witness = tiny_witness();
+        assert!(e2e_test(witness.clone(), circuit.clone()).is_ok());
+
+        let verifier = Verifier::from_circuit(circuit.clone());
+        let other = FVec::<Fr>([1, 1, 4, 8].iter().map(|x| Fr::from_u128(*x)).collect());
+        let multi = Prover::prove_many(vec![witness.clone(), other], circuit.clone()).unwrap();
+        assert_eq!(verifier.verify_many(&multi).unwrap().len(), 2);
+
+        // 280 satisfies the R1CS but isn't in the table
+        circuit.lookups[0].wires.push(3);
+        assert!(matches!(e2e_test(witness, circuit), Err(VerifyError::Zkp)));
+    }
+
+    #[test]
+    fn memory() {
+        let empty = SparseR1CS {
+            a_rows: SparseFMatrix(vec![]),
+            b_rows: SparseFMatrix(vec![]),
+            c_rows: SparseFMatrix(vec![]),
+        };
+        let mut memory = Memory::zeroed(4);
+        memory.init[0] = Fr::ONE;
+        // mem[w0] = w1; w3 = mem[w2]; w5 = mem[w4]
+        memory.write(0, 1);
+        memory.read(2, 3);
+        memory.read(4, 5);
+        let circuit = R1CSWithMetadata {
+            r1cs: R1CS::Sparse(empty),
+            public_inputs_indices: vec![],
+            public_outputs_indices: vec![],
+            unpadded_wtns_len: 6,
+            custom_gates: vec![],
+            lookups: vec![],
+            memories: vec![memory],
+            branches: vec![],
+            ccs: vec![],
+            signal_names: BTreeMap::new(),
+        };
+        let witness = |w: [u128; 6]| FVec::<Fr>(w.iter().map(|x| Fr::from_u128(*x)).collect());
+        assert!(e2e_test(witness([2, 7, 2, 7, 0, 1]), circuit.clone()).is_ok());
+        assert!(e2e_test(witness([2, 7, 3, 0, 2, 7]), circuit.clone()).is_ok());
+
+        // Reading a value that was never written, reading the value before the write, and writing outside the memory
+        for w in [[2, 7, 2, 8, 0, 1], [2, 7, 2, 0, 0, 1], [4, 7, 3, 0, 0, 1]] {
+            assert!(matches!(
+                e2e_test(witness(w), circuit.clone()),
+                Err(VerifyError::Zkp)
+            ));
+        }
+    }
+
+    #[test]
+    fn disjunction() {
+        let row = |v: [u128; 4]| FVec::<Fr>(v.iter().map(|x| Fr::from_u128(*x)).collect());
+        // w0 * w1 = w3
+        let other = R1CSWithMetadata {
+            r1cs: R1CS::Full(FullR1CS {
+                a_rows: FMatrix(vec![row([1, 0, 0, 0])]),
+                b_rows: FMatrix(vec![row([0, 1, 0, 0])]),
+                c_rows: FMatrix(vec![row([0, 0, 0, 1])]),
+            }),
+            public_inputs_indices: vec![],
+            public_outputs_indices: vec![],
+            unpadded_wtns_len: 4,
+            custom_gates: vec![],
+            lookups: vec![],
+            memories: vec![],
+            branches: vec![],
+            ccs: vec![],
+            signal_names: BTreeMap::new(),
+        };
+        let circuit = zkp::test::TEST_CIRCUIT.clone().or(other);
+
+        // Satisfies only the first branch, then only the second
+        for witness in [row([5, 2, 28, 280]), row([5, 2, 0, 10])] {
+            let cnp = Prover::from_witness_and_circuit_unpadded(witness, circuit.clone())
+                .commit_and_prove()
+                .unwrap();
+            assert_eq!(cnp.proof.zkp.branch_proof.len(), 2);
+            assert!(Verifier::from_circuit(circuit.clone()).verify(&cnp).is_ok());
+        }
+        assert!(matches!(
+            e2e_test(row([5, 2, 28, 281]), circuit.clone()),
+            Err(VerifyError::Zkp)
+        ));
+        assert!(matches!(
+            Prover::prove_many(vec![row([5, 2, 28, 280])], circuit),
+            Err(ProofError::RepeatedBranches)
+        ));
+    }
+
+    #[test]
+    fn ccs_and_plonkish() {
+        let row = |v: [u128; 4]| FVec::<Fr>(v.iter().map(|x| Fr::from_u128(*x)).collect());
+        let test_r1cs = R1CS::Full(zkp::test::TEST_R1CS.clone());
+        // The test circuit as CCS, plus a degree 3 row: w0 * w1 * w2 = w3
+        let mut ccs = CCS::from_r1cs(test_r1cs);
+        ccs.matrices
+            .iter_mut()
+            .for_each(|m| m.0.push(SparseVec(vec![])));
+        ccs.matrices.extend((0..4).map(|i| {
+            let (zero, unit) = (SparseVec(vec![]), SparseVec(vec![(i, Fr::ONE)]));
+            SparseFMatrix(vec![zero.clone(), zero, unit])
+        }));
+        ccs.terms.push((Fr::ONE, vec![3, 4, 5]));
+        ccs.terms.push((-Fr::ONE, vec![6]));
+        assert_eq!(ccs.degree(), 3);
+        let circuit = ConstraintSystem::CCS(ccs).with_metadata(vec![0, 2], vec![3], 4);
+        assert!(circuit.ccs[0].is_satisfied(&row([5, 2, 28, 280])));
+        assert!(e2e_test(row([5, 2, 28, 280]), circuit.clone()).is_ok());
+        assert!(matches!(
+            e2e_test(row([5, 2, 28, 281]), circuit),
+            Err(VerifyError::Zkp)
+        ));
+
+        // w0 + w1 = w2 - 21 and w0 * w1 = w3 - 270
+        let gate = |q_l, q_r, q_o, q_m, q_c, c| PlonkGate {
+            q_l: Fr::from_u128(q_l),
+            q_r: Fr::from_u128(q_r),
+            q_o: -Fr::from_u128(q_o),
+            q_m: Fr::from_u128(q_m),
+            q_c: Fr::from_u128(q_c),
+            a: 0,
+            b: 1,
+            c,
+        };
+        let gates = vec![gate(1, 1, 1, 0, 21, 2), gate(0, 0, 1, 1, 270, 3)];
+        let circuit = ConstraintSystem::Plonkish(gates).with_metadata(vec![], vec![3], 4);
+        assert!(e2e_test(row([5, 2, 28, 280]), circuit.clone()).is_ok());
+        assert!(matches!(
+            e2e_test(row([5, 2, 27, 280]), circuit),
+            Err(VerifyError::Zkp)
+        ));
+    }
+
+    #[test]
+    fn sparse_witness() {
+        // Only the first of 100 copies of the test circuit has a nonzero witness
+        let circuit = zkp::test::TEST_CIRCUIT.repeat(100);
+        let witness = SparseVec(
+            [(0, 5), (1, 2), (2, 28), (3, 280)]
+                .iter()
+                .map(|(i, x)| (*i, Fr::from_u128(*x)))
+                .collect(),
+        );
+        let verifier = Verifier::from_circuit(circuit.clone());
+        let cnp = Prover::from_sparse_witness_and_circuit(witness.clone(), circuit.clone())
+            .unwrap()
+            .commit_and_prove()
+            .unwrap();
+        let public_values = verifier.verify(&cnp).unwrap();
+        assert_eq!(public_values.public_outputs[0], Fr::from_u128(280));
+        assert!(public_values.public_outputs[1..]
+            .iter()
+            .all(|x| x.is_zero_vartime()));
+
+        let mut outside = witness;
+        outside.0.push((400, Fr::ONE));
+        assert!(matches!(
+            Prover::from_sparse_witness_and_circuit(outside, circuit),
+            Err(ProofError::SparseWitnessIndex {
+                index: 400,
+                witness_len: 400
+            })
+        ));
+    }
+
+    #[test]
+    fn incremental_proving() {
+        let circuit = zkp::test::TEST_CIRCUIT.repeat(3);
+        let row = |v: &[u128]| FVec::<Fr>(v.iter().map(|x| Fr::from_u128(*x)).collect());
+        let witness = row(&[5, 2, 28, 280, 5, 2, 28, 280, 5, 2, 28, 280]);
+        let verifier = Verifier::from_circuit(circuit.clone());
+        let mut prover =
+            IncrementalProver::new(Prover::from_witness_and_circuit_unpadded(witness, circuit));
+        let cnp = prover.commit_and_prove().unwrap();
+        assert_eq!(
+            verifier.verify(&cnp).unwrap().public_outputs,
+            row(&[280, 280, 280]).0
+        );
+
+        // Change the second copy's witness to another satisfying one
+        let changes = |v: &[(usize, u128)]| {
+            SparseVec(v.iter().map(|(i, x)| (*i, Fr::from_u128(*x))).collect())
+        };
+        prover
+            .update(&changes(&[(4, 6), (5, 3), (6, 54), (7, 648)]))
+            .unwrap();
+        let cnp = prover.commit_and_prove().unwrap();
+        assert_eq!(
+            verifier.verify(&cnp).unwrap().public_outputs,
+            row(&[280, 648, 280]).0
+        );
+
+        // An unsatisfying change is caught, and undoing it proves again
+        prover.update(&changes(&[(11, 281)])).unwrap();
+        let cnp = prover.commit_and_prove().unwrap();
+        assert!(matches!(verifier.verify(&cnp), Err(VerifyError::Zkp)));
+        prover.update(&changes(&[(11, 280)])).unwrap();
+        assert!(verifier.verify(&prover.commit_and_prove().unwrap()).is_ok());
+
+        assert!(matches!(
+            prover.update(&changes(&[(12, 1)])),
+            Err(ProofError::SparseWitnessIndex {
+                index: 12,
+                witness_len: 12
+            })
+        ));
+    }
+
+    #[test]
+    fn composite_circuit() {
+        let row = |v: &[u128]| FVec::<Fr>(v.iter().map(|x| Fr::from_u128(*x)).collect());
+        let mut range = zkp::test::TEST_CIRCUIT.clone();
+        range.lookups = vec![Lookup {
+            table: (0..32).map(Fr::from_u128).collect(),
+            wires: vec![0, 1],
+        }];
+        // w0 * w1 = w2, revealing w2
+        let product = R1CSWithMetadata {
+            r1cs: R1CS::Full(FullR1CS {
+                a_rows: FMatrix(vec![row(&[1, 0, 0])]),
+                b_rows: FMatrix(vec![row(&[0, 1, 0])]),
+                c_rows: FMatrix(vec![row(&[0, 0, 1])]),
+            }),
+            public_inputs_indices: vec![],
+            public_outputs_indices: vec![2],
+            unpadded_wtns_len: 3,
+            custom_gates: vec![],
+            lookups: vec![],
+            memories: vec![],
+            branches: vec![],
+            ccs: vec![],
+            signal_names: BTreeMap::new(),
+        };
+        let composite = CompositeCircuit::new(vec![range, product]);
+        let verifier = Verifier::from_circuit(composite.to_circuit());
+
+        let witnesses = vec![row(&[5, 2, 28, 280]), row(&[3, 4, 12])];
+        let cnp = Prover::prove_composite(witnesses, &composite).unwrap();
+        let public_values = verifier.verify(&cnp).unwrap().split(&composite);
+        assert_eq!(public_values.len(), 2);
+        assert_eq!(
+            public_values[0].public_inputs,
+            vec![Fr::from_u128(5), Fr::from_u128(28)]
+        );
+        assert_eq!(public_values[0].public_outputs, vec![Fr::from_u128(280)]);
+        assert!(public_values[1].public_inputs.is_empty());
+        assert_eq!(public_values[1].public_outputs, vec![Fr::from_u128(12)]);
+
+        let wrong = vec![row(&[5, 2, 28, 280]), row(&[3, 4, 13])];
+        let cnp = Prover::prove_composite(wrong, &composite).unwrap();
+        assert!(matches!(verifier.verify(&cnp), Err(VerifyError::Zkp)));
+        assert!(matches!(
+            Prover::prove_composite(vec![row(&[5, 2, 28, 280])], &composite),
+            Err(ProofError::WitnessCount {
+                expected: 2,
+                found: 1
+            })
+        ));
+    }
+
+    #[test]
+    fn committed_witness_proven_against_several_circuits() {
+        let circuit = zkp::test::TEST_CIRCUIT.clone();
+        let row = |v: [u128; 4]| FVec::<Fr>(v.iter().map(|x| Fr::from_u128(*x)).collect());
+        // Only the first constraint of the test circuit, revealing different wires
+        let first_constraint = R1CSWithMetadata {
+            r1cs: R1CS::Full(FullR1CS {
+                a_rows: FMatrix(vec![row([1, 1, 0, 0])]),
+                b_rows: FMatrix(vec![row([0, 2, 0, 0])]),
+                c_rows: FMatrix(vec![row([0, 0, 1, 0])]),
+            }),
+            public_inputs_indices: vec![1],
+            public_outputs_indices: vec![2],
+            unpadded_wtns_len: 4,
+            custom_gates: vec![],
+            lookups: vec![],
+            memories: vec![],
+            branches: vec![],
+            ccs: vec![],
+            signal_names: BTreeMap::new(),
+        };
+        let keys = [circuit.clone(), first_constraint.clone()].map(VerifyingKey::from_circuit);
+
+        let (committed, commitment) = CommittedWitness::commit(row([5, 2, 28, 280])).unwrap();
+        let proof = committed
+            .prove(vec![circuit.clone(), first_constraint.clone()])
+            .unwrap();
+        let public_values = Verifier::verify_linked(&commitment, &keys, &proof).unwrap();
+        assert_eq!(public_values[0].public_outputs, vec![Fr::from_u128(280)]);
+        assert_eq!(public_values[1].public_inputs, vec![Fr::from_u128(2)]);
+        assert_eq!(public_values[1].public_outputs, vec![Fr::from_u128(28)]);
+
+        // The proof is only valid for the commitment it was made from and the circuits in their order
+        let (_, other_commitment) = CommittedWitness::commit(row([5, 2, 28, 280])).unwrap();
+        assert!(Verifier::verify_linked(&other_commitment, &keys, &proof).is_err());
+        let [k0, k1] = keys;
+        assert!(Verifier::verify_linked(&commitment, &[k1, k0], &proof).is_err());
+
+        let (committed, commitment) = CommittedWitness::commit(row([5, 2, 28, 281])).unwrap();
+        let wrong = committed
+            .prove(vec![first_constraint.clone(), circuit.clone()])
+            .unwrap();
+        let keys = [first_constraint, circuit.clone()].map(VerifyingKey::from_circuit);
+        assert!(matches!(
+            Verifier::verify_linked(&commitment, &keys, &wrong),
+            Err(VerifyError::Zkp)
+        ));
+
+        // The proof is checked at the keys' security level and for the context it was bound to
+        let strict = [circuit.clone()]
+            .map(|c| VerifyingKey::from_circuit(c).require_security(SecurityLevel(140)));
+        let (committed, commitment) = CommittedWitness::commit(row([5, 2, 28, 280])).unwrap();
+        let proof = committed.prove(vec![circuit.clone()]).unwrap();
+        assert!(matches!(
+            Verifier::verify_linked(&commitment, &strict, &proof),
+            Err(VerifyError::InsufficientSecurity {
+                required: 140,
+                found: 128
+            })
+        ));
+        let (committed, commitment) = CommittedWitness::commit(row([5, 2, 28, 280])).unwrap();
+        let proof = committed
+            .security_level(140)
+            .unwrap()
+            .context(b"session 1")
+            .prove(vec![circuit.clone()])
+            .unwrap();
+        assert_eq!(proof.grinding_bits, 12);
+        assert!(
+            Verifier::verify_linked_with_context(&commitment, &strict, &proof, b"session 1")
+                .is_ok()
+        );
+        assert!(matches!(
+            Verifier::verify_linked(&commitment, &strict, &proof),
+            Err(VerifyError::ContextMismatch)
+        ));
+        let mut other_context = proof.clone();
+        other_context.context = b"session 2".to_vec();
+        assert!(Verifier::verify_linked_with_context(
+            &commitment,
+            &strict,
+            &other_context,
+            b"session 2"
+        )
+        .is_err());
+        let mut downgraded = proof.clone();
+        downgraded.security = SecurityLevel::default();
+        assert!(Verifier::verify_linked_with_context(
+            &commitment,
+            &[VerifyingKey::from_circuit(circuit.clone())],
+            &downgraded,
+            b"session 1"
+        )
+        .is_err());
+
+        let (committed, _) = CommittedWitness::commit(FVec::<Fr>(vec![Fr::ONE; 3])).unwrap();
+        assert!(matches!(
+            committed.prove(vec![circuit]),
+            Err(ProofError::WitnessLength {
+                expected: 3,
+                found: 4
+            })
+        ));
+    }
+
+    #[test]
+    fn proofs_from_vole_pool() {
+        let (circuit, witness) = tiny_circuit_and_witness();
+
+        let mut pool = VolePool::for_circuit(&circuit);
+        pool.fill(2).unwrap();
+        assert_eq!(pool.len(), 2);
+
+        let verifier = Verifier::from_circuit(circuit.clone());
+        for _ in 0..2 {
+            let mut prover =
+                Prover::from_witness_and_circuit_unpadded(witness.clone(), circuit.clone());
+            let commitment = prover.mkvole_from_pool(&mut pool).unwrap();
+            let proof = prover.prove().unwrap();
+            assert!(verifier
+                .verify(&CommitAndProof { commitment, proof })
+                .is_ok());
+        }
+
+        assert!(pool.is_empty());
+        let mut prover = Prover::from_witness_and_circuit_unpadded(witness, circuit);
+        assert!(prover.mkvole_from_pool(&mut pool).is_err());
+    }
+
+    #[test]
+    fn injected_rngs_make_voles_reproducible() {
+        let code = RAAACode::rand_default();
+        let generate = |seed| {
+            PreparedVOLE::<Fr>::generate_with_rng(
+                &code,
+                10,
+                code.n(),
+                &mut ChaCha12Rng::from_seed(seed),
+            )
+            .unwrap()
+            .seed_comm
+        };
+        assert_eq!(generate([3; 32]), generate([3; 32]));
+        assert_ne!(generate([3; 32]), generate([4; 32]));
+
+        let circuit = zkp::test::TEST_CIRCUIT.clone();
+        let pooled = |seed| {
+            let mut pool =
+                VolePool::<Fr>::for_circuit(&circuit).with_rng(ChaCha12Rng::from_seed(seed));
+            pool.fill(2).unwrap();
+            (0..2)
+                .map(|_| pool.take().unwrap().seed_comm)
+                .collect::<Vec<_>>()
+        };
+        let voles = pooled([5; 32]);
+        assert_eq!(voles, pooled([5; 32]));
+        assert_ne!(voles[0], voles[1]);
+    }
+
+    #[test]
+    fn streaming_vole_matches_full_vole() {
+        let code = RAAACode::rand_default();
+        let seeds = (0..code.n())
+            .map(|i| [[i as u8; 32], [(i / 256) as u8 + 100; 32]])
+            .collect::<Vec<_>>();
+        let full = PreparedVOLE::<Fr>::from_seeds(&code, 10, seeds.clone()).unwrap();
+        for chunk_rows in [1, 3, 10, 64] {
+            let streamed =
+                PreparedVOLE::<Fr>::from_seeds_streaming(&code, 10, seeds.clone(), chunk_rows)
+                    .unwrap();
+            assert_eq!(streamed.seed_comm, full.seed_comm);
+            assert_eq!(streamed.u_rows, full.u_rows);
+            assert_eq!(streamed.v_rows, full.v_rows);
+            assert_eq!(streamed.correction, full.correction);
+            assert_eq!(streamed.consistency_check.0, full.consistency_check.0);
+            assert_eq!(streamed.consistency_check.1, full.consistency_check.1);
+        }
+        assert!(PreparedVOLE::<Fr>::from_seeds_streaming(&code, 10, seeds, 0).is_err());
+    }
+
+    #[test]
+    fn memory_report() {
+        let circuit = zkp::test::TEST_CIRCUIT.clone();
+        let witness = FVec::<Fr>([5, 2, 28, 280].iter().map(|x| Fr::from_u128(*x)).collect());
+        let mut prover = Prover::from_witness_and_circuit_unpadded(witness, circuit);
+        let before = prover.memory_report();
+        assert!(before.witness > 0 && before.circuit > 0);
+        assert_eq!(before.subspace_vole, 0);
+        prover.commit_and_prove().unwrap();
+        let after = prover.memory_report();
+        assert!(after.subspace_vole > 0 && after.witness_comm > 0);
+        assert_eq!(
+            after.footprint(),
+            after.witness + after.witness_comm + after.circuit + after.subspace_vole
+        );
+    }
+
+    #[test]
+    fn pipelined_vole_matches_full_vole() {
+        let code = RAAACode::rand_default();
+        let seeds = (0..code.n())
+            .map(|i| [[(i as u8).wrapping_add(7); 32], [(i / 256) as u8 + 50; 32]])
+            .collect::<Vec<_>>();
+        let full = PreparedVOLE::<Fr>::from_seeds(&code, 10, seeds.clone()).unwrap();
+        for (chunk_rows, threads) in [(1, 1), (3, 2), (4, 3), (64, 4)] {
+            let pipelined = PreparedVOLE::<Fr>::from_seeds_pipelined(
+                &code,
+                10,
+                seeds.clone(),
+                chunk_rows,
+                threads,
+            )
+            .unwrap();
+            assert_eq!(pipelined.u_rows, full.u_rows);
+            assert_eq!(pipelined.v_rows, full.v_rows);
+            assert_eq!(pipelined.correction, full.correction);
+            assert_eq!(pipelined.consistency_check.0, full.consistency_check.0);
+            assert_eq!(pipelined.consistency_check.1, full.consistency_check.1);
+        }
+        assert!(PreparedVOLE::<Fr>::from_seeds_pipelined(&code, 10, seeds, 4, 0).is_err());
+    }
+
+    #[test]
+    fn streaming_prover() {
+        let (circuit, witness) = tiny_circuit_and_witness();
+        let mut prover = Prover::from_witness_and_circuit_unpadded(witness, circuit.clone());
+        let commitment = prover.mkvole_streaming(1).unwrap();
+        let proof = prover.prove().unwrap();
+        let verifier = Verifier::from_circuit(circuit);
+        assert!(verifier
+            .verify(&CommitAndProof { commitment, proof })
+            .is_ok());
+    }
 }
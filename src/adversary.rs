@@ -0,0 +1,112 @@
+//! A malicious prover for soundness tests: it proves honestly except for one component it tampers with, chosen by an `Attack`.
+//!
+//! Each attack is on a check the verifier must not skip, so `prove_and_verify` must fail for all of them.
+//! It is public so tests of other crates built on this one can use it, but it is not part of the API and can make nothing but invalid proofs
+use crate::{
+    actors::actors::{CommitAndProof, PreparedVOLE, Prover, PublicUOpenings, Verifier},
+    error::{ProofError, VerifyError},
+    zkp::R1CSWithMetadata,
+    FVec, PF,
+};
+
+/// The component of the proof a malicious prover lies about
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Attack {
+    /// Adds one to every value of row `row` of U once the subspace VOLE is made, so U no longer matches V, the correction, or the consistency check
+    FlipURow(usize),
+    /// Sends a zero correction instead of the one that moves U into the code's subspace
+    SkipCorrection,
+    /// Adds one to the first value of U's half of the subspace VOLE consistency check
+    LieInConsistencyCheck,
+    /// Opens the seed of small VOLE `i` that the ∆ choice keeps hidden
+    OpenWrongSeed(usize),
+    /// Adds one to the first value of the S matrix
+    ForgeSMatrix,
+    /// Claims the first public output is one more than it is
+    ForgePublicOutput,
+}
+
+impl Attack {
+    /// One of each attack, on the first row or small VOLE
+    pub const ALL: [Attack; 6] = [
+        Attack::FlipURow(0),
+        Attack::SkipCorrection,
+        Attack::LieInConsistencyCheck,
+        Attack::OpenWrongSeed(0),
+        Attack::ForgeSMatrix,
+        Attack::ForgePublicOutput,
+    ];
+}
+
+/// Proves `witness` satisfies `circuit` honestly except for `attack`
+pub fn malicious_proof<T: PF>(
+    witness: FVec<T>,
+    circuit: R1CSWithMetadata<T>,
+    attack: Attack,
+) -> Result<CommitAndProof<T>, ProofError> {
+    let mut prover = Prover::from_witness_and_circuit_unpadded(witness, circuit);
+    let mut prepared = PreparedVOLE::generate(&prover.code, prover.vole_length, prover.num_voles)?;
+    match attack {
+        Attack::FlipURow(row) => prepared.u_rows.0[row]
+            .0
+            .iter_mut()
+            .for_each(|x| *x += T::ONE),
+        Attack::SkipCorrection => prepared
+            .correction
+            .0
+            .iter_mut()
+            .for_each(|row| row.0.fill(T::ZERO)),
+        Attack::LieInConsistencyCheck => prepared.consistency_check.0 .0[0] += T::ONE,
+        _ => {}
+    }
+    let seeds = prepared.seeds.clone();
+    let commitment = prover.commit_with_prepared(prepared)?;
+    let mut proof = prover.prove()?;
+    match attack {
+        Attack::OpenWrongSeed(i) => {
+            let opened = &mut proof.seed_openings.seed_opens[i];
+            *opened = match *opened == seeds[i][0] {
+                true => seeds[i][1],
+                false => seeds[i][0],
+            };
+        }
+        Attack::ForgeSMatrix => proof.s_matrix.0[0].0[0] += T::ONE,
+        Attack::ForgePublicOutput => proof.public_openings.public_outputs[0].0 += T::ONE,
+        _ => {}
+    }
+    Ok(CommitAndProof { commitment, proof })
+}
+
+/// Makes the malicious proof and verifies it. Any result but an error is a soundness bug
+pub fn prove_and_verify<T: PF>(
+    witness: FVec<T>,
+    circuit: R1CSWithMetadata<T>,
+    attack: Attack,
+) -> Result<PublicUOpenings<T>, VerifyError> {
+    let cnp = malicious_proof(witness, circuit.clone(), attack)
+        .expect("the honest parts of a malicious proof are provable");
+    Verifier::from_circuit(circuit).verify(&cnp)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::testvectors::tiny_circuit;
+
+    #[test]
+    fn attacks_are_rejected() {
+        let (circuit, witness) = tiny_circuit();
+        let vole_length =
+            Prover::from_witness_and_circuit_unpadded(witness.clone(), circuit.clone()).vole_length;
+        let attacks = Attack::ALL.into_iter().chain([
+            Attack::FlipURow(vole_length - 1),
+            Attack::OpenWrongSeed(1023),
+        ]);
+        for attack in attacks {
+            assert!(
+                prove_and_verify(witness.clone(), circuit.clone(), attack).is_err(),
+                "{attack:?} was accepted"
+            );
+        }
+    }
+}
@@ -1,51 +1,102 @@
-use anyhow::{Context, Result};
+use anyhow::{bail, Context, Result};
+use bigdecimal::BigDecimal;
 use clap::{Parser, Subcommand, ValueEnum};
+use ff::PrimeField;
 use lazy_static::lazy_static;
 use rand::{thread_rng, Rng};
 use regex::Regex;
 use serde::Deserialize;
+#[cfg(feature = "http")]
+use std::net::SocketAddr;
 use std::{
     collections::BTreeMap,
+    env,
     fs::{self, File},
-    io::{BufReader, Write},
+    io::{self, BufReader, BufWriter, IsTerminal, Write},
+    ops::Range,
     path::{Path, PathBuf},
     process::Command,
-    sync::Mutex,
-    time::Instant,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex, MutexGuard, PoisonError,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+use tracing::{error, info};
+use tracing_subscriber::EnvFilter;
+use volonym::circom::{
+    self,
+    compile::{
+        build_cpp_witness_generator, run_cpp_witness_generator, CompileOptions, CompiledCircuit,
+        OptimizationLevel,
+    },
+    generator::generate_circom,
+    graph::write_dot,
+    r1cs::R1CSFile,
+    stats::{analyze, field_ops_per_second},
+    sym::SymbolTable,
+    to_decimal,
+    witness::wtns_from_reader,
+};
+#[cfg(feature = "witness-wasm")]
+use volonym::circom::{calculator::WitnessCalculator, witness::wtns_to_writer};
+#[cfg(feature = "http")]
+use volonym::http::{router, HttpConfig};
+use volonym::{
+    actors::actors::{
+        CommitAndProof, MemoryReport, Phase, Prover, ProverBuilder, PublicUOpenings, Verifier,
+        VerifyingKey,
+    },
+    codeparams::{expected_num_outputs_with_weight, values_for_rma_code},
+    error::CompileError,
+    subspacevole::{CodeParams, CodePreset, LinearCode},
+    zkp::R1CSWithMetadata,
+    FVec, Fr,
 };
-use volonym::circom::generator::generate_circom;
-use volonym::circom::r1cs::R1CSFile;
+
+#[cfg(feature = "mem-stats")]
+#[global_allocator]
+static ALLOCATOR: volonym::memstats::TrackingAllocator = volonym::memstats::TrackingAllocator;
 
 lazy_static! {
     static ref LOG_FILE: Mutex<Option<File>> = Mutex::new(None);
 }
 
-macro_rules! log_println {
-    ($($arg:tt)*) => {
-        if let Ok(mut guard) = LOG_FILE.lock() {
-            if let Some(file) = &mut *guard {
-                if let Err(e) = writeln!(file, $($arg)*) {
-                    eprintln!("Failed to write to log file: {}", e);
-                }
-            } else {
-                println!($($arg)*);
-            }
-        }
-    };
+/// The log file `--log` opened, if any. A thread that panicked while writing to it doesn't stop the others from logging
+fn log_file() -> MutexGuard<'static, Option<File>> {
+    LOG_FILE.lock().unwrap_or_else(PoisonError::into_inner)
 }
 
-macro_rules! log_eprintln {
-    ($($arg:tt)*) => {
-        if let Ok(mut guard) = LOG_FILE.lock() {
-            if let Some(file) = &mut *guard {
-                if let Err(e) = writeln!(file, $($arg)*) {
-                    eprintln!("Failed to write to log file: {}", e);
-                }
-            } else {
-                eprintln!($($arg)*);
-            }
+/// Writes tracing's events to the log file once `--log` has opened one, and to stderr otherwise
+struct LogWriter;
+
+impl Write for LogWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match &mut *log_file() {
+            Some(file) => file.write(buf),
+            None => io::stderr().write(buf),
         }
-    };
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match &mut *log_file() {
+            Some(file) => file.flush(),
+            None => io::stderr().flush(),
+        }
+    }
+}
+
+/// Installs a subscriber for the library's and this tool's events. Unless `RUST_LOG` says otherwise,
+/// this tool's progress is shown, and only the library's warnings and errors. Results go to stdout instead
+fn init_logging(log: bool) {
+    let filter =
+        EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("warn,r1cs_tool=info"));
+    tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_writer(|| LogWriter)
+        .with_ansi(!log && io::stderr().is_terminal())
+        .init();
 }
 
 #[derive(Parser, Debug)]
@@ -53,10 +104,14 @@ macro_rules! log_eprintln {
 struct Cli {
     #[command(subcommand)]
     command: Commands,
-    /// Log output to a file instead of stdout/stderr.
+    /// Log progress and diagnostics to a file instead of stderr. Results are written to stdout either way.
     /// The log file will have the same name as the input file, with a .log extension.
+    /// What is logged is set with RUST_LOG, e.g. RUST_LOG=volonym=debug; by default this tool's progress and the library's warnings and errors.
     #[arg(short = 'l', long, global = true)]
     log: bool,
+    /// Always run circom, instead of reusing the output of an earlier compilation of an unchanged circuit.
+    #[arg(long, global = true)]
+    no_cache: bool,
 }
 
 #[derive(Subcommand, Debug)]
@@ -66,6 +121,125 @@ enum Commands {
         /// Path to the .r1cs file to parse.
         #[arg(default_value = "src/circom/examples/falcon.r1cs")]
         r1cs_file: PathBuf,
+        /// Print the constraints as math, or as JSON for other tools.
+        #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+        format: OutputFormat,
+    },
+    /// Report a circuit's constraint density, padding, and estimated proof size and proving time
+    Stats {
+        /// Path to the .r1cs file to analyze.
+        #[arg(default_value = "src/circom/examples/falcon.r1cs")]
+        r1cs_file: PathBuf,
+    },
+    /// Write a circuit's constraints and the wires they use as a Graphviz graph, e.g. to spot constraints with high fan-in
+    Graph {
+        /// Path to the .r1cs file to draw.
+        r1cs_file: PathBuf,
+        /// Path to write the DOT graph to.
+        #[arg(long = "out", default_value = "circuit.dot")]
+        out_file: PathBuf,
+        /// Only draw the constraints in this range, e.g. 100..200. Large circuits are hard to draw whole.
+        #[arg(long, value_parser = parse_range)]
+        constraints: Option<Range<usize>>,
+        /// Path to the .sym file naming the circuit's signals.
+        /// Defaults to the .sym file next to the .r1cs file, if there is one.
+        #[arg(long = "sym")]
+        sym_file: Option<PathBuf>,
+    },
+    /// Prove a circuit for a witness, writing the proof in the canonical encoding
+    Prove {
+        /// Path to the circuit's .r1cs file.
+        #[arg(long = "r1cs")]
+        r1cs_file: PathBuf,
+        /// Path to the .wtns file with the witness.
+        #[arg(long = "wtns")]
+        wtns_file: PathBuf,
+        /// Path to write the proof to.
+        #[arg(long = "out", default_value = "proof.bin")]
+        out_file: PathBuf,
+        /// Path to code parameters from `gen-params` to prove with instead of the default code.
+        #[arg(long = "params")]
+        params_file: Option<PathBuf>,
+    },
+    /// Verify a proof written by `prove`, exiting with an error if it doesn't verify
+    Verify {
+        /// Path to the circuit's .r1cs file.
+        #[arg(long = "r1cs")]
+        r1cs_file: PathBuf,
+        /// Path to the proof.
+        #[arg(long = "proof")]
+        proof_file: PathBuf,
+        /// Path to the expected public values, as snarkjs writes them in public.json:
+        /// a JSON array of decimal strings, the public outputs followed by the public inputs.
+        #[arg(long = "public")]
+        public_file: Option<PathBuf>,
+        /// Path to the code parameters the proof was made with, if it wasn't made with the default code.
+        #[arg(long = "params")]
+        params_file: Option<PathBuf>,
+    },
+    /// Generate a linear code for `prove` and `verify` to share instead of the default code, writing its parameters to a file
+    GenParams {
+        /// Path to write the code parameters to.
+        #[arg(long = "out", default_value = "code.params")]
+        out_file: PathBuf,
+        /// Derive the code from this seed, e.g. a randomness beacon's output, so anyone can check where it came from.
+        /// Random if not given.
+        #[arg(long)]
+        seed: Option<String>,
+        /// Also bound the probability that a code of its dimensions has minimum distance under this, with the codeparams estimator.
+        /// Its time grows with the cube of the block length, so for the default dimensions it is very slow.
+        #[arg(long)]
+        distance: Option<usize>,
+    },
+    /// Time proving a circuit for a witness over several runs, after a warm-up run
+    Bench {
+        /// Path to the circuit's .r1cs file.
+        #[arg(long = "r1cs", default_value = "src/circom/examples/test_2.r1cs")]
+        r1cs_file: PathBuf,
+        /// Path to the .wtns file with the witness.
+        #[arg(long = "wtns", default_value = "src/circom/examples/witness_2.wtns")]
+        wtns_file: PathBuf,
+        /// Number of timed runs.
+        #[arg(long, default_value_t = 10)]
+        runs: usize,
+        /// Also time each phase of proving: the VOLE, its correction and consistency check, the ZKP, and the S matrix.
+        #[arg(long)]
+        phases: bool,
+        /// Print a summary, or every run as CSV or JSON to compare across commits and machines.
+        /// Each run's memory is reported too, with its peak heap usage if built with the mem-stats feature.
+        #[arg(long, value_enum, default_value_t = BenchFormat::Text)]
+        format: BenchFormat,
+    },
+    /// Compile a Circom file, calculate its witness for an input, then prove and verify it, writing every artifact to one directory
+    Pipeline {
+        /// Path to the .circom file to compile.
+        #[arg(long = "circom")]
+        circom_file: PathBuf,
+        /// Path to the input JSON to calculate the witness from.
+        #[arg(long = "input")]
+        input_file: PathBuf,
+        /// Directory to write the compiled circuit, witness, proof, and public values to.
+        /// Defaults to a directory named after the circuit next to it.
+        #[arg(long = "out")]
+        out_dir: Option<PathBuf>,
+        #[clap(flatten)]
+        optimization: Optimization,
+        /// Calculate the witness with circom's C++ witness generator, see `falcon --cpp`.
+        #[arg(long)]
+        cpp: bool,
+    },
+    /// Check a witness satisfies a circuit's constraints without proving it, printing those it doesn't
+    CheckWitness {
+        /// Path to the circuit's .r1cs file.
+        #[arg(long = "r1cs")]
+        r1cs_file: PathBuf,
+        /// Path to the .wtns file with the witness.
+        #[arg(long = "wtns")]
+        wtns_file: PathBuf,
+        /// Path to the .sym file naming the circuit's signals.
+        /// Defaults to the .sym file next to the .r1cs file, if there is one.
+        #[arg(long = "sym")]
+        sym_file: Option<PathBuf>,
     },
     /// Compile a Circom file and parse the output
     Compile {
@@ -104,7 +278,50 @@ enum Commands {
         case: Option<usize>,
         #[clap(flatten)]
         optimization: Optimization,
+        /// Calculate witnesses with circom's C++ witness generator, which is much faster for large circuits.
+        /// Building it needs make, a C++ compiler, and GMP.
+        #[arg(long)]
+        cpp: bool,
+        /// Number of cases to run at once. Each runs in its own process and logs to its own file, as with --log,
+        /// and a summary of the cases is printed once they have all finished.
+        #[arg(long, default_value_t = 1)]
+        jobs: usize,
     },
+    /// Serve `/prove` and `/verify` over HTTP, taking circuits, witnesses, and proofs as multipart uploads
+    #[cfg(feature = "http")]
+    Serve {
+        /// Address to listen on.
+        #[arg(long, default_value = "127.0.0.1:8080")]
+        listen: SocketAddr,
+        /// Largest request body, in bytes.
+        #[arg(long, default_value_t = 256 << 20)]
+        max_body_bytes: usize,
+        /// Seconds a request may take, uploading included, before it is answered with 408.
+        #[arg(long, default_value_t = 300)]
+        timeout: u64,
+        /// Proofs to make at once.
+        #[arg(long, default_value_t = 1)]
+        max_concurrent_proofs: usize,
+        /// Proofs to verify at once.
+        #[arg(long, default_value_t = 4)]
+        max_concurrent_verifications: usize,
+        /// Threads each proof expands its VOLEs on.
+        #[arg(long, default_value_t = 1)]
+        threads: usize,
+    },
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, ValueEnum, Debug)]
+enum OutputFormat {
+    Text,
+    Json,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, ValueEnum, Debug)]
+enum BenchFormat {
+    Text,
+    Csv,
+    Json,
 }
 
 #[derive(Parser, Debug)]
@@ -130,23 +347,6 @@ impl Optimization {
     }
 }
 
-#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum, Debug)]
-enum OptimizationLevel {
-    O0,
-    O1,
-    O2,
-}
-
-impl std::fmt::Display for OptimizationLevel {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        self.to_possible_value()
-            .expect("no values are skipped")
-            .get_name()
-            .to_uppercase()
-            .fmt(f)
-    }
-}
-
 #[derive(Debug, Deserialize)]
 struct FalconCases {
     cases: Vec<FalconCase>,
@@ -158,11 +358,110 @@ struct FalconCase {
     n: usize,
     #[serde(rename = "Q")]
     q: i64,
-    pk: String,
-    s1: String,
-    s2: String,
-    h: String,
-    c: String,
+    pk: Poly,
+    s1: Poly,
+    s2: Poly,
+    h: Poly,
+    c: Poly,
+}
+
+/// A polynomial of a Falcon case: a string such as "4 + 9x^2", an array of the N coefficients, a table
+/// `{ hex = "..." }` of the coefficients packed big-endian in as few bits as Q needs, like Falcon's public key encoding,
+/// or a table `{ file = "..." }` naming a file, relative to the .toml file, with one coefficient per line
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum Poly {
+    Terms(String),
+    Coefficients(Vec<i64>),
+    Hex { hex: String },
+    File { file: PathBuf },
+}
+
+impl FalconCases {
+    /// Makes the paths of polynomials in files relative to `dir`, the .toml file's directory, rather than the working directory
+    fn resolve_files(&mut self, dir: &Path) {
+        for case in &mut self.cases {
+            for poly in [
+                &mut case.pk,
+                &mut case.s1,
+                &mut case.s2,
+                &mut case.h,
+                &mut case.c,
+            ] {
+                if let Poly::File { file } = poly {
+                    *file = dir.join(&*file);
+                }
+            }
+        }
+    }
+}
+
+impl FalconCase {
+    /// The N coefficients of `poly`, the case's polynomial called `name`. If `mod_q`, it's an element of Z_q[x]/(x^N + 1),
+    /// and each coefficient must be in [0, Q)
+    fn coefficients(&self, name: &str, poly: &Poly, mod_q: bool) -> Result<Vec<i64>> {
+        let coefficients = match poly {
+            Poly::Terms(terms) => {
+                let terms = parse_poly(terms);
+                if let Some((exp, _)) = terms.iter().find(|(exp, _)| *exp as usize >= self.n) {
+                    bail!("{} has a term of degree {}, but N is {}", name, exp, self.n);
+                }
+                to_vec(&terms, self.n)
+            }
+            Poly::Coefficients(coefficients) => coefficients.clone(),
+            Poly::Hex { hex } => {
+                let bytes = hex::decode(hex.trim_start_matches("0x"))
+                    .with_context(|| format!("{} isn't hex", name))?;
+                let bits = 64 - (self.q - 1).max(1).leading_zeros() as usize;
+                if bytes.len() != (self.n * bits).div_ceil(8) {
+                    bail!(
+                        "{} has {} bytes, but N {}-bit coefficients take {}",
+                        name,
+                        bytes.len(),
+                        bits,
+                        (self.n * bits).div_ceil(8)
+                    );
+                }
+                (0..self.n)
+                    .map(|i| {
+                        (i * bits..(i + 1) * bits).fold(0, |acc, bit| {
+                            (acc << 1) | ((bytes[bit / 8] >> (7 - bit % 8)) & 1) as i64
+                        })
+                    })
+                    .collect()
+            }
+            Poly::File { file } => fs::read_to_string(file)
+                .with_context(|| format!("Could not read {}", file.display()))?
+                .lines()
+                .map(str::trim)
+                .filter(|line| !line.is_empty())
+                .map(|line| {
+                    line.parse()
+                        .with_context(|| format!("{} has an invalid coefficient {}", name, line))
+                })
+                .collect::<Result<_>>()?,
+        };
+        if coefficients.len() != self.n {
+            bail!(
+                "{} has {} coefficients, but N is {}",
+                name,
+                coefficients.len(),
+                self.n
+            );
+        }
+        if mod_q {
+            if let Some(i) = coefficients.iter().position(|x| !(0..self.q).contains(x)) {
+                bail!(
+                    "coefficient {} of {}, {}, isn't in [0, Q) for Q {}",
+                    i,
+                    name,
+                    coefficients[i],
+                    self.q
+                );
+            }
+        }
+        Ok(coefficients)
+    }
 }
 
 fn parse_poly(poly: &str) -> Vec<(u32, i64)> {
@@ -221,44 +520,117 @@ where
     vec
 }
 
-fn to_string_vec(poly: &Vec<(u32, i64)>, n: usize) -> Vec<String> {
-    to_vec(poly, n)
-        .into_iter()
-        .map(|x: i64| x.to_string())
-        .collect()
-}
-
-fn to_i64_vec(poly: &Vec<(u32, i64)>, n: usize) -> Vec<i64> {
-    to_vec(poly, n)
+fn to_string_vec(coefficients: Vec<i64>) -> Vec<String> {
+    coefficients.into_iter().map(|x| x.to_string()).collect()
 }
 
 fn main() -> Result<()> {
     let cli = Cli::parse();
+    init_logging(cli.log);
 
     if cli.log {
         if let Commands::Falcon { .. } = &cli.command {
             // Falcon handles logging per case
         } else {
             let input_path: &Path = match &cli.command {
-                Commands::Parse { r1cs_file } => r1cs_file,
-                Commands::Compile { circom_file, .. } => circom_file,
+                Commands::Parse { r1cs_file, .. }
+                | Commands::Stats { r1cs_file }
+                | Commands::Graph { r1cs_file, .. }
+                | Commands::Prove { r1cs_file, .. }
+                | Commands::Verify { r1cs_file, .. }
+                | Commands::Bench { r1cs_file, .. }
+                | Commands::CheckWitness { r1cs_file, .. } => r1cs_file,
+                Commands::GenParams { out_file, .. } => out_file,
+                Commands::Compile { circom_file, .. } | Commands::Pipeline { circom_file, .. } => {
+                    circom_file
+                }
                 Commands::Generate { template_file, .. } => template_file,
+                #[cfg(feature = "http")]
+                Commands::Serve { .. } => Path::new("serve"),
                 _ => unreachable!(),
             };
             let log_path = input_path.with_extension("log");
             let file = File::create(log_path)?;
-            *LOG_FILE.lock().unwrap() = Some(file);
+            *log_file() = Some(file);
         }
     }
 
     match &cli.command {
-        Commands::Parse { r1cs_file } => parse(r1cs_file),
+        Commands::Parse { r1cs_file, format } => parse(r1cs_file, *format),
+        Commands::Stats { r1cs_file } => stats(r1cs_file),
+        Commands::Graph {
+            r1cs_file,
+            out_file,
+            constraints,
+            sym_file,
+        } => graph(
+            r1cs_file,
+            out_file,
+            constraints.clone(),
+            sym_file.as_deref(),
+        ),
+        Commands::Prove {
+            r1cs_file,
+            wtns_file,
+            out_file,
+            params_file,
+        } => prove(r1cs_file, wtns_file, out_file, params_file.as_deref()),
+        Commands::Verify {
+            r1cs_file,
+            proof_file,
+            public_file,
+            params_file,
+        } => verify(
+            r1cs_file,
+            proof_file,
+            public_file.as_deref(),
+            params_file.as_deref(),
+        )
+        .map(|_| ()),
+        Commands::GenParams {
+            out_file,
+            seed,
+            distance,
+        } => gen_params(out_file, seed.as_deref(), *distance),
+        Commands::Bench {
+            r1cs_file,
+            wtns_file,
+            runs,
+            phases,
+            format,
+        } => bench(r1cs_file, wtns_file, *runs, *phases, *format),
+        Commands::CheckWitness {
+            r1cs_file,
+            wtns_file,
+            sym_file,
+        } => check_witness(r1cs_file, wtns_file, sym_file.as_deref()),
+        Commands::Pipeline {
+            circom_file,
+            input_file,
+            out_dir,
+            optimization,
+            cpp,
+        } => pipeline(
+            circom_file,
+            input_file,
+            out_dir.clone(),
+            optimization.level(),
+            *cpp,
+            !cli.no_cache,
+        ),
         Commands::Compile {
             circom_file,
             optimization,
         } => {
-            let r1cs_file_path = compile(circom_file, optimization.level())?;
-            parse(&r1cs_file_path)
+            let r1cs_file_path = compile(
+                circom_file,
+                optimization.level(),
+                false,
+                !cli.no_cache,
+                None,
+            )?
+            .r1cs;
+            parse(&r1cs_file_path, OutputFormat::Text)
         }
         Commands::Generate {
             template_file,
@@ -268,17 +640,45 @@ fn main() -> Result<()> {
             let mut rng = thread_rng();
             let pk: Vec<i64> = (0..*n).map(|_| rng.gen()).collect();
             let circom_file_path = generate(template_file, None, 12289, pk)?;
-            let r1cs_file_path = compile(&circom_file_path, optimization.level())?;
-            parse(&r1cs_file_path)
+            let r1cs_file_path = compile(
+                &circom_file_path,
+                optimization.level(),
+                false,
+                !cli.no_cache,
+                None,
+            )?
+            .r1cs;
+            parse(&r1cs_file_path, OutputFormat::Text)
         }
+        #[cfg(feature = "http")]
+        Commands::Serve {
+            listen,
+            max_body_bytes,
+            timeout,
+            max_concurrent_proofs,
+            max_concurrent_verifications,
+            threads,
+        } => serve(
+            *listen,
+            HttpConfig {
+                max_body_bytes: *max_body_bytes,
+                timeout: Duration::from_secs(*timeout),
+                max_concurrent_proofs: *max_concurrent_proofs,
+                max_concurrent_verifications: *max_concurrent_verifications,
+                parallelism: *threads,
+            },
+        ),
         Commands::Falcon {
             template_file,
             input,
             case,
             optimization,
+            cpp,
+            jobs,
         } => {
             let toml_str = fs::read_to_string(input)?;
-            let falcon_cases: FalconCases = toml::from_str(&toml_str)?;
+            let mut falcon_cases: FalconCases = toml::from_str(&toml_str)?;
+            falcon_cases.resolve_files(input.parent().unwrap());
 
             if let Some(case_index) = case {
                 let case = &falcon_cases.cases[*case_index];
@@ -287,11 +687,31 @@ fn main() -> Result<()> {
                     case,
                     *case_index,
                     optimization.level(),
+                    *cpp,
+                    !cli.no_cache,
                     cli.log,
                 )?;
+            } else if *jobs > 1 {
+                run_falcon_cases_in_parallel(
+                    template_file,
+                    input,
+                    falcon_cases.cases.len(),
+                    optimization.level(),
+                    *cpp,
+                    !cli.no_cache,
+                    *jobs,
+                )?;
             } else {
                 for (i, case) in falcon_cases.cases.iter().enumerate() {
-                    run_falcon_case(template_file, case, i, optimization.level(), cli.log)?;
+                    run_falcon_case(
+                        template_file,
+                        case,
+                        i,
+                        optimization.level(),
+                        *cpp,
+                        !cli.no_cache,
+                        cli.log,
+                    )?;
                 }
             }
 
@@ -300,41 +720,157 @@ fn main() -> Result<()> {
     }
 }
 
+/// The name of the circom file generated for the case at `case_index`, without its extension
+fn falcon_case_name(template_file: &Path, case_index: usize) -> String {
+    let file_stem = template_file.file_stem().unwrap().to_str().unwrap();
+    format!("{}_{}", file_stem, case_index)
+}
+
+/// Where the case at `case_index` logs to with --log
+fn falcon_case_log_path(template_file: &Path, case_index: usize) -> PathBuf {
+    let name = falcon_case_name(template_file, case_index);
+    template_file
+        .parent()
+        .unwrap()
+        .join(&name)
+        .join(format!("{}.log", name))
+}
+
+/// How a case run by `run_falcon_cases_in_parallel` went
+struct FalconCaseRun {
+    case_index: usize,
+    time: Duration,
+    /// The error the case failed with, if it did
+    error: Option<String>,
+}
+
+/// Runs the first `num_cases` cases of `input`, `jobs` at a time, each in a process of its own running this binary with --log
+/// so its output goes to its own log file. Prints a table of the cases' results once all have finished, and fails if any case did
+fn run_falcon_cases_in_parallel(
+    template_file: &Path,
+    input: &Path,
+    num_cases: usize,
+    optimization_level: OptimizationLevel,
+    cpp: bool,
+    cache: bool,
+    jobs: usize,
+) -> Result<()> {
+    let exe = env::current_exe()?;
+    let next_case = AtomicUsize::new(0);
+    let runs = Mutex::new(Vec::with_capacity(num_cases));
+    thread::scope(|scope| {
+        for _ in 0..jobs.min(num_cases) {
+            scope.spawn(|| loop {
+                let case_index = next_case.fetch_add(1, Ordering::Relaxed);
+                if case_index >= num_cases {
+                    break;
+                }
+                let mut command = Command::new(&exe);
+                command
+                    .arg("falcon")
+                    .arg(template_file)
+                    .arg("--input")
+                    .arg(input)
+                    .arg("--case")
+                    .arg(case_index.to_string())
+                    .arg(format!("--{}", optimization_level).to_lowercase())
+                    .arg("--log");
+                if cpp {
+                    command.arg("--cpp");
+                }
+                if !cache {
+                    command.arg("--no-cache");
+                }
+
+                let start = Instant::now();
+                let error = match command.output() {
+                    Ok(output) if output.status.success() => None,
+                    // main's error or the panic message, which --log leaves on stderr, without any backtrace after it
+                    Ok(output) => Some(
+                        String::from_utf8_lossy(&output.stderr)
+                            .lines()
+                            .find(|line| {
+                                line.starts_with("Error: ") || line.contains("panicked at")
+                            })
+                            .map_or_else(
+                                || output.status.to_string(),
+                                |line| line.trim_start_matches("Error: ").to_string(),
+                            ),
+                    ),
+                    Err(e) => Some(format!("Could not run {}: {}", exe.display(), e)),
+                };
+                runs.lock().unwrap().push(FalconCaseRun {
+                    case_index,
+                    time: start.elapsed(),
+                    error,
+                });
+            });
+        }
+    });
+
+    let mut runs = runs.into_inner().unwrap();
+    runs.sort_by_key(|run| run.case_index);
+    println!("{:>6}  {:<6}  {:>10}  Log", "Case", "Result", "Time");
+    for run in &runs {
+        println!(
+            "{:>6}  {:<6}  {:>9.1}s  {}",
+            run.case_index,
+            if run.error.is_none() { "ok" } else { "failed" },
+            run.time.as_secs_f64(),
+            falcon_case_log_path(template_file, run.case_index).display()
+        );
+    }
+    let failed = runs
+        .iter()
+        .filter_map(|run| run.error.as_ref().map(|e| (run.case_index, e)))
+        .collect::<Vec<_>>();
+    for (case_index, error) in &failed {
+        error!("Case {}: {}", case_index, error);
+    }
+    if !failed.is_empty() {
+        bail!("{} of {} cases failed", failed.len(), runs.len());
+    }
+    Ok(())
+}
+
 fn run_falcon_case(
     template_file: &Path,
     case: &FalconCase,
     case_index: usize,
     optimization_level: OptimizationLevel,
+    cpp: bool,
+    cache: bool,
     log: bool,
 ) -> Result<()> {
     let file_stem = template_file.file_stem().unwrap().to_str().unwrap();
     let dir = template_file.parent().unwrap();
-    let circom_file_name = format!("{}_{}", file_stem, case_index);
+    let circom_file_name = falcon_case_name(template_file, case_index);
     let circom_file_path = dir.join(format!("{}.circom", circom_file_name));
 
     if log {
-        let output_dir = dir.join(&circom_file_name);
-        fs::create_dir_all(&output_dir)?;
-        let log_path = output_dir.join(format!("{}.log", circom_file_name));
+        let log_path = falcon_case_log_path(template_file, case_index);
+        fs::create_dir_all(log_path.parent().unwrap())?;
         let file = File::create(log_path)?;
-        *LOG_FILE.lock().unwrap() = Some(file);
+        *log_file() = Some(file);
     }
 
-    log_println!("=== Running Falcon Case {} ===\n", case_index);
-    let pk = to_i64_vec(&parse_poly(&case.pk), case.n);
-    let s1 = to_string_vec(&parse_poly(&case.s1), case.n);
-    let s2 = to_string_vec(&parse_poly(&case.s2), case.n);
-    let h = to_string_vec(&parse_poly(&case.h), case.n);
-    let c = to_string_vec(&parse_poly(&case.c), case.n);
+    info!("=== Running Falcon Case {} ===", case_index);
+    // pk and h are in Z_q. The signature's s1 and s2 are small, and c, the product they check, isn't reduced
+    let pk = case.coefficients("pk", &case.pk, true)?;
+    let s1 = to_string_vec(case.coefficients("s1", &case.s1, false)?);
+    let s2 = to_string_vec(case.coefficients("s2", &case.s2, false)?);
+    let h = to_string_vec(case.coefficients("h", &case.h, true)?);
+    let c = to_string_vec(case.coefficients("c", &case.c, false)?);
 
     let circom_file_path = generate(template_file, Some(circom_file_path), case.q, pk)?;
 
-    let r1cs_file_path = compile(&circom_file_path, optimization_level)?;
+    let compiled = compile(&circom_file_path, optimization_level, cpp, cache, None)?;
+    let r1cs_file_path = compiled.r1cs;
     let artifact_dir = r1cs_file_path.parent().unwrap();
 
     let input_json_path = artifact_dir.join(format!("input_{}.json", case_index));
 
-    log_println!("=== Generating input.json ===\n");
+    info!("=== Generating input.json ===");
     let mut output_map = BTreeMap::new();
     output_map.insert("s1", s1);
     output_map.insert("s2", s2);
@@ -345,70 +881,605 @@ fn run_falcon_case(
     let mut file = File::create(&input_json_path)?;
     file.write_all(json_str.as_bytes())?;
 
-    log_println!("Successfully wrote to {}\n", input_json_path.display());
+    info!("Successfully wrote to {}", input_json_path.display());
+
+    info!("{}", read_r1cs_file(&r1cs_file_path)?);
+
+    match &compiled.cpp {
+        Some(cpp_dir) => generate_witness_cpp(cpp_dir, artifact_dir, case_index, &input_json_path)?,
+        None => generate_witness(artifact_dir, file_stem, case_index, &input_json_path)?,
+    }
+
+    Ok(())
+}
+
+fn parse(r1cs_file_path: &Path, format: OutputFormat) -> Result<()> {
+    info!("=== Parsing R1CS File ===");
+    let r1cs_file = read_r1cs_file(r1cs_file_path)?;
+    match format {
+        OutputFormat::Text => println!("{}", r1cs_file),
+        OutputFormat::Json => {
+            println!("{}", serde_json::to_string_pretty(&r1cs_file.to_json())?)
+        }
+    }
+    Ok(())
+}
 
-    parse(&r1cs_file_path)?;
+fn stats(r1cs_file_path: &Path) -> Result<()> {
+    info!("=== Analyzing R1CS File ===");
+    let stats = analyze(&read_circuit(r1cs_file_path)?);
+    println!("{}", stats);
+    println!(
+        "Estimated proving time: {:.2?}",
+        stats.estimated_prove_time(field_ops_per_second::<Fr>())
+    );
+    Ok(())
+}
 
-    generate_witness(&artifact_dir, file_stem, case_index, &input_json_path)?;
+/// Parses a range of the form `start..end`
+fn parse_range(range: &str) -> Result<Range<usize>> {
+    let (start, end) = range
+        .split_once("..")
+        .context("expected a range of the form start..end")?;
+    let (start, end) = (start.trim().parse()?, end.trim().parse()?);
+    if start >= end {
+        bail!("the range {}..{} is empty", start, end);
+    }
+    Ok(start..end)
+}
 
+fn graph(
+    r1cs_file_path: &Path,
+    out_file_path: &Path,
+    constraints: Option<Range<usize>>,
+    sym_file_path: Option<&Path>,
+) -> Result<()> {
+    info!("=== Drawing R1CS File ===");
+    let circuit = read_circuit(r1cs_file_path)?;
+    let symbols = read_symbols(r1cs_file_path, sym_file_path)?;
+    let num_constraints = circuit.r1cs.num_constraints();
+    let constraints = constraints.unwrap_or(0..num_constraints);
+    if constraints.start >= num_constraints {
+        bail!(
+            "the circuit has {} constraints, so none are in {}..{}",
+            num_constraints,
+            constraints.start,
+            constraints.end
+        );
+    }
+    let file = File::create(out_file_path).context(format!(
+        "Could not create graph file: {}",
+        out_file_path.display()
+    ))?;
+    let mut writer = BufWriter::new(file);
+    write_dot(&mut writer, &circuit, constraints.clone(), symbols.as_ref())?;
+    writer.flush()?;
+    info!(
+        "Wrote constraints {}..{} of {} to {}",
+        constraints.start,
+        constraints.end.min(num_constraints),
+        num_constraints,
+        out_file_path.display()
+    );
     Ok(())
 }
 
-fn parse(r1cs_file_path: &Path) -> Result<()> {
-    log_println!("=== Parsing R1CS File ===\n ");
+fn read_r1cs_file(r1cs_file_path: &Path) -> Result<R1CSFile<Fr>> {
     let file = File::open(r1cs_file_path).context(format!(
         "Could not open R1CS file: {}",
         r1cs_file_path.display()
     ))?;
-    let reader = BufReader::new(file);
-    let r1cs_file = R1CSFile::from_reader(reader).context("Failed to parse R1CS file")?;
-    log_println!("{}", r1cs_file);
+    R1CSFile::from_reader(BufReader::new(file)).context("Failed to parse R1CS file")
+}
+
+fn read_circuit(r1cs_file_path: &Path) -> Result<R1CSWithMetadata<Fr>> {
+    Ok(read_r1cs_file(r1cs_file_path)?.to_crate_format()?)
+}
+
+/// The symbol table in `sym_file_path`, or in the .sym file next to the .r1cs file if there is one
+fn read_symbols(
+    r1cs_file_path: &Path,
+    sym_file_path: Option<&Path>,
+) -> Result<Option<SymbolTable>> {
+    let default_sym_path = r1cs_file_path.with_extension("sym");
+    match sym_file_path {
+        Some(path) => Some(path),
+        None => Some(default_sym_path.as_path()).filter(|path| path.exists()),
+    }
+    .map(|path| -> Result<SymbolTable> {
+        let file =
+            File::open(path).context(format!("Could not open symbol file: {}", path.display()))?;
+        SymbolTable::from_reader(BufReader::new(file)).context("Failed to parse symbol file")
+    })
+    .transpose()
+}
+
+fn read_witness(wtns_file_path: &Path) -> Result<FVec<Fr>> {
+    let file = File::open(wtns_file_path).context(format!(
+        "Could not open witness file: {}",
+        wtns_file_path.display()
+    ))?;
+    wtns_from_reader(BufReader::new(file)).context("Failed to parse witness file")
+}
+
+/// The code parameters in `path`, or the default code's if there is none
+fn read_code_params(path: Option<&Path>) -> Result<CodeParams> {
+    let Some(path) = path else {
+        return Ok(CodeParams::default());
+    };
+    let bytes = fs::read(path).context(format!(
+        "Could not open code parameters: {}",
+        path.display()
+    ))?;
+    CodeParams::from_bytes(&bytes).context("Failed to parse code parameters")
+}
+
+fn gen_params(out_file_path: &Path, seed: Option<&str>, distance: Option<usize>) -> Result<()> {
+    info!("=== Generating Code Parameters ===");
+    let params = match seed {
+        Some(seed) => CodeParams::from_seed(CodePreset::RAAADefault, seed.as_bytes()),
+        None => CodeParams::random(CodePreset::RAAADefault, &mut thread_rng()),
+    };
+    let code = params.code();
+    println!(
+        "RAAA code with n = {}, k = {}, and {} interleaves",
+        code.n(),
+        code.k(),
+        code.permutations.len()
+    );
+    println!("Digest: {}", hex::encode(params.digest()));
+
+    if let Some(distance) = distance {
+        if distance > code.n() {
+            bail!(
+                "the distance must be at most the code's length {}",
+                code.n()
+            );
+        }
+        let start_time = Instant::now();
+        // The estimator bounds the expected number of codewords of each weight under `distance` over codes of these dimensions.
+        // By Markov's inequality, their sum bounds the probability that one of them has a codeword that light
+        let (k, repeat_iowe, transition_prob) =
+            values_for_rma_code(code.q, code.n(), code.permutations.len());
+        let bound = (1..distance)
+            .map(|h| expected_num_outputs_with_weight(k, &repeat_iowe, &transition_prob, h))
+            .sum::<BigDecimal>();
+        println!(
+            "Probability of minimum distance under {}: at most {} (estimated in {:.2?})",
+            distance,
+            bound,
+            start_time.elapsed()
+        );
+    }
+
+    fs::write(out_file_path, params.to_bytes()).context(format!(
+        "Could not write code parameters to {}",
+        out_file_path.display()
+    ))?;
+    info!("Wrote the code parameters to {}", out_file_path.display());
     Ok(())
 }
 
-fn compile(circom_file_path: &Path, optimization_level: OptimizationLevel) -> Result<PathBuf> {
-    let circom_file_stem = circom_file_path.file_stem().unwrap().to_str().unwrap();
-    let parent_dir = circom_file_path.parent().unwrap();
-    let output_dir = parent_dir.join(circom_file_stem);
+fn check_witness(
+    r1cs_file_path: &Path,
+    wtns_file_path: &Path,
+    sym_file_path: Option<&Path>,
+) -> Result<()> {
+    info!("=== Checking Witness ===");
+    let circuit = read_circuit(r1cs_file_path)?;
+    let witness = read_witness(wtns_file_path)?;
+    if witness.0.len() != circuit.unpadded_wtns_len {
+        bail!(
+            "the witness has {} values but the circuit has {} wires",
+            witness.0.len(),
+            circuit.unpadded_wtns_len
+        );
+    }
+    let symbols = read_symbols(r1cs_file_path, sym_file_path)?;
 
-    fs::create_dir_all(&output_dir)?;
+    let unsatisfied = circuit.unsatisfied_constraints(&witness);
+    let num_constraints = circuit.r1cs.num_constraints();
+    if unsatisfied.is_empty() {
+        println!("All {} constraints are satisfied", num_constraints);
+        return Ok(());
+    }
+    for constraint in &unsatisfied {
+        match &symbols {
+            Some(symbols) => println!("{}", symbols.describe(&circuit, constraint)),
+            None => println!("{}", constraint),
+        }
+    }
+    bail!(
+        "{} of {} constraints are unsatisfied",
+        unsatisfied.len(),
+        num_constraints
+    )
+}
 
-    log_println!("=== Compiling Circom File ===\n");
-    log_println!(
-        "Compiling {} with optimization {}... Outputting to {}",
-        circom_file_path.display(),
-        optimization_level,
-        output_dir.display()
+fn prove(
+    r1cs_file_path: &Path,
+    wtns_file_path: &Path,
+    out_file_path: &Path,
+    params_file_path: Option<&Path>,
+) -> Result<()> {
+    info!("=== Proving ===");
+    let start_time = Instant::now();
+    let circuit = read_circuit(r1cs_file_path)?;
+    let witness = read_witness(wtns_file_path)?;
+    let params = read_code_params(params_file_path)?;
+    info!(
+        "Loaded the circuit and witness in {:.2?}",
+        start_time.elapsed()
     );
 
     let start_time = Instant::now();
-    let output = Command::new("circom")
-        .arg(circom_file_path)
-        .arg("--r1cs")
-        .arg("--wasm")
-        .arg(format!("--{}", optimization_level))
-        .arg("-o")
-        .arg(&output_dir)
-        .output()
-        .context("Failed to execute circom command. Is circom installed and in your PATH?")?;
-    let elapsed_time = start_time.elapsed();
+    let mut prover = Prover::builder(witness, circuit)
+        .code_params(params)
+        .build()?;
+    let proof = prover.commit_and_prove()?;
+    info!("Proved in {:.2?}", start_time.elapsed());
 
-    if !output.status.success() {
-        log_eprintln!("Error during circom compilation:");
-        log_eprintln!("{}", String::from_utf8_lossy(&output.stderr));
-        anyhow::bail!("Circom compilation failed");
+    let bytes = proof.to_bytes()?;
+    fs::write(out_file_path, &bytes).context(format!(
+        "Could not write proof to {}",
+        out_file_path.display()
+    ))?;
+    info!(
+        "Wrote a {} byte proof to {}",
+        bytes.len(),
+        out_file_path.display()
+    );
+    Ok(())
+}
+
+#[cfg(feature = "http")]
+fn serve(listen: SocketAddr, config: HttpConfig) -> Result<()> {
+    tokio::runtime::Runtime::new()?.block_on(async {
+        let listener = tokio::net::TcpListener::bind(listen)
+            .await
+            .context(format!("Could not listen on {listen}"))?;
+        info!("Serving /prove and /verify on http://{listen}");
+        axum::serve(listener, router(config)).await?;
+        Ok(())
+    })
+}
+
+fn pipeline(
+    circom_file_path: &Path,
+    input_json_path: &Path,
+    out_dir: Option<PathBuf>,
+    optimization_level: OptimizationLevel,
+    cpp: bool,
+    cache: bool,
+) -> Result<()> {
+    let start_time = Instant::now();
+    let compiled = compile(circom_file_path, optimization_level, cpp, cache, out_dir)?;
+    let out_dir = &compiled.output_dir;
+    let wtns_path = out_dir.join("witness.wtns");
+    match (&compiled.cpp, &compiled.wasm) {
+        (Some(cpp_dir), _) => run_cpp_witness_generator_for(cpp_dir, input_json_path, &wtns_path)?,
+        (None, Some(wasm_path)) => calculate_witness(wasm_path, input_json_path, &wtns_path)?,
+        (None, None) => bail!("circom wrote no witness calculator"),
     }
-    log_println!(
-        "Compilation successful in {:.2?}s.\n",
-        elapsed_time.as_secs()
+    let proof_path = out_dir.join("proof.bin");
+    prove(&compiled.r1cs, &wtns_path, &proof_path, None)?;
+    let public_values = verify(&compiled.r1cs, &proof_path, None, None)?;
+
+    // The public values as snarkjs writes them, so `verify --public` can check them
+    let public_path = out_dir.join("public.json");
+    let public_json = public_values
+        .public_outputs
+        .iter()
+        .chain(public_values.public_inputs.iter())
+        .map(to_decimal)
+        .collect::<Vec<_>>();
+    fs::write(&public_path, serde_json::to_string_pretty(&public_json)?)?;
+    info!(
+        "Pipeline finished in {:.2?}. The circuit, witness, proof, and public values are in {}",
+        start_time.elapsed(),
+        out_dir.display()
+    );
+    Ok(())
+}
+
+fn verify(
+    r1cs_file_path: &Path,
+    proof_file_path: &Path,
+    public_file_path: Option<&Path>,
+    params_file_path: Option<&Path>,
+) -> Result<PublicUOpenings<Fr>> {
+    info!("=== Verifying ===");
+    let circuit = read_circuit(r1cs_file_path)?;
+    let params = read_code_params(params_file_path)?;
+    let bytes = fs::read(proof_file_path).context(format!(
+        "Could not open proof file: {}",
+        proof_file_path.display()
+    ))?;
+    let proof = CommitAndProof::<Fr>::from_bytes(&bytes).context("Failed to decode proof")?;
+    let expected = public_file_path
+        .map(|path| read_public_values(path, circuit.public_outputs_indices.len()))
+        .transpose()?;
+
+    let start_time = Instant::now();
+    let verifier = Verifier::from_verifying_key(VerifyingKey::with_code_params(circuit, params));
+    let public_values = match expected {
+        Some(expected) => verifier
+            .verify_with_public(&proof, &expected)
+            .map(|_| expected),
+        None => verifier.verify(&proof),
+    }
+    .context("The proof is invalid")?;
+    println!(
+        "The proof is valid (verified in {:.2?})",
+        start_time.elapsed()
+    );
+    println!("Public outputs: {:?}", public_values.public_outputs);
+    println!("Public inputs: {:?}", public_values.public_inputs);
+    Ok(public_values)
+}
+
+/// Reads public values in snarkjs's public.json format, whose first `num_outputs` values are the public outputs
+fn read_public_values(path: &Path, num_outputs: usize) -> Result<PublicUOpenings<Fr>> {
+    let file = File::open(path).context(format!(
+        "Could not open public values file: {}",
+        path.display()
+    ))?;
+    let values: Vec<String> = serde_json::from_reader(BufReader::new(file))
+        .context("Public values must be a JSON array of decimal strings")?;
+    if values.len() < num_outputs {
+        bail!(
+            "{} public values were given but the circuit has {} public outputs",
+            values.len(),
+            num_outputs
+        );
+    }
+    let mut values = values
+        .iter()
+        .map(|value| {
+            Fr::from_str_vartime(value)
+                .with_context(|| format!("Public value {} isn't a decimal field element", value))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let public_inputs = values.split_off(num_outputs);
+    Ok(PublicUOpenings {
+        public_inputs,
+        public_outputs: values,
+    })
+}
+
+/// The phases `bench --phases` times, with their names in its output
+const BENCH_PHASES: [(Phase, &str); 5] = [
+    (Phase::VoleGeneration, "vole"),
+    (Phase::Correction, "correction"),
+    (Phase::ConsistencyCheck, "consistency"),
+    (Phase::Quicksilver, "zkp"),
+    (Phase::SMatrix, "s_matrix"),
+];
+
+/// Times of one benchmark run
+struct BenchRun {
+    prove: Duration,
+    /// Time spent in each of `BENCH_PHASES`, if they were timed
+    phases: Vec<Duration>,
+    serialize: Duration,
+    proof_bytes: usize,
+    memory: MemoryReport,
+}
+
+fn bench(
+    r1cs_file_path: &Path,
+    wtns_file_path: &Path,
+    runs: usize,
+    phases: bool,
+    format: BenchFormat,
+) -> Result<()> {
+    if runs == 0 {
+        bail!("at least one run is needed");
+    }
+    let circuit = read_circuit(r1cs_file_path)?;
+    let witness = read_witness(wtns_file_path)?;
+
+    // The first run warms up caches and the allocator and isn't counted
+    let results = (0..=runs)
+        .map(|_| bench_run(&witness, &circuit, phases))
+        .collect::<Result<Vec<_>>>()?;
+    let results = &results[1..];
+
+    match format {
+        BenchFormat::Text => {
+            println!("Proof size: {} bytes", results[0].proof_bytes);
+            println!("Benchmark results ({} runs):", runs);
+            print_bench_summary("prove", results.iter().map(|r| r.prove));
+            for (i, (_, name)) in BENCH_PHASES.iter().enumerate().filter(|_| phases) {
+                print_bench_summary(name, results.iter().map(|r| r.phases[i]));
+            }
+            print_bench_summary("serialize", results.iter().map(|r| r.serialize));
+            print_memory_report(&results[0].memory);
+        }
+        BenchFormat::Csv => {
+            let mut header = vec!["run".to_string(), "prove_s".to_string()];
+            if phases {
+                header.extend(BENCH_PHASES.iter().map(|(_, name)| format!("{}_s", name)));
+            }
+            header.extend(
+                [
+                    "serialize_s",
+                    "proof_bytes",
+                    "prover_bytes",
+                    "peak_heap_bytes",
+                    "peak_rss_bytes",
+                ]
+                .map(String::from),
+            );
+            println!("{}", header.join(","));
+            for (i, r) in results.iter().enumerate() {
+                let mut row = vec![i.to_string(), r.prove.as_secs_f64().to_string()];
+                row.extend(r.phases.iter().map(|d| d.as_secs_f64().to_string()));
+                row.push(r.serialize.as_secs_f64().to_string());
+                row.push(r.proof_bytes.to_string());
+                row.push(r.memory.footprint().to_string());
+                row.push(r.memory.peak_heap.map_or(String::new(), |b| b.to_string()));
+                row.push(r.memory.peak_rss.map_or(String::new(), |b| b.to_string()));
+                println!("{}", row.join(","));
+            }
+        }
+        BenchFormat::Json => {
+            let runs = results
+                .iter()
+                .map(|r| {
+                    let mut run = serde_json::Map::new();
+                    run.insert("prove_s".into(), r.prove.as_secs_f64().into());
+                    for ((_, name), d) in BENCH_PHASES.iter().zip(r.phases.iter()) {
+                        run.insert(format!("{}_s", name), d.as_secs_f64().into());
+                    }
+                    run.insert("serialize_s".into(), r.serialize.as_secs_f64().into());
+                    run.insert("proof_bytes".into(), r.proof_bytes.into());
+                    run.insert("prover_bytes".into(), r.memory.footprint().into());
+                    run.insert("peak_heap_bytes".into(), r.memory.peak_heap.into());
+                    run.insert("peak_rss_bytes".into(), r.memory.peak_rss.into());
+                    serde_json::Value::Object(run)
+                })
+                .collect::<Vec<_>>();
+            let report = serde_json::json!({
+                "r1cs": r1cs_file_path.display().to_string(),
+                "threads": std::thread::available_parallelism().map_or(1, |n| n.get()),
+                "runs": runs,
+            });
+            println!("{}", serde_json::to_string_pretty(&report)?);
+        }
+    }
+    Ok(())
+}
+
+/// Proves the witness once, timing it and, if `phases`, each of `BENCH_PHASES`
+fn bench_run(witness: &FVec<Fr>, circuit: &R1CSWithMetadata<Fr>, phases: bool) -> Result<BenchRun> {
+    let spans = Arc::new(Mutex::new(vec![(None, None); BENCH_PHASES.len()]));
+    let mut builder = ProverBuilder::new(witness.clone(), circuit.clone());
+    if phases {
+        let spans = spans.clone();
+        builder = builder.progress(move |phase: Phase, fraction: f64| {
+            let i = BENCH_PHASES.iter().position(|(p, _)| *p == phase).unwrap();
+            let span: &mut (Option<Instant>, Option<Instant>) = &mut spans.lock().unwrap()[i];
+            if fraction == 0.0 {
+                span.0.get_or_insert_with(Instant::now);
+            } else if fraction == 1.0 {
+                span.1 = Some(Instant::now());
+            }
+        });
+    }
+
+    #[cfg(feature = "mem-stats")]
+    volonym::memstats::reset_peak_heap();
+    let start_time = Instant::now();
+    let mut prover = builder.build()?;
+    let proof = prover.commit_and_prove()?;
+    let prove = start_time.elapsed();
+    let memory = prover.memory_report();
+    let start_time = Instant::now();
+    let proof_bytes = proof.to_bytes()?.len();
+    let serialize = start_time.elapsed();
+
+    let phases = spans
+        .lock()
+        .unwrap()
+        .iter()
+        .filter(|_| phases)
+        .map(|span| match span {
+            (Some(start), Some(end)) => *end - *start,
+            _ => Duration::ZERO,
+        })
+        .collect();
+    Ok(BenchRun {
+        prove,
+        phases,
+        serialize,
+        proof_bytes,
+        memory,
+    })
+}
+
+/// Prints the prover's structures' sizes and the peaks that are known
+fn print_memory_report(report: &MemoryReport) {
+    println!("Memory:");
+    for (name, bytes) in [
+        ("witness", report.witness),
+        ("witness commitment", report.witness_comm),
+        ("circuit", report.circuit),
+        ("subspace VOLE", report.subspace_vole),
+        ("witness products", report.witness_products),
+        ("prover total", report.footprint()),
+    ] {
+        println!("  {}: {} bytes", name, bytes);
+    }
+    if let Some(peak) = report.peak_heap {
+        println!("  peak heap: {} bytes", peak);
+    }
+    if let Some(peak) = report.peak_rss {
+        println!("  peak RSS: {} bytes", peak);
+    }
+}
+
+fn print_bench_summary(name: &str, durations: impl Iterator<Item = Duration>) {
+    let durations = durations.collect::<Vec<_>>();
+    let mean = durations.iter().sum::<Duration>() / durations.len() as u32;
+    let variance = durations
+        .iter()
+        .map(|d| (d.as_secs_f64() - mean.as_secs_f64()).powi(2))
+        .sum::<f64>()
+        / durations.len() as f64;
+    println!(
+        "  {}: mean {:.2?}, std dev {:.2?}, min {:.2?}, max {:.2?}",
+        name,
+        mean,
+        Duration::from_secs_f64(variance.sqrt()),
+        durations.iter().min().unwrap(),
+        durations.iter().max().unwrap()
     );
+}
 
-    let r1cs_file_name = circom_file_path
-        .file_stem()
-        .context("Could not get file stem")?;
-    let r1cs_file_path = output_dir.join(format!("{}.r1cs", r1cs_file_name.to_str().unwrap()));
+fn compile(
+    circom_file_path: &Path,
+    optimization_level: OptimizationLevel,
+    cpp: bool,
+    cache: bool,
+    output_dir: Option<PathBuf>,
+) -> Result<CompiledCircuit> {
+    let options = CompileOptions {
+        optimization: optimization_level,
+        output_dir,
+        cpp,
+        cache,
+        ..Default::default()
+    };
+
+    info!("=== Compiling Circom File ===");
+    info!(
+        "Compiling {} with optimization {}...",
+        circom_file_path.display(),
+        optimization_level,
+    );
 
-    Ok(r1cs_file_path)
+    let start_time = Instant::now();
+    let compiled = circom::compile::compile(circom_file_path, &options).inspect_err(|e| {
+        if let CompileError::Failed { stderr } = e {
+            error!("Error during circom compilation:\n{}", stderr);
+        }
+    })?;
+    let elapsed_time = start_time.elapsed();
+    if compiled.cached {
+        info!(
+            "The circuit is unchanged, reusing the output in {}",
+            compiled.output_dir.display()
+        );
+    } else {
+        info!(
+            "Compilation successful in {:.2?}s. Output is in {}",
+            elapsed_time.as_secs(),
+            compiled.output_dir.display()
+        );
+    }
+
+    Ok(compiled)
 }
 
 fn generate(
@@ -417,47 +1488,115 @@ fn generate(
     q: i64,
     pk: Vec<i64>,
 ) -> Result<PathBuf> {
-    log_println!("=== Generating Circom File from Template ===\n");
+    info!("=== Generating Circom File from Template ===");
     let circom_file_path = if let Some(output_path) = output_path {
         output_path
     } else {
         template_file_path.with_extension("circom")
     };
     generate_circom(&circom_file_path, template_file_path, q, pk)?;
-    log_println!("Generated Circom file: {}\n", circom_file_path.display());
+    info!("Generated Circom file: {}", circom_file_path.display());
     Ok(circom_file_path)
 }
 
+/// Builds and runs circom's C++ witness generator, writing the witness to the same .wtns file the other witness calculators would
+fn generate_witness_cpp(
+    cpp_dir: &Path,
+    artifact_dir: &Path,
+    case_index: usize,
+    input_json_path: &Path,
+) -> Result<()> {
+    let witness_wtns_path = artifact_dir.join(format!("witness_{}.wtns", case_index));
+    run_cpp_witness_generator_for(cpp_dir, input_json_path, &witness_wtns_path)
+}
+
+fn run_cpp_witness_generator_for(
+    cpp_dir: &Path,
+    input_json_path: &Path,
+    witness_wtns_path: &Path,
+) -> Result<()> {
+    info!("=== Building C++ Witness Generator ===");
+    let start_time = Instant::now();
+    let generator = build_cpp_witness_generator(cpp_dir).inspect_err(|e| {
+        if let CompileError::BuildFailed { stderr } = e {
+            error!("Error building the witness generator:\n{}", stderr);
+        }
+    })?;
+    info!(
+        "Build successful in {:.2?}s.",
+        start_time.elapsed().as_secs()
+    );
+
+    info!("=== Generating Witness ===");
+    let start_time = Instant::now();
+    run_cpp_witness_generator(&generator, input_json_path, witness_wtns_path).inspect_err(|e| {
+        if let CompileError::WitnessGeneration { stderr } = e {
+            error!("Error during witness generation:\n{}", stderr);
+        }
+    })?;
+    info!(
+        "Witness generation successful in {:.2?}s.",
+        start_time.elapsed().as_secs()
+    );
+
+    Ok(())
+}
+
+/// Calculates the witness of a Falcon case, writing it to witness_{case_index}.wtns next to the compiled circuit
 fn generate_witness(
     artifact_dir: &Path,
     file_stem: &str,
     case_index: usize,
     input_json_path: &Path,
 ) -> Result<()> {
-    let dir = artifact_dir.parent().unwrap();
-    // Run the witness generation command
-    let generate_witness_js_path = artifact_dir.strip_prefix(dir).unwrap().join(format!(
-        "{}_{}_js/generate_witness.js",
-        file_stem, case_index
-    ));
-    let test_wasm_path = artifact_dir.strip_prefix(dir).unwrap().join(format!(
+    let wasm_path = artifact_dir.join(format!(
         "{}_{}_js/{}_{}.wasm",
         file_stem, case_index, file_stem, case_index
     ));
-    let witness_wtns_path = artifact_dir
-        .strip_prefix(dir)
-        .unwrap()
-        .join(format!("witness_{}.wtns", case_index));
-    let input_json_rel_path = input_json_path.strip_prefix(dir).unwrap();
+    let witness_wtns_path = artifact_dir.join(format!("witness_{}.wtns", case_index));
+    calculate_witness(&wasm_path, input_json_path, &witness_wtns_path)
+}
 
-    log_println!("=== Generating Witness ===\n");
+/// Calculates the witness in process, writing it to the same .wtns file `generate_witness.js` would
+#[cfg(feature = "witness-wasm")]
+fn calculate_witness(
+    wasm_path: &Path,
+    input_json_path: &Path,
+    witness_wtns_path: &Path,
+) -> Result<()> {
+    info!("=== Generating Witness ===");
+    let start_time = Instant::now();
+    let inputs = serde_json::from_reader(BufReader::new(File::open(input_json_path)?))
+        .context("Failed to parse the input JSON")?;
+    let witness = WitnessCalculator::from_file(wasm_path)?
+        .calculate_witness(&inputs)
+        .context("Witness generation failed")?;
+    wtns_to_writer(&witness, BufWriter::new(File::create(witness_wtns_path)?))?;
+    let elapsed_time = start_time.elapsed();
+    info!(
+        "Witness generation successful in {:.2?}s.",
+        elapsed_time.as_secs()
+    );
+
+    Ok(())
+}
+
+/// Calculates the witness with the `generate_witness.js` circom writes next to the WebAssembly
+#[cfg(not(feature = "witness-wasm"))]
+fn calculate_witness(
+    wasm_path: &Path,
+    input_json_path: &Path,
+    witness_wtns_path: &Path,
+) -> Result<()> {
+    let generate_witness_js_path = wasm_path.with_file_name("generate_witness.js");
+
+    info!("=== Generating Witness ===");
     let start_time = Instant::now();
     let output = Command::new("node")
-        .current_dir(dir)
         .arg(&generate_witness_js_path)
-        .arg(&test_wasm_path)
-        .arg(input_json_rel_path)
-        .arg(&witness_wtns_path)
+        .arg(wasm_path)
+        .arg(input_json_path)
+        .arg(witness_wtns_path)
         .output()
         .context(
             "Failed to execute node command for witness generation. Is Node.js installed and in your PATH?",
@@ -465,14 +1604,16 @@ fn generate_witness(
     let elapsed_time = start_time.elapsed();
 
     if !output.status.success() {
-        log_eprintln!("Error during witness generation:");
-        log_eprintln!("{}", String::from_utf8_lossy(&output.stderr));
+        error!(
+            "Error during witness generation:\n{}",
+            String::from_utf8_lossy(&output.stderr)
+        );
         anyhow::bail!("Witness generation failed");
     }
-    log_println!(
-        "Witness generation successful in {:.2?}s.\n",
+    info!(
+        "Witness generation successful in {:.2?}s.",
         elapsed_time.as_secs()
     );
 
     Ok(())
-}
\ No newline at end of file
+}
@@ -0,0 +1,101 @@
+use anyhow::{anyhow, Context, Result};
+use clap::Parser;
+use std::{
+    fs::{self, File},
+    io::BufReader,
+    net::SocketAddr,
+    path::PathBuf,
+    thread,
+};
+use tracing::info;
+use tracing_subscriber::EnvFilter;
+use volonym::{
+    circom::r1cs::R1CSFile,
+    grpc::{ProvingServer, ServerConfig},
+    subspacevole::CodeParams,
+};
+
+/// Serves the gRPC ProvingService in proto/volonym.proto for the circuits it is started with.
+/// Logs requests and the library's diagnostics to stdout at the levels set by RUST_LOG, info by default
+#[derive(Parser, Debug)]
+#[command(author, version, about, long_about = None)]
+struct Cli {
+    /// Address to listen on.
+    #[arg(long, default_value = "127.0.0.1:50051")]
+    listen: SocketAddr,
+    /// A circuit to serve, as name=path/to/circuit.r1cs. Requests choose it by its name. May be given several times.
+    #[arg(long = "circuit", value_parser = parse_named_path, required = true)]
+    circuits: Vec<(String, PathBuf)>,
+    /// Code parameters from `r1cs_tool gen-params` requests may choose instead of the default code, as name=path/to/code.params.
+    /// May be given several times.
+    #[arg(long = "params", value_parser = parse_named_path)]
+    code_params: Vec<(String, PathBuf)>,
+    /// Proofs to make at once. Defaults to the number of CPUs divided by `--threads`.
+    #[arg(long)]
+    max_concurrent_proofs: Option<usize>,
+    /// Threads each proof expands its VOLEs on.
+    #[arg(long, default_value_t = 1)]
+    threads: usize,
+    /// Largest witness a request may upload, in bytes. Each request buffers its witness whole, so this bounds its memory.
+    #[arg(long, default_value_t = ServerConfig::default().max_witness_bytes)]
+    max_witness_bytes: usize,
+}
+
+fn parse_named_path(arg: &str) -> Result<(String, PathBuf)> {
+    let (name, path) = arg
+        .split_once('=')
+        .ok_or_else(|| anyhow!("expected name=path, got {arg:?}"))?;
+    Ok((name.to_string(), PathBuf::from(path)))
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let cli = Cli::parse();
+    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info"));
+    tracing_subscriber::fmt().with_env_filter(filter).init();
+    let threads = cli.threads.max(1);
+    let max_concurrent_proofs = cli.max_concurrent_proofs.unwrap_or_else(|| {
+        let cpus = thread::available_parallelism().map_or(1, |n| n.get());
+        (cpus / threads).max(1)
+    });
+    let mut server = ProvingServer::new(ServerConfig {
+        max_concurrent_proofs,
+        parallelism: threads,
+        max_witness_bytes: cli.max_witness_bytes,
+    });
+
+    for (name, path) in &cli.circuits {
+        let file =
+            File::open(path).context(format!("Could not open R1CS file: {}", path.display()))?;
+        let circuit = R1CSFile::from_reader(BufReader::new(file))
+            .context(format!("Failed to parse R1CS file: {}", path.display()))?
+            .to_crate_format()?;
+        info!(
+            "Loaded circuit {name} with {} constraints from {}",
+            circuit.r1cs.num_constraints(),
+            path.display()
+        );
+        server.add_circuit(name, circuit);
+    }
+    for (name, path) in &cli.code_params {
+        let bytes = fs::read(path).context(format!(
+            "Could not open code parameters: {}",
+            path.display()
+        ))?;
+        let params = CodeParams::from_bytes(&bytes).context(format!(
+            "Failed to parse code parameters: {}",
+            path.display()
+        ))?;
+        server.add_code_params(name, params);
+    }
+
+    info!(
+        "Listening on {}, making up to {max_concurrent_proofs} proofs at once",
+        cli.listen
+    );
+    tonic::transport::Server::builder()
+        .add_service(server.into_service())
+        .serve(cli.listen)
+        .await?;
+    Ok(())
+}
@@ -1,113 +1,370 @@
-//! Fiat-shamir challenges all in one place
+//! Fiat-shamir challenges all in one place.
+//!
+//! A proof's challenges come from one `Transcript`, which absorbs the prover's messages in the order they are sent:
+//! 1. the statement digest, the application's context, and the seed commitment (`proof_transcript`)
+//! 2. for circuits with lookups or memories, the rows of the witness commitment before the inverses, then the lookup challenges are squeezed
+//! 3. the whole witness commitment, then the Quicksilver challenges are squeezed
+//! 4. the Quicksilver proof and the public openings, and any disclosed openings
+//! 5. the grinding nonce, then ∆', the small VOLE ∆ choices, and the consistency check challenges are squeezed
+//!
+//! The subspace VOLE's consistency check has a transcript of its own with only the seed commitment, since the VOLE may be made before the circuit is known
+#[cfg(not(feature = "std"))]
+use crate::alloc_prelude::*;
 use crate::{
-    actors::actors::PublicOpenings, vecccom::expand_seed_to_field_vec, zkp::quicksilver::ZKP,
-    DotProduct, FMatrix, FVec, PF,
+    fields::ext::ExtensionField,
+    subspacevole::{CodeParams, CodePreset},
+    vith,
+    zkp::quicksilver::{PublicOpenings, ZKP},
+    FMatrix, FVec, PF,
 };
-use rand::{RngCore, SeedableRng};
-use rand_chacha::ChaCha12Rng;
+use rand::{CryptoRng, RngCore};
+use serde::{Deserialize, Serialize};
 
+pub mod transcript;
+use transcript::Transcript;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(bound(
+    serialize = "T: Serialize, T::Ext: Serialize",
+    deserialize = "T: Deserialize<'de>, T::Ext: Deserialize<'de>"
+))]
 pub struct Challenges<T: PF> {
     /// Small-field VOLE ∆ indices
     pub delta_choices: Vec<usize>,
-    /// VitH ∆'
-    pub vith_delta: T,
+    /// VitH ∆', from `T`'s challenge field
+    pub vith_delta: T::Ext,
     // /// TODO, low priority: making this the whole challenge vector that's generated from the quicksilver_challenge scalar
     // pub quicksilver_challenge: Fr,
-    /// Consistency check challelnge for the validity of the Q, U and V matrices
-    pub subspace_challenge: FVec<T>,
-    /// Consistency check challenge for the validity of the S matrix
-    pub s_challenge: FVec<T>,
-}
-/// Generates a vector of length `length` from a seed (e.g. from the commitment to the prover's seeds)
-/// Be careful not to call this twice the same seed unless that is intended -- it will generate the same randomness
-/// Hence, the salt is included to prevent this from easily happening on accident.
-pub fn challenge_from_seed<T: PF>(seed: &[u8], salt: &[u8], length: usize) -> FVec<T> {
-    let seed = { *blake3::hash(&[seed, salt].concat()).as_bytes() };
-    expand_seed_to_field_vec(seed, length)
-}
-
-pub fn calc_quicksilver_challenge<T: PF>(seed_comm: &[u8; 32], witness_comm: &FMatrix<T>) -> T {
-    // Universal hash of witness commitment to compress it to one value
-    let universal_inner = challenge_from_seed(
-        seed_comm,
-        &"quicksilver_inner".as_bytes(),
-        witness_comm.0.len(),
+    /// Consistency check challelnge for the validity of the Q, U and V matrices, as `vole_consistency_challenge`'s
+    pub subspace_challenge: Vec<T::Ext>,
+    /// Consistency check challenge for the validity of the S matrix, one element per row of U1
+    pub s_challenge: Vec<T::Ext>,
+}
+/// Most bits of grinding a `SecurityLevel` can call for. Proving then takes around 2^MAX_GRINDING_BITS extra hashes
+pub const MAX_GRINDING_BITS: usize = 32;
+
+/// The parameters a prover made its subspace VOLE with, which fix the dimensions of everything it sends.
+/// The prover sends them in its commitment and the verifier rejects a commitment whose parameters differ from the ones it derives
+/// from the circuit and code, so a prover and verifier set up differently fail with both sets of parameters rather than a dimension error
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProtocolParams {
+    pub code: CodePreset,
+    /// Number of small VOLEs, the code's length
+    pub num_voles: usize,
+    /// Length of each small VOLE, i.e. the subspace VOLE's number of rows
+    pub vole_length: usize,
+}
+
+/// Bits of statistical security a proof is made for. It is recorded in the proof's header so verifiers can require a minimum.
+///
+/// Soundness comes from the linear code's minimum distance, which determines how many small VOLE ∆s a cheating prover must guess.
+/// With the one code preset there is, the number of VOLEs is fixed, so bits beyond what the code provides come from grinding:
+/// before the ∆ choices are derived, the prover must find a nonce whose hash with the transcript starts with `grinding_bits` zero bits,
+/// making every attempt to reroll them that much more expensive
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct SecurityLevel(pub usize);
+
+impl Default for SecurityLevel {
+    fn default() -> Self {
+        Self(CodePreset::RAAADefault.security_bits())
+    }
+}
+
+impl SecurityLevel {
+    /// Bits of soundness one challenge from `T`'s challenge field gives, e.g. guessing VitH's ∆'
+    pub fn challenge_bits<T: PF>() -> usize {
+        T::CAPACITY as usize * T::Ext::DEGREE
+    }
+
+    /// Bits of grinding needed on top of `code` and a proof over `T`'s ∆' to reach this level
+    pub fn grinding_bits<T: PF>(&self, code: CodePreset) -> usize {
+        self.0
+            .saturating_sub(code.security_bits().min(Self::challenge_bits::<T>()))
+    }
+
+    /// Highest level a proof over `T` with `code` can have. Besides the code, a cheating prover can guess VitH's ∆',
+    /// which is from `T`'s challenge field, and both are rerolled only through the grinding nonce
+    pub fn max_for<T: PF>(code: CodePreset) -> Self {
+        Self(code.security_bits().min(Self::challenge_bits::<T>()) + MAX_GRINDING_BITS)
+    }
+
+    /// Challenge rows the subspace VOLE consistency check needs for this level over `T`. Each row is from `T`'s challenge field,
+    /// is sound except with probability about 2^-`challenge_bits`, and needs a row of U hiding each of its coordinates
+    pub fn consistency_rows<T: PF>(&self) -> usize {
+        self.0.div_ceil(Self::challenge_bits::<T>()).max(1)
+    }
+
+    /// Challenge rows of the consistency check of any subspace VOLE over `T`, enough for the highest level a proof over it can have.
+    /// The VOLE may be made before the level is chosen
+    pub fn vole_consistency_rows<T: PF>() -> usize {
+        Self::max_for::<T>(CodePreset::RAAADefault).consistency_rows::<T>()
+    }
+
+    /// Length of the subspace VOLE for a witness of `num_padded_wtns_rows` rows of the code's dimension. VitH splits it into
+    /// U1 and one block U2 per coordinate of ∆', each of the same rows: the witness's, then those hiding the consistency check's coordinates
+    pub fn vole_length<T: PF>(num_padded_wtns_rows: usize) -> usize {
+        (T::Ext::DEGREE + 1)
+            * (num_padded_wtns_rows + T::Ext::DEGREE * Self::vole_consistency_rows::<T>())
+    }
+
+    /// Number of independent challenges the Quicksilver checks are batched with. Batching `num_batched` constraints with powers of
+    /// one challenge from `T`'s challenge field is sound except with probability about `num_batched` / 2^`challenge_bits`,
+    /// so the checks are repeated with more challenges when that is too few bits for this level. Over BN254 it is always 1
+    pub fn quicksilver_repetitions<T: PF>(&self, num_batched: usize) -> usize {
+        let lost_bits = (usize::BITS - num_batched.leading_zeros()) as usize;
+        let bits_per_challenge = Self::challenge_bits::<T>().saturating_sub(lost_bits).max(1);
+        self.0.div_ceil(bits_per_challenge).max(1)
+    }
+}
+
+/// Challenge for the subspace VOLE consistency check, from the seed commitment alone:
+/// `SecurityLevel::vole_consistency_rows` rows of `vole_length` elements of `T`'s challenge field, one after another
+pub fn vole_consistency_challenge<T: PF>(seed_comm: &[u8; 32], vole_length: usize) -> Vec<T::Ext> {
+    let mut transcript = Transcript::new(b"volonym_vole");
+    transcript.append_message(b"seed_comm", seed_comm);
+    transcript.challenge_ext_vec::<T>(
+        b"vole_consistency_check",
+        SecurityLevel::vole_consistency_rows::<T>() * vole_length,
+    )
+}
+
+/// Starts the transcript of a proof of the statement with digest `statement` made with the VOLE whose seeds are committed to in `seed_comm`.
+/// `context` is whatever the application binds the proof to, e.g. a session ID, a nonce from the verifier, or the recipient,
+/// so the proof doesn't verify in any other context. It is empty if there is none
+pub fn proof_transcript(statement: &[u8; 32], context: &[u8], seed_comm: &[u8; 32]) -> Transcript {
+    let mut transcript = Transcript::new(b"volonym_proof");
+    transcript.append_message(b"statement", statement);
+    transcript.append_message(b"context", context);
+    transcript.append_message(b"seed_comm", seed_comm);
+    transcript
+}
+
+/// Digest of everything the proof is about besides the witness: the circuit and the protocol parameters.
+/// Every challenge after the VOLE is made depends on it, so a proof can't be replayed against a different statement even if it has the same shape.
+/// The VOLE's own consistency check doesn't depend on it since the VOLE may be made before the circuit is known
+pub fn statement_digest(
+    circuit_id: &[u8; 32],
+    code: &CodeParams,
+    num_voles: usize,
+    vole_length: usize,
+) -> [u8; 32] {
+    let mut hasher = blake3::Hasher::new();
+    hasher.update(b"volonym_statement");
+    hasher.update(circuit_id);
+    hasher.update(&code.digest());
+    hasher.update(&(num_voles as u64).to_le_bytes());
+    hasher.update(&(vole_length as u64).to_le_bytes());
+    *hasher.finalize().as_bytes()
+}
+
+/// `repetitions` Quicksilver challenges from `T`'s challenge field, from a transcript that has absorbed the witness commitment,
+/// see `SecurityLevel::quicksilver_repetitions`. The first is the same for any number of them
+pub fn calc_quicksilver_challenges<T: PF>(
+    transcript: &Transcript,
+    repetitions: usize,
+) -> Vec<T::Ext> {
+    let mut rng = transcript.challenge_rng(b"quicksilver");
+    (0..repetitions).map(|_| T::Ext::sample(&mut rng)).collect()
+}
+
+/// The lookups' and memories' α, and the memories' γ for compressing tuples, after absorbing the rows of the witness commitment
+/// holding the witness, the memories' values, and the lookup multiplicities.
+/// The rows after them hold the inverses that depend on the challenges, so they are absorbed after them with the rest of the commitment
+pub fn calc_lookup_challenges<T: PF>(
+    transcript: &mut Transcript,
+    committed_rows: &[FVec<T>],
+) -> (T, T) {
+    transcript.append_matrix(b"lookup_committed_rows", committed_rows);
+    let mut rng = transcript.challenge_rng(b"lookup");
+    (T::random(&mut rng), T::random(&mut rng))
+}
+
+/// Absorbs the Quicksilver proof and the public openings, which ∆' must depend on
+pub fn append_quicksilver_proof<T: PF>(
+    transcript: &mut Transcript,
+    zkp: &ZKP<T::Ext>,
+    public_openings: &PublicOpenings<T>,
+) {
+    for z in core::iter::once(zkp).chain(zkp.repetitions.iter()) {
+        transcript.append_ext::<T>(b"mul_proof", [z.mul_proof.0, z.mul_proof.1].iter());
+        transcript.append_ext::<T>(b"gate_proof", z.gate_proof.iter());
+        transcript.append_ext::<T>(b"branch_proof", z.branch_proof.iter());
+    }
+    transcript.append_ext::<T>(
+        b"public_inputs",
+        pairs(&public_openings.public_inputs).iter(),
     );
-    let universal_outer = challenge_from_seed(
-        seed_comm,
-        &"quicksilver_outer".as_bytes(),
-        witness_comm.0[0].0.len(),
+    transcript.append_ext::<T>(
+        b"public_outputs",
+        pairs(&public_openings.public_outputs).iter(),
     );
-    let compressed = universal_outer.dot(&(&universal_inner * witness_comm));
-    // Hashing may be unnecessary but is cheap and removes any potential linear correlation (i have not checekd whether that correlation would be problematic)
-    let digest = *blake3::hash(&compressed.to_u8s()).as_bytes();
-    T::random(&mut ChaCha12Rng::from_seed(digest))
 }
 
-/// Called by Verifier and Prover to calculate the original VOLE ∆s along with the ∆'
-/// seed commitment and ZKP as input
-/// Returns (subfield VOLE indices, VitH choice)
+/// (u, v) openings as u and v one after the other, each u lifted to the challenge field v is in
+fn pairs<T: PF>(openings: &[(T, T::Ext)]) -> Vec<T::Ext> {
+    openings
+        .iter()
+        .flat_map(|(u, v)| [T::Ext::from(*u), *v])
+        .collect()
+}
+
+/// Absorbs the witness indices a proof discloses beyond its public inputs and outputs, and their openings.
+/// Once ∆' is known the prover can open any index to any value, so which indices are opened must be fixed before it is derived
+pub fn append_disclosure<T: PF>(
+    transcript: &mut Transcript,
+    indices: &[usize],
+    openings: &[(T, T::Ext)],
+) {
+    transcript.append_u64(b"disclosed_indices", indices.len() as u64);
+    indices
+        .iter()
+        .for_each(|i| transcript.append_u64(b"index", *i as u64));
+    transcript.append_ext::<T>(b"disclosed_openings", pairs(openings).iter());
+}
+
+/// Called by Verifier and Prover to calculate the original VOLE ∆s along with the ∆', once the transcript has absorbed the Quicksilver proof,
+/// public openings, and grinding nonce.
 /// Important note: if u, v, q, ∆ are known to the prover, the prover can forge another (u, v) pair \
 /// that satisfies q = v + u∆
 /// therefore, the prover should open the public inputs before learning ∆. In Fiat-Shamir, ∆'s calculation should then include all prover ZKP and public openings
 pub fn calc_other_challenges<T: PF>(
-    seed_comm: &[u8; 32],
-    _witness_comm: &FMatrix<T>,
-    zkp: &ZKP<T>,
+    transcript: &Transcript,
     vole_length: usize,
     num_voles: usize,
-    public_openings: &PublicOpenings<T>,
 ) -> Challenges<T> {
-    // Fiat-Shamir
-    // TODO: double check it's fine to skip hashing the witness commitment. I am pretty confident it is:
-    // if the prover changes their witness commitment, they will get caught by it either
-    // 1. not being a valid witness
-    // 2. not corresponding to a valid VOLE
-    let mut frs = vec![zkp.mul_proof.0, zkp.mul_proof.1];
-    for i in 0..public_openings.public_inputs.len() {
-        frs.push(public_openings.public_inputs[i].0);
-        frs.push(public_openings.public_inputs[i].1);
-    }
-    for i in 0..public_openings.public_outputs.len() {
-        frs.push(public_openings.public_outputs[i].0);
-        frs.push(public_openings.public_outputs[i].1);
+    let vith_delta = transcript.challenge_ext::<T>(b"vith_delta");
+    let mut prg = transcript.challenge_rng(b"subspace_vole_challenge");
+    let delta_choices = (0..num_voles)
+        .map(|_| (prg.next_u32() % 2) as usize)
+        .collect();
+    Challenges {
+        delta_choices,
+        vith_delta,
+        subspace_challenge: transcript.challenge_ext_vec::<T>(
+            b"subspace_vole_consistency",
+            SecurityLevel::vole_consistency_rows::<T>() * vole_length,
+        ),
+        s_challenge: transcript
+            .challenge_ext_vec::<T>(b"s_matrix_consistency", vith::vole_rows::<T>(vole_length)),
     }
-    let concatted = &mut seed_comm.to_vec();
+}
 
-    // Concatenate Frs byte representation with seed commitment
-    // let mut concatted = Vec::with_capacity(32 * (1 + frs.len()));
+/// Finds the grinding nonce for a proof with `bits` bits of grinding, once the transcript has absorbed everything before it
+pub fn grind(transcript: &Transcript, bits: usize) -> u64 {
+    (0..=u64::MAX)
+        .find(|nonce| check_grinding(transcript, *nonce, bits))
+        .expect("a nonce is found long before running out")
+}
 
-    frs.iter_mut()
-        .for_each(|f| concatted.append(&mut f.to_u8s()));
+/// Whether `nonce` is a valid grinding nonce for a proof with `bits` bits of grinding
+pub fn check_grinding(transcript: &Transcript, nonce: u64, bits: usize) -> bool {
+    let mut transcript = transcript.clone();
+    append_grinding_nonce(&mut transcript, nonce);
+    let hash = transcript.challenge_bytes(b"grinding");
+    let first = u64::from_be_bytes(hash[0..8].try_into().unwrap());
+    first.leading_zeros() as usize >= bits
+}
 
-    let delta_first_try = *blake3::hash(&concatted).as_bytes();
-    let vith_delta = T::random(&mut ChaCha12Rng::from_seed(delta_first_try));
+/// Absorbs the grinding nonce, after which the challenges for opening the VOLE are derived
+pub fn append_grinding_nonce(transcript: &mut Transcript, nonce: u64) {
+    transcript.append_u64(b"grinding_nonce", nonce);
+}
 
-    concatted.append(&mut "subspace_vole_challenge".as_bytes().to_vec());
-    let subspace_vole_delta_seed = *blake3::hash(&concatted).as_bytes();
-    let mut prg = ChaCha12Rng::from_seed(subspace_vole_delta_seed);
-    let mut delta_choices: Vec<usize> = Vec::with_capacity(num_voles);
-    // This is inefficient but not a bottleneck
-    (0..num_voles).for_each(|_| delta_choices.push((prg.next_u32() % 2) as usize));
+/// Starts the transcript of one proof of several statements about the same witness commitment, given each statement's digest,
+/// the context the proof is bound to, and the security level it claims.
+/// Each statement's Quicksilver proof and public openings are then absorbed with `append_quicksilver_proof`, then the grinding nonce
+pub fn linked_transcript<T: PF>(
+    statements: &[[u8; 32]],
+    context: &[u8],
+    security: SecurityLevel,
+    seed_comm: &[u8; 32],
+    witness_comm: &FMatrix<T>,
+) -> Transcript {
+    let mut transcript = Transcript::new(b"volonym_linked_proof");
+    transcript.append_u64(b"statements", statements.len() as u64);
+    statements
+        .iter()
+        .for_each(|s| transcript.append_message(b"statement", s));
+    transcript.append_message(b"context", context);
+    transcript.append_u64(b"security", security.0 as u64);
+    transcript.append_message(b"seed_comm", seed_comm);
+    transcript.append_matrix(b"witness_comm", &witness_comm.0);
+    transcript
+}
 
-    let subspace_challenge = challenge_from_seed(
-        &concatted,
-        "subspace_vole_consistency".as_bytes(),
-        vole_length,
-    );
-    assert!(vole_length % 2 == 0, "VOLE length must be a multiple of 2");
-    let s_challenge = challenge_from_seed(
-        &concatted,
-        "s_matrix_consistency".as_bytes(),
-        vole_length / 2,
-    );
+/// Where a proof's challenges come from. Between `Prover::mkvole` and the proof, and in the verifier, the challenges are asked for
+/// at the two points the protocol needs them, with the transcript of everything the prover has sent by then.
+/// `FiatShamir` derives them from the transcript. An outer protocol, e.g. a larger proof system this one is composed into or
+/// an interactive deployment, can supply its own instead, as long as the prover and verifier are given the same ones
+/// and each is unpredictable to the prover until it has sent what the transcript holds
+pub trait Challenger<T: PF> {
+    /// `repetitions` Quicksilver challenges, once `transcript` has absorbed the witness commitment
+    fn quicksilver_challenges(
+        &mut self,
+        transcript: &Transcript,
+        repetitions: usize,
+    ) -> Vec<T::Ext>;
+    /// ∆', the small VOLE ∆ choices, and the consistency check challenges, once `transcript` has absorbed the Quicksilver proof,
+    /// the public openings, and the grinding nonce
+    fn opening_challenges(
+        &mut self,
+        transcript: &Transcript,
+        vole_length: usize,
+        num_voles: usize,
+    ) -> Challenges<T>;
+}
+
+/// The built-in challenger, deriving every challenge from the transcript
+#[derive(Clone, Copy, Debug, Default)]
+pub struct FiatShamir;
 
+impl<T: PF> Challenger<T> for FiatShamir {
+    fn quicksilver_challenges(
+        &mut self,
+        transcript: &Transcript,
+        repetitions: usize,
+    ) -> Vec<T::Ext> {
+        calc_quicksilver_challenges::<T>(transcript, repetitions)
+    }
+
+    fn opening_challenges(
+        &mut self,
+        transcript: &Transcript,
+        vole_length: usize,
+        num_voles: usize,
+    ) -> Challenges<T> {
+        calc_other_challenges(transcript, vole_length, num_voles)
+    }
+}
+
+impl<T: PF> Challenges<T> {
+    /// Whether the challenges fit a VOLE of `num_voles` small VOLEs of length `vole_length`:
+    /// each ∆ choice is 0 or 1, and there are as many challenges as it needs
+    pub fn fit_vole(&self, vole_length: usize, num_voles: usize) -> bool {
+        self.delta_choices.len() == num_voles
+            && self.delta_choices.iter().all(|c| *c < 2)
+            && self.subspace_challenge.len()
+                == SecurityLevel::vole_consistency_rows::<T>() * vole_length
+            && self.s_challenge.len() == vith::vole_rows::<T>(vole_length)
+    }
+}
+
+/// Samples every challenge from `rng` instead of deriving them from a transcript, for a designated verifier.
+/// The verifier must keep each challenge secret until the prover has sent everything that challenge is meant to come after
+pub fn sample_challenges<T: PF, R: RngCore + CryptoRng>(
+    rng: &mut R,
+    vole_length: usize,
+    num_voles: usize,
+) -> Challenges<T> {
+    let delta_choices = (0..num_voles)
+        .map(|_| (rng.next_u32() % 2) as usize)
+        .collect();
+    let vith_delta = T::Ext::sample(&mut *rng);
+    let mut sample = |len: usize| (0..len).map(|_| T::Ext::sample(&mut *rng)).collect();
     Challenges {
         delta_choices,
         vith_delta,
-        //  quicksilver_challenge: calc_quicksilver_challenge(seed_comm, witness_comm),
-        subspace_challenge,
-        s_challenge,
+        subspace_challenge: sample(SecurityLevel::vole_consistency_rows::<T>() * vole_length),
+        s_challenge: sample(vith::vole_rows::<T>(vole_length)),
     }
 }
-
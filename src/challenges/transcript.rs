@@ -0,0 +1,149 @@
+//! A Fiat-Shamir transcript in the style of Merlin, over blake3.
+//! Messages are absorbed with labels and lengths, so two different sequences of messages can't hash the same, and challenges
+//! are squeezed out of everything absorbed so far. Squeezing doesn't change the transcript: each challenge has its own label,
+//! and the next message absorbed is what later challenges depend on besides it
+use rand::SeedableRng;
+use rand_chacha::ChaCha12Rng;
+
+#[cfg(not(feature = "std"))]
+use crate::alloc_prelude::*;
+use crate::{fields::ext::ExtensionField, vecccom::expand_xof_to_field_vec, FVec, PF};
+
+#[derive(Clone)]
+pub struct Transcript {
+    hasher: blake3::Hasher,
+}
+
+impl Transcript {
+    /// Starts a transcript for the protocol named `protocol`
+    pub fn new(protocol: &'static [u8]) -> Self {
+        let mut transcript = Self {
+            hasher: blake3::Hasher::new(),
+        };
+        transcript.append_message(b"protocol", protocol);
+        transcript
+    }
+
+    pub fn append_message(&mut self, label: &'static [u8], message: &[u8]) {
+        self.hasher.update(&(label.len() as u64).to_le_bytes());
+        self.hasher.update(label);
+        self.hasher.update(&(message.len() as u64).to_le_bytes());
+        self.hasher.update(message);
+    }
+
+    pub fn append_u64(&mut self, label: &'static [u8], x: u64) {
+        self.append_message(label, &x.to_le_bytes());
+    }
+
+    /// Absorbs field elements one at a time, so no copy of them is made
+    pub fn append_scalars<'a, T: PF + 'a>(
+        &mut self,
+        label: &'static [u8],
+        xs: impl ExactSizeIterator<Item = &'a T>,
+    ) {
+        self.append_u64(label, xs.len() as u64);
+        xs.for_each(|x| {
+            self.hasher.update(&x.to_u8s());
+        });
+    }
+
+    /// Absorbs elements of `T`'s challenge field by their coefficients over `T`, which over a field that is its own is `append_scalars`
+    pub fn append_ext<'a, T: PF>(
+        &mut self,
+        label: &'static [u8],
+        xs: impl ExactSizeIterator<Item = &'a T::Ext>,
+    ) {
+        self.append_u64(label, (xs.len() * T::Ext::DEGREE) as u64);
+        xs.for_each(|x| {
+            x.coefficients().iter().for_each(|c| {
+                self.hasher.update(&c.to_u8s());
+            });
+        });
+    }
+
+    /// Absorbs the number of rows of a matrix, e.g. an `FMatrix`'s, followed by each row
+    pub fn append_matrix<T: PF>(&mut self, label: &'static [u8], rows: &[FVec<T>]) {
+        self.append_u64(label, rows.len() as u64);
+        rows.iter()
+            .for_each(|row| self.append_scalars(b"row", row.0.iter()));
+    }
+
+    pub fn challenge_bytes(&self, label: &'static [u8]) -> [u8; 32] {
+        let mut bytes = [0u8; 32];
+        self.challenge_xof(label).fill(&mut bytes);
+        bytes
+    }
+
+    /// blake3's extendable output for the challenge labeled `label`, of which `challenge_bytes` are the first 32 bytes
+    fn challenge_xof(&self, label: &'static [u8]) -> blake3::OutputReader {
+        let mut hasher = self.hasher.clone();
+        hasher.update(b"challenge");
+        hasher.update(&(label.len() as u64).to_le_bytes());
+        hasher.update(label);
+        hasher.finalize_xof()
+    }
+
+    /// A PRG seeded with the challenge labeled `label`, for challenges that aren't field elements or of unknown length
+    pub fn challenge_rng(&self, label: &'static [u8]) -> ChaCha12Rng {
+        ChaCha12Rng::from_seed(self.challenge_bytes(label))
+    }
+
+    pub fn challenge_scalar<T: PF>(&self, label: &'static [u8]) -> T {
+        T::random(&mut self.challenge_rng(label))
+    }
+
+    /// `len` challenges squeezed at once from the XOF, however long the vector
+    pub fn challenge_vec<T: PF>(&self, label: &'static [u8], len: usize) -> FVec<T> {
+        expand_xof_to_field_vec(self.challenge_xof(label), len)
+    }
+
+    /// A challenge from `T`'s challenge field, which over a field that is its own is `challenge_scalar`
+    pub fn challenge_ext<T: PF>(&self, label: &'static [u8]) -> T::Ext {
+        T::Ext::sample(self.challenge_rng(label))
+    }
+
+    /// `len` challenges from `T`'s challenge field, each made of consecutive elements of `challenge_vec`
+    pub fn challenge_ext_vec<T: PF>(&self, label: &'static [u8], len: usize) -> Vec<T::Ext> {
+        self.challenge_vec::<T>(label, len * T::Ext::DEGREE)
+            .0
+            .chunks(T::Ext::DEGREE)
+            .map(T::Ext::from_coefficients)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Transcript;
+    use crate::{FVec, Fr};
+
+    #[test]
+    fn transcript_ordering() {
+        let mut a = Transcript::new(b"test");
+        a.append_message(b"x", b"ab");
+        a.append_message(b"y", b"c");
+        // The same bytes split differently between messages
+        let mut b = Transcript::new(b"test");
+        b.append_message(b"x", b"a");
+        b.append_message(b"y", b"bc");
+        assert_ne!(a.challenge_bytes(b"c"), b.challenge_bytes(b"c"));
+        // Squeezing doesn't change the transcript, but each label gives a different challenge
+        assert_eq!(a.challenge_bytes(b"c"), a.challenge_bytes(b"c"));
+        assert_ne!(a.challenge_bytes(b"c"), a.challenge_bytes(b"d"));
+        assert_eq!(
+            a.challenge_vec::<Fr>(b"v", 3).0[..2],
+            a.challenge_vec::<Fr>(b"v", 2).0
+        );
+
+        // A matrix isn't the same as its rows concatenated
+        let row = |xs: [u64; 2]| FVec(xs.map(Fr::from).to_vec());
+        let mut m = Transcript::new(b"test");
+        m.append_matrix(b"m", &[row([1, 2]), row([3, 4])]);
+        let mut n = Transcript::new(b"test");
+        n.append_matrix(b"m", &[row([1, 2]), row([3, 4])]);
+        assert_eq!(m.challenge_bytes(b"c"), n.challenge_bytes(b"c"));
+        let mut flat = Transcript::new(b"test");
+        flat.append_scalars(b"m", [1u64, 2, 3, 4].map(Fr::from).iter());
+        assert_ne!(m.challenge_bytes(b"c"), flat.challenge_bytes(b"c"));
+    }
+}
@@ -0,0 +1,300 @@
+//! Runs the witness calculator circom compiles to WebAssembly with `--wasm`, in place of its `generate_witness.js`,
+//! so witnesses can be made without Node.js
+use anyhow::{anyhow, bail, Context, Error};
+use ff::PrimeField;
+use num_bigint::{BigInt, BigUint, Sign};
+use num_integer::Integer;
+use serde_json::Value;
+use std::{collections::BTreeMap, path::Path};
+use wasmtime::{Engine, ExternType, Instance, Linker, Module, Store, Val};
+
+use super::modulus;
+use crate::{FVec, Fr, FrRepr};
+
+/// A loaded circom witness calculator. It can calculate any number of witnesses for its circuit
+pub struct WitnessCalculator {
+    store: Store<()>,
+    instance: Instance,
+    /// Number of u32 limbs in a field element
+    n32: usize,
+    prime: BigUint,
+}
+
+impl WitnessCalculator {
+    /// Loads the calculator from a .wasm file, e.g. `<circuit>_js/<circuit>.wasm`
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, Error> {
+        let path = path.as_ref();
+        let wasm = std::fs::read(path)
+            .with_context(|| format!("could not read witness calculator {}", path.display()))?;
+        Self::from_bytes(&wasm)
+    }
+
+    pub fn from_bytes(wasm: &[u8]) -> Result<Self, Error> {
+        let engine = Engine::default();
+        let module = Module::new(&engine, wasm)?;
+        let mut linker = Linker::new(&engine);
+        // The calculator imports functions from "runtime" to report errors and logs, which differ by circom version.
+        // Only its exception handler matters here; the rest only print
+        for import in module.imports() {
+            let ExternType::Func(ty) = import.ty() else {
+                continue;
+            };
+            if import.name() == "exceptionHandler" {
+                linker.func_new(import.module(), import.name(), ty, |_, params, _| {
+                    Err(anyhow!(
+                        "witness calculation failed with code {:?}",
+                        params.first().and_then(|p| p.i32())
+                    ))
+                })?;
+            } else {
+                let results = ty.results().collect::<Vec<_>>();
+                linker.func_new(import.module(), import.name(), ty, move |_, _, out| {
+                    for (out, ty) in out.iter_mut().zip(results.iter()) {
+                        *out = Val::default_for_ty(ty).unwrap_or(Val::I32(0));
+                    }
+                    Ok(())
+                })?;
+            }
+        }
+        let mut store = Store::new(&engine, ());
+        let instance = linker.instantiate(&mut store, &module)?;
+
+        let mut calculator = Self {
+            n32: 0,
+            prime: BigUint::default(),
+            store,
+            instance,
+        };
+        calculator.n32 = calculator.call::<(), i32>("getFieldNumLen32", ())? as usize;
+        calculator.call::<(), ()>("getRawPrime", ())?;
+        calculator.prime = calculator.read_shared()?;
+        if calculator.prime != modulus::<Fr>() {
+            bail!(
+                "witness calculator is for the field with modulus {}, not BN254's",
+                calculator.prime
+            );
+        }
+        Ok(calculator)
+    }
+
+    /// Calculates the witness for the circuit's inputs, given as in the input.json `generate_witness.js` takes:
+    /// a map from each input signal's name to a number, a decimal string, or a possibly nested array of them
+    pub fn calculate_witness(
+        &mut self,
+        inputs: &BTreeMap<String, Value>,
+    ) -> Result<FVec<Fr>, Error> {
+        self.call::<i32, ()>("init", 0)?;
+        let mut num_set = 0;
+        for (name, value) in inputs {
+            let hash = fnv_hash(name);
+            let (msb, lsb) = ((hash >> 32) as i32, hash as i32);
+            let mut values = vec![];
+            flatten(value, &mut values)
+                .with_context(|| format!("invalid value for input {}", name))?;
+            let size = self.call::<(i32, i32), i32>("getInputSignalSize", (msb, lsb))?;
+            if size < 0 {
+                bail!("the circuit has no input signal {}", name);
+            }
+            if values.len() != size as usize {
+                bail!(
+                    "input {} has {} values but the signal has {}",
+                    name,
+                    values.len(),
+                    size
+                );
+            }
+            for (i, value) in values.iter().enumerate() {
+                let value = value.mod_floor(&BigInt::from(self.prime.clone()));
+                self.write_shared(value.magnitude())?;
+                self.call::<(i32, i32, i32), ()>("setInputSignal", (msb, lsb, i as i32))
+                    .with_context(|| format!("failed to set input {}[{}]", name, i))?;
+                num_set += 1;
+            }
+        }
+        // Older calculators don't export the number of inputs
+        if let Ok(expected) = self.call::<(), i32>("getInputSize", ()) {
+            if num_set < expected as usize {
+                bail!(
+                    "only {} of the circuit's {} inputs were given",
+                    num_set,
+                    expected
+                );
+            }
+        }
+
+        let witness_len = self.call::<(), i32>("getWitnessSize", ())?;
+        let mut witness = Vec::with_capacity(witness_len as usize);
+        for i in 0..witness_len {
+            self.call::<i32, ()>("getWitness", i)?;
+            let value = self.read_shared()?;
+            let mut bytes = value.to_bytes_le();
+            bytes.resize(32, 0);
+            bytes.reverse();
+            let value: Option<Fr> = Fr::from_repr(FrRepr(bytes.try_into().unwrap())).into();
+            witness.push(value.context("witness value is not in the field")?);
+        }
+        Ok(FVec(witness))
+    }
+
+    fn call<P: wasmtime::WasmParams, R: wasmtime::WasmResults>(
+        &mut self,
+        name: &str,
+        params: P,
+    ) -> Result<R, Error> {
+        self.instance
+            .get_typed_func::<P, R>(&mut self.store, name)?
+            .call(&mut self.store, params)
+    }
+
+    /// The field element in the calculator's shared memory, whose i-th u32 is the i-th least significant limb
+    fn read_shared(&mut self) -> Result<BigUint, Error> {
+        let limbs = (0..self.n32)
+            .map(|i| Ok(self.call::<i32, i32>("readSharedRWMemory", i as i32)? as u32))
+            .collect::<Result<Vec<_>, Error>>()?;
+        Ok(BigUint::from_slice(&limbs))
+    }
+
+    fn write_shared(&mut self, value: &BigUint) -> Result<(), Error> {
+        let mut limbs = value.to_u32_digits();
+        limbs.resize(self.n32, 0);
+        for (i, limb) in limbs.into_iter().enumerate() {
+            self.call::<(i32, i32), ()>("writeSharedRWMemory", (i as i32, limb as i32))?;
+        }
+        Ok(())
+    }
+}
+
+/// 64 bit FNV-1a hash of a signal name, which the calculator identifies input signals by
+fn fnv_hash(name: &str) -> u64 {
+    name.bytes().fold(0xcbf29ce484222325, |hash, b| {
+        (hash ^ b as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+/// Appends the numbers in a possibly nested array to `out`, in order
+fn flatten(value: &Value, out: &mut Vec<BigInt>) -> Result<(), Error> {
+    match value {
+        Value::Array(values) => values.iter().try_for_each(|v| flatten(v, out)),
+        Value::Number(n) => {
+            out.push(n.to_string().parse().context("not an integer")?);
+            Ok(())
+        }
+        Value::String(s) => {
+            let (sign, digits) = match s.strip_prefix('-') {
+                Some(digits) => (Sign::Minus, digits),
+                None => (Sign::Plus, s.as_str()),
+            };
+            let magnitude = match digits.strip_prefix("0x") {
+                Some(hex) => BigUint::parse_bytes(hex.as_bytes(), 16),
+                None => BigUint::parse_bytes(digits.as_bytes(), 10),
+            }
+            .context("not an integer")?;
+            out.push(BigInt::from_biguint(sign, magnitude));
+            Ok(())
+        }
+        Value::Bool(b) => {
+            out.push(BigInt::from(*b as u8));
+            Ok(())
+        }
+        _ => bail!("expected a number, string, or array"),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use ff::Field;
+
+    /// A calculator with circom's interface for a circuit with inputs `a` and `b[2]`, whose witness is 1 followed by the inputs.
+    /// Signals are stored as 8 limbs at 64 + 32 * index, with the shared memory at 0
+    fn mock_calculator() -> String {
+        let hash = |name| {
+            let h = fnv_hash(name);
+            ((h >> 32) as i32, h as i32)
+        };
+        let ((a_msb, a_lsb), (b_msb, b_lsb)) = (hash("a"), hash("b"));
+        // BN254's modulus in 32 bit limbs, least significant first
+        let prime = [
+            0xf0000001u32,
+            0x43e1f593,
+            0x79b97091,
+            0x2833e848,
+            0x8181585d,
+            0xb85045b6,
+            0xe131a029,
+            0x30644e72,
+        ]
+        .iter()
+        .enumerate()
+        .map(|(i, limb)| {
+            format!(
+                "(i32.store (i32.const {}) (i32.const {}))",
+                4 * i,
+                *limb as i32
+            )
+        })
+        .collect::<String>();
+        format!(
+            r#"(module
+                (import "runtime" "exceptionHandler" (func $exception (param i32)))
+                (import "runtime" "printErrorMessage" (func))
+                (memory (export "memory") 1)
+                (func (export "getFieldNumLen32") (result i32) (i32.const 8))
+                (func (export "getRawPrime") {prime})
+                (func (export "readSharedRWMemory") (param i32) (result i32)
+                    (i32.load (i32.mul (local.get 0) (i32.const 4))))
+                (func (export "writeSharedRWMemory") (param i32 i32)
+                    (i32.store (i32.mul (local.get 0) (i32.const 4)) (local.get 1)))
+                (func (export "init") (param i32)
+                    (i32.store (i32.const 64) (i32.const 1)))
+                (func $offset (param i32 i32) (result i32)
+                    (if (i32.and (i32.eq (local.get 0) (i32.const {a_msb})) (i32.eq (local.get 1) (i32.const {a_lsb})))
+                        (then (return (i32.const 1))))
+                    (if (i32.and (i32.eq (local.get 0) (i32.const {b_msb})) (i32.eq (local.get 1) (i32.const {b_lsb})))
+                        (then (return (i32.const 2))))
+                    (i32.const -1))
+                (func (export "getInputSignalSize") (param i32 i32) (result i32)
+                    (local i32)
+                    (local.set 2 (call $offset (local.get 0) (local.get 1)))
+                    (if (result i32) (i32.lt_s (local.get 2) (i32.const 0))
+                        (then (i32.const -1))
+                        (else (local.get 2))))
+                (func (export "setInputSignal") (param i32 i32 i32)
+                    (local i32)
+                    (local.set 3 (call $offset (local.get 0) (local.get 1)))
+                    (if (i32.lt_s (local.get 3) (i32.const 0)) (then (call $exception (i32.const 1))))
+                    (memory.copy
+                        (i32.add (i32.const 64) (i32.mul (i32.add (local.get 3) (local.get 2)) (i32.const 32)))
+                        (i32.const 0)
+                        (i32.const 32)))
+                (func (export "getInputSize") (result i32) (i32.const 3))
+                (func (export "getWitnessSize") (result i32) (i32.const 4))
+                (func (export "getWitness") (param i32)
+                    (memory.copy
+                        (i32.const 0)
+                        (i32.add (i32.const 64) (i32.mul (local.get 0) (i32.const 32)))
+                        (i32.const 32))))"#
+        )
+    }
+
+    #[test]
+    fn calculate_witness() {
+        let mut calculator = WitnessCalculator::from_bytes(mock_calculator().as_bytes()).unwrap();
+        let inputs: BTreeMap<String, Value> =
+            serde_json::from_str(r#"{"a": 5, "b": ["-1", "0x10"]}"#).unwrap();
+        let witness = calculator.calculate_witness(&inputs).unwrap();
+        assert_eq!(
+            witness.0,
+            vec![Fr::ONE, Fr::from(5), -Fr::ONE, Fr::from(16)]
+        );
+
+        let missing: BTreeMap<String, Value> = serde_json::from_str(r#"{"a": 5}"#).unwrap();
+        assert!(calculator.calculate_witness(&missing).is_err());
+        let unknown: BTreeMap<String, Value> =
+            serde_json::from_str(r#"{"a": 5, "b": [1, 2], "c": 3}"#).unwrap();
+        assert!(calculator.calculate_witness(&unknown).is_err());
+        let too_many: BTreeMap<String, Value> =
+            serde_json::from_str(r#"{"a": 5, "b": [1, 2, 3]}"#).unwrap();
+        assert!(calculator.calculate_witness(&too_many).is_err());
+    }
+}
@@ -0,0 +1,365 @@
+//! Runs the circom compiler, so tools can go from a .circom file to the .r1cs file, witness calculator, and symbols this crate reads.
+//! For large circuits, circom's C++ witness generator is much faster than its WebAssembly calculator, and can be built and run here too.
+//! Compilations can be cached, so a circuit that hasn't changed isn't compiled again
+use std::{
+    fmt,
+    io::ErrorKind,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use crate::error::CompileError;
+
+/// The oldest circom supported. The flags and output layout used here are those of circom 2
+pub const MIN_CIRCOM_VERSION: (u32, u32, u32) = (2, 0, 0);
+
+/// circom's constraint simplification: none, only of linear constraints with signals equal to others, or full
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub enum OptimizationLevel {
+    O0,
+    #[default]
+    O1,
+    O2,
+}
+
+impl fmt::Display for OptimizationLevel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let level = match self {
+            Self::O0 => "O0",
+            Self::O1 => "O1",
+            Self::O2 => "O2",
+        };
+        f.write_str(level)
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct CompileOptions {
+    pub optimization: OptimizationLevel,
+    /// Where circom writes its output. Defaults to a directory named after the circuit next to it
+    pub output_dir: Option<PathBuf>,
+    /// Whether to compile the witness calculator to WebAssembly
+    pub wasm: bool,
+    /// Whether to write the .sym file naming each signal
+    pub sym: bool,
+    /// Whether to write the C++ witness generator's source, which `build_cpp_witness_generator` builds
+    pub cpp: bool,
+    /// The circom binary, by default the one in the PATH
+    pub circom: PathBuf,
+    /// Whether to reuse the output of an earlier compilation of the same circuit with the same options instead of running circom.
+    /// Only the circuit's own file is hashed, so a change to a file it includes isn't noticed
+    pub cache: bool,
+}
+
+impl Default for CompileOptions {
+    fn default() -> Self {
+        Self {
+            optimization: OptimizationLevel::default(),
+            output_dir: None,
+            wasm: true,
+            sym: false,
+            cpp: false,
+            circom: PathBuf::from("circom"),
+            cache: false,
+        }
+    }
+}
+
+/// Paths of the files circom wrote
+#[derive(Clone, Debug)]
+pub struct CompiledCircuit {
+    pub output_dir: PathBuf,
+    pub r1cs: PathBuf,
+    pub wasm: Option<PathBuf>,
+    pub sym: Option<PathBuf>,
+    /// The directory of the C++ witness generator's source
+    pub cpp: Option<PathBuf>,
+    /// What circom printed, which includes the number of constraints and wires
+    pub stdout: String,
+    /// Whether the files are from an earlier compilation, in which case `stdout` is what circom printed then
+    pub cached: bool,
+}
+
+/// Compiles `circuit` to R1CS and, depending on `options`, a witness calculator and symbols, after checking circom is recent enough
+pub fn compile(circuit: &Path, options: &CompileOptions) -> Result<CompiledCircuit, CompileError> {
+    let stem = circuit
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .ok_or_else(|| CompileError::InvalidPath(circuit.to_path_buf()))?;
+    let output_dir = match &options.output_dir {
+        Some(dir) => dir.clone(),
+        None => circuit.with_file_name(stem),
+    };
+    // The key of the cached output is stored next to it, followed by what circom printed
+    let cache_path = output_dir.join(format!("{}.cache", stem));
+    let key = if options.cache {
+        let key = cache_key(&std::fs::read(circuit)?, options);
+        if let Some(compiled) = cached(&cache_path, &key, stem, &output_dir, options) {
+            tracing::debug!(circuit = %circuit.display(), "reusing the cached compilation");
+            return Ok(compiled);
+        }
+        Some(key)
+    } else {
+        None
+    };
+
+    let found = circom_version(&options.circom)?;
+    if found < MIN_CIRCOM_VERSION {
+        return Err(CompileError::UnsupportedVersion {
+            found: version_string(found),
+            required: version_string(MIN_CIRCOM_VERSION),
+        });
+    }
+
+    std::fs::create_dir_all(&output_dir)?;
+    let mut command = Command::new(&options.circom);
+    command
+        .arg(circuit)
+        .arg("--r1cs")
+        .arg(format!("--{}", options.optimization))
+        .arg("-o")
+        .arg(&output_dir);
+    if options.wasm {
+        command.arg("--wasm");
+    }
+    if options.sym {
+        command.arg("--sym");
+    }
+    if options.cpp {
+        command.arg("--c");
+    }
+    let output = command
+        .output()
+        .map_err(|e| missing_binary(e, &options.circom))?;
+    if !output.status.success() {
+        return Err(CompileError::Failed {
+            stderr: String::from_utf8_lossy(&output.stderr).into_owned(),
+        });
+    }
+
+    let stdout = String::from_utf8_lossy(&output.stdout).into_owned();
+    if let Some(key) = key {
+        std::fs::write(&cache_path, format!("{}\n{}", key, stdout))?;
+    }
+    Ok(artifacts(stem, output_dir, options, stdout, false))
+}
+
+/// The files circom writes for `stem` in `output_dir` with `options`
+fn artifacts(
+    stem: &str,
+    output_dir: PathBuf,
+    options: &CompileOptions,
+    stdout: String,
+    cached: bool,
+) -> CompiledCircuit {
+    CompiledCircuit {
+        r1cs: output_dir.join(format!("{}.r1cs", stem)),
+        wasm: options
+            .wasm
+            .then(|| output_dir.join(format!("{}_js/{}.wasm", stem, stem))),
+        sym: options
+            .sym
+            .then(|| output_dir.join(format!("{}.sym", stem))),
+        cpp: options
+            .cpp
+            .then(|| output_dir.join(format!("{}_cpp", stem))),
+        output_dir,
+        stdout,
+        cached,
+    }
+}
+
+/// Hashes the circuit's source and every option that changes circom's output
+fn cache_key(source: &[u8], options: &CompileOptions) -> String {
+    let mut hasher = blake3::Hasher::new();
+    hasher.update(source);
+    hasher.update(options.optimization.to_string().as_bytes());
+    hasher.update(&[options.wasm as u8, options.sym as u8, options.cpp as u8]);
+    hasher.finalize().to_hex().to_string()
+}
+
+/// The earlier compilation's files, if its key is `key` and they all still exist
+fn cached(
+    cache_path: &Path,
+    key: &str,
+    stem: &str,
+    output_dir: &Path,
+    options: &CompileOptions,
+) -> Option<CompiledCircuit> {
+    let contents = std::fs::read_to_string(cache_path).ok()?;
+    let (cached_key, stdout) = contents.split_once('\n')?;
+    if cached_key != key {
+        return None;
+    }
+    let compiled = artifacts(
+        stem,
+        output_dir.to_path_buf(),
+        options,
+        stdout.to_string(),
+        true,
+    );
+    let files = [
+        Some(&compiled.r1cs),
+        compiled.wasm.as_ref(),
+        compiled.sym.as_ref(),
+        compiled.cpp.as_ref(),
+    ];
+    let exist = files.into_iter().flatten().all(|file| file.exists());
+    exist.then_some(compiled)
+}
+
+/// Builds the C++ witness generator in `cpp_dir`, e.g. `CompiledCircuit::cpp`, with its Makefile, returning the path of the generator.
+/// It needs make, a C++ compiler, and GMP
+pub fn build_cpp_witness_generator(cpp_dir: &Path) -> Result<PathBuf, CompileError> {
+    let name = cpp_dir
+        .file_name()
+        .and_then(|name| name.to_str())
+        .and_then(|name| name.strip_suffix("_cpp"))
+        .ok_or_else(|| CompileError::InvalidPath(cpp_dir.to_path_buf()))?;
+    let output = Command::new("make")
+        .current_dir(cpp_dir)
+        .output()
+        .map_err(|e| missing_binary(e, Path::new("make")))?;
+    if !output.status.success() {
+        return Err(CompileError::BuildFailed {
+            stderr: String::from_utf8_lossy(&output.stderr).into_owned(),
+        });
+    }
+    Ok(cpp_dir.join(name))
+}
+
+/// Runs a C++ witness generator on the inputs in `input_json`, writing the witness to the .wtns file `wtns`
+pub fn run_cpp_witness_generator(
+    generator: &Path,
+    input_json: &Path,
+    wtns: &Path,
+) -> Result<(), CompileError> {
+    let output = Command::new(generator)
+        .arg(input_json)
+        .arg(wtns)
+        .output()
+        .map_err(|e| missing_binary(e, generator))?;
+    if !output.status.success() {
+        // The generator reports some errors, such as failed assertions, on stdout
+        return Err(CompileError::WitnessGeneration {
+            stderr: [output.stdout, output.stderr]
+                .iter()
+                .map(|out| String::from_utf8_lossy(out))
+                .collect(),
+        });
+    }
+    Ok(())
+}
+
+/// The version of the circom binary at `circom`
+pub fn circom_version(circom: &Path) -> Result<(u32, u32, u32), CompileError> {
+    let output = Command::new(circom)
+        .arg("--version")
+        .output()
+        .map_err(|e| missing_binary(e, circom))?;
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    parse_version(&stdout).ok_or_else(|| CompileError::UnknownVersion(stdout.into_owned()))
+}
+
+fn missing_binary(e: std::io::Error, circom: &Path) -> CompileError {
+    match e.kind() {
+        ErrorKind::NotFound => CompileError::MissingBinary(circom.to_path_buf()),
+        _ => e.into(),
+    }
+}
+
+/// Parses output such as "circom compiler 2.1.6"
+fn parse_version(output: &str) -> Option<(u32, u32, u32)> {
+    let mut parts = output.split_whitespace().last()?.split('.');
+    let mut part = || parts.next()?.parse().ok();
+    Some((part()?, part()?, part()?))
+}
+
+fn version_string((major, minor, patch): (u32, u32, u32)) -> String {
+    format!("{}.{}.{}", major, minor, patch)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn circom_invocation() {
+        assert_eq!(parse_version("circom compiler 2.1.6\n"), Some((2, 1, 6)));
+        assert_eq!(parse_version("circom compiler"), None);
+        assert!(parse_version("0.5.46").unwrap() < MIN_CIRCOM_VERSION);
+        assert_eq!(OptimizationLevel::default().to_string(), "O1");
+
+        let options = CompileOptions {
+            circom: PathBuf::from("volonym-no-such-circom"),
+            ..Default::default()
+        };
+        assert!(matches!(
+            compile(Path::new("circuit.circom"), &options),
+            Err(CompileError::MissingBinary(_))
+        ));
+        assert!(matches!(
+            compile(Path::new(".."), &options),
+            Err(CompileError::InvalidPath(_))
+        ));
+        assert!(matches!(
+            build_cpp_witness_generator(Path::new("circuit")),
+            Err(CompileError::InvalidPath(_))
+        ));
+        assert!(matches!(
+            run_cpp_witness_generator(
+                Path::new("volonym-no-such-generator"),
+                Path::new("input.json"),
+                Path::new("witness.wtns")
+            ),
+            Err(CompileError::MissingBinary(_))
+        ));
+    }
+
+    #[test]
+    fn compilation_cache() {
+        let dir =
+            std::env::temp_dir().join(format!("volonym-compile-cache-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let circuit = dir.join("circuit.circom");
+        std::fs::write(&circuit, "pragma circom 2.0.0;").unwrap();
+        // circom is missing, so only a cache hit succeeds
+        let options = CompileOptions {
+            circom: PathBuf::from("volonym-no-such-circom"),
+            wasm: false,
+            cache: true,
+            ..Default::default()
+        };
+        assert!(compile(&circuit, &options).is_err());
+
+        let output_dir = dir.join("circuit");
+        std::fs::create_dir_all(&output_dir).unwrap();
+        std::fs::write(output_dir.join("circuit.r1cs"), []).unwrap();
+        let key = cache_key(b"pragma circom 2.0.0;", &options);
+        std::fs::write(
+            output_dir.join("circuit.cache"),
+            format!("{}\nnon-linear constraints: 0\n", key),
+        )
+        .unwrap();
+        let compiled = compile(&circuit, &options).unwrap();
+        assert!(compiled.cached);
+        assert_eq!(compiled.stdout, "non-linear constraints: 0\n");
+        assert_eq!(compiled.r1cs, output_dir.join("circuit.r1cs"));
+
+        // Other options, a missing artifact, or a changed circuit miss the cache
+        let o2 = CompileOptions {
+            optimization: OptimizationLevel::O2,
+            ..options.clone()
+        };
+        assert!(compile(&circuit, &o2).is_err());
+        let with_wasm = CompileOptions {
+            wasm: true,
+            ..options.clone()
+        };
+        assert!(compile(&circuit, &with_wasm).is_err());
+        std::fs::write(&circuit, "pragma circom 2.1.0;").unwrap();
+        assert!(compile(&circuit, &options).is_err());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
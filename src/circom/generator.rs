@@ -1,25 +1,20 @@
 use anyhow::Result;
 use handlebars::Handlebars;
-use serde_json::json;
+use serde_json::{json, Value};
 use std::fs::File;
 use std::io::Write;
 use std::path::Path;
 
-pub fn generate_circom(
+/// Renders the handlebars template at `template_path` with `data`, e.g. a JSON object of the template's parameters, writing the circom code to `output_path`
+pub fn generate_circom_from_data(
     output_path: &Path,
     template_path: &Path,
-    q: i64,
-    pk: Vec<i64>,
+    data: &Value,
 ) -> Result<()> {
     let mut handlebars = Handlebars::new();
     handlebars.register_template_file("template", template_path)?;
 
-    let data = json!({
-        "q": q,
-        "pk": pk,
-    });
-
-    let output = handlebars.render("template", &data)?;
+    let output = handlebars.render("template", data)?;
 
     let mut file = File::create(output_path)?;
     file.write_all(output.as_bytes())?;
@@ -27,6 +22,20 @@ pub fn generate_circom(
     Ok(())
 }
 
+/// Renders a Falcon signature verification template such as falcon.hbs for the modulus `q` and public key `pk`
+pub fn generate_circom(
+    output_path: &Path,
+    template_path: &Path,
+    q: i64,
+    pk: Vec<i64>,
+) -> Result<()> {
+    let data = json!({
+        "q": q,
+        "pk": pk,
+    });
+    generate_circom_from_data(output_path, template_path, &data)
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -38,4 +47,24 @@ mod test {
         let pk = vec![1, 2, 3];
         generate_circom(output_path, template_path, 12289, pk).unwrap();
     }
+
+    #[test]
+    fn generate_from_data() {
+        let dir = std::env::temp_dir();
+        let template_path = dir.join(format!("volonym_template_{}.hbs", std::process::id()));
+        let output_path = template_path.with_extension("circom");
+        std::fs::write(
+            &template_path,
+            "component main = {{name}}({{#each sizes}}{{this}}{{#unless @last}}, {{/unless}}{{/each}});",
+        )
+        .unwrap();
+        let data = json!({ "name": "Sum", "sizes": [3, 8] });
+        generate_circom_from_data(&output_path, &template_path, &data).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&output_path).unwrap(),
+            "component main = Sum(3, 8);"
+        );
+        std::fs::remove_file(template_path).unwrap();
+        std::fs::remove_file(output_path).unwrap();
+    }
 }
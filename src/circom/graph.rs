@@ -0,0 +1,106 @@
+//! Exports a circuit's structure as a Graphviz DOT graph: each constraint is joined to the wires it uses, so constraints
+//! with unusually many wires, and wires used by unusually many constraints, stand out when it is drawn
+use std::{
+    collections::BTreeMap,
+    io::{self, Write},
+    ops::Range,
+};
+
+use crate::{circom::sym::SymbolTable, zkp::R1CSWithMetadata, PF};
+
+/// Writes the bipartite graph of the constraints in `constraints` and the wires they use to `writer` in DOT format, e.g. to render
+/// with `dot -Tsvg`. Constraints are boxes labeled with their fan-in, the number of wires they use. Wires are ellipses labeled with
+/// their signal's name in `symbols`, if it has one, and their fan-out among the constraints drawn, with public inputs and outputs
+/// filled in. Only the first branch of circuits with several is drawn
+pub fn write_dot<W: Write, T: PF>(
+    mut writer: W,
+    circuit: &R1CSWithMetadata<T>,
+    constraints: Range<usize>,
+    symbols: Option<&SymbolTable>,
+) -> io::Result<()> {
+    let constraints = constraints.start..constraints.end.min(circuit.r1cs.num_constraints());
+    let mut edges = Vec::new();
+    // Constraints each wire is used by, among those drawn
+    let mut wires = BTreeMap::<usize, usize>::new();
+
+    writeln!(writer, "graph circuit {{")?;
+    writeln!(writer, "  node [fontsize=10];")?;
+    for constraint in constraints {
+        let constraint_wires = circuit.r1cs.constraint_wires(constraint);
+        writeln!(
+            writer,
+            "  c{} [shape=box, label=\"constraint {}\\nfan-in {}\"];",
+            constraint,
+            constraint,
+            constraint_wires.len()
+        )?;
+        for wire in constraint_wires {
+            *wires.entry(wire).or_default() += 1;
+            edges.push((constraint, wire));
+        }
+    }
+    for (wire, uses) in wires {
+        let name = match (wire, symbols.and_then(|s| s.name(wire))) {
+            (_, Some(name)) => escape(name),
+            (0, None) => "one".to_string(),
+            (_, None) => format!("wire {}", wire),
+        };
+        let fill = if circuit.public_inputs_indices.contains(&wire) {
+            ", style=filled, fillcolor=lightblue"
+        } else if circuit.public_outputs_indices.contains(&wire) {
+            ", style=filled, fillcolor=lightgreen"
+        } else {
+            ""
+        };
+        writeln!(
+            writer,
+            "  w{} [shape=ellipse, label=\"{}\\nfan-out {}\"{}];",
+            wire, name, uses, fill
+        )?;
+    }
+    for (constraint, wire) in edges {
+        writeln!(writer, "  c{} -- w{};", constraint, wire)?;
+    }
+    writeln!(writer, "}}")
+}
+
+/// `name` as the inside of a DOT string
+fn escape(name: &str) -> String {
+    name.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{gadgets::CircuitBuilder, Fr};
+
+    #[test]
+    fn circuit_dot() {
+        // x * x = y, y * x = z
+        let mut builder = CircuitBuilder::new();
+        let x = builder.public_input(Fr::from(3));
+        let y = builder.mul(x, x);
+        let z = builder.mul(y, x);
+        builder.public_output(z);
+        let (circuit, _) = builder.build();
+        let x = circuit.public_inputs_indices[0];
+
+        let mut dot = vec![];
+        write_dot(&mut dot, &circuit, 0..usize::MAX, None).unwrap();
+        let dot = String::from_utf8(dot).unwrap();
+        assert!(dot.starts_with("graph circuit {") && dot.ends_with("}\n"));
+        assert!(dot.contains("c1 [shape=box, label=\"constraint 1\\nfan-in 3\"];"));
+        assert!(dot.contains(&format!("w{} [shape=ellipse, label=\"wire {}\\nfan-out 2\", style=filled, fillcolor=lightblue];", x, x)));
+        assert_eq!(dot.matches(" -- ").count(), 5);
+
+        // Only the second constraint, with its wires named
+        let mut symbols = SymbolTable::default();
+        symbols.signals.insert(x, ("main.\"x\"".to_string(), 0));
+        let mut dot = vec![];
+        write_dot(&mut dot, &circuit, 1..2, Some(&symbols)).unwrap();
+        let dot = String::from_utf8(dot).unwrap();
+        assert!(!dot.contains("c0 "));
+        assert!(dot.contains("label=\"main.\\\"x\\\"\\nfan-out 1\""));
+        assert_eq!(dot.matches(" -- ").count(), 3);
+    }
+}
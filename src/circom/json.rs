@@ -0,0 +1,148 @@
+//! Reads the JSON circuits and witnesses snarkjs exports with `snarkjs r1cs export json` (`rej`) and `snarkjs wtns export json` (`wej`)
+use anyhow::{bail, Context, Error};
+use num_bigint::BigUint;
+use serde::Deserialize;
+use std::{collections::BTreeMap, io::Read};
+
+use super::{modulus, r1cs::Header};
+use crate::{
+    zkp::{R1CSWithMetadata, SparseR1CS},
+    FVec, SparseFMatrix, SparseVec, PF,
+};
+
+/// The fields of an r1cs.json this crate uses
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct R1CSJson {
+    n8: u32,
+    prime: String,
+    n_vars: u32,
+    n_outputs: u32,
+    n_pub_inputs: u32,
+    n_prv_inputs: u32,
+    n_labels: u64,
+    n_constraints: u32,
+    /// A, B, and C of each constraint, as maps from signal index to coefficient
+    constraints: Vec<[BTreeMap<String, String>; 3]>,
+}
+
+/// Parses an r1cs.json exported by snarkjs over the field `T`, which must have the circuit's prime
+pub fn r1cs_from_json_reader<R: Read, T: PF>(reader: R) -> Result<R1CSWithMetadata<T>, Error> {
+    let json: R1CSJson = serde_json::from_reader(reader)?;
+    if json.prime != modulus::<T>().to_string() {
+        bail!(
+            "the circuit's prime {} isn't the modulus of the field it is parsed into",
+            json.prime
+        );
+    }
+    if json.constraints.len() != json.n_constraints as usize {
+        bail!(
+            "the circuit has {} constraints but says it has {}",
+            json.constraints.len(),
+            json.n_constraints
+        );
+    }
+    let header = Header {
+        field_size: json.n8,
+        prime_size: modulus::<T>(),
+        n_wires: json.n_vars,
+        n_pub_out: json.n_outputs,
+        n_pub_in: json.n_pub_inputs,
+        n_prv_in: json.n_prv_inputs,
+        n_labels: json.n_labels,
+        n_constraints: json.n_constraints,
+    };
+    let mut rows: [Vec<SparseVec<T>>; 3] = Default::default();
+    for (i, constraint) in json.constraints.iter().enumerate() {
+        for (rows, lc) in rows.iter_mut().zip(constraint) {
+            let row = lc
+                .iter()
+                .map(|(wire, coeff)| {
+                    let wire = wire.parse::<usize>()?;
+                    if wire >= json.n_vars as usize {
+                        bail!("signal {} is out of range", wire);
+                    }
+                    Ok((wire, parse_element(coeff)?))
+                })
+                .collect::<Result<Vec<_>, Error>>()
+                .with_context(|| format!("invalid constraint {}", i))?;
+            rows.push(SparseVec(row));
+        }
+    }
+    let [a_rows, b_rows, c_rows] = rows;
+    Ok(header.with_metadata(SparseR1CS {
+        a_rows: SparseFMatrix(a_rows),
+        b_rows: SparseFMatrix(b_rows),
+        c_rows: SparseFMatrix(c_rows),
+    }))
+}
+
+/// Parses a witness.json exported by snarkjs, an array of the witness's values as decimal strings
+pub fn wtns_from_json_reader<R: Read, T: PF>(reader: R) -> Result<FVec<T>, Error> {
+    let values: Vec<String> = serde_json::from_reader(reader)?;
+    values
+        .iter()
+        .enumerate()
+        .map(|(i, value)| {
+            parse_element(value).with_context(|| format!("invalid witness value {}", i))
+        })
+        .collect::<Result<_, _>>()
+        .map(FVec)
+}
+
+/// A decimal number less than the prime
+fn parse_element<T: PF>(value: &str) -> Result<T, Error> {
+    let n = BigUint::parse_bytes(value.as_bytes(), 10)
+        .with_context(|| format!("{} isn't a decimal number", value))?;
+    if n >= modulus::<T>() {
+        bail!("{} isn't less than the prime", value);
+    }
+    Ok(T::from_str_vartime(value).expect("the value is a decimal number less than the prime"))
+}
+
+#[cfg(test)]
+mod test {
+    use super::{r1cs_from_json_reader, wtns_from_json_reader};
+    use crate::{fields::bls12_381, Fr};
+    use ff::Field;
+
+    #[test]
+    fn read_json() {
+        // out = a * b with the output first, as circom orders it: w0 = 1, w1 = out, w2 = a, w3 = b
+        let r1cs = r#"{
+            "n8": 32,
+            "prime": "21888242871839275222246405745257275088548364400416034343698204186575808495617",
+            "nVars": 4,
+            "nOutputs": 1,
+            "nPubInputs": 1,
+            "nPrvInputs": 1,
+            "nLabels": 4,
+            "nConstraints": 1,
+            "useCustomGates": false,
+            "constraints": [[
+                {"2": "21888242871839275222246405745257275088548364400416034343698204186575808495616"},
+                {"3": "1"},
+                {"1": "21888242871839275222246405745257275088548364400416034343698204186575808495616"}
+            ]],
+            "map": [0, 1, 2, 3]
+        }"#;
+        let circuit = r1cs_from_json_reader::<_, Fr>(r1cs.as_bytes()).unwrap();
+        assert_eq!(circuit.public_outputs_indices, vec![1]);
+        assert_eq!(circuit.public_inputs_indices, vec![2]);
+        let witness = wtns_from_json_reader(r#"["1", "15", "3", "5"]"#.as_bytes()).unwrap();
+        assert_eq!(witness.0[0], Fr::ONE);
+        assert!(circuit.check_witness(&witness).is_ok());
+        let wrong = wtns_from_json_reader(r#"["1", "16", "3", "5"]"#.as_bytes()).unwrap();
+        assert!(circuit.check_witness(&wrong).is_err());
+
+        assert!(wtns_from_json_reader::<_, Fr>(r#"["1", "-2"]"#.as_bytes()).is_err());
+        assert!(wtns_from_json_reader::<_, Fr>(
+            r#"["21888242871839275222246405745257275088548364400416034343698204186575808495617"]"#
+                .as_bytes()
+        )
+        .is_err());
+        let other_prime = r1cs.replace("495617", "495619");
+        assert!(r1cs_from_json_reader::<_, Fr>(other_prime.as_bytes()).is_err());
+        assert!(r1cs_from_json_reader::<_, bls12_381::Fr>(r1cs.as_bytes()).is_err());
+    }
+}
@@ -0,0 +1,170 @@
+//! Parsing .r1cs files too large to read into memory comfortably. The file is memory mapped,
+//! so the OS pages it in as its constraints are parsed, one at a time
+use anyhow::{bail, Context, Error};
+use memmap2::Mmap;
+use std::{fs::File, io::Cursor, marker::PhantomData, path::Path};
+
+use super::{
+    r1cs::{
+        check_custom_gates, check_prime, read_custom_gate_applications, read_custom_gates,
+        read_header, read_sections, CustomGateApplication, CustomGateTemplate, Header,
+        CONSTRAINT_TYPE, CUSTOM_GATES_APPLICATION_TYPE, CUSTOM_GATES_LIST_TYPE, HEADER_TYPE,
+    },
+    read_constraint_vec,
+};
+use crate::{
+    zkp::{R1CSWithMetadata, SparseR1CS},
+    Fr, SparseFMatrix, SparseVec, PF,
+};
+
+/// The rows of A, B, and C of a constraint
+pub type ConstraintRows<T> = (SparseVec<T>, SparseVec<T>, SparseVec<T>);
+
+/// A memory mapped .r1cs file over the field `T`, which must have the same prime as the file
+pub struct MappedR1CS<T: PF = Fr> {
+    mmap: Mmap,
+    pub header: Header,
+    /// Where the constraints section is in the file
+    constraints: (usize, usize),
+    /// Custom gates, as in `R1CSFile`
+    pub custom_gates: Vec<CustomGateTemplate<T>>,
+    pub custom_gate_applications: Vec<CustomGateApplication>,
+    _field: PhantomData<T>,
+}
+
+impl<T: PF> MappedR1CS<T> {
+    /// Maps the file and parses its header. The file must not be changed while it is mapped
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, Error> {
+        let file = File::open(path)?;
+        // Safety: the map is only read, and the file isn't changed while it is mapped as required above
+        let mmap = unsafe { Mmap::map(&file)? };
+
+        let mut reader = Cursor::new(&mmap[..]);
+        let (_, sections) = read_sections(&mut reader)?;
+        let section = |section_type| -> Result<(usize, usize), Error> {
+            let (start, size) = sections
+                .get(&section_type)
+                .with_context(|| format!("the file has no section {}", section_type))?;
+            let truncated = || format!("section {} is truncated", section_type);
+            let start = usize::try_from(*start).ok().with_context(truncated)?;
+            let end = usize::try_from(*size)
+                .ok()
+                .and_then(|size| start.checked_add(size))
+                .filter(|end| *end <= mmap.len())
+                .with_context(truncated)?;
+            Ok((start, end))
+        };
+        let (header_start, header_end) = section(HEADER_TYPE)?;
+        let header = read_header(
+            &mmap[header_start..header_end],
+            (header_end - header_start) as u64,
+        )?;
+        check_prime::<T>(&header)?;
+        let constraints = section(CONSTRAINT_TYPE)?;
+        // Each constraint is at least the three u32 lengths of its rows
+        let min_size = (header.n_constraints as usize).checked_mul(12);
+        if min_size.map_or(true, |min_size| min_size > constraints.1 - constraints.0) {
+            bail!("Invalid constraint section size");
+        }
+        let mut custom_gates = vec![];
+        let mut custom_gate_applications = vec![];
+        if sections.contains_key(&CUSTOM_GATES_LIST_TYPE) {
+            let (start, end) = section(CUSTOM_GATES_LIST_TYPE)?;
+            custom_gates = read_custom_gates(&mmap[start..end], header.field_size as usize)?;
+        }
+        if sections.contains_key(&CUSTOM_GATES_APPLICATION_TYPE) {
+            let (start, end) = section(CUSTOM_GATES_APPLICATION_TYPE)?;
+            custom_gate_applications = read_custom_gate_applications(&mmap[start..end])?;
+        }
+        Ok(Self {
+            mmap,
+            header,
+            constraints,
+            custom_gates,
+            custom_gate_applications,
+            _field: PhantomData,
+        })
+    }
+
+    /// The rows of A, B, and C of each constraint in order, each parsed only when it is reached
+    pub fn constraints(&self) -> impl Iterator<Item = Result<ConstraintRows<T>, Error>> + '_ {
+        let mut reader = &self.mmap[self.constraints.0..self.constraints.1];
+        let field_size = self.header.field_size as usize;
+        (0..self.header.n_constraints).map(move |i| {
+            let mut row = || {
+                read_constraint_vec(&mut reader, field_size)
+                    .with_context(|| format!("constraint {} is truncated", i))
+            };
+            Ok((row()?, row()?, row()?))
+        })
+    }
+
+    /// Converts this to the R1CS format used by the rest of this crate, in one pass over the constraints. Fails if the circuit applies custom gates
+    pub fn to_crate_format(&self) -> Result<R1CSWithMetadata<T>, Error> {
+        check_custom_gates(&self.custom_gates, &self.custom_gate_applications)?;
+        let n = self.header.n_constraints as usize;
+        let (mut a_rows, mut b_rows, mut c_rows) = (
+            Vec::with_capacity(n),
+            Vec::with_capacity(n),
+            Vec::with_capacity(n),
+        );
+        for constraint in self.constraints() {
+            let (a, b, c) = constraint?;
+            a_rows.push(a);
+            b_rows.push(b);
+            c_rows.push(c);
+        }
+        Ok(self.header.with_metadata(SparseR1CS {
+            a_rows: SparseFMatrix(a_rows),
+            b_rows: SparseFMatrix(b_rows),
+            c_rows: SparseFMatrix(c_rows),
+        }))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::MappedR1CS;
+    use crate::{
+        circom::{
+            modulus,
+            r1cs::test::{r1cs_bytes, with_custom_gates},
+        },
+        zkp::test::F61,
+        FVec, Fr,
+    };
+
+    #[test]
+    fn mapped_r1cs() {
+        let path = std::env::temp_dir().join(format!("volonym_mapped_{}.r1cs", std::process::id()));
+        std::fs::write(&path, r1cs_bytes(&Fr::prime(), 32)).unwrap();
+        let mapped = MappedR1CS::<Fr>::open(&path).unwrap();
+        assert_eq!(mapped.constraints().count(), 1);
+        let circuit = mapped.to_crate_format().unwrap();
+        let witness = FVec([1, 3, 5, 15].into_iter().map(Fr::from).collect());
+        assert!(circuit.check_witness(&witness).is_ok());
+        assert_eq!(circuit.public_inputs_indices, vec![1]);
+        assert!(MappedR1CS::<F61>::open(&path).is_err());
+
+        std::fs::write(&path, with_custom_gates(r1cs_bytes(&Fr::prime(), 32), 32)).unwrap();
+        let mapped = MappedR1CS::<Fr>::open(&path).unwrap();
+        assert_eq!(mapped.custom_gates[0].name, "CMul");
+        assert!(mapped.to_crate_format().is_err());
+
+        // A truncated file is an error, not a panic
+        let bytes = r1cs_bytes(&modulus::<F61>(), 8);
+        std::fs::write(&path, &bytes[..bytes.len() - 40]).unwrap();
+        assert!(MappedR1CS::<F61>::open(&path).is_err());
+
+        // So are sizes and counts that overflow when added or multiplied
+        let mut bytes = r1cs_bytes(&modulus::<F61>(), 8);
+        bytes[16..24].copy_from_slice(&u64::MAX.to_le_bytes());
+        std::fs::write(&path, &bytes).unwrap();
+        assert!(MappedR1CS::<F61>::open(&path).is_err());
+        let mut bytes = r1cs_bytes(&modulus::<F61>(), 8);
+        bytes[60..64].copy_from_slice(&u32::MAX.to_le_bytes());
+        std::fs::write(&path, &bytes).unwrap();
+        assert!(MappedR1CS::<F61>::open(&path).is_err());
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -1,42 +1,91 @@
 //! Modules for reading and writing witness and R1CS from circom format
 
-use std::io::Read;
+use std::io::{Read, Write};
 
-use byteorder::{LittleEndian, ReadBytesExt};
-use ff::PrimeField;
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use num_bigint::BigUint;
 
+#[cfg(feature = "witness-wasm")]
+pub mod calculator;
+pub mod compile;
+#[cfg(feature = "circom-gen")]
 pub mod generator;
+pub mod graph;
+pub mod json;
+#[cfg(feature = "mmap")]
+pub mod mmap;
 
-use crate::{Fr, FrRepr, SparseVec};
+use crate::{SparseVec, PF};
 pub mod r1cs;
+pub mod stats;
+pub mod sym;
 pub mod witness;
 
-/// Reads l Frs from a circom file
-/// I believe this should be more performant because it seems the compiler will be able to vectorize easily than doing multiple individual function calls
-fn read_fr_vec<R: Read>(mut reader: R, l: usize) -> Vec<Fr> {
-    let mut bufs = vec![[0u8; 32]; l];
-    bufs.iter_mut()
-        .map(|buf| {
-            reader.read_exact(buf).unwrap();
-            buf.reverse();
-            Fr::from_repr(FrRepr(*buf)).unwrap()
-        })
-        .collect()
-}
+/// Most entries `read_constraint_vec` allocates before reading them
+const MAX_PREALLOCATED_ROW_LEN: usize = 1 << 12;
 
-/// Reads l u32 wire labels and corresponding Frs from a R1CS file
-pub fn read_constraint_vec<R: Read>(mut reader: R) -> SparseVec<Fr> {
-    let l = reader.read_u32::<LittleEndian>().unwrap() as usize;
-    let mut constraints = Vec::with_capacity(l);
+/// Reads l u32 wire labels and corresponding field elements of `field_size` bytes from a R1CS file
+pub fn read_constraint_vec<R: Read, T: PF>(
+    mut reader: R,
+    field_size: usize,
+) -> Result<SparseVec<T>, std::io::Error> {
+    let l = reader.read_u32::<LittleEndian>()? as usize;
+    // `l` is untrusted, so only preallocate what a short row needs
+    let mut constraints = Vec::with_capacity(l.min(MAX_PREALLOCATED_ROW_LEN));
+    let mut buf = vec![0u8; field_size];
     for _ in 0..l {
-        constraints.push((reader.read_u32::<LittleEndian>().unwrap() as usize, {
-            let mut buf = [0u8; 32];
-            reader.read_exact(&mut buf).unwrap();
-            buf.reverse();
-            Fr::from_repr(FrRepr(buf)).unwrap()
-        }))
+        let wire = reader.read_u32::<LittleEndian>()? as usize;
+        reader.read_exact(&mut buf)?;
+        constraints.push((wire, from_le_bytes(&buf)));
+    }
+    Ok(SparseVec(constraints))
+}
+
+/// Writes a sparse row of a R1CS in the format `read_constraint_vec` reads, with field elements of `field_size` bytes
+pub fn write_constraint_vec<W: Write, T: PF>(
+    mut writer: W,
+    row: &SparseVec<T>,
+    field_size: usize,
+) -> Result<(), std::io::Error> {
+    writer.write_u32::<LittleEndian>(row.0.len() as u32)?;
+    for (wire, x) in &row.0 {
+        writer.write_u32::<LittleEndian>(*wire as u32)?;
+        writer.write_all(&to_le_bytes(x, field_size))?;
+    }
+    Ok(())
+}
+
+/// The little endian bytes of `x`'s value, padded to `field_size`
+pub(crate) fn to_le_bytes<T: PF>(x: &T, field_size: usize) -> Vec<u8> {
+    let mut bytes = x.to_repr().as_ref().to_vec();
+    // The field chooses its representation's endianness. One's representation starts with 1 iff it is little endian
+    if T::ONE.to_repr().as_ref()[0] != 1 {
+        bytes.reverse();
     }
-    SparseVec(constraints)
+    bytes.resize(field_size, 0);
+    bytes
+}
+
+/// The decimal string of `x`'s value, as circom's JSON files and snarkjs write field elements
+pub fn to_decimal<T: PF>(x: &T) -> String {
+    BigUint::from_bytes_le(&to_le_bytes(x, x.to_repr().as_ref().len())).to_string()
+}
+
+/// The field element whose value has the little endian bytes `bytes`, reduced modulo the prime.
+/// Unlike `PrimeField::from_repr`, it doesn't depend on the field's representation
+fn from_le_bytes<T: PF>(bytes: &[u8]) -> T {
+    let shift = T::from(1 << 32).square();
+    bytes.chunks(8).rev().fold(T::ZERO, |acc, chunk| {
+        let mut limb = [0u8; 8];
+        limb[..chunk.len()].copy_from_slice(chunk);
+        acc * shift + T::from(u64::from_le_bytes(limb))
+    })
+}
+
+/// The modulus of a field, to compare with the prime in a circom file's header
+pub fn modulus<T: PF>() -> BigUint {
+    BigUint::parse_bytes(T::MODULUS.trim_start_matches("0x").as_bytes(), 16)
+        .expect("the modulus is hex")
 }
 
 #[cfg(test)]
@@ -56,7 +105,8 @@ mod test {
         let r1cs_reader = BufReader::new(r1cs_file);
         let r1cs = R1CSFile::from_reader(r1cs_reader)
             .unwrap()
-            .to_crate_format();
+            .to_crate_format()
+            .unwrap();
 
         assert!(e2e_test(witness, r1cs).is_ok());
     }
@@ -1,23 +1,28 @@
 //! Borrowed extensively from Nova Scotia https://github.com/nalinbhardwaj/Nova-Scotia/
 
-use anyhow::{bail, Error};
-use byteorder::{LittleEndian, ReadBytesExt};
+use anyhow::{bail, Context, Error};
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
 use itertools::Itertools;
 use num_bigint::{BigInt, Sign};
 use num_traits::One as _;
+use serde_json::{json, Value};
 use std::{
-    collections::HashMap,
+    collections::{BTreeMap, HashMap},
     fmt,
-    io::{Read, Seek, SeekFrom},
+    io::{Read, Seek, SeekFrom, Write},
 };
 
 use crate::{
-    zkp::{R1CSWithMetadata, SparseR1CS, R1CS},
-    Fr, SparseFMatrix, SparseVec,
+    error::UnsupportedFeature,
+    zkp::{quicksilver::CustomGate, R1CSWithMetadata, SparseR1CS, R1CS},
+    FVec, Fr, SparseFMatrix, SparseVec, PF,
 };
 use num_bigint::BigUint;
 
-use super::read_constraint_vec;
+use super::{
+    from_le_bytes, modulus, read_constraint_vec, to_decimal, to_le_bytes, witness::element_size,
+    write_constraint_vec,
+};
 
 // R1CSFile's header
 #[derive(Debug)]
@@ -33,123 +38,382 @@ pub struct Header {
 }
 
 #[derive(Debug)]
-pub struct Constraints {
-    a_rows: SparseFMatrix<Fr>,
-    b_rows: SparseFMatrix<Fr>,
-    c_rows: SparseFMatrix<Fr>,
+pub struct Constraints<T: PF = Fr> {
+    a_rows: SparseFMatrix<T>,
+    b_rows: SparseFMatrix<T>,
+    c_rows: SparseFMatrix<T>,
+}
+
+/// A custom gate template declared with circom's `pragma custom_templates`, with the parameters it is instantiated with
+#[derive(Clone, Debug)]
+pub struct CustomGateTemplate<T: PF = Fr> {
+    pub name: String,
+    pub parameters: Vec<T>,
+}
+
+/// A custom gate applied to signals, by its index in the file's custom gate templates
+#[derive(Clone, Debug)]
+pub struct CustomGateApplication {
+    pub template: u32,
+    pub signals: Vec<u64>,
 }
 
+/// A circom .r1cs file over the field `T`, which must have the same prime as the file
 #[derive(Debug)]
-pub struct R1CSFile {
+pub struct R1CSFile<T: PF = Fr> {
     pub version: u32,
     pub header: Header,
-    pub constraints: Constraints,
+    pub constraints: Constraints<T>,
     pub wire_mapping: Vec<u64>,
+    /// Custom gates, which circom 2.1 can output with `--O2`. Circuits applying them can't be converted to this crate's format
+    pub custom_gates: Vec<CustomGateTemplate<T>>,
+    pub custom_gate_applications: Vec<CustomGateApplication>,
 }
 
-impl R1CSFile {
-    /// Converts this to the R1CS format used by the rest of this crate
-    pub fn to_crate_format(self) -> R1CSWithMetadata<Fr> {
-        let r1cs_ = SparseR1CS {
+impl<T: PF> R1CSFile<T> {
+    /// Converts this to the R1CS format used by the rest of this crate. Fails if the circuit applies custom gates
+    pub fn to_crate_format(self) -> Result<R1CSWithMetadata<T>, UnsupportedFeature> {
+        check_custom_gates(&self.custom_gates, &self.custom_gate_applications)?;
+        Ok(self.header.with_metadata(SparseR1CS {
             a_rows: self.constraints.a_rows,
             b_rows: self.constraints.b_rows,
             c_rows: self.constraints.c_rows,
-        };
-        let pub_in_start = 1 + self.header.n_pub_out as usize;
-        let public_outputs_indices = (1..pub_in_start).collect_vec();
-        let public_inputs_indices =
-            (pub_in_start..pub_in_start + self.header.n_pub_in as usize).collect_vec();
-        let unpadded_wtns_len = self.header.n_wires as usize; // overflow is possible but not practical given circuits of feasible size
-        let r1cs = R1CS::Sparse(r1cs_);
-        R1CSWithMetadata {
-            r1cs,
-            public_inputs_indices,
-            public_outputs_indices,
-            unpadded_wtns_len,
-        }
+        }))
     }
 
-    /// Parses bytes in a circom .r1cs binary format
+    /// Parses bytes in a circom .r1cs binary format. Fails if the file's prime isn't `T`'s modulus
     pub fn from_reader<R: Read + Seek>(mut reader: R) -> Result<Self, Error> {
-        let mut magic = [0u8; 4];
-        reader.read_exact(&mut magic)?;
-        if magic != "r1cs".as_bytes() {
-            bail!("Invalid magic number");
+        let (version, sections) = read_sections(&mut reader)?;
+
+        let size = seek_to_section(&mut reader, &sections, HEADER_TYPE)?;
+        let header = read_header(&mut reader, size)?;
+        check_prime::<T>(&header)?;
+
+        let size = seek_to_section(&mut reader, &sections, CONSTRAINT_TYPE)?;
+        let constraints = read_constraints(&mut reader, size, &header)?;
+
+        let size = seek_to_section(&mut reader, &sections, WIRE2LABEL_TYPE)?;
+        let wire_mapping = read_map(&mut reader, size, &header)?;
+
+        let mut custom_gates = vec![];
+        let mut custom_gate_applications = vec![];
+        if sections.contains_key(&CUSTOM_GATES_LIST_TYPE) {
+            seek_to_section(&mut reader, &sections, CUSTOM_GATES_LIST_TYPE)?;
+            custom_gates = read_custom_gates(&mut reader, header.field_size as usize)?;
         }
+        if sections.contains_key(&CUSTOM_GATES_APPLICATION_TYPE) {
+            seek_to_section(&mut reader, &sections, CUSTOM_GATES_APPLICATION_TYPE)?;
+            custom_gate_applications = read_custom_gate_applications(&mut reader)?;
+        }
+
+        Ok(R1CSFile {
+            version,
+            header,
+            constraints,
+            wire_mapping,
+            custom_gates,
+            custom_gate_applications,
+        })
+    }
 
-        let version = reader.read_u32::<LittleEndian>()?;
-        if version != 1 {
-            bail!("Unsupported version")
+    /// A JSON description of the file for other tools: the header, each of A, B, and C as sparse `[constraint, wire, value]` triplets,
+    /// the wire to label map, and any custom gates. Field elements are decimal strings
+    pub fn to_json(&self) -> Value {
+        let triplets = |m: &SparseFMatrix<T>| {
+            m.0.iter()
+                .enumerate()
+                .flat_map(|(i, row)| row.0.iter().map(move |(wire, x)| (i, *wire, x)))
+                .map(|(i, wire, x)| json!([i, wire, to_decimal(x)]))
+                .collect::<Vec<_>>()
+        };
+        let header = &self.header;
+        json!({
+            "version": self.version,
+            "header": {
+                "field_size": header.field_size,
+                "prime": header.prime_size.to_string(),
+                "n_wires": header.n_wires,
+                "n_pub_out": header.n_pub_out,
+                "n_pub_in": header.n_pub_in,
+                "n_prv_in": header.n_prv_in,
+                "n_labels": header.n_labels,
+                "n_constraints": header.n_constraints,
+            },
+            "constraints": {
+                "a": triplets(&self.constraints.a_rows),
+                "b": triplets(&self.constraints.b_rows),
+                "c": triplets(&self.constraints.c_rows),
+            },
+            "wire_mapping": self.wire_mapping,
+            "custom_gates": self.custom_gates.iter().map(|gate| json!({
+                "name": gate.name,
+                "parameters": gate.parameters.iter().map(to_decimal).collect::<Vec<_>>(),
+            })).collect::<Vec<_>>(),
+            "custom_gate_applications": self.custom_gate_applications.iter().map(|application| json!({
+                "template": application.template,
+                "signals": application.signals,
+            })).collect::<Vec<_>>(),
+        })
+    }
+
+    /// Converts a witness to one with a value per wire, in the order of the circuit's wires. A witness with a value per label,
+    /// i.e. per signal before circom's optimizer removed and renumbered wires, is mapped through `wire_mapping`.
+    /// A witness with a value per wire, such as circom's witness calculator outputs, is returned as is
+    pub fn wire_witness(&self, witness: FVec<T>) -> Result<FVec<T>, Error> {
+        let (n_wires, n_labels) = (self.header.n_wires as usize, self.header.n_labels as usize);
+        if witness.0.len() == n_wires {
+            return Ok(witness);
         }
+        if witness.0.len() != n_labels {
+            bail!(
+                "the witness has {} values but the circuit has {} wires and {} labels",
+                witness.0.len(),
+                n_wires,
+                n_labels
+            );
+        }
+        self.wire_mapping
+            .iter()
+            .map(|label| {
+                witness
+                    .0
+                    .get(*label as usize)
+                    .copied()
+                    .with_context(|| format!("wire label {} is outside the witness", label))
+            })
+            .collect::<Result<_, _>>()
+            .map(FVec)
+    }
 
-        let num_sections = reader.read_u32::<LittleEndian>()?;
+    /// Converts a circuit of this crate's format, e.g. one made with `gadgets::CircuitBuilder`, so circom tools can read it.
+    /// Its wires must be in circom's order: the constant one, the public outputs, then the public inputs. Its only custom gate can be
+    /// `CircuitBuilder`'s constraining wire 0 to be one, which circom assumes, and it can't have lookups, memories, branches, or CCS constraints.
+    /// Which private wires are inputs isn't known, so the file has none, and each wire is its own label
+    pub fn from_crate_format(circuit: &R1CSWithMetadata<T>) -> Result<Self, Error> {
+        let n_pub_out = circuit.public_outputs_indices.len();
+        let n_pub_in = circuit.public_inputs_indices.len();
+        if circuit.public_outputs_indices != (1..1 + n_pub_out).collect_vec()
+            || circuit.public_inputs_indices
+                != (1 + n_pub_out..1 + n_pub_out + n_pub_in).collect_vec()
+        {
+            bail!("the public outputs and then the public inputs must follow wire 0");
+        }
+        let one_gate = |gate: &CustomGate<T>| {
+            gate.coeffs == [T::ONE, -T::ONE] && gate.wires == [vec![0], vec![]]
+        };
+        if !circuit.custom_gates.iter().all(one_gate)
+            || !circuit.lookups.is_empty()
+            || !circuit.memories.is_empty()
+            || !circuit.branches.is_empty()
+            || !circuit.ccs.is_empty()
+        {
+            bail!("circom's format only has R1CS constraints");
+        }
 
-        // section type -> file offset
-        let mut section_offsets = HashMap::<u32, u64>::new();
-        let mut section_sizes = HashMap::<u32, u64>::new();
+        let r1cs = circuit.r1cs.clone().to_sparse();
+        let n_wires = circuit.unpadded_wtns_len;
+        Ok(R1CSFile {
+            version: 1,
+            header: Header {
+                field_size: element_size::<T>(),
+                prime_size: modulus::<T>(),
+                n_wires: n_wires as u32,
+                n_pub_out: n_pub_out as u32,
+                n_pub_in: n_pub_in as u32,
+                n_prv_in: 0,
+                n_labels: n_wires as u64,
+                n_constraints: r1cs.a_rows.0.len() as u32,
+            },
+            constraints: Constraints {
+                a_rows: r1cs.a_rows,
+                b_rows: r1cs.b_rows,
+                c_rows: r1cs.c_rows,
+            },
+            wire_mapping: (0..n_wires as u64).collect(),
+            custom_gates: vec![],
+            custom_gate_applications: vec![],
+        })
+    }
 
-        // get file offset of each section
-        for _ in 0..num_sections {
-            let section_type = reader.read_u32::<LittleEndian>()?;
-            let section_size = reader.read_u64::<LittleEndian>()?;
-            let offset = reader.seek(SeekFrom::Current(0))?;
-            section_offsets.insert(section_type, offset);
-            section_sizes.insert(section_type, section_size);
-            reader.seek(SeekFrom::Current(section_size as i64))?;
+    /// Writes this in circom's .r1cs binary format, with the header, constraints, and wire to label map sections in that order
+    pub fn to_writer<W: Write>(&self, mut writer: W) -> Result<(), Error> {
+        let header = &self.header;
+        let field_size = header.field_size as usize;
+        let mut prime = header.prime_size.to_bytes_le();
+        prime.resize(field_size, 0);
+        let mut header_section = Vec::with_capacity(32 + field_size);
+        header_section.write_u32::<LittleEndian>(header.field_size)?;
+        header_section.extend(prime);
+        for n in [
+            header.n_wires,
+            header.n_pub_out,
+            header.n_pub_in,
+            header.n_prv_in,
+        ] {
+            header_section.write_u32::<LittleEndian>(n)?;
+        }
+        header_section.write_u64::<LittleEndian>(header.n_labels)?;
+        header_section.write_u32::<LittleEndian>(header.n_constraints)?;
+
+        let mut constraints_section = vec![];
+        let Constraints {
+            a_rows,
+            b_rows,
+            c_rows,
+        } = &self.constraints;
+        for ((a, b), c) in a_rows.0.iter().zip(&b_rows.0).zip(&c_rows.0) {
+            for row in [a, b, c] {
+                write_constraint_vec(&mut constraints_section, row, field_size)?;
+            }
         }
 
-        let header_type = 1;
-        let constraint_type = 2;
-        let wire2label_type = 3;
+        let mut map_section = Vec::with_capacity(8 * self.wire_mapping.len());
+        for label in &self.wire_mapping {
+            map_section.write_u64::<LittleEndian>(*label)?;
+        }
 
-        reader.seek(SeekFrom::Start(*section_offsets.get(&header_type).unwrap()))?;
-        let header = read_header(&mut reader, *section_sizes.get(&header_type).unwrap())?;
-        if header.field_size != 32 {
-            bail!("This parser only supports 32-byte fields");
+        let mut sections = vec![
+            (HEADER_TYPE, header_section),
+            (CONSTRAINT_TYPE, constraints_section),
+            (WIRE2LABEL_TYPE, map_section),
+        ];
+        if !self.custom_gates.is_empty() || !self.custom_gate_applications.is_empty() {
+            let mut list_section = vec![];
+            list_section.write_u32::<LittleEndian>(self.custom_gates.len() as u32)?;
+            for gate in &self.custom_gates {
+                list_section.extend(gate.name.as_bytes());
+                list_section.push(0);
+                list_section.write_u32::<LittleEndian>(gate.parameters.len() as u32)?;
+                for parameter in &gate.parameters {
+                    list_section.extend(to_le_bytes(parameter, field_size));
+                }
+            }
+            let mut application_section = vec![];
+            application_section
+                .write_u32::<LittleEndian>(self.custom_gate_applications.len() as u32)?;
+            for application in &self.custom_gate_applications {
+                application_section.write_u32::<LittleEndian>(application.template)?;
+                application_section.write_u32::<LittleEndian>(application.signals.len() as u32)?;
+                for signal in &application.signals {
+                    application_section.write_u64::<LittleEndian>(*signal)?;
+                }
+            }
+            sections.push((CUSTOM_GATES_LIST_TYPE, list_section));
+            sections.push((CUSTOM_GATES_APPLICATION_TYPE, application_section));
         }
 
-        if header.prime_size != Fr::prime() {
-            bail!("This parser only supports bn254");
+        writer.write_all(b"r1cs")?;
+        writer.write_u32::<LittleEndian>(self.version)?;
+        writer.write_u32::<LittleEndian>(sections.len() as u32)?;
+        for (section_type, section) in sections {
+            writer.write_u32::<LittleEndian>(section_type)?;
+            writer.write_u64::<LittleEndian>(section.len() as u64)?;
+            writer.write_all(&section)?;
         }
+        Ok(())
+    }
+}
 
-        reader.seek(SeekFrom::Start(
-            *section_offsets.get(&constraint_type).unwrap(),
-        ))?;
+pub(super) const HEADER_TYPE: u32 = 1;
+pub(super) const CONSTRAINT_TYPE: u32 = 2;
+const WIRE2LABEL_TYPE: u32 = 3;
+pub(super) const CUSTOM_GATES_LIST_TYPE: u32 = 4;
+pub(super) const CUSTOM_GATES_APPLICATION_TYPE: u32 = 5;
 
-        let constraints = read_constraints(
-            &mut reader,
-            *section_sizes.get(&constraint_type).unwrap(),
-            &header,
-        );
+impl Header {
+    /// The circuit with constraints `r1cs` and this header's public inputs and outputs
+    pub(super) fn with_metadata<T: PF>(&self, r1cs: SparseR1CS<T>) -> R1CSWithMetadata<T> {
+        let pub_in_start = 1 + self.n_pub_out as usize;
+        let public_outputs_indices = (1..pub_in_start).collect_vec();
+        let public_inputs_indices =
+            (pub_in_start..pub_in_start + self.n_pub_in as usize).collect_vec();
+        let unpadded_wtns_len = self.n_wires as usize; // overflow is possible but not practical given circuits of feasible size
+        R1CSWithMetadata {
+            r1cs: R1CS::Sparse(r1cs),
+            public_inputs_indices,
+            public_outputs_indices,
+            unpadded_wtns_len,
+            custom_gates: vec![],
+            lookups: vec![],
+            memories: vec![],
+            branches: vec![],
+            ccs: vec![],
+            signal_names: BTreeMap::new(),
+        }
+    }
+}
 
-        reader.seek(SeekFrom::Start(
-            *section_offsets.get(&wire2label_type).unwrap(),
-        ))?;
-        let wire_mapping = read_map(
-            &mut reader,
-            *section_sizes.get(&wire2label_type).unwrap(),
-            &header,
-        )?;
+/// File offset and size of each section of a .r1cs file by its type
+pub(super) type Sections = HashMap<u32, (u64, u64)>;
 
-        Ok(R1CSFile {
-            version,
-            header,
-            constraints,
-            wire_mapping,
-        })
+/// Checks the magic number and version, returning the version and the file offset and size of each section by its type
+pub(super) fn read_sections<R: Read + Seek>(mut reader: R) -> Result<(u32, Sections), Error> {
+    let mut magic = [0u8; 4];
+    reader.read_exact(&mut magic)?;
+    if magic != "r1cs".as_bytes() {
+        bail!("Invalid magic number");
+    }
+
+    let version = reader.read_u32::<LittleEndian>()?;
+    if version != 1 {
+        bail!("Unsupported version")
+    }
+
+    let num_sections = reader.read_u32::<LittleEndian>()?;
+    let start = reader.stream_position()?;
+    let len = reader.seek(SeekFrom::End(0))?;
+    reader.seek(SeekFrom::Start(start))?;
+
+    // section type -> file offset and size
+    let mut sections = Sections::new();
+
+    // get file offset of each section
+    for _ in 0..num_sections {
+        let section_type = reader.read_u32::<LittleEndian>()?;
+        let section_size = reader.read_u64::<LittleEndian>()?;
+        let offset = reader.stream_position()?;
+        if section_size > len.saturating_sub(offset) {
+            bail!("Section {} runs past the end of the file", section_type);
+        }
+        sections.insert(section_type, (offset, section_size));
+        reader.seek(SeekFrom::Current(section_size as i64))?;
     }
+    Ok((version, sections))
 }
 
-fn read_header<R: Read>(mut reader: R, size: u64) -> Result<Header, Error> {
-    let field_size = reader.read_u32::<LittleEndian>()?;
-    let mut prime_size_bytes = vec![0u8; field_size as usize];
-    reader.read_exact(&mut prime_size_bytes)?;
-    let prime_size = BigUint::from_bytes_le(&prime_size_bytes);
+/// Seeks to the start of a section, returning its size
+fn seek_to_section<R: Seek>(
+    reader: &mut R,
+    sections: &Sections,
+    section_type: u32,
+) -> Result<u64, Error> {
+    let (offset, size) = sections
+        .get(&section_type)
+        .with_context(|| format!("the file has no section {}", section_type))?;
+    reader.seek(SeekFrom::Start(*offset))?;
+    Ok(*size)
+}
+
+pub(super) fn check_prime<T: PF>(header: &Header) -> Result<(), Error> {
+    if header.prime_size != modulus::<T>() {
+        bail!(
+            "The file's prime {} isn't the modulus of the field it is parsed into",
+            header.prime_size
+        );
+    }
+    Ok(())
+}
 
+pub(super) fn read_header<R: Read>(mut reader: R, size: u64) -> Result<Header, Error> {
+    let field_size = reader.read_u32::<LittleEndian>()?;
     if size != 32 + field_size as u64 {
         bail!("Invalid header section size");
     }
 
+    let mut prime_size_bytes = vec![0u8; field_size as usize];
+    reader.read_exact(&mut prime_size_bytes)?;
+    let prime_size = BigUint::from_bytes_le(&prime_size_bytes);
+
     Ok(Header {
         field_size,
         prime_size,
@@ -162,25 +426,34 @@ fn read_header<R: Read>(mut reader: R, size: u64) -> Result<Header, Error> {
     })
 }
 
-fn read_constraints<R: Read>(mut reader: R, _size: u64, header: &Header) -> Constraints {
+fn read_constraints<R: Read, T: PF>(
+    mut reader: R,
+    size: u64,
+    header: &Header,
+) -> Result<Constraints<T>, Error> {
+    // Each constraint is at least the three u32 lengths of its rows
+    if header.n_constraints as u64 * 12 > size {
+        bail!("Invalid constraint section size");
+    }
+    let field_size = header.field_size as usize;
     let mut a_rows = Vec::with_capacity(header.n_constraints as usize);
     let mut b_rows = Vec::with_capacity(header.n_constraints as usize);
     let mut c_rows = Vec::with_capacity(header.n_constraints as usize);
 
     for _ in 0..header.n_constraints {
-        a_rows.push(read_constraint_vec(&mut reader));
-        b_rows.push(read_constraint_vec(&mut reader));
-        c_rows.push(read_constraint_vec(&mut reader));
+        a_rows.push(read_constraint_vec(&mut reader, field_size)?);
+        b_rows.push(read_constraint_vec(&mut reader, field_size)?);
+        c_rows.push(read_constraint_vec(&mut reader, field_size)?);
     }
     let a_rows = SparseFMatrix(a_rows);
     let b_rows = SparseFMatrix(b_rows);
     let c_rows = SparseFMatrix(c_rows);
 
-    Constraints {
+    Ok(Constraints {
         a_rows,
         b_rows,
         c_rows,
-    }
+    })
 }
 
 fn read_map<R: Read>(mut reader: R, size: u64, header: &Header) -> Result<Vec<u64>, Error> {
@@ -191,12 +464,79 @@ fn read_map<R: Read>(mut reader: R, size: u64, header: &Header) -> Result<Vec<u6
     for _ in 0..header.n_wires {
         vec.push(reader.read_u64::<LittleEndian>()?);
     }
-    if vec[0] != 0 {
+    if vec.first() != Some(&0) {
         bail!("Wire 0 should always be mapped to 0");
     }
     Ok(vec)
 }
 
+/// Reads the custom gates list section: each template's null terminated name and its parameters
+pub(super) fn read_custom_gates<R: Read, T: PF>(
+    mut reader: R,
+    field_size: usize,
+) -> Result<Vec<CustomGateTemplate<T>>, Error> {
+    let n = reader.read_u32::<LittleEndian>()?;
+    let mut buf = vec![0u8; field_size];
+    (0..n)
+        .map(|_| {
+            let mut name = vec![];
+            loop {
+                match reader.read_u8()? {
+                    0 => break,
+                    b => name.push(b),
+                }
+            }
+            let n_parameters = reader.read_u32::<LittleEndian>()?;
+            let parameters = (0..n_parameters)
+                .map(|_| {
+                    reader.read_exact(&mut buf)?;
+                    Ok(from_le_bytes(&buf))
+                })
+                .collect::<Result<_, Error>>()?;
+            Ok(CustomGateTemplate {
+                name: String::from_utf8(name).context("custom gate name isn't UTF-8")?,
+                parameters,
+            })
+        })
+        .collect()
+}
+
+/// Reads the custom gates application section: each application's template index and signals
+pub(super) fn read_custom_gate_applications<R: Read>(
+    mut reader: R,
+) -> Result<Vec<CustomGateApplication>, Error> {
+    let n = reader.read_u32::<LittleEndian>()?;
+    (0..n)
+        .map(|_| {
+            let template = reader.read_u32::<LittleEndian>()?;
+            let n_signals = reader.read_u32::<LittleEndian>()?;
+            let signals = (0..n_signals)
+                .map(|_| reader.read_u64::<LittleEndian>())
+                .collect::<Result<_, _>>()?;
+            Ok(CustomGateApplication { template, signals })
+        })
+        .collect()
+}
+
+/// Fails with the names of the custom gates applied, if any are
+pub(super) fn check_custom_gates<T: PF>(
+    templates: &[CustomGateTemplate<T>],
+    applications: &[CustomGateApplication],
+) -> Result<(), UnsupportedFeature> {
+    if applications.is_empty() {
+        return Ok(());
+    }
+    let names = applications
+        .iter()
+        .map(|a| match templates.get(a.template as usize) {
+            Some(template) => template.name.clone(),
+            None => format!("undeclared template {}", a.template),
+        })
+        .unique()
+        .collect();
+    Err(UnsupportedFeature::CustomGates(names))
+}
+
 fn factor_leading_sign(coeffs: &SparseVec<Fr>) -> (i32, String) {
     if coeffs.0.is_empty() {
         return (0, "0".to_string());
@@ -231,7 +571,7 @@ fn factor_leading_sign(coeffs: &SparseVec<Fr>) -> (i32, String) {
     (sign, terms.join(" "))
 }
 
-impl fmt::Display for R1CSFile {
+impl fmt::Display for R1CSFile<Fr> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         writeln!(f, "=== R1CS Binary Format Parser ===\n")?;
         writeln!(f, "Version: {}", self.version)?;
@@ -246,11 +586,28 @@ impl fmt::Display for R1CSFile {
         writeln!(f, "  Number of labels: {}", self.header.n_labels)?;
         writeln!(f, "  Number of constraints: {}", self.header.n_constraints)?;
         writeln!(f, "\n=== Constraints Section ===\n")?;
-        write!(f, "{}", self.constraints)
+        write!(f, "{}", self.constraints)?;
+        if !self.custom_gates.is_empty() {
+            writeln!(f, "\n=== Custom Gates ===\n")?;
+            for (i, gate) in self.custom_gates.iter().enumerate() {
+                let applications = self
+                    .custom_gate_applications
+                    .iter()
+                    .filter(|a| a.template as usize == i)
+                    .count();
+                let parameters = gate.parameters.iter().map(|p| p.to_string()).join(", ");
+                writeln!(
+                    f,
+                    "  {}({}): applied {} times",
+                    gate.name, parameters, applications
+                )?;
+            }
+        }
+        Ok(())
     }
 }
 
-impl fmt::Display for Constraints {
+impl fmt::Display for Constraints<Fr> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         for i in 0..self.a_rows.0.len() {
             let (a_sign, a_str) = factor_leading_sign(&self.a_rows.0[i]);
@@ -291,23 +648,254 @@ impl fmt::Display for Constraints {
 }
 
 #[cfg(test)]
-mod test {
-    use std::{fs::File, io::BufReader};
+pub(super) mod test {
+    use std::{
+        fs::File,
+        io::{BufReader, Cursor},
+    };
 
     use super::*;
+    use crate::{
+        actors::actors::{Prover, Verifier},
+        gadgets::CircuitBuilder,
+        fields::bls12_381,
+        zkp::test::F61,
+    };
     #[test]
     fn read_r1cs_file() {
         let file = File::open("src/circom/examples/test.r1cs").unwrap();
         let buf_reader = BufReader::new(file);
-        let r1cs = R1CSFile::from_reader(buf_reader).unwrap();
+        let r1cs: R1CSFile = R1CSFile::from_reader(buf_reader).unwrap();
+        assert_eq!(r1cs.constraints.a_rows.0.len(), r1cs.header.n_constraints as usize);
+    }
+
+    /// A .r1cs file with prime `prime` of `field_size` bytes for w1 * w2 = w3, with w1 public
+    pub(in crate::circom) fn r1cs_bytes(prime: &BigUint, field_size: usize) -> Vec<u8> {
+        let element = |x: u64| {
+            let mut bytes = x.to_le_bytes().to_vec();
+            bytes.resize(field_size, 0);
+            bytes
+        };
+        let mut header = (field_size as u32).to_le_bytes().to_vec();
+        let mut prime = prime.to_bytes_le();
+        prime.resize(field_size, 0);
+        header.extend(prime);
+        for n in [4u32, 0, 1, 2] {
+            header.extend(n.to_le_bytes());
+        }
+        header.extend(4u64.to_le_bytes());
+        header.extend(1u32.to_le_bytes());
+        let mut constraints = vec![];
+        for wire in [1u32, 2, 3] {
+            constraints.extend(1u32.to_le_bytes());
+            constraints.extend(wire.to_le_bytes());
+            constraints.extend(element(1));
+        }
+        let map = (0..4u64).flat_map(|i| i.to_le_bytes()).collect_vec();
+
+        let mut bytes = b"r1cs".to_vec();
+        bytes.extend(1u32.to_le_bytes());
+        bytes.extend(3u32.to_le_bytes());
+        for (section_type, section) in [(1u32, header), (2, constraints), (3, map)] {
+            bytes.extend(section_type.to_le_bytes());
+            bytes.extend((section.len() as u64).to_le_bytes());
+            bytes.extend(section);
+        }
+        bytes
+    }
+
+    #[test]
+    fn other_fields() {
+        let bytes = r1cs_bytes(&modulus::<F61>(), 8);
+        let circuit = R1CSFile::<F61>::from_reader(Cursor::new(&bytes))
+            .unwrap()
+            .to_crate_format()
+            .unwrap();
+        assert_eq!(circuit.public_inputs_indices, vec![1]);
+        let witness = FVec([1, 3, 5, 15].into_iter().map(F61::from).collect());
+        assert!(circuit.check_witness(&witness).is_ok());
+        assert!(R1CSFile::<Fr>::from_reader(Cursor::new(&bytes)).is_err());
+
+        let bytes = r1cs_bytes(&Fr::prime(), 32);
+        let circuit: R1CSFile = R1CSFile::from_reader(Cursor::new(&bytes)).unwrap();
+        let witness = FVec([1, 3, 5, 15].into_iter().map(Fr::from).collect());
+        assert!(circuit
+            .to_crate_format()
+            .unwrap()
+            .check_witness(&witness)
+            .is_ok());
+        assert!(R1CSFile::<bls12_381::Fr>::from_reader(Cursor::new(&bytes)).is_err());
+
+        let bytes = r1cs_bytes(&modulus::<bls12_381::Fr>(), 32);
+        let circuit = R1CSFile::<bls12_381::Fr>::from_reader(Cursor::new(&bytes))
+            .unwrap()
+            .to_crate_format()
+            .unwrap();
+        let witness = FVec([1, 3, 5, 15].into_iter().map(bls12_381::Fr::from).collect());
+        let proof = Prover::from_witness_and_circuit_unpadded(witness, circuit.clone())
+            .commit_and_prove()
+            .unwrap();
+        let public = Verifier::from_circuit(circuit).verify(&proof).unwrap();
+        assert_eq!(public.public_inputs, vec![bls12_381::Fr::from(3)]);
+        assert!(R1CSFile::<Fr>::from_reader(Cursor::new(&bytes)).is_err());
+    }
+
+    #[test]
+    fn rejects_counts_the_file_cannot_hold() {
+        let bytes = r1cs_bytes(&modulus::<F61>(), 8);
+        let patched = |offset: usize, value: &[u8]| {
+            let mut bytes = bytes.clone();
+            bytes[offset..offset + value.len()].copy_from_slice(value);
+            R1CSFile::<F61>::from_reader(Cursor::new(bytes))
+        };
+        // The header starts at 24, after the magic number, version, section count, and its type and size
+        assert!(patched(24, &u32::MAX.to_le_bytes()).is_err());
+        assert!(patched(24 + 4 + 8 + 16 + 8, &u32::MAX.to_le_bytes()).is_err());
+        // The map is the last section, of 4 wires
+        assert!(patched(bytes.len() - 32 - 8, &u64::MAX.to_le_bytes()).is_err());
+
+        let header = Header {
+            field_size: 8,
+            prime_size: modulus::<F61>(),
+            n_wires: 0,
+            n_pub_out: 0,
+            n_pub_in: 0,
+            n_prv_in: 0,
+            n_labels: 0,
+            n_constraints: 0,
+        };
+        assert!(read_map(&[][..], 0, &header).is_err());
+    }
+
+    #[test]
+    fn write_r1cs_file() {
+        let bytes = r1cs_bytes(&Fr::prime(), 32);
+        let r1cs: R1CSFile = R1CSFile::from_reader(Cursor::new(&bytes)).unwrap();
+        let mut written = vec![];
+        r1cs.to_writer(&mut written).unwrap();
+        assert_eq!(written, bytes);
+
+        let bytes = r1cs_bytes(&modulus::<F61>(), 8);
+        let r1cs = R1CSFile::<F61>::from_reader(Cursor::new(&bytes)).unwrap();
+        let mut written = vec![];
+        r1cs.to_writer(&mut written).unwrap();
+        assert_eq!(written, bytes);
+
+        // x * x = y, with x public
+        let mut builder = CircuitBuilder::<Fr>::new();
+        let x = builder.alloc(Fr::from(7));
+        builder.public_output(x);
+        builder.mul(x, x);
+        let (circuit, witness) = builder.build();
+        let mut written = vec![];
+        R1CSFile::from_crate_format(&circuit)
+            .unwrap()
+            .to_writer(&mut written)
+            .unwrap();
+        let read: R1CSFile = R1CSFile::from_reader(Cursor::new(&written)).unwrap();
+        let read = read.to_crate_format().unwrap();
+        assert_eq!(read.public_outputs_indices, vec![1]);
+        assert!(read.check_witness(&witness).is_ok());
+
+        // y isn't where circom's public outputs go
+        let mut builder = CircuitBuilder::<Fr>::new();
+        let x = builder.alloc(Fr::from(7));
+        let y = builder.mul(x, x);
+        builder.public_output(y);
+        assert!(R1CSFile::from_crate_format(&builder.build().0).is_err());
+    }
+
+    #[test]
+    fn wire_witness() {
+        let bytes = r1cs_bytes(&Fr::prime(), 32);
+        let mut r1cs: R1CSFile = R1CSFile::from_reader(Cursor::new(&bytes)).unwrap();
+        let witness = FVec([1, 3, 5, 15].into_iter().map(Fr::from).collect());
+        assert_eq!(r1cs.wire_witness(witness.clone()).unwrap().0, witness.0);
+
+        // As if the optimizer removed labels 2 and 4
+        r1cs.header.n_labels = 6;
+        r1cs.wire_mapping = vec![0, 1, 3, 5];
+        let by_label = FVec([1, 3, 9, 5, 9, 15].into_iter().map(Fr::from).collect());
+        let by_wire = r1cs.wire_witness(by_label).unwrap();
+        assert_eq!(by_wire.0, witness.0);
+        assert!(r1cs.wire_witness(FVec(vec![Fr::from(1); 5])).is_err());
+        assert!(r1cs
+            .to_crate_format()
+            .unwrap()
+            .check_witness(&by_wire)
+            .is_ok());
+    }
+
+    /// `r1cs_bytes` with a custom gate template `CMul` with parameter 3, applied to wires 1, 2, and 3
+    pub(in crate::circom) fn with_custom_gates(mut bytes: Vec<u8>, field_size: usize) -> Vec<u8> {
+        bytes[8..12].copy_from_slice(&5u32.to_le_bytes());
+        let mut list = 1u32.to_le_bytes().to_vec();
+        list.extend(b"CMul\0");
+        list.extend(1u32.to_le_bytes());
+        let mut parameter = 3u64.to_le_bytes().to_vec();
+        parameter.resize(field_size, 0);
+        list.extend(parameter);
+        let mut applications = 1u32.to_le_bytes().to_vec();
+        applications.extend(0u32.to_le_bytes());
+        applications.extend(3u32.to_le_bytes());
+        for signal in [1u64, 2, 3] {
+            applications.extend(signal.to_le_bytes());
+        }
+        for (section_type, section) in [(4u32, list), (5, applications)] {
+            bytes.extend(section_type.to_le_bytes());
+            bytes.extend((section.len() as u64).to_le_bytes());
+            bytes.extend(section);
+        }
+        bytes
+    }
+
+    #[test]
+    fn custom_gates() {
+        let bytes = with_custom_gates(r1cs_bytes(&Fr::prime(), 32), 32);
+        let r1cs: R1CSFile = R1CSFile::from_reader(Cursor::new(&bytes)).unwrap();
+        assert_eq!(r1cs.custom_gates[0].name, "CMul");
+        assert_eq!(r1cs.custom_gates[0].parameters, vec![Fr::from(3)]);
+        assert_eq!(r1cs.custom_gate_applications[0].signals, vec![1, 2, 3]);
+        assert!(r1cs.to_string().contains("CMul(3): applied 1 times"));
+        let mut written = vec![];
+        r1cs.to_writer(&mut written).unwrap();
+        assert_eq!(written, bytes);
+        let err = r1cs.to_crate_format().err().unwrap();
+        assert!(err.to_string().contains("CMul"));
+
+        // A missing section is an error rather than a panic
+        let mut bytes = r1cs_bytes(&Fr::prime(), 32);
+        bytes[8..12].copy_from_slice(&2u32.to_le_bytes());
+        assert!(R1CSFile::<Fr>::from_reader(Cursor::new(&bytes)).is_err());
+    }
+
+    #[test]
+    fn r1cs_json() {
+        let bytes = r1cs_bytes(&Fr::prime(), 32);
+        let r1cs: R1CSFile = R1CSFile::from_reader(Cursor::new(&bytes)).unwrap();
+        let json = r1cs.to_json();
+        assert_eq!(json["header"]["n_pub_in"], 1);
+        assert_eq!(json["header"]["prime"], Fr::prime().to_string());
+        assert_eq!(json["constraints"]["a"], serde_json::json!([[0, 1, "1"]]));
+        assert_eq!(json["constraints"]["c"], serde_json::json!([[0, 3, "1"]]));
+        assert_eq!(json["wire_mapping"], serde_json::json!([0, 1, 2, 3]));
+
+        let bytes = with_custom_gates(r1cs_bytes(&Fr::prime(), 32), 32);
+        let r1cs: R1CSFile = R1CSFile::from_reader(Cursor::new(&bytes)).unwrap();
+        let json = r1cs.to_json();
+        assert_eq!(json["custom_gates"][0]["name"], "CMul");
+        assert_eq!(
+            json["custom_gate_applications"][0]["signals"],
+            serde_json::json!([1, 2, 3])
+        );
     }
 
     #[test]
     fn correct_public_indices() {
         let file = File::open("src/circom/examples/test.r1cs").unwrap();
         let buf_reader = BufReader::new(file);
-        let r1cs = R1CSFile::from_reader(buf_reader).unwrap();
-        let r1cs = r1cs.to_crate_format();
+        let r1cs: R1CSFile = R1CSFile::from_reader(buf_reader).unwrap();
+        let r1cs = r1cs.to_crate_format().unwrap();
         assert!(r1cs.public_outputs_indices == (1..258).collect_vec());
         assert!(r1cs.public_inputs_indices == (258..260).collect_vec());
     }
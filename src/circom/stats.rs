@@ -0,0 +1,211 @@
+//! Statistics about a circuit, to see before proving what it will cost: how dense its constraints are, how much the
+//! witness is padded for the linear code, and about how large and slow its proof is with the default code
+use std::{
+    fmt::{self, Display},
+    mem,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    challenges::SecurityLevel,
+    fields::ext::ExtensionField,
+    subspacevole::{CodePreset, LinearCode},
+    vith,
+    zkp::{quicksilver, PadParams, R1CSWithMetadata},
+    PF,
+};
+
+#[derive(Debug)]
+pub struct CircuitStats {
+    pub num_constraints: usize,
+    /// Length of the witness before padding
+    pub num_wires: usize,
+    pub num_public_inputs: usize,
+    pub num_public_outputs: usize,
+    /// Wires with a nonzero coefficient in each constraint, counting a wire in more than one of A, B, and C once
+    pub avg_row_density: f64,
+    pub max_row_density: usize,
+    /// Wires in at least one constraint. Other wires are unconstrained
+    pub used_wires: usize,
+    /// Constraints each used wire is in
+    pub avg_wire_reuse: f64,
+    pub max_wire_reuse: usize,
+    /// What `calc_padding_needed` adds for the default code
+    pub padding: PadParams,
+    pub num_voles: usize,
+    pub vole_length: usize,
+    /// Size of the commitment and proof, as `DataSize` measures them
+    pub proof_size_bytes: usize,
+    /// A rough count of the field operations proving takes, dominated by creating and correcting the subspace VOLE
+    pub prove_field_ops: u64,
+}
+
+impl CircuitStats {
+    /// The time proving takes at `ops_per_second`, e.g. from `field_ops_per_second`
+    pub fn estimated_prove_time(&self, ops_per_second: f64) -> Duration {
+        Duration::from_secs_f64(self.prove_field_ops as f64 / ops_per_second)
+    }
+}
+
+/// Analyzes the circuit's constraints and the cost of proving it with `CodePreset::RAAADefault` at the default security level.
+/// Only the first branch of circuits with several is counted in the constraint statistics
+pub fn analyze<T: PF>(circuit: &R1CSWithMetadata<T>) -> CircuitStats {
+    let num_constraints = circuit.r1cs.num_constraints();
+    let mut max_row_density = 0;
+    let mut nonzero = 0;
+    let mut reuse = vec![0usize; circuit.unpadded_wtns_len];
+    for constraint in 0..num_constraints {
+        let wires = circuit.r1cs.constraint_wires(constraint);
+        max_row_density = max_row_density.max(wires.len());
+        nonzero += wires.len();
+        for wire in wires {
+            if wire >= reuse.len() {
+                reuse.resize(wire + 1, 0);
+            }
+            reuse[wire] += 1;
+        }
+    }
+    let used_wires = reuse.iter().filter(|r| **r > 0).count();
+
+    let code = CodePreset::RAAADefault.code();
+    let (n, k) = (code.n(), code.k());
+    let padding = circuit.calc_padding_needed(k);
+    let vole_length = SecurityLevel::vole_length::<T>(padding.num_padded_wtns_rows);
+    let degree = T::Ext::DEGREE;
+    let vith_rows = vith::vole_rows::<T>(vole_length);
+    let checks = SecurityLevel::vole_consistency_rows::<T>() * degree;
+
+    // Each repetition of the Quicksilver proof has the multiplication proof, the custom gate proof, and the branch proof
+    let repetitions = SecurityLevel::default()
+        .quicksilver_repetitions::<T>(quicksilver::num_batched(circuit, vith_rows * k));
+    let gate_proof = if circuit.custom_gates.is_empty() && circuit.ccs.is_empty() {
+        0
+    } else {
+        quicksilver::max_degree(circuit)
+    };
+    let branch_proof = 2 * circuit.branches.len();
+    let zkp_elements = repetitions * (2 + gate_proof + branch_proof) * degree;
+    // The commitment: the witness and the subspace VOLE's correction and consistency check
+    let commitment_elements =
+        padding.num_padded_wtns_rows * k + vole_length * (n - k) + checks * (k + n);
+    // The proof: the public openings, VitH's S matrix and its consistency check, and the seed openings
+    let num_public = circuit.public_inputs_indices.len() + circuit.public_outputs_indices.len();
+    let proof_elements = zkp_elements + (1 + degree) * num_public + degree * (vith_rows * k + n);
+    let proof_size_bytes = (commitment_elements + proof_elements) * mem::size_of::<T>()
+        + 32
+        + 2 * n * 32
+        + mem::size_of::<u64>();
+
+    // Per row of the VOLE: expanding and combining two seeds per small VOLE, correcting into the code, and the consistency check.
+    // Then the Quicksilver proof over the constraints and VitH's S matrix
+    let vole_ops = vole_length as u64 * (6 * n as u64 + 4 * n as u64 + 2 * (n + k) as u64);
+    let quicksilver_ops = 2 * nonzero as u64 + 6 * num_constraints as u64;
+    let s_ops = (degree * vith_rows) as u64 * 2 * (n + k) as u64;
+
+    CircuitStats {
+        num_constraints,
+        num_wires: circuit.unpadded_wtns_len,
+        num_public_inputs: circuit.public_inputs_indices.len(),
+        num_public_outputs: circuit.public_outputs_indices.len(),
+        avg_row_density: nonzero as f64 / num_constraints.max(1) as f64,
+        max_row_density,
+        used_wires,
+        avg_wire_reuse: nonzero as f64 / used_wires.max(1) as f64,
+        max_wire_reuse: reuse.iter().copied().max().unwrap_or(0),
+        padding,
+        num_voles: n,
+        vole_length,
+        proof_size_bytes,
+        prove_field_ops: vole_ops + quicksilver_ops + s_ops,
+    }
+}
+
+/// Times multiply-adds over `T` on this machine, to turn `CircuitStats::prove_field_ops` into a time
+pub fn field_ops_per_second<T: PF>() -> f64 {
+    const OPS: u32 = 1 << 20;
+    let x = T::from(3);
+    let mut acc = T::from(5);
+    let start = Instant::now();
+    for _ in 0..OPS {
+        acc = acc * x + x;
+    }
+    std::hint::black_box(acc);
+    OPS as f64 / start.elapsed().as_secs_f64().max(f64::MIN_POSITIVE)
+}
+
+impl Display for CircuitStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Constraints: {}", self.num_constraints)?;
+        writeln!(
+            f,
+            "Wires: {} ({} public inputs, {} public outputs, {} unconstrained)",
+            self.num_wires,
+            self.num_public_inputs,
+            self.num_public_outputs,
+            self.num_wires.saturating_sub(self.used_wires)
+        )?;
+        writeln!(
+            f,
+            "Wires per constraint: {:.2} on average, {} at most",
+            self.avg_row_density, self.max_row_density
+        )?;
+        writeln!(
+            f,
+            "Constraints per wire: {:.2} on average, {} at most",
+            self.avg_wire_reuse, self.max_wire_reuse
+        )?;
+        writeln!(
+            f,
+            "Padding: {} wires to {} ({} rows)",
+            self.padding.pad_len, self.padding.padded_wtns_len, self.padding.num_padded_wtns_rows
+        )?;
+        writeln!(
+            f,
+            "VOLE: {} small VOLEs of length {}",
+            self.num_voles, self.vole_length
+        )?;
+        writeln!(f, "Estimated proof size: {} bytes", self.proof_size_bytes)?;
+        write!(
+            f,
+            "Estimated field operations to prove: {}",
+            self.prove_field_ops
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{actors::actors::Prover, gadgets::CircuitBuilder, DataSize, Fr};
+
+    #[test]
+    fn circuit_stats() {
+        // x * x = y, y * x = z, with x used in both constraints and an unconstrained wire
+        let mut builder = CircuitBuilder::new();
+        let x = builder.public_input(Fr::from(3));
+        let y = builder.mul(x, x);
+        let z = builder.mul(y, x);
+        builder.public_output(z);
+        builder.alloc(Fr::from(7));
+        let (circuit, witness) = builder.build();
+
+        let stats = analyze(&circuit);
+        assert_eq!(stats.num_constraints, 2);
+        assert_eq!(stats.num_wires, 5);
+        assert_eq!(stats.max_row_density, 3);
+        assert_eq!(stats.avg_row_density, 2.5);
+        assert_eq!(stats.used_wires, 3);
+        assert_eq!(stats.max_wire_reuse, 2);
+        assert_eq!(
+            stats.padding.padded_wtns_len,
+            stats.padding.num_padded_wtns_rows * CodePreset::RAAADefault.code().k()
+        );
+        assert_eq!(stats.padding.orig_wtns_len, 5);
+        assert!(stats.estimated_prove_time(field_ops_per_second::<Fr>()) > Duration::ZERO);
+
+        let mut prover = Prover::from_witness_and_circuit_unpadded(witness, circuit);
+        assert_eq!(prover.vole_length, stats.vole_length);
+        let proof = prover.commit_and_prove().unwrap();
+        assert_eq!(proof.size_in_bytes(), stats.proof_size_bytes);
+    }
+}
@@ -0,0 +1,138 @@
+use anyhow::{bail, Context, Error};
+use std::{collections::BTreeMap, io::BufRead};
+
+use crate::{
+    error::UnsatisfiedConstraint,
+    zkp::{R1CSWithMetadata, R1CS},
+    PF,
+};
+
+/// The signals of a circom .sym file by witness index, with the index of the component each belongs to.
+/// Several signals can share a witness index once circom optimizes the circuit; the first one in the file is kept
+#[derive(Clone, Debug, Default)]
+pub struct SymbolTable {
+    pub signals: BTreeMap<usize, (String, usize)>,
+}
+
+impl SymbolTable {
+    pub fn from_reader<R: BufRead>(reader: R) -> Result<Self, Error> {
+        let mut signals = BTreeMap::new();
+        for symbol in symbols(reader) {
+            let (wire, name, component) = symbol?;
+            signals.entry(wire).or_insert((name, component));
+        }
+        Ok(Self { signals })
+    }
+
+    /// Name of the signal at a witness index
+    pub fn name(&self, wire: usize) -> Option<&str> {
+        self.signals.get(&wire).map(|(name, _)| name.as_str())
+    }
+
+    /// Names of the signals used in a constraint of `r1cs`. Wires without a name, such as the constant one, are left out
+    pub fn constraint_signals(&self, r1cs: &R1CS<impl PF>, constraint: usize) -> Vec<&str> {
+        r1cs.constraint_wires(constraint)
+            .into_iter()
+            .filter_map(|wire| self.name(wire))
+            .collect()
+    }
+
+    /// Describes an unsatisfied constraint of `circuit`, e.g. from `R1CSWithMetadata::check_witness`, by the signals it uses
+    pub fn describe<T: PF>(
+        &self,
+        circuit: &R1CSWithMetadata<T>,
+        unsatisfied: &UnsatisfiedConstraint<T>,
+    ) -> String {
+        format!(
+            "{} (signals {})",
+            unsatisfied,
+            self.constraint_signals(&circuit.r1cs, unsatisfied.index)
+                .join(", ")
+        )
+    }
+}
+
+/// Parses a circom .sym file into a map from each signal's name to its witness index.
+/// Each line is `label,wire,component,name`, where `wire` is -1 for signals the compiler optimized away; those are skipped
+pub fn signal_names_from_reader<R: BufRead>(reader: R) -> Result<BTreeMap<String, usize>, Error> {
+    symbols(reader)
+        .map(|symbol| symbol.map(|(wire, name, _)| (name, wire)))
+        .collect()
+}
+
+/// Witness index, name, and component of each signal of a .sym file that wasn't optimized away
+fn symbols<R: BufRead>(reader: R) -> impl Iterator<Item = Result<(usize, String, usize), Error>> {
+    reader
+        .lines()
+        .enumerate()
+        .filter_map(|(i, line)| parse_line(i + 1, line).transpose())
+}
+
+fn parse_line(
+    line_number: usize,
+    line: std::io::Result<String>,
+) -> Result<Option<(usize, String, usize)>, Error> {
+    let line = line?;
+    if line.trim().is_empty() {
+        return Ok(None);
+    }
+    let fields = line.trim().splitn(4, ',').collect::<Vec<_>>();
+    if fields.len() != 4 {
+        bail!(
+            "line {} of the .sym file has fewer than 4 fields",
+            line_number
+        );
+    }
+    let wire = fields[1].parse::<i64>().with_context(|| {
+        format!(
+            "invalid wire index on line {} of the .sym file",
+            line_number
+        )
+    })?;
+    let component = fields[2]
+        .parse::<usize>()
+        .with_context(|| format!("invalid component on line {} of the .sym file", line_number))?;
+    Ok((wire >= 0).then(|| (wire as usize, fields[3].to_string(), component)))
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Cursor;
+
+    use super::{signal_names_from_reader, SymbolTable};
+    use crate::{zkp::test::TEST_CIRCUIT, FVec, Fr};
+    use ff::PrimeField;
+
+    #[test]
+    fn read_sym_file() {
+        let sym = "1,1,0,main.out\n2,2,0,main.a\n3,-1,0,main.unused\n4,3,1,main.sub.in[0]\n";
+        let names = signal_names_from_reader(Cursor::new(sym)).unwrap();
+        assert_eq!(names.len(), 3);
+        assert_eq!(names["main.out"], 1);
+        assert_eq!(names["main.sub.in[0]"], 3);
+        assert!(!names.contains_key("main.unused"));
+        assert!(signal_names_from_reader(Cursor::new("1,x,0,main.out")).is_err());
+    }
+
+    #[test]
+    fn symbol_table() {
+        let sym = "1,0,0,main.x\n2,1,0,main.y\n3,2,0,main.xy\n4,2,1,main.sub.out\n5,3,0,main.out\n";
+        let table = SymbolTable::from_reader(Cursor::new(sym)).unwrap();
+        assert_eq!(table.signals.len(), 4);
+        assert_eq!(table.name(2), Some("main.xy"));
+        assert_eq!(table.signals[&2].1, 0);
+        assert_eq!(table.name(4), None);
+        assert!(SymbolTable::from_reader(Cursor::new("1,1,x,main.out")).is_err());
+
+        let circuit = &*TEST_CIRCUIT;
+        let witness = FVec([5, 2, 28, 281].iter().map(|x| Fr::from_u128(*x)).collect());
+        let unsatisfied = circuit.check_witness(&witness).unwrap_err();
+        assert_eq!(
+            table.constraint_signals(&circuit.r1cs, unsatisfied.index),
+            vec!["main.x", "main.xy", "main.out"]
+        );
+        assert!(table
+            .describe(circuit, &unsatisfied)
+            .ends_with("(signals main.x, main.xy, main.out)"));
+    }
+}
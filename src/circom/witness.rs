@@ -1,14 +1,37 @@
-use anyhow::{bail, Error};
-use byteorder::{LittleEndian, ReadBytesExt};
-use std::io::Read;
+use anyhow::{anyhow, bail, Error};
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use num_bigint::BigUint;
+use std::{
+    io::{Read, Write},
+    marker::PhantomData,
+};
 
-use crate::{FVec, Fr};
+use crate::{FVec, SparseVec, PF};
 
-use super::read_fr_vec;
+use super::{from_le_bytes, modulus, to_le_bytes};
 
-/// Parses bytes in a circom .wtns binary format
+/// Size in bytes of a field element in circom's file formats, a multiple of 8 bytes
+pub(super) fn element_size<T: PF>() -> u32 {
+    T::NUM_BITS.div_ceil(64) * 8
+}
+
+/// Parses bytes in a circom .wtns binary format into a witness over `T`, e.g. `Fr`.
+/// Fails if the file's field size or prime isn't `T`'s, or a value isn't less than the prime
 /// Borrowed extensively from Nova Scotia https://github.com/nalinbhardwaj/Nova-Scotia/blob/main/src/circom/reader.rs
-pub fn wtns_from_reader<R: Read>(mut reader: R) -> Result<FVec<Fr>, Error> {
+pub fn wtns_from_reader<R: Read, T: PF>(reader: R) -> Result<FVec<T>, Error> {
+    let mut stream = wtns_from_reader_streaming(reader, usize::MAX)?;
+    Ok(stream.next().transpose()?.unwrap_or(FVec(vec![])))
+}
+
+/// Like `wtns_from_reader`, but only reads the header up front, returning the witness `chunk_len` values at a time.
+/// For witnesses of hundreds of millions of values, so they needn't all be in memory at once as an `FVec`
+pub fn wtns_from_reader_streaming<R: Read, T: PF>(
+    mut reader: R,
+    chunk_len: usize,
+) -> Result<WtnsStream<R, T>, Error> {
+    if chunk_len == 0 {
+        bail!("chunks must have at least one value");
+    }
     let mut wtns_header = [0u8; 4];
     reader.read_exact(&mut wtns_header)?;
     if wtns_header != "wtns".as_bytes() {
@@ -29,19 +52,26 @@ pub fn wtns_from_reader<R: Read>(mut reader: R) -> Result<FVec<Fr>, Error> {
         bail!("invalid section type");
     }
     let sec_size = reader.read_u64::<LittleEndian>()?;
-    if sec_size != 4 + 32 + 4 {
-        bail!("invalid section len")
-    }
     let field_size = reader.read_u32::<LittleEndian>()?;
-    if field_size != 32 {
-        bail!("invalid field byte size");
+    if field_size != element_size::<T>() {
+        bail!(
+            "the witness has {} byte field elements but the field's are {} bytes",
+            field_size,
+            element_size::<T>()
+        );
+    }
+    if sec_size != 4 + field_size as u64 + 4 {
+        bail!("invalid section len")
     }
     let mut prime = vec![0u8; field_size as usize];
     reader.read_exact(&mut prime)?;
-    if prime
-        != hex::decode("010000f093f5e1439170b97948e833285d588181b64550b829a031e1724e6430").unwrap()
-    {
-        bail!("invalid curve prime {:?}", prime);
+    let prime = BigUint::from_bytes_le(&prime);
+    if prime != modulus::<T>() {
+        bail!(
+            "the witness's prime {} isn't the field's modulus {}",
+            prime,
+            modulus::<T>()
+        );
     }
     let witness_len = reader.read_u32::<LittleEndian>()?;
     // println!("witness len {}", witness_len);
@@ -50,11 +80,110 @@ pub fn wtns_from_reader<R: Read>(mut reader: R) -> Result<FVec<Fr>, Error> {
         bail!("invalid section type");
     }
     let sec_size = reader.read_u64::<LittleEndian>()?;
-    if sec_size != (witness_len * field_size) as u64 {
+    if sec_size != witness_len as u64 * field_size as u64 {
         bail!("invalid witness section size {}", sec_size);
     }
 
-    Ok(FVec::<Fr>(read_fr_vec(reader, witness_len as usize)))
+    Ok(WtnsStream {
+        reader,
+        buf: vec![0u8; field_size as usize],
+        len: witness_len as usize,
+        read: 0,
+        chunk_len,
+        _field: PhantomData,
+    })
+}
+
+/// The values of a .wtns file, read a chunk at a time. Each chunk has `chunk_len` values, except perhaps the last
+pub struct WtnsStream<R: Read, T: PF> {
+    reader: R,
+    buf: Vec<u8>,
+    len: usize,
+    read: usize,
+    chunk_len: usize,
+    _field: PhantomData<T>,
+}
+
+impl<R: Read, T: PF> WtnsStream<R, T> {
+    /// The number of values in the witness, including those already read
+    pub fn witness_len(&self) -> usize {
+        self.len
+    }
+
+    /// Reads the rest of the witness, keeping only its nonzero values. For the mostly zero witnesses of some circuits,
+    /// this is far smaller than the witness, and `Prover::from_sparse_witness_and_circuit` proves it without densifying it first
+    pub fn into_sparse(self) -> Result<SparseVec<T>, Error> {
+        let mut nonzero = vec![];
+        let mut start = self.read;
+        for chunk in self {
+            let chunk = chunk?;
+            nonzero.extend(
+                chunk
+                    .0
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, x)| !bool::from(x.is_zero()))
+                    .map(|(i, x)| (start + i, *x)),
+            );
+            start += chunk.0.len();
+        }
+        Ok(SparseVec(nonzero))
+    }
+}
+
+impl<R: Read, T: PF> Iterator for WtnsStream<R, T> {
+    type Item = Result<FVec<T>, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.read == self.len {
+            return None;
+        }
+        let len = self.chunk_len.min(self.len - self.read);
+        let mut chunk = Vec::with_capacity(len);
+        for i in self.read..self.read + len {
+            if let Err(e) = self.reader.read_exact(&mut self.buf) {
+                // Stop after an error rather than reading on from somewhere in the middle of a value
+                self.read = self.len;
+                return Some(Err(e.into()));
+            }
+            let x = from_le_bytes(&self.buf);
+            // from_le_bytes reduces its input, so a value at least the prime doesn't round trip
+            if to_le_bytes(&x, self.buf.len()) != self.buf {
+                self.read = self.len;
+                return Some(Err(anyhow!(
+                    "witness value {} isn't less than the prime",
+                    i
+                )));
+            }
+            chunk.push(x);
+        }
+        self.read += len;
+        Some(Ok(FVec(chunk)))
+    }
+}
+
+/// Writes a witness in the version 2 .wtns format snarkjs writes, so it can be used by circom tooling
+pub fn wtns_to_writer<W: Write, T: PF>(witness: &FVec<T>, mut writer: W) -> Result<(), Error> {
+    let witness_len = u32::try_from(witness.0.len())?;
+    let field_size = element_size::<T>();
+    let mut prime = modulus::<T>().to_bytes_le();
+    prime.resize(field_size as usize, 0);
+    writer.write_all("wtns".as_bytes())?;
+    writer.write_u32::<LittleEndian>(2)?;
+    writer.write_u32::<LittleEndian>(2)?;
+    // Header section: field size, prime, and witness length
+    writer.write_u32::<LittleEndian>(1)?;
+    writer.write_u64::<LittleEndian>(4 + field_size as u64 + 4)?;
+    writer.write_u32::<LittleEndian>(field_size)?;
+    writer.write_all(&prime)?;
+    writer.write_u32::<LittleEndian>(witness_len)?;
+    // Witness section
+    writer.write_u32::<LittleEndian>(2)?;
+    writer.write_u64::<LittleEndian>(witness_len as u64 * field_size as u64)?;
+    for x in witness.0.iter() {
+        writer.write_all(&to_le_bytes(x, field_size as usize))?;
+    }
+    Ok(())
 }
 
 #[cfg(test)]
@@ -62,13 +191,94 @@ mod test {
     use std::{fs::File, io::BufReader};
 
     use super::*;
+    use crate::{zkp::test::F61, Fr};
+    use ff::Field;
     #[test]
     fn read_wtns_file() {
         let file = File::open("src/circom/examples/witness.wtns").unwrap();
         let buf_reader = BufReader::new(file);
-        let witness = wtns_from_reader(buf_reader).unwrap();
+        let witness: FVec<Fr> = wtns_from_reader(buf_reader).unwrap();
         println!("Witness\n{:?}", witness.0);
         println!("Witness\n{}", witness);
     }
-}
 
+    #[test]
+    fn write_wtns_file() {
+        let witness = FVec((0..10u64).map(|x| -Fr::from(x)).collect());
+        let mut written = vec![];
+        wtns_to_writer(&witness, &mut written).unwrap();
+        assert_eq!(written.len(), 4 + 8 + 12 + 40 + 12 + 10 * 32);
+        assert_eq!(&written[..4], "wtns".as_bytes());
+        assert_eq!(
+            wtns_from_reader::<_, Fr>(written.as_slice()).unwrap().0,
+            witness.0
+        );
+    }
+
+    #[test]
+    fn wtns_fields() {
+        let witness = FVec((0..10u64).map(|x| -F61::from(x)).collect());
+        let mut written = vec![];
+        wtns_to_writer(&witness, &mut written).unwrap();
+        assert_eq!(written.len(), 4 + 8 + 12 + 16 + 12 + 10 * 8);
+        assert_eq!(
+            wtns_from_reader::<_, F61>(written.as_slice()).unwrap().0,
+            witness.0
+        );
+        let err = wtns_from_reader::<_, Fr>(written.as_slice()).unwrap_err();
+        assert!(err.to_string().contains("8 byte field elements"));
+
+        // A witness for a field with another 32 byte prime
+        let witness = FVec(vec![Fr::from(1), -Fr::from(1)]);
+        let mut written = vec![];
+        wtns_to_writer(&witness, &mut written).unwrap();
+        let mut other_prime = written.clone();
+        other_prime[28] ^= 2;
+        let err = wtns_from_reader::<_, Fr>(other_prime.as_slice()).unwrap_err();
+        assert!(err.to_string().contains("isn't the field's modulus"));
+
+        // p - 1 + 1 = p, which isn't a valid value
+        let mut too_big = written.clone();
+        too_big[written.len() - 32] += 1;
+        assert!(wtns_from_reader::<_, Fr>(too_big.as_slice()).is_err());
+    }
+
+    #[test]
+    fn stream_wtns_file() {
+        let mut witness = FVec((0..10u64).map(Fr::from).collect());
+        witness.0[7] = Fr::ZERO;
+        let mut written = vec![];
+        wtns_to_writer(&witness, &mut written).unwrap();
+
+        let stream = wtns_from_reader_streaming::<_, Fr>(written.as_slice(), 4).unwrap();
+        assert_eq!(stream.witness_len(), 10);
+        let chunks = stream.collect::<Result<Vec<_>, _>>().unwrap();
+        assert_eq!(
+            chunks.iter().map(|c| c.0.len()).collect::<Vec<_>>(),
+            vec![4, 4, 2]
+        );
+        assert_eq!(
+            chunks.into_iter().flat_map(|c| c.0).collect::<Vec<_>>(),
+            witness.0
+        );
+
+        // The rest of a partly read witness, without its zeros
+        let mut stream = wtns_from_reader_streaming::<_, Fr>(written.as_slice(), 4).unwrap();
+        stream.next().unwrap().unwrap();
+        let sparse = stream.into_sparse().unwrap();
+        assert_eq!(
+            sparse.0.iter().map(|(i, _)| *i).collect::<Vec<_>>(),
+            vec![4, 5, 6, 8, 9]
+        );
+        assert_eq!(sparse.0[3].1, Fr::from(8));
+
+        // A truncated file fails when its last chunk is read, and the stream stops
+        let truncated = &written[..written.len() - 1];
+        let mut stream = wtns_from_reader_streaming::<_, Fr>(truncated, 4).unwrap();
+        assert!(stream.next().unwrap().is_ok());
+        assert!(stream.next().unwrap().is_ok());
+        assert!(stream.next().unwrap().is_err());
+        assert!(stream.next().is_none());
+        assert!(wtns_from_reader_streaming::<_, Fr>(written.as_slice(), 0).is_err());
+    }
+}
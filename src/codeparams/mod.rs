@@ -187,11 +187,6 @@ pub fn expected_num_outputs_with_weight(
     assert!(h > 0, "h must be > 0");
 
     let inner_cols = inner_transition_prob.transpose();
-    println!(
-        "dimensions: {:?} {:?}",
-        (outer_iowe.0.len(), outer_iowe.0[0].0.len()),
-        (inner_cols.0.len(), inner_cols.0[0].0.len())
-    );
     let mut res: BigDecimal = BigDecimal::from(0);
     for i in 1..k + 1 {
         // The expected number of outputs of Hamming weight h given input of Hamming weight i
@@ -212,8 +207,7 @@ pub fn max_prob_distance_lt(
     let mut upper_bounds = Vec::<BigDecimal>::with_capacity(d - 1);
     let mut upper_bound_d = BigDecimal::from(0);
     let (k, inner_iowe, outper_tp) = values_for_rma_code(q, block_size, num_accumulators);
-    println!("inner iowe {}", inner_iowe);
-    println!("outer_tp {}", outper_tp);
+    tracing::debug!(%inner_iowe, outer_tp = %outper_tp, "RMA code's IOWE and transition probabilities");
     for i in 1..d {
         let a_h = expected_num_outputs_with_weight(k, &inner_iowe, &outper_tp, i);
         upper_bound_d += a_h;
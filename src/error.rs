@@ -0,0 +1,239 @@
+//! Errors returned by the prover, the verifier, and the proof encoding.
+//! The CLI tools wrap these in `anyhow`; the library returns them directly so callers can tell failures apart.
+use core::fmt::Debug;
+#[cfg(feature = "std")]
+use std::path::PathBuf;
+
+#[cfg(not(feature = "std"))]
+use crate::alloc_prelude::*;
+use thiserror::Error;
+
+use crate::challenges::ProtocolParams;
+
+/// Reasons the prover (or a proving session) can fail
+#[derive(Debug, Error)]
+pub enum ProofError {
+    #[error("VOLE must be completed before this step")]
+    VoleNotCompleted,
+    #[error("invalid num_voles param")]
+    InvalidNumVoles,
+    #[error("chunk_rows must be at least 1")]
+    InvalidChunkRows,
+    #[error("VOLE pool is empty")]
+    EmptyVolePool,
+    #[error("prepared VOLE has dimensions {found:?} but the prover needs {expected:?}")]
+    VoleDimensionMismatch {
+        found: (usize, usize),
+        expected: (usize, usize),
+    },
+    #[error(
+        "number of U and V rows must be a multiple of one more than the challenge field's degree"
+    )]
+    UnsplittableVoleLength,
+    #[error("parallelism must be at least 1 thread")]
+    InvalidParallelism,
+    #[error("proving key was prepared for a different code")]
+    ProvingKeyCode,
+    #[error("requested {requested} bits of security but the code provides at most {available}")]
+    InsufficientSecurity { requested: usize, available: usize },
+    #[error("requested {requested} bits of grinding but at most {max} are allowed")]
+    TooMuchGrinding { requested: usize, max: usize },
+    #[error("witness has length {found} but the circuit needs {expected}")]
+    WitnessLength { expected: usize, found: usize },
+    #[error("sparse witness has a value at index {index} but the circuit's witness has length {witness_len}")]
+    SparseWitnessIndex { index: usize, witness_len: usize },
+    #[error("{found} witnesses were given for {expected} circuits")]
+    WitnessCount { expected: usize, found: usize },
+    #[error("at least one witness is needed")]
+    NoWitnesses,
+    #[error("circuits with branches can't be proven for several witnesses at once")]
+    RepeatedBranches,
+    #[error("at least one circuit is needed")]
+    NoCircuits,
+    #[error("cannot disclose witness index {index} of a witness of length {witness_len}")]
+    DisclosureIndex { index: usize, witness_len: usize },
+    #[error("circuit has no signal named {0}")]
+    UnknownSignal(String),
+    #[error("linked proofs cannot prove circuits with lookups or memories")]
+    UnlinkableCircuit,
+    #[error("challenge has the wrong length for this prover's VOLE")]
+    ChallengeLength,
+    #[error("session state failed authentication: wrong key or tampered state")]
+    SessionAuthentication,
+    #[error("session state has a malformed seed list")]
+    MalformedSessionState,
+    #[error("resumed VOLE does not match the saved commitment")]
+    SessionVoleMismatch,
+    #[error("witness or circuit differs from the one the session was started with")]
+    SessionWitnessMismatch,
+    #[error("MiMC's x^7 is not a permutation of this field, since 7 divides p - 1")]
+    MimcField,
+}
+
+/// A constraint a witness doesn't satisfy, with the witness's dot products with its rows of A, B, and C
+#[derive(Debug, Error)]
+#[error("constraint {index} is unsatisfied: a·w = {a:?}, b·w = {b:?}, c·w = {c:?}")]
+pub struct UnsatisfiedConstraint<T: Debug> {
+    pub index: usize,
+    pub a: T,
+    pub b: T,
+    pub c: T,
+}
+
+/// Reasons a proof can be rejected
+#[derive(Debug, Error)]
+pub enum VerifyError {
+    /// The proof's dimensions don't match the verifier's parameters, so none of its checks can be run
+    #[error("malformed proof: {0}")]
+    Malformed(&'static str),
+    #[error("proof was made with {found:?} but the verifier expects {expected:?}")]
+    ParamsMismatch {
+        expected: ProtocolParams,
+        found: ProtocolParams,
+    },
+    #[error("seed commitment is not a commitment to the seeds")]
+    SeedCommitmentMismatch,
+    #[error("subspace VOLE consistency check failed")]
+    ConsistencyCheck,
+    #[error("parity check failure")]
+    ParityCheck,
+    #[error("codeword has length {found} but the code's length is {expected}")]
+    WrongLength { expected: usize, found: usize },
+    #[error("failed to verify S matrix")]
+    SMatrix,
+    #[error("Quicksilver proof was not verified with success")]
+    Zkp,
+    #[error("public values have the wrong input or output length(s)")]
+    PublicValuesLength,
+    #[error("invalid opening of a public input or output")]
+    PublicOpening,
+    #[error("public values differ from the expected ones")]
+    PublicValuesMismatch,
+    #[error("proof has {found} bits of security but at least {required} are required")]
+    InsufficientSecurity { required: usize, found: usize },
+    #[error("proof claims {found} bits of security but at most {max} are possible with its field and code")]
+    UnreachableSecurity { found: usize, max: usize },
+    #[error("grinding nonce does not meet the proof's security level")]
+    Grinding,
+    #[error("proof was made for another context")]
+    ContextMismatch,
+    #[error("challenges have the wrong length for the verifying key's VOLE")]
+    ChallengeLength,
+    #[error("designated verifier received {0}")]
+    UnexpectedMessage(&'static str),
+    #[error("transcript differs from the messages the designated verifier received")]
+    DesignatedTranscript,
+    #[error("invalid proof encoding: {0}")]
+    Encoding(#[from] FormatError),
+}
+
+/// Reasons an encoded proof can fail to decode
+#[derive(Debug, Error)]
+pub enum FormatError {
+    #[error("not an encoded proof: invalid magic number")]
+    InvalidMagic,
+    #[error("unsupported proof encoding version {0}")]
+    UnsupportedVersion(u16),
+    #[error("unknown proof encoding flags {0:#04x}")]
+    UnknownFlags(u8),
+    #[error("unknown linear code preset {0}")]
+    UnknownCodePreset(u8),
+    #[error("invalid code parameters: {0}")]
+    InvalidCodeParams(&'static str),
+    #[error("unexpected end of encoded proof")]
+    UnexpectedEnd,
+    #[error("trailing bytes after encoded proof")]
+    TrailingBytes,
+    #[error("declared length exceeds the encoded proof's size")]
+    LengthTooLarge,
+    #[error("length {0} does not fit in the encoding's u32")]
+    LengthOverflow(usize),
+    #[error("matrix rows do not all have the same length")]
+    RaggedMatrix,
+    #[error("field element is not in canonical form")]
+    NonCanonicalElement,
+    #[error("dimensions do not match the declared parameters: {0}")]
+    DimensionMismatch(&'static str),
+    #[error("proof is compressed but volonym was built without the \"compression\" feature")]
+    CompressionUnsupported,
+    #[error("proof declares more {0} than the decoding limits allow")]
+    LimitExceeded(&'static str),
+    #[error("compressed proof decompresses to more than {0} bytes")]
+    DecompressedTooLarge(u64),
+    #[cfg(feature = "std")]
+    #[error("failed to decompress proof: {0}")]
+    Decompression(#[from] std::io::Error),
+    #[error("invalid proof bundle index: {0}")]
+    InvalidBundleIndex(&'static str),
+    #[cfg(feature = "std")]
+    #[error("invalid JSON proof: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("invalid value in JSON proof: {0}")]
+    InvalidJsonValue(&'static str),
+    #[cfg(feature = "proto")]
+    #[error("invalid protobuf message: {0}")]
+    Protobuf(#[from] prost::DecodeError),
+    #[error("invalid value in protobuf message: {0}")]
+    InvalidProtobufValue(&'static str),
+}
+
+/// Reasons running the protocol over a transport can fail
+#[cfg(feature = "net")]
+#[derive(Debug, Error)]
+pub enum NetError {
+    #[error("transport error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("failed to encode or decode a message: {0}")]
+    Encoding(#[from] bincode::Error),
+    #[error("message of {0} bytes is too large")]
+    FrameTooLarge(usize),
+    #[error("received a message out of order")]
+    UnexpectedMessage,
+    #[error("the verifier rejected the proof: {0}")]
+    Rejected(String),
+    #[error(transparent)]
+    Proof(#[from] ProofError),
+    #[error(transparent)]
+    Verify(#[from] VerifyError),
+}
+
+/// Reasons a known-answer test vector fails to generate or replay
+#[cfg(feature = "std")]
+#[derive(Debug, Error)]
+pub enum TestVectorError {
+    #[error(transparent)]
+    Prove(#[from] ProofError),
+    #[error(transparent)]
+    Verify(#[from] VerifyError),
+    #[error("test vector's {0} differs from the replayed one")]
+    Mismatch(&'static str),
+}
+
+/// Reasons compiling a circuit with circom, or building and running its C++ witness generator, can fail
+#[cfg(feature = "std")]
+#[derive(Debug, Error)]
+pub enum CompileError {
+    #[error("could not run {}. Is it installed and in your PATH?", .0.display())]
+    MissingBinary(PathBuf),
+    #[error("could not read a version from `circom --version`'s output {0:?}")]
+    UnknownVersion(String),
+    #[error("circom {found} is older than {required}, the oldest supported version")]
+    UnsupportedVersion { found: String, required: String },
+    #[error("{} has no file name to name the compiled circuit after", .0.display())]
+    InvalidPath(PathBuf),
+    #[error("circom failed to compile the circuit:\n{stderr}")]
+    Failed { stderr: String },
+    #[error("building the C++ witness generator failed:\n{stderr}")]
+    BuildFailed { stderr: String },
+    #[error("the witness generator failed:\n{stderr}")]
+    WitnessGeneration { stderr: String },
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+}
+
+/// Features of a circuit file this crate can't prove
+#[derive(Debug, Error)]
+pub enum UnsupportedFeature {
+    #[error("the circuit applies circom custom gates, which aren't supported: {}", .0.join(", "))]
+    CustomGates(Vec<String>),
+}
@@ -0,0 +1,178 @@
+//! C ABI for embedding the prover and verifier. The declarations are in `include/volonym.h`.
+//!
+//! Every function returns one of the `VOLONYM_*` status codes and writes any output through out-parameters.
+//! Circuits and witnesses are circom's .r1cs and .wtns files, and proofs are in the encoding of `CommitAndProof::to_bytes`.
+//! Buffers returned by the library must be freed with `volonym_free_buffer`.
+use std::{
+    io::Cursor,
+    panic::{catch_unwind, AssertUnwindSafe},
+    ptr, slice,
+};
+
+use crate::{
+    actors::actors::{CommitAndProof, Prover, Verifier},
+    circom::{r1cs::R1CSFile, witness::wtns_from_reader},
+    Fr,
+};
+
+pub const VOLONYM_OK: i32 = 0;
+/// A required pointer was null
+pub const VOLONYM_NULL_POINTER: i32 = 1;
+pub const VOLONYM_INVALID_R1CS: i32 = 2;
+pub const VOLONYM_INVALID_WITNESS: i32 = 3;
+/// The proof bytes are not a valid encoded proof
+pub const VOLONYM_INVALID_PROOF_ENCODING: i32 = 4;
+pub const VOLONYM_PROVING_FAILED: i32 = 5;
+/// The proof is well-formed but does not verify
+pub const VOLONYM_PROOF_REJECTED: i32 = 6;
+/// The library panicked, e.g. on a malformed input the parsers don't yet reject gracefully
+pub const VOLONYM_PANIC: i32 = 7;
+
+/// Proves the witness satisfies the circuit. On success, `*proof_out` and `*proof_len_out` are set to a buffer holding the encoded proof
+///
+/// # Safety
+/// `r1cs` and `wtns` must point to `r1cs_len` and `wtns_len` readable bytes. `proof_out` and `proof_len_out` must be writable
+#[no_mangle]
+pub unsafe extern "C" fn volonym_prove(
+    r1cs: *const u8,
+    r1cs_len: usize,
+    wtns: *const u8,
+    wtns_len: usize,
+    proof_out: *mut *mut u8,
+    proof_len_out: *mut usize,
+) -> i32 {
+    if r1cs.is_null() || wtns.is_null() || proof_out.is_null() || proof_len_out.is_null() {
+        return VOLONYM_NULL_POINTER;
+    }
+    let r1cs = slice::from_raw_parts(r1cs, r1cs_len);
+    let wtns = slice::from_raw_parts(wtns, wtns_len);
+    let result = catch_unwind(AssertUnwindSafe(|| {
+        let file: R1CSFile =
+            R1CSFile::from_reader(Cursor::new(r1cs)).map_err(|_| VOLONYM_INVALID_R1CS)?;
+        let witness = wtns_from_reader(wtns)
+            .and_then(|witness| file.wire_witness(witness))
+            .map_err(|_| VOLONYM_INVALID_WITNESS)?;
+        let circuit = file.to_crate_format().map_err(|_| VOLONYM_INVALID_R1CS)?;
+        let mut prover = Prover::from_witness_and_circuit_unpadded(witness, circuit);
+        let cnp = prover
+            .commit_and_prove()
+            .map_err(|_| VOLONYM_PROVING_FAILED)?;
+        cnp.to_bytes().map_err(|_| VOLONYM_INVALID_PROOF_ENCODING)
+    }));
+    match result {
+        Ok(Ok(bytes)) => {
+            let (ptr, len) = into_raw_buffer(bytes);
+            *proof_out = ptr;
+            *proof_len_out = len;
+            VOLONYM_OK
+        }
+        Ok(Err(code)) => code,
+        Err(_) => VOLONYM_PANIC,
+    }
+}
+
+/// Verifies an encoded proof for the circuit, returning `VOLONYM_OK` if it verifies and `VOLONYM_PROOF_REJECTED` if it doesn't
+///
+/// # Safety
+/// `proof` and `r1cs` must point to `proof_len` and `r1cs_len` readable bytes
+#[no_mangle]
+pub unsafe extern "C" fn volonym_verify(
+    proof: *const u8,
+    proof_len: usize,
+    r1cs: *const u8,
+    r1cs_len: usize,
+) -> i32 {
+    if proof.is_null() || r1cs.is_null() {
+        return VOLONYM_NULL_POINTER;
+    }
+    let proof = slice::from_raw_parts(proof, proof_len);
+    let r1cs = slice::from_raw_parts(r1cs, r1cs_len);
+    let result = catch_unwind(AssertUnwindSafe(|| {
+        let cnp =
+            CommitAndProof::<Fr>::from_bytes(proof).map_err(|_| VOLONYM_INVALID_PROOF_ENCODING)?;
+        let circuit = match R1CSFile::from_reader(Cursor::new(r1cs)) {
+            Ok(file) => file.to_crate_format().map_err(|_| VOLONYM_INVALID_R1CS)?,
+            Err(_) => return Err(VOLONYM_INVALID_R1CS),
+        };
+        Verifier::from_circuit(circuit)
+            .verify(&cnp)
+            .map_err(|_| VOLONYM_PROOF_REJECTED)
+    }));
+    match result {
+        Ok(Ok(_)) => VOLONYM_OK,
+        Ok(Err(code)) => code,
+        Err(_) => VOLONYM_PANIC,
+    }
+}
+
+/// Frees a buffer returned by this library. Does nothing if `buf` is null
+///
+/// # Safety
+/// `buf` and `len` must be exactly as returned by this library, and the buffer must not be used or freed again afterwards
+#[no_mangle]
+pub unsafe extern "C" fn volonym_free_buffer(buf: *mut u8, len: usize) {
+    if !buf.is_null() {
+        drop(Box::from_raw(ptr::slice_from_raw_parts_mut(buf, len)));
+    }
+}
+
+fn into_raw_buffer(bytes: Vec<u8>) -> (*mut u8, usize) {
+    let len = bytes.len();
+    (Box::into_raw(bytes.into_boxed_slice()) as *mut u8, len)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn errors_are_reported_as_codes() {
+        let garbage = b"not a file";
+        let mut out = ptr::null_mut();
+        let mut out_len = 0;
+        unsafe {
+            assert_eq!(
+                volonym_prove(
+                    ptr::null(),
+                    0,
+                    garbage.as_ptr(),
+                    garbage.len(),
+                    &mut out,
+                    &mut out_len
+                ),
+                VOLONYM_NULL_POINTER
+            );
+            assert_eq!(
+                volonym_prove(
+                    garbage.as_ptr(),
+                    garbage.len(),
+                    garbage.as_ptr(),
+                    garbage.len(),
+                    &mut out,
+                    &mut out_len
+                ),
+                VOLONYM_INVALID_R1CS
+            );
+            assert!(out.is_null());
+            assert_eq!(
+                volonym_verify(
+                    garbage.as_ptr(),
+                    garbage.len(),
+                    garbage.as_ptr(),
+                    garbage.len()
+                ),
+                VOLONYM_INVALID_PROOF_ENCODING
+            );
+            volonym_free_buffer(ptr::null_mut(), 0);
+        }
+    }
+
+    #[test]
+    fn buffers_round_trip() {
+        let (buf, len) = into_raw_buffer(vec![1, 2, 3]);
+        unsafe {
+            assert_eq!(slice::from_raw_parts(buf, len), &[1, 2, 3]);
+            volonym_free_buffer(buf, len);
+        }
+    }
+}
@@ -0,0 +1,29 @@
+//! BLS12-381's scalar field, so statements native to arkworks' and zcash's BLS12-381 tooling can be proven without
+//! moving them to BN254. Like `crate::Fr`, its elements are encoded as 32 big endian bytes
+#[cfg(not(feature = "std"))]
+use crate::alloc_prelude::*;
+use crate::{fields::ext::ChallengeField, FromU8s, ToU8s};
+use ff::PrimeField;
+
+#[derive(PrimeField)]
+#[PrimeFieldModulus = "52435875175126190479447740508185965837690552500527637822603658699938581184513"]
+#[PrimeFieldGenerator = "7"]
+#[PrimeFieldReprEndianness = "big"]
+pub struct Fr([u64; 4]);
+
+impl FromU8s for Fr {
+    fn from_u8s(u: &Vec<u8>) -> Self {
+        if u.len() != 32 {
+            panic!("field element must be 32 bytes")
+        }
+        Fr::from_repr(FrRepr(u[0..32].try_into().unwrap())).unwrap()
+    }
+}
+impl ToU8s for Fr {
+    fn to_u8s(&self) -> Vec<u8> {
+        self.to_repr().0.to_vec()
+    }
+}
+impl ChallengeField for Fr {
+    type Ext = Fr;
+}
@@ -0,0 +1,286 @@
+//! Extensions of the witness's field that challenges can be drawn from when the field is too small for one challenge to be sound.
+//!
+//! A check batched with powers of a challenge χ from an extension K of F is as sound as one over K: a nonzero batched polynomial
+//! with coefficients in F is still nonzero over K, so it vanishes at χ with probability at most its degree over |K|.
+//! Each check is F-linear in the values it batches, so the prover's messages are computed from F values and F's own arithmetic,
+//! and only the batched sums are in K. Every field is an extension of itself, which is how the checks run over large fields
+#[cfg(not(feature = "std"))]
+use crate::alloc_prelude::*;
+use crate::{FVec, PF};
+use core::{
+    fmt::Debug,
+    ops::{Add, AddAssign, Mul, MulAssign, Neg, Sub, SubAssign},
+};
+use ff::Field;
+use rand::RngCore;
+
+use super::m31::M31;
+
+/// A field containing `F`, whose elements are `DEGREE` coefficients over `F`
+pub trait ExtensionField<F: Field>:
+    Copy
+    + Debug
+    + Eq
+    + Send
+    + Sync
+    + From<F>
+    + Add<Output = Self>
+    + Sub<Output = Self>
+    + Mul<Output = Self>
+    + Mul<F, Output = Self>
+    + Neg<Output = Self>
+    + AddAssign
+    + SubAssign
+    + MulAssign
+{
+    /// Degree of the extension over `F`
+    const DEGREE: usize;
+    /// A uniformly random element, e.g. a challenge from a transcript's RNG
+    fn sample(rng: impl RngCore) -> Self;
+    /// The element's `DEGREE` coefficients over `F`
+    fn coefficients(&self) -> Vec<F>;
+    /// The element with `coefficients`, which must be `DEGREE` long. It is F-linear, the inverse of `coefficients`
+    fn from_coefficients(coefficients: &[F]) -> Self;
+}
+
+impl<F: Field> ExtensionField<F> for F {
+    const DEGREE: usize = 1;
+    fn sample(rng: impl RngCore) -> Self {
+        F::random(rng)
+    }
+    fn coefficients(&self) -> Vec<F> {
+        vec![*self]
+    }
+    fn from_coefficients(coefficients: &[F]) -> Self {
+        coefficients[0]
+    }
+}
+
+/// A field's choice of the extension its challenges and VitH's ∆' are drawn from: the field itself when it is large enough
+/// for one challenge to be sound, and `QM31` for `M31`
+pub trait ChallengeField: Field {
+    type Ext: ExtensionField<Self>;
+}
+
+/// Each coordinate of `vec` over `F`, i.e. `DEGREE` vectors with the first, second, ... coefficient of every element
+pub fn coordinates<F: PF, E: ExtensionField<F>>(vec: &[E]) -> Vec<FVec<F>> {
+    let mut coords = vec![FVec(Vec::with_capacity(vec.len())); E::DEGREE];
+    for x in vec {
+        for (c, coeff) in coords.iter_mut().zip(x.coefficients()) {
+            c.0.push(coeff);
+        }
+    }
+    coords
+}
+
+/// The vector whose coordinates are `coords`, the inverse of `coordinates`
+pub fn from_coordinates<F: PF, E: ExtensionField<F>>(coords: &[FVec<F>]) -> Vec<E> {
+    let mut coefficients = Vec::with_capacity(E::DEGREE);
+    (0..coords.first().map_or(0, |c| c.0.len()))
+        .map(|i| {
+            coefficients.clear();
+            coefficients.extend(coords.iter().map(|c| c.0[i]));
+            E::from_coefficients(&coefficients)
+        })
+        .collect()
+}
+
+/// Implements the assigning operators and multiplication by an `M31` from `Add`, `Sub`, and `Mul`
+macro_rules! impl_ops {
+    ($t:ty, $base_mul:expr) => {
+        impl AddAssign for $t {
+            fn add_assign(&mut self, rhs: Self) {
+                *self = *self + rhs;
+            }
+        }
+        impl SubAssign for $t {
+            fn sub_assign(&mut self, rhs: Self) {
+                *self = *self - rhs;
+            }
+        }
+        impl MulAssign for $t {
+            fn mul_assign(&mut self, rhs: Self) {
+                *self = *self * rhs;
+            }
+        }
+        impl Mul<M31> for $t {
+            type Output = Self;
+            fn mul(self, rhs: M31) -> Self {
+                $base_mul(self, rhs)
+            }
+        }
+    };
+}
+
+/// M31's degree 2 extension, a + bi with i^2 = -1, which is irreducible since 2^31 - 1 is 3 mod 4
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CM31(pub M31, pub M31);
+
+impl CM31 {
+    pub const ZERO: Self = Self(M31::ZERO, M31::ZERO);
+    pub const ONE: Self = Self(M31::ONE, M31::ZERO);
+
+    /// The inverse, (a - bi) / (a^2 + b^2), or None for zero
+    pub fn inverse(&self) -> Option<Self> {
+        let norm: Option<M31> = (self.0.square() + self.1.square()).invert().into();
+        norm.map(|n| Self(self.0 * n, -self.1 * n))
+    }
+
+    /// Multiplies by 2 + i, the non-square QM31 adjoins the square root of
+    fn mul_by_nonresidue(self) -> Self {
+        Self(self.0.double() - self.1, self.0 + self.1.double())
+    }
+}
+
+impl From<M31> for CM31 {
+    fn from(x: M31) -> Self {
+        Self(x, M31::ZERO)
+    }
+}
+
+impl Add for CM31 {
+    type Output = Self;
+    fn add(self, rhs: Self) -> Self {
+        Self(self.0 + rhs.0, self.1 + rhs.1)
+    }
+}
+impl Sub for CM31 {
+    type Output = Self;
+    fn sub(self, rhs: Self) -> Self {
+        Self(self.0 - rhs.0, self.1 - rhs.1)
+    }
+}
+impl Mul for CM31 {
+    type Output = Self;
+    fn mul(self, rhs: Self) -> Self {
+        Self(
+            self.0 * rhs.0 - self.1 * rhs.1,
+            self.0 * rhs.1 + self.1 * rhs.0,
+        )
+    }
+}
+impl Neg for CM31 {
+    type Output = Self;
+    fn neg(self) -> Self {
+        Self(-self.0, -self.1)
+    }
+}
+impl_ops!(CM31, |x: CM31, y: M31| CM31(x.0 * y, x.1 * y));
+
+/// M31's degree 4 extension, a + bu with a and b in `CM31` and u^2 = 2 + i, which has about 124 bits.
+/// Its coefficients over M31 are those of a then b
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct QM31(pub CM31, pub CM31);
+
+impl QM31 {
+    pub const ZERO: Self = Self(CM31::ZERO, CM31::ZERO);
+    pub const ONE: Self = Self(CM31::ONE, CM31::ZERO);
+
+    /// The inverse, (a - bu) / (a^2 - (2 + i)b^2), or None for zero
+    pub fn inverse(&self) -> Option<Self> {
+        let norm = self.0 * self.0 - (self.1 * self.1).mul_by_nonresidue();
+        norm.inverse().map(|n| Self(self.0 * n, -self.1 * n))
+    }
+}
+
+impl From<M31> for QM31 {
+    fn from(x: M31) -> Self {
+        Self(CM31::from(x), CM31::ZERO)
+    }
+}
+
+impl Add for QM31 {
+    type Output = Self;
+    fn add(self, rhs: Self) -> Self {
+        Self(self.0 + rhs.0, self.1 + rhs.1)
+    }
+}
+impl Sub for QM31 {
+    type Output = Self;
+    fn sub(self, rhs: Self) -> Self {
+        Self(self.0 - rhs.0, self.1 - rhs.1)
+    }
+}
+impl Mul for QM31 {
+    type Output = Self;
+    fn mul(self, rhs: Self) -> Self {
+        Self(
+            self.0 * rhs.0 + (self.1 * rhs.1).mul_by_nonresidue(),
+            self.0 * rhs.1 + self.1 * rhs.0,
+        )
+    }
+}
+impl Neg for QM31 {
+    type Output = Self;
+    fn neg(self) -> Self {
+        Self(-self.0, -self.1)
+    }
+}
+impl_ops!(QM31, |x: QM31, y: M31| QM31(x.0 * y, x.1 * y));
+
+impl ExtensionField<M31> for QM31 {
+    const DEGREE: usize = 4;
+    fn sample(mut rng: impl RngCore) -> Self {
+        let mut coefficients = [M31::ZERO; 4];
+        coefficients
+            .iter_mut()
+            .for_each(|c| *c = M31::random(&mut rng));
+        Self::from_coefficients(&coefficients)
+    }
+    fn coefficients(&self) -> Vec<M31> {
+        vec![self.0 .0, self.0 .1, self.1 .0, self.1 .1]
+    }
+    fn from_coefficients(coefficients: &[M31]) -> Self {
+        Self(
+            CM31(coefficients[0], coefficients[1]),
+            CM31(coefficients[2], coefficients[3]),
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha12Rng;
+
+    use super::*;
+    use crate::Fr;
+
+    #[test]
+    fn qm31_is_a_field() {
+        // 2 + i is not a square in CM31, i.e. u^2 = 2 + i is irreducible, iff it to the (p^2 - 1) / 2 is -1
+        let p = (1u64 << 31) - 1;
+        let nonresidue = CM31::ONE.mul_by_nonresidue();
+        let mut exp = (p * p - 1) / 2;
+        let (mut acc, mut base) = (CM31::ONE, nonresidue);
+        while exp > 0 {
+            if exp & 1 == 1 {
+                acc *= base;
+            }
+            base *= base;
+            exp >>= 1;
+        }
+        assert_eq!(acc, -CM31::ONE);
+
+        let mut rng = ChaCha12Rng::seed_from_u64(31);
+        for _ in 0..32 {
+            let [a, b, c] = [(); 3].map(|_| QM31::sample(&mut rng));
+            assert_eq!(a * b, b * a);
+            assert_eq!((a * b) * c, a * (b * c));
+            assert_eq!(a * (b + c), a * b + a * c);
+            assert_eq!(a * a.inverse().unwrap(), QM31::ONE);
+            assert_eq!(a - a, QM31::ZERO);
+            assert_eq!(QM31::from_coefficients(&a.coefficients()), a);
+            let x = M31::random(&mut rng);
+            assert_eq!(a * x, a * QM31::from(x));
+        }
+        assert_eq!(QM31::ZERO.inverse(), None);
+
+        // Fr is its own extension
+        assert_eq!(<Fr as ExtensionField<Fr>>::DEGREE, 1);
+        assert_eq!(
+            Fr::from_coefficients(&Fr::from(5).coefficients()),
+            Fr::from(5)
+        );
+    }
+}
@@ -0,0 +1,29 @@
+//! The Mersenne prime field of 2^31 - 1, whose elements fit in a machine word, for circuits whose arithmetic is over 31 bits.
+//! Its challenges and VitH's ∆' are from its degree 4 extension `super::ext::QM31`. Elements are encoded as 8 little endian bytes
+#[cfg(not(feature = "std"))]
+use crate::alloc_prelude::*;
+use crate::{fields::ext::ChallengeField, FromU8s, ToU8s};
+use ff::PrimeField;
+
+#[derive(PrimeField)]
+#[PrimeFieldModulus = "2147483647"]
+#[PrimeFieldGenerator = "7"]
+#[PrimeFieldReprEndianness = "little"]
+pub struct M31([u64; 1]);
+
+impl FromU8s for M31 {
+    fn from_u8s(u: &Vec<u8>) -> Self {
+        if u.len() != 8 {
+            panic!("field element must be 8 bytes")
+        }
+        M31::from_repr(M31Repr(u[0..8].try_into().unwrap())).unwrap()
+    }
+}
+impl ToU8s for M31 {
+    fn to_u8s(&self) -> Vec<u8> {
+        self.to_repr().0.to_vec()
+    }
+}
+impl ChallengeField for M31 {
+    type Ext = super::ext::QM31;
+}
@@ -0,0 +1,9 @@
+//! Fields besides BN254's scalar field `crate::Fr`. Everything is generic over the field through `PF`,
+//! but each field is a module of its own since `PrimeField`'s derive defines constants that would clash with `crate::Fr`'s.
+//!
+//! `m31::M31` is small enough for witness arithmetic to be cheap, but too small for one challenge from it to make a check sound.
+//! Each field names the extension its challenges are drawn from with `ext::ChallengeField`, `ext::QM31` for M31:
+//! Quicksilver's challenges, the subspace VOLE's consistency check, and VitH's ∆' are from it, while the witness stays in the field
+pub mod bls12_381;
+pub mod ext;
+pub mod m31;
@@ -1,5 +1,19 @@
 //! Reads and write proof formats.
-use crate::{Fr, FrRepr};
+use crate::{
+    actors::actors::{
+        CommitAndProof, Proof, ProverCommitment, PublicOpenings, SubspaceVOLEOpening,
+    },
+    challenges::{ProtocolParams, SecurityLevel},
+    circom::to_decimal,
+    fields::ext::ExtensionField,
+    subspacevole::CodePreset,
+    vith,
+    zkp::quicksilver::ZKP,
+    error::FormatError,
+    DataSize, FMatrix, FVec, Fr, FrRepr, PF,
+};
+use byteorder::{ByteOrder, LittleEndian};
+use std::{borrow::Cow, ops::Range};
 use ff::PrimeField;
 use serde::{ser::{Serialize, Serializer}, de::{Deserialize, Visitor}};
 
@@ -54,12 +68,1058 @@ impl<'de> Visitor<'de> for FrVisitor {
     }
 }
 
+/// First bytes of every encoded proof
+pub const PROOF_MAGIC: [u8; 4] = *b"VOLE";
+/// Version of the encoding written by `CommitAndProof::to_bytes`
+pub const PROOF_VERSION: u16 = 7;
+/// Header flag set when everything after the header is zstd-compressed
+pub const FLAG_COMPRESSED: u8 = 0b1;
+/// Decompressing stops with an error past this many bytes, so a small malicious proof can't exhaust memory
+pub const MAX_DECOMPRESSED_LEN: u64 = 1 << 30;
+
+/// Bounds on what decoding a proof may allocate. Declared lengths are also checked against the bytes left before anything is allocated,
+/// but a matrix of empty rows takes no bytes, and a compressed proof can declare far more than it takes.
+/// A bundle's proofs share one budget, so `max_elements` and `max_decompressed_len` bound the whole bundle rather than each proof
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DecodeLimits {
+    /// Most rows any one matrix may have
+    pub max_rows: usize,
+    /// Most field elements in the whole proof or bundle
+    pub max_elements: usize,
+    /// Most bytes the compressed payloads of the proof or bundle may decompress to
+    pub max_decompressed_len: u64,
+    /// Most proofs a bundle may have
+    pub max_proofs: usize,
+}
+
+impl Default for DecodeLimits {
+    /// Enough for circuits with hundreds of millions of wires
+    fn default() -> Self {
+        Self {
+            max_rows: 1 << 20,
+            max_elements: 1 << 25,
+            max_decompressed_len: MAX_DECOMPRESSED_LEN,
+            max_proofs: 1 << 16,
+        }
+    }
+}
+
+/// The protocol parameters an encoded proof declares, which fix the dimensions of everything in its payload
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ProofParams {
+    pub code: CodePreset,
+    pub num_voles: usize,
+    pub vole_length: usize,
+    /// Security level the proof claims, so verifiers can reject proofs below their minimum without decoding them
+    pub security: SecurityLevel,
+    /// Bits of grinding the proof was made with, i.e. the leading zero bits its grinding nonce's hash has
+    pub grinding_bits: u8,
+}
+
+impl ProofParams {
+    /// Parses and validates the parameters in an encoded proof's header without decoding or copying its payload
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, FormatError> {
+        let mut r = ByteReader::with_limits(bytes, &DecodeLimits::default());
+        Ok(read_header(&mut r)?.1)
+    }
+
+    /// The parameters the proof's commitment was made with
+    pub fn protocol(&self) -> ProtocolParams {
+        ProtocolParams {
+            code: self.code,
+            num_voles: self.num_voles,
+            vole_length: self.vole_length,
+        }
+    }
+}
+
+/// The canonical encoding of a proof, which independent implementations must produce and accept byte for byte:
+///
+/// | magic | version | flags | code | num_voles | vole_length | security | grinding_bits | payload |
+/// |-------|---------|-------|------|-----------|-------------|----------|---------------|---------|
+/// | 4     | u16     | u8    | u8   | u32       | u32         | u16      | u8            | rest    |
+///
+/// with integers little-endian. The payload is the commitment and proof as `CommitAndProof::to_envelope` lays them out,
+/// zstd-compressed if `FLAG_COMPRESSED` is set. Decoding rejects anything else: another magic number or version, unknown flags or code,
+/// an odd VOLE length, and, once the payload is decoded, any dimension that disagrees with the parameters, non-canonical field elements,
+/// or trailing bytes. Every proof therefore has exactly one uncompressed encoding
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ProofEnvelope {
+    pub magic: [u8; 4],
+    pub version: u16,
+    pub flags: u8,
+    pub params: ProofParams,
+    pub payload: Vec<u8>,
+}
+
+impl ProofEnvelope {
+    /// Length of the encoding before the payload in bytes
+    pub const HEADER_LEN: usize = 4 + 2 + 1 + 1 + 4 + 4 + 2 + 1;
+
+    /// An envelope with this version's magic number and version
+    pub fn new(flags: u8, params: ProofParams, payload: Vec<u8>) -> Self {
+        Self {
+            magic: PROOF_MAGIC,
+            version: PROOF_VERSION,
+            flags,
+            params,
+            payload,
+        }
+    }
+
+    pub fn is_compressed(&self) -> bool {
+        self.flags & FLAG_COMPRESSED != 0
+    }
+
+    /// Fails with `LengthOverflow` if the parameters' dimensions don't fit in a u32
+    pub fn to_bytes(&self) -> Result<Vec<u8>, FormatError> {
+        let mut out = Vec::with_capacity(Self::HEADER_LEN + self.payload.len());
+        out.extend_from_slice(&self.magic);
+        write_u16(&mut out, self.version);
+        out.push(self.flags);
+        out.push(self.params.code as u8);
+        write_u32(&mut out, self.params.num_voles)?;
+        write_u32(&mut out, self.params.vole_length)?;
+        write_u16(&mut out, self.params.security.0.min(u16::MAX as usize) as u16);
+        out.push(self.params.grinding_bits);
+        out.extend_from_slice(&self.payload);
+        Ok(out)
+    }
+
+    /// Parses and validates the header. The payload is validated when `CommitAndProof::from_envelope` decodes it
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, FormatError> {
+        let mut r = ByteReader::with_limits(bytes, &DecodeLimits::default());
+        let (flags, params) = read_header(&mut r)?;
+        Ok(Self::new(flags, params, r.bytes.to_vec()))
+    }
+
+    /// The payload, decompressed if it is compressed, taking its decompressed length out of `limits`
+    fn uncompressed_payload(
+        &self,
+        limits: &mut DecodeLimits,
+    ) -> Result<Cow<'_, [u8]>, FormatError> {
+        if !self.is_compressed() {
+            return Ok(Cow::Borrowed(&self.payload));
+        }
+        let payload = decompress(&self.payload, limits.max_decompressed_len)?;
+        limits.max_decompressed_len -= payload.len() as u64;
+        Ok(Cow::Owned(payload))
+    }
+}
+
+/// Reads and validates the envelope's header, returning its flags and parameters
+fn read_header(r: &mut ByteReader) -> Result<(u8, ProofParams), FormatError> {
+    if r.take(4)? != PROOF_MAGIC {
+        return Err(FormatError::InvalidMagic);
+    }
+    let version = r.u16()?;
+    if version != PROOF_VERSION {
+        return Err(FormatError::UnsupportedVersion(version));
+    }
+    let flags = r.u8()?;
+    if flags & !FLAG_COMPRESSED != 0 {
+        return Err(FormatError::UnknownFlags(flags));
+    }
+    let code_id = r.u8()?;
+    let code = CodePreset::from_u8(code_id).ok_or(FormatError::UnknownCodePreset(code_id))?;
+    let num_voles = r.u32()? as usize;
+    let vole_length = r.u32()? as usize;
+    let security = SecurityLevel(r.u16()? as usize);
+    let grinding_bits = r.u8()?;
+    Ok((
+        flags,
+        ProofParams {
+            code,
+            num_voles,
+            vole_length,
+            security,
+            grinding_bits,
+        },
+    ))
+}
+
+impl<T: PF> CommitAndProof<T> {
+    /// Encodes the commitment and proof as a `ProofEnvelope`. The payload has field elements as their fixed-length canonical representation
+    /// and every integer as a little-endian u32.
+    /// The number of seed openings is given by the parameters, so only variable-length vectors and matrices carry their dimensions.
+    /// Fails with `RaggedMatrix` or `LengthOverflow` rather than writing something that can't be decoded
+    pub fn to_envelope(&self) -> Result<ProofEnvelope, FormatError> {
+        let mut payload = Vec::with_capacity(self.size_in_bytes());
+        self.write_body(&mut payload)?;
+        Ok(ProofEnvelope::new(0, self.params(), payload))
+    }
+
+    /// Like `to_envelope` but with the payload zstd-compressed and `FLAG_COMPRESSED` set
+    #[cfg(feature = "compression")]
+    pub fn to_compressed_envelope(&self) -> Result<ProofEnvelope, FormatError> {
+        let mut body = Vec::with_capacity(self.size_in_bytes());
+        self.write_body(&mut body)?;
+        let mut payload = Vec::with_capacity(body.len());
+        zstd::stream::copy_encode(&body[..], &mut payload, zstd::DEFAULT_COMPRESSION_LEVEL)
+            .expect("compressing into memory can't fail");
+        Ok(ProofEnvelope::new(FLAG_COMPRESSED, self.params(), payload))
+    }
+
+    /// Decodes an envelope's payload, checking that every dimension agrees with the declared parameters,
+    /// that every field element is canonical, and that there are no trailing bytes
+    pub fn from_envelope(envelope: &ProofEnvelope) -> Result<Self, FormatError> {
+        Self::from_envelope_with_limits(envelope, &DecodeLimits::default())
+    }
+
+    /// Like `from_envelope`, failing with `LimitExceeded` rather than allocating beyond `limits`
+    pub fn from_envelope_with_limits(
+        envelope: &ProofEnvelope,
+        limits: &DecodeLimits,
+    ) -> Result<Self, FormatError> {
+        Self::decode_within(envelope, &mut { *limits })
+    }
+
+    /// Like `from_envelope_with_limits`, taking what is decoded out of `limits` so several proofs can share them
+    fn decode_within(
+        envelope: &ProofEnvelope,
+        limits: &mut DecodeLimits,
+    ) -> Result<Self, FormatError> {
+        let payload = envelope.uncompressed_payload(limits)?;
+        Self::decode_body(&envelope.params, &payload, limits)
+    }
+
+    /// The bytes of `to_envelope`
+    pub fn to_bytes(&self) -> Result<Vec<u8>, FormatError> {
+        self.to_envelope()?.to_bytes()
+    }
+
+    /// The bytes of `to_compressed_envelope`
+    #[cfg(feature = "compression")]
+    pub fn to_compressed_bytes(&self) -> Result<Vec<u8>, FormatError> {
+        self.to_compressed_envelope()?.to_bytes()
+    }
+
+    /// Decodes the output of `to_bytes` or `to_compressed_bytes`
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, FormatError> {
+        Self::from_envelope(&ProofEnvelope::from_bytes(bytes)?)
+    }
+
+    /// Like `from_bytes`, failing with `LimitExceeded` rather than allocating beyond `limits`
+    pub fn from_bytes_with_limits(bytes: &[u8], limits: &DecodeLimits) -> Result<Self, FormatError> {
+        Self::from_envelope_with_limits(&ProofEnvelope::from_bytes(bytes)?, limits)
+    }
+
+    fn params(&self) -> ProofParams {
+        let params = self.commitment.params;
+        ProofParams {
+            code: params.code,
+            num_voles: params.num_voles,
+            vole_length: params.vole_length,
+            security: self.proof.security,
+            grinding_bits: self.proof.grinding_bits.min(u8::MAX as usize) as u8,
+        }
+    }
+
+    fn write_body(&self, out: &mut Vec<u8>) -> Result<(), FormatError> {
+        let comm = &self.commitment;
+        out.extend_from_slice(&comm.seed_comm);
+        write_matrix(out, &comm.witness_comm)?;
+        write_matrix(out, &comm.subspace_vole_correction)?;
+        write_vec(out, &comm.consistency_check.0)?;
+        write_vec(out, &comm.consistency_check.1)?;
+
+        let proof = &self.proof;
+        write_zkp::<T>(out, &proof.zkp)?;
+        write_u32(out, proof.zkp.repetitions.len())?;
+        for z in &proof.zkp.repetitions {
+            write_zkp::<T>(out, z)?;
+        }
+        proof
+            .seed_openings
+            .seed_opens
+            .iter()
+            .for_each(|s| out.extend_from_slice(s));
+        proof
+            .seed_openings
+            .seed_proofs
+            .iter()
+            .for_each(|s| out.extend_from_slice(s));
+        write_pairs(out, &proof.public_openings.public_inputs)?;
+        write_pairs(out, &proof.public_openings.public_outputs)?;
+        write_matrix(out, &proof.s_matrix)?;
+        write_vec(out, &proof.s_consistency_check)?;
+        out.extend_from_slice(&proof.grinding_nonce.to_le_bytes());
+        Ok(())
+    }
+
+    fn decode_body(
+        params: &ProofParams,
+        body: &[u8],
+        limits: &mut DecodeLimits,
+    ) -> Result<Self, FormatError> {
+        let mut r = ByteReader::with_limits(body, limits);
+
+        let seed_comm = r.array32()?;
+        let witness_comm = r.matrix::<T>()?;
+        let subspace_vole_correction = r.matrix::<T>()?;
+        let consistency_check = (r.vec::<T>()?, r.vec::<T>()?);
+
+        let mut zkp = r.zkp::<T>()?;
+        let repetitions = r.u32()? as usize;
+        r.check_remaining(repetitions, 2 * T::Ext::DEGREE * element_len::<T>() + 8)?;
+        zkp.repetitions = (0..repetitions)
+            .map(|_| r.zkp::<T>())
+            .collect::<Result<_, _>>()?;
+        r.check_remaining(params.num_voles, 64)?;
+        let seed_opens = (0..params.num_voles)
+            .map(|_| r.array32())
+            .collect::<Result<Vec<_>, _>>()?;
+        let seed_proofs = (0..params.num_voles)
+            .map(|_| r.array32())
+            .collect::<Result<Vec<_>, _>>()?;
+        let public_inputs = r.pairs::<T>()?;
+        let public_outputs = r.pairs::<T>()?;
+        let s_matrix = r.matrix::<T>()?;
+        let s_consistency_check = r.vec::<T>()?;
+        let grinding_nonce = r.u64()?;
+        if !r.is_empty() {
+            return Err(FormatError::TrailingBytes);
+        }
+        limits.max_elements = r.elements_left;
+
+        let cnp = CommitAndProof {
+            commitment: ProverCommitment {
+                params: params.protocol(),
+                seed_comm,
+                witness_comm,
+                subspace_vole_correction,
+                consistency_check,
+            },
+            proof: Proof {
+                zkp,
+                seed_openings: SubspaceVOLEOpening {
+                    seed_opens,
+                    seed_proofs,
+                },
+                public_openings: PublicOpenings {
+                    public_inputs,
+                    public_outputs,
+                },
+                s_matrix,
+                s_consistency_check,
+                security: params.security,
+                grinding_bits: params.grinding_bits as usize,
+                grinding_nonce,
+            },
+        };
+        cnp.check_dimensions()?;
+        Ok(cnp)
+    }
+
+    /// Checks everything has the dimensions the commitment's parameters give it, as decoding does
+    fn check_dimensions(&self) -> Result<(), FormatError> {
+        let (comm, proof, params) = (&self.commitment, &self.proof, &self.commitment.params);
+        let degree = T::Ext::DEGREE;
+        let checks = SecurityLevel::vole_consistency_rows::<T>() * degree;
+        if params.vole_length % (degree + 1) != 0 {
+            return Err(FormatError::DimensionMismatch(
+                "VOLE length must be a multiple of one more than the challenge field's degree",
+            ));
+        }
+        let k = proof.s_matrix.0.first().map(|row| row.0.len()).unwrap_or(0);
+        if proof.seed_openings.seed_opens.len() != params.num_voles
+            || proof.seed_openings.seed_proofs.len() != params.num_voles
+        {
+            return Err(FormatError::DimensionMismatch(
+                "there must be num_voles seed openings",
+            ));
+        }
+        if proof.s_matrix.0.len() != degree * vith::vole_rows::<T>(params.vole_length) {
+            return Err(FormatError::DimensionMismatch(
+                "S matrix must have degree × vole_length / (degree + 1) rows",
+            ));
+        }
+        if comm.subspace_vole_correction.0.len() != params.vole_length {
+            return Err(FormatError::DimensionMismatch(
+                "subspace VOLE correction must have vole_length rows",
+            ));
+        }
+        if comm
+            .subspace_vole_correction
+            .0
+            .iter()
+            .any(|row| row.0.len() + k != params.num_voles)
+            || comm.witness_comm.0.iter().any(|row| row.0.len() != k)
+            || comm.consistency_check.0 .0.len() != checks * k
+        {
+            return Err(FormatError::DimensionMismatch(
+                "commitment dimensions do not match the code's dimensions",
+            ));
+        }
+        if comm.consistency_check.1 .0.len() != checks * params.num_voles
+            || proof.s_consistency_check.0.len() != degree * params.num_voles
+        {
+            return Err(FormatError::DimensionMismatch(
+                "consistency checks must have a multiple of num_voles elements",
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// First bytes of every proof bundle
+pub const BUNDLE_MAGIC: [u8; 4] = *b"VOLB";
+/// Version of the encoding written by `ProofBundle::to_bytes`
+pub const BUNDLE_VERSION: u16 = 1;
+
+/// Several proofs for one circuit in a single file, e.g. for `Verifier::verify_bundle`. Encoded as
+///
+/// | magic | version | circuit digest | code | num_voles | vole_length | count | index | proofs |
+/// |-------|---------|----------------|------|-----------|-------------|-------|-------|--------|
+/// | 4     | u16     | 32             | u8   | u32       | u32         | u32   | count × (u64, u64) | rest |
+///
+/// with integers little-endian. The index has each proof's offset from the start of the proofs and its length, and the proofs are
+/// `ProofEnvelope`s, one after another in the order of the index. Every envelope must have the bundle's code and dimensions,
+/// so they are checked once for the whole bundle, and `ProofBundle::proof_at` decodes one proof without decoding the others
+#[derive(Clone, Debug)]
+pub struct ProofBundle<T: PF> {
+    /// `circuit_id` of the circuit every proof is for
+    pub circuit_digest: [u8; 32],
+    pub code: CodePreset,
+    pub num_voles: usize,
+    pub vole_length: usize,
+    pub proofs: Vec<CommitAndProof<T>>,
+}
+
+/// The byte range of each proof in a bundle, within the bytes after the index
+type BundleIndex = Vec<Range<usize>>;
+
+impl<T: PF> ProofBundle<T> {
+    /// Length of the encoding before the index in bytes
+    pub const HEADER_LEN: usize = 4 + 2 + 32 + 1 + 4 + 4 + 4;
+
+    /// Bundles `proofs` of the circuit with `circuit_digest`. Fails if there are none or they don't all have the same dimensions
+    pub fn new(
+        circuit_digest: [u8; 32],
+        proofs: Vec<CommitAndProof<T>>,
+    ) -> Result<Self, FormatError> {
+        let params = proofs
+            .first()
+            .ok_or(FormatError::InvalidBundleIndex(
+                "a bundle must have at least one proof",
+            ))?
+            .params();
+        let bundle = Self {
+            circuit_digest,
+            code: params.code,
+            num_voles: params.num_voles,
+            vole_length: params.vole_length,
+            proofs,
+        };
+        for proof in &bundle.proofs {
+            bundle.check_params(&proof.params())?;
+            proof.check_dimensions()?;
+        }
+        Ok(bundle)
+    }
+
+    pub fn to_bytes(&self) -> Result<Vec<u8>, FormatError> {
+        let envelopes = self
+            .proofs
+            .iter()
+            .map(|cnp| cnp.to_bytes())
+            .collect::<Result<Vec<_>, _>>()?;
+        let mut out = Vec::with_capacity(
+            Self::HEADER_LEN + 16 * envelopes.len() + envelopes.iter().map(Vec::len).sum::<usize>(),
+        );
+        out.extend_from_slice(&BUNDLE_MAGIC);
+        write_u16(&mut out, BUNDLE_VERSION);
+        out.extend_from_slice(&self.circuit_digest);
+        out.push(self.code as u8);
+        write_u32(&mut out, self.num_voles)?;
+        write_u32(&mut out, self.vole_length)?;
+        write_u32(&mut out, envelopes.len())?;
+        let mut offset = 0u64;
+        for envelope in &envelopes {
+            out.extend_from_slice(&offset.to_le_bytes());
+            out.extend_from_slice(&(envelope.len() as u64).to_le_bytes());
+            offset += envelope.len() as u64;
+        }
+        envelopes.iter().for_each(|e| out.extend_from_slice(e));
+        Ok(out)
+    }
+
+    /// Decodes every proof in the bundle, checking them as `CommitAndProof::from_bytes` does,
+    /// and that the index covers the proofs exactly, in order, with nothing after them
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, FormatError> {
+        Self::from_bytes_with_limits(bytes, &DecodeLimits::default())
+    }
+
+    /// Like `from_bytes`, failing with `LimitExceeded` rather than allocating beyond `limits` for all the proofs together
+    pub fn from_bytes_with_limits(
+        bytes: &[u8],
+        limits: &DecodeLimits,
+    ) -> Result<Self, FormatError> {
+        let (mut bundle, index, proofs) = Self::read_index(bytes, limits)?;
+        let mut limits = *limits;
+        bundle.proofs = index
+            .iter()
+            .map(|range| bundle.decode_proof(&proofs[range.clone()], &mut limits))
+            .collect::<Result<_, _>>()?;
+        Ok(bundle)
+    }
+
+    /// Decodes only the `i`th proof of an encoded bundle
+    pub fn proof_at(bytes: &[u8], i: usize) -> Result<CommitAndProof<T>, FormatError> {
+        Self::proof_at_with_limits(bytes, i, &DecodeLimits::default())
+    }
+
+    /// Like `proof_at`, failing with `LimitExceeded` rather than allocating beyond `limits`
+    pub fn proof_at_with_limits(
+        bytes: &[u8],
+        i: usize,
+        limits: &DecodeLimits,
+    ) -> Result<CommitAndProof<T>, FormatError> {
+        let (bundle, index, proofs) = Self::read_index(bytes, limits)?;
+        let range = index
+            .get(i)
+            .ok_or(FormatError::InvalidBundleIndex("no proof at that position"))?;
+        bundle.decode_proof(&proofs[range.clone()], &mut { *limits })
+    }
+
+    /// Reads and validates the header and index, returning the bundle without its proofs,
+    /// the byte range of each proof, and the bytes the ranges are in
+    fn read_index<'a>(
+        bytes: &'a [u8],
+        limits: &DecodeLimits,
+    ) -> Result<(Self, BundleIndex, &'a [u8]), FormatError> {
+        let mut r = ByteReader::with_limits(bytes, limits);
+        if r.take(4)? != BUNDLE_MAGIC {
+            return Err(FormatError::InvalidMagic);
+        }
+        let version = r.u16()?;
+        if version != BUNDLE_VERSION {
+            return Err(FormatError::UnsupportedVersion(version));
+        }
+        let circuit_digest = r.array32()?;
+        let code_id = r.u8()?;
+        let code = CodePreset::from_u8(code_id).ok_or(FormatError::UnknownCodePreset(code_id))?;
+        let num_voles = r.u32()? as usize;
+        let vole_length = r.u32()? as usize;
+        let count = r.u32()? as usize;
+        if count > limits.max_proofs {
+            return Err(FormatError::LimitExceeded("proofs"));
+        }
+        r.check_remaining(count, 16)?;
+        let mut index = Vec::with_capacity(count);
+        for _ in 0..count {
+            let (offset, len) = (r.u64()?, r.u64()?);
+            // Each proof starts where the last ended, so no bytes are skipped or shared
+            let start = index
+                .last()
+                .map_or(0, |last: &Range<usize>| last.end);
+            if offset != start as u64 {
+                return Err(FormatError::InvalidBundleIndex(
+                    "proofs must follow each other in the order of the index",
+                ));
+            }
+            let end = usize::try_from(len)
+                .ok()
+                .and_then(|len| start.checked_add(len))
+                .filter(|end| *end <= r.bytes.len())
+                .ok_or(FormatError::LengthTooLarge)?;
+            index.push(start..end);
+        }
+        if index.last().map_or(0, |last| last.end) != r.bytes.len() {
+            return Err(FormatError::TrailingBytes);
+        }
+        let bundle = Self {
+            circuit_digest,
+            code,
+            num_voles,
+            vole_length,
+            proofs: vec![],
+        };
+        Ok((bundle, index, r.bytes))
+    }
+
+    fn decode_proof(
+        &self,
+        bytes: &[u8],
+        limits: &mut DecodeLimits,
+    ) -> Result<CommitAndProof<T>, FormatError> {
+        let envelope = ProofEnvelope::from_bytes(bytes)?;
+        self.check_params(&envelope.params)?;
+        CommitAndProof::decode_within(&envelope, limits)
+    }
+
+    fn check_params(&self, params: &ProofParams) -> Result<(), FormatError> {
+        if params.code != self.code
+            || params.num_voles != self.num_voles
+            || params.vole_length != self.vole_length
+        {
+            return Err(FormatError::DimensionMismatch(
+                "a proof's parameters differ from the bundle's",
+            ));
+        }
+        Ok(())
+    }
+}
+
+impl<T: PF> Proof<T> {
+    /// The proof as indented JSON, for debugging, examples, and verifiers written in other languages.
+    /// Field elements are decimal strings, so they needn't fit in a JSON number, and the seed openings and their proofs are hex.
+    /// Elements of the challenge field are their coefficients' decimal strings joined by commas
+    pub fn to_json_pretty(&self) -> String {
+        let zkp = |z: &ZKP<T::Ext>| JsonZKP {
+            mul_proof: [
+                to_decimal_ext::<T>(&z.mul_proof.0),
+                to_decimal_ext::<T>(&z.mul_proof.1),
+            ],
+            gate_proof: z.gate_proof.iter().map(to_decimal_ext::<T>).collect(),
+            branch_proof: z.branch_proof.iter().map(to_decimal_ext::<T>).collect(),
+            repetitions: vec![],
+        };
+        let pairs = |p: &[(T, T::Ext)]| {
+            p.iter()
+                .map(|(u, v)| [to_decimal(u), to_decimal_ext::<T>(v)])
+                .collect()
+        };
+        let json = JsonProof {
+            zkp: JsonZKP {
+                repetitions: self.zkp.repetitions.iter().map(zkp).collect(),
+                ..zkp(&self.zkp)
+            },
+            seed_openings: JsonSeedOpenings {
+                seed_opens: self
+                    .seed_openings
+                    .seed_opens
+                    .iter()
+                    .map(hex::encode)
+                    .collect(),
+                seed_proofs: self
+                    .seed_openings
+                    .seed_proofs
+                    .iter()
+                    .map(hex::encode)
+                    .collect(),
+            },
+            public_openings: JsonPublicOpenings {
+                public_inputs: pairs(&self.public_openings.public_inputs),
+                public_outputs: pairs(&self.public_openings.public_outputs),
+            },
+            s_matrix: self
+                .s_matrix
+                .0
+                .iter()
+                .map(|row| row.0.iter().map(to_decimal).collect())
+                .collect(),
+            s_consistency_check: self.s_consistency_check.0.iter().map(to_decimal).collect(),
+            security: self.security.0,
+            grinding_bits: self.grinding_bits,
+            grinding_nonce: self.grinding_nonce,
+        };
+        serde_json::to_string_pretty(&json).expect("serializing to a string can't fail")
+    }
+
+    /// Parses the output of `to_json_pretty`, or any JSON of the same shape. Field elements must be their value below the modulus
+    /// in decimal, without leading zeros, and the seeds and proofs 32 bytes of hex. Dimensions are only checked when the proof is verified
+    pub fn from_json(json: &str) -> Result<Self, FormatError> {
+        Self::from_json_with_limits(json, &DecodeLimits::default())
+    }
+
+    /// Like `from_json`, failing with `LimitExceeded` if the proof has more rows or field elements than `limits` allow
+    pub fn from_json_with_limits(json: &str, limits: &DecodeLimits) -> Result<Self, FormatError> {
+        let json: JsonProof = serde_json::from_str(json)?;
+        json.check_limits(limits, T::Ext::DEGREE)?;
+        let elements = |xs: &[String]| {
+            xs.iter()
+                .map(|x| from_decimal(x))
+                .collect::<Result<Vec<T>, _>>()
+        };
+        let ext_elements = |xs: &[String]| {
+            xs.iter()
+                .map(|x| from_decimal_ext::<T>(x))
+                .collect::<Result<Vec<_>, _>>()
+        };
+        let zkp = |z: &JsonZKP| -> Result<ZKP<T::Ext>, FormatError> {
+            Ok(ZKP {
+                mul_proof: (
+                    from_decimal_ext::<T>(&z.mul_proof[0])?,
+                    from_decimal_ext::<T>(&z.mul_proof[1])?,
+                ),
+                gate_proof: ext_elements(&z.gate_proof)?,
+                branch_proof: ext_elements(&z.branch_proof)?,
+                repetitions: vec![],
+            })
+        };
+        let pairs = |p: &[[String; 2]]| {
+            p.iter()
+                .map(|[u, v]| Ok((from_decimal(u)?, from_decimal_ext::<T>(v)?)))
+                .collect::<Result<Vec<_>, FormatError>>()
+        };
+        let hashes = |h: &[String]| {
+            h.iter()
+                .map(|x| from_hex32(x))
+                .collect::<Result<Vec<_>, _>>()
+        };
+        Ok(Proof {
+            zkp: ZKP {
+                repetitions: json
+                    .zkp
+                    .repetitions
+                    .iter()
+                    .map(zkp)
+                    .collect::<Result<_, _>>()?,
+                ..zkp(&json.zkp)?
+            },
+            seed_openings: SubspaceVOLEOpening {
+                seed_opens: hashes(&json.seed_openings.seed_opens)?,
+                seed_proofs: hashes(&json.seed_openings.seed_proofs)?,
+            },
+            public_openings: PublicOpenings {
+                public_inputs: pairs(&json.public_openings.public_inputs)?,
+                public_outputs: pairs(&json.public_openings.public_outputs)?,
+            },
+            s_matrix: FMatrix(
+                json.s_matrix
+                    .iter()
+                    .map(|row| Ok(FVec(elements(row)?)))
+                    .collect::<Result<_, FormatError>>()?,
+            ),
+            s_consistency_check: FVec(elements(&json.s_consistency_check)?),
+            security: SecurityLevel(json.security),
+            grinding_bits: json.grinding_bits,
+            grinding_nonce: json.grinding_nonce,
+        })
+    }
+}
+
+/// The shape of `Proof::to_json_pretty`'s output
+#[derive(serde::Serialize, serde::Deserialize)]
+struct JsonProof {
+    zkp: JsonZKP,
+    seed_openings: JsonSeedOpenings,
+    public_openings: JsonPublicOpenings,
+    s_matrix: Vec<Vec<String>>,
+    s_consistency_check: Vec<String>,
+    security: usize,
+    grinding_bits: usize,
+    grinding_nonce: u64,
+}
+
+impl JsonProof {
+    /// Checks the parsed proof against `limits` before its field elements are parsed.
+    /// Each element of the challenge field counts as `degree` field elements
+    fn check_limits(&self, limits: &DecodeLimits, degree: usize) -> Result<(), FormatError> {
+        if self.s_matrix.len() > limits.max_rows {
+            return Err(FormatError::LimitExceeded("matrix rows"));
+        }
+        let zkp = |z: &JsonZKP| (2 + z.gate_proof.len() + z.branch_proof.len()) * degree;
+        let elements = zkp(&self.zkp)
+            + self.zkp.repetitions.iter().map(zkp).sum::<usize>()
+            + (1 + degree) * self.public_openings.public_inputs.len()
+            + (1 + degree) * self.public_openings.public_outputs.len()
+            + self.s_matrix.iter().map(Vec::len).sum::<usize>()
+            + self.s_consistency_check.len();
+        if elements > limits.max_elements {
+            return Err(FormatError::LimitExceeded("field elements"));
+        }
+        Ok(())
+    }
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+struct JsonZKP {
+    mul_proof: [String; 2],
+    gate_proof: Vec<String>,
+    branch_proof: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    repetitions: Vec<JsonZKP>,
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+struct JsonSeedOpenings {
+    seed_opens: Vec<String>,
+    seed_proofs: Vec<String>,
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+struct JsonPublicOpenings {
+    public_inputs: Vec<[String; 2]>,
+    public_outputs: Vec<[String; 2]>,
+}
+
+fn from_decimal<T: PF>(s: &str) -> Result<T, FormatError> {
+    // from_str_vartime reduces values above the modulus, so only values that print back the same are canonical
+    T::from_str_vartime(s)
+        .filter(|x| to_decimal(x) == s)
+        .ok_or(FormatError::InvalidJsonValue(
+            "field elements must be decimal and less than the modulus",
+        ))
+}
+
+fn to_decimal_ext<T: PF>(x: &T::Ext) -> String {
+    x.coefficients()
+        .iter()
+        .map(to_decimal)
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+fn from_decimal_ext<T: PF>(s: &str) -> Result<T::Ext, FormatError> {
+    let coefficients = s
+        .split(',')
+        .map(from_decimal)
+        .collect::<Result<Vec<T>, _>>()?;
+    if coefficients.len() != T::Ext::DEGREE {
+        return Err(FormatError::InvalidJsonValue(
+            "challenge field elements must have one coefficient per degree",
+        ));
+    }
+    Ok(T::Ext::from_coefficients(&coefficients))
+}
+
+fn from_hex32(s: &str) -> Result<[u8; 32], FormatError> {
+    hex::decode(s)
+        .ok()
+        .and_then(|bytes| bytes.try_into().ok())
+        .ok_or(FormatError::InvalidJsonValue(
+            "seeds and seed proofs must be 32 bytes of hex",
+        ))
+}
+
+#[cfg(feature = "compression")]
+fn decompress(body: &[u8], max_len: u64) -> Result<Vec<u8>, FormatError> {
+    use std::io::Read;
+    let mut out = Vec::new();
+    zstd::stream::Decoder::new(body)?
+        .take(max_len + 1)
+        .read_to_end(&mut out)?;
+    if out.len() as u64 > max_len {
+        return Err(FormatError::DecompressedTooLarge(max_len));
+    }
+    Ok(out)
+}
+
+#[cfg(not(feature = "compression"))]
+fn decompress(_body: &[u8], _max_len: u64) -> Result<Vec<u8>, FormatError> {
+    Err(FormatError::CompressionUnsupported)
+}
+
+/// Number of bytes in a field element's canonical representation
+fn element_len<T: PF>() -> usize {
+    T::Repr::default().as_ref().len()
+}
+
+fn write_u16(out: &mut Vec<u8>, x: u16) {
+    out.extend_from_slice(&x.to_le_bytes());
+}
+
+/// Lengths and dimensions are written as u32s. Anything longer could never be proven in practice
+fn write_u32(out: &mut Vec<u8>, x: usize) -> Result<(), FormatError> {
+    let x: u32 = x.try_into().map_err(|_| FormatError::LengthOverflow(x))?;
+    out.extend_from_slice(&x.to_le_bytes());
+    Ok(())
+}
+
+fn write_element<T: PF>(out: &mut Vec<u8>, x: &T) {
+    out.extend_from_slice(x.to_repr().as_ref());
+}
+
+fn write_vec<T: PF>(out: &mut Vec<u8>, v: &FVec<T>) -> Result<(), FormatError> {
+    write_u32(out, v.0.len())?;
+    v.0.iter().for_each(|x| write_element(out, x));
+    Ok(())
+}
+
+/// Elements of the challenge field are written as their coefficients
+fn write_ext<T: PF>(out: &mut Vec<u8>, x: &T::Ext) {
+    x.coefficients().iter().for_each(|c| write_element(out, c));
+}
+
+/// Vectors over the challenge field are written as their length followed by each element
+fn write_ext_vec<T: PF>(out: &mut Vec<u8>, v: &[T::Ext]) -> Result<(), FormatError> {
+    write_u32(out, v.len())?;
+    v.iter().for_each(|x| write_ext::<T>(out, x));
+    Ok(())
+}
+
+/// A Quicksilver proof without its repetitions
+fn write_zkp<T: PF>(out: &mut Vec<u8>, zkp: &ZKP<T::Ext>) -> Result<(), FormatError> {
+    write_ext::<T>(out, &zkp.mul_proof.0);
+    write_ext::<T>(out, &zkp.mul_proof.1);
+    write_ext_vec::<T>(out, &zkp.gate_proof)?;
+    write_ext_vec::<T>(out, &zkp.branch_proof)
+}
+
+fn write_pairs<T: PF>(out: &mut Vec<u8>, pairs: &[(T, T::Ext)]) -> Result<(), FormatError> {
+    write_u32(out, pairs.len())?;
+    pairs.iter().for_each(|(a, b)| {
+        write_element(out, a);
+        write_ext::<T>(out, b);
+    });
+    Ok(())
+}
+
+/// Matrices are written as their number of rows and columns followed by the elements in row-major order
+fn write_matrix<T: PF>(out: &mut Vec<u8>, m: &FMatrix<T>) -> Result<(), FormatError> {
+    let cols = m.0.first().map(|row| row.0.len()).unwrap_or(0);
+    if m.0.iter().any(|row| row.0.len() != cols) {
+        return Err(FormatError::RaggedMatrix);
+    }
+    write_u32(out, m.0.len())?;
+    write_u32(out, cols)?;
+    m.0.iter()
+        .for_each(|row| row.0.iter().for_each(|x| write_element(out, x)));
+    Ok(())
+}
+
+/// Reads the encoding's primitives from a byte slice, erroring rather than panicking when the input is too short or malformed
+struct ByteReader<'a> {
+    bytes: &'a [u8],
+    /// Most rows a matrix may have
+    max_rows: usize,
+    /// Field elements that may still be read
+    elements_left: usize,
+}
+
+impl<'a> ByteReader<'a> {
+    fn with_limits(bytes: &'a [u8], limits: &DecodeLimits) -> Self {
+        Self {
+            bytes,
+            max_rows: limits.max_rows,
+            elements_left: limits.max_elements,
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8], FormatError> {
+        if self.bytes.len() < len {
+            return Err(FormatError::UnexpectedEnd);
+        }
+        let (taken, rest) = self.bytes.split_at(len);
+        self.bytes = rest;
+        Ok(taken)
+    }
+
+    /// Errors if fewer than `count` * `item_len` bytes remain, so declared lengths are checked before anything is allocated for them
+    fn check_remaining(&self, count: usize, item_len: usize) -> Result<(), FormatError> {
+        match count.checked_mul(item_len) {
+            Some(len) if len <= self.bytes.len() => Ok(()),
+            _ => Err(FormatError::LengthTooLarge),
+        }
+    }
+
+    fn u8(&mut self) -> Result<u8, FormatError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, FormatError> {
+        Ok(LittleEndian::read_u16(self.take(2)?))
+    }
+
+    fn u32(&mut self) -> Result<u32, FormatError> {
+        Ok(LittleEndian::read_u32(self.take(4)?))
+    }
+
+    fn u64(&mut self) -> Result<u64, FormatError> {
+        Ok(LittleEndian::read_u64(self.take(8)?))
+    }
+
+    fn array32(&mut self) -> Result<[u8; 32], FormatError> {
+        Ok(self.take(32)?.try_into().unwrap())
+    }
+
+    fn element<T: PF>(&mut self) -> Result<T, FormatError> {
+        let mut repr = T::Repr::default();
+        repr.as_mut()
+            .copy_from_slice(self.take(element_len::<T>())?);
+        Option::from(T::from_repr(repr)).ok_or(FormatError::NonCanonicalElement)
+    }
+
+    /// Counts `count` field elements against the limit, before they are allocated
+    fn reserve_elements(&mut self, count: usize) -> Result<(), FormatError> {
+        self.elements_left = self
+            .elements_left
+            .checked_sub(count)
+            .ok_or(FormatError::LimitExceeded("field elements"))?;
+        Ok(())
+    }
+
+    fn elements<T: PF>(&mut self, len: usize) -> Result<Vec<T>, FormatError> {
+        self.check_remaining(len, element_len::<T>())?;
+        self.reserve_elements(len)?;
+        (0..len).map(|_| self.element()).collect()
+    }
+
+    fn vec<T: PF>(&mut self) -> Result<FVec<T>, FormatError> {
+        let len = self.u32()? as usize;
+        Ok(FVec(self.elements(len)?))
+    }
+
+    /// An element of the challenge field from its coefficients
+    fn ext<T: PF>(&mut self) -> Result<T::Ext, FormatError> {
+        let coefficients = (0..T::Ext::DEGREE)
+            .map(|_| self.element())
+            .collect::<Result<Vec<T>, _>>()?;
+        Ok(T::Ext::from_coefficients(&coefficients))
+    }
+
+    fn ext_vec<T: PF>(&mut self) -> Result<Vec<T::Ext>, FormatError> {
+        let len = self.u32()? as usize;
+        let degree = T::Ext::DEGREE;
+        self.check_remaining(len, degree * element_len::<T>())?;
+        self.reserve_elements(len.saturating_mul(degree))?;
+        (0..len).map(|_| self.ext::<T>()).collect()
+    }
+
+    fn zkp<T: PF>(&mut self) -> Result<ZKP<T::Ext>, FormatError> {
+        Ok(ZKP {
+            mul_proof: (self.ext::<T>()?, self.ext::<T>()?),
+            gate_proof: self.ext_vec::<T>()?,
+            branch_proof: self.ext_vec::<T>()?,
+            repetitions: vec![],
+        })
+    }
+
+    fn pairs<T: PF>(&mut self) -> Result<Vec<(T, T::Ext)>, FormatError> {
+        let len = self.u32()? as usize;
+        let pair_len = 1 + T::Ext::DEGREE;
+        self.check_remaining(len, pair_len * element_len::<T>())?;
+        self.reserve_elements(len.saturating_mul(pair_len))?;
+        (0..len)
+            .map(|_| Ok((self.element()?, self.ext::<T>()?)))
+            .collect()
+    }
+
+    fn matrix<T: PF>(&mut self) -> Result<FMatrix<T>, FormatError> {
+        let rows = self.u32()? as usize;
+        let cols = self.u32()? as usize;
+        if rows > self.max_rows {
+            return Err(FormatError::LimitExceeded("matrix rows"));
+        }
+        let num_elements = rows
+            .checked_mul(cols)
+            .ok_or(FormatError::LengthTooLarge)?;
+        self.check_remaining(num_elements, element_len::<T>())?;
+        Ok(FMatrix(
+            (0..rows)
+                .map(|_| Ok(FVec(self.elements(cols)?)))
+                .collect::<Result<Vec<_>, FormatError>>()?,
+        ))
+    }
+}
+
 #[cfg(test)]
 mod test {
     use ff::Field;
-    use rand::thread_rng;
+    use rand::{thread_rng, Rng, SeedableRng};
+    use rand_chacha::ChaCha12Rng;
 
-    use crate::FVec;
+    use crate::{
+        actors::actors::{Prover, Verifier},
+        error::VerifyError,
+        zkp, FVec,
+    };
 
     use super::*;
     #[test]
@@ -88,4 +1148,317 @@ mod test {
         let d: FVec<Fr> = bincode::deserialize(&s).unwrap();
         assert_eq!(v, d);
     }
-}
\ No newline at end of file
+
+    fn commit_and_proof() -> CommitAndProof<Fr> {
+        let witness = FVec::<Fr>([5, 2, 28, 280].iter().map(|x| Fr::from_u128(*x)).collect());
+        let mut prover =
+            Prover::from_witness_and_circuit_unpadded(witness, zkp::test::TEST_CIRCUIT.clone());
+        prover.commit_and_prove().unwrap()
+    }
+
+    #[test]
+    fn proof_bytes_round_trip() {
+        let cnp = commit_and_proof();
+        let bytes = cnp.to_bytes().unwrap();
+        assert!(bytes.len() < bincode::serialize(&cnp).unwrap().len());
+
+        let params = ProofParams::from_bytes(&bytes).unwrap();
+        assert_eq!(params.num_voles, cnp.proof.seed_openings.seed_opens.len());
+        assert_eq!(params.vole_length, 2 * cnp.proof.s_matrix.0.len());
+        assert_eq!(params.security, SecurityLevel::default());
+        assert_eq!(params.grinding_bits, 0);
+
+        let decoded = CommitAndProof::<Fr>::from_bytes(&bytes).unwrap();
+        assert_eq!(decoded.to_bytes().unwrap(), bytes);
+        let verifier = Verifier::from_circuit(zkp::test::TEST_CIRCUIT.clone());
+        assert!(verifier.verify(&decoded).is_ok());
+    }
+
+    #[test]
+    fn envelope_layout() {
+        let cnp = commit_and_proof();
+        let envelope = cnp.to_envelope().unwrap();
+        let bytes = envelope.to_bytes().unwrap();
+        assert_eq!(bytes, cnp.to_bytes().unwrap());
+        assert_eq!(ProofEnvelope::from_bytes(&bytes).unwrap(), envelope);
+
+        // The header's fields at the offsets the spec gives them
+        let params = envelope.params;
+        assert_eq!(&bytes[..4], b"VOLE");
+        assert_eq!(bytes[4..6], PROOF_VERSION.to_le_bytes());
+        assert_eq!(bytes[6], 0);
+        assert_eq!(bytes[7], CodePreset::RAAADefault as u8);
+        assert_eq!(bytes[8..12], (params.num_voles as u32).to_le_bytes());
+        assert_eq!(bytes[12..16], (params.vole_length as u32).to_le_bytes());
+        assert_eq!(bytes[16..18], 128u16.to_le_bytes());
+        assert_eq!(bytes[18], 0);
+        assert_eq!(&bytes[ProofEnvelope::HEADER_LEN..], envelope.payload);
+        let verifier = Verifier::from_circuit(zkp::test::TEST_CIRCUIT.clone());
+        assert!(verifier.verify_envelope(&envelope).is_ok());
+
+        // A valid header doesn't make a valid payload
+        let mut odd_voles = envelope.clone();
+        odd_voles.params.num_voles += 1;
+        let decoded = ProofEnvelope::from_bytes(&odd_voles.to_bytes().unwrap()).unwrap();
+        assert!(CommitAndProof::<Fr>::from_envelope(&decoded).is_err());
+        assert!(matches!(
+            verifier.verify_envelope(&decoded),
+            Err(VerifyError::ParamsMismatch { .. })
+        ));
+        // The VOLE length must split into one more part than the challenge field's degree, which only the proof's field gives
+        let mut odd_length = envelope;
+        odd_length.params.vole_length += 1;
+        let decoded = ProofEnvelope::from_bytes(&odd_length.to_bytes().unwrap()).unwrap();
+        assert!(matches!(
+            CommitAndProof::<Fr>::from_envelope(&decoded),
+            Err(FormatError::DimensionMismatch(_))
+        ));
+    }
+
+    #[test]
+    fn proof_bytes_rejects_malformed() {
+        let bytes = commit_and_proof().to_bytes().unwrap();
+
+        let mut bad_magic = bytes.clone();
+        bad_magic[0] ^= 1;
+        assert!(CommitAndProof::<Fr>::from_bytes(&bad_magic).is_err());
+
+        let mut bad_version = bytes.clone();
+        bad_version[4] = 99;
+        assert!(CommitAndProof::<Fr>::from_bytes(&bad_version).is_err());
+
+        let mut unknown_flag = bytes.clone();
+        unknown_flag[6] = 0x80;
+        assert!(CommitAndProof::<Fr>::from_bytes(&unknown_flag).is_err());
+
+        let mut trailing = bytes.clone();
+        trailing.push(0);
+        assert!(CommitAndProof::<Fr>::from_bytes(&trailing).is_err());
+
+        for len in [0, 3, ProofEnvelope::HEADER_LEN, bytes.len() / 2, bytes.len() - 1] {
+            assert!(CommitAndProof::<Fr>::from_bytes(&bytes[..len]).is_err());
+        }
+
+        // The first element of the witness commitment, overwritten with a value larger than the modulus
+        let mut non_canonical = bytes.clone();
+        let start = ProofEnvelope::HEADER_LEN + 32 + 8;
+        non_canonical[start..start + 32].copy_from_slice(&[0xff; 32]);
+        assert!(CommitAndProof::<Fr>::from_bytes(&non_canonical).is_err());
+    }
+
+    #[test]
+    fn encoding_rejects_unencodable() {
+        let mut ragged = commit_and_proof();
+        ragged.proof.s_matrix.0[0].0.pop();
+        assert!(matches!(ragged.to_bytes(), Err(FormatError::RaggedMatrix)));
+
+        let mut overflow = commit_and_proof().to_envelope().unwrap();
+        overflow.params.num_voles = u32::MAX as usize + 1;
+        assert!(matches!(
+            overflow.to_bytes(),
+            Err(FormatError::LengthOverflow(_))
+        ));
+    }
+
+    #[test]
+    fn decode_limits() {
+        let bytes = commit_and_proof().to_bytes().unwrap();
+        let limits = DecodeLimits::default();
+        assert!(CommitAndProof::<Fr>::from_bytes_with_limits(&bytes, &limits).is_ok());
+
+        // A witness commitment of 2^32 - 1 empty rows takes no bytes, but would take far too much memory
+        let mut empty_rows = bytes.clone();
+        let start = ProofEnvelope::HEADER_LEN + 32;
+        empty_rows[start..start + 8].copy_from_slice(&[0xff, 0xff, 0xff, 0xff, 0, 0, 0, 0]);
+        assert!(matches!(
+            CommitAndProof::<Fr>::from_bytes(&empty_rows),
+            Err(FormatError::LimitExceeded(_))
+        ));
+
+        let few_elements = DecodeLimits {
+            max_elements: 16,
+            ..limits
+        };
+        assert!(matches!(
+            CommitAndProof::<Fr>::from_bytes_with_limits(&bytes, &few_elements),
+            Err(FormatError::LimitExceeded(_))
+        ));
+        let proof = CommitAndProof::<Fr>::from_bytes(&bytes).unwrap().proof;
+        assert!(matches!(
+            Proof::<Fr>::from_json_with_limits(&proof.to_json_pretty(), &few_elements),
+            Err(FormatError::LimitExceeded(_))
+        ));
+
+        // A bundle's proofs share the limits, so its total is bounded rather than each proof's
+        let mut used = limits;
+        CommitAndProof::<Fr>::decode_within(&ProofEnvelope::from_bytes(&bytes).unwrap(), &mut used)
+            .unwrap();
+        let one_proof = DecodeLimits {
+            max_elements: limits.max_elements - used.max_elements,
+            ..limits
+        };
+        let proofs = vec![commit_and_proof(), commit_and_proof()];
+        let bundle = ProofBundle::new([0; 32], proofs)
+            .unwrap()
+            .to_bytes()
+            .unwrap();
+        assert!(ProofBundle::<Fr>::proof_at_with_limits(&bundle, 1, &one_proof).is_ok());
+        assert!(matches!(
+            ProofBundle::<Fr>::from_bytes_with_limits(&bundle, &one_proof),
+            Err(FormatError::LimitExceeded(_))
+        ));
+        let two_proofs = DecodeLimits {
+            max_elements: 2 * one_proof.max_elements,
+            ..limits
+        };
+        assert!(ProofBundle::<Fr>::from_bytes_with_limits(&bundle, &two_proofs).is_ok());
+        let max_proofs = DecodeLimits {
+            max_proofs: 1,
+            ..limits
+        };
+        assert!(matches!(
+            ProofBundle::<Fr>::proof_at_with_limits(&bundle, 0, &max_proofs),
+            Err(FormatError::LimitExceeded(_))
+        ));
+    }
+
+    /// Decoding arbitrary bytes, and verifying whatever decodes, must fail without panicking
+    #[test]
+    fn fuzz_decoding() {
+        let bytes = commit_and_proof().to_bytes().unwrap();
+        let verifier = Verifier::from_circuit(zkp::test::TEST_CIRCUIT.clone());
+        let mut rng = ChaCha12Rng::seed_from_u64(2160);
+        for i in 0..256 {
+            let mut fuzzed = match i % 4 {
+                // A valid header with random bytes after it
+                0 => {
+                    let mut fuzzed = bytes[..ProofEnvelope::HEADER_LEN].to_vec();
+                    fuzzed.extend((0..rng.gen_range(0..4096)).map(|_| rng.gen::<u8>()));
+                    fuzzed
+                }
+                // Truncated
+                1 => bytes[..rng.gen_range(0..bytes.len())].to_vec(),
+                _ => bytes.clone(),
+            };
+            // A few random bytes overwritten, mostly at the start where the header and dimensions are
+            for _ in 0..rng.gen_range(1..4) {
+                if fuzzed.is_empty() {
+                    break;
+                }
+                let end = match rng.gen_bool(0.5) {
+                    true => fuzzed.len().min(ProofEnvelope::HEADER_LEN + 64),
+                    false => fuzzed.len(),
+                };
+                let at = rng.gen_range(0..end);
+                fuzzed[at] = rng.gen();
+            }
+            if let Ok(cnp) = CommitAndProof::<Fr>::from_bytes(&fuzzed) {
+                let _ = verifier.verify(&cnp);
+            }
+        }
+    }
+
+    #[test]
+    fn proof_bundle() {
+        let proofs = vec![commit_and_proof(), commit_and_proof()];
+        let circuit = zkp::test::TEST_CIRCUIT.clone();
+        let bundle = ProofBundle::new(circuit.circuit_id(), proofs.clone()).unwrap();
+        let bytes = bundle.to_bytes().unwrap();
+        let decoded = ProofBundle::<Fr>::from_bytes(&bytes).unwrap();
+        assert_eq!(decoded.proofs.len(), 2);
+        assert_eq!(decoded.to_bytes().unwrap(), bytes);
+        assert_eq!(
+            ProofBundle::<Fr>::proof_at(&bytes, 1)
+                .unwrap()
+                .to_bytes()
+                .unwrap(),
+            proofs[1].to_bytes().unwrap()
+        );
+        assert!(ProofBundle::<Fr>::proof_at(&bytes, 2).is_err());
+
+        let verifier = Verifier::from_circuit(circuit);
+        let results = verifier.verify_bundle(&decoded).unwrap();
+        assert!(results.iter().all(|r| r.is_ok()));
+        let mut other_circuit = decoded;
+        other_circuit.circuit_digest[0] ^= 1;
+        assert!(matches!(
+            verifier.verify_bundle(&other_circuit),
+            Err(VerifyError::Malformed(_))
+        ));
+
+        // The second proof's offset, moved to the first so the proofs overlap
+        let mut overlapping = bytes.clone();
+        let second = ProofBundle::<Fr>::HEADER_LEN + 16;
+        overlapping[second..second + 8].copy_from_slice(&0u64.to_le_bytes());
+        assert!(matches!(
+            ProofBundle::<Fr>::from_bytes(&overlapping),
+            Err(FormatError::InvalidBundleIndex(_))
+        ));
+        let mut trailing = bytes.clone();
+        trailing.push(0);
+        assert!(ProofBundle::<Fr>::from_bytes(&trailing).is_err());
+        assert!(ProofBundle::<Fr>::from_bytes(&bytes[..bytes.len() - 1]).is_err());
+
+        // Proofs with other dimensions can't be bundled together
+        let mut smaller = proofs[0].clone();
+        smaller.proof.s_matrix.0.pop();
+        assert!(ProofBundle::new([0; 32], vec![proofs[0].clone(), smaller]).is_err());
+        assert!(ProofBundle::<Fr>::new([0; 32], vec![]).is_err());
+    }
+
+    #[test]
+    fn proof_json_round_trip() {
+        let proof = commit_and_proof().proof;
+        let json = proof.to_json_pretty();
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(
+            value["seed_openings"]["seed_opens"][0],
+            hex::encode(proof.seed_openings.seed_opens[0])
+        );
+        assert_eq!(
+            value["public_openings"]["public_inputs"].as_array().unwrap().len(),
+            proof.public_openings.public_inputs.len()
+        );
+        assert_eq!(value["security"], 128);
+
+        let decoded = Proof::<Fr>::from_json(&json).unwrap();
+        assert_eq!(decoded.to_json_pretty(), json);
+        assert_eq!(decoded.s_matrix, proof.s_matrix);
+
+        // The modulus itself, a leading zero, and a seed of the wrong length
+        let modulus = crate::circom::modulus::<Fr>().to_string();
+        let s_element = value["s_matrix"][0][0].as_str().unwrap().to_string();
+        for (from, to) in [
+            (format!("\"{}\"", s_element), format!("\"{}\"", modulus)),
+            (format!("\"{}\"", s_element), format!("\"0{}\"", s_element)),
+            (
+                hex::encode(proof.seed_openings.seed_opens[0]),
+                "00".to_string(),
+            ),
+        ] {
+            assert!(matches!(
+                Proof::<Fr>::from_json(&json.replacen(&from, &to, 1)),
+                Err(FormatError::InvalidJsonValue(_))
+            ));
+        }
+        assert!(matches!(
+            Proof::<Fr>::from_json("{}"),
+            Err(FormatError::Json(_))
+        ));
+    }
+
+    #[cfg(feature = "compression")]
+    #[test]
+    fn compressed_proof_bytes_round_trip() {
+        let cnp = commit_and_proof();
+        let compressed = cnp.to_compressed_bytes().unwrap();
+        assert!(ProofEnvelope::from_bytes(&compressed)
+            .unwrap()
+            .is_compressed());
+        assert_eq!(cnp.compressed_size_in_bytes(), compressed.len());
+
+        let decoded = CommitAndProof::<Fr>::from_bytes(&compressed).unwrap();
+        assert_eq!(decoded.to_bytes().unwrap(), cnp.to_bytes().unwrap());
+        assert!(CommitAndProof::<Fr>::from_bytes(&compressed[..compressed.len() - 1]).is_err());
+    }
+}
@@ -0,0 +1,398 @@
+//! Gadgets for writing small circuits in Rust rather than circom. A `CircuitBuilder` allocates wires with their values,
+//! so building a circuit also computes its witness. The verifier builds the same circuit with any values, e.g. zeros,
+//! since which constraints a gadget adds never depends on them.
+//!
+//! As in circom, wire 0 of a new circuit is the constant one. It is constrained to be one by a custom gate.
+//!
+//! `commit_outputs` uses the gadgets to replace a circuit's public outputs with a public hash of them
+use std::collections::BTreeMap;
+
+use crate::{
+    circom::modulus,
+    error::ProofError,
+    vecccom::expand_seed_to_field_vec,
+    zkp::{quicksilver::CustomGate, R1CSWithMetadata, SparseR1CS, R1CS},
+    FVec, SparseFMatrix, SparseVec, PF,
+};
+
+/// Index of a wire in the witness
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Wire(pub usize);
+
+pub struct CircuitBuilder<T: PF> {
+    /// The circuit being extended. Its R1CS is sparse and its public indices are moved into the builder
+    base: R1CSWithMetadata<T>,
+    one: Wire,
+    values: Vec<T>,
+    a_rows: Vec<SparseVec<T>>,
+    b_rows: Vec<SparseVec<T>>,
+    c_rows: Vec<SparseVec<T>>,
+    public_inputs: Vec<usize>,
+    public_outputs: Vec<usize>,
+}
+
+impl<T: PF> Default for CircuitBuilder<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: PF> CircuitBuilder<T> {
+    pub fn new() -> Self {
+        let empty = R1CSWithMetadata {
+            r1cs: R1CS::Sparse(SparseR1CS {
+                a_rows: SparseFMatrix(vec![]),
+                b_rows: SparseFMatrix(vec![]),
+                c_rows: SparseFMatrix(vec![]),
+            }),
+            public_inputs_indices: vec![],
+            public_outputs_indices: vec![],
+            unpadded_wtns_len: 0,
+            custom_gates: vec![],
+            lookups: vec![],
+            memories: vec![],
+            branches: vec![],
+            ccs: vec![],
+            signal_names: BTreeMap::new(),
+        };
+        Self::extend(empty, FVec(vec![]))
+    }
+
+    /// Adds gadgets to an existing circuit, whose wires keep their indices. New wires start with a constant one wire
+    pub fn extend(mut circuit: R1CSWithMetadata<T>, witness: FVec<T>) -> Self {
+        assert_eq!(
+            witness.0.len(),
+            circuit.unpadded_wtns_len,
+            "witness doesn't fit the circuit"
+        );
+        circuit.r1cs = R1CS::Sparse(circuit.r1cs.to_sparse());
+        let mut builder = Self {
+            public_inputs: std::mem::take(&mut circuit.public_inputs_indices),
+            public_outputs: std::mem::take(&mut circuit.public_outputs_indices),
+            base: circuit,
+            one: Wire(0),
+            values: witness.0,
+            a_rows: vec![],
+            b_rows: vec![],
+            c_rows: vec![],
+        };
+        builder.one = builder.alloc(T::ONE);
+        let one = builder.one.0;
+        builder.base.custom_gates.push(CustomGate::new(
+            vec![T::ONE, -T::ONE],
+            vec![vec![one], vec![]],
+        ));
+        builder
+    }
+
+    /// The constant one
+    pub fn one(&self) -> Wire {
+        self.one
+    }
+
+    pub fn value(&self, wire: Wire) -> T {
+        self.values[wire.0]
+    }
+
+    /// A private wire with no constraints yet
+    pub fn alloc(&mut self, value: T) -> Wire {
+        self.values.push(value);
+        Wire(self.values.len() - 1)
+    }
+
+    pub fn public_input(&mut self, value: T) -> Wire {
+        let wire = self.alloc(value);
+        self.public_inputs.push(wire.0);
+        wire
+    }
+
+    pub fn public_output(&mut self, wire: Wire) {
+        self.public_outputs.push(wire.0);
+    }
+
+    /// Makes the public outputs private, returning them
+    pub fn take_public_outputs(&mut self) -> Vec<Wire> {
+        self.public_outputs.drain(..).map(Wire).collect()
+    }
+
+    /// Constrains the linear combinations a, b, and c of wires to satisfy a * b = c
+    pub fn enforce(&mut self, a: &[(Wire, T)], b: &[(Wire, T)], c: &[(Wire, T)]) {
+        let lc = |terms: &[(Wire, T)]| SparseVec(terms.iter().map(|(w, x)| (w.0, *x)).collect());
+        self.a_rows.push(lc(a));
+        self.b_rows.push(lc(b));
+        self.c_rows.push(lc(c));
+    }
+
+    /// x * y
+    pub fn mul(&mut self, x: Wire, y: Wire) -> Wire {
+        let product = self.alloc(self.value(x) * self.value(y));
+        self.enforce(&[(x, T::ONE)], &[(y, T::ONE)], &[(product, T::ONE)]);
+        product
+    }
+
+    /// Constrains x to be 0 or 1
+    pub fn assert_boolean(&mut self, x: Wire) {
+        self.enforce(&[(x, T::ONE)], &[(x, T::ONE)], &[(x, T::ONE)]);
+    }
+
+    /// The `n` lowest bits of x, least significant first, constraining x to be less than 2^`n`.
+    /// `n` must be less than the field's number of bits so the decomposition is unique
+    pub fn to_bits(&mut self, x: Wire, n: usize) -> Vec<Wire> {
+        assert!(
+            n < T::NUM_BITS as usize,
+            "too many bits for a unique decomposition"
+        );
+        let two_inv = T::from(2).invert().unwrap();
+        let mut rest = self.value(x);
+        let mut bits = Vec::with_capacity(n);
+        let mut sum = Vec::with_capacity(n);
+        let mut power = T::ONE;
+        for _ in 0..n {
+            let bit = T::from(rest.is_odd().unwrap_u8() as u64);
+            rest = (rest - bit) * two_inv;
+            let wire = self.alloc(bit);
+            self.assert_boolean(wire);
+            bits.push(wire);
+            sum.push((wire, power));
+            power = power.double();
+        }
+        self.enforce(&sum, &[(self.one(), T::ONE)], &[(x, T::ONE)]);
+        bits
+    }
+
+    /// Constrains x to be less than 2^`bits`
+    pub fn range_check(&mut self, x: Wire, bits: usize) {
+        self.to_bits(x, bits);
+    }
+
+    /// 1 if x < y and 0 otherwise, for x and y less than 2^`bits`, which the caller must constrain separately.
+    /// Bit `bits` of x + 2^`bits` - y is set iff x >= y
+    pub fn less_than(&mut self, x: Wire, y: Wire, bits: usize) -> Wire {
+        let offset = T::from(2).pow_vartime([bits as u64]);
+        let shifted = self.alloc(self.value(x) + offset - self.value(y));
+        let one = self.one();
+        self.enforce(
+            &[(x, T::ONE), (one, offset), (y, -T::ONE)],
+            &[(one, T::ONE)],
+            &[(shifted, T::ONE)],
+        );
+        let top = self.to_bits(shifted, bits + 1)[bits];
+        let lt = self.alloc(T::ONE - self.value(top));
+        self.enforce(
+            &[(one, T::ONE), (top, -T::ONE)],
+            &[(one, T::ONE)],
+            &[(lt, T::ONE)],
+        );
+        lt
+    }
+
+    /// `if_true` if `condition` is 1 and `if_false` if it is 0. Constrains `condition` to be 0 or 1
+    pub fn select(&mut self, condition: Wire, if_true: Wire, if_false: Wire) -> Wire {
+        self.assert_boolean(condition);
+        let (t, f) = (self.value(if_true), self.value(if_false));
+        let selected = self.alloc(f + self.value(condition) * (t - f));
+        // condition * (if_true - if_false) = selected - if_false
+        self.enforce(
+            &[(condition, T::ONE)],
+            &[(if_true, T::ONE), (if_false, -T::ONE)],
+            &[(selected, T::ONE), (if_false, -T::ONE)],
+        );
+        selected
+    }
+
+    /// MiMC hash of `inputs`; see `mimc_hash`
+    pub fn hash(&mut self, inputs: &[Wire]) -> Result<Wire, ProofError> {
+        check_mimc_field::<T>()?;
+        let constants = mimc_constants::<T>();
+        let one = self.one();
+        let mut h = self.alloc(T::ZERO);
+        self.enforce(&[(h, T::ONE)], &[(one, T::ONE)], &[]);
+        for m in inputs {
+            // x_0 = m, x_{i+1} = (x_i + h + c_i)^7
+            let mut x = *m;
+            for c in constants.iter() {
+                let t = [(x, T::ONE), (h, T::ONE), (one, *c)];
+                let t_value = self.value(x) + self.value(h) + c;
+                let t2 = self.alloc(t_value.square());
+                self.enforce(&t, &t, &[(t2, T::ONE)]);
+                let t4 = self.mul(t2, t2);
+                let t6 = self.mul(t4, t2);
+                let next = self.alloc(self.value(t6) * t_value);
+                self.enforce(&[(t6, T::ONE)], &t, &[(next, T::ONE)]);
+                x = next;
+            }
+            // h' = E_h(m) + h + m = x + 2h + m
+            let next = self.alloc(self.value(x) + self.value(h).double() + self.value(*m));
+            self.enforce(
+                &[(x, T::ONE), (h, T::from(2)), (*m, T::ONE)],
+                &[(one, T::ONE)],
+                &[(next, T::ONE)],
+            );
+            h = next;
+        }
+        Ok(h)
+    }
+
+    /// The circuit and its witness
+    pub fn build(self) -> (R1CSWithMetadata<T>, FVec<T>) {
+        let mut circuit = self.base;
+        let R1CS::Sparse(base) = circuit.r1cs else {
+            unreachable!("the base circuit is converted to sparse form")
+        };
+        circuit.r1cs = R1CS::Sparse(SparseR1CS {
+            a_rows: SparseFMatrix([base.a_rows.0, self.a_rows].concat()),
+            b_rows: SparseFMatrix([base.b_rows.0, self.b_rows].concat()),
+            c_rows: SparseFMatrix([base.c_rows.0, self.c_rows].concat()),
+        });
+        circuit.public_inputs_indices = self.public_inputs;
+        circuit.public_outputs_indices = self.public_outputs;
+        circuit.unpadded_wtns_len = self.values.len();
+        (circuit, FVec(self.values))
+    }
+}
+
+/// Number of rounds of the MiMC permutation x -> x^7, enough for 254 bit fields
+const MIMC_ROUNDS: usize = 91;
+
+/// x^7 permutes the field, so the MiMC rounds are injective, unless 7 divides p - 1, as it does for M31's
+fn check_mimc_field<T: PF>() -> Result<(), ProofError> {
+    if modulus::<T>() % 7u32 == 1u32.into() {
+        return Err(ProofError::MimcField);
+    }
+    Ok(())
+}
+
+fn mimc_constants<T: PF>() -> Vec<T> {
+    let seed = *blake3::hash(b"volonym_mimc_constants").as_bytes();
+    let mut constants = expand_seed_to_field_vec::<T>(seed, MIMC_ROUNDS).0;
+    constants[0] = T::ZERO;
+    constants
+}
+
+/// MiMC hash of `inputs` in Miyaguchi-Preneel mode: h starts at zero and becomes E_h(m) + h + m for each input m,
+/// where E_h is 91 rounds of x -> (x + h + c_i)^7 followed by adding h. It takes 4 constraints a round in a circuit.
+/// It is only a hash over fields where x^7 is a permutation, i.e. 7 doesn't divide p - 1, such as BN254's, and fails over other fields
+pub fn mimc_hash<T: PF>(inputs: &[T]) -> Result<T, ProofError> {
+    check_mimc_field::<T>()?;
+    let constants = mimc_constants::<T>();
+    Ok(inputs.iter().fold(T::ZERO, |h, m| {
+        let x = constants.iter().fold(*m, |x, c| {
+            let t = x + h + c;
+            let t2 = t.square();
+            t2.square() * t2 * t
+        });
+        x + h + h + m
+    }))
+}
+
+/// Committed-output mode: replaces the circuit's public outputs with a public input, the `mimc_hash` of `blinding` followed by the outputs,
+/// so a proof shows the outputs hash to a digest without revealing them. `blinding` should be random and kept by whoever may later open the outputs,
+/// since outputs with few possible values could be found from their hash alone.
+/// Returns the circuit, its witness, and the digest, which is the last public input. The verifier gets the same circuit from any witness and blinding.
+/// Fails over fields `mimc_hash` doesn't support
+pub fn commit_outputs<T: PF>(
+    circuit: R1CSWithMetadata<T>,
+    witness: FVec<T>,
+    blinding: T,
+) -> Result<(R1CSWithMetadata<T>, FVec<T>, T), ProofError> {
+    let mut builder = CircuitBuilder::extend(circuit, witness);
+    let outputs = builder.take_public_outputs();
+    let blinding = builder.alloc(blinding);
+    let digest = builder.hash(&[[blinding].as_slice(), &outputs].concat())?;
+    builder.public_inputs.push(digest.0);
+    let value = builder.value(digest);
+    let (circuit, witness) = builder.build();
+    Ok((circuit, witness, value))
+}
+
+#[cfg(test)]
+mod test {
+    use super::{commit_outputs, mimc_hash, CircuitBuilder};
+    use crate::{actors::test_helpers::e2e_test, error::ProofError, fields::m31::M31, Fr};
+    use ff::{Field, PrimeField};
+
+    /// Range checks x and y to 8 bits and reveals a if x < y, otherwise b
+    fn example(x: u128, y: u128, a: u128, b: u128) -> CircuitBuilder<Fr> {
+        let mut builder = CircuitBuilder::new();
+        let x = builder.alloc(Fr::from_u128(x));
+        let y = builder.public_input(Fr::from_u128(y));
+        let (a, b) = (
+            builder.alloc(Fr::from_u128(a)),
+            builder.alloc(Fr::from_u128(b)),
+        );
+        builder.range_check(x, 8);
+        builder.range_check(y, 8);
+        let lt = builder.less_than(x, y, 8);
+        let selected = builder.select(lt, a, b);
+        builder.public_output(selected);
+        builder
+    }
+
+    #[test]
+    fn gadgets() {
+        for (x, y, expected) in [(3, 200, 10), (200, 3, 20), (7, 7, 20)] {
+            let (circuit, witness) = example(x, y, 10, 20).build();
+            assert!(circuit.check_witness(&witness).is_ok());
+            let public_values = e2e_test(witness, circuit).unwrap();
+            assert_eq!(public_values.public_outputs, vec![Fr::from_u128(expected)]);
+        }
+
+        // The verifier's circuit doesn't depend on the values
+        let (circuit, _) = example(0, 0, 0, 0).build();
+        let (other, _) = example(3, 200, 10, 20).build();
+        assert_eq!(circuit.circuit_id(), other.circuit_id());
+
+        // x doesn't fit in 8 bits
+        let (circuit, witness) = example(300, 3, 10, 20).build();
+        assert!(circuit.check_witness(&witness).is_err());
+        assert!(e2e_test(witness, circuit).is_err());
+
+        // The constant one wire can't be changed
+        let (circuit, mut witness) = example(3, 200, 10, 20).build();
+        witness.0[0] = Fr::ZERO;
+        assert!(e2e_test(witness, circuit).is_err());
+    }
+
+    #[test]
+    fn committed_outputs() {
+        let blinding = Fr::from_u128(12345);
+        let (circuit, witness) = example(3, 200, 10, 20).build();
+        let (circuit, witness, digest) = commit_outputs(circuit, witness, blinding).unwrap();
+        assert_eq!(digest, mimc_hash(&[blinding, Fr::from_u128(10)]).unwrap());
+        assert!(circuit.public_outputs_indices.is_empty());
+        let public_values = e2e_test(witness, circuit).unwrap();
+        assert_eq!(
+            public_values.public_inputs,
+            vec![Fr::from_u128(200), digest]
+        );
+
+        // The verifier's circuit doesn't depend on the values
+        let (circuit, witness) = example(0, 0, 0, 0).build();
+        let (circuit, ..) = commit_outputs(circuit, witness, Fr::ZERO).unwrap();
+        let (other, witness) = example(3, 200, 10, 20).build();
+        let (other, ..) = commit_outputs(other, witness, blinding).unwrap();
+        assert_eq!(circuit.circuit_id(), other.circuit_id());
+
+        // The digest must match the outputs
+        let (circuit, witness) = example(3, 200, 10, 20).build();
+        let (circuit, mut witness, _) = commit_outputs(circuit, witness, blinding).unwrap();
+        let digest_index = *circuit.public_inputs_indices.last().unwrap();
+        witness.0[digest_index] = mimc_hash(&[blinding, Fr::from_u128(20)]).unwrap();
+        assert!(circuit.check_witness(&witness).is_err());
+        assert!(e2e_test(witness, circuit).is_err());
+    }
+
+    #[test]
+    fn mimc_rejects_fields_x7_does_not_permute() {
+        // 7 divides 2^31 - 2, so x^7 isn't injective over M31
+        assert!(matches!(mimc_hash(&[M31::ONE]), Err(ProofError::MimcField)));
+        let mut builder = CircuitBuilder::<M31>::new();
+        let one = builder.one();
+        assert!(matches!(builder.hash(&[one]), Err(ProofError::MimcField)));
+        let (circuit, witness) = builder.build();
+        assert!(matches!(
+            commit_outputs(circuit, witness, M31::ONE),
+            Err(ProofError::MimcField)
+        ));
+    }
+}
@@ -0,0 +1,32 @@
+// The sum of a matrix's rows times their coefficients, one column per invocation. The coefficients are in Montgomery form, times 2^256,
+// so that Montgomery multiplying them by the matrix's entries gives their plain products.
+// params after the modulus: rows, cols, and 1 if the matrix is stored row by row or 0 if column by column
+@group(0) @binding(1) var<storage, read> coefficients: array<u32>;
+@group(0) @binding(2) var<storage, read> matrix: array<u32>;
+@group(0) @binding(3) var<storage, read_write> output: array<u32>;
+
+@compute @workgroup_size(64)
+fn combine(@builtin(global_invocation_id) id: vec3<u32>) {
+    let rows = params[LIMBS + 1u];
+    let cols = params[LIMBS + 2u];
+    let row_major = params[LIMBS + 3u] == 1u;
+    let col = id.x;
+    if (col >= cols) {
+        return;
+    }
+    load_modulus();
+    var acc: array<u32, 8>;
+    for (var i = 0u; i < rows; i++) {
+        let entry = select(col * rows + i, i * cols + col, row_major);
+        var c: array<u32, 8>;
+        var x: array<u32, 8>;
+        for (var l = 0u; l < LIMBS; l++) {
+            c[l] = coefficients[i * LIMBS + l];
+            x[l] = matrix[entry * LIMBS + l];
+        }
+        acc = add_mod(acc, mont_mul(c, x));
+    }
+    for (var l = 0u; l < LIMBS; l++) {
+        output[col * LIMBS + l] = acc[l];
+    }
+}
@@ -0,0 +1,88 @@
+// RAAA encoding, one row per invocation: repetition, then three rounds of an interleave and an accumulation.
+// params after the modulus: rows, k, n. perms holds the three forward permutations, each n long
+@group(0) @binding(1) var<storage, read> input: array<u32>;
+@group(0) @binding(2) var<storage, read> perms: array<u32>;
+@group(0) @binding(3) var<storage, read_write> a: array<u32>;
+@group(0) @binding(4) var<storage, read_write> b: array<u32>;
+
+fn load_a(i: u32) -> array<u32, 8> {
+    var x: array<u32, 8>;
+    for (var l = 0u; l < LIMBS; l++) {
+        x[l] = a[i * LIMBS + l];
+    }
+    return x;
+}
+
+fn store_a(i: u32, x: array<u32, 8>) {
+    var xs = x;
+    for (var l = 0u; l < LIMBS; l++) {
+        a[i * LIMBS + l] = xs[l];
+    }
+}
+
+fn load_b(i: u32) -> array<u32, 8> {
+    var x: array<u32, 8>;
+    for (var l = 0u; l < LIMBS; l++) {
+        x[l] = b[i * LIMBS + l];
+    }
+    return x;
+}
+
+fn store_b(i: u32, x: array<u32, 8>) {
+    var xs = x;
+    for (var l = 0u; l < LIMBS; l++) {
+        b[i * LIMBS + l] = xs[l];
+    }
+}
+
+fn interleave_a_to_b(row: u32, n: u32, perm: u32) {
+    for (var j = 0u; j < n; j++) {
+        store_b(row + perms[perm * n + j], load_a(row + j));
+    }
+}
+
+fn interleave_b_to_a(row: u32, n: u32, perm: u32) {
+    for (var j = 0u; j < n; j++) {
+        store_a(row + perms[perm * n + j], load_b(row + j));
+    }
+}
+
+fn accumulate_a(row: u32, n: u32) {
+    var acc = load_a(row);
+    for (var j = 1u; j < n; j++) {
+        acc = add_mod(acc, load_a(row + j));
+        store_a(row + j, acc);
+    }
+}
+
+fn accumulate_b(row: u32, n: u32) {
+    var acc = load_b(row);
+    for (var j = 1u; j < n; j++) {
+        acc = add_mod(acc, load_b(row + j));
+        store_b(row + j, acc);
+    }
+}
+
+// The codeword of each input row ends up in b
+@compute @workgroup_size(64)
+fn encode(@builtin(global_invocation_id) id: vec3<u32>) {
+    let rows = params[LIMBS + 1u];
+    let k = params[LIMBS + 2u];
+    let n = params[LIMBS + 3u];
+    if (id.x >= rows) {
+        return;
+    }
+    load_modulus();
+    let row = id.x * n;
+    for (var j = 0u; j < n; j++) {
+        for (var l = 0u; l < LIMBS; l++) {
+            a[(row + j) * LIMBS + l] = input[(id.x * k + j % k) * LIMBS + l];
+        }
+    }
+    interleave_a_to_b(row, n, 0u);
+    accumulate_b(row, n);
+    interleave_b_to_a(row, n, 1u);
+    accumulate_a(row, n);
+    interleave_a_to_b(row, n, 2u);
+    accumulate_b(row, n);
+}
@@ -0,0 +1,121 @@
+// Arithmetic modulo a prime of at most 256 bits, on field elements stored as eight 32 bit little endian limbs of their canonical value.
+// params holds the modulus's limbs, then -modulus^-1 mod 2^32 for Montgomery multiplication, then the entry point's dimensions
+@group(0) @binding(0) var<storage, read> params: array<u32>;
+
+const LIMBS: u32 = 8u;
+
+var<private> modulus: array<u32, 8>;
+var<private> modulus_inv: u32;
+
+fn load_modulus() {
+    for (var i = 0u; i < LIMBS; i++) {
+        modulus[i] = params[i];
+    }
+    modulus_inv = params[LIMBS];
+}
+
+fn less_than_modulus(x: array<u32, 8>) -> bool {
+    var xs = x;
+    for (var i = LIMBS; i > 0u; i--) {
+        if (xs[i - 1u] != modulus[i - 1u]) {
+            return xs[i - 1u] < modulus[i - 1u];
+        }
+    }
+    return false;
+}
+
+// x - modulus, wrapping
+fn sub_modulus(x: array<u32, 8>) -> array<u32, 8> {
+    var xs = x;
+    var borrow = 0u;
+    for (var i = 0u; i < LIMBS; i++) {
+        let d = xs[i] - modulus[i];
+        let b1 = select(0u, 1u, xs[i] < modulus[i]);
+        let d2 = d - borrow;
+        let b2 = select(0u, 1u, d < borrow);
+        xs[i] = d2;
+        borrow = b1 + b2;
+    }
+    return xs;
+}
+
+fn add_mod(x: array<u32, 8>, y: array<u32, 8>) -> array<u32, 8> {
+    var xs = x;
+    var ys = y;
+    var carry = 0u;
+    for (var i = 0u; i < LIMBS; i++) {
+        let s = xs[i] + ys[i];
+        let c1 = select(0u, 1u, s < xs[i]);
+        let s2 = s + carry;
+        let c2 = select(0u, 1u, s2 < s);
+        xs[i] = s2;
+        carry = c1 + c2;
+    }
+    if (carry != 0u || !less_than_modulus(xs)) {
+        return sub_modulus(xs);
+    }
+    return xs;
+}
+
+// The low and high words of a * b, from 16 bit halves since WGSL has no 64 bit integers
+fn mul_wide(a: u32, b: u32) -> vec2<u32> {
+    let a0 = a & 0xffffu;
+    let a1 = a >> 16u;
+    let b0 = b & 0xffffu;
+    let b1 = b >> 16u;
+    let p00 = a0 * b0;
+    let p01 = a0 * b1;
+    let p10 = a1 * b0;
+    let p11 = a1 * b1;
+    let mid = (p00 >> 16u) + (p01 & 0xffffu) + (p10 & 0xffffu);
+    let lo = (p00 & 0xffffu) | (mid << 16u);
+    let hi = p11 + (p01 >> 16u) + (p10 >> 16u) + (mid >> 16u);
+    return vec2<u32>(lo, hi);
+}
+
+// t + a * b + c as low and high words. It can't overflow 64 bits
+fn mac(t: u32, a: u32, b: u32, c: u32) -> vec2<u32> {
+    let p = mul_wide(a, b);
+    let s = t + p.x;
+    let c1 = select(0u, 1u, s < t);
+    let s2 = s + c;
+    let c2 = select(0u, 1u, s2 < s);
+    return vec2<u32>(s2, p.y + c1 + c2);
+}
+
+// x * y * 2^-256 modulo the modulus, by coarsely integrated operand scanning
+fn mont_mul(x: array<u32, 8>, y: array<u32, 8>) -> array<u32, 8> {
+    var xs = x;
+    var ys = y;
+    var t: array<u32, 10>;
+    for (var i = 0u; i < LIMBS; i++) {
+        var c = 0u;
+        for (var j = 0u; j < LIMBS; j++) {
+            let r = mac(t[j], xs[j], ys[i], c);
+            t[j] = r.x;
+            c = r.y;
+        }
+        let s = t[LIMBS] + c;
+        t[LIMBS + 1u] = select(0u, 1u, s < c);
+        t[LIMBS] = s;
+
+        let m = t[0] * modulus_inv;
+        c = mac(t[0], m, modulus[0], 0u).y;
+        for (var j = 1u; j < LIMBS; j++) {
+            let r = mac(t[j], m, modulus[j], c);
+            t[j - 1u] = r.x;
+            c = r.y;
+        }
+        let s2 = t[LIMBS] + c;
+        t[LIMBS - 1u] = s2;
+        t[LIMBS] = t[LIMBS + 1u] + select(0u, 1u, s2 < c);
+    }
+    var out: array<u32, 8>;
+    for (var i = 0u; i < LIMBS; i++) {
+        out[i] = t[i];
+    }
+    if (t[LIMBS] != 0u || !less_than_modulus(out)) {
+        return sub_modulus(out);
+    }
+    return out;
+}
@@ -0,0 +1,484 @@
+//! Offloads the largest linear algebra to a GPU through wgpu: `RAAACode::batch_encode`, `FMatrix::transpose`, and
+//! `LayoutMatrix::row_combination`. Field elements go to the GPU as their canonical values in eight 32 bit limbs, with the modulus
+//! passed to the shaders, so any field of at most 256 bits works.
+//!
+//! Each function returns None for its caller to compute on the CPU instead when there is no GPU, the input has fewer than
+//! `MIN_GPU_ELEMENTS` elements, or the GPU fails. Inputs larger than the GPU's buffers are split into chunks of rows
+use std::sync::{mpsc, OnceLock};
+
+use wgpu::util::DeviceExt;
+
+use crate::{circom::modulus, subspacevole::RAAACode, FMatrix, FVec, Layout, PF};
+
+/// Matrices with fewer elements than this are faster to work on without copying them to the GPU and back.
+/// With the default code, the VOLE of a circuit with about a million constraints has this many
+pub const MIN_GPU_ELEMENTS: usize = 1 << 22;
+
+const LIMBS: usize = 8;
+const ELEMENT_BYTES: usize = LIMBS * 4;
+
+struct Gpu {
+    device: wgpu::Device,
+    queue: wgpu::Queue,
+    /// The largest buffer a shader can bind, in bytes
+    max_buffer: usize,
+    encode: wgpu::ComputePipeline,
+    combine: wgpu::ComputePipeline,
+    transpose: wgpu::ComputePipeline,
+}
+
+/// The GPU, which is looked for the first time one is needed
+fn gpu() -> Option<&'static Gpu> {
+    static GPU: OnceLock<Option<Gpu>> = OnceLock::new();
+    GPU.get_or_init(|| {
+        let gpu = Gpu::new();
+        match &gpu {
+            Some(_) => tracing::info!("offloading large matrix operations to the GPU"),
+            None => tracing::info!("no usable GPU was found, computing on the CPU"),
+        }
+        gpu
+    })
+    .as_ref()
+}
+
+/// Whether a GPU was found to offload to
+pub fn available() -> bool {
+    gpu().is_some()
+}
+
+/// `code.batch_encode(matrix)` on the GPU
+pub fn batch_encode<T: PF>(code: &RAAACode, matrix: &[FVec<T>]) -> Option<Vec<FVec<T>>> {
+    let n = code.permutations[0].0.len();
+    if matrix.len() * n < MIN_GPU_ELEMENTS {
+        return None;
+    }
+    gpu()?.batch_encode(code, matrix)
+}
+
+/// `matrix.transpose()` on the GPU
+pub fn transpose<T: PF>(matrix: &FMatrix<T>) -> Option<FMatrix<T>> {
+    if elements(matrix) < MIN_GPU_ELEMENTS {
+        return None;
+    }
+    gpu()?.transpose(matrix)
+}
+
+/// `LayoutMatrix::row_combination` on the GPU, for a matrix stored in `layout`
+pub fn row_combination<T: PF>(
+    matrix: &FMatrix<T>,
+    layout: Layout,
+    coefficients: &FVec<T>,
+) -> Option<FVec<T>> {
+    if elements(matrix) < MIN_GPU_ELEMENTS {
+        return None;
+    }
+    gpu()?.row_combination(matrix, layout, coefficients)
+}
+
+fn elements<T: PF>(matrix: &FMatrix<T>) -> usize {
+    matrix.0.iter().map(|row| row.0.len()).sum()
+}
+
+impl Gpu {
+    fn new() -> Option<Self> {
+        let instance = wgpu::Instance::default();
+        let adapter = pollster::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions {
+            power_preference: wgpu::PowerPreference::HighPerformance,
+            ..Default::default()
+        }))?;
+        let limits = adapter.limits();
+        let (device, queue) = pollster::block_on(adapter.request_device(
+            &wgpu::DeviceDescriptor {
+                label: Some("volonym"),
+                required_features: wgpu::Features::empty(),
+                required_limits: limits.clone(),
+            },
+            None,
+        ))
+        .ok()?;
+        let field = include_str!("field.wgsl");
+        let pipeline = |source: String, entry_point: &str| {
+            let module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+                label: Some(entry_point),
+                source: wgpu::ShaderSource::Wgsl(source.into()),
+            });
+            device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                label: Some(entry_point),
+                layout: None,
+                module: &module,
+                entry_point,
+            })
+        };
+        device.push_error_scope(wgpu::ErrorFilter::Validation);
+        let encode = pipeline(field.to_string() + include_str!("encode.wgsl"), "encode");
+        let combine = pipeline(field.to_string() + include_str!("combine.wgsl"), "combine");
+        let transpose = pipeline(include_str!("transpose.wgsl").to_string(), "transpose");
+        if pollster::block_on(device.pop_error_scope()).is_some() {
+            return None;
+        }
+        Some(Self {
+            max_buffer: limits
+                .max_storage_buffer_binding_size
+                .min(limits.max_buffer_size.min(u32::MAX as u64) as u32)
+                as usize,
+            device,
+            queue,
+            encode,
+            combine,
+            transpose,
+        })
+    }
+
+    /// Rows of `row_elements` elements that fit in one buffer
+    fn chunk_rows(&self, row_elements: usize) -> usize {
+        (self.max_buffer / (row_elements * ELEMENT_BYTES).max(1)).max(1)
+    }
+
+    fn batch_encode<T: PF>(&self, code: &RAAACode, matrix: &[FVec<T>]) -> Option<Vec<FVec<T>>> {
+        let n = code.permutations[0].0.len();
+        let k = n / code.q;
+        if matrix.iter().any(|row| row.0.len() != k) {
+            return None;
+        }
+        let field = FieldParams::<T>::new()?;
+        let perms = code
+            .permutations
+            .iter()
+            .flat_map(|(forward, _)| forward.iter().copied())
+            .collect::<Vec<u32>>();
+        let mut encoded = Vec::with_capacity(matrix.len());
+        for chunk in matrix.chunks(self.chunk_rows(n)) {
+            let rows = chunk.len();
+            let params = field.params(&[rows as u32, k as u32, n as u32]);
+            let out = self.output(rows * n);
+            let words = self.run(
+                &self.encode,
+                &[
+                    &self.input(&params),
+                    &self.input(&to_limbs(chunk.iter().flat_map(|row| row.0.iter()))),
+                    &self.input(&perms),
+                    &self.output(rows * n),
+                    &out,
+                ],
+                (rows.div_ceil(64) as u32, 1),
+                &out,
+            )?;
+            let values = from_limbs::<T>(&words)?;
+            encoded.extend(values.chunks(n).map(|row| FVec(row.to_vec())));
+        }
+        Some(encoded)
+    }
+
+    fn transpose<T: PF>(&self, matrix: &FMatrix<T>) -> Option<FMatrix<T>> {
+        let cols = matrix.0.first()?.0.len();
+        if matrix.0.iter().any(|row| row.0.len() != cols) {
+            return None;
+        }
+        let mut transposed = vec![Vec::with_capacity(matrix.0.len()); cols];
+        for chunk in matrix.0.chunks(self.chunk_rows(cols)) {
+            let rows = chunk.len();
+            let out = self.output(rows * cols);
+            let words = self.run(
+                &self.transpose,
+                &[
+                    &self.input(&[rows as u32, cols as u32]),
+                    &self.input(&to_limbs(chunk.iter().flat_map(|row| row.0.iter()))),
+                    &out,
+                ],
+                (cols.div_ceil(16) as u32, rows.div_ceil(16) as u32),
+                &out,
+            )?;
+            // Transposed, the chunk has a row of `rows` values for each column, which continues it
+            let values = from_limbs::<T>(&words)?;
+            transposed
+                .iter_mut()
+                .zip(values.chunks(rows))
+                .for_each(|(col, part)| col.extend_from_slice(part));
+        }
+        Some(FMatrix(transposed.into_iter().map(FVec).collect()))
+    }
+
+    fn row_combination<T: PF>(
+        &self,
+        matrix: &FMatrix<T>,
+        layout: Layout,
+        coefficients: &FVec<T>,
+    ) -> Option<FVec<T>> {
+        let len = matrix.0.first()?.0.len();
+        if matrix.0.iter().any(|v| v.0.len() != len) {
+            return None;
+        }
+        let field = FieldParams::<T>::new()?;
+        // The rows of the matrix are either its vectors or the entries of each of them
+        let num_rows = match layout {
+            Layout::RowMajor => matrix.0.len(),
+            Layout::ColMajor => len,
+        };
+        if coefficients.0.len() < num_rows {
+            return None;
+        }
+        let coefficients = coefficients.0[..num_rows]
+            .iter()
+            .map(|c| *c * field.montgomery)
+            .collect::<Vec<T>>();
+        // Chunks of the stored vectors: of rows, whose partial sums are added, or of columns, whose results are concatenated
+        let mut acc = match layout {
+            Layout::RowMajor => vec![T::ZERO; len],
+            Layout::ColMajor => Vec::with_capacity(matrix.0.len()),
+        };
+        let chunk_len = self.chunk_rows(len);
+        for (i, chunk) in matrix.0.chunks(chunk_len).enumerate() {
+            let (rows, cols, chunk_coefficients) = match layout {
+                Layout::RowMajor => (
+                    chunk.len(),
+                    len,
+                    &coefficients[i * chunk_len..i * chunk_len + chunk.len()],
+                ),
+                Layout::ColMajor => (len, chunk.len(), &coefficients[..]),
+            };
+            let params = field.params(&[
+                rows as u32,
+                cols as u32,
+                (layout == Layout::RowMajor) as u32,
+            ]);
+            let out = self.output(cols);
+            let words = self.run(
+                &self.combine,
+                &[
+                    &self.input(&params),
+                    &self.input(&to_limbs(chunk_coefficients.iter())),
+                    &self.input(&to_limbs(chunk.iter().flat_map(|v| v.0.iter()))),
+                    &out,
+                ],
+                (cols.div_ceil(64) as u32, 1),
+                &out,
+            )?;
+            let values = from_limbs::<T>(&words)?;
+            match layout {
+                Layout::RowMajor => acc.iter_mut().zip(values).for_each(|(a, x)| *a += x),
+                Layout::ColMajor => acc.extend(values),
+            }
+        }
+        Some(FVec(acc))
+    }
+
+    fn input(&self, words: &[u32]) -> wgpu::Buffer {
+        self.device
+            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: None,
+                contents: &words
+                    .iter()
+                    .flat_map(|w| w.to_le_bytes())
+                    .collect::<Vec<u8>>(),
+                usage: wgpu::BufferUsages::STORAGE,
+            })
+    }
+
+    fn output(&self, elements: usize) -> wgpu::Buffer {
+        self.device.create_buffer(&wgpu::BufferDescriptor {
+            label: None,
+            size: (elements * ELEMENT_BYTES) as u64,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        })
+    }
+
+    /// Runs `pipeline` on `workgroups` workgroups with `buffers` bound in order, returning the words of `result` afterwards
+    fn run(
+        &self,
+        pipeline: &wgpu::ComputePipeline,
+        buffers: &[&wgpu::Buffer],
+        workgroups: (u32, u32),
+        result: &wgpu::Buffer,
+    ) -> Option<Vec<u32>> {
+        self.device.push_error_scope(wgpu::ErrorFilter::OutOfMemory);
+        self.device.push_error_scope(wgpu::ErrorFilter::Validation);
+        let bind_group = self.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: None,
+            layout: &pipeline.get_bind_group_layout(0),
+            entries: &buffers
+                .iter()
+                .enumerate()
+                .map(|(i, buffer)| wgpu::BindGroupEntry {
+                    binding: i as u32,
+                    resource: buffer.as_entire_binding(),
+                })
+                .collect::<Vec<_>>(),
+        });
+        let staging = self.device.create_buffer(&wgpu::BufferDescriptor {
+            label: None,
+            size: result.size(),
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let mut encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+        {
+            let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor::default());
+            pass.set_pipeline(pipeline);
+            pass.set_bind_group(0, &bind_group, &[]);
+            pass.dispatch_workgroups(workgroups.0, workgroups.1, 1);
+        }
+        encoder.copy_buffer_to_buffer(result, 0, &staging, 0, result.size());
+        self.queue.submit([encoder.finish()]);
+        let failed = pollster::block_on(self.device.pop_error_scope()).is_some()
+            | pollster::block_on(self.device.pop_error_scope()).is_some();
+        if failed {
+            return None;
+        }
+
+        let (sender, receiver) = mpsc::channel();
+        let slice = staging.slice(..);
+        slice.map_async(wgpu::MapMode::Read, move |mapped| {
+            let _ = sender.send(mapped);
+        });
+        self.device.poll(wgpu::Maintain::Wait);
+        receiver.recv().ok()?.ok()?;
+        let words = slice
+            .get_mapped_range()
+            .chunks_exact(4)
+            .map(|w| u32::from_le_bytes([w[0], w[1], w[2], w[3]]))
+            .collect();
+        staging.unmap();
+        Some(words)
+    }
+}
+
+/// What the field shaders need to know about `T`
+struct FieldParams<T: PF> {
+    /// The modulus's limbs and -modulus^-1 mod 2^32
+    header: Vec<u32>,
+    /// 2^256, the Montgomery form of one
+    montgomery: T,
+}
+
+impl<T: PF> FieldParams<T> {
+    /// None for fields the shaders can't compute in: those over 256 bits, or of characteristic 2
+    fn new() -> Option<Self> {
+        let digits = modulus::<T>().to_u32_digits();
+        if digits.len() > LIMBS || digits[0].is_multiple_of(2) {
+            return None;
+        }
+        let mut header = digits;
+        header.resize(LIMBS, 0);
+        // Newton's iteration doubles the bits of the inverse each time, from the 1 bit of 1
+        let mut inverse = 1u32;
+        for _ in 0..5 {
+            inverse = inverse.wrapping_mul(2u32.wrapping_sub(header[0].wrapping_mul(inverse)));
+        }
+        header.push(inverse.wrapping_neg());
+        Some(Self {
+            header,
+            montgomery: T::from(2).pow_vartime([256u64]),
+        })
+    }
+
+    /// The params buffer of a field shader, with an entry point's dimensions after the header
+    fn params(&self, dims: &[u32]) -> Vec<u32> {
+        [self.header.as_slice(), dims].concat()
+    }
+}
+
+/// Canonical little endian limbs of each element
+fn to_limbs<'a, T: PF + 'a>(xs: impl Iterator<Item = &'a T>) -> Vec<u32> {
+    let big_endian = T::ONE.to_repr().as_ref()[0] != 1;
+    let mut words = Vec::new();
+    for x in xs {
+        let repr = x.to_repr();
+        let mut bytes = repr.as_ref().to_vec();
+        if big_endian {
+            bytes.reverse();
+        }
+        bytes.resize(ELEMENT_BYTES, 0);
+        words.extend(
+            bytes
+                .chunks_exact(4)
+                .map(|w| u32::from_le_bytes([w[0], w[1], w[2], w[3]])),
+        );
+    }
+    words
+}
+
+/// The elements whose canonical limbs are `words`, or None if any isn't canonical
+fn from_limbs<T: PF>(words: &[u32]) -> Option<Vec<T>> {
+    let big_endian = T::ONE.to_repr().as_ref()[0] != 1;
+    words
+        .chunks_exact(LIMBS)
+        .map(|limbs| {
+            let bytes = limbs
+                .iter()
+                .flat_map(|w| w.to_le_bytes())
+                .collect::<Vec<u8>>();
+            let mut repr = T::Repr::default();
+            let len = repr.as_ref().len();
+            if bytes[len.min(ELEMENT_BYTES)..].iter().any(|b| *b != 0) {
+                return None;
+            }
+            repr.as_mut().copy_from_slice(&bytes[..len]);
+            if big_endian {
+                repr.as_mut().reverse();
+            }
+            Option::from(T::from_repr(repr))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{subspacevole::LinearCode, Fr, LayoutMatrix};
+    use rand::thread_rng;
+
+    #[test]
+    fn limbs_round_trip() {
+        let xs = FVec::<Fr>::random_with_rng(5, &mut thread_rng()).0;
+        let words = to_limbs(xs.iter());
+        assert_eq!(words.len(), 5 * LIMBS);
+        assert_eq!(to_limbs([Fr::from(3)].iter())[..2], [3, 0]);
+        assert_eq!(from_limbs::<Fr>(&words), Some(xs));
+        // The modulus itself isn't canonical
+        let mut modulus = FieldParams::<Fr>::new().unwrap().header;
+        let inverse = modulus.pop().unwrap();
+        assert_eq!(modulus[0].wrapping_mul(inverse.wrapping_neg()), 1);
+        assert_eq!(from_limbs::<Fr>(&modulus), None);
+    }
+
+    #[test]
+    fn gpu_matches_cpu() {
+        // Without a GPU, callers compute on the CPU and there is nothing to compare
+        let Some(gpu) = gpu() else {
+            return;
+        };
+        let code = RAAACode::rand_with_parameters(64, 2);
+        let rows = (0..5)
+            .map(|_| FVec::<Fr>::random_with_rng(32, &mut thread_rng()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            gpu.batch_encode(&code, &rows).unwrap(),
+            rows.iter().map(|row| code.encode(row)).collect::<Vec<_>>()
+        );
+
+        let matrix = FMatrix(
+            (0..5)
+                .map(|_| FVec::<Fr>::random_with_rng(7, &mut thread_rng()))
+                .collect(),
+        );
+        let transposed = gpu.transpose(&matrix).unwrap();
+        assert_eq!(transposed.0, matrix.transpose().0);
+        let coefficients = FVec::<Fr>::random_with_rng(5, &mut thread_rng());
+        let expected = LayoutMatrix::from_rows(matrix.clone()).row_combination(&coefficients);
+        assert_eq!(
+            gpu.row_combination(&matrix, Layout::RowMajor, &coefficients)
+                .unwrap()
+                .0,
+            expected.0
+        );
+        assert_eq!(
+            gpu.row_combination(&transposed, Layout::ColMajor, &coefficients)
+                .unwrap()
+                .0,
+            expected.0
+        );
+    }
+}
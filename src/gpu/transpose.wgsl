@@ -0,0 +1,18 @@
+// Transposes a matrix of field elements, eight 32 bit limbs each, one element per invocation. dims holds rows and cols
+@group(0) @binding(0) var<storage, read> dims: array<u32>;
+@group(0) @binding(1) var<storage, read> input: array<u32>;
+@group(0) @binding(2) var<storage, read_write> output: array<u32>;
+
+@compute @workgroup_size(16, 16)
+fn transpose(@builtin(global_invocation_id) id: vec3<u32>) {
+    let rows = dims[0];
+    let cols = dims[1];
+    let row = id.y;
+    let col = id.x;
+    if (row >= rows || col >= cols) {
+        return;
+    }
+    for (var l = 0u; l < 8u; l++) {
+        output[(col * rows + row) * 8u + l] = input[(row * cols + col) * 8u + l];
+    }
+}
@@ -0,0 +1,421 @@
+//! A gRPC proving service with `Prove`, `Verify`, and `Status` RPCs, defined in `proto/volonym.proto` and served by the volonym-server binary.
+//!
+//! The server loads its circuits and code parameters once, under names requests choose them by. `Prove` takes the witness
+//! as a stream of chunks so large witnesses needn't fit in one message, buffering them up to `ServerConfig::max_witness_bytes`.
+//! It proves on a blocking thread, at most `ServerConfig::max_concurrent_proofs` at once; other requests are served while proofs are in progress.
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::{
+        atomic::{AtomicU32, AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::Instant,
+};
+
+use tokio::sync::Semaphore;
+use tonic::{Code, Request, Response, Status, Streaming};
+
+use crate::{
+    actors::actors::{CommitAndProof, ProverBuilder, ProvingKey, Verifier, VerifyingKey},
+    challenges::SecurityLevel,
+    circom::witness::wtns_from_reader,
+    proto::{
+        self, elements, CircuitInfo, ProveChunk, ProveResponse, StatusRequest, StatusResponse,
+        VerifyRequest, VerifyResponse,
+    },
+    subspacevole::CodeParams,
+    zkp::R1CSWithMetadata,
+    Fr,
+};
+
+include!(concat!(env!("OUT_DIR"), "/volonym.ProvingService.rs"));
+
+pub use proving_service_client::ProvingServiceClient;
+pub use proving_service_server::{ProvingService, ProvingServiceServer};
+
+#[derive(Clone, Debug)]
+pub struct ServerConfig {
+    /// Proofs to make at once. Each takes a blocking thread and the memory of proving its circuit
+    pub max_concurrent_proofs: usize,
+    /// Threads each proof expands its VOLEs on, see `ProverBuilder::parallelism`
+    pub parallelism: usize,
+    /// Largest witness, as a .wtns file, a `Prove` call may stream. The witness is buffered whole before it is parsed,
+    /// so every `Prove` call receiving or waiting to prove can hold this much. Defaults to 64 MiB, about two million values
+    pub max_witness_bytes: usize,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            max_concurrent_proofs: 1,
+            parallelism: 1,
+            max_witness_bytes: 1 << 26,
+        }
+    }
+}
+
+#[derive(Default)]
+struct Stats {
+    proofs_in_progress: AtomicU32,
+    proofs_completed: AtomicU64,
+    proofs_failed: AtomicU64,
+    verifications: AtomicU64,
+}
+
+/// Why a request failed, converted to its `Status` when returned. `Status` itself is too large to return from every helper
+struct RequestError(Code, String);
+
+impl From<Status> for RequestError {
+    fn from(status: Status) -> Self {
+        Self(status.code(), status.message().to_string())
+    }
+}
+
+impl From<RequestError> for Status {
+    fn from(e: RequestError) -> Self {
+        Status::new(e.0, e.1)
+    }
+}
+
+type ProvingKeys = HashMap<(String, String), Arc<ProvingKey<Fr>>>;
+
+/// Implements `ProvingService` for the circuits and code parameters added to it
+pub struct ProvingServer {
+    circuits: BTreeMap<String, Arc<R1CSWithMetadata<Fr>>>,
+    code_params: BTreeMap<String, CodeParams>,
+    /// Proving keys for each (circuit, code parameters) pair proven for so far
+    proving_keys: Mutex<ProvingKeys>,
+    proofs: Arc<Semaphore>,
+    config: ServerConfig,
+    stats: Stats,
+}
+
+impl ProvingServer {
+    pub fn new(config: ServerConfig) -> Self {
+        Self {
+            circuits: BTreeMap::new(),
+            code_params: BTreeMap::new(),
+            proving_keys: Mutex::new(HashMap::new()),
+            proofs: Arc::new(Semaphore::new(config.max_concurrent_proofs)),
+            config,
+            stats: Stats::default(),
+        }
+    }
+
+    /// Serves `circuit` to requests naming it `name`
+    pub fn add_circuit(&mut self, name: &str, circuit: R1CSWithMetadata<Fr>) {
+        self.circuits.insert(name.to_string(), Arc::new(circuit));
+    }
+
+    /// Lets requests prove and verify with the code of `params` by naming it `name`. Requests naming none use the default code
+    pub fn add_code_params(&mut self, name: &str, params: CodeParams) {
+        self.code_params.insert(name.to_string(), params);
+    }
+
+    pub fn into_service(self) -> ProvingServiceServer<Self> {
+        ProvingServiceServer::new(self)
+    }
+
+    fn circuit(&self, name: &str) -> Result<Arc<R1CSWithMetadata<Fr>>, RequestError> {
+        self.circuits
+            .get(name)
+            .cloned()
+            .ok_or_else(|| RequestError(Code::NotFound, format!("no circuit named {name:?}")))
+    }
+
+    fn code_params(&self, name: &str) -> Result<CodeParams, RequestError> {
+        if name.is_empty() {
+            return Ok(CodeParams::default());
+        }
+        self.code_params.get(name).cloned().ok_or_else(|| {
+            RequestError(Code::NotFound, format!("no code parameters named {name:?}"))
+        })
+    }
+
+    fn proving_key(
+        &self,
+        circuit: &str,
+        params: &str,
+    ) -> Result<Arc<ProvingKey<Fr>>, RequestError> {
+        let id = (circuit.to_string(), params.to_string());
+        if let Some(key) = self.proving_keys.lock().unwrap().get(&id) {
+            return Ok(key.clone());
+        }
+        let key = Arc::new(ProvingKey::with_code_params(
+            (*self.circuit(circuit)?).clone(),
+            self.code_params(params)?,
+        ));
+        self.proving_keys.lock().unwrap().insert(id, key.clone());
+        Ok(key)
+    }
+
+    /// Reads the parameters and the whole witness from a `Prove` call's stream
+    async fn receive_witness(
+        &self,
+        stream: &mut Streaming<ProveChunk>,
+    ) -> Result<(proto::ProveParams, Vec<u8>), RequestError> {
+        let first = stream.message().await?.ok_or_else(|| {
+            RequestError(Code::InvalidArgument, "no messages were sent".to_string())
+        })?;
+        let params = first.params.ok_or_else(|| {
+            RequestError(
+                Code::InvalidArgument,
+                "the first message must have the parameters".to_string(),
+            )
+        })?;
+        let mut witness = first.witness;
+        while let Some(chunk) = stream.message().await? {
+            if chunk.params.is_some() {
+                return Err(RequestError(
+                    Code::InvalidArgument,
+                    "only the first message may have parameters".to_string(),
+                ));
+            }
+            if witness.len() + chunk.witness.len() > self.config.max_witness_bytes {
+                return Err(RequestError(
+                    Code::ResourceExhausted,
+                    format!(
+                        "the witness is larger than {} bytes",
+                        self.config.max_witness_bytes
+                    ),
+                ));
+            }
+            witness.extend_from_slice(&chunk.witness);
+        }
+        Ok((params, witness))
+    }
+}
+
+/// Proves for `key` with the request's parameters. Runs on a blocking thread
+fn prove_blocking(
+    key: &ProvingKey<Fr>,
+    params: &proto::ProveParams,
+    witness: &[u8],
+    parallelism: usize,
+) -> Result<ProveResponse, RequestError> {
+    let start = Instant::now();
+    let witness = wtns_from_reader::<_, Fr>(witness)
+        .map_err(|e| RequestError(Code::InvalidArgument, format!("invalid witness: {e:#}")))?;
+    let mut builder = ProverBuilder::from_proving_key(witness, key)
+        .grinding_bits(params.grinding_bits as usize)
+        .context(&params.context)
+        .parallelism(parallelism);
+    if params.security_level != 0 {
+        builder = builder.security_level(params.security_level as usize);
+    }
+    let proof = builder
+        .build()
+        .and_then(|mut prover| prover.commit_and_prove())
+        .map_err(|e| RequestError(Code::InvalidArgument, e.to_string()))?;
+    Ok(ProveResponse {
+        proof: Some(proto::CommitAndProof::from(&proof)),
+        prove_millis: start.elapsed().as_millis() as u64,
+    })
+}
+
+#[tonic::async_trait]
+impl ProvingService for ProvingServer {
+    async fn prove(
+        &self,
+        mut request: Request<Streaming<ProveChunk>>,
+    ) -> Result<Response<ProveResponse>, Status> {
+        let (params, witness) = self.receive_witness(request.get_mut()).await?;
+        let key = self.proving_key(&params.circuit, &params.code_params)?;
+        let parallelism = self.config.parallelism;
+        let circuit = params.circuit.clone();
+        let permit = self
+            .proofs
+            .clone()
+            .acquire_owned()
+            .await
+            .map_err(|_| Status::unavailable("the server is shutting down"))?;
+
+        self.stats
+            .proofs_in_progress
+            .fetch_add(1, Ordering::Relaxed);
+        let result = tokio::task::spawn_blocking(move || {
+            let _permit = permit;
+            prove_blocking(&key, &params, &witness, parallelism)
+        })
+        .await
+        .unwrap_or_else(|e| Err(RequestError(Code::Internal, format!("proving failed: {e}"))));
+        self.stats
+            .proofs_in_progress
+            .fetch_sub(1, Ordering::Relaxed);
+        match &result {
+            Ok(response) => {
+                tracing::info!(circuit = %circuit, millis = response.prove_millis, "proved");
+                &self.stats.proofs_completed
+            }
+            Err(e) => {
+                tracing::warn!(circuit = %circuit, error = %e.1, "proving failed");
+                &self.stats.proofs_failed
+            }
+        }
+        .fetch_add(1, Ordering::Relaxed);
+        result.map(Response::new).map_err(Status::from)
+    }
+
+    async fn verify(
+        &self,
+        request: Request<VerifyRequest>,
+    ) -> Result<Response<VerifyResponse>, Status> {
+        let request = request.into_inner();
+        let circuit = self.circuit(&request.circuit)?;
+        let params = self.code_params(&request.code_params)?;
+        let proof = request
+            .proof
+            .ok_or_else(|| Status::invalid_argument("missing proof"))?;
+        let proof = CommitAndProof::<Fr>::try_from(proof)
+            .map_err(|e| Status::invalid_argument(format!("invalid proof: {e}")))?;
+        let min_security = request.min_security_level as usize;
+        let context = request.context;
+
+        let verified = tokio::task::spawn_blocking(move || {
+            let mut key = VerifyingKey::with_code_params((*circuit).clone(), params);
+            if min_security != 0 {
+                key = key.require_security(SecurityLevel(min_security));
+            }
+            Verifier::from_verifying_key(key).verify_with_context(&proof, &context)
+        })
+        .await
+        .map_err(|e| Status::internal(format!("verifying failed: {e}")))?;
+        self.stats.verifications.fetch_add(1, Ordering::Relaxed);
+        if let Err(e) = &verified {
+            tracing::debug!(circuit = %request.circuit, error = %e, "rejected a proof");
+        }
+        Ok(Response::new(match verified {
+            Ok(public) => VerifyResponse {
+                valid: true,
+                error: String::new(),
+                public_inputs: elements(&public.public_inputs),
+                public_outputs: elements(&public.public_outputs),
+            },
+            Err(e) => VerifyResponse {
+                valid: false,
+                error: e.to_string(),
+                ..Default::default()
+            },
+        }))
+    }
+
+    async fn status(
+        &self,
+        _request: Request<StatusRequest>,
+    ) -> Result<Response<StatusResponse>, Status> {
+        Ok(Response::new(StatusResponse {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            circuits: self
+                .circuits
+                .iter()
+                .map(|(name, circuit)| CircuitInfo {
+                    name: name.clone(),
+                    num_constraints: circuit.r1cs.num_constraints() as u64,
+                    witness_len: circuit.unpadded_wtns_len as u64,
+                })
+                .collect(),
+            code_params: self.code_params.keys().cloned().collect(),
+            max_concurrent_proofs: self.config.max_concurrent_proofs as u32,
+            proofs_in_progress: self.stats.proofs_in_progress.load(Ordering::Relaxed),
+            proofs_completed: self.stats.proofs_completed.load(Ordering::Relaxed),
+            proofs_failed: self.stats.proofs_failed.load(Ordering::Relaxed),
+            verifications: self.stats.verifications.load(Ordering::Relaxed),
+        }))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use ff::PrimeField;
+    use tokio_stream::wrappers::TcpListenerStream;
+    use tonic::{transport::Channel, Code};
+
+    use super::*;
+    use crate::{circom::witness::wtns_to_writer, proto::ProveParams, zkp, FVec};
+
+    async fn serve() -> ProvingServiceClient<Channel> {
+        let mut server = ProvingServer::new(ServerConfig::default());
+        server.add_circuit("test", zkp::test::TEST_CIRCUIT.clone());
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(
+            tonic::transport::Server::builder()
+                .add_service(server.into_service())
+                .serve_with_incoming(TcpListenerStream::new(listener)),
+        );
+        ProvingServiceClient::connect(format!("http://{addr}"))
+            .await
+            .unwrap()
+    }
+
+    fn witness_chunks(params: ProveParams) -> Vec<ProveChunk> {
+        let witness = FVec::<Fr>([5, 2, 28, 280].iter().map(|x| Fr::from_u128(*x)).collect());
+        let mut wtns = Vec::new();
+        wtns_to_writer(&witness, &mut wtns).unwrap();
+        let mut chunks = wtns
+            .chunks(16)
+            .map(|chunk| ProveChunk {
+                params: None,
+                witness: chunk.to_vec(),
+            })
+            .collect::<Vec<_>>();
+        chunks[0].params = Some(params);
+        chunks
+    }
+
+    #[tokio::test]
+    async fn prove_and_verify() {
+        let mut client = serve().await;
+        let params = ProveParams {
+            circuit: "test".to_string(),
+            context: b"session".to_vec(),
+            ..Default::default()
+        };
+        let proof = client
+            .prove(tokio_stream::iter(witness_chunks(params)))
+            .await
+            .unwrap()
+            .into_inner()
+            .proof;
+
+        let mut request = VerifyRequest {
+            circuit: "test".to_string(),
+            proof,
+            context: b"session".to_vec(),
+            ..Default::default()
+        };
+        let verified = client.verify(request.clone()).await.unwrap().into_inner();
+        assert!(verified.valid, "{}", verified.error);
+        assert_eq!(verified.public_outputs, elements(&[Fr::from_u128(280)]));
+
+        request.context = b"another session".to_vec();
+        assert!(!client.verify(request).await.unwrap().into_inner().valid);
+
+        let status = client.status(StatusRequest {}).await.unwrap().into_inner();
+        assert_eq!(status.circuits[0].witness_len, 4);
+        assert_eq!(status.proofs_completed, 1);
+        assert_eq!(status.verifications, 2);
+    }
+
+    #[tokio::test]
+    async fn rejects_bad_requests() {
+        let mut client = serve().await;
+        let unknown = ProveParams {
+            circuit: "unknown".to_string(),
+            ..Default::default()
+        };
+        let err = client
+            .prove(tokio_stream::iter(witness_chunks(unknown)))
+            .await
+            .unwrap_err();
+        assert_eq!(err.code(), Code::NotFound);
+
+        let mut chunks = witness_chunks(ProveParams {
+            circuit: "test".to_string(),
+            ..Default::default()
+        });
+        chunks[0].params = None;
+        let err = client.prove(tokio_stream::iter(chunks)).await.unwrap_err();
+        assert_eq!(err.code(), Code::InvalidArgument);
+    }
+}
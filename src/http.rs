@@ -0,0 +1,346 @@
+//! An HTTP proving service for deployments that don't need gRPC's streaming or loaded circuits, served by `r1cs_tool serve`.
+//!
+//! Both endpoints take multipart forms with the circuit's .r1cs file as the `r1cs` field, and optionally code parameters from
+//! `r1cs_tool gen-params` as `params`:
+//! - `POST /prove` also takes the .wtns file as `wtns`, and responds with the proof's canonical encoding, as `CommitAndProof::to_bytes` writes it.
+//! - `POST /verify` also takes that encoding as `proof`, and responds with JSON: `{"valid": true, "public_inputs": [...], "public_outputs": [...]}`
+//!   with the public values as decimal strings, or `{"valid": false, "error": "..."}`. A proof that doesn't verify is not an HTTP error.
+//!
+//! Malformed requests get 400, bodies over `HttpConfig::max_body_bytes` get 413, and requests that take longer than
+//! `HttpConfig::timeout`, uploading included, get 408. Parsing, proving, and verifying run on blocking threads, at most
+//! `HttpConfig::max_concurrent_proofs` proofs and `HttpConfig::max_concurrent_verifications` verifications at once.
+use std::{io::Cursor, sync::Arc, time::Duration};
+
+use axum::{
+    extract::{multipart::MultipartError, DefaultBodyLimit, Multipart, State},
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+    routing::post,
+    Json, Router,
+};
+use serde::Serialize;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+use crate::{
+    actors::actors::{CommitAndProof, Prover, Verifier, VerifyingKey},
+    circom::{r1cs::R1CSFile, to_decimal, witness::wtns_from_reader},
+    subspacevole::CodeParams,
+    zkp::R1CSWithMetadata,
+    Fr,
+};
+
+#[derive(Clone, Debug)]
+pub struct HttpConfig {
+    /// Largest request body, with the circuit, witness, and proof together
+    pub max_body_bytes: usize,
+    /// Longest a request may take, from the start of its upload until it is proven or verified
+    pub timeout: Duration,
+    /// Proofs to make at once
+    pub max_concurrent_proofs: usize,
+    /// Proofs to verify at once, separately from `max_concurrent_proofs` so verifying isn't stuck behind slow proofs
+    pub max_concurrent_verifications: usize,
+    /// Threads each proof expands its VOLEs on, see `ProverBuilder::parallelism`
+    pub parallelism: usize,
+}
+
+impl Default for HttpConfig {
+    fn default() -> Self {
+        Self {
+            max_body_bytes: 256 << 20,
+            timeout: Duration::from_secs(300),
+            max_concurrent_proofs: 1,
+            max_concurrent_verifications: 4,
+            parallelism: 1,
+        }
+    }
+}
+
+struct AppState {
+    config: HttpConfig,
+    /// Held by each proof until it finishes, even if its request has timed out
+    proofs: Arc<Semaphore>,
+    /// Likewise held by each verification
+    verifications: Arc<Semaphore>,
+}
+
+/// A failed request's status and the reason, sent as plain text
+struct HttpError(StatusCode, String);
+
+impl HttpError {
+    fn bad_request(message: impl Into<String>) -> Self {
+        Self(StatusCode::BAD_REQUEST, message.into())
+    }
+}
+
+impl From<MultipartError> for HttpError {
+    fn from(e: MultipartError) -> Self {
+        Self(e.status(), e.body_text())
+    }
+}
+
+impl IntoResponse for HttpError {
+    fn into_response(self) -> Response {
+        tracing::debug!(status = %self.0, "{}", self.1);
+        (self.0, self.1).into_response()
+    }
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct VerifyResponse {
+    pub valid: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    pub public_inputs: Vec<String>,
+    pub public_outputs: Vec<String>,
+}
+
+/// The service's routes, to serve with `axum::serve` or nest in an application's own router
+pub fn router(config: HttpConfig) -> Router {
+    let state = Arc::new(AppState {
+        proofs: Arc::new(Semaphore::new(config.max_concurrent_proofs)),
+        verifications: Arc::new(Semaphore::new(config.max_concurrent_verifications)),
+        config,
+    });
+    Router::new()
+        .route("/prove", post(prove))
+        .route("/verify", post(verify))
+        .layer(DefaultBodyLimit::max(state.config.max_body_bytes))
+        .with_state(state)
+}
+
+/// The form's fields, by name. Fields other than `names` are rejected
+async fn read_fields<const N: usize>(
+    mut form: Multipart,
+    names: [&str; N],
+) -> Result<[Option<Vec<u8>>; N], HttpError> {
+    let mut fields = [(); N].map(|_| None);
+    while let Some(field) = form.next_field().await? {
+        let name = field.name().unwrap_or_default().to_string();
+        let Some(i) = names.iter().position(|n| *n == name) else {
+            return Err(HttpError::bad_request(format!("unexpected field {name:?}")));
+        };
+        fields[i] = Some(field.bytes().await?.to_vec());
+    }
+    Ok(fields)
+}
+
+fn required(field: Option<Vec<u8>>, name: &str) -> Result<Vec<u8>, HttpError> {
+    field.ok_or_else(|| HttpError::bad_request(format!("missing field {name:?}")))
+}
+
+fn parse_circuit(
+    r1cs: Vec<u8>,
+    params: Option<Vec<u8>>,
+) -> Result<(R1CSWithMetadata<Fr>, CodeParams), HttpError> {
+    let circuit = R1CSFile::<Fr>::from_reader(Cursor::new(r1cs))
+        .map_err(|e| HttpError::bad_request(format!("invalid R1CS file: {e:#}")))?
+        .to_crate_format()
+        .map_err(|e| HttpError::bad_request(format!("unsupported R1CS file: {e}")))?;
+    let params = match params {
+        Some(bytes) => CodeParams::from_bytes(&bytes)
+            .map_err(|e| HttpError::bad_request(format!("invalid code parameters: {e}")))?,
+        None => CodeParams::default(),
+    };
+    Ok((circuit, params))
+}
+
+/// Fails with 408 if `work`, i.e. reading the upload and proving or verifying it, takes longer than the timeout
+async fn with_timeout<R>(
+    state: &AppState,
+    work: impl std::future::Future<Output = Result<R, HttpError>>,
+) -> Result<R, HttpError> {
+    tokio::time::timeout(state.config.timeout, work)
+        .await
+        .map_err(|_| {
+            HttpError(
+                StatusCode::REQUEST_TIMEOUT,
+                format!("the request took longer than {:?}", state.config.timeout),
+            )
+        })?
+}
+
+/// Waits for one of `semaphore`'s permits, to hold until the request's blocking work finishes
+async fn acquire(semaphore: &Arc<Semaphore>) -> Result<OwnedSemaphorePermit, HttpError> {
+    semaphore
+        .clone()
+        .acquire_owned()
+        .await
+        .map_err(|_| HttpError(StatusCode::SERVICE_UNAVAILABLE, "shutting down".into()))
+}
+
+/// Runs `work` on a blocking thread, as parsing, proving, and verifying are too slow for the async runtime
+async fn run_blocking<R: Send + 'static>(
+    what: &str,
+    work: impl FnOnce() -> Result<R, HttpError> + Send + 'static,
+) -> Result<R, HttpError> {
+    tokio::task::spawn_blocking(work).await.unwrap_or_else(|e| {
+        Err(HttpError(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("{what} failed: {e}"),
+        ))
+    })
+}
+
+async fn prove(State(state): State<Arc<AppState>>, form: Multipart) -> Result<Response, HttpError> {
+    let proof = with_timeout(&state, async {
+        let [r1cs, wtns, params] = read_fields(form, ["r1cs", "wtns", "params"]).await?;
+        let r1cs = required(r1cs, "r1cs")?;
+        let wtns = required(wtns, "wtns")?;
+        let permit = acquire(&state.proofs).await?;
+        let parallelism = state.config.parallelism;
+        run_blocking("proving", move || {
+            let _permit = permit;
+            let (circuit, params) = parse_circuit(r1cs, params)?;
+            let witness = wtns_from_reader::<_, Fr>(wtns.as_slice())
+                .map_err(|e| HttpError::bad_request(format!("invalid witness: {e:#}")))?;
+            Prover::builder(witness, circuit)
+                .code_params(params)
+                .parallelism(parallelism)
+                .build()
+                .and_then(|mut prover| prover.commit_and_prove())
+                .map_err(|e| HttpError::bad_request(e.to_string()))?
+                .to_bytes()
+                .map_err(|e| HttpError(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))
+        })
+        .await
+    })
+    .await?;
+    tracing::info!(bytes = proof.len(), "proved");
+    Ok(([(header::CONTENT_TYPE, "application/octet-stream")], proof).into_response())
+}
+
+async fn verify(
+    State(state): State<Arc<AppState>>,
+    form: Multipart,
+) -> Result<Json<VerifyResponse>, HttpError> {
+    let verified = with_timeout(&state, async {
+        let [r1cs, proof, params] = read_fields(form, ["r1cs", "proof", "params"]).await?;
+        let r1cs = required(r1cs, "r1cs")?;
+        let proof = required(proof, "proof")?;
+        let permit = acquire(&state.verifications).await?;
+        run_blocking("verifying", move || {
+            let _permit = permit;
+            let (circuit, params) = parse_circuit(r1cs, params)?;
+            let proof = CommitAndProof::<Fr>::from_bytes(&proof)
+                .map_err(|e| HttpError::bad_request(format!("invalid proof: {e}")))?;
+            Ok(
+                Verifier::from_verifying_key(VerifyingKey::with_code_params(circuit, params))
+                    .verify(&proof),
+            )
+        })
+        .await
+    })
+    .await?;
+    Ok(Json(match verified {
+        Ok(public) => VerifyResponse {
+            valid: true,
+            error: None,
+            public_inputs: public.public_inputs.iter().map(to_decimal).collect(),
+            public_outputs: public.public_outputs.iter().map(to_decimal).collect(),
+        },
+        Err(e) => VerifyResponse {
+            error: Some(e.to_string()),
+            ..Default::default()
+        },
+    }))
+}
+
+#[cfg(test)]
+mod test {
+    use axum::body::{to_bytes, Body};
+    use axum::http::Request;
+    use tower::ServiceExt;
+
+    use super::*;
+    use crate::{circom::witness::wtns_to_writer, gadgets::CircuitBuilder};
+
+    const BOUNDARY: &str = "volonym-test-boundary";
+
+    fn form_request(uri: &str, fields: &[(&str, &[u8])]) -> Request<Body> {
+        let mut body = Vec::new();
+        for (name, bytes) in fields {
+            body.extend_from_slice(
+                format!(
+                    "--{BOUNDARY}\r\nContent-Disposition: form-data; name=\"{name}\"; filename=\"{name}\"\r\n\
+                     Content-Type: application/octet-stream\r\n\r\n"
+                )
+                .as_bytes(),
+            );
+            body.extend_from_slice(bytes);
+            body.extend_from_slice(b"\r\n");
+        }
+        body.extend_from_slice(format!("--{BOUNDARY}--\r\n").as_bytes());
+        Request::post(uri)
+            .header(
+                header::CONTENT_TYPE,
+                format!("multipart/form-data; boundary={BOUNDARY}"),
+            )
+            .body(Body::from(body))
+            .unwrap()
+    }
+
+    /// x * x = y, with x = 7 public, as .r1cs and .wtns files
+    fn circuit_and_witness() -> (Vec<u8>, Vec<u8>) {
+        let mut builder = CircuitBuilder::<Fr>::new();
+        let x = builder.alloc(Fr::from(7));
+        builder.public_output(x);
+        builder.mul(x, x);
+        let (circuit, witness) = builder.build();
+        let mut r1cs = Vec::new();
+        R1CSFile::from_crate_format(&circuit)
+            .unwrap()
+            .to_writer(&mut r1cs)
+            .unwrap();
+        let mut wtns = Vec::new();
+        wtns_to_writer(&witness, &mut wtns).unwrap();
+        (r1cs, wtns)
+    }
+
+    #[tokio::test]
+    async fn prove_and_verify() {
+        let app = router(HttpConfig::default());
+        let (r1cs, wtns) = circuit_and_witness();
+        let response = app
+            .clone()
+            .oneshot(form_request("/prove", &[("r1cs", &r1cs), ("wtns", &wtns)]))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let proof = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+
+        let response = app
+            .oneshot(form_request(
+                "/verify",
+                &[("r1cs", &r1cs), ("proof", &proof)],
+            ))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let verified: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(verified["valid"], true);
+        assert_eq!(verified["public_outputs"], serde_json::json!(["7"]));
+    }
+
+    #[tokio::test]
+    async fn rejects_bad_requests() {
+        let (r1cs, wtns) = circuit_and_witness();
+        let app = router(HttpConfig::default());
+        let response = app
+            .clone()
+            .oneshot(form_request("/prove", &[("r1cs", &r1cs)]))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let app = router(HttpConfig {
+            max_body_bytes: 64,
+            ..Default::default()
+        });
+        let response = app
+            .oneshot(form_request("/prove", &[("r1cs", &r1cs), ("wtns", &wtns)]))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+    }
+}
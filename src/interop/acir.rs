@@ -0,0 +1,174 @@
+//! Lowers ACIR, the circuit format Noir compiles to, into R1CS. The types mirror those of the `acir` crate with only the opcodes
+//! that constrain the witness and can be expressed here: arithmetic gates and range constraints. Brillig calls and other unconstrained
+//! opcodes only solve for witness values, so they can be left out once `nargo execute` has solved the witness.
+//!
+//! As with `gadgets::CircuitBuilder`, lowering a program computes its witness too, and the verifier lowers it with no witness values
+use anyhow::{bail, Error};
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::{
+    gadgets::{CircuitBuilder, Wire},
+    zkp::R1CSWithMetadata,
+    FVec, PF,
+};
+
+/// Index of a witness in an ACIR program. It is wire `index + 1` of the lowered circuit, after the constant one
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Witness(pub u32);
+
+/// The polynomial Σ q * a * b + Σ q * w + `q_c` of witnesses
+#[derive(Clone, Debug)]
+pub struct Expression<T: PF> {
+    pub mul_terms: Vec<(T, Witness, Witness)>,
+    pub linear_combinations: Vec<(T, Witness)>,
+    pub q_c: T,
+}
+
+#[derive(Clone, Debug)]
+pub enum Opcode<T: PF> {
+    /// Constrains the expression to be zero
+    AssertZero(Expression<T>),
+    /// The RANGE black box function, constraining `input` to be less than 2^`num_bits`
+    Range { input: Witness, num_bits: u32 },
+}
+
+/// An ACIR circuit. Witnesses in neither set of public parameters are private
+#[derive(Clone, Debug)]
+pub struct Circuit<T: PF> {
+    /// The highest witness index the program uses
+    pub current_witness_index: u32,
+    pub opcodes: Vec<Opcode<T>>,
+    pub public_parameters: BTreeSet<Witness>,
+    pub return_values: BTreeSet<Witness>,
+}
+
+/// Lowers `program` to R1CS along with its witness. `witness` is the program's solved witness, e.g. from `nargo execute`;
+/// witnesses missing from it are zero, so the verifier can pass an empty map. Public parameters are public inputs and return values public outputs
+pub fn lower<T: PF>(
+    program: &Circuit<T>,
+    witness: &BTreeMap<Witness, T>,
+) -> Result<(R1CSWithMetadata<T>, FVec<T>), Error> {
+    let mut builder = CircuitBuilder::new();
+    for index in 0..=program.current_witness_index {
+        let w = Witness(index);
+        let value = witness.get(&w).copied().unwrap_or(T::ZERO);
+        let wire = if program.public_parameters.contains(&w) {
+            builder.public_input(value)
+        } else {
+            builder.alloc(value)
+        };
+        if program.return_values.contains(&w) {
+            builder.public_output(wire);
+        }
+    }
+    let wire = |w: Witness| {
+        if w.0 > program.current_witness_index {
+            bail!(
+                "witness {} is above the current witness index {}",
+                w.0,
+                program.current_witness_index
+            );
+        }
+        Ok(Wire(w.0 as usize + 1))
+    };
+
+    for opcode in &program.opcodes {
+        match opcode {
+            Opcode::AssertZero(expr) => {
+                // The first product is the constraint's multiplication. Any others are computed in wires of their own and added linearly
+                let mut linear = expr
+                    .linear_combinations
+                    .iter()
+                    .map(|(q, w)| Ok((wire(*w)?, *q)))
+                    .collect::<Result<Vec<_>, Error>>()?;
+                linear.push((builder.one(), expr.q_c));
+                let mut products = expr.mul_terms.iter();
+                let (a, b) = match products.next() {
+                    Some((q, x, y)) => (vec![(wire(*x)?, *q)], vec![(wire(*y)?, T::ONE)]),
+                    None => (vec![], vec![(builder.one(), T::ONE)]),
+                };
+                for (q, x, y) in products {
+                    let product = builder.mul(wire(*x)?, wire(*y)?);
+                    linear.push((product, *q));
+                }
+                if a.is_empty() {
+                    // linear * 1 = 0
+                    builder.enforce(&linear, &b, &[]);
+                } else {
+                    let minus_linear = linear.into_iter().map(|(w, q)| (w, -q)).collect::<Vec<_>>();
+                    builder.enforce(&a, &b, &minus_linear);
+                }
+            }
+            Opcode::Range { input, num_bits } => {
+                // Every element is less than 2^NUM_BITS, so wider ranges constrain nothing
+                if *num_bits < T::NUM_BITS {
+                    builder.range_check(wire(*input)?, *num_bits as usize);
+                }
+            }
+        }
+    }
+    Ok(builder.build())
+}
+
+#[cfg(test)]
+mod test {
+    use super::{lower, Circuit, Expression, Opcode, Witness};
+    use crate::Fr;
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn lower_acir() {
+        // w0 * w1 + 2 * w0 * w2 - w3 + 1 = 0, with w0 < 2^8, w3 public, and w2 returned
+        let program = Circuit {
+            current_witness_index: 3,
+            opcodes: vec![
+                Opcode::AssertZero(Expression {
+                    mul_terms: vec![
+                        (Fr::from(1), Witness(0), Witness(1)),
+                        (Fr::from(2), Witness(0), Witness(2)),
+                    ],
+                    linear_combinations: vec![(-Fr::from(1), Witness(3))],
+                    q_c: Fr::from(1),
+                }),
+                Opcode::Range {
+                    input: Witness(0),
+                    num_bits: 8,
+                },
+                Opcode::Range {
+                    input: Witness(1),
+                    num_bits: 254,
+                },
+            ],
+            public_parameters: [Witness(3)].into(),
+            return_values: [Witness(2)].into(),
+        };
+        let witness = |w0: u64| {
+            let w1 = 5;
+            let w2 = 7;
+            BTreeMap::from([
+                (Witness(0), Fr::from(w0)),
+                (Witness(1), Fr::from(w1)),
+                (Witness(2), Fr::from(w2)),
+                (Witness(3), Fr::from(w0 * w1 + 2 * w0 * w2 + 1)),
+            ])
+        };
+
+        let (circuit, values) = lower(&program, &witness(3)).unwrap();
+        assert_eq!(circuit.public_inputs_indices, vec![4]);
+        assert_eq!(circuit.public_outputs_indices, vec![3]);
+        assert!(circuit.check_witness(&values).is_ok());
+        // The verifier lowers the same circuit without the witness
+        let (verifier_circuit, _) = lower(&program, &BTreeMap::new()).unwrap();
+        assert_eq!(verifier_circuit.circuit_id(), circuit.circuit_id());
+
+        let (circuit, values) = lower(&program, &witness(300)).unwrap();
+        assert!(circuit.check_witness(&values).is_err());
+
+        let mut out_of_bounds = program.clone();
+        out_of_bounds.opcodes.push(Opcode::Range {
+            input: Witness(4),
+            num_bits: 8,
+        });
+        assert!(lower(&out_of_bounds, &BTreeMap::new()).is_err());
+    }
+}
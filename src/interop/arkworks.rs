@@ -0,0 +1,119 @@
+//! Converts arkworks constraint systems over BN254's scalar field, so circuits written with arkworks gadgets can be proven directly.
+//! arkworks orders the witness as the constant one, the other public inputs, then the private witness, which is kept here
+use anyhow::{bail, Error};
+use ark_ff::{BigInteger, PrimeField as _};
+use ark_relations::r1cs::{ConstraintSystemRef, Matrix};
+use ff::PrimeField;
+use std::collections::BTreeMap;
+
+use crate::{
+    zkp::{R1CSWithMetadata, SparseR1CS, R1CS},
+    FVec, Fr, FrRepr, SparseFMatrix, SparseVec,
+};
+
+/// Converts a constraint system after synthesis. Its symbolic linear combinations are inlined first, so `cs` must not be added to afterwards.
+/// Its public inputs are the instance variables other than the constant one; arkworks doesn't distinguish outputs from inputs
+pub fn r1cs_from_constraint_system(
+    cs: &ConstraintSystemRef<ark_bn254::Fr>,
+) -> Result<R1CSWithMetadata<Fr>, Error> {
+    cs.finalize();
+    let Some(matrices) = cs.to_matrices() else {
+        bail!("the constraint system was synthesized without constructing its matrices");
+    };
+    let num_instance = matrices.num_instance_variables;
+    Ok(R1CSWithMetadata {
+        r1cs: R1CS::Sparse(SparseR1CS {
+            a_rows: to_sparse_matrix(&matrices.a),
+            b_rows: to_sparse_matrix(&matrices.b),
+            c_rows: to_sparse_matrix(&matrices.c),
+        }),
+        public_inputs_indices: (1..num_instance).collect(),
+        public_outputs_indices: vec![],
+        unpadded_wtns_len: num_instance + matrices.num_witness_variables,
+        custom_gates: vec![],
+        lookups: vec![],
+        memories: vec![],
+        branches: vec![],
+        ccs: vec![],
+        signal_names: BTreeMap::new(),
+    })
+}
+
+/// The witness of a constraint system synthesized in proving mode, in the order `r1cs_from_constraint_system` expects
+pub fn witness_from_constraint_system(
+    cs: &ConstraintSystemRef<ark_bn254::Fr>,
+) -> Result<FVec<Fr>, Error> {
+    let Some(cs) = cs.borrow() else {
+        bail!("the constraint system is empty");
+    };
+    if cs.is_in_setup_mode() {
+        bail!("the constraint system was synthesized in setup mode, which has no witness");
+    }
+    Ok(FVec(
+        cs.instance_assignment
+            .iter()
+            .chain(&cs.witness_assignment)
+            .map(|x| from_ark(*x))
+            .collect(),
+    ))
+}
+
+fn to_sparse_matrix(matrix: &Matrix<ark_bn254::Fr>) -> SparseFMatrix<Fr> {
+    SparseFMatrix(
+        matrix
+            .iter()
+            .map(|row| SparseVec(row.iter().map(|(x, i)| (*i, from_ark(*x))).collect()))
+            .collect(),
+    )
+}
+
+fn from_ark(x: ark_bn254::Fr) -> Fr {
+    let bytes: [u8; 32] = x.into_bigint().to_bytes_be().try_into().unwrap();
+    // Both are BN254's scalar field, so any arkworks element is valid here
+    Fr::from_repr(FrRepr(bytes)).unwrap()
+}
+
+#[cfg(test)]
+mod test {
+    use super::{r1cs_from_constraint_system, witness_from_constraint_system};
+    use crate::Fr;
+    use ark_relations::{
+        lc,
+        r1cs::{ConstraintSystem, SynthesisMode, Variable},
+    };
+
+    #[test]
+    fn convert_constraint_system() {
+        // out = a * b + a, with out public
+        let cs = ConstraintSystem::<ark_bn254::Fr>::new_ref();
+        let out = cs
+            .new_input_variable(|| Ok(ark_bn254::Fr::from(18u64)))
+            .unwrap();
+        let a = cs
+            .new_witness_variable(|| Ok(ark_bn254::Fr::from(3u64)))
+            .unwrap();
+        let b = cs
+            .new_witness_variable(|| Ok(ark_bn254::Fr::from(5u64)))
+            .unwrap();
+        let ab = cs.new_lc(lc!() + a).unwrap();
+        cs.enforce_constraint(lc!() + ab, lc!() + b + Variable::One, lc!() + out)
+            .unwrap();
+        assert!(cs.is_satisfied().unwrap());
+
+        let circuit = r1cs_from_constraint_system(&cs).unwrap();
+        assert_eq!(circuit.public_inputs_indices, vec![1]);
+        assert_eq!(circuit.unpadded_wtns_len, 4);
+        let mut witness = witness_from_constraint_system(&cs).unwrap();
+        assert_eq!(
+            witness.0,
+            vec![Fr::from(1), Fr::from(18), Fr::from(3), Fr::from(5)]
+        );
+        assert!(circuit.check_witness(&witness).is_ok());
+        witness.0[1] = Fr::from(15);
+        assert!(circuit.check_witness(&witness).is_err());
+
+        let setup = ConstraintSystem::<ark_bn254::Fr>::new_ref();
+        setup.set_mode(SynthesisMode::Setup);
+        assert!(witness_from_constraint_system(&setup).is_err());
+    }
+}
@@ -0,0 +1,5 @@
+//! Converting circuits and witnesses from other proving systems' frontends to this crate's format
+
+pub mod acir;
+#[cfg(feature = "arkworks")]
+pub mod arkworks;
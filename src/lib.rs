@@ -1,35 +1,114 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
+/// What the standard prelude brings into scope that the modules building without the "std" feature use
+#[cfg(not(feature = "std"))]
+mod alloc_prelude {
+    pub use alloc::{
+        format,
+        string::{String, ToString},
+        vec,
+        vec::Vec,
+    };
+}
+#[cfg(not(feature = "std"))]
+use alloc_prelude::*;
+
+/// Enters a `tracing` span until the returned guard is dropped. Does nothing without the "tracing" feature
+#[cfg(feature = "tracing")]
+macro_rules! span {
+    ($name:literal) => {
+        tracing::info_span!($name).entered()
+    };
+}
+#[cfg(all(feature = "std", not(feature = "tracing")))]
+macro_rules! span {
+    ($name:literal) => {
+        ()
+    };
+}
+
+#[cfg(feature = "std")]
 pub mod actors;
+#[cfg(feature = "std")]
+#[doc(hidden)]
+pub mod adversary;
 pub mod challenges;
+#[cfg(feature = "std")]
 pub mod circom;
+#[cfg(feature = "cli")]
 pub mod codeparams;
+pub mod error;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+pub mod fields;
+#[cfg(feature = "std")]
 pub mod format;
+#[cfg(feature = "std")]
+pub mod gadgets;
+#[cfg(feature = "gpu")]
+pub mod gpu;
+#[cfg(feature = "grpc")]
+pub mod grpc;
+#[cfg(feature = "http")]
+pub mod http;
+#[cfg(feature = "std")]
+pub mod interop;
+#[cfg(feature = "mem-stats")]
+pub mod memstats;
+#[cfg(feature = "net")]
+pub mod net;
+#[cfg(feature = "proto")]
+pub mod proto;
+#[cfg(feature = "std")]
+pub mod session;
+#[cfg(feature = "std")]
 pub mod smallvole;
 pub mod subspacevole;
+#[cfg(feature = "std")]
+pub mod testvectors;
 pub mod utils;
 pub mod vecccom;
 pub mod vith;
+#[cfg(feature = "wasm")]
+pub mod wasm;
 pub mod zkp;
 
-use std::{
+use alloc::borrow::Cow;
+use core::{
+    cmp::Ordering,
     fmt::{self, Display},
     mem,
-    ops::{Add, Mul, Neg, Sub, SubAssign},
+    ops::{Add, Mul, Neg, Range, Sub, SubAssign},
 };
 
 pub trait DataSize {
     fn size_in_bytes(&self) -> usize;
+    /// Size once compressed, for types with a compressed encoding. Otherwise the same as `size_in_bytes`
+    fn compressed_size_in_bytes(&self) -> usize {
+        self.size_in_bytes()
+    }
 }
 
 use num_bigint::{BigInt, BigUint, Sign};
+#[cfg(feature = "std")]
 use rand::rngs::ThreadRng;
+use rand::{CryptoRng, RngCore, SeedableRng};
+use rand_chacha::ChaCha20Rng;
 use serde::{Deserialize, Serialize};
 
 #[macro_use]
 extern crate ff;
 use crate::ff::PrimeField;
+use crate::fields::ext::{coordinates, from_coordinates, ChallengeField, ExtensionField};
 
 /// Important that it is the block size of the linear code
 const NUM_VOLES: u32 = 1024;
+/// Side of the square blocks `FMatrix::transpose` copies at a time
+const TRANSPOSE_BLOCK: usize = 32;
+/// Below this many elements `FMatrix::transpose` doesn't spawn threads
+const PARALLEL_TRANSPOSE_THRESHOLD: usize = 1 << 16;
 
 #[derive(PrimeField)]
 #[PrimeFieldModulus = "21888242871839275222246405745257275088548364400416034343698204186575808495617"]
@@ -40,10 +119,29 @@ pub struct Fr([u64; 4]);
 
 impl Display for Fr {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{}", self.norm())
+        let (sign, magnitude) = self.norm_limbs();
+        if sign == Sign::Minus {
+            f.write_str("-")?;
+        }
+        f.write_str(&limbs_to_decimal(magnitude))
     }
 }
 
+/// Fr's modulus as little endian 64 bit limbs
+const PRIME_LIMBS: [u64; 4] = [
+    0x43e1f593f0000001,
+    0x2833e84879b97091,
+    0xb85045b68181585d,
+    0x30644e72e131a029,
+];
+/// Fr's modulus divided by two, rounded down, as little endian 64 bit limbs
+const HALF_PRIME_LIMBS: [u64; 4] = [
+    0xa1f0fac9f8000000,
+    0x9419f4243cdcb848,
+    0xdc2822db40c0ac2e,
+    0x183227397098d014,
+];
+
 impl Fr {
     pub fn prime() -> BigUint {
         let p = Fr::MODULUS;
@@ -55,19 +153,65 @@ impl Fr {
     }
 
     pub fn norm(&self) -> BigInt {
-        let self_bu = BigUint::from_bytes_be(&self.to_repr().0);
-        if self_bu > Self::half_prime() {
-            BigInt::from_biguint(Sign::Plus, self_bu)
-                - BigInt::from_biguint(Sign::Plus, Self::prime())
+        let (sign, magnitude) = self.norm_limbs();
+        let bytes = magnitude
+            .iter()
+            .rev()
+            .flat_map(|limb| limb.to_be_bytes())
+            .collect::<Vec<u8>>();
+        BigInt::from_bytes_be(sign, &bytes)
+    }
+
+    /// The sign and little endian limbs of the magnitude of `norm`, without allocating
+    pub fn norm_limbs(&self) -> (Sign, [u64; 4]) {
+        let repr = self.to_repr().0;
+        let mut limbs = [0u64; 4];
+        for (limb, bytes) in limbs.iter_mut().zip(repr.rchunks_exact(8)) {
+            *limb = u64::from_be_bytes(bytes.try_into().unwrap());
+        }
+        if limbs == [0; 4] {
+            (Sign::NoSign, limbs)
+        } else if limbs.iter().rev().cmp(HALF_PRIME_LIMBS.iter().rev()) == Ordering::Greater {
+            let mut borrow = false;
+            for (limb, p) in limbs.iter_mut().zip(PRIME_LIMBS) {
+                let (diff, b1) = p.overflowing_sub(*limb);
+                let (diff, b2) = diff.overflowing_sub(borrow as u64);
+                *limb = diff;
+                borrow = b1 || b2;
+            }
+            (Sign::Minus, limbs)
         } else {
-            BigInt::from_biguint(Sign::Plus, self_bu)
+            (Sign::Plus, limbs)
+        }
+    }
+}
+
+/// Decimal digits of a little endian number, peeling off 19 digits at a time
+fn limbs_to_decimal(mut limbs: [u64; 4]) -> String {
+    const CHUNK: u64 = 10_000_000_000_000_000_000;
+    let mut chunks = Vec::with_capacity(5);
+    loop {
+        let mut remainder = 0u128;
+        for limb in limbs.iter_mut().rev() {
+            let acc = (remainder << 64) | *limb as u128;
+            *limb = (acc / CHUNK as u128) as u64;
+            remainder = acc % CHUNK as u128;
+        }
+        chunks.push(remainder as u64);
+        if limbs == [0; 4] {
+            break;
         }
     }
+    let mut out = chunks.pop().unwrap().to_string();
+    for chunk in chunks.iter().rev() {
+        out.push_str(&format!("{chunk:019}"));
+    }
+    out
 }
 
 /// Alias for types suitable for the prime field element
-pub trait PF: PrimeField + Add + Sub + Mul + FromU8s + ToU8s {}
-impl<T: PrimeField + Add + Sub + Mul + FromU8s + ToU8s> PF for T {}
+pub trait PF: PrimeField + Add + Sub + Mul + FromU8s + ToU8s + ChallengeField {}
+impl<T: PrimeField + Add + Sub + Mul + FromU8s + ToU8s + ChallengeField> PF for T {}
 
 /// A vector of field elements
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -101,6 +245,9 @@ impl ToU8s for Fr {
         self.to_repr().0.try_into().unwrap()
     }
 }
+impl ChallengeField for Fr {
+    type Ext = Fr;
+}
 
 /// Data size
 impl<T: PF> DataSize for FVec<T> {
@@ -111,7 +258,7 @@ impl<T: PF> DataSize for FVec<T> {
 
 /// Pretty display
 impl Display for FVec<Fr> {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(
             f,
             "[ {} ]",
@@ -131,9 +278,23 @@ impl<T: PF> DataSize for FMatrix<T> {
     }
 }
 
+/// Data size
+impl<T: PF> DataSize for SparseVec<T> {
+    fn size_in_bytes(&self) -> usize {
+        self.0.len() * mem::size_of::<(usize, T)>()
+    }
+}
+
+/// Data size
+impl<T: PF> DataSize for SparseFMatrix<T> {
+    fn size_in_bytes(&self) -> usize {
+        self.0.iter().map(|row| row.size_in_bytes()).sum()
+    }
+}
+
 /// Pretty display
 impl Display for FMatrix<Fr> {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         let s = self
             .0
             .iter()
@@ -272,6 +433,7 @@ impl<T: PF> PartialEq for FVec<T> {
 }
 
 impl<T: PF> FVec<T> {
+    #[cfg(feature = "std")]
     fn scalar_mul(&self, rhs: T) -> Self {
         Self(self.0.iter().map(|a| *a * rhs).collect())
     }
@@ -279,36 +441,164 @@ impl<T: PF> FVec<T> {
     pub fn zero_pad(&mut self, len: usize) {
         self.0.append(&mut vec![T::ZERO; len]);
     }
+    #[cfg(feature = "std")]
+    #[deprecated(note = "use `random_with_rng` with an explicit RNG")]
     pub fn random(len: usize) -> Self {
-        let mut r = &mut ThreadRng::default();
-        Self((0..len).map(|_| T::random(&mut r)).collect())
+        Self::random_with_rng(len, &mut ThreadRng::default())
+    }
+    /// `len` uniformly random elements from `rng`
+    pub fn random_with_rng(len: usize, rng: &mut (impl RngCore + CryptoRng)) -> Self {
+        Self((0..len).map(|_| T::random(&mut *rng)).collect())
+    }
+    /// Like `random_with_rng` for RNGs that can fail, e.g. hardware RNGs. A seed is drawn from `rng` and expanded with ChaCha20
+    pub fn try_random_with_rng(
+        len: usize,
+        rng: &mut (impl RngCore + CryptoRng),
+    ) -> Result<Self, rand::Error> {
+        let mut seed = [0u8; 32];
+        rng.try_fill_bytes(&mut seed)?;
+        Ok(Self::random_with_rng(
+            len,
+            &mut ChaCha20Rng::from_seed(seed),
+        ))
+    }
+    pub fn is_zero(&self) -> bool {
+        self.0.iter().all(|x| x.is_zero().into())
+    }
+    /// self += `a` * `x` without allocating. Panics unless `x` is as long as `self`
+    pub fn axpy_in_place(&mut self, a: T, x: &Self) {
+        assert_eq!(self.0.len(), x.0.len(), "vectors must have the same length");
+        self.0
+            .iter_mut()
+            .zip(x.0.iter())
+            .for_each(|(s, x)| *s += a * *x);
+    }
+    /// self = self * `a` + `y` without allocating. Panics unless `y` is as long as `self`
+    pub fn mul_add_assign(&mut self, a: T, y: &Self) {
+        assert_eq!(self.0.len(), y.0.len(), "vectors must have the same length");
+        self.0
+            .iter_mut()
+            .zip(y.0.iter())
+            .for_each(|(s, y)| *s = *s * a + *y);
+    }
+    /// self += the elementwise product of `a` and `b` without allocating. Panics unless `a` and `b` are as long as `self`
+    pub fn hadamard_acc(&mut self, a: &Self, b: &Self) {
+        assert_eq!(self.0.len(), a.0.len(), "vectors must have the same length");
+        assert_eq!(self.0.len(), b.0.len(), "vectors must have the same length");
+        self.0
+            .iter_mut()
+            .zip(a.0.iter().zip(b.0.iter()))
+            .for_each(|(s, (a, b))| *s += *a * *b);
     }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct FMatrix<T: PF>(pub Vec<FVec<T>>);
 impl<T: PF> FMatrix<T> {
+    /// Transposes on as many threads as are available, or on a GPU with the "gpu" feature
     pub fn transpose(&self) -> Self {
+        #[cfg(feature = "gpu")]
+        if let Some(transposed) = gpu::transpose(self) {
+            return transposed;
+        }
+        #[cfg(feature = "std")]
+        let threads = std::thread::available_parallelism()
+            .map(|n| n.get())
+            .unwrap_or(1);
+        #[cfg(not(feature = "std"))]
+        let threads = 1;
+        self.transpose_with_threads(threads)
+    }
+
+    /// Transposes on up to `threads` threads, each writing a band of the transpose's rows.
+    /// Without the "std" feature there are no threads, so it transposes on the calling one
+    pub fn transpose_with_threads(&self, threads: usize) -> Self {
         let outer_len = self.0.len();
         let inner_len = self.0[0].0.len();
-        let mut res = Vec::with_capacity(inner_len);
-        for i in 0..inner_len {
-            let mut new = Vec::with_capacity(outer_len);
-            for j in 0..outer_len {
-                new.push(self.0[j].0[i]);
+        if threads <= 1 || outer_len * inner_len < PARALLEL_TRANSPOSE_THRESHOLD {
+            return Self(self.transpose_band(0..inner_len));
+        }
+        self.transpose_in_bands(threads)
+    }
+
+    #[cfg(feature = "std")]
+    fn transpose_in_bands(&self, threads: usize) -> Self {
+        let inner_len = self.0[0].0.len();
+        let band_len = inner_len.div_ceil(threads);
+        std::thread::scope(|scope| {
+            let handles = (0..inner_len)
+                .step_by(band_len)
+                .map(|start| {
+                    scope.spawn(move || self.transpose_band(start..inner_len.min(start + band_len)))
+                })
+                .collect::<Vec<_>>();
+            Self(
+                handles
+                    .into_iter()
+                    .flat_map(|h| h.join().expect("transpose thread panicked"))
+                    .collect(),
+            )
+        })
+    }
+
+    #[cfg(not(feature = "std"))]
+    fn transpose_in_bands(&self, _threads: usize) -> Self {
+        Self(self.transpose_band(0..self.0[0].0.len()))
+    }
+
+    /// Rows `band` of the transpose, i.e. those columns, copied a block at a time so the rows read and written stay in cache
+    fn transpose_band(&self, band: Range<usize>) -> Vec<FVec<T>> {
+        let outer_len = self.0.len();
+        let mut res = band
+            .clone()
+            .map(|_| Vec::with_capacity(outer_len))
+            .collect::<Vec<Vec<T>>>();
+        for row_start in (0..outer_len).step_by(TRANSPOSE_BLOCK) {
+            let rows = &self.0[row_start..outer_len.min(row_start + TRANSPOSE_BLOCK)];
+            for col_start in band.clone().step_by(TRANSPOSE_BLOCK) {
+                for col in col_start..band.end.min(col_start + TRANSPOSE_BLOCK) {
+                    res[col - band.start].extend(rows.iter().map(|row| row.0[col]));
+                }
             }
-            res.push(FVec::<T>(new));
         }
-        Self(res)
+        res.into_iter().map(FVec).collect()
     }
 
-    fn scalar_mul(&self, rhs: T) -> Self {
-        Self(self.0.iter().map(|x| x.scalar_mul(rhs)).collect())
+    /// Each row's dot product with `v`, whose elements are from an extension of `T`
+    pub fn mul_ext<E: ExtensionField<T>>(&self, v: &[E]) -> Vec<E> {
+        self.0
+            .iter()
+            .map(|row| {
+                row.0
+                    .iter()
+                    .zip(v)
+                    .fold(E::from(T::ZERO), |acc, (x, y)| acc + *y * *x)
+            })
+            .collect()
     }
 
     pub fn dim(&self) -> (usize, usize) {
         (self.0[0].0.len(), self.0.len())
     }
+
+    /// Keeps only the nonzero entries of each row
+    pub fn to_sparse(&self) -> SparseFMatrix<T> {
+        SparseFMatrix(
+            self.0
+                .iter()
+                .map(|row| {
+                    SparseVec(
+                        row.0
+                            .iter()
+                            .enumerate()
+                            .filter(|(_, x)| !bool::from(x.is_zero()))
+                            .map(|(i, x)| (i, *x))
+                            .collect(),
+                    )
+                })
+                .collect(),
+        )
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -318,6 +608,122 @@ impl<T: PF> SparseFMatrix<T> {
     pub fn to_fmatrix(&self, len: usize) -> FMatrix<T> {
         FMatrix(self.0.iter().map(|row| row.to_fvec(len)).collect())
     }
+
+    /// Each row's dot product with `v`, whose elements are from an extension of `T`
+    pub fn mul_ext<E: ExtensionField<T>>(&self, v: &[E]) -> Vec<E> {
+        self.0
+            .iter()
+            .map(|row| {
+                row.0
+                    .iter()
+                    .fold(E::from(T::ZERO), |acc, (i, x)| acc + v[*i] * *x)
+            })
+            .collect()
+    }
+}
+
+/// How a `LayoutMatrix` stores its entries
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Layout {
+    /// An `FMatrix` of rows
+    RowMajor,
+    /// An `FMatrix` of columns
+    ColMajor,
+}
+
+/// A matrix that remembers whether its `FMatrix` holds its rows or its columns, so it can be kept in the layout it was made in
+/// and only transposed when something needs the other one
+#[derive(Debug, Clone)]
+pub struct LayoutMatrix<T: PF> {
+    layout: Layout,
+    matrix: FMatrix<T>,
+}
+
+impl<T: PF> LayoutMatrix<T> {
+    pub fn from_rows(rows: FMatrix<T>) -> Self {
+        Self {
+            layout: Layout::RowMajor,
+            matrix: rows,
+        }
+    }
+
+    pub fn from_cols(cols: FMatrix<T>) -> Self {
+        Self {
+            layout: Layout::ColMajor,
+            matrix: cols,
+        }
+    }
+
+    pub fn layout(&self) -> Layout {
+        self.layout
+    }
+
+    pub fn num_rows(&self) -> usize {
+        match self.layout {
+            Layout::RowMajor => self.matrix.0.len(),
+            Layout::ColMajor => self.matrix.0.first().map_or(0, |col| col.0.len()),
+        }
+    }
+
+    /// The rows, transposed only if they are stored as columns
+    pub fn rows(&self) -> Cow<'_, FMatrix<T>> {
+        match self.layout {
+            Layout::RowMajor => Cow::Borrowed(&self.matrix),
+            Layout::ColMajor => Cow::Owned(self.matrix.transpose()),
+        }
+    }
+
+    /// The columns, transposed only if they are stored as rows
+    pub fn cols(&self) -> Cow<'_, FMatrix<T>> {
+        match self.layout {
+            Layout::RowMajor => Cow::Owned(self.matrix.transpose()),
+            Layout::ColMajor => Cow::Borrowed(&self.matrix),
+        }
+    }
+
+    pub fn into_rows(self) -> FMatrix<T> {
+        match self.layout {
+            Layout::RowMajor => self.matrix,
+            Layout::ColMajor => self.matrix.transpose(),
+        }
+    }
+
+    pub fn into_cols(self) -> FMatrix<T> {
+        match self.layout {
+            Layout::RowMajor => self.matrix.transpose(),
+            Layout::ColMajor => self.matrix,
+        }
+    }
+
+    /// The sum of each row times its coefficient, i.e. `coefficients` times the matrix, in either layout without transposing it
+    pub fn row_combination(&self, coefficients: &FVec<T>) -> FVec<T> {
+        #[cfg(feature = "gpu")]
+        if let Some(combination) = gpu::row_combination(&self.matrix, self.layout, coefficients) {
+            return combination;
+        }
+        match self.layout {
+            Layout::RowMajor => {
+                let len = self.matrix.0.first().map_or(0, |row| row.0.len());
+                let mut acc = vec![T::ZERO; len];
+                for (c, row) in coefficients.0.iter().zip(self.matrix.0.iter()) {
+                    acc.iter_mut()
+                        .zip(row.0.iter())
+                        .for_each(|(a, x)| *a += *c * *x);
+                }
+                FVec(acc)
+            }
+            Layout::ColMajor => coefficients * &self.matrix,
+        }
+    }
+
+    /// `row_combination` with coefficients from an extension of `T`, one combination per coordinate of them
+    pub fn row_combination_ext<E: ExtensionField<T>>(&self, coefficients: &[E]) -> Vec<E> {
+        let combinations = coordinates(coefficients)
+            .iter()
+            .map(|c| self.row_combination(c))
+            .collect::<Vec<_>>();
+        from_coordinates(&combinations)
+    }
 }
 
 impl<'a, 'b, T: PF> Add<&'b FMatrix<T>> for &'a FMatrix<T> {
@@ -395,6 +801,78 @@ mod test {
             FVec(vec![Fr::from(3u64), Fr::from(6u64), Fr::from(9u64)]),
         ]);
         assert_eq!(x.transpose(), x_t);
+
+        // Large enough for several blocks and threads, with partial blocks at the edges
+        let (rows, cols) = (300, 250);
+        let x = FMatrix(
+            (0..rows)
+                .map(|i| FVec((0..cols).map(|j| Fr::from((i * cols + j) as u64)).collect()))
+                .collect(),
+        );
+        let x_t = x.transpose_with_threads(3);
+        assert_eq!(x_t.dim(), (rows, cols));
+        assert!((0..rows).all(|i| (0..cols).all(|j| x_t.0[j].0[i] == x.0[i].0[j])));
+        assert_eq!(x.transpose_with_threads(1), x_t);
+    }
+
+    #[test]
+    fn norm_and_display() {
+        let reference = |x: &Fr| {
+            let x_bu = BigUint::from_bytes_be(&x.to_repr().0);
+            if x_bu > Fr::half_prime() {
+                BigInt::from_biguint(Sign::Plus, x_bu)
+                    - BigInt::from_biguint(Sign::Plus, Fr::prime())
+            } else {
+                BigInt::from_biguint(Sign::Plus, x_bu)
+            }
+        };
+        let half = Fr::from_str_vartime(&Fr::half_prime().to_string()).unwrap();
+        let mut rng = rand::thread_rng();
+        let values = [
+            Fr::ZERO,
+            Fr::ONE,
+            -Fr::ONE,
+            half,
+            half + Fr::ONE,
+            Fr::from(u64::MAX),
+        ]
+        .into_iter()
+        .chain((0..100).map(|_| Fr::random(&mut rng)));
+        for x in values {
+            assert_eq!(x.norm(), reference(&x));
+            assert_eq!(x.to_string(), reference(&x).to_string());
+        }
+        assert_eq!((-Fr::from(12u64)).to_string(), "-12");
+        assert_eq!(
+            BigUint::from_slice(
+                &PRIME_LIMBS
+                    .iter()
+                    .flat_map(|l| [*l as u32, (*l >> 32) as u32])
+                    .collect::<Vec<u32>>()
+            ),
+            Fr::prime()
+        );
+    }
+
+    #[test]
+    fn layout_matrix() {
+        let rows = FMatrix(vec![
+            FVec(vec![Fr::from(1u64), Fr::from(2u64), Fr::from(3u64)]),
+            FVec(vec![Fr::from(4u64), Fr::from(5u64), Fr::from(6u64)]),
+        ]);
+        let by_rows = LayoutMatrix::from_rows(rows.clone());
+        let by_cols = LayoutMatrix::from_cols(rows.transpose());
+        assert_eq!(by_cols.layout(), Layout::ColMajor);
+        assert_eq!(*by_rows.rows(), *by_cols.rows());
+        assert_eq!(*by_rows.cols(), *by_cols.cols());
+        assert!(matches!(by_rows.rows(), Cow::Borrowed(_)));
+
+        // 10 * the first row + the second
+        let coefficients = FVec(vec![Fr::from(10u64), Fr::from(1u64)]);
+        let combination = FVec(vec![Fr::from(14u64), Fr::from(25u64), Fr::from(36u64)]);
+        assert_eq!(by_rows.row_combination(&coefficients), combination);
+        assert_eq!(by_cols.row_combination(&coefficients), combination);
+        assert_eq!(by_cols.into_rows(), rows);
     }
 
     // Could cover more edge cases
@@ -404,4 +882,52 @@ mod test {
         let b = SparseVec(vec![(3, Fr::from_u128(100)), (2, Fr::from_u128(5))]);
         assert!(a.sparse_dot(&b) == Fr::from_u128(6900));
     }
+
+    #[test]
+    fn random_with_rng() {
+        let seeded = || ChaCha20Rng::seed_from_u64(5);
+        let a = FVec::<Fr>::random_with_rng(8, &mut seeded());
+        assert_eq!(a, FVec::<Fr>::random_with_rng(8, &mut seeded()));
+        assert_eq!(a.0.len(), 8);
+        assert!(a != FVec::<Fr>::random_with_rng(8, &mut ChaCha20Rng::seed_from_u64(6)));
+
+        let b = FVec::<Fr>::try_random_with_rng(8, &mut seeded()).unwrap();
+        assert_eq!(
+            b,
+            FVec::<Fr>::try_random_with_rng(8, &mut seeded()).unwrap()
+        );
+        assert!(a != b);
+    }
+
+    #[test]
+    fn fused_ops() {
+        let mut rng = ChaCha20Rng::seed_from_u64(7);
+        let [x, y, z] = [(); 3].map(|_| FVec::<Fr>::random_with_rng(5, &mut rng));
+        let a = Fr::random(&mut rng);
+
+        let mut axpy = x.clone();
+        axpy.axpy_in_place(a, &y);
+        assert_eq!(axpy, &x + &y.scalar_mul(a));
+
+        let mut mul_add = x.clone();
+        mul_add.mul_add_assign(a, &y);
+        assert_eq!(mul_add, &x.scalar_mul(a) + &y);
+
+        let mut hadamard = x.clone();
+        hadamard.hadamard_acc(&y, &z);
+        assert_eq!(hadamard, &x + &(&y * &z));
+
+        // A shorter operand panics rather than truncating the result
+        let short = FVec::<Fr>::random_with_rng(4, &mut rng);
+        let mismatched: [&dyn Fn(&mut FVec<Fr>); 4] = [
+            &|s| s.axpy_in_place(a, &short),
+            &|s| s.mul_add_assign(a, &short),
+            &|s| s.hadamard_acc(&short, &z),
+            &|s| s.hadamard_acc(&y, &short),
+        ];
+        for op in mismatched {
+            let mut s = x.clone();
+            assert!(std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| op(&mut s))).is_err());
+        }
+    }
 }
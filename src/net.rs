@@ -0,0 +1,211 @@
+//! Runs the protocol between two parties over any async byte stream, e.g. a TCP or TLS stream.
+//!
+//! The prover sends its commitment (with the ID of the circuit it is proving), the verifier confirms the circuit matches,
+//! then the prover sends its proof and the verifier replies with its verdict. Challenges are still derived by Fiat-Shamir,
+//! so the verifier only has to answer and never has to keep secrets between messages.
+//!
+//! Each message is a frame made of its length as a little-endian u32 followed by the bincode encoding of a `Message`.
+//! Proving and verifying are CPU-bound and run on the calling task; on a multithreaded runtime, consider calling
+//! these from `tokio::task::spawn_blocking` or a dedicated thread.
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+use crate::{
+    actors::actors::{
+        CommitAndProof, Proof, Prover, ProverCommitment, PublicUOpenings, Verifier, VerifyingKey,
+    },
+    error::NetError,
+    zkp::R1CSWithMetadata,
+    FVec, PF,
+};
+
+/// Frames larger than this are rejected before anything is allocated for them
+pub const MAX_FRAME_LEN: u32 = 1 << 30;
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(bound(
+    serialize = "T: Serialize, T::Ext: Serialize",
+    deserialize = "T: Deserialize<'de>, T::Ext: Deserialize<'de>"
+))]
+pub enum Message<T: PF> {
+    /// Prover to verifier
+    Commitment {
+        circuit_id: [u8; 32],
+        commitment: ProverCommitment<T>,
+    },
+    /// Verifier to prover, once it has checked the circuit IDs match
+    Ready,
+    /// Prover to verifier
+    Proof(Proof<T>),
+    /// Verifier to prover: the proof verified and these are its public values
+    Accepted(PublicUOpenings<T>),
+    /// Verifier to prover: the reason the protocol was aborted
+    Rejected(String),
+}
+
+/// Writes one length-prefixed frame
+pub async fn write_message<T, S>(transport: &mut S, message: &Message<T>) -> Result<(), NetError>
+where
+    T: PF + Serialize,
+    T::Ext: Serialize,
+    S: AsyncWrite + Unpin,
+{
+    let payload = bincode::serialize(message)?;
+    let len = u32::try_from(payload.len())
+        .ok()
+        .filter(|len| *len <= MAX_FRAME_LEN)
+        .ok_or(NetError::FrameTooLarge(payload.len()))?;
+    transport.write_all(&len.to_le_bytes()).await?;
+    transport.write_all(&payload).await?;
+    transport.flush().await?;
+    Ok(())
+}
+
+/// Reads one length-prefixed frame
+pub async fn read_message<T, S>(transport: &mut S) -> Result<Message<T>, NetError>
+where
+    T: PF + DeserializeOwned,
+    T::Ext: DeserializeOwned,
+    S: AsyncRead + Unpin,
+{
+    let len = transport.read_u32_le().await?;
+    if len > MAX_FRAME_LEN {
+        return Err(NetError::FrameTooLarge(len as usize));
+    }
+    let mut payload = vec![0u8; len as usize];
+    transport.read_exact(&mut payload).await?;
+    Ok(bincode::deserialize(&payload)?)
+}
+
+/// Proves `witness` satisfies `circuit` to the verifier at the other end of `transport`,
+/// returning the public values the verifier accepted
+pub async fn run_prover<T, S>(
+    transport: &mut S,
+    witness: FVec<T>,
+    circuit: R1CSWithMetadata<T>,
+) -> Result<PublicUOpenings<T>, NetError>
+where
+    T: PF + Serialize + DeserializeOwned,
+    T::Ext: Serialize + DeserializeOwned,
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let circuit_id = circuit.circuit_id();
+    let mut prover = Prover::from_witness_and_circuit_unpadded(witness, circuit);
+    let commitment = prover.mkvole()?;
+    write_message(
+        transport,
+        &Message::Commitment {
+            circuit_id,
+            commitment,
+        },
+    )
+    .await?;
+
+    match read_message::<T, _>(transport).await? {
+        Message::Ready => {}
+        Message::Rejected(reason) => return Err(NetError::Rejected(reason)),
+        _ => return Err(NetError::UnexpectedMessage),
+    }
+
+    let proof = prover.prove()?;
+    write_message(transport, &Message::Proof(proof)).await?;
+
+    match read_message::<T, _>(transport).await? {
+        Message::Accepted(public_values) => Ok(public_values),
+        Message::Rejected(reason) => Err(NetError::Rejected(reason)),
+        _ => Err(NetError::UnexpectedMessage),
+    }
+}
+
+/// Verifies a proof from the prover at the other end of `transport`, returning its public values if it verifies.
+/// The prover is told the verdict either way
+pub async fn run_verifier<T, S>(
+    transport: &mut S,
+    key: VerifyingKey<T>,
+) -> Result<PublicUOpenings<T>, NetError>
+where
+    T: PF + Serialize + DeserializeOwned,
+    T::Ext: Serialize + DeserializeOwned,
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let (circuit_id, commitment) = match read_message::<T, _>(transport).await? {
+        Message::Commitment {
+            circuit_id,
+            commitment,
+        } => (circuit_id, commitment),
+        _ => return Err(NetError::UnexpectedMessage),
+    };
+    if circuit_id != key.circuit_digest {
+        let reason = "proof is for a different circuit".to_string();
+        write_message::<T, _>(transport, &Message::Rejected(reason.clone())).await?;
+        return Err(NetError::Rejected(reason));
+    }
+    write_message::<T, _>(transport, &Message::Ready).await?;
+
+    let proof = match read_message::<T, _>(transport).await? {
+        Message::Proof(proof) => proof,
+        _ => return Err(NetError::UnexpectedMessage),
+    };
+    let verifier = Verifier::from_verifying_key(key);
+    match verifier.verify(&CommitAndProof { commitment, proof }) {
+        Ok(public_values) => {
+            write_message(transport, &Message::Accepted(public_values.clone())).await?;
+            Ok(public_values)
+        }
+        Err(e) => {
+            write_message::<T, _>(transport, &Message::Rejected(e.to_string())).await?;
+            Err(e.into())
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use ff::Field;
+    use tokio::io::duplex;
+
+    use super::*;
+    use crate::{actors::test_helpers::tiny_witness, zkp, Fr};
+
+    #[tokio::test]
+    async fn prover_and_verifier_over_a_stream() {
+        let circuit = zkp::test::TEST_CIRCUIT.clone();
+        let key = VerifyingKey::from_circuit(circuit.clone());
+        let (mut prover_end, mut verifier_end) = duplex(1 << 16);
+
+        let (proved, verified) = tokio::join!(
+            run_prover(&mut prover_end, tiny_witness(), circuit),
+            run_verifier(&mut verifier_end, key)
+        );
+        let proved = proved.unwrap();
+        let verified = verified.unwrap();
+        assert_eq!(proved.public_inputs, verified.public_inputs);
+        assert_eq!(proved.public_outputs, verified.public_outputs);
+    }
+
+    #[tokio::test]
+    async fn verifier_rejects_wrong_witness_and_circuit() {
+        let circuit = zkp::test::TEST_CIRCUIT.clone();
+        let key = VerifyingKey::from_circuit(circuit.clone());
+        let mut wrong_witness = tiny_witness();
+        wrong_witness.0[1] += Fr::ONE;
+        let (mut prover_end, mut verifier_end) = duplex(1 << 16);
+        let (proved, verified) = tokio::join!(
+            run_prover(&mut prover_end, wrong_witness, circuit.clone()),
+            run_verifier(&mut verifier_end, key)
+        );
+        assert!(matches!(proved, Err(NetError::Rejected(_))));
+        assert!(matches!(verified, Err(NetError::Verify(_))));
+
+        let mut other_circuit = circuit.clone();
+        other_circuit.public_outputs_indices.clear();
+        let key = VerifyingKey::from_circuit(other_circuit);
+        let (mut prover_end, mut verifier_end) = duplex(1 << 16);
+        let (proved, verified) = tokio::join!(
+            run_prover(&mut prover_end, tiny_witness(), circuit),
+            run_verifier(&mut verifier_end, key)
+        );
+        assert!(matches!(proved, Err(NetError::Rejected(_))));
+        assert!(matches!(verified, Err(NetError::Rejected(_))));
+    }
+}
@@ -0,0 +1,549 @@
+//! Protobuf messages for `ProverCommitment`, `Proof`, and `CommitAndProof`, for services that exchange messages as protobuf.
+//! The schema is `proto/volonym.proto`, so other languages can generate the same messages from it.
+//! These structs can be embedded in an application's own prost messages, or the proofs encoded directly with `to_protobuf`
+use prost::Message;
+
+use crate::{
+    actors::actors::{self, PublicOpenings, SubspaceVOLEOpening},
+    challenges::{self, SecurityLevel},
+    error::FormatError,
+    fields::ext::ExtensionField,
+    subspacevole::CodePreset,
+    zkp::quicksilver::ZKP,
+    FMatrix, FVec, PF,
+};
+
+/// One row of a matrix of field elements
+#[derive(Clone, PartialEq, Message)]
+pub struct Row {
+    #[prost(bytes = "vec", repeated, tag = "1")]
+    pub elements: Vec<Vec<u8>>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct Matrix {
+    #[prost(message, repeated, tag = "1")]
+    pub rows: Vec<Row>,
+}
+
+/// A witness value's (u, v) opening. v is in the challenge field
+#[derive(Clone, PartialEq, Message)]
+pub struct Opening {
+    #[prost(bytes = "vec", tag = "1")]
+    pub u: Vec<u8>,
+    #[prost(bytes = "vec", tag = "2")]
+    pub v: Vec<u8>,
+}
+
+/// The parameters the subspace VOLE was made with
+#[derive(Clone, PartialEq, Message)]
+pub struct ProtocolParams {
+    /// The linear code preset, see `CodePreset`
+    #[prost(uint32, tag = "1")]
+    pub code: u32,
+    #[prost(uint32, tag = "2")]
+    pub num_voles: u32,
+    #[prost(uint32, tag = "3")]
+    pub vole_length: u32,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct ProverCommitment {
+    #[prost(bytes = "vec", tag = "1")]
+    pub seed_comm: Vec<u8>,
+    #[prost(message, optional, tag = "2")]
+    pub witness_comm: Option<Matrix>,
+    #[prost(message, optional, tag = "3")]
+    pub subspace_vole_correction: Option<Matrix>,
+    #[prost(bytes = "vec", repeated, tag = "4")]
+    pub consistency_check_u: Vec<Vec<u8>>,
+    #[prost(bytes = "vec", repeated, tag = "5")]
+    pub consistency_check_v: Vec<Vec<u8>>,
+    #[prost(message, optional, tag = "6")]
+    pub params: Option<ProtocolParams>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct Zkp {
+    /// The two elements of the Quicksilver multiplication proof. Its elements are in the challenge field
+    #[prost(bytes = "vec", repeated, tag = "1")]
+    pub mul_proof: Vec<Vec<u8>>,
+    #[prost(bytes = "vec", repeated, tag = "2")]
+    pub gate_proof: Vec<Vec<u8>>,
+    #[prost(bytes = "vec", repeated, tag = "3")]
+    pub branch_proof: Vec<Vec<u8>>,
+    #[prost(message, repeated, tag = "4")]
+    pub repetitions: Vec<Zkp>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct Proof {
+    #[prost(message, optional, tag = "1")]
+    pub zkp: Option<Zkp>,
+    #[prost(bytes = "vec", repeated, tag = "2")]
+    pub seed_opens: Vec<Vec<u8>>,
+    #[prost(bytes = "vec", repeated, tag = "3")]
+    pub seed_proofs: Vec<Vec<u8>>,
+    #[prost(message, repeated, tag = "4")]
+    pub public_inputs: Vec<Opening>,
+    #[prost(message, repeated, tag = "5")]
+    pub public_outputs: Vec<Opening>,
+    #[prost(message, optional, tag = "6")]
+    pub s_matrix: Option<Matrix>,
+    #[prost(bytes = "vec", repeated, tag = "7")]
+    pub s_consistency_check: Vec<Vec<u8>>,
+    #[prost(uint32, tag = "8")]
+    pub security: u32,
+    #[prost(uint32, tag = "9")]
+    pub grinding_bits: u32,
+    #[prost(uint64, tag = "10")]
+    pub grinding_nonce: u64,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct CommitAndProof {
+    #[prost(message, optional, tag = "1")]
+    pub commitment: Option<ProverCommitment>,
+    #[prost(message, optional, tag = "2")]
+    pub proof: Option<Proof>,
+}
+
+/// What to prove, sent in the first `ProveChunk` of a `ProvingService.Prove` call
+#[derive(Clone, PartialEq, Message)]
+pub struct ProveParams {
+    /// Name the server loaded the circuit under
+    #[prost(string, tag = "1")]
+    pub circuit: String,
+    /// Name the server loaded the code parameters under, or empty for the default code
+    #[prost(string, tag = "2")]
+    pub code_params: String,
+    /// Bits of security, or 0 for the default, see `ProverBuilder::security_level`
+    #[prost(uint32, tag = "3")]
+    pub security_level: u32,
+    #[prost(uint32, tag = "4")]
+    pub grinding_bits: u32,
+    /// See `ProverBuilder::context`
+    #[prost(bytes = "vec", tag = "5")]
+    pub context: Vec<u8>,
+}
+
+/// A `ProvingService.Prove` call streams these: the first has the parameters, and the witness, as a .wtns file, is split across all of them
+#[derive(Clone, PartialEq, Message)]
+pub struct ProveChunk {
+    #[prost(message, optional, tag = "1")]
+    pub params: Option<ProveParams>,
+    #[prost(bytes = "vec", tag = "2")]
+    pub witness: Vec<u8>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct ProveResponse {
+    #[prost(message, optional, tag = "1")]
+    pub proof: Option<CommitAndProof>,
+    #[prost(uint64, tag = "2")]
+    pub prove_millis: u64,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct VerifyRequest {
+    #[prost(string, tag = "1")]
+    pub circuit: String,
+    #[prost(string, tag = "2")]
+    pub code_params: String,
+    #[prost(message, optional, tag = "3")]
+    pub proof: Option<CommitAndProof>,
+    /// The context the proof was made for, see `Verifier::verify_with_context`
+    #[prost(bytes = "vec", tag = "4")]
+    pub context: Vec<u8>,
+    /// Rejects proofs with fewer bits of security, or 0 for the default, see `VerifyingKey::require_security`
+    #[prost(uint32, tag = "5")]
+    pub min_security_level: u32,
+}
+
+/// Whether the proof verified. A proof that doesn't verify is a response, not an error
+#[derive(Clone, PartialEq, Message)]
+pub struct VerifyResponse {
+    #[prost(bool, tag = "1")]
+    pub valid: bool,
+    /// Why the proof didn't verify
+    #[prost(string, tag = "2")]
+    pub error: String,
+    #[prost(bytes = "vec", repeated, tag = "3")]
+    pub public_inputs: Vec<Vec<u8>>,
+    #[prost(bytes = "vec", repeated, tag = "4")]
+    pub public_outputs: Vec<Vec<u8>>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct StatusRequest {}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct CircuitInfo {
+    #[prost(string, tag = "1")]
+    pub name: String,
+    #[prost(uint64, tag = "2")]
+    pub num_constraints: u64,
+    /// Length of the witnesses it takes, before padding
+    #[prost(uint64, tag = "3")]
+    pub witness_len: u64,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct StatusResponse {
+    #[prost(string, tag = "1")]
+    pub version: String,
+    #[prost(message, repeated, tag = "2")]
+    pub circuits: Vec<CircuitInfo>,
+    #[prost(string, repeated, tag = "3")]
+    pub code_params: Vec<String>,
+    #[prost(uint32, tag = "4")]
+    pub max_concurrent_proofs: u32,
+    #[prost(uint32, tag = "5")]
+    pub proofs_in_progress: u32,
+    #[prost(uint64, tag = "6")]
+    pub proofs_completed: u64,
+    #[prost(uint64, tag = "7")]
+    pub proofs_failed: u64,
+    #[prost(uint64, tag = "8")]
+    pub verifications: u64,
+}
+
+impl<T: PF> From<&actors::ProverCommitment<T>> for ProverCommitment {
+    fn from(c: &actors::ProverCommitment<T>) -> Self {
+        Self {
+            seed_comm: c.seed_comm.to_vec(),
+            witness_comm: Some(matrix(&c.witness_comm)),
+            subspace_vole_correction: Some(matrix(&c.subspace_vole_correction)),
+            consistency_check_u: elements(&c.consistency_check.0 .0),
+            consistency_check_v: elements(&c.consistency_check.1 .0),
+            params: Some(ProtocolParams::from(&c.params)),
+        }
+    }
+}
+
+impl From<&challenges::ProtocolParams> for ProtocolParams {
+    fn from(p: &challenges::ProtocolParams) -> Self {
+        Self {
+            code: p.code as u32,
+            num_voles: p.num_voles as u32,
+            vole_length: p.vole_length as u32,
+        }
+    }
+}
+
+impl TryFrom<ProtocolParams> for challenges::ProtocolParams {
+    type Error = FormatError;
+    fn try_from(p: ProtocolParams) -> Result<Self, FormatError> {
+        let code = u8::try_from(p.code)
+            .ok()
+            .and_then(CodePreset::from_u8)
+            .ok_or(FormatError::InvalidProtobufValue("unknown code preset"))?;
+        Ok(Self {
+            code,
+            num_voles: p.num_voles as usize,
+            vole_length: p.vole_length as usize,
+        })
+    }
+}
+
+impl<T: PF> TryFrom<ProverCommitment> for actors::ProverCommitment<T> {
+    type Error = FormatError;
+    fn try_from(c: ProverCommitment) -> Result<Self, FormatError> {
+        Ok(Self {
+            params: required(c.params, "missing params")?.try_into()?,
+            seed_comm: hash(&c.seed_comm)?,
+            witness_comm: from_matrix(required(c.witness_comm, "missing witness_comm")?)?,
+            subspace_vole_correction: from_matrix(required(
+                c.subspace_vole_correction,
+                "missing subspace_vole_correction",
+            )?)?,
+            consistency_check: (
+                FVec(from_elements(&c.consistency_check_u)?),
+                FVec(from_elements(&c.consistency_check_v)?),
+            ),
+        })
+    }
+}
+
+/// A Quicksilver proof over `T`'s challenge field as a `Zkp` message
+fn zkp<T: PF>(z: &ZKP<T::Ext>) -> Zkp {
+    Zkp {
+        mul_proof: ext_elements::<T>(&[z.mul_proof.0, z.mul_proof.1]),
+        gate_proof: ext_elements::<T>(&z.gate_proof),
+        branch_proof: ext_elements::<T>(&z.branch_proof),
+        repetitions: z.repetitions.iter().map(zkp::<T>).collect(),
+    }
+}
+
+fn from_zkp<T: PF>(z: Zkp) -> Result<ZKP<T::Ext>, FormatError> {
+    let [u, v]: [T::Ext; 2] = from_ext_elements::<T>(&z.mul_proof)?
+        .try_into()
+        .map_err(|_| FormatError::InvalidProtobufValue("mul_proof must have two elements"))?;
+    Ok(ZKP {
+        mul_proof: (u, v),
+        gate_proof: from_ext_elements::<T>(&z.gate_proof)?,
+        branch_proof: from_ext_elements::<T>(&z.branch_proof)?,
+        repetitions: z
+            .repetitions
+            .into_iter()
+            .map(from_zkp::<T>)
+            .collect::<Result<_, _>>()?,
+    })
+}
+
+impl<T: PF> From<&actors::Proof<T>> for Proof {
+    fn from(p: &actors::Proof<T>) -> Self {
+        let hashes = |h: &[[u8; 32]]| h.iter().map(|x| x.to_vec()).collect();
+        Self {
+            zkp: Some(zkp::<T>(&p.zkp)),
+            seed_opens: hashes(&p.seed_openings.seed_opens),
+            seed_proofs: hashes(&p.seed_openings.seed_proofs),
+            public_inputs: openings(&p.public_openings.public_inputs),
+            public_outputs: openings(&p.public_openings.public_outputs),
+            s_matrix: Some(matrix(&p.s_matrix)),
+            s_consistency_check: elements(&p.s_consistency_check.0),
+            security: p.security.0 as u32,
+            grinding_bits: p.grinding_bits as u32,
+            grinding_nonce: p.grinding_nonce,
+        }
+    }
+}
+
+impl<T: PF> TryFrom<Proof> for actors::Proof<T> {
+    type Error = FormatError;
+    fn try_from(p: Proof) -> Result<Self, FormatError> {
+        let hashes = |h: &[Vec<u8>]| h.iter().map(|x| hash(x)).collect::<Result<Vec<_>, _>>();
+        Ok(Self {
+            zkp: from_zkp::<T>(required(p.zkp, "missing zkp")?)?,
+            seed_openings: SubspaceVOLEOpening {
+                seed_opens: hashes(&p.seed_opens)?,
+                seed_proofs: hashes(&p.seed_proofs)?,
+            },
+            public_openings: PublicOpenings {
+                public_inputs: from_openings(&p.public_inputs)?,
+                public_outputs: from_openings(&p.public_outputs)?,
+            },
+            s_matrix: from_matrix(required(p.s_matrix, "missing s_matrix")?)?,
+            s_consistency_check: FVec(from_elements(&p.s_consistency_check)?),
+            security: SecurityLevel(p.security as usize),
+            grinding_bits: p.grinding_bits as usize,
+            grinding_nonce: p.grinding_nonce,
+        })
+    }
+}
+
+impl<T: PF> From<&actors::CommitAndProof<T>> for CommitAndProof {
+    fn from(cnp: &actors::CommitAndProof<T>) -> Self {
+        Self {
+            commitment: Some(ProverCommitment::from(&cnp.commitment)),
+            proof: Some(Proof::from(&cnp.proof)),
+        }
+    }
+}
+
+impl<T: PF> TryFrom<CommitAndProof> for actors::CommitAndProof<T> {
+    type Error = FormatError;
+    fn try_from(cnp: CommitAndProof) -> Result<Self, FormatError> {
+        Ok(Self {
+            commitment: required(cnp.commitment, "missing commitment")?.try_into()?,
+            proof: required(cnp.proof, "missing proof")?.try_into()?,
+        })
+    }
+}
+
+impl<T: PF> actors::ProverCommitment<T> {
+    /// The commitment as a protobuf `ProverCommitment` message
+    pub fn to_protobuf(&self) -> Vec<u8> {
+        ProverCommitment::from(self).encode_to_vec()
+    }
+
+    /// Decodes a protobuf `ProverCommitment` message. Dimensions are only checked when the proof is verified
+    pub fn from_protobuf(bytes: &[u8]) -> Result<Self, FormatError> {
+        ProverCommitment::decode(bytes)?.try_into()
+    }
+}
+
+impl<T: PF> actors::Proof<T> {
+    /// The proof as a protobuf `Proof` message
+    pub fn to_protobuf(&self) -> Vec<u8> {
+        Proof::from(self).encode_to_vec()
+    }
+
+    /// Decodes a protobuf `Proof` message. Dimensions are only checked when the proof is verified
+    pub fn from_protobuf(bytes: &[u8]) -> Result<Self, FormatError> {
+        Proof::decode(bytes)?.try_into()
+    }
+}
+
+impl<T: PF> actors::CommitAndProof<T> {
+    /// The commitment and proof as a protobuf `CommitAndProof` message
+    pub fn to_protobuf(&self) -> Vec<u8> {
+        CommitAndProof::from(self).encode_to_vec()
+    }
+
+    /// Decodes a protobuf `CommitAndProof` message. Dimensions are only checked when the proof is verified
+    pub fn from_protobuf(bytes: &[u8]) -> Result<Self, FormatError> {
+        CommitAndProof::decode(bytes)?.try_into()
+    }
+}
+
+pub(crate) fn elements<T: PF>(xs: &[T]) -> Vec<Vec<u8>> {
+    xs.iter().map(|x| x.to_repr().as_ref().to_vec()).collect()
+}
+
+fn matrix<T: PF>(m: &FMatrix<T>) -> Matrix {
+    Matrix {
+        rows: m
+            .0
+            .iter()
+            .map(|row| Row {
+                elements: elements(&row.0),
+            })
+            .collect(),
+    }
+}
+
+/// Elements of `T`'s challenge field are their coefficients' representations, concatenated
+fn ext_elements<T: PF>(xs: &[T::Ext]) -> Vec<Vec<u8>> {
+    xs.iter()
+        .map(|x| elements(&x.coefficients()).concat())
+        .collect()
+}
+
+fn openings<T: PF>(pairs: &[(T, T::Ext)]) -> Vec<Opening> {
+    pairs
+        .iter()
+        .map(|(u, v)| Opening {
+            u: u.to_repr().as_ref().to_vec(),
+            v: elements(&v.coefficients()).concat(),
+        })
+        .collect()
+}
+
+/// Field elements must be exactly as long as the field's representation, and canonical
+fn from_element<T: PF>(bytes: &[u8]) -> Result<T, FormatError> {
+    let mut repr = T::Repr::default();
+    if repr.as_ref().len() != bytes.len() {
+        return Err(FormatError::InvalidProtobufValue(
+            "field elements must be as long as the field's representation",
+        ));
+    }
+    repr.as_mut().copy_from_slice(bytes);
+    Option::from(T::from_repr(repr)).ok_or(FormatError::NonCanonicalElement)
+}
+
+fn from_elements<T: PF>(xs: &[Vec<u8>]) -> Result<Vec<T>, FormatError> {
+    xs.iter().map(|x| from_element(x)).collect()
+}
+
+fn from_matrix<T: PF>(m: Matrix) -> Result<FMatrix<T>, FormatError> {
+    Ok(FMatrix(
+        m.rows
+            .iter()
+            .map(|row| Ok(FVec(from_elements(&row.elements)?)))
+            .collect::<Result<_, FormatError>>()?,
+    ))
+}
+
+fn from_ext_element<T: PF>(bytes: &[u8]) -> Result<T::Ext, FormatError> {
+    let len = T::Repr::default().as_ref().len();
+    if bytes.len() != len * T::Ext::DEGREE {
+        return Err(FormatError::InvalidProtobufValue(
+            "challenge field elements must be as long as the field's representation times the degree",
+        ));
+    }
+    let coefficients = bytes
+        .chunks(len)
+        .map(from_element)
+        .collect::<Result<Vec<T>, _>>()?;
+    Ok(T::Ext::from_coefficients(&coefficients))
+}
+
+fn from_ext_elements<T: PF>(xs: &[Vec<u8>]) -> Result<Vec<T::Ext>, FormatError> {
+    xs.iter().map(|x| from_ext_element::<T>(x)).collect()
+}
+
+fn from_openings<T: PF>(openings: &[Opening]) -> Result<Vec<(T, T::Ext)>, FormatError> {
+    openings
+        .iter()
+        .map(|o| Ok((from_element(&o.u)?, from_ext_element::<T>(&o.v)?)))
+        .collect()
+}
+
+fn hash(bytes: &[u8]) -> Result<[u8; 32], FormatError> {
+    bytes.try_into().map_err(|_| {
+        FormatError::InvalidProtobufValue(
+            "seed commitments, seeds, and their proofs must be 32 bytes",
+        )
+    })
+}
+
+fn required<M>(message: Option<M>, missing: &'static str) -> Result<M, FormatError> {
+    message.ok_or(FormatError::InvalidProtobufValue(missing))
+}
+
+#[cfg(test)]
+mod test {
+    use ff::PrimeField;
+
+    use super::*;
+    use crate::{
+        actors::actors::{CommitAndProof, Prover, Verifier},
+        zkp, Fr,
+    };
+
+    fn commit_and_proof() -> CommitAndProof<Fr> {
+        let witness = FVec::<Fr>([5, 2, 28, 280].iter().map(|x| Fr::from_u128(*x)).collect());
+        let mut prover =
+            Prover::from_witness_and_circuit_unpadded(witness, zkp::test::TEST_CIRCUIT.clone());
+        prover.commit_and_prove().unwrap()
+    }
+
+    #[test]
+    fn protobuf_round_trip() {
+        let cnp = commit_and_proof();
+        let bytes = cnp.to_protobuf();
+        let decoded = CommitAndProof::<Fr>::from_protobuf(&bytes).unwrap();
+        assert_eq!(decoded.to_bytes().unwrap(), cnp.to_bytes().unwrap());
+        let verifier = Verifier::from_circuit(zkp::test::TEST_CIRCUIT.clone());
+        assert!(verifier.verify(&decoded).is_ok());
+
+        let commitment =
+            actors::ProverCommitment::<Fr>::from_protobuf(&cnp.commitment.to_protobuf()).unwrap();
+        let proof = actors::Proof::<Fr>::from_protobuf(&cnp.proof.to_protobuf()).unwrap();
+        assert_eq!(
+            CommitAndProof { commitment, proof }.to_bytes().unwrap(),
+            cnp.to_bytes().unwrap()
+        );
+    }
+
+    #[test]
+    fn malformed_protobuf() {
+        let cnp = commit_and_proof();
+        assert!(matches!(
+            CommitAndProof::<Fr>::from_protobuf(&[0xff, 0xff]),
+            Err(FormatError::Protobuf(_))
+        ));
+
+        let mut message = super::CommitAndProof::from(&cnp);
+        message.proof.as_mut().unwrap().zkp = None;
+        assert!(matches!(
+            CommitAndProof::<Fr>::try_from(message),
+            Err(FormatError::InvalidProtobufValue(_))
+        ));
+
+        let mut message = super::CommitAndProof::from(&cnp);
+        message.proof.as_mut().unwrap().s_consistency_check[0] = vec![0xff; 32];
+        assert!(matches!(
+            CommitAndProof::<Fr>::try_from(message),
+            Err(FormatError::NonCanonicalElement)
+        ));
+
+        let mut message = super::CommitAndProof::from(&cnp);
+        message.commitment.as_mut().unwrap().seed_comm.pop();
+        assert!(matches!(
+            CommitAndProof::<Fr>::try_from(message),
+            Err(FormatError::InvalidProtobufValue(_))
+        ));
+    }
+}
@@ -0,0 +1,329 @@
+//! Resumable proving sessions.
+//! A `ProvingSession` walks the prover through its phases one step at a time and can be saved between steps, so a
+//! long proof on a device that may be interrupted (e.g. a phone going to sleep) can pick up where it left off.
+//!
+//! The saved state never contains the witness or the subspace VOLE in the clear. The only secret it needs are the small VOLE seeds,
+//! which are stored encrypted under a key the caller keeps. The VOLE is regenerated from them when the session is resumed.
+use rand::{rngs::OsRng, CryptoRng, RngCore};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    actors::actors::{
+        CommitAndProof, PreparedVOLE, Proof, Prover, ProverCommitment, PublicOpenings,
+    },
+    error::ProofError,
+    zkp::{quicksilver::ZKP, R1CSWithMetadata},
+    FVec, PF,
+};
+
+/// Where a session is in the protocol
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SessionPhase {
+    /// The subspace VOLE has been made and the witness committed to
+    Committed,
+    /// The Quicksilver challenge has been derived and the Quicksilver proof made
+    Challenged,
+    /// The proof is complete
+    Proved,
+}
+
+/// The non-secret outputs a session has produced so far
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(bound(
+    serialize = "T: Serialize, T::Ext: Serialize",
+    deserialize = "T: Deserialize<'de>, T::Ext: Deserialize<'de>"
+))]
+pub enum SessionProgress<T: PF> {
+    Committed,
+    Challenged {
+        zkp: ZKP<T::Ext>,
+        public_openings: PublicOpenings<T>,
+    },
+    Proved {
+        proof: Proof<T>,
+    },
+}
+
+/// The small VOLE seeds encrypted with a blake3 keystream and authenticated with a keyed blake3 MAC (encrypt-then-MAC).
+/// The MAC also covers the rest of the `SessionState`, the commitment and progress, so sealed seeds can't be swapped
+/// between sessions and a resumed session can't be made to skip or repeat a phase
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SealedSeeds {
+    pub nonce: [u8; 32],
+    pub ciphertext: Vec<u8>,
+    pub tag: [u8; 32],
+}
+
+/// Everything needed to resume a session, apart from the witness, circuit, and key
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(bound(
+    serialize = "T: Serialize, T::Ext: Serialize",
+    deserialize = "T: Deserialize<'de>, T::Ext: Deserialize<'de>"
+))]
+pub struct SessionState<T: PF> {
+    pub commitment: ProverCommitment<T>,
+    pub progress: SessionProgress<T>,
+    pub sealed_seeds: SealedSeeds,
+}
+
+pub struct ProvingSession<T: PF> {
+    prover: Prover<T>,
+    commitment: ProverCommitment<T>,
+    progress: SessionProgress<T>,
+}
+
+impl<T: PF> ProvingSession<T> {
+    /// Starts a session by making the subspace VOLE and committing to the witness
+    pub fn start(witness: FVec<T>, circuit: R1CSWithMetadata<T>) -> Result<Self, ProofError> {
+        let mut prover = Prover::from_witness_and_circuit_unpadded(witness, circuit);
+        let commitment = prover.mkvole()?;
+        Ok(Self {
+            prover,
+            commitment,
+            progress: SessionProgress::Committed,
+        })
+    }
+
+    pub fn phase(&self) -> SessionPhase {
+        match self.progress {
+            SessionProgress::Committed => SessionPhase::Committed,
+            SessionProgress::Challenged { .. } => SessionPhase::Challenged,
+            SessionProgress::Proved { .. } => SessionPhase::Proved,
+        }
+    }
+
+    pub fn commitment(&self) -> &ProverCommitment<T> {
+        &self.commitment
+    }
+
+    /// Runs the next step of the protocol, returning the phase it reached. Does nothing once the proof is complete
+    pub fn advance(&mut self) -> Result<SessionPhase, ProofError> {
+        let next = match &self.progress {
+            SessionProgress::Committed => {
+                let (zkp, public_openings) = self.prover.prove_quicksilver()?;
+                SessionProgress::Challenged {
+                    zkp,
+                    public_openings,
+                }
+            }
+            SessionProgress::Challenged {
+                zkp,
+                public_openings,
+            } => SessionProgress::Proved {
+                proof: self
+                    .prover
+                    .open_vith(zkp.clone(), public_openings.clone())?,
+            },
+            SessionProgress::Proved { .. } => return Ok(SessionPhase::Proved),
+        };
+        self.progress = next;
+        Ok(self.phase())
+    }
+
+    /// Runs all remaining steps and returns the commitment and proof
+    pub fn finish(mut self) -> Result<CommitAndProof<T>, ProofError> {
+        while self.advance()? != SessionPhase::Proved {}
+        match self.progress {
+            SessionProgress::Proved { proof } => Ok(CommitAndProof {
+                commitment: self.commitment,
+                proof,
+            }),
+            _ => unreachable!("session is proved after advancing to the proved phase"),
+        }
+    }
+
+    /// Returns a serializable snapshot of the session with its seeds encrypted under `key`
+    pub fn save(&self, key: &[u8; 32]) -> Result<SessionState<T>, ProofError>
+    where
+        T: Serialize,
+        T::Ext: Serialize,
+    {
+        self.save_with_rng(key, &mut OsRng)
+    }
+
+    /// Like `save` with the encryption nonce drawn from `rng` instead of `OsRng`
+    pub fn save_with_rng<R: RngCore + CryptoRng + ?Sized>(
+        &self,
+        key: &[u8; 32],
+        rng: &mut R,
+    ) -> Result<SessionState<T>, ProofError>
+    where
+        T: Serialize,
+        T::Ext: Serialize,
+    {
+        let seeds = self
+            .prover
+            .vole_seeds()
+            .ok_or(ProofError::VoleNotCompleted)?;
+        let plaintext = seeds
+            .iter()
+            .flat_map(|pair| pair.iter().flatten().copied())
+            .collect::<Vec<u8>>();
+
+        let mut nonce = [0u8; 32];
+        rng.fill_bytes(&mut nonce);
+        let ciphertext = xor_keystream(key, &nonce, &plaintext);
+        let tag = *mac(key, &nonce, &self.commitment, &self.progress, &ciphertext)?.as_bytes();
+
+        Ok(SessionState {
+            commitment: self.commitment.clone(),
+            progress: self.progress.clone(),
+            sealed_seeds: SealedSeeds {
+                nonce,
+                ciphertext,
+                tag,
+            },
+        })
+    }
+
+    /// Resumes a session saved by `save`. `witness` and `circuit` must be the ones the session was started with;
+    /// this is checked by recomputing the commitment
+    pub fn resume(
+        state: SessionState<T>,
+        key: &[u8; 32],
+        witness: FVec<T>,
+        circuit: R1CSWithMetadata<T>,
+    ) -> Result<Self, ProofError>
+    where
+        T: Serialize,
+        T::Ext: Serialize,
+    {
+        let sealed = &state.sealed_seeds;
+        if mac(
+            key,
+            &sealed.nonce,
+            &state.commitment,
+            &state.progress,
+            &sealed.ciphertext,
+        )? != sealed.tag
+        {
+            return Err(ProofError::SessionAuthentication);
+        }
+        let plaintext = xor_keystream(key, &sealed.nonce, &sealed.ciphertext);
+        if !plaintext.len().is_multiple_of(64) {
+            return Err(ProofError::MalformedSessionState);
+        }
+        let seeds = plaintext
+            .chunks_exact(64)
+            .map(|c| [c[0..32].try_into().unwrap(), c[32..64].try_into().unwrap()])
+            .collect::<Vec<[[u8; 32]; 2]>>();
+
+        let mut prover = Prover::from_witness_and_circuit_unpadded(witness, circuit);
+        let prepared = PreparedVOLE::from_seeds(&prover.code, prover.vole_length, seeds)?;
+        let commitment = prover.commit_with_prepared(prepared)?;
+        if commitment.seed_comm != state.commitment.seed_comm {
+            return Err(ProofError::SessionVoleMismatch);
+        }
+        // The MAC vouches for the saved commitment, so the recomputed one only differs if the witness or circuit does
+        if serialize(&commitment)? != serialize(&state.commitment)? {
+            return Err(ProofError::SessionWitnessMismatch);
+        }
+
+        Ok(Self {
+            prover,
+            commitment: state.commitment,
+            progress: state.progress,
+        })
+    }
+}
+
+fn xor_keystream(key: &[u8; 32], nonce: &[u8; 32], input: &[u8]) -> Vec<u8> {
+    let enc_key = blake3::derive_key("volonym session seed encryption", key);
+    let mut keystream = vec![0u8; input.len()];
+    blake3::Hasher::new_keyed(&enc_key)
+        .update(nonce)
+        .finalize_xof()
+        .fill(&mut keystream);
+    input.iter().zip(keystream).map(|(a, b)| a ^ b).collect()
+}
+
+fn serialize(value: &impl Serialize) -> Result<Vec<u8>, ProofError> {
+    bincode::serialize(value).map_err(|_| ProofError::MalformedSessionState)
+}
+
+/// MAC of every field of a `SessionState` but the tag. Returns a blake3 Hash, whose equality check is constant-time
+fn mac<T: PF + Serialize>(
+    key: &[u8; 32],
+    nonce: &[u8; 32],
+    commitment: &ProverCommitment<T>,
+    progress: &SessionProgress<T>,
+    ciphertext: &[u8],
+) -> Result<blake3::Hash, ProofError>
+where
+    T::Ext: Serialize,
+{
+    let mac_key = blake3::derive_key("volonym session seed authentication", key);
+    // bincode's encoding of the pair is self-delimiting, so the ciphertext after it can't be confused with it
+    Ok(blake3::Hasher::new_keyed(&mac_key)
+        .update(nonce)
+        .update(&serialize(&(commitment, progress))?)
+        .update(ciphertext)
+        .finalize())
+}
+
+#[cfg(test)]
+mod test {
+    use ff::Field;
+
+    use super::*;
+    use crate::{
+        actors::{actors::Verifier, test_helpers::tiny_witness},
+        zkp, Fr,
+    };
+
+    #[test]
+    fn save_and_resume() {
+        let circuit = zkp::test::TEST_CIRCUIT.clone();
+        let key = [7u8; 32];
+        let mut session = ProvingSession::start(tiny_witness(), circuit.clone()).unwrap();
+        assert_eq!(session.phase(), SessionPhase::Committed);
+
+        // Save before and after the first step
+        for steps in 0..2 {
+            if steps == 1 {
+                assert_eq!(session.advance().unwrap(), SessionPhase::Challenged);
+            }
+            let saved = bincode::serialize(&session.save(&key).unwrap()).unwrap();
+            let state: SessionState<Fr> = bincode::deserialize(&saved).unwrap();
+            let resumed =
+                ProvingSession::resume(state, &key, tiny_witness(), circuit.clone()).unwrap();
+            assert_eq!(resumed.phase(), session.phase());
+
+            let cnp = resumed.finish().unwrap();
+            assert!(Verifier::from_circuit(circuit.clone()).verify(&cnp).is_ok());
+        }
+    }
+
+    #[test]
+    fn resume_rejects_wrong_key_tampering_and_witness() {
+        let circuit = zkp::test::TEST_CIRCUIT.clone();
+        let key = [7u8; 32];
+        let session = ProvingSession::start(tiny_witness(), circuit.clone()).unwrap();
+        let state = session.save(&key).unwrap();
+
+        assert!(
+            ProvingSession::resume(state.clone(), &[8u8; 32], tiny_witness(), circuit.clone())
+                .is_err()
+        );
+
+        let mut tampered = state.clone();
+        tampered.sealed_seeds.ciphertext[0] ^= 1;
+        assert!(ProvingSession::resume(tampered, &key, tiny_witness(), circuit.clone()).is_err());
+
+        // Skipping a phase by editing the progress is caught by the MAC too
+        let mut advanced = ProvingSession::start(tiny_witness(), circuit.clone()).unwrap();
+        advanced.advance().unwrap();
+        let mut skipped = advanced.save(&key).unwrap();
+        skipped.progress = SessionProgress::Proved {
+            proof: session.finish().unwrap().proof,
+        };
+        assert!(matches!(
+            ProvingSession::resume(skipped, &key, tiny_witness(), circuit.clone()),
+            Err(ProofError::SessionAuthentication)
+        ));
+
+        let mut wrong_witness = tiny_witness();
+        wrong_witness.0[1] += Fr::ONE;
+        assert!(ProvingSession::resume(state, &key, wrong_witness, circuit).is_err());
+    }
+}
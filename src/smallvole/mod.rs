@@ -46,6 +46,18 @@ impl<T: PF> VOLE<T> {
             .collect();
         ProverSmallVOLEOutputs { u, v: FVec(v) }
     }
+    /// Like `prover_outputs` but yields the outputs a chunk at a time rather than all `vole_length` of them at once
+    pub fn prover_output_stream(
+        &self,
+        seed1: &[u8; 32],
+        seed2: &[u8; 32],
+    ) -> ProverSmallVOLEStream<T> {
+        ProverSmallVOLEStream {
+            delta_choices: self.delta_choices,
+            rng1: ChaCha12Rng::from_seed(*seed1),
+            rng2: ChaCha12Rng::from_seed(*seed2),
+        }
+    }
     /// Verifier should call this after (get) to receive their small VOLE output
     pub fn verifier_outputs(
         &self,
@@ -70,6 +82,30 @@ impl<T: PF> VOLE<T> {
         VerifierSmallVOLEOutputs { delta, q }
     }
 }
+/// Produces the same values as `VOLE::prover_outputs`, in order, without holding the whole output in memory
+pub struct ProverSmallVOLEStream<T: PF> {
+    delta_choices: [T; 2],
+    rng1: ChaCha12Rng,
+    rng2: ChaCha12Rng,
+}
+impl<T: PF> ProverSmallVOLEStream<T> {
+    /// Returns the next `len` (u, v) pairs
+    pub fn next_chunk(&mut self, len: usize) -> ProverSmallVOLEOutputs<T> {
+        let mut u = Vec::with_capacity(len);
+        let mut v = Vec::with_capacity(len);
+        for _ in 0..len {
+            let o1 = T::random(&mut self.rng1);
+            let o2 = T::random(&mut self.rng2);
+            u.push(o1 + o2);
+            v.push(T::ZERO - (o1 * self.delta_choices[0] + o2 * self.delta_choices[1]));
+        }
+        ProverSmallVOLEOutputs {
+            u: FVec(u),
+            v: FVec(v),
+        }
+    }
+}
+
 /// Construct many small VOLEs and stack into big matrix. This has both prover and verifier output in plaintext
 /// TODO: Halve communication cost of sharing the seeds by bringing the seed down to 16 bytes
 // #[cfg(test)]
@@ -138,4 +174,23 @@ mod test {
         )
         .all(|(u, v, q)| u * verifier_outputs_1.delta + v == q))
     }
+
+    #[test]
+    fn stream_matches_prover_outputs() {
+        let seed0 = [9u8; 32];
+        let seed1 = [2u8; 32];
+        let vole = VOLE::<Fr>::init();
+        let prover_outputs = vole.prover_outputs(&seed0, &seed1, 100);
+        let mut stream = vole.prover_output_stream(&seed0, &seed1);
+        let mut u = Vec::new();
+        let mut v = Vec::new();
+        for len in [1, 33, 0, 66] {
+            let mut chunk = stream.next_chunk(len);
+            assert_eq!(chunk.u.0.len(), len);
+            u.append(&mut chunk.u.0);
+            v.append(&mut chunk.v.0);
+        }
+        assert_eq!(u, prover_outputs.u.0);
+        assert_eq!(v, prover_outputs.v.0);
+    }
 }
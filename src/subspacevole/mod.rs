@@ -1,8 +1,13 @@
-use crate::{FMatrix, FVec, NUM_VOLES, PF};
-use anyhow::{anyhow, Error};
+#[cfg(not(feature = "std"))]
+use crate::alloc_prelude::*;
+use crate::{
+    error::{FormatError, VerifyError},
+    fields::ext::{coordinates, ExtensionField},
+    FMatrix, FVec, LayoutMatrix, NUM_VOLES, PF,
+};
 use rand::{Rng, SeedableRng};
 use rand_chacha::ChaCha20Rng;
-use std::usize;
+use serde::{Deserialize, Serialize};
 
 // lazy_static! {
 //     // pub static ref RAAA_CODE: RAAACode = RAAACode::deserialize(bytes)
@@ -13,12 +18,13 @@ pub trait LinearCode {
     fn n(&self) -> usize;
     fn encode<T: PF>(&self, vec: &FVec<T>) -> FVec<T>;
     fn encode_extended<T: PF>(&self, vec: &FVec<T>) -> FVec<T>;
-    fn check_parity<T: PF>(&self, putative_codeword: &FVec<T>) -> bool;
-    fn check_parity_batch<T: PF>(&self, putative_codewords: &Vec<FVec<T>>) -> Result<(), Error> {
-        match putative_codewords.iter().all(|pc| self.check_parity(pc)) {
-            true => Ok(()),
-            false => Err(anyhow!("Parity check failure")),
-        }
+    /// Fails with `WrongLength` if `putative_codeword` isn't `n` long, or `ParityCheck` if it isn't a codeword
+    fn check_parity<T: PF>(&self, putative_codeword: &FVec<T>) -> Result<(), VerifyError>;
+    /// `check_parity` on each of `putative_codewords`, failing at the first that fails
+    fn check_parity_batch<T: PF>(&self, putative_codewords: &[FVec<T>]) -> Result<(), VerifyError> {
+        putative_codewords
+            .iter()
+            .try_for_each(|pc| self.check_parity(pc))
     }
     fn mul_vec_by_extended_inverse<T: PF>(&self, u: &FVec<T>) -> FVec<T>;
     fn batch_encode<T: PF>(&self, matrix: &Vec<FVec<T>>) -> Vec<FVec<T>> {
@@ -79,50 +85,221 @@ pub trait LinearCode {
             .collect::<Vec<FVec<T>>>();
 
         let times_extended_generator = self.batch_encode_extended(&zeroes_cons_c);
-        let times_deltas = times_extended_generator
-            .iter()
-            .map(|x| {
-                x * deltas
-                //x.0.iter().zip(deltas.0.iter()).map(|(a, b)| a * b)
-            })
-            .collect::<Vec<FVec<T>>>();
+        let minus_deltas = -deltas;
 
         FMatrix::<T>(
             old_qs
                 .0
                 .iter()
-                .zip(&times_deltas)
-                .map(|(q, t)| q - t)
+                .zip(&times_extended_generator)
+                .map(|(q, t)| {
+                    let mut corrected = q.clone();
+                    corrected.hadamard_acc(t, &minus_deltas);
+                    corrected
+                })
                 .collect(),
         )
     }
-    /// `challenge_hash`` is the universal hash
+    /// `challenge_hash`` is the universal hash, rows of elements of `T`'s challenge field as `calc_consistency_check` takes it
     /// `consistency_check` is the value returned from `calc_consistency_check`
-    /// `deltas` and `q` are the verifier's deltas and q
+    /// `deltas` and `q` are the verifier's deltas and q, in either layout
     /// encoder
     /// TODO: generics instead of RAAACode. And ofc generics for field
     /// AUDIT this consistency check -- in the original paper the challenge hash is a matrix. For large fields it seems a 1xn matrix,
     /// i.e. a vector, is sufficient. However, this should be double-checked :)
     fn verify_consistency_check<T: PF>(
         &self,
-        challenge_hash: &FVec<T>,
+        challenge_hash: &[T::Ext],
         consistency_check: &(FVec<T>, FVec<T>),
         deltas: &FVec<T>,
-        q_cols: &FMatrix<T>,
-    ) -> Result<(), Error> {
-        let u_hash = &consistency_check.0;
-        let v_hash = &consistency_check.1;
-        let q_hash = challenge_hash * q_cols;
-        let u_hash_x_generator_x_diag_delta = &self.encode(u_hash) * deltas;
-        if *v_hash != &q_hash - &u_hash_x_generator_x_diag_delta {
-            Err(anyhow!("Consistency check fail!"))
-        } else {
+        q: &LayoutMatrix<T>,
+    ) -> Result<(), VerifyError> {
+        let checks = challenge_hash.len().div_ceil(q.num_rows().max(1)) * T::Ext::DEGREE;
+        if consistency_check.0 .0.len() != checks * self.k()
+            || consistency_check.1 .0.len() != checks * self.n()
+            || deltas.0.len() != self.n()
+        {
+            return Err(VerifyError::Malformed(
+                "consistency check or ∆s have the wrong length",
+            ));
+        }
+        if self
+            .consistency_check_residual(challenge_hash, consistency_check, deltas, q)
+            .is_zero()
+        {
             Ok(())
+        } else {
+            Err(VerifyError::ConsistencyCheck)
+        }
+    }
+    /// The difference between the two sides of the equation `verify_consistency_check` checks, which is zero iff the check passes.
+    /// Being linear, residuals from several checks can be checked at once by checking a random linear combination of them is zero.
+    /// The code is linear over `T`, so the check of each coordinate of each challenge row is a check over `T`, and their residuals are concatenated
+    fn consistency_check_residual<T: PF>(
+        &self,
+        challenge_hash: &[T::Ext],
+        consistency_check: &(FVec<T>, FVec<T>),
+        deltas: &FVec<T>,
+        q: &LayoutMatrix<T>,
+    ) -> FVec<T> {
+        let challenges = consistency_challenges::<T>(challenge_hash, q.num_rows());
+        FVec(
+            challenges
+                .iter()
+                .zip(consistency_check.0 .0.chunks(self.k()))
+                .zip(consistency_check.1 .0.chunks(self.n()))
+                .flat_map(|((challenge, u_hash), v_hash)| {
+                    let q_hash = q.row_combination(challenge);
+                    let u_hash_x_generator_x_diag_delta =
+                        &self.encode(&FVec(u_hash.to_vec())) * deltas;
+                    (&(&q_hash - &u_hash_x_generator_x_diag_delta) - &FVec(v_hash.to_vec())).0
+                })
+                .collect(),
+        )
+    }
+}
+
+/// Identifies a code that the prover and verifier can both construct without exchanging it.
+/// Its u8 value is what gets written into encoded proofs
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[repr(u8)]
+pub enum CodePreset {
+    /// A rate 1/2 RAAA code of block length `NUM_VOLES`: `RAAACode::rand_default()`, or the interleaves of any `CodeParams`
+    /// both parties load, which the statement binds
+    RAAADefault = 0,
+}
+
+impl CodePreset {
+    pub fn from_u8(id: u8) -> Option<Self> {
+        match id {
+            0 => Some(Self::RAAADefault),
+            _ => None,
+        }
+    }
+
+    pub fn code(&self) -> RAAACode {
+        match self {
+            Self::RAAADefault => RAAACode::rand_default(),
+        }
+    }
+
+    /// Bits of soundness proofs made with this code have
+    pub fn security_bits(&self) -> usize {
+        match self {
+            Self::RAAADefault => 128,
+        }
+    }
+}
+
+/// A code of a `CodePreset`'s dimensions with interleaves from the given seeds, so parties can pin a code they agree on
+/// rather than the one from the default seeds' fixed strings. Its digest goes into `statement_digest`, so a proof only verifies
+/// with the code it was made with. `to_bytes` is its parameter file, as `r1cs_tool gen-params` writes it
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CodeParams {
+    pub preset: CodePreset,
+    /// Seeds of the three interleave permutations, in the order they are applied
+    pub interleave_seeds: [[u8; 32]; 3],
+}
+
+/// First bytes of a code parameter file
+pub const CODE_PARAMS_MAGIC: [u8; 4] = *b"VOLC";
+pub const CODE_PARAMS_VERSION: u16 = 1;
+
+impl Default for CodeParams {
+    /// The seeds `RAAACode::rand_default()` uses
+    fn default() -> Self {
+        let interleave_seed = |i: usize| {
+            *blake3::hash(format!("VOLE in the head RAAA code interleave {}", i).as_bytes())
+                .as_bytes()
+        };
+        Self {
+            preset: CodePreset::RAAADefault,
+            interleave_seeds: [interleave_seed(0), interleave_seed(1), interleave_seed(2)],
+        }
+    }
+}
+
+impl CodeParams {
+    /// Length of `to_bytes` in bytes
+    pub const LEN: usize = 4 + 2 + 1 + 3 * 32;
+
+    /// Derives the interleave seeds from `seed`, so anyone with it can check the code came from it, e.g. a public beacon's output
+    pub fn from_seed(preset: CodePreset, seed: &[u8]) -> Self {
+        let interleave_seed = |i: u8| {
+            let mut hasher = blake3::Hasher::new();
+            hasher.update(b"volonym_code_params");
+            hasher.update(&(seed.len() as u64).to_le_bytes());
+            hasher.update(seed);
+            hasher.update(&[i]);
+            *hasher.finalize().as_bytes()
+        };
+        Self {
+            preset,
+            interleave_seeds: [interleave_seed(0), interleave_seed(1), interleave_seed(2)],
+        }
+    }
+
+    /// Interleave seeds from `rng`
+    pub fn random(preset: CodePreset, rng: &mut impl Rng) -> Self {
+        let mut interleave_seeds = [[0u8; 32]; 3];
+        interleave_seeds.iter_mut().for_each(|seed| rng.fill(seed));
+        Self {
+            preset,
+            interleave_seeds,
+        }
+    }
+
+    pub fn code(&self) -> RAAACode {
+        let block_size = match self.preset {
+            CodePreset::RAAADefault => NUM_VOLES,
+        };
+        let permutations = self
+            .interleave_seeds
+            .map(|seed| RAAACode::random_interleave_permutations(block_size, Some(seed)));
+        RAAACode { permutations, q: 2 }
+    }
+
+    pub fn digest(&self) -> [u8; 32] {
+        *blake3::hash(&self.to_bytes()).as_bytes()
+    }
+
+    /// The magic number, a u16 little-endian version, the preset's u8 value, then the seeds
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(Self::LEN);
+        out.extend_from_slice(&CODE_PARAMS_MAGIC);
+        out.extend_from_slice(&CODE_PARAMS_VERSION.to_le_bytes());
+        out.push(self.preset as u8);
+        self.interleave_seeds
+            .iter()
+            .for_each(|seed| out.extend_from_slice(seed));
+        out
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, FormatError> {
+        if bytes.len() != Self::LEN {
+            return Err(FormatError::InvalidCodeParams("wrong length"));
+        }
+        if bytes[..4] != CODE_PARAMS_MAGIC {
+            return Err(FormatError::InvalidCodeParams("invalid magic number"));
+        }
+        let version = u16::from_le_bytes([bytes[4], bytes[5]]);
+        if version != CODE_PARAMS_VERSION {
+            return Err(FormatError::InvalidCodeParams("unsupported version"));
+        }
+        let preset =
+            CodePreset::from_u8(bytes[6]).ok_or(FormatError::UnknownCodePreset(bytes[6]))?;
+        let mut interleave_seeds = [[0u8; 32]; 3];
+        for (seed, chunk) in interleave_seeds.iter_mut().zip(bytes[7..].chunks(32)) {
+            seed.copy_from_slice(chunk);
         }
+        Ok(Self {
+            preset,
+            interleave_seeds,
+        })
     }
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct RAAACode {
     /// Forward and reverse permutations required for interleave and inverting interleave each time
     /// In order of when the interleaves are applied (e.g. 0th is after repetition and 2nd is before final accumulation)
@@ -276,7 +453,7 @@ impl RAAACode {
         FVec::<T>(out)
     }
     /// Returns a uniform permutation and its inverse
-    /// It will be deterministic if and only if a seed is provided
+    /// It will be deterministic if and only if a seed is provided. Without the "std" feature a seed is required
     pub fn random_interleave_permutations(
         len: u32,
         seed: Option<[u8; 32]>,
@@ -284,7 +461,10 @@ impl RAAACode {
         let range = 0..len;
         let mut rng = match seed {
             Some(s) => ChaCha20Rng::from_seed(s),
-            None => ChaCha20Rng::from_rng(&mut rand::thread_rng()).unwrap(),
+            #[cfg(feature = "std")]
+            None => ChaCha20Rng::from_rng(rand::rngs::OsRng).unwrap(),
+            #[cfg(not(feature = "std"))]
+            None => panic!("random permutations without a seed need the \"std\" feature"),
         };
         // let mut rng = ThreadRng::default();
         let mut forward = Vec::with_capacity(len as usize);
@@ -312,20 +492,7 @@ impl RAAACode {
 
     /// Creates an RAAA code of the default parameters
     pub fn rand_default() -> RAAACode {
-        let interleave_seeds = (0..3)
-            .map(|i| {
-                *blake3::hash(format!("VOLE in the head RAAA code interleave {}", i).as_bytes())
-                    .as_bytes()
-            })
-            .collect::<Vec<[u8; 32]>>();
-
-        let permutations = [
-            RAAACode::random_interleave_permutations(NUM_VOLES, Some(interleave_seeds[0])),
-            RAAACode::random_interleave_permutations(NUM_VOLES, Some(interleave_seeds[1])),
-            RAAACode::random_interleave_permutations(NUM_VOLES, Some(interleave_seeds[2])),
-        ];
-
-        RAAACode { permutations, q: 2 }
+        CodeParams::default().code()
     }
     /// For testing. Note that block size under roughly 1024 for current code may not give 128 bits of security
     pub fn rand_with_parameters(block_size: u32, q: usize) -> Self {
@@ -417,81 +584,242 @@ impl LinearCode for RAAACode {
     }
     /// Converts a vector to its codeword
     fn encode<T: PF>(&self, vec: &FVec<T>) -> FVec<T> {
-        let repeated = Self::repeat(vec, self.q);
-        let in0 = Self::interleave(&repeated, &self.permutations[0].0);
-        let acc0 = Self::accumulate(&in0);
-        let in1 = Self::interleave(&acc0, &self.permutations[1].0);
-        let acc1 = Self::accumulate(&in1);
-        let in2 = Self::interleave(&acc1, &self.permutations[2].0);
-        let acc2 = Self::accumulate(&in2);
-
-        acc2
+        self.encode_with_scratch(vec, &mut EncodeScratch::new())
     }
 
     /// Multiplies a single vector by the Tc matrix, the extended codeword generator to be invertible
     fn encode_extended<T: PF>(&self, vec: &FVec<T>) -> FVec<T> {
-        let repeated = Self::repeat_extended(vec, self.q);
-        let in0 = Self::interleave(&repeated, &self.permutations[0].0);
-        let acc0 = Self::accumulate(&in0);
-        let in1 = Self::interleave(&acc0, &self.permutations[1].0);
-        let acc1 = Self::accumulate(&in1);
-        let in2 = Self::interleave(&acc1, &self.permutations[2].0);
-        let acc2 = Self::accumulate(&in2);
-
-        acc2
+        self.encode_extended_with_scratch(vec, &mut EncodeScratch::new())
     }
 
     /// Returns a single u vector multiplied by the Tc^-1 matrix (the extended generator matrix that is invertible).
     fn mul_vec_by_extended_inverse<T: PF>(&self, u: &FVec<T>) -> FVec<T> {
-        let acc2_inv = Self::accumulate_inverse(&u);
-        let in2_inv = Self::interleave(&acc2_inv, &self.permutations[2].1);
-        let acc1_inv = Self::accumulate_inverse(&in2_inv);
-        let in1_inv = Self::interleave(&acc1_inv, &self.permutations[1].1);
-        let acc0_inv = Self::accumulate_inverse(&in1_inv);
-        let in0_inv = Self::interleave(&acc0_inv, &self.permutations[0].1);
-        let re_inv = Self::repeat_extended_inverse(&in0_inv, self.q);
+        self.mul_vec_by_extended_inverse_with_scratch(u, &mut EncodeScratch::new())
+    }
+
+    fn check_parity<T: PF>(&self, putative_codeword: &FVec<T>) -> Result<(), VerifyError> {
+        self.check_parity_with_scratch(putative_codeword, &mut EncodeScratch::new())
+    }
+
+    fn check_parity_batch<T: PF>(&self, putative_codewords: &[FVec<T>]) -> Result<(), VerifyError> {
+        let mut scratch = EncodeScratch::new();
+        putative_codewords
+            .iter()
+            .try_for_each(|pc| self.check_parity_with_scratch(pc, &mut scratch))
+    }
+
+    fn batch_encode<T: PF>(&self, matrix: &Vec<FVec<T>>) -> Vec<FVec<T>> {
+        #[cfg(feature = "gpu")]
+        if let Some(encoded) = crate::gpu::batch_encode(self, matrix) {
+            return encoded;
+        }
+        let mut scratch = EncodeScratch::new();
+        matrix
+            .iter()
+            .map(|x| self.encode_with_scratch(x, &mut scratch))
+            .collect()
+    }
+
+    fn batch_encode_extended<T: PF>(&self, matrix: &Vec<FVec<T>>) -> Vec<FVec<T>> {
+        let mut scratch = EncodeScratch::new();
+        matrix
+            .iter()
+            .map(|x| self.encode_extended_with_scratch(x, &mut scratch))
+            .collect()
+    }
+
+    fn mul_matrix_by_extended_inverse<T: PF>(&self, old_us: &FMatrix<T>) -> Vec<FVec<T>> {
+        let mut scratch = EncodeScratch::new();
+        old_us
+            .0
+            .iter()
+            .map(|u| self.mul_vec_by_extended_inverse_with_scratch(u, &mut scratch))
+            .collect()
+    }
+}
+
+/// Buffers for the RAAA code's encoding and its inverse, so the chain of repetition, interleaves, and accumulations allocates
+/// only its output rather than a vector for each step. Reusing one across rows, as the batch methods do, allocates the buffers once
+pub struct EncodeScratch<T: PF> {
+    /// The chain's current value
+    current: Vec<T>,
+    /// Where each interleave writes before it is swapped with `current`
+    next: Vec<T>,
+}
+
+impl<T: PF> EncodeScratch<T> {
+    pub fn new() -> Self {
+        Self {
+            current: Vec::new(),
+            next: Vec::new(),
+        }
+    }
+}
+
+impl<T: PF> Default for EncodeScratch<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RAAACode {
+    /// `encode`, in `scratch`'s buffers
+    pub fn encode_with_scratch<T: PF>(
+        &self,
+        vec: &FVec<T>,
+        scratch: &mut EncodeScratch<T>,
+    ) -> FVec<T> {
+        scratch.current.clear();
+        for _ in 0..self.q {
+            scratch.current.extend_from_slice(&vec.0);
+        }
+        self.interleave_accumulate(scratch);
+        FVec(scratch.current.clone())
+    }
+
+    /// `encode_extended`, in `scratch`'s buffers
+    pub fn encode_extended_with_scratch<T: PF>(
+        &self,
+        vec: &FVec<T>,
+        scratch: &mut EncodeScratch<T>,
+    ) -> FVec<T> {
+        scratch.current.clear();
+        scratch.current.extend_from_slice(&vec.0);
+        self.repeat_extended_in_place(&mut scratch.current, false);
+        self.interleave_accumulate(scratch);
+        FVec(scratch.current.clone())
+    }
 
-        re_inv
+    /// `mul_vec_by_extended_inverse`, in `scratch`'s buffers
+    pub fn mul_vec_by_extended_inverse_with_scratch<T: PF>(
+        &self,
+        u: &FVec<T>,
+        scratch: &mut EncodeScratch<T>,
+    ) -> FVec<T> {
+        scratch.current.clear();
+        scratch.current.extend_from_slice(&u.0);
+        self.interleave_accumulate_inverse(scratch);
+        self.repeat_extended_in_place(&mut scratch.current, true);
+        FVec(scratch.current.clone())
     }
 
+    /// `check_parity`, in `scratch`'s buffers
     /// SECURITY TODO: (for audit?) check this is sufficient for determining whether something is a RAAA codeword
     /// For partity check, you can invert the accumulations and permutations and then check the result is in the subspace of the repetition code
-    fn check_parity<T: PF>(&self, putative_codeword: &FVec<T>) -> bool {
-        // Invet all the operations until the initial repetition code
-        let acc2_inv = Self::accumulate_inverse(&putative_codeword);
-        let in2_inv = Self::interleave(&acc2_inv, &self.permutations[2].1);
-        let acc1_inv = Self::accumulate_inverse(&in2_inv);
-        let in1_inv = Self::interleave(&acc1_inv, &self.permutations[1].1);
-        let acc0_inv = Self::accumulate_inverse(&in1_inv);
-        let should_be_repeated = Self::interleave(&acc0_inv, &self.permutations[0].1);
+    pub fn check_parity_with_scratch<T: PF>(
+        &self,
+        putative_codeword: &FVec<T>,
+        scratch: &mut EncodeScratch<T>,
+    ) -> Result<(), VerifyError> {
+        // The inverse interleaves index the codeword up to n, and the sections below must be equally long
+        if putative_codeword.0.len() != self.n() {
+            return Err(VerifyError::WrongLength {
+                expected: self.n(),
+                found: putative_codeword.0.len(),
+            });
+        }
+        // Invert all the operations until the initial repetition code
+        scratch.current.clear();
+        scratch.current.extend_from_slice(&putative_codeword.0);
+        self.interleave_accumulate_inverse(scratch);
         // Check that the result is a codeword for the repetition code
-        let len = should_be_repeated.0.len();
-        assert!(len % self.q == 0, "length must be divisible by q");
-        let section_len = len / self.q;
         assert!(self.q > 1, "can't check parity without repetition");
-        let zeroth_section = should_be_repeated.0[0..section_len].to_vec();
-        for i in 1..self.q {
-            let idx_start = section_len * i;
-            if should_be_repeated.0[idx_start..idx_start + section_len].to_vec() != zeroth_section {
-                return false;
+        let (zeroth_section, rest) = scratch.current.split_at(self.k());
+        match rest
+            .chunks(zeroth_section.len())
+            .all(|section| section == zeroth_section)
+        {
+            true => Ok(()),
+            false => Err(VerifyError::ParityCheck),
+        }
+    }
+
+    /// The three interleaves, each followed by an accumulation, applied to `scratch`'s current value
+    fn interleave_accumulate<T: PF>(&self, scratch: &mut EncodeScratch<T>) {
+        scratch.next.resize(scratch.current.len(), T::ZERO);
+        for (forward, _) in &self.permutations {
+            Self::interleave_into(&scratch.current, forward, &mut scratch.next);
+            let mut acc = T::ZERO;
+            for x in scratch.next.iter_mut() {
+                acc += *x;
+                *x = acc;
+            }
+            core::mem::swap(&mut scratch.current, &mut scratch.next);
+        }
+    }
+
+    /// Undoes `interleave_accumulate`: each accumulation inverted, followed by the inverse interleave
+    fn interleave_accumulate_inverse<T: PF>(&self, scratch: &mut EncodeScratch<T>) {
+        scratch.next.resize(scratch.current.len(), T::ZERO);
+        for (_, backward) in self.permutations.iter().rev() {
+            let mut prev = T::ZERO;
+            for x in scratch.current.iter_mut() {
+                let cur = *x;
+                *x = cur - prev;
+                prev = cur;
+            }
+            Self::interleave_into(&scratch.current, backward, &mut scratch.next);
+            core::mem::swap(&mut scratch.current, &mut scratch.next);
+        }
+    }
+
+    /// `repeat_extended`, or if `inverse` `repeat_extended_inverse`, in place
+    fn repeat_extended_in_place<T: PF>(&self, input: &mut [T], inverse: bool) {
+        let len = input.len();
+        assert!(len.is_multiple_of(self.q), "length must be divisible by q");
+        let (zeroth_section, rest) = input.split_at_mut(len / self.q);
+        for section in rest.chunks_mut(zeroth_section.len()) {
+            for (x, z) in section.iter_mut().zip(zeroth_section.iter()) {
+                match inverse {
+                    true => *x -= *z,
+                    false => *x += *z,
+                }
             }
         }
+    }
 
-        true
+    /// `interleave` into `out`, which is as long as `input`
+    fn interleave_into<T: PF>(input: &[T], permutation: &[u32], out: &mut [T]) {
+        assert!(
+            input.len() == permutation.len(),
+            "input length {} must match number of swaps {}",
+            input.len(),
+            permutation.len()
+        );
+        for (x, p) in input.iter().zip(permutation) {
+            out[*p as usize] = *x;
+        }
     }
 }
 
-/// `challenge_hash`` is the universal hash
-/// `u` and `v` are the prover's u and v values
-/// WARNING If Using a smaller field, it may be important to use a challenge matrix instead of vector for sufficient security!
-/// Returns (challenge_hash*u, challenge_hash*v)
-///
+/// `challenge_hash`` is the universal hash: rows as long as U of elements of `T`'s challenge field, one after another, e.g. `vole_consistency_challenge`'s
+/// `u` and `v` are the prover's u and v values, in either layout
+/// Returns (challenge_hash*u, challenge_hash*v), with the combinations of each coordinate of each row one after another.
+/// It reveals one combination of U's rows per coordinate and row, so U needs as many rows hiding them
 pub fn calc_consistency_check<T: PF>(
-    challenge_hash: &FVec<T>,
-    u_cols: &FMatrix<T>,
-    v_cols: &FMatrix<T>,
+    challenge_hash: &[T::Ext],
+    u: &LayoutMatrix<T>,
+    v: &LayoutMatrix<T>,
 ) -> (FVec<T>, FVec<T>) {
-    (challenge_hash * u_cols, challenge_hash * v_cols)
+    let challenges = consistency_challenges::<T>(challenge_hash, u.num_rows());
+    let combine = |m: &LayoutMatrix<T>| {
+        FVec(
+            challenges
+                .iter()
+                .flat_map(|c| m.row_combination(c).0)
+                .collect(),
+        )
+    };
+    (combine(u), combine(v))
+}
+
+/// The challenge vectors over `T` the consistency check with `challenge_hash`, of rows of `rows` elements, is made of:
+/// each coordinate of each row
+pub fn consistency_challenges<T: PF>(challenge_hash: &[T::Ext], rows: usize) -> Vec<FVec<T>> {
+    challenge_hash
+        .chunks(rows.max(1))
+        .flat_map(coordinates::<T, T::Ext>)
+        .collect()
 }
 
 #[cfg(test)]
@@ -501,9 +829,10 @@ mod test {
     use ff::{Field, PrimeField};
     use itertools::izip;
     use nalgebra::{Matrix2x4, Matrix4x2};
-    use rand::rngs::ThreadRng;
+    use rand::{rngs::ThreadRng, thread_rng};
 
     use crate::{
+        fields::{ext::QM31, m31::M31},
         smallvole::{self, TestMOLE, VOLE},
         Fr, FrRepr,
     };
@@ -712,7 +1041,7 @@ mod test {
             ],
             q: 2,
         };
-        let input = FVec::<Fr>::random(3);
+        let input = FVec::<Fr>::random_with_rng(3, &mut thread_rng());
         // let code = RAAACode::rand_default();
         // let input = FVec<T>::random(512);
         let codeword = code.encode(&input);
@@ -720,18 +1049,139 @@ mod test {
         invalid_codeword.0[2] = Fr::random(&mut rand::thread_rng());
         let mut invalid_length = codeword.clone();
         invalid_length.0.push(Fr::random(&mut rand::thread_rng()));
-        assert!(code.check_parity(&codeword));
-        assert!(!code.check_parity(&invalid_codeword));
-        // assert!(!code.check_parity(&invalid_length));
+        assert!(code.check_parity(&codeword).is_ok());
+        assert!(matches!(
+            code.check_parity(&invalid_codeword),
+            Err(VerifyError::ParityCheck)
+        ));
+        assert!(matches!(
+            code.check_parity(&invalid_length),
+            Err(VerifyError::WrongLength {
+                expected: 6,
+                found: 7
+            })
+        ));
+        assert!(matches!(
+            code.check_parity(&FVec::<Fr>::random_with_rng(4, &mut thread_rng())),
+            Err(VerifyError::WrongLength { .. })
+        ));
     }
     #[test]
     fn check_parity_batch() {
         let code = RAAACode::rand_default();
-        let input: Vec<FVec<Fr>> = (0..10).map(|_| FVec::<Fr>::random(512)).collect();
+        let input: Vec<FVec<Fr>> = (0..10)
+            .map(|_| FVec::<Fr>::random_with_rng(512, &mut thread_rng()))
+            .collect();
         let mut codewords: Vec<FVec<Fr>> = input.iter().map(|x| code.encode(x)).collect();
         assert!(code.check_parity_batch(&codewords).is_ok());
         codewords[2].0[7] = Fr::random(&mut rand::thread_rng());
-        assert!(code.check_parity_batch(&codewords).is_err())
+        assert!(code.check_parity_batch(&codewords).is_err());
+        codewords[2] = code.encode(&input[2]);
+        codewords[5].0.pop();
+        assert!(matches!(
+            code.check_parity_batch(&codewords),
+            Err(VerifyError::WrongLength { .. })
+        ));
+    }
+    #[test]
+    fn consistency_check_over_an_extension() {
+        let code = RAAACode::rand_with_parameters(24, 3);
+        let u_rows = (0..6)
+            .map(|_| FVec::<M31>::random_with_rng(8, &mut thread_rng()))
+            .collect::<Vec<_>>();
+        let v_rows = (0..6)
+            .map(|_| FVec::<M31>::random_with_rng(24, &mut thread_rng()))
+            .collect::<Vec<_>>();
+        let deltas = FVec::<M31>::random_with_rng(24, &mut thread_rng());
+        let q_rows = u_rows
+            .iter()
+            .zip(v_rows.iter())
+            .map(|(u, v)| &(&code.encode(u) * &deltas) + v)
+            .collect::<Vec<_>>();
+        let (u, v) = (
+            LayoutMatrix::from_rows(FMatrix(u_rows)),
+            LayoutMatrix::from_rows(FMatrix(v_rows)),
+        );
+        let mut q = LayoutMatrix::from_rows(FMatrix(q_rows));
+
+        // Two challenge rows, each checked coordinate by coordinate
+        let challenge = (0..12)
+            .map(|_| QM31::sample(rand::thread_rng()))
+            .collect::<Vec<_>>();
+        let check = calc_consistency_check::<M31>(&challenge, &u, &v);
+        assert_eq!(
+            (check.0 .0.len(), check.1 .0.len()),
+            (2 * 4 * 8, 2 * 4 * 24)
+        );
+        assert!(code
+            .verify_consistency_check(&challenge, &check, &deltas, &q)
+            .is_ok());
+        let first_coordinate = FVec(challenge[..6].iter().map(|c| c.0 .0).collect());
+        assert_eq!(u.row_combination(&first_coordinate).0, check.0 .0[..8]);
+
+        let mut short = check.clone();
+        short.1 .0.pop();
+        assert!(matches!(
+            code.verify_consistency_check(&challenge, &short, &deltas, &q),
+            Err(VerifyError::Malformed(_))
+        ));
+        let mut rows = q.into_rows();
+        rows.0[4].0[17] += M31::ONE;
+        q = LayoutMatrix::from_rows(rows);
+        assert!(matches!(
+            code.verify_consistency_check(&challenge, &check, &deltas, &q),
+            Err(VerifyError::ConsistencyCheck)
+        ));
+    }
+    #[test]
+    fn scratch_matches_step_by_step() {
+        let code = RAAACode::rand_with_parameters(24, 3);
+        let mut scratch = EncodeScratch::new();
+        // The same scratch for each row, and for both directions
+        for _ in 0..3 {
+            let input = FVec::<Fr>::random_with_rng(8, &mut thread_rng());
+            let mut expected = RAAACode::repeat(&input, 3);
+            for (forward, _) in &code.permutations {
+                expected = RAAACode::accumulate(&RAAACode::interleave(&expected, forward));
+            }
+            let codeword = code.encode_with_scratch(&input, &mut scratch);
+            assert_eq!(codeword, expected);
+            assert!(code.check_parity_with_scratch(&codeword, &mut scratch).is_ok());
+
+            let full = FVec::<Fr>::random_with_rng(24, &mut thread_rng());
+            let mut expected = RAAACode::repeat_extended(&full, 3);
+            for (forward, _) in &code.permutations {
+                expected = RAAACode::accumulate(&RAAACode::interleave(&expected, forward));
+            }
+            let extended = code.encode_extended_with_scratch(&full, &mut scratch);
+            assert_eq!(extended, expected);
+            assert_eq!(
+                code.mul_vec_by_extended_inverse_with_scratch(&extended, &mut scratch),
+                full
+            );
+        }
+    }
+    #[test]
+    fn code_params() {
+        assert_eq!(CodeParams::default().code(), RAAACode::rand_default());
+        let params = CodeParams::from_seed(CodePreset::RAAADefault, b"seed");
+        assert_eq!(
+            params,
+            CodeParams::from_seed(CodePreset::RAAADefault, b"seed")
+        );
+        assert_ne!(params.code(), RAAACode::rand_default());
+        assert_ne!(params.digest(), CodeParams::default().digest());
+
+        let bytes = CodeParams::random(CodePreset::RAAADefault, &mut rand::thread_rng()).to_bytes();
+        assert_eq!(bytes.len(), CodeParams::LEN);
+        assert_eq!(CodeParams::from_bytes(&bytes).unwrap().to_bytes(), bytes);
+        assert!(CodeParams::from_bytes(&bytes[1..]).is_err());
+        let mut unknown_preset = bytes.clone();
+        unknown_preset[6] = 0xff;
+        assert!(matches!(
+            CodeParams::from_bytes(&unknown_preset),
+            Err(FormatError::UnknownCodePreset(0xff))
+        ));
     }
     // /// This is tested in the integration tests for e2e prover and verifier
     // #[test]
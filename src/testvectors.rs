@@ -0,0 +1,225 @@
+//! Known-answer test vectors: a proof of a tiny circuit from a fixed master seed, with everything the prover and verifier
+//! derive on the way to it, as JSON.
+//!
+//! Another implementation of the protocol can check it derives the same seeds, commitment, and challenges and accepts the same proof,
+//! and `replay` pins the Fiat-Shamir transcript here, since any change to what it absorbs changes the challenges.
+//! Field elements are decimal strings and byte strings are hex
+use rand::SeedableRng;
+use rand_chacha::ChaCha20Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    actors::actors::{random_seeds, CommitAndProof, Prover, PublicUOpenings, Verifier},
+    challenges::{transcript::Transcript, Challenger, Challenges, FiatShamir},
+    circom::to_decimal,
+    error::{ProofError, TestVectorError, VerifyError},
+    zkp::{FullR1CS, R1CSWithMetadata, R1CS},
+    FMatrix, FVec, Fr,
+};
+
+/// Master seed of the vectors this crate's tests pin
+pub const DEFAULT_MASTER_SEED: [u8; 32] = *b"volonym known-answer test vector";
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TestVector {
+    /// Seed every small VOLE seed is drawn from with ChaCha20, see `ProverBuilder::seed`
+    pub master_seed: String,
+    /// Each small VOLE's two seeds
+    pub vole_seeds: Vec<[String; 2]>,
+    pub seed_comm: String,
+    pub quicksilver_challenges: Vec<String>,
+    pub delta_choices: Vec<usize>,
+    pub vith_delta: String,
+    pub subspace_challenge: Vec<String>,
+    pub s_challenge: Vec<String>,
+    /// The proof's canonical encoding, as `CommitAndProof::to_bytes` writes it
+    pub proof: String,
+    pub public_inputs: Vec<String>,
+    pub public_outputs: Vec<String>,
+}
+
+/// The circuit the vectors prove, with its witness: (w0 + w1) * 2w1 = w2 and 2w0 * w2 = w3,
+/// satisfied by 5, 2, 28, 280 with w0 and w2 public inputs and w3 the public output
+pub fn tiny_circuit() -> (R1CSWithMetadata<Fr>, FVec<Fr>) {
+    let rows = |rows: [[u64; 4]; 2]| {
+        FMatrix(
+            rows.iter()
+                .map(|row| FVec(row.iter().map(|x| Fr::from(*x)).collect()))
+                .collect(),
+        )
+    };
+    let r1cs = FullR1CS {
+        a_rows: rows([[1, 1, 0, 0], [2, 0, 0, 0]]),
+        b_rows: rows([[0, 2, 0, 0], [0, 0, 1, 0]]),
+        c_rows: rows([[0, 0, 1, 0], [0, 0, 0, 1]]),
+    };
+    let circuit = R1CSWithMetadata {
+        r1cs: R1CS::Full(r1cs),
+        public_inputs_indices: vec![0, 2],
+        public_outputs_indices: vec![3],
+        unpadded_wtns_len: 4,
+        custom_gates: vec![],
+        lookups: vec![],
+        memories: vec![],
+        branches: vec![],
+        ccs: vec![],
+        signal_names: Default::default(),
+    };
+    (circuit, FVec([5u64, 2, 28, 280].map(Fr::from).to_vec()))
+}
+
+/// Proves the tiny circuit with its small VOLE seeds drawn from `master_seed`, recording the challenges its verifier derives
+pub fn generate(master_seed: [u8; 32]) -> Result<TestVector, TestVectorError> {
+    let (circuit, witness) = tiny_circuit();
+    let mut prover = Prover::builder(witness, circuit.clone())
+        .seed(master_seed)
+        .build()?;
+    let cnp = prover.commit_and_prove()?;
+    let vole_seeds = prover
+        .vole_seeds()
+        .ok_or(ProofError::VoleNotCompleted)?
+        .iter()
+        .map(|pair| pair.map(hex::encode))
+        .collect();
+
+    let mut recorder = Recorder::default();
+    let public =
+        Verifier::from_circuit(circuit).verify_with_challenger(&cnp, &[], &mut recorder)?;
+    recorder.into_vector(master_seed, vole_seeds, &cnp, &public)
+}
+
+/// Verifies the vector's proof for the tiny circuit and checks the seeds, commitment, challenges, and public values
+/// it records are the ones derived here. Returns the public values
+pub fn replay(vector: &TestVector) -> Result<PublicUOpenings<Fr>, TestVectorError> {
+    let bytes = hex::decode(&vector.proof).map_err(|_| TestVectorError::Mismatch("proof"))?;
+    let cnp = CommitAndProof::<Fr>::from_bytes(&bytes).map_err(VerifyError::from)?;
+    let master_seed = <[u8; 32]>::try_from(
+        hex::decode(&vector.master_seed).map_err(|_| TestVectorError::Mismatch("master seed"))?,
+    )
+    .map_err(|_| TestVectorError::Mismatch("master seed"))?;
+    let vole_seeds = random_seeds(
+        &mut ChaCha20Rng::from_seed(master_seed),
+        cnp.proof.seed_openings.seed_opens.len(),
+    )
+    .iter()
+    .map(|pair| pair.map(hex::encode))
+    .collect();
+
+    let (circuit, _) = tiny_circuit();
+    let mut recorder = Recorder::default();
+    let public =
+        Verifier::from_circuit(circuit).verify_with_challenger(&cnp, &[], &mut recorder)?;
+    let replayed = recorder.into_vector(master_seed, vole_seeds, &cnp, &public)?;
+
+    let fields = [
+        ("VOLE seeds", replayed.vole_seeds == vector.vole_seeds),
+        ("seed commitment", replayed.seed_comm == vector.seed_comm),
+        (
+            "Quicksilver challenges",
+            replayed.quicksilver_challenges == vector.quicksilver_challenges,
+        ),
+        ("∆ choices", replayed.delta_choices == vector.delta_choices),
+        ("VitH ∆'", replayed.vith_delta == vector.vith_delta),
+        (
+            "consistency check challenges",
+            replayed.subspace_challenge == vector.subspace_challenge
+                && replayed.s_challenge == vector.s_challenge,
+        ),
+        (
+            "public values",
+            replayed.public_inputs == vector.public_inputs
+                && replayed.public_outputs == vector.public_outputs,
+        ),
+    ];
+    match fields.iter().find(|(_, matches)| !matches) {
+        Some((field, _)) => Err(TestVectorError::Mismatch(field)),
+        None => Ok(public),
+    }
+}
+
+/// Derives challenges the way `FiatShamir` does, keeping a copy of each
+#[derive(Default)]
+struct Recorder {
+    quicksilver: Vec<Fr>,
+    opening: Option<Challenges<Fr>>,
+}
+
+impl Challenger<Fr> for Recorder {
+    fn quicksilver_challenges(&mut self, transcript: &Transcript, repetitions: usize) -> Vec<Fr> {
+        self.quicksilver =
+            Challenger::<Fr>::quicksilver_challenges(&mut FiatShamir, transcript, repetitions);
+        self.quicksilver.clone()
+    }
+
+    fn opening_challenges(
+        &mut self,
+        transcript: &Transcript,
+        vole_length: usize,
+        num_voles: usize,
+    ) -> Challenges<Fr> {
+        let challenges = FiatShamir.opening_challenges(transcript, vole_length, num_voles);
+        self.opening = Some(challenges.clone());
+        challenges
+    }
+}
+
+impl Recorder {
+    fn into_vector(
+        self,
+        master_seed: [u8; 32],
+        vole_seeds: Vec<[String; 2]>,
+        cnp: &CommitAndProof<Fr>,
+        public: &PublicUOpenings<Fr>,
+    ) -> Result<TestVector, TestVectorError> {
+        let decimals = |xs: &[Fr]| xs.iter().map(to_decimal).collect::<Vec<_>>();
+        let opening = self
+            .opening
+            .expect("a verified proof has had its opening challenges derived");
+        Ok(TestVector {
+            master_seed: hex::encode(master_seed),
+            vole_seeds,
+            seed_comm: hex::encode(cnp.commitment.seed_comm),
+            quicksilver_challenges: decimals(&self.quicksilver),
+            delta_choices: opening.delta_choices,
+            vith_delta: to_decimal(&opening.vith_delta),
+            subspace_challenge: decimals(&opening.subspace_challenge),
+            s_challenge: decimals(&opening.s_challenge),
+            proof: hex::encode(cnp.to_bytes().map_err(VerifyError::from)?),
+            public_inputs: decimals(&public.public_inputs),
+            public_outputs: decimals(&public.public_outputs),
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn vectors_replay() {
+        let vector = generate(DEFAULT_MASTER_SEED).unwrap();
+        let json = serde_json::to_string(&vector).unwrap();
+        let parsed: TestVector = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed, generate(DEFAULT_MASTER_SEED).unwrap());
+        // Pins the transcript: this changes whenever anything a proof absorbs, or how its challenges are derived, changes
+        assert_eq!(
+            blake3::hash(json.as_bytes()).to_hex().as_str(),
+            "f020fabe3522a521ccc26f4b270224f9cf4cb80aa5858715ba7f7dfe87fdd66c"
+        );
+        let public = replay(&parsed).unwrap();
+        assert_eq!(public.public_outputs, vec![Fr::from(280)]);
+
+        let mut wrong_challenge = parsed.clone();
+        wrong_challenge.vith_delta = "1".to_string();
+        assert!(matches!(
+            replay(&wrong_challenge),
+            Err(TestVectorError::Mismatch("VitH ∆'"))
+        ));
+        let mut wrong_seed = parsed;
+        wrong_seed.master_seed = hex::encode([1u8; 32]);
+        assert!(matches!(
+            replay(&wrong_seed),
+            Err(TestVectorError::Mismatch("VOLE seeds"))
+        ));
+    }
+}
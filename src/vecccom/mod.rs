@@ -1,6 +1,8 @@
 use rand::prelude::*;
 use rand_chacha::ChaCha12Rng;
 
+#[cfg(not(feature = "std"))]
+use crate::alloc_prelude::*;
 use crate::{FVec, PF};
 
 /// Newer method much faster: use a CSPRNG
@@ -16,6 +18,79 @@ pub fn expand_seed_to_field_vec<T: PF>(seed: [u8; 32], num_outputs: usize) -> FV
     FVec(out)
 }
 
+/// Below this many outputs `expand_xof_to_field_vec` doesn't spawn threads
+const PARALLEL_EXPANSION_THRESHOLD: usize = 1 << 14;
+
+/// Expands an extendable output, e.g. a transcript's, into `num_outputs` field elements.
+/// Each is reduced from twice as many bytes as the field's elements have, so it is close to uniform regardless of the prime.
+/// The XOF can be read from any position, so long outputs are converted in parallel without rehashing
+pub fn expand_xof_to_field_vec<T: PF>(xof: blake3::OutputReader, num_outputs: usize) -> FVec<T> {
+    let bytes_per_output = 2 * (T::NUM_BITS.div_ceil(64) * 8) as usize;
+    let expand = |start: usize, len: usize| {
+        let mut xof = xof.clone();
+        xof.set_position((start * bytes_per_output) as u64);
+        let mut buf = vec![0u8; bytes_per_output];
+        (0..len)
+            .map(|_| {
+                xof.fill(&mut buf);
+                reduce_le_bytes(&buf)
+            })
+            .collect::<Vec<T>>()
+    };
+    #[cfg(feature = "std")]
+    let threads = std::thread::available_parallelism()
+        .map(|n| n.get())
+        .unwrap_or(1);
+    #[cfg(not(feature = "std"))]
+    let threads = 1;
+    if threads == 1 || num_outputs < PARALLEL_EXPANSION_THRESHOLD {
+        return FVec(expand(0, num_outputs));
+    }
+    expand_in_parallel(expand, num_outputs, threads)
+}
+
+/// `expand`'s outputs from 0 to `num_outputs`, split across `threads` threads
+#[cfg(feature = "std")]
+fn expand_in_parallel<T: PF>(
+    expand: impl Fn(usize, usize) -> Vec<T> + Sync,
+    num_outputs: usize,
+    threads: usize,
+) -> FVec<T> {
+    let expand = &expand;
+    let chunk_len = num_outputs.div_ceil(threads);
+    std::thread::scope(|scope| {
+        let handles = (0..num_outputs)
+            .step_by(chunk_len)
+            .map(|start| scope.spawn(move || expand(start, chunk_len.min(num_outputs - start))))
+            .collect::<Vec<_>>();
+        FVec(
+            handles
+                .into_iter()
+                .flat_map(|h| h.join().expect("challenge expansion thread panicked"))
+                .collect(),
+        )
+    })
+}
+
+#[cfg(not(feature = "std"))]
+fn expand_in_parallel<T: PF>(
+    expand: impl Fn(usize, usize) -> Vec<T>,
+    num_outputs: usize,
+    _threads: usize,
+) -> FVec<T> {
+    FVec(expand(0, num_outputs))
+}
+
+/// The field element whose value is the little endian `bytes` modulo the prime
+fn reduce_le_bytes<T: PF>(bytes: &[u8]) -> T {
+    let shift = T::from(1 << 32).square();
+    bytes.chunks(8).rev().fold(T::ZERO, |acc, chunk| {
+        let mut limb = [0u8; 8];
+        limb[..chunk.len()].copy_from_slice(chunk);
+        acc * shift + T::from(u64::from_le_bytes(limb))
+    })
+}
+
 /// Instead of long vectors in most VOLE protocols, we're just doing a "vector" commitment to two values,
 /// This means k for our SoftSpokenVOLE instantiation is 2, i.e. ∆ has just two bits of entropy.
 /// Since we have to open and transmit all but one of the seeds, using a larger k for SoftSpokenVOLE doesn't save significant communication and solely wastes computation.
@@ -69,7 +144,9 @@ pub fn reconstruct_commitment(
 
 #[cfg(test)]
 mod test {
-    use crate::Fr;
+    use ff::PrimeField;
+
+    use crate::{zkp::test::F61, Fr};
 
     use super::*;
 
@@ -102,6 +179,25 @@ mod test {
         );
     }
 
+    #[test]
+    fn xof_expansion() {
+        let xof = || blake3::Hasher::new().update(b"seed").finalize_xof();
+        let long = expand_xof_to_field_vec::<Fr>(xof(), 3 * PARALLEL_EXPANSION_THRESHOLD + 1);
+        assert_eq!(long.0.len(), 3 * PARALLEL_EXPANSION_THRESHOLD + 1);
+        // Shorter expansions are prefixes of longer ones, however the longer one was split between threads
+        assert_eq!(expand_xof_to_field_vec::<Fr>(xof(), 5).0, long.0[..5]);
+        assert_ne!(long.0[0], long.0[1]);
+
+        // Over a 61 bit prime, each output reduces 16 bytes of the XOF
+        let mut bytes = [0u8; 32];
+        xof().fill(&mut bytes);
+        let small = expand_xof_to_field_vec::<F61>(xof(), 2);
+        for (x, chunk) in small.0.iter().zip(bytes.chunks(16)) {
+            let value = u128::from_le_bytes(chunk.try_into().unwrap()) % ((1 << 61) - 1);
+            assert_eq!(*x, F61::from_u128(value));
+        }
+    }
+
     #[test]
     fn test_seed_commit_prove() {
         let seed0 = [5u8; 32];
@@ -151,4 +247,3 @@ mod test {
         ));
     }
 }
-
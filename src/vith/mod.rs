@@ -1,35 +1,370 @@
-// use crate::{FrMatrix, Fr};
-
-// struct VoleInTheHeadTest {
-//     /// A commitment to the prover's witness
-//     pub witness_comm: FrMatrix,
-//     /// S from the paper
-//     pub s: FrMatrix,
-//     /// U1 from the paper
-//     pub u1: Fr,
-//     /// R (U2) from the paper
-//     pub r: Fr,
-//     /// ∆' from paper
-//     pub delta: Fr,
-// }
-
-// struct VITHValues {
-//     /// A commitment to the prover's witness
-//     pub witness_comm: FrMatrix,
-//     /// U1 from the paper
-//     pub u1: Fr,
-//     /// R (U2) from the paper
-//     pub r: Fr,
-
-// }
-// pub fn from_subspace_vole_to_vith(u_rows: &FrMatrix, v_rows: &FrMatrix, witness: &FrMatrix) -> VITHValues {
-//     let num_u_rows = u_rows.0.len();
-//     let num_v_rows = v_rows.0.len();
-//     assert!(num_u_rows % 2 == 0, "U must have an even number of rows");
-//     assert!(num_v_rows % 2 == 0, "V must have an even number of rows");
-//     let u_halfway = num_u_rows / 2;
-//     let v_halfway = num_v_rows / 2;
-//     let u1 = u_rows.0[0..u_halfway].to_vec();
-//     let r = u_rows.0[u_halfway..].to_vec();
-    
-// }
\ No newline at end of file
+//! VOLE in the head (VitH): turning the subspace VOLE, whose ∆s the verifier learns when the seeds are opened,
+//! into a VOLE with a single ∆' that neither party knows until the prover has committed to everything it proves.
+//!
+//! ∆' is from the field's challenge field (see `fields::ext::ChallengeField`), of degree D over it, so the subspace VOLE's U and V,
+//! with (D + 1)h rows, are split into U1, V1 and D blocks U2_j, V2_j of h rows each.
+//! The prover commits to its witness W, rows as long as the code's dimension, as W minus U1's first rows,
+//! and once ∆' is chosen reveals S_j = U1∆'_j + U2_j for each coefficient ∆'_j of ∆', with a proof that S was made from the subspace VOLE.
+//! From S and the commitment, the verifier learns q = u∆' + v, where u is the witness followed by U1's rows after it and v is Σ_j e_j U2_j
+//! for the basis e_j of the challenge field. That is a VOLE any ZKP over (u, v, q, ∆') can run on, e.g. `zkp::quicksilver`,
+//! with U1's rows after the witness left to hide its proof and the consistency check.
+//! Over a field that is its own challenge field, D is 1 and h is half the rows.
+//!
+//! Nothing here depends on the ZKP: `VithProver::vole` and `VithVerifier::vole` are the VOLE it is given
+#[cfg(not(feature = "std"))]
+use crate::alloc_prelude::*;
+use crate::{
+    error::{ProofError, VerifyError},
+    fields::ext::{coordinates, ExtensionField},
+    subspacevole::LinearCode,
+    DataSize, FMatrix, FVec, LayoutMatrix, PF,
+};
+
+/// Rows of U1, and of each block of U2, for a subspace VOLE of `vole_length` rows over `T`
+pub fn vole_rows<T: PF>(vole_length: usize) -> usize {
+    vole_length / (T::Ext::DEGREE + 1)
+}
+
+/// The prover's half of VitH: the subspace VOLE's U and V, split into U1, V1 and U2, V2 of `T::Ext::DEGREE` blocks
+pub struct VithProver<T: PF> {
+    u1: FMatrix<T>,
+    u2: FMatrix<T>,
+    v1: FMatrix<T>,
+    v2: FMatrix<T>,
+}
+
+impl<T: PF> DataSize for VithProver<T> {
+    fn size_in_bytes(&self) -> usize {
+        self.u1.size_in_bytes()
+            + self.u2.size_in_bytes()
+            + self.v1.size_in_bytes()
+            + self.v2.size_in_bytes()
+    }
+}
+
+impl<T: PF> VithProver<T> {
+    /// Splits the subspace VOLE's rows into U1 and U2. Their number must be a multiple of one more than the challenge field's degree
+    pub fn from_subspace_vole(u_rows: FMatrix<T>, v_rows: FMatrix<T>) -> Result<Self, ProofError> {
+        let parts = T::Ext::DEGREE + 1;
+        if !u_rows.0.len().is_multiple_of(parts) || v_rows.0.len() != u_rows.0.len() {
+            return Err(ProofError::UnsplittableVoleLength);
+        }
+        let (mut u1, mut v1) = (u_rows.0, v_rows.0);
+        let u2 = u1.split_off(u1.len() / parts);
+        let v2 = v1.split_off(v1.len() / parts);
+        Ok(Self {
+            u1: FMatrix(u1),
+            u2: FMatrix(u2),
+            v1: FMatrix(v1),
+            v2: FMatrix(v2),
+        })
+    }
+
+    /// The commitment to `witness`, which has fewer rows than U1: the witness minus U1's first rows
+    pub fn commit_witness(&self, witness: &FMatrix<T>) -> FMatrix<T> {
+        witness - &FMatrix(self.u1.0[..witness.0.len()].to_vec())
+    }
+
+    /// The subspace VOLE's U rows, rejoined
+    pub fn u_rows(&self) -> FMatrix<T> {
+        FMatrix([self.u1.0.as_slice(), &self.u2.0].concat())
+    }
+
+    /// The subspace VOLE's V rows, rejoined
+    pub fn v_rows(&self) -> FMatrix<T> {
+        FMatrix([self.v1.0.as_slice(), &self.v2.0].concat())
+    }
+
+    /// The v value of the witness value at `index`, i.e. the value the verifier's q at `index` is `witness[index]`∆' plus,
+    /// for rows of `k` values. Opening it with the witness value discloses the value
+    pub fn v_at(&self, index: usize, k: usize) -> T::Ext {
+        let h = self.u1.0.len();
+        let coefficients = (0..T::Ext::DEGREE)
+            .map(|j| self.u2.0[j * h + index / k].0[index % k])
+            .collect::<Vec<_>>();
+        T::Ext::from_coefficients(&coefficients)
+    }
+
+    /// The prover's side of the VOLE with ∆': u, the witness followed by U1's rows after it, and v, Σ_j e_j U2_j,
+    /// both flattened row by row. `witness` must have fewer rows than U1
+    pub fn vole(&self, witness: &FMatrix<T>) -> (FVec<T>, Vec<T::Ext>) {
+        assert!(
+            witness.0.len() < self.u1.0.len(),
+            "witness must have fewer rows than U1"
+        );
+        assert!(
+            witness
+                .0
+                .iter()
+                .all(|row| row.0.len() == self.u1.0[0].0.len()),
+            "witness rows must be as long as U1's"
+        );
+        let u = witness
+            .0
+            .iter()
+            .chain(&self.u1.0[witness.0.len()..])
+            .flat_map(|row| row.0.iter().copied())
+            .collect();
+        let h = self.u1.0.len();
+        let blocks = self.u2.0.chunks(h).collect::<Vec<_>>();
+        let mut coefficients = Vec::with_capacity(blocks.len());
+        let v = (0..h)
+            .flat_map(|row| (0..self.u1.0[0].0.len()).map(move |col| (row, col)))
+            .map(|(row, col)| {
+                coefficients.clear();
+                coefficients.extend(blocks.iter().map(|b| b[row].0[col]));
+                T::Ext::from_coefficients(&coefficients)
+            })
+            .collect();
+        (FVec(u), v)
+    }
+
+    /// Opens VitH for ∆' = `vith_delta`: S, the blocks U1∆'_j + U2_j one after another, and the proof that S was made
+    /// from the subspace VOLE, `challenge` times (V1∆' + Σ_j e_j V2_j) transposed, flattened coordinate by coordinate
+    pub fn open(&self, vith_delta: &T::Ext, challenge: &[T::Ext]) -> (FMatrix<T>, FVec<T>) {
+        let h = self.u1.0.len();
+        let s = vith_delta
+            .coefficients()
+            .into_iter()
+            .zip(self.u2.0.chunks(h))
+            .flat_map(|(d, u2)| mul_add(&self.u1.0, d, u2))
+            .collect();
+        let proof = combine_blocks::<T>(
+            challenge,
+            vith_delta,
+            &self.v1,
+            &self.v2.0.chunks(h).collect::<Vec<_>>(),
+        );
+        (FMatrix(s), flatten::<T>(&proof))
+    }
+}
+
+/// `x` * `a` + `y`, row by row with one allocation per row
+fn mul_add<T: PF>(x: &[FVec<T>], a: T, y: &[FVec<T>]) -> Vec<FVec<T>> {
+    x.iter()
+        .zip(y.iter())
+        .map(|(x, y)| {
+            let mut row = x.clone();
+            row.mul_add_assign(a, y);
+            row
+        })
+        .collect()
+}
+
+/// `challenge` times (`first`∆' + Σ_j e_j `blocks`[j]), computed as a combination of the rows of each matrix over `T`
+fn combine_blocks<T: PF>(
+    challenge: &[T::Ext],
+    delta: &T::Ext,
+    first: &FMatrix<T>,
+    blocks: &[&[FVec<T>]],
+) -> Vec<T::Ext> {
+    let mut combined = LayoutMatrix::from_rows(first.clone()).row_combination_ext(challenge);
+    combined.iter_mut().for_each(|x| *x *= *delta);
+    for (e, block) in basis::<T>().into_iter().zip(blocks) {
+        let block = LayoutMatrix::from_rows(FMatrix(block.to_vec())).row_combination_ext(challenge);
+        combined
+            .iter_mut()
+            .zip(block)
+            .for_each(|(c, b)| *c += e * b);
+    }
+    combined
+}
+
+/// The basis e_j of `T`'s challenge field whose coefficients are ∆'s coordinates
+fn basis<T: PF>() -> Vec<T::Ext> {
+    (0..T::Ext::DEGREE)
+        .map(|j| {
+            let mut unit = vec![T::ZERO; T::Ext::DEGREE];
+            unit[j] = T::ONE;
+            T::Ext::from_coefficients(&unit)
+        })
+        .collect()
+}
+
+/// A vector of `T`'s challenge field as its coordinates over `T`, one after another
+fn flatten<T: PF>(vec: &[T::Ext]) -> FVec<T> {
+    FVec(coordinates(vec).into_iter().flat_map(|c| c.0).collect())
+}
+
+/// The verifier's half of VitH, once it has chosen ∆'
+pub struct VithVerifier<T: PF> {
+    delta: T::Ext,
+}
+
+impl<T: PF> VithVerifier<T> {
+    pub fn new(vith_delta: T::Ext) -> Self {
+        Self { delta: vith_delta }
+    }
+
+    /// ∆'
+    pub fn delta(&self) -> T::Ext {
+        self.delta
+    }
+
+    /// The verifier's side of the VOLE with ∆': q = Σ_j e_j S_j + the witness commitment times ∆', flattened row by row.
+    /// Its rows after the commitment's are S's alone
+    pub fn vole(&self, s_matrix: &FMatrix<T>, witness_comm: &FMatrix<T>) -> Vec<T::Ext> {
+        let delta = self.delta.coefficients();
+        let h = s_matrix.0.len() / delta.len();
+        let blocks = s_matrix.0.chunks(h.max(1)).collect::<Vec<_>>();
+        let row_len = s_matrix.0.first().map_or(0, |row| row.0.len());
+        let mut coefficients = Vec::with_capacity(delta.len());
+        (0..h)
+            .flat_map(|row| (0..row_len).map(move |col| (row, col)))
+            .map(|(row, col)| {
+                let comm = witness_comm.0.get(row).map(|r| r.0[col]);
+                coefficients.clear();
+                coefficients.extend(blocks.iter().zip(&delta).map(|(b, d)| match comm {
+                    Some(c) => b[row].0[col] + c * *d,
+                    None => b[row].0[col],
+                }));
+                T::Ext::from_coefficients(&coefficients)
+            })
+            .collect()
+    }
+
+    /// Residual of the check that S and its proof were made from the subspace VOLE whose ∆s are `deltas` and whose
+    /// corrected Q rows are `q_rows`, for the same `challenge` as `VithProver::open`, flattened coordinate by coordinate.
+    /// It is zero iff the check passes. Fails if any of them has the wrong dimensions for `code`
+    pub fn s_matrix_residual(
+        &self,
+        code: &impl LinearCode,
+        deltas: &FVec<T>,
+        q_rows: &FMatrix<T>,
+        s_matrix: &FMatrix<T>,
+        s_consistency_check: &FVec<T>,
+        challenge: &[T::Ext],
+    ) -> Result<FVec<T>, VerifyError> {
+        let degree = T::Ext::DEGREE;
+        let h = vole_rows::<T>(q_rows.0.len());
+        if !q_rows.0.len().is_multiple_of(degree + 1)
+            || q_rows.0.iter().any(|row| row.0.len() != code.n())
+        {
+            return Err(VerifyError::Malformed(
+                "Q must have a multiple of one more than the challenge field's degree rows as long as the code's length",
+            ));
+        }
+        if deltas.0.len() != code.n() || challenge.len() != h {
+            return Err(VerifyError::Malformed(
+                "∆s or S matrix challenge have the wrong length",
+            ));
+        }
+        if s_matrix.0.len() != degree * h
+            || s_matrix.0.iter().any(|row| row.0.len() != code.k())
+            || s_consistency_check.0.len() != degree * code.n()
+        {
+            return Err(VerifyError::Malformed(
+                "S matrix or its consistency check has the wrong dimensions",
+            ));
+        }
+        let q1 = FMatrix(q_rows.0[..h].to_vec());
+        let lhs = combine_blocks::<T>(
+            challenge,
+            &self.delta,
+            &q1,
+            &q_rows.0[h..].chunks(h.max(1)).collect::<Vec<_>>(),
+        );
+        let sgc_diag_delta = code
+            .batch_encode(&s_matrix.0)
+            .iter()
+            .map(|row| row * deltas)
+            .collect::<Vec<FVec<T>>>();
+        let mut rhs = vec![T::Ext::from(T::ZERO); code.n()];
+        for (e, block) in basis::<T>()
+            .into_iter()
+            .zip(sgc_diag_delta.chunks(h.max(1)))
+        {
+            let block =
+                LayoutMatrix::from_rows(FMatrix(block.to_vec())).row_combination_ext(challenge);
+            rhs.iter_mut().zip(block).for_each(|(r, b)| *r += e * b);
+        }
+        let residual = lhs
+            .into_iter()
+            .zip(rhs)
+            .map(|(l, r)| l - r)
+            .collect::<Vec<_>>();
+        Ok(&flatten::<T>(&residual) - s_consistency_check)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use rand::thread_rng;
+
+    use super::*;
+    use crate::{fields::m31::M31, subspacevole::RAAACode, Fr};
+
+    fn round_trip<T: PF>() {
+        let code = RAAACode::rand_default();
+        let (k, n, degree) = (code.k(), code.n(), T::Ext::DEGREE);
+        let random_rows = |rows: usize, len: usize| {
+            FMatrix(
+                (0..rows)
+                    .map(|_| FVec((0..len).map(|_| T::random(&mut thread_rng())).collect()))
+                    .collect(),
+            )
+        };
+        // A subspace VOLE of 3 rows per part: Q = V + (UG) * ∆s
+        let rows = 3 * (degree + 1);
+        let u_rows = random_rows(rows, k);
+        let v_rows = random_rows(rows, n);
+        let deltas = random_rows(1, n).0.remove(0);
+        let q_rows = FMatrix(
+            code.batch_encode(&u_rows.0)
+                .iter()
+                .zip(v_rows.0.iter())
+                .map(|(uc, v)| v + &(uc * &deltas))
+                .collect(),
+        );
+        let witness = random_rows(2, k);
+
+        let prover = VithProver::from_subspace_vole(u_rows.clone(), v_rows.clone()).unwrap();
+        assert_eq!(prover.u_rows(), u_rows);
+        assert_eq!(prover.v_rows(), v_rows);
+        let witness_comm = prover.commit_witness(&witness);
+
+        let verifier = VithVerifier::<T>::new(T::Ext::sample(thread_rng()));
+        let challenge = (0..3)
+            .map(|_| T::Ext::sample(thread_rng()))
+            .collect::<Vec<_>>();
+        let (s, proof) = prover.open(&verifier.delta(), &challenge);
+        assert_eq!((s.0.len(), proof.0.len()), (3 * degree, degree * n));
+        let residual = verifier
+            .s_matrix_residual(&code, &deltas, &q_rows, &s, &proof, &challenge)
+            .unwrap();
+        assert!(residual.is_zero());
+        // Q whose rows can't be split is rejected rather than split unevenly
+        let uneven_q = FMatrix(q_rows.0[..rows - 1].to_vec());
+        assert!(verifier
+            .s_matrix_residual(&code, &deltas, &uneven_q, &s, &proof, &challenge)
+            .is_err());
+
+        // The VOLE the ZKP runs on, whose first values are the witness's
+        let (u, v) = prover.vole(&witness);
+        let q = verifier.vole(&s, &witness_comm);
+        assert_eq!(u.0[..k], witness.0[0].0[..]);
+        assert_eq!(prover.v_at(k + 1, k), v[k + 1]);
+        assert_eq!((u.0.len(), q.len()), (3 * k, 3 * k));
+        for i in 0..q.len() {
+            assert_eq!(q[i], verifier.delta() * u.0[i] + v[i]);
+        }
+
+        // S from another ∆' fails the check
+        let (forged, proof) = prover.open(&(verifier.delta() + T::Ext::from(T::ONE)), &challenge);
+        let residual = verifier
+            .s_matrix_residual(&code, &deltas, &q_rows, &forged, &proof, &challenge)
+            .unwrap();
+        assert!(!residual.is_zero());
+        assert!(
+            VithProver::from_subspace_vole(random_rows(rows - 1, k), random_rows(rows - 1, n))
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn vith_round_trip() {
+        round_trip::<Fr>();
+        // ∆' from QM31, with U2 split into 4 blocks
+        round_trip::<M31>();
+    }
+}
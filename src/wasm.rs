@@ -0,0 +1,55 @@
+//! wasm-bindgen bindings for proving and verifying in the browser.
+//! Circuits and witnesses are circom's .r1cs and .wtns files, and proofs are in the encoding of `CommitAndProof::to_bytes`.
+use std::io::Cursor;
+
+use anyhow::Error;
+use wasm_bindgen::prelude::*;
+
+use crate::{
+    actors::actors::{CommitAndProof, Prover, Verifier},
+    circom::{r1cs::R1CSFile, witness::wtns_from_reader},
+    zkp::R1CSWithMetadata,
+    Fr,
+};
+
+/// Proves the witness in `wtns_bytes` satisfies the circuit in `r1cs_bytes`, returning the encoded commitment and proof
+#[wasm_bindgen]
+pub fn prove(r1cs_bytes: &[u8], wtns_bytes: &[u8]) -> Result<Vec<u8>, JsError> {
+    prove_bytes(r1cs_bytes, wtns_bytes).map_err(|e| JsError::new(&e.to_string()))
+}
+
+/// Returns whether the encoded proof in `proof_bytes` verifies for the circuit in `r1cs_bytes`.
+/// Throws if either can't be decoded
+#[wasm_bindgen]
+pub fn verify(proof_bytes: &[u8], r1cs_bytes: &[u8]) -> Result<bool, JsError> {
+    verify_bytes(proof_bytes, r1cs_bytes).map_err(|e| JsError::new(&e.to_string()))
+}
+
+fn read_circuit(r1cs_bytes: &[u8]) -> Result<R1CSWithMetadata<Fr>, Error> {
+    Ok(R1CSFile::from_reader(Cursor::new(r1cs_bytes))?.to_crate_format()?)
+}
+
+fn prove_bytes(r1cs_bytes: &[u8], wtns_bytes: &[u8]) -> Result<Vec<u8>, Error> {
+    let file: R1CSFile = R1CSFile::from_reader(Cursor::new(r1cs_bytes))?;
+    let witness = file.wire_witness(wtns_from_reader(wtns_bytes)?)?;
+    let circuit = file.to_crate_format()?;
+    let mut prover = Prover::from_witness_and_circuit_unpadded(witness, circuit);
+    Ok(prover.commit_and_prove()?.to_bytes()?)
+}
+
+fn verify_bytes(proof_bytes: &[u8], r1cs_bytes: &[u8]) -> Result<bool, Error> {
+    let cnp = CommitAndProof::<Fr>::from_bytes(proof_bytes)?;
+    let verifier = Verifier::from_circuit(read_circuit(r1cs_bytes)?);
+    Ok(verifier.verify(&cnp).is_ok())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn malformed_inputs_are_errors() {
+        assert!(prove_bytes(b"not an r1cs file", b"not a wtns file").is_err());
+        assert!(verify_bytes(b"not a proof", b"not an r1cs file").is_err());
+    }
+}
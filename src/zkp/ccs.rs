@@ -0,0 +1,155 @@
+//! Constraint systems other than R1CS. Circuits from front-ends that emit CCS or Plonkish gates are proven
+//! with the same higher degree Quicksilver check as custom gates, alongside the circuit's R1CS, which is then usually empty.
+use alloc::collections::BTreeMap;
+
+use super::{quicksilver::CustomGate, R1CSWithMetadata, SparseR1CS, R1CS};
+#[cfg(not(feature = "std"))]
+use crate::alloc_prelude::*;
+use crate::{fields::ext::ExtensionField, FVec, SparseFMatrix, PF};
+use serde::{Deserialize, Serialize};
+
+/// Customizable constraint system: each row i must satisfy Σ_t c_t ∏_{j ∈ S_t} (M_j z)_i = 0, where `terms` holds each (c_t, S_t).
+/// The matrices must have the same number of rows. R1CS is the special case with terms (1, {A, B}) and (-1, {C})
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct CCS<T: PF> {
+    pub matrices: Vec<SparseFMatrix<T>>,
+    /// Coefficient and the indices of the matrices whose products are multiplied together in each term.
+    /// An index may repeat
+    pub terms: Vec<(T, Vec<usize>)>,
+}
+
+impl<T: PF> CCS<T> {
+    pub fn from_r1cs(r1cs: R1CS<T>) -> Self {
+        let SparseR1CS {
+            a_rows,
+            b_rows,
+            c_rows,
+        } = r1cs.to_sparse();
+        Self {
+            matrices: vec![a_rows, b_rows, c_rows],
+            terms: vec![(T::ONE, vec![0, 1]), (-T::ONE, vec![2])],
+        }
+    }
+
+    /// Highest number of matrices in a term
+    pub fn degree(&self) -> usize {
+        self.terms.iter().map(|(_, s)| s.len()).max().unwrap_or(0)
+    }
+
+    pub fn num_rows(&self) -> usize {
+        self.matrices.first().map_or(0, |m| m.0.len())
+    }
+
+    /// Each matrix times `z`, padded with zeros to the number of rows
+    pub(crate) fn products(&self, z: &FVec<T>) -> Vec<FVec<T>> {
+        let rows = self.num_rows();
+        self.matrices
+            .iter()
+            .map(|m| {
+                let mut product = z * m;
+                product.0.resize(rows, T::ZERO);
+                product
+            })
+            .collect()
+    }
+
+    /// `products` for a vector of an extension of `T`
+    pub(crate) fn products_ext<E: ExtensionField<T>>(&self, z: &[E]) -> Vec<Vec<E>> {
+        let rows = self.num_rows();
+        self.matrices
+            .iter()
+            .map(|m| {
+                let mut product = m.mul_ext(z);
+                product.resize(rows, E::from(T::ZERO));
+                product
+            })
+            .collect()
+    }
+
+    /// Whether `z` satisfies every row
+    pub fn is_satisfied(&self, z: &FVec<T>) -> bool {
+        let products = self.products(z);
+        (0..self.num_rows()).all(|i| {
+            self.terms
+                .iter()
+                .map(|(c, s)| s.iter().fold(*c, |acc, j| acc * products[*j].0[i]))
+                .sum::<T>()
+                == T::ZERO
+        })
+    }
+}
+
+/// A Plonk gate q_L a + q_R b + q_O c + q_M ab + q_C = 0 on the witness indices `a`, `b`, and `c`
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct PlonkGate<T: PF> {
+    pub q_l: T,
+    pub q_r: T,
+    pub q_o: T,
+    pub q_m: T,
+    pub q_c: T,
+    pub a: usize,
+    pub b: usize,
+    pub c: usize,
+}
+
+impl<T: PF> PlonkGate<T> {
+    pub fn to_custom_gate(&self) -> CustomGate<T> {
+        CustomGate::new(
+            vec![self.q_l, self.q_r, self.q_o, self.q_m, self.q_c],
+            vec![
+                vec![self.a],
+                vec![self.b],
+                vec![self.c],
+                vec![self.a, self.b],
+                vec![],
+            ],
+        )
+    }
+}
+
+/// The constraint systems a circuit can be given in
+#[derive(Clone, Serialize, Deserialize)]
+pub enum ConstraintSystem<T: PF> {
+    R1CS(R1CS<T>),
+    CCS(CCS<T>),
+    Plonkish(Vec<PlonkGate<T>>),
+}
+
+impl<T: PF> ConstraintSystem<T> {
+    /// Makes a circuit that can be proven and verified from the constraint system
+    pub fn with_metadata(
+        self,
+        public_inputs_indices: Vec<usize>,
+        public_outputs_indices: Vec<usize>,
+        unpadded_wtns_len: usize,
+    ) -> R1CSWithMetadata<T> {
+        let empty = || {
+            R1CS::Sparse(SparseR1CS {
+                a_rows: SparseFMatrix(vec![]),
+                b_rows: SparseFMatrix(vec![]),
+                c_rows: SparseFMatrix(vec![]),
+            })
+        };
+        let (r1cs, ccs, custom_gates) = match self {
+            Self::R1CS(r1cs) => (r1cs, vec![], vec![]),
+            Self::CCS(ccs) => (empty(), vec![ccs], vec![]),
+            Self::Plonkish(gates) => (
+                empty(),
+                vec![],
+                gates.iter().map(|g| g.to_custom_gate()).collect(),
+            ),
+        };
+        R1CSWithMetadata {
+            r1cs,
+            public_inputs_indices,
+            public_outputs_indices,
+            unpadded_wtns_len,
+            custom_gates,
+            lookups: vec![],
+            memories: vec![],
+            branches: vec![],
+            ccs,
+            signal_names: BTreeMap::new(),
+        }
+    }
+}
@@ -0,0 +1,89 @@
+//! Lookup arguments: constraints that wires take values from a precomputed table, such as a range or an S-box.
+//! Proving x is in a table of 2^16 values takes one gate here rather than the 16 bit decomposition constraints and more it takes in R1CS.
+//!
+//! This is the log-derivative multiset check. The wires f_i are in a table t_j iff there are multiplicities m_j with
+//! Σ_i 1/(α - f_i) = Σ_j m_j/(α - t_j) for a random α. The prover commits to the witness and the multiplicities, then learns α
+//! and commits to each h_i = 1/(α - f_i). The Quicksilver proof then shows h_i(α - f_i) = 1 for each wire, and
+//! Σ_i h_i = Σ_j m_j/(α - t_j), which is linear since the t_j are public.
+//!
+//! The multiplicities and inverses are appended to the witness by the prover, so circuits only declare the lookups.
+//! See `AuxLayout` for where they go
+use super::quicksilver::CustomGate;
+#[cfg(not(feature = "std"))]
+use crate::alloc_prelude::*;
+use crate::{FVec, PF};
+use serde::{Deserialize, Serialize};
+
+/// Constrains every wire in `wires` to equal some value in `table`
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Lookup<T: PF> {
+    pub table: Vec<T>,
+    /// Witness indices of the looked up values
+    pub wires: Vec<usize>,
+}
+
+/// Number of multiplicities and of inverses the prover adds to the witness for `lookups`
+pub fn num_values<T: PF>(lookups: &[Lookup<T>]) -> (usize, usize) {
+    (
+        lookups.iter().map(|l| l.table.len()).sum(),
+        lookups.iter().map(|l| l.wires.len()).sum(),
+    )
+}
+
+/// Writes the number of times each table value is looked up into a padded `witness`, starting at index `start`.
+/// Values missing from a table aren't counted, which leaves the lookup unsatisfied
+pub fn fill_multiplicities<T: PF>(lookups: &[Lookup<T>], witness: &mut FVec<T>, mut start: usize) {
+    for lookup in lookups.iter() {
+        for wire in lookup.wires.iter() {
+            if let Some(j) = lookup.table.iter().position(|t| *t == witness.0[*wire]) {
+                witness.0[start + j] += T::ONE;
+            }
+        }
+        start += lookup.table.len();
+    }
+}
+
+/// The inverses 1/(α - f_i) for each looked up value f_i, where `witness_at` gives the value of a witness index.
+/// If α happens to equal a looked up value, its inverse is zero and the proof will fail
+pub fn inverses<T: PF>(
+    lookups: &[Lookup<T>],
+    alpha: &T,
+    witness_at: impl Fn(usize) -> T,
+) -> Vec<T> {
+    lookups
+        .iter()
+        .flat_map(|l| l.wires.iter())
+        .map(|wire| (*alpha - witness_at(*wire)).invert().unwrap_or(T::ZERO))
+        .collect()
+}
+
+/// The custom gates `lookups` reduce to once α is known, with their multiplicities and inverses at the given indices
+pub fn gates<T: PF>(
+    lookups: &[Lookup<T>],
+    alpha: &T,
+    mut multiplicity: usize,
+    mut inverse: usize,
+) -> Vec<CustomGate<T>> {
+    let mut gates = Vec::new();
+    for lookup in lookups.iter() {
+        let mut sum_coeffs = Vec::with_capacity(lookup.wires.len() + lookup.table.len());
+        let mut sum_wires = Vec::with_capacity(lookup.wires.len() + lookup.table.len());
+        for wire in lookup.wires.iter() {
+            // h * α - h * f - 1 = 0
+            gates.push(CustomGate::new(
+                vec![*alpha, -T::ONE, -T::ONE],
+                vec![vec![inverse], vec![inverse, *wire], vec![]],
+            ));
+            sum_coeffs.push(T::ONE);
+            sum_wires.push(vec![inverse]);
+            inverse += 1;
+        }
+        for t in lookup.table.iter() {
+            sum_coeffs.push(-(*alpha - t).invert().unwrap_or(T::ZERO));
+            sum_wires.push(vec![multiplicity]);
+            multiplicity += 1;
+        }
+        gates.push(CustomGate::new(sum_coeffs, sum_wires));
+    }
+    gates
+}
@@ -0,0 +1,230 @@
+//! Read/write memory with constraints linear in the number of accesses, rather than the multiplexers over every address
+//! a circom circuit needs for each dynamic array access.
+//!
+//! This is offline memory checking with timestamps. Access i happens at time i + 1 and reads a tuple (address, value, time)
+//! written by an earlier access, or by initialization at time 0, then writes (address, value, i + 1). Memory is consistent iff
+//! every read tuple's time is before its access, and the tuples written by initialization and the accesses are, as a multiset,
+//! the tuples read by the accesses plus one final tuple per address.
+//!
+//! The prover adds each access's read time, the old value each write overwrites, and the final tuples to the witness.
+//! Read times are range checked with a lookup, and the multiset equality is the same log-derivative check as lookups:
+//! with tuples compressed to a + γv + γ²t, Σ 1/(α - x) over the written tuples equals Σ 1/(α - y) over the read ones.
+//! Since the initial tuples are public, their terms are constants
+use alloc::collections::BTreeMap;
+
+use super::{lookup::Lookup, quicksilver::CustomGate};
+#[cfg(not(feature = "std"))]
+use crate::alloc_prelude::*;
+use crate::{FVec, PF};
+use serde::{Deserialize, Serialize};
+
+/// One read or write of a memory. Addresses and values are witness indices, so which address is accessed can depend on the witness
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct Access {
+    pub address: usize,
+    /// The value read, or the value written if `write`
+    pub value: usize,
+    pub write: bool,
+}
+
+/// A memory whose addresses are 0 to `init.len()` - 1, accessed in the order of `accesses`
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Memory<T: PF> {
+    /// Public initial value of each address
+    pub init: Vec<T>,
+    pub accesses: Vec<Access>,
+}
+
+impl<T: PF> Memory<T> {
+    /// A memory of `size` addresses initialized to zero
+    pub fn zeroed(size: usize) -> Self {
+        Self {
+            init: vec![T::ZERO; size],
+            accesses: vec![],
+        }
+    }
+
+    pub fn read(&mut self, address: usize, value: usize) {
+        self.accesses.push(Access {
+            address,
+            value,
+            write: false,
+        });
+    }
+
+    pub fn write(&mut self, address: usize, value: usize) {
+        self.accesses.push(Access {
+            address,
+            value,
+            write: true,
+        });
+    }
+
+    fn num_writes(&self) -> usize {
+        self.accesses.iter().filter(|a| a.write).count()
+    }
+
+    /// Number of values the prover adds to the witness before the challenges are known:
+    /// a read time and its distance from the access's time per access, an old value per write, and a final value and time per address
+    pub fn num_values(&self) -> usize {
+        2 * self.accesses.len() + self.num_writes() + 2 * self.init.len()
+    }
+
+    /// Number of inverses the prover adds once the challenges are known: one per read tuple, written tuple, and final tuple
+    pub fn num_inverses(&self) -> usize {
+        2 * self.accesses.len() + self.init.len()
+    }
+
+    /// Range check of each access's read time, given the memory's values start at index `start`
+    pub fn range_lookup(&self, start: usize) -> Lookup<T> {
+        let n = self.accesses.len();
+        Lookup {
+            table: (0..n as u64).map(T::from).collect(),
+            wires: (0..n).map(|i| start + 2 * i + 1).collect(),
+        }
+    }
+
+    /// Index of the value access `i` reads, which is the old value for writes
+    fn read_value(&self, start: usize, i: usize) -> usize {
+        let access = &self.accesses[i];
+        match access.write {
+            false => access.value,
+            true => {
+                let writes_before = self.accesses[..i].iter().filter(|a| a.write).count();
+                start + 2 * self.accesses.len() + writes_before
+            }
+        }
+    }
+
+    fn final_value(&self, start: usize, address: usize) -> usize {
+        start + 2 * self.accesses.len() + self.num_writes() + address
+    }
+
+    fn final_time(&self, start: usize, address: usize) -> usize {
+        self.final_value(start, address) + self.init.len()
+    }
+
+    /// Runs the accesses on the witness's values to write the memory's values into a padded `witness`, starting at index `start`.
+    /// Accesses to addresses outside the memory are skipped, which leaves the memory check unsatisfied
+    pub fn fill_values(&self, witness: &mut FVec<T>, start: usize) {
+        let addresses = (0..self.init.len())
+            .map(|a| (T::from(a as u64).to_repr().as_ref().to_vec(), a))
+            .collect::<BTreeMap<_, _>>();
+        let mut values = self.init.clone();
+        let mut times = vec![0u64; self.init.len()];
+        for (i, access) in self.accesses.iter().enumerate() {
+            let Some(&a) = addresses.get(witness.0[access.address].to_repr().as_ref()) else {
+                continue;
+            };
+            witness.0[start + 2 * i] = T::from(times[a]);
+            witness.0[start + 2 * i + 1] = T::from(i as u64 - times[a]);
+            if access.write {
+                witness.0[self.read_value(start, i)] = values[a];
+                values[a] = witness.0[access.value];
+            }
+            times[a] = i as u64 + 1;
+        }
+        for a in 0..self.init.len() {
+            witness.0[self.final_value(start, a)] = values[a];
+            witness.0[self.final_time(start, a)] = T::from(times[a]);
+        }
+    }
+
+    /// The tuples whose inverses are committed to, as (constant, [(coefficient, wire)]) linear combinations of the witness:
+    /// the written tuples, then the read tuples, then the final tuples
+    fn tuples(&self, gamma: &T, start: usize) -> Vec<(T, Vec<(T, usize)>)> {
+        let gamma_sq = gamma.square();
+        let written = self.accesses.iter().enumerate().map(|(i, access)| {
+            (
+                gamma_sq * T::from(i as u64 + 1),
+                vec![(T::ONE, access.address), (*gamma, access.value)],
+            )
+        });
+        let read = self.accesses.iter().enumerate().map(|(i, access)| {
+            (
+                T::ZERO,
+                vec![
+                    (T::ONE, access.address),
+                    (*gamma, self.read_value(start, i)),
+                    (gamma_sq, start + 2 * i),
+                ],
+            )
+        });
+        let last = (0..self.init.len()).map(|a| {
+            (
+                T::from(a as u64),
+                vec![
+                    (*gamma, self.final_value(start, a)),
+                    (gamma_sq, self.final_time(start, a)),
+                ],
+            )
+        });
+        written.chain(read).chain(last).collect()
+    }
+
+    /// The inverses 1/(α - x) of the compressed written, read, and final tuples, where `witness_at` gives the value of a witness index
+    pub fn inverses(
+        &self,
+        alpha: &T,
+        gamma: &T,
+        start: usize,
+        witness_at: impl Fn(usize) -> T,
+    ) -> Vec<T> {
+        self.tuples(gamma, start)
+            .into_iter()
+            .map(|(constant, terms)| {
+                let x = terms
+                    .iter()
+                    .fold(constant, |acc, (c, wire)| acc + *c * witness_at(*wire));
+                (*alpha - x).invert().unwrap_or(T::ZERO)
+            })
+            .collect()
+    }
+
+    /// The custom gates the memory check reduces to once α and γ are known, with its values and inverses starting at the given indices.
+    /// The read times' range check is a separate lookup; see `range_lookup`
+    pub fn gates(&self, alpha: &T, gamma: &T, start: usize, inverses: usize) -> Vec<CustomGate<T>> {
+        let n = self.accesses.len();
+        // Each read time is its access's time minus one minus its distance from it
+        let mut gates = (0..n)
+            .map(|i| {
+                CustomGate::new(
+                    vec![T::ONE, T::ONE, -T::from(i as u64)],
+                    vec![vec![start + 2 * i], vec![start + 2 * i + 1], vec![]],
+                )
+            })
+            .collect::<Vec<_>>();
+
+        let tuples = self.tuples(gamma, start);
+        let mut sum_coeffs = Vec::with_capacity(tuples.len() + 1);
+        let mut sum_wires = Vec::with_capacity(tuples.len() + 1);
+        for (j, (constant, terms)) in tuples.into_iter().enumerate() {
+            let h = inverses + j;
+            // h * (α - x) - 1 = 0
+            let mut coeffs = vec![*alpha - constant, -T::ONE];
+            let mut wires = vec![vec![h], vec![]];
+            for (c, wire) in terms {
+                coeffs.push(-c);
+                wires.push(vec![h, wire]);
+            }
+            gates.push(CustomGate::new(coeffs, wires));
+            // Written tuples add to the sum and read and final tuples subtract from it
+            sum_coeffs.push(if j < n { T::ONE } else { -T::ONE });
+            sum_wires.push(vec![h]);
+        }
+        let init_sum = self
+            .init
+            .iter()
+            .enumerate()
+            .map(|(a, v)| {
+                (*alpha - T::from(a as u64) - *gamma * v)
+                    .invert()
+                    .unwrap_or(T::ZERO)
+            })
+            .sum::<T>();
+        sum_coeffs.push(init_sum);
+        sum_wires.push(vec![]);
+        gates.push(CustomGate::new(sum_coeffs, sum_wires));
+        gates
+    }
+}
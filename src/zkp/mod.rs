@@ -1,5 +1,19 @@
-use crate::{FMatrix, FVec, SparseFMatrix, PF};
+use alloc::{
+    borrow::Cow,
+    collections::{BTreeMap, BTreeSet},
+};
+
+#[cfg(not(feature = "std"))]
+use crate::alloc_prelude::*;
+use crate::{
+    error::{ProofError, UnsatisfiedConstraint},
+    fields::ext::ExtensionField,
+    DataSize, FMatrix, FVec, SparseFMatrix, SparseVec, PF,
+};
 use serde::{Deserialize, Serialize};
+pub mod ccs;
+pub mod lookup;
+pub mod memory;
 #[derive(Clone, Serialize, Deserialize)]
 pub struct FullR1CS<T: PF> {
     pub a_rows: FMatrix<T>,
@@ -24,8 +38,26 @@ pub struct R1CSWithMetadata<T: PF> {
     pub public_inputs_indices: Vec<usize>,
     pub public_outputs_indices: Vec<usize>,
     pub unpadded_wtns_len: usize,
+    /// Polynomial constraints checked in addition to the R1CS
+    #[serde(default)]
+    pub custom_gates: Vec<quicksilver::CustomGate<T>>,
+    /// Table membership constraints, which the prover adds values to the witness for
+    #[serde(default)]
+    pub lookups: Vec<lookup::Lookup<T>>,
+    /// Read/write memories, which the prover adds values to the witness for
+    #[serde(default)]
+    pub memories: Vec<memory::Memory<T>>,
+    /// Alternatives to `r1cs`: the circuit is satisfied if `r1cs` or any one of these is. See `or`
+    #[serde(default)]
+    pub branches: Vec<R1CS<T>>,
+    /// Customizable constraint systems checked in addition to the R1CS. See `ccs::ConstraintSystem`
+    #[serde(default)]
+    pub ccs: Vec<ccs::CCS<T>>,
+    /// Names of witness indices, e.g. circom signal names from a .sym file. They don't change the statement, so aren't part of the circuit ID
+    #[serde(default)]
+    pub signal_names: BTreeMap<String, usize>,
 }
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct PadParams {
     pub orig_wtns_len: usize,
     pub padded_wtns_len: usize,
@@ -34,15 +66,100 @@ pub struct PadParams {
     /// When a matrix is formed via chunking the padded witness, this value represents how many rows it has
     pub num_padded_wtns_rows: usize,
 }
+/// Where the values the prover adds to the witness for lookups and memories go in the padded witness.
+/// The lookup challenges must be derived after the memories' values and the multiplicities are committed to but before the inverses are,
+/// so the inverses start a new row of the witness commitment
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AuxLayout {
+    /// Index of the first memory value. Each memory's values follow the witness in the order of the memories
+    pub memory_start: usize,
+    /// Index of the first multiplicity. The multiplicities of each lookup's table follow the memories' values in the order of the lookups,
+    /// followed by those of the memories' range checks
+    pub multiplicities_start: usize,
+    /// Rows of the witness commitment the lookup challenges are derived from
+    pub committed_rows: usize,
+    /// Index of the first lookup inverse, at the start of the row after `committed_rows`. Inverses are in the order of the lookups' wires
+    pub inverses_start: usize,
+    /// Index of the first memory inverse, after the lookup inverses
+    pub memory_inverses_start: usize,
+    /// Rows of the padded witness including the inverses
+    pub num_rows: usize,
+}
 impl<T: PF> R1CS<T> {
     /// Returns Av, Bv, Cv for a vector v
-    fn vec_mul(&self, v: &FVec<T>) -> (FVec<T>, FVec<T>, FVec<T>) {
+    pub(crate) fn vec_mul(&self, v: &FVec<T>) -> (FVec<T>, FVec<T>, FVec<T>) {
         match self {
             Self::Sparse(s) => (v * &s.a_rows, v * &s.b_rows, v * &s.c_rows),
             Self::Full(f) => (v * &f.a_rows, v * &f.b_rows, v * &f.c_rows),
         }
     }
 
+    /// `vec_mul` for a vector of an extension of `T`, e.g. the VOLE's v and q values over `T`'s challenge field
+    pub(crate) fn vec_mul_ext<E: ExtensionField<T>>(&self, v: &[E]) -> (Vec<E>, Vec<E>, Vec<E>) {
+        match self {
+            Self::Sparse(s) => (
+                s.a_rows.mul_ext(v),
+                s.b_rows.mul_ext(v),
+                s.c_rows.mul_ext(v),
+            ),
+            Self::Full(f) => (
+                f.a_rows.mul_ext(v),
+                f.b_rows.mul_ext(v),
+                f.c_rows.mul_ext(v),
+            ),
+        }
+    }
+
+    /// The constraints `v` doesn't satisfy, with their evaluated linear combinations
+    fn unsatisfied(&self, v: &FVec<T>) -> Vec<UnsatisfiedConstraint<T>> {
+        let (a, b, c) = self.vec_mul(v);
+        a.0.into_iter()
+            .zip(b.0)
+            .zip(c.0)
+            .enumerate()
+            .filter(|(_, ((a, b), c))| *a * b != *c)
+            .map(|(index, ((a, b), c))| UnsatisfiedConstraint { index, a, b, c })
+            .collect()
+    }
+
+    pub fn num_constraints(&self) -> usize {
+        match self {
+            Self::Sparse(s) => s.a_rows.0.len(),
+            Self::Full(f) => f.a_rows.0.len(),
+        }
+    }
+
+    /// Witness indices with a nonzero coefficient in any of the constraint's rows of A, B, or C
+    pub fn constraint_wires(&self, constraint: usize) -> BTreeSet<usize> {
+        match self {
+            Self::Sparse(s) => [&s.a_rows, &s.b_rows, &s.c_rows]
+                .iter()
+                .filter_map(|m| m.0.get(constraint))
+                .flat_map(|row| row.0.iter().filter(|(_, x)| !x.is_zero_vartime()))
+                .map(|(i, _)| *i)
+                .collect(),
+            Self::Full(f) => [&f.a_rows, &f.b_rows, &f.c_rows]
+                .iter()
+                .filter_map(|m| m.0.get(constraint))
+                .flat_map(|row| row.0.iter().enumerate())
+                .filter(|(_, x)| !x.is_zero_vartime())
+                .map(|(i, _)| i)
+                .collect(),
+        }
+    }
+
+    /// Converts to the sparse representation, which is faster to multiply by when most entries are zero, as they are in most circuits
+    pub fn to_sparse(self) -> SparseR1CS<T> {
+        match self {
+            Self::Sparse(s) => s,
+            Self::Full(f) => SparseR1CS {
+                a_rows: f.a_rows.to_sparse(),
+                b_rows: f.b_rows.to_sparse(),
+                c_rows: f.c_rows.to_sparse(),
+            },
+        }
+    }
+
     pub fn zero_pad(&mut self, pad_len: usize) {
         match self {
             Self::Full(f) => {
@@ -59,9 +176,40 @@ impl<T: PF> R1CS<T> {
     }
 }
 
+impl<T: PF> DataSize for R1CS<T> {
+    fn size_in_bytes(&self) -> usize {
+        match self {
+            Self::Sparse(s) => {
+                s.a_rows.size_in_bytes() + s.b_rows.size_in_bytes() + s.c_rows.size_in_bytes()
+            }
+            Self::Full(f) => {
+                f.a_rows.size_in_bytes() + f.b_rows.size_in_bytes() + f.c_rows.size_in_bytes()
+            }
+        }
+    }
+}
+
+/// Counts the constraint systems, which dwarf the rest of the metadata for any circuit worth measuring
+impl<T: PF> DataSize for R1CSWithMetadata<T> {
+    fn size_in_bytes(&self) -> usize {
+        self.constraint_systems().map(|c| c.size_in_bytes()).sum()
+    }
+}
+
 impl<T: PF> R1CSWithMetadata<T> {
-    /// Given self and number of desired columns i.e. linear code `k`, returns the amount of padding required
+    /// Given self and number of desired columns i.e. linear code `k`, returns the amount of padding required.
+    /// It depends only on the circuit, so dense and sparse witnesses are padded alike
     pub fn calc_padding_needed(&self, k: usize) -> PadParams {
+        if let Some(layout) = self.aux_layout(k) {
+            // The padding holds the values for lookups and memories
+            let padded_len = layout.num_rows * k;
+            return PadParams {
+                orig_wtns_len: self.unpadded_wtns_len,
+                padded_wtns_len: padded_len,
+                pad_len: padded_len - self.unpadded_wtns_len,
+                num_padded_wtns_rows: layout.num_rows,
+            };
+        }
         // Pad witness so its length is a product of NUM_VOLES
         // note this pads with a whole new row if it is a product. this is neither intentional nor important.
         let pad_len = k - (self.unpadded_wtns_len % k);
@@ -77,26 +225,461 @@ impl<T: PF> R1CSWithMetadata<T> {
             num_padded_wtns_rows,
         }
     }
-    // pub fn circuit_id(&self) -> Result<[u8; 32], anyhow::Error> {
-    //     let serialized = bincode::serialize(&self)?;
-    //     let hashed = blake3::hash(&serialized);
-    //     Ok(*hashed.as_bytes())
-    // }
+    /// A circuit satisfied by witnesses satisfying either `self` or `other`. Proofs for it don't reveal which.
+    /// The branches share the witness, so both circuits must have the same witness length, and the public inputs and outputs are `self`'s.
+    /// `self`'s custom gates, lookups, memories, and CCS constraints apply whichever branch is satisfied, so `other` can't have any
+    pub fn or(mut self, other: Self) -> Self {
+        assert_eq!(
+            self.unpadded_wtns_len, other.unpadded_wtns_len,
+            "branches must have the same witness length"
+        );
+        assert!(
+            other.custom_gates.is_empty()
+                && other.lookups.is_empty()
+                && other.memories.is_empty()
+                && other.ccs.is_empty(),
+            "only the first branch can have custom gates, lookups, memories, or CCS constraints"
+        );
+        self.branches.push(other.r1cs);
+        self.branches.extend(other.branches);
+        self
+    }
+
+    /// The R1CS followed by its alternative branches, if any
+    pub fn constraint_systems(&self) -> impl Iterator<Item = &R1CS<T>> {
+        core::iter::once(&self.r1cs).chain(self.branches.iter())
+    }
+
+    /// Checks the witness satisfies the R1CS, or one of its branches, before it's proven.
+    /// On failure returns the first constraint of `r1cs` the witness doesn't satisfy; see `unsatisfied_constraints` for all of them.
+    /// Custom gates, lookups, memories, and CCS constraints aren't checked
+    pub fn check_witness(&self, witness: &FVec<T>) -> Result<(), UnsatisfiedConstraint<T>> {
+        match self.unsatisfied_constraints(witness).into_iter().next() {
+            Some(unsatisfied) => Err(unsatisfied),
+            None => Ok(()),
+        }
+    }
+
+    /// Every constraint of `r1cs` the witness doesn't satisfy, or none if it satisfies one of the branches
+    pub fn unsatisfied_constraints(&self, witness: &FVec<T>) -> Vec<UnsatisfiedConstraint<T>> {
+        let mut unsatisfied = self
+            .constraint_systems()
+            .map(|r1cs| r1cs.unsatisfied(witness));
+        let first = unsatisfied.next().unwrap_or_default();
+        match first.is_empty() || unsatisfied.any(|u| u.is_empty()) {
+            true => vec![],
+            false => first,
+        }
+    }
+
+    /// Zero pads every branch's R1CS to match the padded witness
+    pub fn zero_pad_constraints(&mut self, pad_len: usize) {
+        self.r1cs.zero_pad(pad_len);
+        self.branches.iter_mut().for_each(|b| b.zero_pad(pad_len));
+    }
+
+    /// Witness indices of the named signals, e.g. to disclose them with `Prover::prove_disclosing`
+    pub fn signal_indices(&self, names: &[&str]) -> Result<Vec<usize>, ProofError> {
+        names
+            .iter()
+            .map(|name| {
+                self.signal_names
+                    .get(*name)
+                    .copied()
+                    .ok_or_else(|| ProofError::UnknownSignal(name.to_string()))
+            })
+            .collect()
+    }
+
+    /// `copies` independent copies of the circuit in sparse form, i.e. a block diagonal R1CS
+    /// whose witness is the concatenation of `copies` witnesses of this circuit.
+    /// Public inputs and outputs are ordered by copy. Signal names are dropped since they would be ambiguous.
+    /// Panics if the circuit has branches, since each copy could satisfy a different one
+    pub fn repeat(&self, copies: usize) -> Self {
+        Self::stack(core::iter::repeat_n(self, copies))
+    }
+
+    /// Circuits on consecutive segments of one witness, in sparse form, i.e. a block diagonal R1CS
+    /// whose witness is the concatenation of a witness for each circuit.
+    /// Public inputs and outputs are ordered by circuit. Signal names are dropped since they would be ambiguous.
+    /// Panics if any circuit has branches, since each could satisfy a different one
+    pub fn stack<'a>(circuits: impl IntoIterator<Item = &'a Self>) -> Self
+    where
+        T: 'a,
+    {
+        let empty = || SparseFMatrix(vec![]);
+        // The R1CS is filled in last
+        let mut stacked = R1CSWithMetadata {
+            r1cs: R1CS::Sparse(SparseR1CS {
+                a_rows: empty(),
+                b_rows: empty(),
+                c_rows: empty(),
+            }),
+            public_inputs_indices: vec![],
+            public_outputs_indices: vec![],
+            unpadded_wtns_len: 0,
+            custom_gates: vec![],
+            lookups: vec![],
+            memories: vec![],
+            branches: vec![],
+            ccs: vec![],
+            signal_names: BTreeMap::new(),
+        };
+        let (mut a_rows, mut b_rows, mut c_rows) = (vec![], vec![], vec![]);
+        for circuit in circuits {
+            assert!(
+                circuit.branches.is_empty(),
+                "circuits with branches can't be stacked"
+            );
+            let offset = stacked.unpadded_wtns_len;
+            let shift = |m: &SparseFMatrix<T>| {
+                m.0.iter()
+                    .map(|row| SparseVec(row.0.iter().map(|(i, x)| (i + offset, *x)).collect()))
+                    .collect::<Vec<_>>()
+            };
+            let sparse = circuit.r1cs.clone().to_sparse();
+            a_rows.extend(shift(&sparse.a_rows));
+            b_rows.extend(shift(&sparse.b_rows));
+            c_rows.extend(shift(&sparse.c_rows));
+            let shift_indices =
+                |indices: &Vec<usize>| indices.iter().map(|i| i + offset).collect::<Vec<_>>();
+            stacked
+                .public_inputs_indices
+                .extend(shift_indices(&circuit.public_inputs_indices));
+            stacked
+                .public_outputs_indices
+                .extend(shift_indices(&circuit.public_outputs_indices));
+            stacked.unpadded_wtns_len += circuit.unpadded_wtns_len;
+            stacked
+                .custom_gates
+                .extend(circuit.custom_gates.iter().map(|g| {
+                    quicksilver::CustomGate {
+                        degree: g.degree,
+                        coeffs: g.coeffs.clone(),
+                        wires: g
+                            .wires
+                            .iter()
+                            .map(|term| term.iter().map(|i| i + offset).collect())
+                            .collect(),
+                    }
+                }));
+            stacked
+                .lookups
+                .extend(circuit.lookups.iter().map(|l| lookup::Lookup {
+                    table: l.table.clone(),
+                    wires: l.wires.iter().map(|i| i + offset).collect(),
+                }));
+            stacked.memories.extend(circuit.memories.iter().map(|m| {
+                memory::Memory {
+                    init: m.init.clone(),
+                    accesses: m
+                        .accesses
+                        .iter()
+                        .map(|a| memory::Access {
+                            address: a.address + offset,
+                            value: a.value + offset,
+                            write: a.write,
+                        })
+                        .collect(),
+                }
+            }));
+            stacked.ccs.extend(circuit.ccs.iter().map(|c| ccs::CCS {
+                matrices: c.matrices.iter().map(|m| SparseFMatrix(shift(m))).collect(),
+                terms: c.terms.clone(),
+            }));
+        }
+        stacked.r1cs = R1CS::Sparse(SparseR1CS {
+            a_rows: SparseFMatrix(a_rows),
+            b_rows: SparseFMatrix(b_rows),
+            c_rows: SparseFMatrix(c_rows),
+        });
+        stacked
+    }
+    /// blake3 hash of the circuit's matrices, public indices, witness length, custom gates, lookups, memories, and CCS constraints.
+    /// Note a circuit's sparse and full representations have different IDs
+    pub fn circuit_id(&self) -> [u8; 32] {
+        let mut hasher = blake3::Hasher::new();
+        let hash_usize = |h: &mut blake3::Hasher, x: usize| {
+            h.update(&(x as u64).to_le_bytes());
+        };
+        // Branches are only hashed if present so circuits without them keep the IDs they had before disjunctions existed
+        for (i, r1cs) in self.constraint_systems().enumerate() {
+            if i > 0 {
+                hasher.update(b"branch");
+            }
+            match r1cs {
+                R1CS::Sparse(s) => {
+                    hasher.update(b"sparse");
+                    for m in [&s.a_rows, &s.b_rows, &s.c_rows] {
+                        hash_usize(&mut hasher, m.0.len());
+                        for row in m.0.iter() {
+                            hash_usize(&mut hasher, row.0.len());
+                            for (idx, val) in row.0.iter() {
+                                hash_usize(&mut hasher, *idx);
+                                hasher.update(val.to_repr().as_ref());
+                            }
+                        }
+                    }
+                }
+                R1CS::Full(f) => {
+                    hasher.update(b"full");
+                    for m in [&f.a_rows, &f.b_rows, &f.c_rows] {
+                        hash_usize(&mut hasher, m.0.len());
+                        for row in m.0.iter() {
+                            hash_usize(&mut hasher, row.0.len());
+                            row.0.iter().for_each(|val| {
+                                hasher.update(val.to_repr().as_ref());
+                            });
+                        }
+                    }
+                }
+            }
+        }
+        for indices in [&self.public_inputs_indices, &self.public_outputs_indices] {
+            hash_usize(&mut hasher, indices.len());
+            indices.iter().for_each(|i| hash_usize(&mut hasher, *i));
+        }
+        hash_usize(&mut hasher, self.unpadded_wtns_len);
+        // Only hashed if present so circuits without them keep the IDs they had before custom gates existed
+        if !self.custom_gates.is_empty() {
+            hasher.update(b"custom_gates");
+            hash_usize(&mut hasher, self.custom_gates.len());
+            for gate in self.custom_gates.iter() {
+                hash_usize(&mut hasher, gate.degree);
+                hash_usize(&mut hasher, gate.coeffs.len());
+                for (coeff, wires) in gate.coeffs.iter().zip(gate.wires.iter()) {
+                    hasher.update(coeff.to_repr().as_ref());
+                    hash_usize(&mut hasher, wires.len());
+                    wires.iter().for_each(|i| hash_usize(&mut hasher, *i));
+                }
+            }
+        }
+        if !self.lookups.is_empty() {
+            hasher.update(b"lookups");
+            hash_usize(&mut hasher, self.lookups.len());
+            for lookup in self.lookups.iter() {
+                hash_usize(&mut hasher, lookup.table.len());
+                lookup.table.iter().for_each(|t| {
+                    hasher.update(t.to_repr().as_ref());
+                });
+                hash_usize(&mut hasher, lookup.wires.len());
+                lookup
+                    .wires
+                    .iter()
+                    .for_each(|i| hash_usize(&mut hasher, *i));
+            }
+        }
+        if !self.memories.is_empty() {
+            hasher.update(b"memories");
+            hash_usize(&mut hasher, self.memories.len());
+            for memory in self.memories.iter() {
+                hash_usize(&mut hasher, memory.init.len());
+                memory.init.iter().for_each(|v| {
+                    hasher.update(v.to_repr().as_ref());
+                });
+                hash_usize(&mut hasher, memory.accesses.len());
+                for access in memory.accesses.iter() {
+                    hash_usize(&mut hasher, access.address);
+                    hash_usize(&mut hasher, access.value);
+                    hasher.update(&[access.write as u8]);
+                }
+            }
+        }
+        if !self.ccs.is_empty() {
+            hasher.update(b"ccs");
+            hash_usize(&mut hasher, self.ccs.len());
+            for ccs in self.ccs.iter() {
+                hash_usize(&mut hasher, ccs.matrices.len());
+                for m in ccs.matrices.iter() {
+                    hash_usize(&mut hasher, m.0.len());
+                    for row in m.0.iter() {
+                        hash_usize(&mut hasher, row.0.len());
+                        for (idx, val) in row.0.iter() {
+                            hash_usize(&mut hasher, *idx);
+                            hasher.update(val.to_repr().as_ref());
+                        }
+                    }
+                }
+                hash_usize(&mut hasher, ccs.terms.len());
+                for (c, s) in ccs.terms.iter() {
+                    hasher.update(c.to_repr().as_ref());
+                    hash_usize(&mut hasher, s.len());
+                    s.iter().for_each(|j| hash_usize(&mut hasher, *j));
+                }
+            }
+        }
+        *hasher.finalize().as_bytes()
+    }
+
+    /// Layout of the values the prover adds for lookups and memories when the witness is split into rows of length `k`,
+    /// or None if the circuit has neither
+    pub fn aux_layout(&self, k: usize) -> Option<AuxLayout> {
+        if self.lookups.is_empty() && self.memories.is_empty() {
+            return None;
+        }
+        let memory_start = self.unpadded_wtns_len;
+        let num_memory_values: usize = self.memories.iter().map(|m| m.num_values()).sum();
+        let multiplicities_start = memory_start + num_memory_values;
+        let (num_multiplicities, num_inverses) =
+            lookup::num_values(&self.all_lookups(memory_start));
+        let num_memory_inverses: usize = self.memories.iter().map(|m| m.num_inverses()).sum();
+        let committed_rows = (multiplicities_start + num_multiplicities)
+            .div_ceil(k)
+            .max(1);
+        let inverses_start = committed_rows * k;
+        Some(AuxLayout {
+            memory_start,
+            multiplicities_start,
+            committed_rows,
+            inverses_start,
+            memory_inverses_start: inverses_start + num_inverses,
+            num_rows: committed_rows + (num_inverses + num_memory_inverses).div_ceil(k),
+        })
+    }
+
+    /// The circuit's lookups followed by its memories' range checks, given the memories' values start at `memory_start`
+    fn all_lookups(&self, memory_start: usize) -> Cow<'_, [lookup::Lookup<T>]> {
+        if self.memories.is_empty() {
+            return Cow::Borrowed(&self.lookups);
+        }
+        let mut lookups = self.lookups.clone();
+        let mut start = memory_start;
+        for memory in self.memories.iter() {
+            lookups.push(memory.range_lookup(start));
+            start += memory.num_values();
+        }
+        Cow::Owned(lookups)
+    }
+
+    /// Writes the memories' values and then the lookups' multiplicities into a padded `witness`
+    pub fn fill_aux_values(&self, witness: &mut FVec<T>, layout: &AuxLayout) {
+        let mut start = layout.memory_start;
+        for memory in self.memories.iter() {
+            memory.fill_values(witness, start);
+            start += memory.num_values();
+        }
+        lookup::fill_multiplicities(
+            &self.all_lookups(layout.memory_start),
+            witness,
+            layout.multiplicities_start,
+        );
+    }
+
+    /// The lookups' and then the memories' inverses for the challenges `alpha` and `gamma`, where `witness_at` gives the value of a witness index
+    pub fn aux_inverses(
+        &self,
+        alpha: &T,
+        gamma: &T,
+        layout: &AuxLayout,
+        witness_at: impl Fn(usize) -> T,
+    ) -> Vec<T> {
+        let mut inverses =
+            lookup::inverses(&self.all_lookups(layout.memory_start), alpha, &witness_at);
+        let mut start = layout.memory_start;
+        for memory in self.memories.iter() {
+            inverses.extend(memory.inverses(alpha, gamma, start, &witness_at));
+            start += memory.num_values();
+        }
+        inverses
+    }
+
+    /// The custom gates the lookups and memories reduce to once `alpha` and `gamma` are known
+    pub fn aux_gates(
+        &self,
+        alpha: &T,
+        gamma: &T,
+        layout: &AuxLayout,
+    ) -> Vec<quicksilver::CustomGate<T>> {
+        let mut gates = lookup::gates(
+            &self.all_lookups(layout.memory_start),
+            alpha,
+            layout.multiplicities_start,
+            layout.inverses_start,
+        );
+        let (mut start, mut inverses) = (layout.memory_start, layout.memory_inverses_start);
+        for memory in self.memories.iter() {
+            gates.extend(memory.gates(alpha, gamma, start, inverses));
+            start += memory.num_values();
+            inverses += memory.num_inverses();
+        }
+        gates
+    }
+
+    /// The circuit with its lookups and memories replaced by the gates they reduce to for `alpha` and `gamma`.
+    /// Borrows the circuit if it has neither
+    pub fn with_aux_gates(&self, alpha: &T, gamma: &T, k: usize) -> Cow<'_, Self> {
+        match self.aux_layout(k) {
+            None => Cow::Borrowed(self),
+            Some(layout) => {
+                let mut resolved = self.clone();
+                resolved
+                    .custom_gates
+                    .extend(self.aux_gates(alpha, gamma, &layout));
+                resolved.lookups = vec![];
+                resolved.memories = vec![];
+                Cow::Owned(resolved)
+            }
+        }
+    }
+}
+
+/// Different circuits proven in one proof, each on its own segment of the witness, so there is one commitment rather than one per circuit.
+/// Prove it with `Prover::prove_composite` and verify it with a `Verifier` for `to_circuit`
+#[derive(Clone, Serialize, Deserialize)]
+pub struct CompositeCircuit<T: PF> {
+    pub circuits: Vec<R1CSWithMetadata<T>>,
+}
+
+impl<T: PF> CompositeCircuit<T> {
+    pub fn new(circuits: Vec<R1CSWithMetadata<T>>) -> Self {
+        Self { circuits }
+    }
+
+    /// The single circuit actually proven and verified. See `R1CSWithMetadata::stack`
+    pub fn to_circuit(&self) -> R1CSWithMetadata<T> {
+        R1CSWithMetadata::stack(self.circuits.iter())
+    }
 }
 pub mod quicksilver {
 
     // use std::time::Instant;
-    use anyhow::{anyhow, bail, Error, Ok};
+    use alloc::borrow::Cow;
+    use core::ops::Mul;
     use serde::{Deserialize, Serialize};
 
-    use crate::{actors::actors::PublicOpenings, DotProduct, FMatrix, FVec, PF};
+    #[cfg(not(feature = "std"))]
+    use crate::alloc_prelude::*;
+    use crate::{error::VerifyError, fields::ext::ExtensionField, FVec, PF};
 
-    use super::R1CSWithMetadata;
+    use super::{R1CSWithMetadata, R1CS};
 
-    #[derive(Clone, Debug, Serialize, Deserialize)]
-    pub struct ZKP<T: PF> {
+    /// Values of the witness that the prover opens, with their v values from the challenge field
+    #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+    #[serde(bound(
+        serialize = "T: Serialize, T::Ext: Serialize",
+        deserialize = "T: Deserialize<'de>, T::Ext: Deserialize<'de>"
+    ))]
+    pub struct PublicOpenings<T: PF> {
+        pub public_inputs: Vec<(T, T::Ext)>,
+        pub public_outputs: Vec<(T, T::Ext)>,
+    }
+
+    /// A Quicksilver proof, whose elements are in the field its challenges are from, i.e. the witness's challenge field
+    #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+    pub struct ZKP<T> {
         /// Quicksilver multiplication proof of two field elements
         pub mul_proof: (T, T),
+        /// Proof of the custom gates: the coefficients of their batched polynomial in ∆ besides the leading one.
+        /// Its length is the highest degree of the circuit's custom gates, so it is empty if there are none
+        #[serde(default = "Vec::new")]
+        pub gate_proof: Vec<T>,
+        /// For circuits with N > 1 branches, the coefficients of the product of the branches' batched checks besides the first two,
+        /// which are `mul_proof`. It has 2N - 2 elements, so it is empty if there is one branch
+        #[serde(default = "Vec::new")]
+        pub branch_proof: Vec<T>,
+        /// The same proof for each challenge after the first, when the field is too small for one. See `SecurityLevel::quicksilver_repetitions`
+        #[serde(default = "Vec::new")]
+        pub repetitions: Vec<ZKP<T>>,
         // Public inputs and outputs should not be checked in the Quicksilver; they should be opened after converting VitH to subspace VOLE, before VitH ∆ is chosen
         // It may be possible to securely reveal public inputs after ∆ is known, but why worry about it if we can reveal public inputs before cheating is as big a concern?
         // /// Opening (u, v) of public input wires
@@ -104,51 +687,104 @@ pub mod quicksilver {
         // /// Opening (u, v) of public output wires
         // pub public_output_openings: Vec<(T, T)>
     }
-    pub struct Prover<T: PF> {
-        pub u: FVec<T>,
-        pub v: FVec<T>,
-        pub r1cs_with_metadata: R1CSWithMetadata<T>,
-    }
-    impl<T: PF> Prover<T> {
-        /// Creates a prover Tom VitH U1 and R matrices of equal dimension with 2l+2 rows where the witness is split into l chunks of length vole_length
-        /// Takes ownership and mutates most of its inputs to something useless
-        pub fn from_vith(
-            u1_rows: FMatrix<T>,
-            mut r_rows: FMatrix<T>,
-            mut witness_rows: FMatrix<T>,
-            r1cswm: R1CSWithMetadata<T>,
-        ) -> Self {
-            // println!("VOLE dimensions: {:?}", (u1_rows.0.len(), u1_rows.0[0].0.len()));
-            // println!("R1CS dimensions: {:?}", (r1cs.a_rows.0.len(), r1cs.a_rows.0[0].0.len()));
-            assert!(
-                (u1_rows.0.len() == r_rows.0.len())
-                    && (u1_rows.0[0].0.len() == r_rows.0[0].0.len()),
-                "u and v must be same dimension"
-            );
-            assert!(
-                witness_rows.0.len() == u1_rows.0.len() - 1,
-                "witness must have one fewer column than u1"
-            );
-            assert!(
-                witness_rows.0[0].0.len() == u1_rows.0[0].0.len(),
-                "witness must same number rows as u1 does"
-            );
-            // assert!((r1cs.a_rows.0.len() == u1_rows.0.len()) && (r1cs.a_rows.0[0].0.len() == u1_rows.0[0].0.len()), "VOLE dimensions must match R1CS dimensions");
+    /// A polynomial constraint on the witness: the sum over each term t of `coeffs[t]` times the product of the wires in `wires[t]` must be zero.
+    /// One gate replaces the several R1CS rows a relation of degree above 2 would take, e.g. x^5 - y = 0 is one gate rather than three constraints.
+    /// Terms may repeat a wire and an empty term is a constant
+    #[derive(Clone, Debug, Serialize, Deserialize)]
+    pub struct CustomGate<T: PF> {
+        /// Highest number of wires in a term
+        pub degree: usize,
+        pub coeffs: Vec<T>,
+        /// Witness indices multiplied together in each term
+        pub wires: Vec<Vec<usize>>,
+    }
 
-            let vith_size = u1_rows.0.len() * u1_rows.0[0].0.len();
-            let mut u = Vec::with_capacity(vith_size);
-            let mut v = Vec::with_capacity(vith_size);
-            witness_rows
-                .0
-                .iter_mut()
-                .for_each(|row| u.append(&mut row.0));
-            // Append the final u1_row which wasn't included by iterating through the witness rows:
-            u.append(&mut u1_rows.0.last().unwrap().0.clone());
-            r_rows.0.iter_mut().for_each(|row| v.append(&mut row.0));
+    impl<T: PF> CustomGate<T> {
+        /// Makes a gate with the degree of its highest degree term
+        pub fn new(coeffs: Vec<T>, wires: Vec<Vec<usize>>) -> Self {
+            assert_eq!(coeffs.len(), wires.len(), "every term needs a coefficient");
             Self {
-                u: FVec(u),
-                v: FVec(v),
+                degree: wires.iter().map(|w| w.len()).max().unwrap_or(0),
+                coeffs,
+                wires,
+            }
+        }
+
+        /// Evaluates the gate's polynomial at `witness`, which satisfies it iff this is zero
+        pub fn evaluate(&self, witness: &FVec<T>) -> T {
+            self.coeffs
+                .iter()
+                .zip(self.wires.iter())
+                .map(|(c, wires)| wires.iter().fold(*c, |acc, i| acc * witness.0[*i]))
+                .sum()
+        }
+    }
+
+    /// Degree the batched custom gate and CCS polynomial is lifted to. `degree` is trusted no more than the terms themselves so malformed gates can't underflow it
+    pub(crate) fn max_degree<T: PF>(circuit: &R1CSWithMetadata<T>) -> usize {
+        circuit
+            .custom_gates
+            .iter()
+            .flat_map(|g| g.wires.iter().map(|w| w.len()).chain([g.degree]))
+            .chain(circuit.ccs.iter().map(|c| c.degree()))
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// Number of challenge powers the custom gates and CCS rows are batched with
+    fn num_higher_degree_constraints<T: PF>(circuit: &R1CSWithMetadata<T>) -> usize {
+        circuit.custom_gates.len() + circuit.ccs.iter().map(|c| c.num_rows()).sum::<usize>()
+    }
+
+    /// Bound on how many constraints a Quicksilver proof batches with one challenge, given the length of its VOLE.
+    /// The gates lookups and memories reduce to aren't counted: there are about as many as the values they add to the witness, which the VOLE's length counts
+    pub fn num_batched<T: PF>(circuit: &R1CSWithMetadata<T>, vole_len: usize) -> usize {
+        vole_len + num_higher_degree_constraints(circuit)
+    }
+
+    /// Adds `coeff` times the product of the (v + uX) `factors` times X^(`degree` - number of factors) to the polynomial `batched`
+    fn add_lifted_term<T: PF, E: ExtensionField<T>>(
+        batched: &mut [E],
+        degree: usize,
+        coeff: E,
+        factors: impl ExactSizeIterator<Item = (T, E)>,
+    ) {
+        // Coefficients from lowest to highest degree
+        let zero = E::from(T::ZERO);
+        let mut poly = vec![zero; degree - factors.len()];
+        poly.push(coeff);
+        for (u, v) in factors {
+            let mut next = vec![zero; poly.len() + 1];
+            for (k, p) in poly.iter().enumerate() {
+                next[k] += *p * v;
+                next[k + 1] += *p * u;
+            }
+            poly = next;
+        }
+        batched
+            .iter_mut()
+            .zip(poly.iter())
+            .for_each(|(b, p)| *b += *p);
+    }
+
+    /// Borrows the circuit, and A·w and B·w if it is given them, so proving a large circuit doesn't copy it.
+    /// The v values are from the challenge field, as ∆' is
+    pub struct Prover<'a, T: PF> {
+        pub u: FVec<T>,
+        pub v: Vec<T::Ext>,
+        pub r1cs_with_metadata: &'a R1CSWithMetadata<T>,
+        /// A·w and B·w if they were computed before, e.g. kept up to date by an `IncrementalProver`
+        pub witness_products: Option<&'a (FVec<T>, FVec<T>)>,
+    }
+    impl<'a, T: PF> Prover<'a, T> {
+        /// Creates a prover from its side of a VOLE, e.g. `vith::VithProver::vole`'s, whose first values are the witness
+        pub fn from_vole(u: FVec<T>, v: Vec<T::Ext>, r1cswm: &'a R1CSWithMetadata<T>) -> Self {
+            assert!(u.0.len() == v.len(), "u and v must be the same length");
+            Self {
+                u,
+                v,
                 r1cs_with_metadata: r1cswm,
+                witness_products: None,
             }
         }
         /// TODO: explore efficiency gains for polynomial Quicksilver rather than gate-by-gate Quicksilver
@@ -161,72 +797,204 @@ pub mod quicksilver {
         /// the prover can find a 'collision'. This is as simple as changing the witnesss
         /// so u is different but still produces the same Quicksilver check value. Note this would not affect the underlying subspace VOLE if used with VitH since a different witness would still
         /// lay in the correct subspace. Therefore, it's important `challenge` depends on the witness.
-        pub fn prove(&self, challenge: &T) -> ZKP<T> {
-            let l = self.u.0.len();
+        ///
+        /// `challenge` is from the challenge field, e.g. `fields::ext::QM31` over M31, so one challenge is enough over a small field
+        pub fn prove(&self, challenge: &T::Ext) -> ZKP<T::Ext> {
+            if !self.r1cs_with_metadata.branches.is_empty() {
+                return ZKP {
+                    gate_proof: self.prove_custom_gates(challenge),
+                    ..self.prove_branches(challenge)
+                };
+            }
             let r1cs = &self.r1cs_with_metadata.r1cs;
             // let mut start = Instant::now();
 
             // Can calculate all linear gates by just dot product of the prover's values with the A, B, and C R1CS rows. These are not multiplication in & out wires
-            let (u_a, u_b, _u_c) = r1cs.vec_mul(&self.u);
-            let (v_a, v_b, v_c) = r1cs.vec_mul(&self.v);
+            let (u_a, u_b) = match self.witness_products {
+                Some((u_a, u_b)) => (Cow::Borrowed(u_a), Cow::Borrowed(u_b)),
+                None => {
+                    let (u_a, u_b, _u_c) = r1cs.vec_mul(&self.u);
+                    (Cow::Owned(u_a), Cow::Owned(u_b))
+                }
+            };
+            let (u_a, u_b) = (u_a.as_ref(), u_b.as_ref());
+            let (v_a, v_b, v_c) = r1cs.vec_mul_ext(&self.v);
             // println!("QuickSilver Linear gates {}", start.elapsed().as_micros()); start = Instant::now();
             // Quicksilver protocol to transform VOLE into a new VOLE for linear gates
-            let new_u = &(&u_b * &v_a + &u_a * &v_b) - &v_c;
-            let new_v = &v_a * &v_b;
+            let new_u = cross_terms(u_a, u_b, &v_a, &v_b, &v_c);
+            let new_v = hadamard(&v_a, &v_b);
             // println!("QuickSilver Transformation {}", start.elapsed().as_micros()); start = Instant::now();
-            let challenge_vec = get_challenge_vec::<T>(challenge, l);
-            // println!("QuickSilver Challenge {}", start.elapsed().as_micros()); start = Instant::now();
-            let mul_proof = (new_u.dot(&challenge_vec), new_v.dot(&challenge_vec));
+            let mul_proof = (batch::<T>(&new_u, challenge), batch::<T>(&new_v, challenge));
             // println!("QuickSilver Multiplciation proof {}", start.elapsed().as_micros()); start = Instant::now();
 
-            ZKP { mul_proof }
+            ZKP {
+                mul_proof,
+                gate_proof: self.prove_custom_gates(challenge),
+                branch_proof: vec![],
+                repetitions: vec![],
+            }
+        }
+
+        /// Proves with the first of `challenges` as `prove` does, then with each of the rest as the proof's repetitions
+        pub fn prove_repeated(&self, challenges: &[T::Ext]) -> ZKP<T::Ext> {
+            let (first, rest) = challenges
+                .split_first()
+                .expect("at least one challenge is needed");
+            ZKP {
+                repetitions: rest.iter().map(|c| self.prove(c)).collect(),
+                ..self.prove(first)
+            }
+        }
+
+        /// Disjunction of the branches, in the style of Mac'n'Cheese. Each branch's batched multiplication check
+        /// is a degree 2 polynomial in ∆ whose ∆^2 coefficient is zero iff the branch is satisfied (with high probability over `challenge`).
+        /// The product of the branches' polynomials then has a zero leading coefficient iff one of them is satisfied,
+        /// without saying which. The rest of its coefficients are the proof, the first two in the same place as a single branch's.
+        /// Returns them without the custom gate proof
+        fn prove_branches(&self, challenge: &T::Ext) -> ZKP<T::Ext> {
+            let mut product = vec![T::Ext::from(T::ONE)];
+            for r1cs in self.r1cs_with_metadata.constraint_systems() {
+                let (u_a, u_b, u_c) = r1cs.vec_mul(&self.u);
+                let (v_a, v_b, v_c) = r1cs.vec_mul_ext(&self.v);
+                let u_product = &(&u_a * &u_b) - &u_c;
+                let branch = [
+                    batch::<T>(&hadamard(&v_a, &v_b), challenge),
+                    batch::<T>(&cross_terms(&u_a, &u_b, &v_a, &v_b, &v_c), challenge),
+                    batch::<T>(
+                        &u_product
+                            .0
+                            .iter()
+                            .map(|x| T::Ext::from(*x))
+                            .collect::<Vec<_>>(),
+                        challenge,
+                    ),
+                ];
+                let mut next = vec![T::Ext::from(T::ZERO); product.len() + 2];
+                for (i, p) in product.iter().enumerate() {
+                    for (j, b) in branch.iter().enumerate() {
+                        next[i + j] += *p * *b;
+                    }
+                }
+                product = next;
+            }
+            product.pop();
+            let rest = product.split_off(2);
+            ZKP {
+                mul_proof: (product[1], product[0]),
+                gate_proof: vec![],
+                branch_proof: rest,
+                repetitions: vec![],
+            }
+        }
+
+        /// Each term of each gate is a polynomial in ∆ with coefficients from the u and v values of its wires, namely the product of (v + u∆) over them.
+        /// Lifting every term to the same degree d by multiplying by a power of ∆ and batching the gates with powers of `challenge`,
+        /// the coefficient of ∆^d is the batched gates' values, which is zero if they are all satisfied. The rest are the proof.
+        /// CCS rows are batched into the same polynomial, their factors being matrix rows times the witness rather than wires
+        fn prove_custom_gates(&self, challenge: &T::Ext) -> Vec<T::Ext> {
+            let circuit = self.r1cs_with_metadata;
+            let degree = max_degree(circuit);
+            let challenge_vec =
+                get_challenge_vec(challenge, num_higher_degree_constraints(circuit));
+            let mut chis = challenge_vec.iter();
+            let mut batched = vec![T::Ext::from(T::ZERO); degree + 1];
+            for (gate, chi) in circuit.custom_gates.iter().zip(&mut chis) {
+                for (coeff, wires) in gate.coeffs.iter().zip(gate.wires.iter()) {
+                    let factors = wires.iter().map(|i| (self.u.0[*i], self.v[*i]));
+                    add_lifted_term(&mut batched, degree, *chi * *coeff, factors);
+                }
+            }
+            for ccs in circuit.ccs.iter() {
+                let (mu, mv) = (ccs.products(&self.u), ccs.products_ext(&self.v));
+                for (row, chi) in (0..ccs.num_rows()).zip(&mut chis) {
+                    for (coeff, s) in ccs.terms.iter() {
+                        let factors = s.iter().map(|j| (mu[*j].0[row], mv[*j][row]));
+                        add_lifted_term(&mut batched, degree, *chi * *coeff, factors);
+                    }
+                }
+            }
+            batched.truncate(degree);
+            batched
         }
         /// Opens VOLE correlations at public indices
-        pub fn open_public(&self, indices: &Vec<usize>) -> Vec<(T, T)> {
-            indices
-                .iter()
-                .map(|i| (self.u.0[*i], self.v.0[*i]))
-                .collect()
+        pub fn open_public(&self, indices: &Vec<usize>) -> Vec<(T, T::Ext)> {
+            indices.iter().map(|i| (self.u.0[*i], self.v[*i])).collect()
         }
     }
 
+    /// u_b·v_a + u_a·v_b - v_c, the u values of the VOLE whose v values are v_a·v_b, element by element
+    fn cross_terms<T: PF>(
+        u_a: &FVec<T>,
+        u_b: &FVec<T>,
+        v_a: &[T::Ext],
+        v_b: &[T::Ext],
+        v_c: &[T::Ext],
+    ) -> Vec<T::Ext> {
+        u_a.0
+            .iter()
+            .zip(&u_b.0)
+            .zip(v_a.iter().zip(v_b).zip(v_c))
+            .map(|((ua, ub), ((va, vb), vc))| *va * *ub + *vb * *ua - *vc)
+            .collect()
+    }
+
+    /// Element by element product of two vectors of the challenge field
+    fn hadamard<E: Copy + Mul<Output = E>>(a: &[E], b: &[E]) -> Vec<E> {
+        a.iter().zip(b).map(|(x, y)| *x * *y).collect()
+    }
+
     /// Creates a vector [challenge, challenge^2, challenge^3, ..., challenge^length]
-    fn get_challenge_vec<T: PF>(challenge: &T, length: usize) -> FVec<T> {
+    fn get_challenge_vec<E: Copy + Mul<Output = E>>(challenge: &E, length: usize) -> Vec<E> {
         let mut challenge_vec = Vec::with_capacity(length);
-        challenge_vec.push(challenge.clone());
+        challenge_vec.push(*challenge);
         for i in 1..length {
             // TODO: posisble very slight performance gain by caching i-1
-            challenge_vec.push(challenge_vec[i - 1] * challenge);
-        }
-        FVec::<T>(challenge_vec)
-    }
-    pub struct Verifier<T: PF> {
-        pub delta: T,
-        pub q: FVec<T>,
-        pub r1cs_with_metadata: R1CSWithMetadata<T>,
-    }
-    impl<T: PF> Verifier<T> {
-        /// Creates a verifier Tom VitH S and D matrices where D is the prover's commitment to the witness
-        /// Takes ownership and mutates most of its inputs to something useless
-        pub fn from_vith(
-            s_rows: &FMatrix<T>,
-            delta: T,
-            witness_comm: &FMatrix<T>,
-            r1cswm: R1CSWithMetadata<T>,
-        ) -> Self {
-            // Adjust S by adding the witness to its first part
-            let mut s_adjustment = witness_comm.scalar_mul(delta);
-            let row_len = s_adjustment.0[0].0.len();
-            // Performance note: this pushes one beyond capacity
-            s_adjustment.0.push(FVec::<T>(vec![T::ZERO; row_len]));
-
-            let mut s_adjusted = s_rows + &s_adjustment;
-
-            // assert!((r1cs.a_rows.0.len() == s_adjusted.0.len()) /* && (r1cs.a_rows.0[0].0.len() == q_rows.0[0].0.len()) */, "VOLE dimensions must correspond R1CS");
-            let vith_size = s_adjusted.0.len() * s_adjusted.0[0].0.len();
-            let mut q = Vec::with_capacity(vith_size);
-            s_adjusted.0.iter_mut().for_each(|row| q.append(&mut row.0));
-            let q = FVec::<T>(q);
+            challenge_vec.push(challenge_vec[i - 1] * *challenge);
+        }
+        challenge_vec
+    }
+
+    /// `values` batched with the challenge vector of `challenge`, i.e. their sum times successive powers of it starting from the first,
+    /// by Horner's rule
+    fn batch<T: PF>(values: &[T::Ext], challenge: &T::Ext) -> T::Ext {
+        values
+            .iter()
+            .rev()
+            .fold(T::Ext::from(T::ZERO), |acc, x| (acc + *x) * *challenge)
+    }
+    /// Rejects a proof with `repetitions` repetitions in all that doesn't have as many coefficients as `circuit` gives each of them
+    pub fn check_dimensions<T: PF, E>(
+        circuit: &R1CSWithMetadata<T>,
+        proof: &ZKP<E>,
+        repetitions: usize,
+    ) -> Result<(), VerifyError> {
+        if proof.repetitions.len() + 1 != repetitions
+            || proof.repetitions.iter().any(|r| !r.repetitions.is_empty())
+        {
+            return Err(VerifyError::Malformed(
+                "wrong number of Quicksilver repetitions",
+            ));
+        }
+        let (degree, branches) = (max_degree(circuit), circuit.branches.len());
+        if core::iter::once(proof)
+            .chain(proof.repetitions.iter())
+            .any(|p| p.gate_proof.len() != degree || p.branch_proof.len() != 2 * branches)
+        {
+            return Err(VerifyError::Malformed(
+                "Quicksilver proof has the wrong number of coefficients for the circuit",
+            ));
+        }
+        Ok(())
+    }
+    /// Holds ∆ and the q values, which are from the challenge field
+    pub struct Verifier<'a, T: PF> {
+        pub delta: T::Ext,
+        pub q: Vec<T::Ext>,
+        pub r1cs_with_metadata: &'a R1CSWithMetadata<T>,
+    }
+    impl<'a, T: PF> Verifier<'a, T> {
+        /// Creates a verifier from its side of a VOLE, e.g. `vith::VithVerifier::vole`'s
+        pub fn from_vole(q: Vec<T::Ext>, delta: T::Ext, r1cswm: &'a R1CSWithMetadata<T>) -> Self {
             Self {
                 delta,
                 q,
@@ -234,28 +1002,102 @@ pub mod quicksilver {
             }
         }
 
+        /// Verifies a proof made by `Prover::prove_repeated` with the same `challenges`
+        pub fn verify_repeated(
+            &self,
+            challenges: &[T::Ext],
+            proof: &ZKP<T::Ext>,
+        ) -> Result<(), VerifyError> {
+            check_dimensions(self.r1cs_with_metadata, proof, challenges.len())?;
+            core::iter::once(proof)
+                .chain(proof.repetitions.iter())
+                .zip(challenges.iter())
+                .try_for_each(|(p, c)| self.verify(c, p))
+        }
+
         /// Verifies a (degree 2) Quicksilver proof, returning the public inputs and outputs if successful. Otherwise, returns an error
         /// NOTE: According to the Quicksilver paper, `challenge` should be given after the values are determined.
-        pub fn verify(&self, challenge: &T, proof: &ZKP<T>) -> Result<(), Error> {
-            let r1cs = &self.r1cs_with_metadata.r1cs;
-            let (q_a, q_b, q_c) = r1cs.vec_mul(&self.q);
-
+        pub fn verify(&self, challenge: &T::Ext, proof: &ZKP<T::Ext>) -> Result<(), VerifyError> {
             // Quicksilver protocol to transform VOLE into a new VOLE that makes multiplication gates linear relations
-            let new_q = &(&q_a * &q_b) - &q_c.scalar_mul(self.delta);
-            let challenge_vec = get_challenge_vec(challenge, self.q.0.len());
-            let success =
-                proof.mul_proof.1 + proof.mul_proof.0 * self.delta == new_q.dot(&challenge_vec);
-            match success {
+            let batched_check = |r1cs: &R1CS<T>| {
+                let (q_a, q_b, q_c) = r1cs.vec_mul_ext(&self.q);
+                let residual = q_a
+                    .iter()
+                    .zip(&q_b)
+                    .zip(&q_c)
+                    .map(|((a, b), c)| *a * *b - self.delta * *c)
+                    .collect::<Vec<_>>();
+                batch::<T>(&residual, challenge)
+            };
+            let num_branches = self.r1cs_with_metadata.branches.len() + 1;
+            let success = proof.branch_proof.len() == 2 * (num_branches - 1) && {
+                let product = self
+                    .r1cs_with_metadata
+                    .constraint_systems()
+                    .fold(T::Ext::from(T::ONE), |acc, r1cs| acc * batched_check(r1cs));
+                let claimed = proof
+                    .branch_proof
+                    .iter()
+                    .rev()
+                    .fold(T::Ext::from(T::ZERO), |acc, c| acc * self.delta + *c);
+                proof.mul_proof.1
+                    + proof.mul_proof.0 * self.delta
+                    + claimed * self.delta * self.delta
+                    == product
+            };
+            match success && self.verify_custom_gates(challenge, &proof.gate_proof) {
                 true => Ok(()),
-                false => Err(anyhow!("Proof was not verified with success")),
+                false => Err(VerifyError::Zkp),
+            }
+        }
+
+        /// Evaluates the batched custom gate polynomial at ∆ from q values and checks it against the prover's coefficients
+        fn verify_custom_gates(&self, challenge: &T::Ext, gate_proof: &[T::Ext]) -> bool {
+            let circuit = self.r1cs_with_metadata;
+            let degree = max_degree(circuit);
+            if gate_proof.len() != degree {
+                return false;
+            }
+            let challenge_vec =
+                get_challenge_vec(challenge, num_higher_degree_constraints(circuit));
+            let mut chis = challenge_vec.iter();
+            // ∆ to each power up to the degree, to lift each term to it
+            let mut powers = vec![T::Ext::from(T::ONE)];
+            (0..degree).for_each(|i| powers.push(powers[i] * self.delta));
+            let lift = |e: usize| powers[degree - e];
+            let mut expected = T::Ext::from(T::ZERO);
+            for (gate, chi) in circuit.custom_gates.iter().zip(&mut chis) {
+                for (coeff, wires) in gate.coeffs.iter().zip(gate.wires.iter()) {
+                    let term = wires
+                        .iter()
+                        .fold(T::Ext::from(*coeff), |acc, i| acc * self.q[*i]);
+                    expected += *chi * (term * lift(wires.len()));
+                }
+            }
+            for ccs in circuit.ccs.iter() {
+                let mq = ccs.products_ext(&self.q);
+                for (row, chi) in (0..ccs.num_rows()).zip(&mut chis) {
+                    for (coeff, s) in ccs.terms.iter() {
+                        let term = s
+                            .iter()
+                            .fold(T::Ext::from(*coeff), |acc, j| acc * mq[*j][row]);
+                        expected += *chi * (term * lift(s.len()));
+                    }
+                }
             }
+            let claimed = gate_proof
+                .iter()
+                .rev()
+                .fold(T::Ext::from(T::ZERO), |acc, c| acc * self.delta + *c);
+            expected == claimed
         }
         /// Assuming the VOLE was constructed properly, this verifies the opening of witness VOLE correlations
-        pub fn verify_public(&self, pos: &PublicOpenings<T>) -> Result<(), Error> {
-            if (!pos.public_inputs.len() == self.r1cs_with_metadata.public_inputs_indices.len())
-                && (!pos.public_inputs.len() == self.r1cs_with_metadata.public_inputs_indices.len())
+        pub fn verify_public(&self, pos: &PublicOpenings<T>) -> Result<(), VerifyError> {
+            if (pos.public_inputs.len() != self.r1cs_with_metadata.public_inputs_indices.len())
+                || (pos.public_outputs.len()
+                    != self.r1cs_with_metadata.public_outputs_indices.len())
             {
-                bail!("Public values have the wrong input or output length(s)")
+                return Err(VerifyError::PublicValuesLength);
             }
 
             let mut indices = self.r1cs_with_metadata.public_inputs_indices.clone();
@@ -263,11 +1105,19 @@ pub mod quicksilver {
 
             indices.extend(&self.r1cs_with_metadata.public_outputs_indices);
             public.extend(&pos.public_outputs);
+            self.verify_openings(&indices, &public)
+        }
 
-            for (i, (u, v)) in indices.iter().zip(public.iter()) {
+        /// Checks the (u, v) pair opened at each of `indices`. The caller must check the indices are in range
+        pub fn verify_openings(
+            &self,
+            indices: &[usize],
+            openings: &[(T, T::Ext)],
+        ) -> Result<(), VerifyError> {
+            for (i, (u, v)) in indices.iter().zip(openings.iter()) {
                 // TODO: consider giving index of which input was invalid.  This could impact performance slightly as it would not be static but dynamic
-                if !(*u * &self.delta + v == self.q.0[*i]) {
-                    bail!("Invaliding opening of a public input")
+                if !(self.delta * *u + *v == self.q[*i]) {
+                    return Err(VerifyError::PublicOpening);
                 }
             }
             Ok(())
@@ -277,11 +1127,23 @@ pub mod quicksilver {
 
 #[cfg(test)]
 pub mod test {
-    use super::{quicksilver::Prover, *};
-    use crate::{zkp::quicksilver::Verifier, FVec, Fr};
+    use super::{
+        quicksilver::{CustomGate, Prover},
+        *,
+    };
+    use crate::{
+        challenges::{calc_quicksilver_challenges, transcript::Transcript, SecurityLevel},
+        error::VerifyError,
+        fields::{
+            ext::{ChallengeField, ExtensionField, QM31},
+            m31::M31,
+        },
+        zkp::quicksilver::Verifier,
+        FVec, Fr, FromU8s, ToU8s,
+    };
     use ff::{Field, PrimeField};
     use lazy_static::lazy_static;
-    use rand::rngs::ThreadRng;
+    use rand::{rngs::ThreadRng, thread_rng};
 
     lazy_static! {
         pub static ref TEST_R1CS: FullR1CS<Fr> = {
@@ -309,6 +1171,17 @@ pub mod test {
             public_inputs_indices: vec![0, 2],
             public_outputs_indices: vec![3],
             unpadded_wtns_len: TEST_R1CS.a_rows.0.len(),
+            custom_gates: vec![],
+            lookups: vec![],
+            memories: vec![],
+            branches: vec![],
+            ccs: vec![],
+            signal_names: BTreeMap::new(),
+        };
+        /// `TEST_R1CS_WITH_METADA` with `unpadded_wtns_len` the witness's length, as a `Prover` requires
+        pub static ref TEST_CIRCUIT: R1CSWithMetadata<Fr> = R1CSWithMetadata {
+            unpadded_wtns_len: TEST_R1CS.a_rows.0[0].0.len(),
+            ..TEST_R1CS_WITH_METADA.clone()
         };
     }
 
@@ -333,6 +1206,38 @@ pub mod test {
         ));
     }
 
+    #[test]
+    fn witness_diagnostics() {
+        let row = |v: [u128; 4]| FVec(v.iter().map(|x| Fr::from_u128(*x)).collect::<Vec<Fr>>());
+        assert!(TEST_CIRCUIT.check_witness(&row([5, 2, 28, 280])).is_ok());
+        let err = TEST_CIRCUIT
+            .check_witness(&row([5, 2, 28, 281]))
+            .unwrap_err();
+        assert_eq!(err.index, 1);
+        assert_eq!(
+            (err.a, err.b, err.c),
+            (Fr::from_u128(10), Fr::from_u128(28), Fr::from_u128(281))
+        );
+        let all = TEST_CIRCUIT.unsatisfied_constraints(&row([5, 2, 27, 281]));
+        assert_eq!(all.iter().map(|u| u.index).collect::<Vec<_>>(), vec![0, 1]);
+    }
+
+    #[test]
+    fn repeated_circuit() {
+        let witnesses = [vec![5, 2, 28, 280], vec![1, 1, 4, 8]]
+            .iter()
+            .map(|w| w.iter().map(|x| Fr::from_u128(*x)).collect::<Vec<Fr>>())
+            .collect::<Vec<_>>();
+        let repeated = TEST_CIRCUIT.repeat(2);
+        assert_eq!(repeated.unpadded_wtns_len, 8);
+        assert_eq!(repeated.public_inputs_indices, vec![0, 2, 4, 6]);
+        assert_eq!(repeated.public_outputs_indices, vec![3, 7]);
+        assert!(witness_check(&repeated.r1cs, &FVec(witnesses.concat())));
+        let mut wrong = witnesses.concat();
+        wrong[7] += Fr::ONE;
+        assert!(!witness_check(&repeated.r1cs, &FVec(wrong)));
+    }
+
     #[test]
     pub fn circuit_satisfiability_proof() {
         let witness = FVec(
@@ -344,28 +1249,225 @@ pub mod test {
 
         // Prove it in ZK this time:
         let delta = Fr::random(&mut ThreadRng::default());
-        let v = FVec::<Fr>::random(witness.0.len());
+        let v = FVec::<Fr>::random_with_rng(witness.0.len(), &mut thread_rng());
         let u = witness.clone();
         let q = &u.scalar_mul(delta) + &v;
 
         let prover = Prover {
             u,
-            v: v.clone(),
-            r1cs_with_metadata: TEST_R1CS_WITH_METADA.clone(),
+            v: v.0.clone(),
+            r1cs_with_metadata: &TEST_R1CS_WITH_METADA,
+            witness_products: None,
         };
         let challenge = &Fr::from_u128(123);
         let proof = prover.prove(challenge);
 
         let verifier = Verifier {
-            q,
+            q: q.0,
             delta,
-            r1cs_with_metadata: TEST_R1CS_WITH_METADA.clone(),
+            r1cs_with_metadata: &TEST_R1CS_WITH_METADA,
         };
         assert!(verifier.verify(challenge, &proof).is_ok());
         assert!(verifier.verify(&Fr::from_u128(69), &proof).is_err());
         // TODO: assert a bad witness fails (is this necessary tho bc ZK protocol will catch that lol)
     }
 
+    #[test]
+    fn custom_gate_proof() {
+        let witness = FVec(
+            [5, 2, 28, 280]
+                .iter()
+                .map(|x| Fr::from_u128(*x))
+                .collect::<Vec<Fr>>(),
+        );
+        // 5 * 2 * 28 - 280 = 0 and 2^3 + 5^2 - 33 = 0
+        let mut circuit = TEST_CIRCUIT.clone();
+        circuit.custom_gates = vec![
+            CustomGate::new(vec![Fr::ONE, -Fr::ONE], vec![vec![0, 1, 2], vec![3]]),
+            CustomGate::new(
+                vec![Fr::ONE, Fr::ONE, -Fr::from_u128(33)],
+                vec![vec![1, 1, 1], vec![0, 0], vec![]],
+            ),
+        ];
+        assert!(circuit
+            .custom_gates
+            .iter()
+            .all(|g| g.evaluate(&witness) == Fr::ZERO));
+
+        let delta = Fr::random(&mut ThreadRng::default());
+        let v = FVec::<Fr>::random_with_rng(witness.0.len(), &mut thread_rng());
+        let q = &witness.scalar_mul(delta) + &v;
+        let challenge = &Fr::from_u128(123);
+        let verifier = Verifier {
+            q: q.0.clone(),
+            delta,
+            r1cs_with_metadata: &circuit,
+        };
+        let proof = Prover {
+            u: witness.clone(),
+            v: v.0.clone(),
+            r1cs_with_metadata: &circuit,
+            witness_products: None,
+        }
+        .prove(challenge);
+        assert_eq!(proof.gate_proof.len(), 3);
+        assert!(verifier.verify(challenge, &proof).is_ok());
+
+        // A witness satisfying the R1CS but not the first gate
+        let mut unsatisfying = circuit.clone();
+        unsatisfying.custom_gates[0].coeffs[1] = -Fr::from_u128(2);
+        let proof = Prover {
+            u: witness,
+            v: v.0,
+            r1cs_with_metadata: &unsatisfying,
+            witness_products: None,
+        }
+        .prove(challenge);
+        let verifier = Verifier {
+            q: q.0,
+            delta,
+            r1cs_with_metadata: &unsatisfying,
+        };
+        assert!(verifier.verify(challenge, &proof).is_err());
+    }
+
+    /// A 61 bit field, too small for one Quicksilver challenge to batch soundly at 60 bits of security
+    #[derive(PrimeField)]
+    #[PrimeFieldModulus = "2305843009213693951"]
+    #[PrimeFieldGenerator = "37"]
+    #[PrimeFieldReprEndianness = "little"]
+    pub(crate) struct F61([u64; 1]);
+
+    impl FromU8s for F61 {
+        fn from_u8s(u: &Vec<u8>) -> Self {
+            F61::from_repr(F61Repr(u[0..8].try_into().unwrap())).unwrap()
+        }
+    }
+    impl ToU8s for F61 {
+        fn to_u8s(&self) -> Vec<u8> {
+            self.to_repr().0.to_vec()
+        }
+    }
+    impl ChallengeField for F61 {
+        type Ext = F61;
+    }
+
+    #[test]
+    fn repeated_quicksilver_over_small_field() {
+        assert_eq!(SecurityLevel(60).quicksilver_repetitions::<F61>(4), 2);
+        assert_eq!(SecurityLevel(160).quicksilver_repetitions::<Fr>(1 << 30), 1);
+        assert_eq!(SecurityLevel(60).consistency_rows::<F61>(), 1);
+        assert_eq!(SecurityLevel(128).consistency_rows::<F61>(), 3);
+        assert_eq!(SecurityLevel(160).consistency_rows::<Fr>(), 1);
+
+        let row = |v: [u64; 4]| FVec(v.map(F61::from).to_vec());
+        let circuit = R1CSWithMetadata {
+            r1cs: R1CS::Full(FullR1CS {
+                a_rows: FMatrix(vec![row([1, 1, 0, 0]), row([2, 0, 0, 0])]),
+                b_rows: FMatrix(vec![row([0, 2, 0, 0]), row([0, 0, 1, 0])]),
+                c_rows: FMatrix(vec![row([0, 0, 1, 0]), row([0, 0, 0, 1])]),
+            }),
+            public_inputs_indices: vec![],
+            public_outputs_indices: vec![],
+            unpadded_wtns_len: 4,
+            custom_gates: vec![],
+            lookups: vec![],
+            memories: vec![],
+            branches: vec![],
+            ccs: vec![],
+            signal_names: BTreeMap::new(),
+        };
+        let witness = row([5, 2, 28, 280]);
+        let delta = F61::random(&mut ThreadRng::default());
+        let v = FVec::<F61>::random_with_rng(4, &mut thread_rng());
+        let q = &witness.scalar_mul(delta) + &v;
+        let challenges = [F61::from(123), F61::from(456)];
+        let proof = Prover {
+            u: witness,
+            v: v.0,
+            r1cs_with_metadata: &circuit,
+            witness_products: None,
+        }
+        .prove_repeated(&challenges);
+        assert_eq!(proof.repetitions.len(), 1);
+        let verifier = Verifier {
+            q: q.0,
+            delta,
+            r1cs_with_metadata: &circuit,
+        };
+        assert!(verifier.verify_repeated(&challenges, &proof).is_ok());
+        assert!(matches!(
+            verifier.verify_repeated(&challenges[..1], &proof),
+            Err(VerifyError::Malformed(_))
+        ));
+        let mut tampered = proof;
+        tampered.repetitions[0].mul_proof.0 += F61::ONE;
+        assert!(verifier.verify_repeated(&challenges, &tampered).is_err());
+    }
+
+    #[test]
+    fn quicksilver_over_m31_with_extension_challenges() {
+        let row = |v: [u64; 4]| FVec(v.map(M31::from).to_vec());
+        let circuit = R1CSWithMetadata {
+            r1cs: R1CS::Full(FullR1CS {
+                a_rows: FMatrix(vec![row([1, 1, 0, 0]), row([2, 0, 0, 0])]),
+                b_rows: FMatrix(vec![row([0, 2, 0, 0]), row([0, 0, 1, 0])]),
+                c_rows: FMatrix(vec![row([0, 0, 1, 0]), row([0, 0, 0, 1])]),
+            }),
+            public_inputs_indices: vec![],
+            public_outputs_indices: vec![],
+            unpadded_wtns_len: 4,
+            // w0 * w1^2 = 20
+            custom_gates: vec![CustomGate::new(
+                vec![M31::ONE, -M31::from(20)],
+                vec![vec![0, 1, 1], vec![]],
+            )],
+            lookups: vec![],
+            memories: vec![],
+            branches: vec![],
+            ccs: vec![],
+            signal_names: BTreeMap::new(),
+        };
+        let prove_and_verify = |witness: FVec<M31>| {
+            let delta = QM31::sample(ThreadRng::default());
+            let v = (0..4)
+                .map(|_| QM31::sample(thread_rng()))
+                .collect::<Vec<_>>();
+            let q = witness
+                .0
+                .iter()
+                .zip(&v)
+                .map(|(u, v)| delta * QM31::from(*u) + *v)
+                .collect::<Vec<_>>();
+            let mut transcript = Transcript::new(b"test");
+            transcript.append_ext::<M31>(b"q", q.iter());
+            let challenge = calc_quicksilver_challenges::<M31>(&transcript, 1)[0];
+            let proof = Prover::from_vole(witness, v, &circuit).prove(&challenge);
+            let verifier = Verifier::from_vole(q, delta, &circuit);
+            (
+                verifier.verify(&challenge, &proof),
+                proof,
+                verifier,
+                challenge,
+            )
+        };
+
+        let (verified, proof, verifier, challenge) = prove_and_verify(row([5, 2, 28, 280]));
+        assert!(verified.is_ok());
+        assert_eq!(proof.gate_proof.len(), 3);
+        let mut tampered = proof.clone();
+        tampered.gate_proof[0] += QM31::ONE;
+        assert!(verifier.verify(&challenge, &tampered).is_err());
+        let mut tampered = proof;
+        tampered.mul_proof.1 += QM31::ONE;
+        assert!(verifier.verify(&challenge, &tampered).is_err());
+
+        assert!(matches!(
+            prove_and_verify(row([5, 2, 28, 281])).0,
+            Err(VerifyError::Zkp)
+        ));
+    }
+
     // /// This is covered by practiaclly every single integration tests so commenting it instead of implementing it
     // #[test]
     // pub fn Tom_vith() {